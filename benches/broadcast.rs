@@ -0,0 +1,145 @@
+//! Compares broadcasting a message (serialized once) to sending it to each client in a loop (serialized once per client).
+//!
+//! Run with `cargo bench --bench broadcast`.
+
+use std::{
+    hint::black_box,
+    net::Ipv4Addr,
+    time::{Duration, Instant},
+};
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::App};
+use bevy_quinnet::{
+    server::{
+        certificate::CertificateRetrievalMode, QuinnetServer, QuinnetServerPlugin,
+        ServerEndpointConfiguration,
+    },
+    shared::{
+        channels::{ChannelId, ChannelsConfiguration},
+        transport::memory::MemoryConnection,
+    },
+};
+use serde::Serialize;
+
+const CLIENTS_COUNT: usize = 256;
+const ITERATIONS: u32 = 200;
+
+#[derive(Serialize)]
+struct EntityState {
+    id: u64,
+    position: [f32; 3],
+    velocity: [f32; 3],
+}
+
+#[derive(Serialize)]
+struct WorldSnapshot {
+    tick: u64,
+    entities: Vec<EntityState>,
+}
+
+fn snapshot(tick: u64) -> WorldSnapshot {
+    WorldSnapshot {
+        tick,
+        entities: (0..CLIENTS_COUNT as u64)
+            .map(|id| EntityState {
+                id,
+                position: [id as f32, 0., 1.],
+                velocity: [0., 1., id as f32],
+            })
+            .collect(),
+    }
+}
+
+fn start_server() -> (App, Vec<MemoryConnection>) {
+    let mut app = App::new();
+    app.add_plugins((
+        ScheduleRunnerPlugin::default(),
+        QuinnetServerPlugin::default(),
+    ));
+    app.world_mut()
+        .resource_mut::<QuinnetServer>()
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(Ipv4Addr::LOCALHOST, 0),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: Ipv4Addr::LOCALHOST.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    // Keep the client ends alive, nothing reads from them
+    let mut client_ends = Vec::with_capacity(CLIENTS_COUNT);
+    for _ in 0..CLIENTS_COUNT {
+        let (client_end, server_end) = MemoryConnection::pair();
+        app.world()
+            .resource::<QuinnetServer>()
+            .endpoint()
+            .add_transport_connection(server_end);
+        client_ends.push(client_end);
+    }
+    while app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .clients()
+        .len()
+        < CLIENTS_COUNT
+    {
+        app.update();
+    }
+    (app, client_ends)
+}
+
+/// Discards the queued messages so that the outgoing queues never fill up, this is not measured
+fn clear_queues(app: &mut App, channel: ChannelId) {
+    let mut server = app.world_mut().resource_mut::<QuinnetServer>();
+    let endpoint = server.endpoint_mut();
+    for client_id in endpoint.clients() {
+        if let Some(connection) = endpoint.get_connection_mut(client_id) {
+            connection.clear_pending_messages(channel);
+        }
+    }
+}
+
+fn bench(name: &str, app: &mut App, mut send: impl FnMut(&mut App, u64)) {
+    let channel = app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .get_default_channel()
+        .unwrap();
+    let mut total = Duration::ZERO;
+    for tick in 0..ITERATIONS as u64 {
+        let start = Instant::now();
+        send(app, tick);
+        total += start.elapsed();
+        clear_queues(app, channel);
+    }
+    println!(
+        "{:<24} {:>10.1?} per send to {} clients",
+        name,
+        total / ITERATIONS,
+        CLIENTS_COUNT
+    );
+}
+
+fn main() {
+    let (mut app, _client_ends) = start_server();
+
+    bench("broadcast_message", &mut app, |app, tick| {
+        app.world_mut()
+            .resource_mut::<QuinnetServer>()
+            .endpoint_mut()
+            .broadcast_message(black_box(snapshot(tick)))
+            .unwrap();
+    });
+
+    bench("send_message per client", &mut app, |app, tick| {
+        let message = black_box(snapshot(tick));
+        let mut server = app.world_mut().resource_mut::<QuinnetServer>();
+        let endpoint = server.endpoint_mut();
+        for client_id in endpoint.clients() {
+            endpoint.send_message(client_id, &message).unwrap();
+        }
+    });
+}
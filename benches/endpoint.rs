@@ -0,0 +1,49 @@
+//! Throughput and latency of a server endpoint under the load of synthetic clients, for each message pattern and channel kind, see [`bevy_quinnet::loadtest`].
+//!
+//! Run with `cargo bench --features loadtest --bench endpoint`.
+
+use std::time::Duration;
+
+use bevy_quinnet::{
+    loadtest::{self, LoadTestConfiguration, LoadTestTransport, MessagePattern},
+    shared::channels::{ChannelKind, DEFAULT_MAX_RELIABLE_FRAME_LEN},
+};
+
+const CLIENTS_COUNT: usize = 64;
+const DURATION: Duration = Duration::from_secs(3);
+
+fn main() {
+    let channels = [
+        ChannelKind::OrderedReliable {
+            max_frame_size: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+        },
+        ChannelKind::UnorderedReliable {
+            max_frame_size: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+        },
+        ChannelKind::Unreliable,
+    ];
+    for transport in [LoadTestTransport::Memory, LoadTestTransport::Quic] {
+        for pattern in [
+            MessagePattern::Upload,
+            MessagePattern::Broadcast,
+            MessagePattern::Echo,
+        ] {
+            for channel in channels {
+                let config = LoadTestConfiguration::new(CLIENTS_COUNT)
+                    .with_transport(transport)
+                    .with_pattern(pattern)
+                    .with_channel(channel)
+                    .with_message_size(256)
+                    .with_rate(60.)
+                    .with_duration(DURATION);
+                match loadtest::run(config) {
+                    Ok(report) => println!("{}\n", report),
+                    Err(err) => println!(
+                        "{:?} {:?} {:?} failed: {}\n",
+                        transport, pattern, channel, err
+                    ),
+                }
+            }
+        }
+    }
+}
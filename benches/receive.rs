@@ -0,0 +1,143 @@
+//! Time for a server endpoint to receive the messages sent at once by many clients, mixing reliable and unreliable messages.
+//!
+//! Each client has its own receiving lanes on the server, one per receiving task of its streams and datagrams: neither the clients sending simultaneously nor the streams of a client contend with each other.
+//!
+//! Run with `cargo bench --bench receive`.
+
+use std::{
+    net::Ipv4Addr,
+    time::{Duration, Instant},
+};
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::App};
+use bevy_quinnet::{
+    client::{connection::ConnectionState, QuinnetClient, QuinnetClientPlugin},
+    server::{
+        certificate::CertificateRetrievalMode, QuinnetServer, QuinnetServerPlugin,
+        ServerEndpointConfiguration,
+    },
+    shared::{
+        channels::{ChannelKind, ChannelsConfiguration},
+        transport::memory::MemoryConnection,
+    },
+};
+use bytes::Bytes;
+
+const CLIENTS_COUNT: usize = 256;
+/// Reliable channels of each client, each received by a task of its own on the server
+const RELIABLE_CHANNELS_COUNT: usize = 4;
+/// Messages sent by each client on each channel per iteration
+const MESSAGES_PER_CHANNEL: usize = 8;
+const MESSAGE_SIZE: usize = 64;
+const ITERATIONS: u32 = 50;
+
+fn start(channels: ChannelsConfiguration) -> (App, App) {
+    let mut server_app = App::new();
+    server_app.add_plugins((
+        ScheduleRunnerPlugin::default(),
+        QuinnetServerPlugin::default(),
+    ));
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(Ipv4Addr::LOCALHOST, 0),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: Ipv4Addr::LOCALHOST.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+
+    let mut client_app = App::new();
+    client_app.add_plugins((
+        ScheduleRunnerPlugin::default(),
+        QuinnetClientPlugin::default(),
+    ));
+    for _ in 0..CLIENTS_COUNT {
+        let (client_end, server_end) = MemoryConnection::pair();
+        server_app
+            .world()
+            .resource::<QuinnetServer>()
+            .endpoint()
+            .add_transport_connection(server_end);
+        client_app
+            .world_mut()
+            .resource_mut::<QuinnetClient>()
+            .open_transport_connection(client_end, channels.clone())
+            .unwrap();
+    }
+    while server_app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .clients()
+        .len()
+        < CLIENTS_COUNT
+        || client_app
+            .world()
+            .resource::<QuinnetClient>()
+            .connections()
+            .any(|(_, connection)| connection.state() != ConnectionState::Connected)
+    {
+        server_app.update();
+        client_app.update();
+    }
+    (server_app, client_app)
+}
+
+fn main() {
+    let mut channels = ChannelsConfiguration::default();
+    let mut channel_ids: Vec<_> = (0..RELIABLE_CHANNELS_COUNT)
+        .map(|_| {
+            channels
+                .add(ChannelKind::OrderedReliable {
+                    max_frame_size: 1024,
+                })
+                .unwrap()
+        })
+        .collect();
+    channel_ids.push(channels.add(ChannelKind::Unreliable).unwrap());
+    let (mut server_app, mut client_app) = start(channels);
+
+    let payload = Bytes::from(vec![0; MESSAGE_SIZE]);
+    let expected = CLIENTS_COUNT * MESSAGES_PER_CHANNEL * channel_ids.len();
+    let mut total = Duration::ZERO;
+    let mut lost = 0;
+    for _ in 0..ITERATIONS {
+        let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+        for (_, connection) in client.connections_mut() {
+            for _ in 0..MESSAGES_PER_CHANNEL {
+                for channel in &channel_ids {
+                    connection
+                        .send_payload_on(*channel, payload.clone())
+                        .unwrap();
+                }
+            }
+        }
+
+        let start = Instant::now();
+        client_app.update();
+        let mut received = 0;
+        // Unreliable messages may be dropped by full queues, don't wait forever for them
+        while received < expected && start.elapsed() < Duration::from_secs(2) {
+            server_app.update();
+            received += server_app
+                .world_mut()
+                .resource_mut::<QuinnetServer>()
+                .endpoint_mut()
+                .drain_received()
+                .count();
+        }
+        total += start.elapsed();
+        lost += expected - received.min(expected);
+    }
+    println!(
+        "{:>10.1?} to receive {} messages from {} clients ({:.0} messages/s, {} lost)",
+        total / ITERATIONS,
+        expected,
+        CLIENTS_COUNT,
+        (expected as f64 * ITERATIONS as f64) / total.as_secs_f64(),
+        lost
+    );
+}
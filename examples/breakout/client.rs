@@ -98,7 +98,11 @@ struct WallBundle {
 pub(crate) fn start_connection(mut client: ResMut<QuinnetClient>) {
     client
         .open_connection(
-            ClientEndpointConfiguration::from_ips(SERVER_HOST, SERVER_PORT, LOCAL_BIND_IP, 0),
+            ClientEndpointConfiguration::builder()
+                .with_server_ip(SERVER_HOST, SERVER_PORT)
+                .with_local_bind_ip(LOCAL_BIND_IP, 0)
+                .build()
+                .unwrap(),
             CertificateVerificationMode::SkipVerification,
             ClientChannel::channels_configuration(),
         )
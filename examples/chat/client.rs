@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    net::Ipv6Addr,
     thread::{self, sleep},
     time::Duration,
 };
@@ -122,7 +123,10 @@ fn start_terminal_listener(mut commands: Commands) {
 fn start_connection(mut client: ResMut<QuinnetClient>) {
     client
         .open_connection(
-            ClientEndpointConfiguration::from_strings("[::1]:6000", "[::]:0").unwrap(),
+            ClientEndpointConfiguration::builder()
+                .with_server_ip(Ipv6Addr::LOCALHOST, 6000)
+                .build()
+                .unwrap(),
             CertificateVerificationMode::SkipVerification,
             ChannelsConfiguration::default(),
         )
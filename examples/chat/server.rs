@@ -56,9 +56,8 @@ fn handle_client_messages(mut server: ResMut<QuinnetServer>, mut users: ResMut<U
                     }
                 }
                 ClientMessage::Disconnect {} => {
-                    // We tell the server to disconnect this user
+                    // We tell the server to disconnect this user, its ConnectionLostEvent is handled below
                     endpoint.disconnect_client(client_id).unwrap();
-                    handle_disconnect(endpoint, &mut users, client_id);
                 }
                 ClientMessage::ChatMessage { message } => {
                     info!(
@@ -84,13 +83,13 @@ fn handle_server_events(
     mut server: ResMut<QuinnetServer>,
     mut users: ResMut<Users>,
 ) {
-    // The server signals us about users that lost connection
+    // The server signals us about users that lost connection or were disconnected
     for client in connection_lost_events.read() {
         handle_disconnect(server.endpoint_mut(), &mut users, client.id);
     }
 }
 
-/// Shared disconnection behaviour, whether the client lost connection or asked to disconnect
+/// Disconnection behaviour, whether the client lost connection or asked to disconnect
 fn handle_disconnect(endpoint: &mut Endpoint, users: &mut ResMut<Users>, client_id: ClientId) {
     // Remove this user
     if let Some(username) = users.names.remove(&client_id) {
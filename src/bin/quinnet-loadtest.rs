@@ -0,0 +1,90 @@
+//! Runs a load test against a local server endpoint and prints its report, see [`bevy_quinnet::loadtest`].
+//!
+//! Run with `cargo run --release --features loadtest --bin quinnet-loadtest -- [OPTIONS]`, `--help` lists the options.
+
+use std::{env, process::exit, str::FromStr, time::Duration};
+
+use bevy_quinnet::{
+    loadtest::{self, LoadTestConfiguration},
+    shared::channels::{ChannelKind, DEFAULT_MAX_RELIABLE_FRAME_LEN},
+};
+
+const USAGE: &str = "Usage: quinnet-loadtest [OPTIONS]
+
+Options:
+  --clients <N>           Number of synthetic clients [default: 16]
+  --pattern <PATTERN>     upload, broadcast or echo [default: echo]
+  --channel <CHANNEL>     ordered, unordered or unreliable [default: ordered]
+  --size <BYTES>          Size of the messages, at least 8 bytes [default: 64]
+  --rate <N>              Messages per second, per client or broadcasted by the server [default: 30]
+  --duration <SECONDS>    Duration of the sending phase [default: 5]
+  --tick <MILLISECONDS>   Sleep between two updates [default: 1]
+  --transport <TRANSPORT> quic or memory [default: quic]
+  --help                  Prints this message";
+
+fn parse_channel(value: &str) -> Option<ChannelKind> {
+    match value {
+        "ordered" => Some(ChannelKind::OrderedReliable {
+            max_frame_size: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+        }),
+        "unordered" => Some(ChannelKind::UnorderedReliable {
+            max_frame_size: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+        }),
+        "unreliable" => Some(ChannelKind::Unreliable),
+        _ => None,
+    }
+}
+
+fn parse<T: FromStr>(option: &str, value: &str) -> T {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value `{}` for {}\n\n{}", value, option, USAGE);
+        exit(2)
+    })
+}
+
+fn parse_args() -> LoadTestConfiguration {
+    let mut config = LoadTestConfiguration::default();
+    let mut args = env::args().skip(1);
+    while let Some(option) = args.next() {
+        if option == "--help" {
+            println!("{}", USAGE);
+            exit(0);
+        }
+        let Some(value) = args.next() else {
+            eprintln!("Missing value for {}\n\n{}", option, USAGE);
+            exit(2)
+        };
+        config = match option.as_str() {
+            "--clients" => config.with_clients(parse(&option, &value)),
+            "--pattern" => config.with_pattern(parse(&option, &value)),
+            "--channel" => match parse_channel(&value) {
+                Some(channel) => config.with_channel(channel),
+                None => {
+                    eprintln!("Invalid value `{}` for {}\n\n{}", value, option, USAGE);
+                    exit(2)
+                }
+            },
+            "--size" => config.with_message_size(parse(&option, &value)),
+            "--rate" => config.with_rate(parse(&option, &value)),
+            "--duration" => config.with_duration(Duration::from_secs_f64(parse(&option, &value))),
+            "--tick" => config.with_tick(Duration::from_millis(parse(&option, &value))),
+            "--transport" => config.with_transport(parse(&option, &value)),
+            _ => {
+                eprintln!("Unknown option {}\n\n{}", option, USAGE);
+                exit(2)
+            }
+        };
+    }
+    config
+}
+
+fn main() {
+    let config = parse_args();
+    match loadtest::run(config) {
+        Ok(report) => println!("{}", report),
+        Err(err) => {
+            eprintln!("Load test failed: {}", err);
+            exit(1)
+        }
+    }
+}
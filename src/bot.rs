@@ -0,0 +1,507 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use quinn::{crypto::rustls::QuicClientConfig, ClientConfig, Endpoint, TransportConfig};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+use crate::shared::{
+    buffer_pool::{BufferPool, DEFAULT_BUFFER_CHUNK_SIZE},
+    certificate::SkipServerVerification,
+    channels::{
+        ack::MAX_ACKS_PER_CONTROL_MESSAGE,
+        compression::DictionaryNegotiation,
+        control::{control_channel_config, ControlMessage, CONTROL_CHANNEL_ID},
+        incoming::{incoming_channel, IncomingPayloads},
+        queue::OutgoingQueue,
+        spawn_recv_channels_tasks, spawn_send_channels_tasks_spawner, Channel, ChannelAsyncMessage,
+        ChannelConfig, ChannelId, ChannelSyncMessage, ChannelsConfiguration, CloseReason,
+        SharedChannelConfigs,
+    },
+    close::CloseCode,
+    error::{AsyncChannelError, BotError},
+    hardening::ReceiveHardening,
+    memory::SharedConnectionMemory,
+    tick::{NetworkTick, SharedNetworkTick},
+    ClientId, DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE, DEFAULT_KEEP_ALIVE_INTERVAL_S,
+    DEFAULT_KILL_MESSAGE_QUEUE_SIZE, DEFAULT_MESSAGE_QUEUE_SIZE,
+    DEFAULT_QCHANNEL_MESSAGES_CHANNEL_SIZE,
+};
+
+/// Configuration of a [`BotConnection`]
+#[derive(Debug, Clone)]
+pub struct BotConfiguration {
+    server_addr: SocketAddr,
+    server_hostname: String,
+    local_bind_addr: SocketAddr,
+    tls_config: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl BotConfiguration {
+    /// Connects to the server at `server_addr`, from any local port. The server name defaults to the IP of the server.
+    ///
+    /// The certificate of the server is not verified unless a TLS configuration is given, see [`BotConfiguration::with_tls_config`].
+    pub fn new(server_addr: SocketAddr) -> Self {
+        let local_ip: IpAddr = match server_addr {
+            SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        Self {
+            server_addr,
+            server_hostname: server_addr.ip().to_string(),
+            local_bind_addr: SocketAddr::new(local_ip, 0),
+            tls_config: None,
+        }
+    }
+
+    /// Name of the server, used by the TLS handshake
+    pub fn with_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_hostname = server_name.into();
+        self
+    }
+
+    /// Local address of the endpoint of the bot
+    pub fn with_local_bind_addr(mut self, local_bind_addr: SocketAddr) -> Self {
+        self.local_bind_addr = local_bind_addr;
+        self
+    }
+
+    /// Replaces the TLS configuration skipping the verification of the certificate of the server. Its ALPN protocols are sent as they are, such as the [`crate::shared::QUINNET_ALPN`] protocol expected by the servers advertising ALPN protocols.
+    pub fn with_tls_config(mut self, tls_config: Arc<rustls::ClientConfig>) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    fn client_config(&self) -> Result<ClientConfig, BotError> {
+        let crypto = match &self.tls_config {
+            Some(tls_config) => rustls::ClientConfig::clone(tls_config),
+            None => rustls::ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(SkipServerVerification::new())
+                .with_no_client_auth(),
+        };
+        let mut client_config = ClientConfig::new(Arc::new(QuicClientConfig::try_from(crypto)?));
+        let mut transport = TransportConfig::default();
+        transport.keep_alive_interval(Some(DEFAULT_KEEP_ALIVE_INTERVAL_S));
+        client_config.transport_config(Arc::new(transport));
+        Ok(client_config)
+    }
+}
+
+/// Connection of a headless client to a Quinnet server, without the Bevy plugin layer: for load test bots and server tools.
+///
+/// Runs the same channels, codecs and control messages as the connections of the [`crate::client::QuinnetClient`], on the tokio runtime the connection was opened from. Messages are sent and received either by polling, like with the client, or by awaiting [`BotConnection::send`] and [`BotConnection::recv`] from tokio tasks. The control messages of the server are handled and the tracked messages are acknowledged when receiving. A compressed channel whose dictionary differs from the dictionary of the server is closed, with a warning.
+///
+/// Redirects of the server and forwarded clients are not supported.
+#[derive(Debug)]
+pub struct BotConnection {
+    endpoint: Endpoint,
+    connection: quinn::Connection,
+    client_id: Option<ClientId>,
+    channels: BTreeMap<ChannelId, Channel>,
+    channels_configs: SharedChannelConfigs,
+    control_channel: Channel,
+    buffer_pool: BufferPool,
+    /// Dictionaries of the compressed channels, announced to the server and checked against its own
+    dictionaries: DictionaryNegotiation,
+    incoming: IncomingPayloads,
+    from_channels_recv: mpsc::Receiver<ChannelAsyncMessage>,
+    /// Keeps the tasks of the channels alive until the bot is dropped
+    _to_channels_send: mpsc::Sender<ChannelSyncMessage>,
+    close_send: broadcast::Sender<CloseReason>,
+    /// Last network tick read from the payloads of the server, see [`crate::shared::channels::ChannelConfig::ticked`]
+    server_tick: SharedNetworkTick,
+    closed: bool,
+}
+
+impl BotConnection {
+    /// Connects to the server and opens the channels of `channels_config`, which must match the channels of the server.
+    ///
+    /// With the `shared-client-id` feature, waits for the client id sent by the server. Must be called from a tokio runtime, which drives the connection.
+    pub async fn connect(
+        config: BotConfiguration,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<Self, BotError> {
+        let mut endpoint = Endpoint::client(config.local_bind_addr)?;
+        endpoint.set_default_client_config(config.client_config()?);
+        let connection = endpoint
+            .connect(config.server_addr, &config.server_hostname)?
+            .await?;
+
+        let (bytes_incoming_send, bytes_incoming_recv) =
+            incoming_channel(DEFAULT_MESSAGE_QUEUE_SIZE);
+        let (from_channels_send, from_channels_recv) =
+            mpsc::channel(DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE);
+        let (to_channels_send, to_channels_recv) =
+            mpsc::channel(DEFAULT_QCHANNEL_MESSAGES_CHANNEL_SIZE);
+        let (close_send, close_recv) = broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
+        let channels_configs = SharedChannelConfigs::default();
+        let server_tick = SharedNetworkTick::default();
+
+        spawn_recv_channels_tasks(
+            connection.clone(),
+            0,
+            close_recv.resubscribe(),
+            bytes_incoming_send,
+            channels_configs.clone(),
+            ReceiveHardening::lenient(),
+            server_tick.clone(),
+        );
+        spawn_send_channels_tasks_spawner(
+            connection.clone(),
+            0,
+            close_recv,
+            to_channels_recv,
+            from_channels_send,
+            Box::new(|_, _| {}),
+        );
+
+        let buffer_pool = BufferPool::new(DEFAULT_BUFFER_CHUNK_SIZE);
+        let memory = SharedConnectionMemory::default();
+        let control_channel = create_channel(
+            &to_channels_send,
+            &buffer_pool,
+            &memory,
+            CONTROL_CHANNEL_ID,
+            &control_channel_config(),
+        )?;
+        let mut channels = BTreeMap::new();
+        let mut dictionaries = DictionaryNegotiation::default();
+        for (channel_id, channel_config) in channels_config.configs().iter().enumerate() {
+            let channel_id = channel_id as ChannelId;
+            dictionaries.opened(channel_id, channel_config);
+            channels.insert(
+                channel_id,
+                create_channel(
+                    &to_channels_send,
+                    &buffer_pool,
+                    &memory,
+                    channel_id,
+                    channel_config,
+                )?,
+            );
+            if let Ok(mut configs) = channels_configs.write() {
+                configs.insert(channel_id, channel_config.clone());
+            }
+        }
+
+        #[cfg(feature = "shared-client-id")]
+        let client_id = Some(receive_client_id(&connection).await?);
+        #[cfg(not(feature = "shared-client-id"))]
+        let client_id = None;
+
+        let mut bot = Self {
+            endpoint,
+            connection,
+            client_id,
+            channels,
+            channels_configs,
+            control_channel,
+            buffer_pool,
+            dictionaries,
+            incoming: IncomingPayloads::new(bytes_incoming_recv, memory),
+            from_channels_recv,
+            _to_channels_send: to_channels_send,
+            close_send,
+            server_tick,
+            closed: false,
+        };
+        bot.send_control(ControlMessage::ProtocolHash(
+            channels_config.protocol_hash(),
+        ))?;
+        for announcement in bot.dictionaries.take_announcements() {
+            bot.send_control(announcement)?;
+        }
+        info!(
+            "Bot connected to {} with client_id {:?}",
+            config.server_addr, bot.client_id
+        );
+        Ok(bot)
+    }
+
+    /// Id of the client on the server, `None` without the `shared-client-id` feature
+    pub fn client_id(&self) -> Option<ClientId> {
+        self.client_id
+    }
+
+    /// Most recent tick read from the payloads of the ticked channels of the server, see [`NetworkTick`]
+    pub fn server_tick(&self) -> Option<NetworkTick> {
+        self.server_tick.get()
+    }
+
+    /// Underlying QUIC connection, for its statistics or custom streams
+    pub fn quic_connection(&self) -> &quinn::Connection {
+        &self.connection
+    }
+
+    /// Returns true once the connection is closed, by the bot or by the server
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Sends a payload on a channel
+    pub fn send_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &self,
+        channel_id: C,
+        payload: T,
+    ) -> Result<(), BotError> {
+        if self.closed {
+            return Err(BotError::ConnectionClosed);
+        }
+        let channel_id = channel_id.into();
+        let payload = payload.into();
+        let channel = self
+            .channels
+            .get(&channel_id)
+            .ok_or(BotError::InvalidChannelId(channel_id))?;
+        if let Some(max_message_size) = channel.max_message_size() {
+            if payload.len() > max_message_size {
+                return Err(BotError::PayloadTooLarge {
+                    size: payload.len(),
+                    max_message_size,
+                });
+            }
+        }
+        Ok(channel.send_payload(payload, None, false)?)
+    }
+
+    /// Serializes a message with bincode and sends it on a channel
+    pub fn send_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), BotError> {
+        match self.buffer_pool.serialize(&message) {
+            Some(payload) => self.send_payload_on(channel_id, payload),
+            None => Err(BotError::Serialization),
+        }
+    }
+
+    /// Sends a payload on a channel, waiting for room in the outgoing queue of the channel instead of failing with [`AsyncChannelError::FullQueue`]
+    pub async fn send<T: Into<Bytes>, C: Into<ChannelId>>(
+        &self,
+        channel_id: C,
+        payload: T,
+    ) -> Result<(), BotError> {
+        let channel_id = channel_id.into();
+        self.channels
+            .get(&channel_id)
+            .ok_or(BotError::InvalidChannelId(channel_id))?
+            .reserve()
+            .await;
+        self.send_payload_on(channel_id, payload)
+    }
+
+    /// Serializes a message with bincode and sends it on a channel, see [`BotConnection::send`]
+    pub async fn send_message<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), BotError> {
+        match self.buffer_pool.serialize(&message) {
+            Some(payload) => self.send(channel_id, payload).await,
+            None => Err(BotError::Serialization),
+        }
+    }
+
+    /// Waits for the next payload sent by the server. Cancel safe, it can be used in a `tokio::select!`.
+    ///
+    /// Returns [`BotError::ConnectionClosed`] once the connection is closed and all its payloads were received.
+    pub async fn recv(&mut self) -> Result<(ChannelId, Bytes), BotError> {
+        let payload = self.incoming.recv().await;
+        self.update();
+        self.handle_control_messages();
+        payload.map_err(|_| {
+            self.closed = true;
+            BotError::ConnectionClosed
+        })
+    }
+
+    /// Waits for the next message sent by the server, deserialized with bincode, see [`BotConnection::recv`]
+    pub async fn recv_message<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<(ChannelId, T), BotError> {
+        let (channel_id, payload) = self.recv().await?;
+        match bincode::deserialize(&payload) {
+            Ok(message) => Ok((channel_id, message)),
+            Err(_) => Err(BotError::Deserialization),
+        }
+    }
+
+    /// Receives the next payload sent by the server, if any.
+    ///
+    /// Returns [`BotError::ConnectionClosed`] once the connection is closed and all its payloads were received.
+    pub fn receive_payload(&mut self) -> Result<Option<(ChannelId, Bytes)>, BotError> {
+        self.update();
+        let payload = self
+            .incoming
+            .try_recv()
+            .map_err(|_| BotError::ConnectionClosed)?;
+        self.handle_control_messages();
+        Ok(payload)
+    }
+
+    /// Receives the next message sent by the server, deserialized with bincode, if any
+    pub fn receive_message<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<(ChannelId, T)>, BotError> {
+        match self.receive_payload()? {
+            Some((channel_id, payload)) => match bincode::deserialize(&payload) {
+                Ok(message) => Ok(Some((channel_id, message))),
+                Err(_) => Err(BotError::Deserialization),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Closes the connection once the messages waiting in the channels are sent, and waits for the server to be notified. The server receives `code` as the application close code of the connection.
+    pub async fn disconnect(mut self, code: CloseCode) {
+        if !self.closed {
+            self.closed = true;
+            let _ = self.close_send.send(CloseReason::LocalOrder(code));
+            self.connection.closed().await;
+        }
+        self.endpoint.wait_idle().await;
+    }
+
+    fn update(&mut self) {
+        while let Ok(message) = self.from_channels_recv.try_recv() {
+            match message {
+                ChannelAsyncMessage::LostConnection => self.closed = true,
+                ChannelAsyncMessage::ProtocolViolation(violation) => {
+                    warn!("Bot connection closed: {:?}", violation);
+                    self.closed = true;
+                }
+                ChannelAsyncMessage::ChannelResumed(channel_id) => {
+                    info!("Bot channel {} resumed on a new stream", channel_id)
+                }
+                ChannelAsyncMessage::ChannelError(channel_id, err) => {
+                    warn!(
+                        "Bot failed to send a message on channel {}: {}",
+                        channel_id, err
+                    )
+                }
+            }
+        }
+        if self.connection.close_reason().is_some() {
+            self.closed = true;
+        }
+    }
+
+    fn handle_control_messages(&mut self) {
+        for payload in self.incoming.take_control() {
+            match ControlMessage::decode(&payload) {
+                Some(ControlMessage::ProtocolMismatch { server_hash }) => {
+                    warn!(
+                        "Bot protocol hash does not match the hash {:x} of the server, closing the connection",
+                        server_hash
+                    );
+                    // The server waits for the bot to close the connection, once notified
+                    self.closed = true;
+                    let _ = self
+                        .close_send
+                        .send(CloseReason::LocalOrder(CloseCode::ProtocolMismatch));
+                }
+                Some(ControlMessage::ChannelOpened {
+                    channel_id,
+                    dictionary_id,
+                }) => self.dictionaries.announced(channel_id, dictionary_id),
+                Some(ControlMessage::ChannelProbe {
+                    channel_id,
+                    sequence,
+                }) => {
+                    if let Err(err) = self.send_control(ControlMessage::ChannelProbeAck {
+                        channel_id,
+                        sequence,
+                    }) {
+                        warn!(
+                            "Bot failed to answer the liveness probe of channel {}: {}",
+                            channel_id, err
+                        );
+                    }
+                }
+                _ => (),
+            }
+        }
+        for mismatch in self.dictionaries.take_mismatches() {
+            warn!(
+                "Bot dictionary {:?} of channel {} does not match the dictionary {:?} of the server, closing the channel",
+                mismatch.local_dictionary_id, mismatch.channel_id, mismatch.peer_dictionary_id
+            );
+            // Dropping the channel closes its queue and its tasks
+            self.channels.remove(&mismatch.channel_id);
+            if let Ok(mut configs) = self.channels_configs.write() {
+                configs.remove(&mismatch.channel_id);
+            }
+        }
+        let acks = self.incoming.take_acks();
+        for ids in acks.chunks(MAX_ACKS_PER_CONTROL_MESSAGE) {
+            if let Err(err) = self.send_control(ControlMessage::Acks(ids.to_vec())) {
+                warn!(
+                    "Bot failed to acknowledge the messages of the server: {}",
+                    err
+                );
+            }
+        }
+    }
+
+    fn send_control(&self, message: ControlMessage) -> Result<(), BotError> {
+        Ok(self
+            .control_channel
+            .send_payload(message.encode(), None, false)?)
+    }
+}
+
+impl Drop for BotConnection {
+    fn drop(&mut self) {
+        if !self.closed {
+            let _ = self
+                .close_send
+                .send(CloseReason::LocalOrder(CloseCode::Closed));
+        }
+    }
+}
+
+fn create_channel(
+    to_channels_send: &mpsc::Sender<ChannelSyncMessage>,
+    buffer_pool: &BufferPool,
+    memory: &SharedConnectionMemory,
+    channel_id: ChannelId,
+    channel_config: &ChannelConfig,
+) -> Result<Channel, BotError> {
+    let queue = Arc::new(OutgoingQueue::new(
+        DEFAULT_MESSAGE_QUEUE_SIZE,
+        memory.clone(),
+    ));
+    let (channel_close_send, channel_close_recv) = mpsc::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
+    to_channels_send
+        .try_send(ChannelSyncMessage::CreateChannel {
+            id: channel_id,
+            config: channel_config.clone(),
+            queue: queue.clone(),
+            buffers: buffer_pool.sibling(),
+            channel_close_recv,
+        })
+        .map_err(|_| AsyncChannelError::InternalChannelClosed)?;
+    Ok(Channel::new(
+        channel_id,
+        channel_config,
+        queue,
+        channel_close_send,
+    ))
+}
+
+/// Reads the client id sent by the server on the first bidirectional stream, see [`crate::shared::wire::encode_client_id`]
+#[cfg(feature = "shared-client-id")]
+async fn receive_client_id(connection: &quinn::Connection) -> Result<ClientId, BotError> {
+    let (_send, mut recv) = connection.accept_bi().await?;
+    let mut frame = [0; 4 + crate::shared::CLIENT_ID_LEN];
+    recv.read_exact(&mut frame)
+        .await
+        .map_err(|_| BotError::ClientIdNotReceived)?;
+    crate::shared::wire::decode_client_id(&frame).ok_or(BotError::ClientIdNotReceived)
+}
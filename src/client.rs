@@ -3,38 +3,82 @@ use std::{
         hash_map::{Iter, IterMut},
         HashMap,
     },
+    future::Future,
+    net::SocketAddr,
     sync::Mutex,
+    time::{Duration, Instant},
 };
 
-use bevy::prelude::*;
+use bevy::{
+    ecs::{
+        schedule::{InternedScheduleLabel, ScheduleLabel},
+        system::SystemParam,
+    },
+    prelude::*,
+};
+use bytes::Bytes;
 
 use tokio::{
     runtime::{self},
-    sync::oneshot,
+    sync::{mpsc::error::TryRecvError, oneshot},
 };
 
 use crate::shared::{
-    channels::{ChannelAsyncMessage, ChannelsConfiguration},
+    channels::{ChannelAsyncMessage, ChannelId, ChannelsConfiguration},
+    close::{CloseCode, CloseStage},
     error::AsyncChannelError,
-    AsyncRuntime, ClientId, InternalConnectionRef, QuinnetSyncUpdate,
+    invite::{InviteCode, InviteKey},
+    par_map_connections,
+    tick::NetworkTick,
+    transport::TransportConnection,
+    AsyncRuntime, ClientId, InternalConnectionRef, QuinnetFlush, QuinnetShutdown,
+    QuinnetSyncUpdate,
 };
 
+#[cfg(feature = "testing")]
+use crate::testing::ScriptedServer;
+
 use self::{
     certificate::{
         CertConnectionAbortEvent, CertInteractionEvent, CertTrustUpdateEvent, CertVerificationInfo,
-        CertVerificationStatus, CertVerifierAction, CertificateVerificationMode,
+        CertVerificationStatus, CertVerifierAction, CertificateVerificationMode, KnownHost,
+        ServerName, TrustOnFirstUseConfig,
     },
     connection::{
-        async_connection_task, create_async_channels, ClientEndpointConfiguration,
-        ClientSideConnection, ConnectionEvent, ConnectionFailedEvent, ConnectionLocalId,
-        ConnectionLostEvent, ConnectionState, InternalConnectionState,
+        async_connection_task, connect_quic, create_async_channels, race_connect_quic,
+        AsyncConnectionEnds, ChannelErrorEvent, ChannelRejectedEvent, ChannelResumedEvent,
+        ChannelUnresponsiveEvent, ClientAsyncMsgRecv, ClientAsyncMsgSend,
+        ClientEndpointConfiguration, ClientSideConnection, CongestionEvent,
+        ConnectionCloseStageEvent, ConnectionEvent, ConnectionFailedEvent, ConnectionLocalId,
+        ConnectionLostEvent, ConnectionRaceEvent, ConnectionState, ConnectionTransferEvent,
+        InternalConnectionState, MaxDatagramSizeChangedEvent, MemoryBudgetExceededEvent,
+        MessageAckedEvent, MessageLostEvent, ProtocolMismatchEvent, RaceAttempt,
     },
 };
 
+/// Module for the client's side of the baseline synchronization
+pub mod baseline;
+/// Ready-made dialog answering the certificate interactions
+#[cfg(feature = "cert-dialog")]
+pub mod cert_dialog;
 /// Module for the client's certificate features
 pub mod certificate;
+/// Module for the client's side of the text chat
+pub mod chat;
 /// Module for a client's connection to a server
 pub mod connection;
+/// Module for the client's input streams
+pub mod input;
+/// Module for the client's side of the lockstep simulations
+pub mod lockstep;
+/// Module for the registration to a master server and the browsing of its servers
+pub mod master;
+/// Module for the interpolation of the snapshots received from the server
+pub mod snapshot;
+/// Module for the spectator side of the server's spectator streams
+pub mod spectator;
+/// Module for the send and receive halves of a client's connection
+pub mod split;
 
 mod error;
 pub use error::*;
@@ -54,13 +98,27 @@ pub enum QuinnetConnectionError {
     /// Client did not receive its client id
     #[error("Client did not receive its client id")]
     ClientIdNotReceived,
+    /// The local socket could not be bound on the local bind address
+    #[error("Failed to bind the local socket on `{0}`: {1}")]
+    LocalBindFailed(SocketAddr, std::io::ErrorKind),
+    /// None of the servers raced by [`QuinnetClient::open_connection_race`] finished the handshake, see the [`ConnectionRaceEvent`] for the error of each of them
+    #[error("None of the raced servers could be connected to")]
+    NoRaceWinner,
+    /// The server closed the connection before it was established, for example with [`CloseCode::NotAccepting`]
+    #[error("The server closed the connection: {0}")]
+    ConnectionClosed(CloseCode),
 }
 
 #[derive(Debug)]
 pub(crate) enum ClientAsyncMessage {
-    Connected(InternalConnectionRef, Option<ClientId>),
+    Connected(InternalConnectionRef, Option<ClientId>, Option<SocketAddr>),
     ConnectionFailed(QuinnetConnectionError),
-    ConnectionClosed, // TODO Might set a ConnectionError
+    ConnectionClosed(Option<CloseCode>),
+    CloseStage(CloseStage, Duration),
+    RaceFinished {
+        winner: Option<Box<ClientEndpointConfiguration>>,
+        attempts: Vec<RaceAttempt>,
+    },
     CertificateInteractionRequest {
         status: CertVerificationStatus,
         info: CertVerificationInfo,
@@ -82,6 +140,9 @@ pub struct QuinnetClient {
     connections: HashMap<ConnectionLocalId, ClientSideConnection>,
     connection_local_id_gen: ConnectionLocalId,
     default_connection_id: Option<ConnectionLocalId>,
+    deferred_flush: bool,
+    /// Async messages of the connections closed by [`QuinnetClient::close_connection`], until they report their [`CloseStage::Closed`] stage
+    closing_connections: HashMap<ConnectionLocalId, ClientAsyncMsgRecv>,
 }
 
 impl FromWorld for QuinnetClient {
@@ -91,7 +152,7 @@ impl FromWorld for QuinnetClient {
                 .enable_all()
                 .build()
                 .unwrap();
-            world.insert_resource(AsyncRuntime(async_runtime));
+            world.insert_resource(AsyncRuntime::new(async_runtime));
         };
 
         let runtime = world.resource::<AsyncRuntime>();
@@ -106,6 +167,16 @@ impl QuinnetClient {
             runtime: runtime_handle,
             connection_local_id_gen: 0,
             default_connection_id: None,
+            deferred_flush: false,
+            closing_connections: HashMap::new(),
+        }
+    }
+
+    /// Sets the flush mode of the current connections and of the connections opened later, see [`ClientSideConnection::set_deferred_flush`]
+    pub fn set_deferred_flush(&mut self, deferred: bool) {
+        self.deferred_flush = deferred;
+        for connection in self.connections.values_mut() {
+            connection.set_deferred_flush(deferred);
         }
     }
 
@@ -177,15 +248,49 @@ impl QuinnetClient {
     }
 
     /// Returns an iterator over all connections
-    pub fn connections(&self) -> Iter<ConnectionLocalId, ClientSideConnection> {
+    pub fn connections(&self) -> Iter<'_, ConnectionLocalId, ClientSideConnection> {
         self.connections.iter()
     }
 
     /// Returns an iterator over all connections as muts
-    pub fn connections_mut(&mut self) -> IterMut<ConnectionLocalId, ClientSideConnection> {
+    pub fn connections_mut(&mut self) -> IterMut<'_, ConnectionLocalId, ClientSideConnection> {
         self.connections.iter_mut()
     }
 
+    /// Receives the payloads sent by the servers of all the connections, processing the connections in parallel.
+    ///
+    /// `handler` is called for each payload, in the receiving order of each connection. Connections are spread over the threads of Bevy's [`ComputeTaskPool`](bevy::tasks::ComputeTaskPool). Closed connections are skipped.
+    pub fn par_receive_payloads<F>(&mut self, handler: F)
+    where
+        F: Fn(ConnectionLocalId, ChannelId, Bytes) + Send + Sync,
+    {
+        par_map_connections(self.connections.iter_mut(), |connection_id, connection| {
+            while let Ok(Some((channel_id, payload))) = connection.receive_payload() {
+                handler(connection_id, channel_id, payload);
+            }
+        });
+    }
+
+    /// Same as [`QuinnetClient::par_receive_payloads`], deserializing the payloads into messages of type `T`.
+    ///
+    /// Payloads that can't be deserialized are logged and skipped.
+    pub fn par_receive_messages<T, F>(&mut self, handler: F)
+    where
+        T: serde::de::DeserializeOwned,
+        F: Fn(ConnectionLocalId, ChannelId, T) + Send + Sync,
+    {
+        self.par_receive_payloads(
+            |connection_id, channel_id, payload| match bincode::deserialize(&payload) {
+                Ok(message) => handler(connection_id, channel_id, message),
+                Err(_) => error!(
+                    "par_receive_messages on connection {}: {}",
+                    connection_id,
+                    ClientMessageReceiveError::Deserialization
+                ),
+            },
+        );
+    }
+
     /// Open a connection to a server with the given [ClientEndpointConfiguration], [CertificateVerificationMode] and [ChannelsConfiguration]. The connection will raise an event when fully connected, see [ConnectionEvent]
     ///
     /// Returns the [ConnectionLocalId]
@@ -195,6 +300,128 @@ impl QuinnetClient {
         cert_mode: CertificateVerificationMode,
         channels_config: ChannelsConfiguration,
     ) -> Result<ConnectionLocalId, AsyncChannelError> {
+        let endpoint_config = endpoint_config.detach_local_socket();
+        self.open_connection_with(
+            Some(endpoint_config.clone()),
+            Some(cert_mode.clone()),
+            channels_config,
+            |local_id, to_sync_client_send| {
+                connect_quic(local_id, endpoint_config, cert_mode, to_sync_client_send)
+            },
+        )
+    }
+
+    /// Open a connection to the server of an invite code encrypted with `key`, see [`InviteCode`], with the given [ChannelsConfiguration] and a default [ClientEndpointConfiguration]. Only the certificate whose fingerprint is in the invite is trusted.
+    ///
+    /// The join token of the invite, if any, is not sent: decode the code with [`InviteCode::decode`] to send it once connected.
+    ///
+    /// Returns the [ConnectionLocalId]
+    pub fn open_connection_from_invite(
+        &mut self,
+        code: &str,
+        key: &InviteKey,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<ConnectionLocalId, InviteConnectionError> {
+        let invite = InviteCode::decode(code, key)?;
+        let server_name = ServerName::try_from(invite.host())
+            .map_err(|_| ClientConfigurationError::InvalidServerName(invite.host().to_string()))?;
+        let endpoint_config = ClientEndpointConfiguration::builder()
+            .with_server_host(invite.host(), invite.port())
+            .build()?;
+        let cert_mode =
+            CertificateVerificationMode::TrustOnFirstUse(TrustOnFirstUseConfig::pinned(
+                KnownHost::new(server_name, invite.port()),
+                invite.fingerprint().clone(),
+            ));
+        Ok(self.open_connection(endpoint_config, cert_mode, channels_config)?)
+    }
+
+    /// Open a connection to the first of several servers to finish the handshake, such as the regional servers of a game. The servers are connected to concurrently, with the given [CertificateVerificationMode] and [ChannelsConfiguration], and the other attempts are aborted as soon as one of them is connected.
+    ///
+    /// A [ConnectionRaceEvent] reports the result of each attempt, before the [ConnectionEvent] or the [ConnectionFailedEvent] of the connection. The configuration of the winner is kept to reconnect the connection.
+    ///
+    /// Returns the [ConnectionLocalId]
+    pub fn open_connection_race(
+        &mut self,
+        endpoint_configs: Vec<ClientEndpointConfiguration>,
+        cert_mode: CertificateVerificationMode,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<ConnectionLocalId, AsyncChannelError> {
+        let endpoint_configs: Vec<_> = endpoint_configs
+            .into_iter()
+            .map(ClientEndpointConfiguration::detach_local_socket)
+            .collect();
+        self.open_connection_with(
+            endpoint_configs.first().cloned(),
+            Some(cert_mode.clone()),
+            channels_config,
+            |local_id, to_sync_client_send| {
+                race_connect_quic(local_id, endpoint_configs, cert_mode, to_sync_client_send)
+            },
+        )
+    }
+
+    /// Open a connection to a server over an already established custom [`TransportConnection`], such as the client end of a [`crate::shared::transport::memory::MemoryConnection`], with the given [ChannelsConfiguration]. The server end must be added to the server with [`crate::server::Endpoint::add_transport_connection`].
+    ///
+    /// The connection will raise an event when fully connected, see [ConnectionEvent]. It cannot be reconnected once closed.
+    ///
+    /// Returns the [ConnectionLocalId]
+    pub fn open_transport_connection<C: TransportConnection>(
+        &mut self,
+        connection: C,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<ConnectionLocalId, AsyncChannelError> {
+        self.open_connection_with(None, None, channels_config, |_, _| async move {
+            Ok((connection, None))
+        })
+    }
+
+    fn open_connection_with<C: TransportConnection, F>(
+        &mut self,
+        endpoint_config: Option<ClientEndpointConfiguration>,
+        cert_mode: Option<CertificateVerificationMode>,
+        channels_config: ChannelsConfiguration,
+        connect: impl FnOnce(ConnectionLocalId, ClientAsyncMsgSend) -> F,
+    ) -> Result<ConnectionLocalId, AsyncChannelError>
+    where
+        F: Future<Output = Result<(C, Option<SocketAddr>), QuinnetConnectionError>>
+            + Send
+            + 'static,
+    {
+        let (local_id, ends) = self.add_connection(endpoint_config, cert_mode, channels_config)?;
+        let channels_configs = self.connections[&local_id].channels_configs.clone();
+        let server_tick = self.connections[&local_id].server_tick.clone();
+
+        // Async connection
+        let connect = connect(local_id, ends.to_sync_client_send.clone());
+        self.runtime.spawn(async move {
+            async_connection_task(local_id, connect, ends, channels_configs, server_tick).await
+        });
+
+        Ok(local_id)
+    }
+
+    /// Opens a connection driven by the returned [`ScriptedServer`] instead of a transport and its async tasks, see [`crate::testing`].
+    ///
+    /// Meant for deterministic tests of the systems handling the connection: the events and messages scripted with the [`ScriptedServer`] are all raised by the next update of the client.
+    #[cfg(feature = "testing")]
+    pub fn open_scripted_connection(
+        &mut self,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<(ConnectionLocalId, ScriptedServer), AsyncChannelError> {
+        let (local_id, ends) = self.add_connection(None, None, channels_config)?;
+        let script = ScriptedServer::new(ends);
+        self.connections.get_mut(&local_id).unwrap().script = Some(script.slot());
+        Ok((local_id, script))
+    }
+
+    /// Creates a new connection and its internal channels, returns the async ends of the channels
+    fn add_connection(
+        &mut self,
+        endpoint_config: Option<ClientEndpointConfiguration>,
+        cert_mode: Option<CertificateVerificationMode>,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<(ConnectionLocalId, AsyncConnectionEnds), AsyncChannelError> {
         // Generate a local connection id
         let local_id = self.connection_local_id_gen;
         self.connection_local_id_gen += 1;
@@ -215,8 +442,8 @@ impl QuinnetClient {
         let mut connection = ClientSideConnection::new(
             local_id,
             self.runtime.clone(),
-            endpoint_config.clone(),
-            cert_mode.clone(),
+            endpoint_config,
+            cert_mode,
             channels_config.clone(),
             bytes_from_server_recv,
             close_send,
@@ -224,6 +451,7 @@ impl QuinnetClient {
             to_channels_send,
             from_channels_recv,
         );
+        connection.set_deferred_flush(self.deferred_flush);
         connection.open_configured_channels(channels_config)?;
 
         self.connections.insert(local_id, connection);
@@ -231,22 +459,16 @@ impl QuinnetClient {
             self.default_connection_id = Some(local_id);
         }
 
-        // Async connection
-        self.runtime.spawn(async move {
-            async_connection_task(
-                local_id,
-                endpoint_config,
-                cert_mode,
-                to_sync_client_send,
+        Ok((
+            local_id,
+            AsyncConnectionEnds {
                 bytes_from_server_send,
-                to_channels_recv,
+                to_sync_client_send,
                 from_channels_send,
+                to_channels_recv,
                 close_recv,
-            )
-            .await
-        });
-
-        Ok(local_id)
+            },
+        ))
     }
 
     /// Set the default connection
@@ -273,7 +495,12 @@ impl QuinnetClient {
                 if Some(connection_id) == self.default_connection_id {
                     self.default_connection_id = None;
                 }
-                connection.disconnect()
+                let result = connection.disconnect();
+                if result.is_ok() {
+                    self.closing_connections
+                        .insert(connection_id, connection.from_async_client_recv);
+                }
+                result
             }
             None => Err(ClientConnectionCloseError::InvalidConnectionId(
                 connection_id,
@@ -292,86 +519,381 @@ impl QuinnetClient {
             let _ = self.close_connection(connection_id);
         }
     }
-}
 
-/// Receive messages from the async client tasks and update the sync client.
-///
-/// This system generates the client's bevy events
-pub fn update_sync_client(
-    mut connection_events: EventWriter<ConnectionEvent>,
-    mut connection_failed_events: EventWriter<ConnectionFailedEvent>,
-    mut connection_lost_events: EventWriter<ConnectionLostEvent>,
-    mut certificate_interaction_events: EventWriter<CertInteractionEvent>,
-    mut cert_trust_update_events: EventWriter<CertTrustUpdateEvent>,
-    mut cert_connection_abort_events: EventWriter<CertConnectionAbortEvent>,
-    mut client: ResMut<QuinnetClient>,
-) {
-    for (connection_id, connection) in &mut client.connections {
-        while let Ok(message) = connection.from_async_client_recv.try_recv() {
-            match message {
-                ClientAsyncMessage::Connected(internal_connection, client_id) => {
-                    connection.state =
-                        InternalConnectionState::Connected(internal_connection, client_id);
-                    connection_events.write(ConnectionEvent {
-                        id: *connection_id,
-                        client_id,
-                    });
-                }
-                ClientAsyncMessage::ConnectionFailed(err) => {
-                    connection.state = InternalConnectionState::Disconnected;
-                    connection_failed_events.write(ConnectionFailedEvent {
-                        id: *connection_id,
-                        err,
-                    });
+    /// Receives the messages from the async client tasks and updates the connections, like [`update_sync_client`] does, without any Bevy system.
+    ///
+    /// Meant for custom runners and schedules, or tools which don't add the [`QuinnetClientPlugin`]. Returns the events which would have been raised, in order.
+    pub fn pump(&mut self) -> Vec<QuinnetClientEvent> {
+        let mut events = Vec::new();
+        self.closing_connections
+            .retain(|connection_id, from_async_client_recv| loop {
+                match from_async_client_recv.try_recv() {
+                    Ok(ClientAsyncMessage::CloseStage(stage, elapsed)) => {
+                        let closed = matches!(stage, CloseStage::Closed { .. });
+                        events.push(QuinnetClientEvent::ConnectionCloseStage(
+                            ConnectionCloseStageEvent {
+                                id: *connection_id,
+                                stage,
+                                elapsed,
+                            },
+                        ));
+                        if closed {
+                            break false;
+                        }
+                    }
+                    // The connection is removed, its other messages are dropped
+                    Ok(_) => (),
+                    Err(TryRecvError::Empty) => break true,
+                    Err(TryRecvError::Disconnected) => break false,
                 }
-                ClientAsyncMessage::ConnectionClosed => match connection.state {
-                    InternalConnectionState::Disconnected => (),
-                    _ => {
-                        connection.try_disconnect_closed_connection();
-                        connection_lost_events.write(ConnectionLostEvent { id: *connection_id });
+            });
+        for (connection_id, connection) in &mut self.connections {
+            while let Ok(message) = connection.from_async_client_recv.try_recv() {
+                match message {
+                    ClientAsyncMessage::Connected(internal_connection, client_id, local_addr) => {
+                        connection.set_state(InternalConnectionState::Connected(
+                            internal_connection,
+                            client_id,
+                        ));
+                        connection.local_addr = local_addr;
+                        connection.present_protocol_hash();
+                        connection.present_forwarded_client();
+                        connection.present_transfer_token();
+                        events.push(QuinnetClientEvent::Connection(ConnectionEvent {
+                            id: *connection_id,
+                            client_id,
+                        }));
+                    }
+                    ClientAsyncMessage::ConnectionFailed(err) => {
+                        // Already reported if the server closed the connection while connecting
+                        if let InternalConnectionState::Disconnected = connection.state {
+                            continue;
+                        }
+                        connection.set_state(InternalConnectionState::Disconnected);
+                        events.push(QuinnetClientEvent::ConnectionFailed(
+                            ConnectionFailedEvent {
+                                id: *connection_id,
+                                err,
+                            },
+                        ));
+                    }
+                    ClientAsyncMessage::ConnectionClosed(close_code) => match connection.state {
+                        InternalConnectionState::Disconnected => (),
+                        InternalConnectionState::Connecting => {
+                            connection.try_disconnect_closed_connection();
+                            events.push(QuinnetClientEvent::ConnectionFailed(
+                                ConnectionFailedEvent {
+                                    id: *connection_id,
+                                    err: match close_code {
+                                        Some(code) => {
+                                            QuinnetConnectionError::ConnectionClosed(code)
+                                        }
+                                        None => QuinnetConnectionError::ClientIdNotReceived,
+                                    },
+                                },
+                            ));
+                        }
+                        _ => {
+                            connection.try_disconnect_closed_connection();
+                            events.push(QuinnetClientEvent::ConnectionLost(ConnectionLostEvent {
+                                id: *connection_id,
+                                close_code,
+                            }));
+                        }
+                    },
+                    ClientAsyncMessage::CloseStage(stage, elapsed) => {
+                        events.push(QuinnetClientEvent::ConnectionCloseStage(
+                            ConnectionCloseStageEvent {
+                                id: *connection_id,
+                                stage,
+                                elapsed,
+                            },
+                        ));
                     }
-                },
-                ClientAsyncMessage::CertificateInteractionRequest {
-                    status,
-                    info,
-                    action_sender,
-                } => {
-                    certificate_interaction_events.write(CertInteractionEvent {
-                        connection_id: *connection_id,
+                    ClientAsyncMessage::RaceFinished { winner, attempts } => {
+                        if let Some(winner) = winner {
+                            connection.keep_race_winner(*winner);
+                        }
+                        events.push(QuinnetClientEvent::ConnectionRace(ConnectionRaceEvent {
+                            id: *connection_id,
+                            attempts,
+                        }));
+                    }
+                    ClientAsyncMessage::CertificateInteractionRequest {
                         status,
                         info,
-                        action_sender: Mutex::new(Some(action_sender)),
-                    });
+                        action_sender,
+                    } => {
+                        events.push(QuinnetClientEvent::CertInteraction(CertInteractionEvent {
+                            connection_id: *connection_id,
+                            status,
+                            info,
+                            action_sender: Mutex::new(Some(action_sender)),
+                        }));
+                    }
+                    ClientAsyncMessage::CertificateTrustUpdate(info) => {
+                        events.push(QuinnetClientEvent::CertTrustUpdate(CertTrustUpdateEvent {
+                            connection_id: *connection_id,
+                            cert_info: info,
+                        }));
+                    }
+                    ClientAsyncMessage::CertificateConnectionAbort { status, cert_info } => {
+                        events.push(QuinnetClientEvent::CertConnectionAbort(
+                            CertConnectionAbortEvent {
+                                connection_id: *connection_id,
+                                status,
+                                cert_info,
+                            },
+                        ));
+                    }
                 }
-                ClientAsyncMessage::CertificateTrustUpdate(info) => {
-                    cert_trust_update_events.write(CertTrustUpdateEvent {
-                        connection_id: *connection_id,
-                        cert_info: info,
-                    });
+            }
+            connection.capture_received();
+            let (transfer, mismatch) = connection.handle_control_messages();
+            if let Some(event) = mismatch {
+                events.push(QuinnetClientEvent::ProtocolMismatch(event));
+                // The server waits for the client to close the connection, once notified
+                if let Err(err) = connection.disconnect_with_code(CloseCode::ProtocolMismatch) {
+                    trace!("Connection {} already closed: {}", connection_id, err);
                 }
-                ClientAsyncMessage::CertificateConnectionAbort { status, cert_info } => {
-                    cert_connection_abort_events.write(CertConnectionAbortEvent {
-                        connection_id: *connection_id,
-                        status,
-                        cert_info,
-                    });
+                events.push(QuinnetClientEvent::ConnectionLost(ConnectionLostEvent {
+                    id: *connection_id,
+                    close_code: Some(CloseCode::ProtocolMismatch),
+                }));
+                continue;
+            }
+            let now = Instant::now();
+            events.extend(connection.update_acks(now));
+            events.extend(connection.poll_liveness(now));
+            events.extend(connection.negotiate_dictionaries());
+            events.extend(connection.sample_stats(now));
+            events.extend(connection.update_max_datagram_size());
+            events.extend(connection.check_memory_budget());
+            if let Some(event) = transfer {
+                events.push(QuinnetClientEvent::ConnectionTransfer(event));
+                continue;
+            }
+            while let Ok(message) = connection.from_channels_recv.try_recv() {
+                match message {
+                    ChannelAsyncMessage::LostConnection => match connection.state {
+                        InternalConnectionState::Disconnected => (),
+                        _ => {
+                            connection.try_disconnect_closed_connection();
+                            events.push(QuinnetClientEvent::ConnectionLost(ConnectionLostEvent {
+                                id: *connection_id,
+                                close_code: None,
+                            }));
+                        }
+                    },
+                    // Only reported by the server connections, the client only logs them
+                    ChannelAsyncMessage::ProtocolViolation(_) => (),
+                    ChannelAsyncMessage::ChannelResumed(channel_id) => {
+                        events.push(QuinnetClientEvent::ChannelResumed(ChannelResumedEvent {
+                            id: *connection_id,
+                            channel_id,
+                        }));
+                    }
+                    ChannelAsyncMessage::ChannelError(channel_id, error) => {
+                        events.push(QuinnetClientEvent::ChannelError(ChannelErrorEvent {
+                            id: *connection_id,
+                            channel_id,
+                            error,
+                        }));
+                    }
                 }
             }
         }
-        while let Ok(message) = connection.from_channels_recv.try_recv() {
-            match message {
-                ChannelAsyncMessage::LostConnection => match connection.state {
-                    InternalConnectionState::Disconnected => (),
-                    _ => {
-                        connection.try_disconnect_closed_connection();
-                        connection_lost_events.write(ConnectionLostEvent { id: *connection_id });
-                    }
-                },
+        events
+    }
+}
+
+/// Event produced by [`QuinnetClient::pump`]. [`update_sync_client`] raises each of them as the bevy event it wraps.
+pub enum QuinnetClientEvent {
+    /// See [`ConnectionEvent`]
+    Connection(ConnectionEvent),
+    /// See [`ConnectionFailedEvent`]
+    ConnectionFailed(ConnectionFailedEvent),
+    /// See [`ConnectionLostEvent`]
+    ConnectionLost(ConnectionLostEvent),
+    /// See [`ConnectionTransferEvent`]
+    ConnectionTransfer(ConnectionTransferEvent),
+    /// See [`ProtocolMismatchEvent`]
+    ProtocolMismatch(ProtocolMismatchEvent),
+    /// See [`ConnectionRaceEvent`]
+    ConnectionRace(ConnectionRaceEvent),
+    /// See [`ConnectionCloseStageEvent`]
+    ConnectionCloseStage(ConnectionCloseStageEvent),
+    /// See [`MessageAckedEvent`]
+    MessageAcked(MessageAckedEvent),
+    /// See [`MessageLostEvent`]
+    MessageLost(MessageLostEvent),
+    /// See [`ChannelResumedEvent`]
+    ChannelResumed(ChannelResumedEvent),
+    /// See [`ChannelErrorEvent`]
+    ChannelError(ChannelErrorEvent),
+    /// See [`ChannelUnresponsiveEvent`]
+    ChannelUnresponsive(ChannelUnresponsiveEvent),
+    /// See [`ChannelRejectedEvent`]
+    ChannelRejected(ChannelRejectedEvent),
+    /// See [`CongestionEvent`]
+    Congestion(CongestionEvent),
+    /// See [`MaxDatagramSizeChangedEvent`]
+    MaxDatagramSizeChanged(MaxDatagramSizeChangedEvent),
+    /// See [`MemoryBudgetExceededEvent`]
+    MemoryBudgetExceeded(MemoryBudgetExceededEvent),
+    /// See [`CertInteractionEvent`]
+    CertInteraction(CertInteractionEvent),
+    /// See [`CertTrustUpdateEvent`]
+    CertTrustUpdate(CertTrustUpdateEvent),
+    /// See [`CertConnectionAbortEvent`]
+    CertConnectionAbort(CertConnectionAbortEvent),
+}
+
+/// Writers of the events of the connection state, see [`update_sync_client`]
+#[derive(SystemParam)]
+pub struct ConnectionEventWriters<'w> {
+    connection: EventWriter<'w, ConnectionEvent>,
+    connection_failed: EventWriter<'w, ConnectionFailedEvent>,
+    connection_lost: EventWriter<'w, ConnectionLostEvent>,
+    connection_transfer: EventWriter<'w, ConnectionTransferEvent>,
+    connection_race: EventWriter<'w, ConnectionRaceEvent>,
+    connection_close_stage: EventWriter<'w, ConnectionCloseStageEvent>,
+    protocol_mismatch: EventWriter<'w, ProtocolMismatchEvent>,
+}
+
+/// Writers of the events of the certificate verification, see [`update_sync_client`]
+#[derive(SystemParam)]
+pub struct CertificateEventWriters<'w> {
+    interaction: EventWriter<'w, CertInteractionEvent>,
+    trust_update: EventWriter<'w, CertTrustUpdateEvent>,
+    connection_abort: EventWriter<'w, CertConnectionAbortEvent>,
+}
+
+/// Writers of the events of the delivery of the tracked messages, see [`update_sync_client`]
+#[derive(SystemParam)]
+pub struct DeliveryEventWriters<'w> {
+    message_acked: EventWriter<'w, MessageAckedEvent>,
+    message_lost: EventWriter<'w, MessageLostEvent>,
+    channel_resumed: EventWriter<'w, ChannelResumedEvent>,
+    channel_error: EventWriter<'w, ChannelErrorEvent>,
+    channel_unresponsive: EventWriter<'w, ChannelUnresponsiveEvent>,
+    channel_rejected: EventWriter<'w, ChannelRejectedEvent>,
+    congestion: EventWriter<'w, CongestionEvent>,
+    max_datagram_size_changed: EventWriter<'w, MaxDatagramSizeChangedEvent>,
+    memory_budget_exceeded: EventWriter<'w, MemoryBudgetExceededEvent>,
+}
+
+/// Receive messages from the async client tasks and update the sync client.
+///
+/// This system generates the client's bevy events
+pub fn update_sync_client(
+    mut connection_events: ConnectionEventWriters,
+    mut certificate_events: CertificateEventWriters,
+    mut delivery_events: DeliveryEventWriters,
+    mut client: ResMut<QuinnetClient>,
+) {
+    for event in client.pump() {
+        match event {
+            QuinnetClientEvent::Connection(event) => {
+                connection_events.connection.write(event);
+            }
+            QuinnetClientEvent::ConnectionFailed(event) => {
+                connection_events.connection_failed.write(event);
+            }
+            QuinnetClientEvent::ConnectionLost(event) => {
+                connection_events.connection_lost.write(event);
+            }
+            QuinnetClientEvent::ConnectionTransfer(event) => {
+                connection_events.connection_transfer.write(event);
+            }
+            QuinnetClientEvent::ProtocolMismatch(event) => {
+                connection_events.protocol_mismatch.write(event);
+            }
+            QuinnetClientEvent::ConnectionRace(event) => {
+                connection_events.connection_race.write(event);
+            }
+            QuinnetClientEvent::ConnectionCloseStage(event) => {
+                connection_events.connection_close_stage.write(event);
+            }
+            QuinnetClientEvent::MessageAcked(event) => {
+                delivery_events.message_acked.write(event);
+            }
+            QuinnetClientEvent::MessageLost(event) => {
+                delivery_events.message_lost.write(event);
+            }
+            QuinnetClientEvent::ChannelResumed(event) => {
+                delivery_events.channel_resumed.write(event);
+            }
+            QuinnetClientEvent::ChannelUnresponsive(event) => {
+                delivery_events.channel_unresponsive.write(event);
+            }
+            QuinnetClientEvent::ChannelRejected(event) => {
+                delivery_events.channel_rejected.write(event);
+            }
+            QuinnetClientEvent::ChannelError(event) => {
+                delivery_events.channel_error.write(event);
+            }
+            QuinnetClientEvent::Congestion(event) => {
+                delivery_events.congestion.write(event);
+            }
+            QuinnetClientEvent::MaxDatagramSizeChanged(event) => {
+                delivery_events.max_datagram_size_changed.write(event);
+            }
+            QuinnetClientEvent::MemoryBudgetExceeded(event) => {
+                delivery_events.memory_budget_exceeded.write(event);
+            }
+            QuinnetClientEvent::CertInteraction(event) => {
+                certificate_events.interaction.write(event);
+            }
+            QuinnetClientEvent::CertTrustUpdate(event) => {
+                certificate_events.trust_update.write(event);
+            }
+            QuinnetClientEvent::CertConnectionAbort(event) => {
+                certificate_events.connection_abort.write(event);
             }
         }
     }
 }
 
+/// Sends the messages held by the client connections, see [`ClientSideConnection::set_deferred_flush`]
+pub fn flush_sync_client(client: Res<QuinnetClient>) {
+    for connection in client.connections.values() {
+        connection.flush();
+    }
+}
+
+/// Closes all the connections of the client when the app exits, so that the server is notified before the [`AsyncRuntime`] is dropped. Runs in the [`QuinnetShutdown`] set.
+pub fn shutdown_sync_client(mut client: ResMut<QuinnetClient>) {
+    client.close_all_connections();
+}
+
+/// Updates the [`NetworkTick`] resource with the last tick received from the server by the default connection, see [`ClientSideConnection::server_tick`].
+///
+/// In an app also running a [`crate::server::QuinnetServer`], the resource is left to the server.
+pub fn update_network_tick(
+    mut commands: Commands,
+    client: Res<QuinnetClient>,
+    tick: Option<ResMut<NetworkTick>>,
+    #[cfg(feature = "server")] server: Option<Res<crate::server::QuinnetServer>>,
+) {
+    #[cfg(feature = "server")]
+    if server.is_some() {
+        return;
+    }
+    let Some(server_tick) = client
+        .get_connection()
+        .and_then(ClientSideConnection::server_tick)
+    else {
+        return;
+    };
+    match tick {
+        Some(mut tick) => {
+            tick.set_if_neq(server_tick);
+        }
+        None => commands.insert_resource(server_tick),
+    }
+}
+
 /// Quinnet Server's plugin
 ///
 /// It is possbile to add both this plugin and the [`crate::server::QuinnetServerPlugin`]
@@ -380,34 +902,92 @@ pub struct QuinnetClientPlugin {
     /// Client systems are scheduled to only run if the `Client` resource exists.
     /// A Bevy command to create the resource `commands.init_resource::<Client>();` can be done later on, when needed.
     pub initialize_later: bool,
+    /// Schedule of the [`QuinnetSyncUpdate`] set, where the client receives from its async back-end and raises its events. `PreUpdate` by default, `FixedPreUpdate` aligns message intake with the fixed tick.
+    pub sync_schedule: InternedScheduleLabel,
+    /// Schedule of the [`QuinnetFlush`] set, where the deferred messages are sent. `PostUpdate` by default.
+    pub flush_schedule: InternedScheduleLabel,
+    /// Enables the deferred flush of the client initialized by the plugin, see [`ClientSideConnection::set_deferred_flush`]
+    pub deferred_flush: bool,
 }
 
 impl Default for QuinnetClientPlugin {
     fn default() -> Self {
         Self {
             initialize_later: false,
+            sync_schedule: PreUpdate.intern(),
+            flush_schedule: PostUpdate.intern(),
+            deferred_flush: false,
         }
     }
 }
 
+impl QuinnetClientPlugin {
+    /// Runs the [`QuinnetSyncUpdate`] set in `schedule`
+    pub fn with_sync_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.sync_schedule = schedule.intern();
+        self
+    }
+
+    /// Runs the [`QuinnetFlush`] set in `schedule`
+    pub fn with_flush_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.flush_schedule = schedule.intern();
+        self
+    }
+
+    /// Holds the messages sent by the client until the [`QuinnetFlush`] set runs
+    pub fn with_deferred_flush(mut self) -> Self {
+        self.deferred_flush = true;
+        self
+    }
+}
+
 impl Plugin for QuinnetClientPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<ConnectionEvent>()
             .add_event::<ConnectionFailedEvent>()
             .add_event::<ConnectionLostEvent>()
+            .add_event::<ConnectionTransferEvent>()
+            .add_event::<ProtocolMismatchEvent>()
+            .add_event::<ConnectionRaceEvent>()
+            .add_event::<ConnectionCloseStageEvent>()
+            .add_event::<MessageAckedEvent>()
+            .add_event::<MessageLostEvent>()
+            .add_event::<ChannelResumedEvent>()
+            .add_event::<ChannelErrorEvent>()
+            .add_event::<ChannelUnresponsiveEvent>()
+            .add_event::<ChannelRejectedEvent>()
+            .add_event::<CongestionEvent>()
+            .add_event::<MaxDatagramSizeChangedEvent>()
+            .add_event::<MemoryBudgetExceededEvent>()
             .add_event::<CertInteractionEvent>()
             .add_event::<CertTrustUpdateEvent>()
             .add_event::<CertConnectionAbortEvent>();
 
         if !self.initialize_later {
             app.init_resource::<QuinnetClient>();
+            app.world_mut()
+                .resource_mut::<QuinnetClient>()
+                .set_deferred_flush(self.deferred_flush);
         }
 
         app.add_systems(
-            PreUpdate,
-            update_sync_client
+            self.sync_schedule,
+            (update_sync_client, update_network_tick)
+                .chain()
                 .in_set(QuinnetSyncUpdate)
                 .run_if(resource_exists::<QuinnetClient>),
+        )
+        .add_systems(
+            self.flush_schedule,
+            flush_sync_client
+                .in_set(QuinnetFlush)
+                .run_if(resource_exists::<QuinnetClient>),
+        )
+        .add_systems(
+            Last,
+            shutdown_sync_client
+                .in_set(QuinnetShutdown)
+                .run_if(resource_exists::<QuinnetClient>.and(on_event::<AppExit>)),
         );
     }
 }
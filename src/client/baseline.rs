@@ -0,0 +1,152 @@
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+use bytes::Bytes;
+
+use crate::shared::{baseline::BaselineMessage, channels::ChannelId, QuinnetSyncUpdate};
+
+use super::{connection::ClientSideConnection, ConnectionClosed, QuinnetClient};
+
+/// Raised on the client for each update receiving chunks of its baseline. Raised in the CoreStage::PreUpdate stage by the [`QuinnetBaselineReceiverPlugin`].
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaselineReceiveProgressEvent {
+    /// Number of bytes of the baseline received
+    pub received: usize,
+    /// Size of the baseline, in bytes
+    pub total: usize,
+}
+
+/// Raised on the client when its whole baseline was received, before any live update. Raised in the CoreStage::PreUpdate stage by the [`QuinnetBaselineReceiverPlugin`].
+#[derive(Event, Debug, Clone)]
+pub struct BaselineReceivedEvent {
+    /// The baseline
+    pub baseline: Bytes,
+}
+
+/// Client side of a [`crate::server::baseline::BaselineSync`]: reassembles the chunks of the baseline, and answers the server that the client is ready once the baseline is complete, after which the server sends the live updates.
+#[derive(Resource, Debug)]
+pub struct BaselineReceiver {
+    channel_id: ChannelId,
+    buffer: Vec<u8>,
+    total: Option<usize>,
+}
+
+impl BaselineReceiver {
+    /// Receiver of the baseline sent on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            buffer: Vec::new(),
+            total: None,
+        }
+    }
+
+    /// Channel the baseline is received on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Returns the number of bytes of the baseline received and the size of the baseline, `None` if no baseline is being received
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        self.total.map(|total| (self.buffer.len(), total))
+    }
+
+    /// Receives the chunks of the baseline. Returns the baseline once complete, and answers the server that the client is ready. A new baseline sent by the server restarts the reception.
+    ///
+    /// Messages that can't be deserialized are dropped. Will return an [`Err`] if the connection is closed.
+    pub fn receive(
+        &mut self,
+        connection: &mut ClientSideConnection,
+    ) -> Result<Option<Bytes>, ConnectionClosed> {
+        let mut baseline = None;
+        for payload in connection.receive_all_on(self.channel_id)? {
+            let Ok(BaselineMessage::Chunk { total, data }) = bincode::deserialize(&payload) else {
+                continue;
+            };
+            let total = total as usize;
+            if self.total != Some(total) {
+                self.buffer.clear();
+                self.total = Some(total);
+            }
+            self.buffer.extend_from_slice(&data);
+            if self.buffer.len() >= total {
+                self.total = None;
+                baseline = Some(Bytes::from(std::mem::take(&mut self.buffer)));
+                connection.try_send_message_on(self.channel_id, BaselineMessage::Ready);
+            }
+        }
+        Ok(baseline)
+    }
+
+    /// Drops the chunks received, such as when reconnecting
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.total = None;
+    }
+}
+
+/// Receives the baseline of the default connection with a [`BaselineReceiver`] resource, and raises a [`BaselineReceiveProgressEvent`] for each update receiving chunks and a [`BaselineReceivedEvent`] once it is complete.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetBaselineReceiverPlugin {
+    channel_id: ChannelId,
+    schedule: InternedScheduleLabel,
+}
+
+impl QuinnetBaselineReceiverPlugin {
+    /// Plugin receiving the baseline on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            schedule: PreUpdate.intern(),
+        }
+    }
+
+    /// Receives the baseline in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetBaselineReceiverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BaselineReceiveProgressEvent>()
+            .add_event::<BaselineReceivedEvent>()
+            .insert_resource(BaselineReceiver::new(self.channel_id))
+            .add_systems(
+                self.schedule,
+                receive_baseline
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetClient>),
+            );
+    }
+}
+
+fn receive_baseline(
+    mut client: ResMut<QuinnetClient>,
+    mut receiver: ResMut<BaselineReceiver>,
+    mut progress_events: EventWriter<BaselineReceiveProgressEvent>,
+    mut received_events: EventWriter<BaselineReceivedEvent>,
+) {
+    let Some(connection) = client.get_connection_mut() else {
+        return;
+    };
+    let before = receiver.progress();
+    let Ok(baseline) = receiver.receive(connection) else {
+        return;
+    };
+    let progress = match &baseline {
+        Some(baseline) => Some((baseline.len(), baseline.len())),
+        None => receiver
+            .progress()
+            .filter(|progress| Some(*progress) != before),
+    };
+    if let Some((received, total)) = progress {
+        progress_events.write(BaselineReceiveProgressEvent { received, total });
+    }
+    if let Some(baseline) = baseline {
+        received_events.write(BaselineReceivedEvent { baseline });
+    }
+}
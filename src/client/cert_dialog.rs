@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use tokio::sync::oneshot;
+
+use super::{
+    certificate::{
+        CertInteractionEvent, CertVerificationInfo, CertVerificationStatus, CertVerifierAction,
+    },
+    ConnectionLocalId,
+};
+
+/// Answers the [`CertInteractionEvent`] of the client with a dialog: the name of the server, the fingerprint of its certificate and the previously known one if any, and a button for each [`CertVerifierAction`].
+///
+/// The interactions are shown one at a time, in the order they are raised. The dialog takes over the interactions still waiting for an action when it reads them in [`Update`], they can then only be answered through the dialog or [`CertDialogs::answer`], see [`CertInteractionEvent::apply_cert_verifier_action`]. The dialog is drawn by `bevy_ui`: the app needs a camera.
+#[derive(Debug, Default, Clone)]
+pub struct QuinnetCertDialogPlugin;
+
+/// Certificate interaction waiting for the action of the user
+#[derive(Debug)]
+pub struct PendingCertInteraction {
+    /// Local id of the concerned connection
+    pub connection_id: ConnectionLocalId,
+    /// The current status of the verification
+    pub status: CertVerificationStatus,
+    /// Server & Certificate info
+    pub info: CertVerificationInfo,
+    action_sender: oneshot::Sender<CertVerifierAction>,
+}
+
+/// Certificate interactions taken over by the [`QuinnetCertDialogPlugin`]
+#[derive(Resource, Debug, Default)]
+pub struct CertDialogs {
+    pending: VecDeque<PendingCertInteraction>,
+    dialog: Option<Entity>,
+    outdated: bool,
+}
+
+impl CertDialogs {
+    /// Interactions waiting for the action of the user, the first one is the one displayed
+    pub fn pending(&self) -> impl Iterator<Item = &PendingCertInteraction> {
+        self.pending.iter()
+    }
+
+    /// Answers the displayed interaction with `action`, as if the user pressed the matching button
+    pub fn answer(&mut self, action: CertVerifierAction) {
+        if let Some(interaction) = self.pending.pop_front() {
+            // The connection may have been closed in the meantime, nothing is waiting for the action anymore
+            let _ = interaction.action_sender.send(action);
+            self.outdated = true;
+        }
+    }
+}
+
+/// Marker of the root entity of the dialog
+#[derive(Component)]
+struct CertDialog;
+
+/// Action applied by a button of the dialog
+#[derive(Component)]
+struct CertDialogButton(CertVerifierAction);
+
+impl Plugin for QuinnetCertDialogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CertDialogs>().add_systems(
+            Update,
+            (
+                take_over_cert_interactions,
+                handle_cert_dialog_buttons,
+                update_cert_dialog,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn take_over_cert_interactions(
+    mut cert_interactions: EventReader<CertInteractionEvent>,
+    mut dialogs: ResMut<CertDialogs>,
+) {
+    for interaction in cert_interactions.read() {
+        let Some(action_sender) = interaction.take_action_sender() else {
+            continue;
+        };
+        dialogs.pending.push_back(PendingCertInteraction {
+            connection_id: interaction.connection_id,
+            status: interaction.status.clone(),
+            info: interaction.info.clone(),
+            action_sender,
+        });
+    }
+}
+
+fn handle_cert_dialog_buttons(
+    buttons: Query<(&Interaction, &CertDialogButton), Changed<Interaction>>,
+    mut dialogs: ResMut<CertDialogs>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            dialogs.answer(button.0.clone());
+            // Only one answer per displayed dialog
+            return;
+        }
+    }
+}
+
+fn update_cert_dialog(mut commands: Commands, mut dialogs: ResMut<CertDialogs>) {
+    // Interactions of the connections closed meanwhile do not need an answer anymore
+    let pending_count = dialogs.pending.len();
+    dialogs
+        .pending
+        .retain(|interaction| !interaction.action_sender.is_closed());
+    if dialogs.pending.len() != pending_count {
+        dialogs.outdated = true;
+    }
+
+    if dialogs.dialog.is_some() && !dialogs.outdated {
+        return;
+    }
+    dialogs.outdated = false;
+    if let Some(dialog) = dialogs.dialog.take() {
+        commands.entity(dialog).despawn();
+    }
+    if let Some(interaction) = dialogs.pending.front() {
+        let dialog = spawn_cert_dialog(&mut commands, interaction);
+        dialogs.dialog = Some(dialog);
+    }
+}
+
+fn cert_dialog_message(interaction: &PendingCertInteraction) -> String {
+    let info = &interaction.info;
+    let mut message = match interaction.status {
+        CertVerificationStatus::UnknownCertificate => format!(
+            "First connection to {}:{}, its certificate is not known yet.\n",
+            info.server_name, info.port
+        ),
+        CertVerificationStatus::UntrustedCertificate => format!(
+            "The certificate of {}:{} changed since the last connection!\nSomeone may be impersonating the server.\n",
+            info.server_name, info.port
+        ),
+        CertVerificationStatus::TrustedCertificate => format!(
+            "The certificate of {}:{} is known and trusted.\n",
+            info.server_name, info.port
+        ),
+    };
+    message.push_str(&format!("\nFingerprint: {}", info.fingerprint));
+    if let Some(known_fingerprint) = &info.known_fingerprint {
+        message.push_str(&format!("\nKnown fingerprint: {}", known_fingerprint));
+    }
+    message
+}
+
+fn spawn_cert_dialog(commands: &mut Commands, interaction: &PendingCertInteraction) -> Entity {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.5)),
+            GlobalZIndex(i32::MAX),
+            CertDialog,
+        ))
+        .with_children(|overlay| {
+            overlay
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(12.),
+                        padding: UiRect::all(Val::Px(16.)),
+                        max_width: Val::Percent(80.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+                ))
+                .with_children(|dialog| {
+                    dialog.spawn((
+                        Text::new("Server certificate"),
+                        TextFont {
+                            font_size: 20.,
+                            ..default()
+                        },
+                    ));
+                    dialog.spawn((
+                        Text::new(cert_dialog_message(interaction)),
+                        TextFont {
+                            font_size: 14.,
+                            ..default()
+                        },
+                    ));
+                    dialog
+                        .spawn(Node {
+                            column_gap: Val::Px(8.),
+                            justify_content: JustifyContent::End,
+                            ..default()
+                        })
+                        .with_children(|buttons| {
+                            for (label, action) in [
+                                ("Abort", CertVerifierAction::AbortConnection),
+                                ("Trust once", CertVerifierAction::TrustOnce),
+                                ("Trust and remember", CertVerifierAction::TrustAndStore),
+                            ] {
+                                buttons
+                                    .spawn((
+                                        Button,
+                                        Node {
+                                            padding: UiRect::axes(Val::Px(10.), Val::Px(6.)),
+                                            ..default()
+                                        },
+                                        BackgroundColor(Color::srgb(0.3, 0.3, 0.3)),
+                                        CertDialogButton(action),
+                                    ))
+                                    .with_children(|button| {
+                                        button.spawn((
+                                            Text::new(label),
+                                            TextFont {
+                                                font_size: 14.,
+                                                ..default()
+                                            },
+                                        ));
+                                    });
+                            }
+                        });
+                });
+        })
+        .id()
+}
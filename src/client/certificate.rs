@@ -1,23 +1,28 @@
 use std::{
     collections::HashMap,
-    error::Error,
     fmt,
     fs::File,
     io::{BufRead, BufReader, Write},
     path::Path,
+    str::FromStr,
     sync::{Arc, Mutex},
 };
 
 use bevy::{log::warn, prelude::Event};
 use futures::executor::block_on;
-use rustls::pki_types::{CertificateDer, ServerName as RustlsServerName, UnixTime};
+use rustls::pki_types::{
+    CertificateDer, InvalidDnsNameError, PrivateKeyDer, ServerName as RustlsServerName, UnixTime,
+};
 use tokio::sync::{mpsc, oneshot};
 
 use crate::shared::{certificate::CertificateFingerprint, error::AsyncChannelError};
 
+mod encryption;
+pub use encryption::*;
+
 use super::{
     CertificateInteractionError, ClientAsyncMessage, ConnectionLocalId, InvalidHostFile,
-    DEFAULT_KNOWN_HOSTS_FILE,
+    KnownHostsError, DEFAULT_KNOWN_HOSTS_FILE,
 };
 
 /// Default certificate behavior is to abort the connection
@@ -53,6 +58,12 @@ impl CertInteractionEvent {
             Err(CertificateInteractionError::CertificateActionAlreadyApplied)
         }
     }
+
+    /// Takes the sender of the action, to answer the interaction after the event is dropped. `None` if an action was already applied.
+    #[cfg(feature = "cert-dialog")]
+    pub(crate) fn take_action_sender(&self) -> Option<oneshot::Sender<CertVerifierAction>> {
+        self.action_sender.lock().ok()?.take()
+    }
 }
 
 /// Event raised when a new certificate is trusted
@@ -84,6 +95,43 @@ pub enum CertificateVerificationMode {
     SignedByCertificateAuthority,
     /// The client will use a Trust on first authentication scheme (<https://en.wikipedia.org/wiki/Trust_on_first_use>) configured by a [`TrustOnFirstUseConfig`].
     TrustOnFirstUse(TrustOnFirstUseConfig),
+    /// Client will only trust a server certificate signed by one of these authorities, such as the private authority of a backend
+    SignedBy(Vec<CertificateDer<'static>>),
+}
+
+/// Certificate presented by a client to the servers authenticating their clients, see [`crate::client::connection::ClientEndpointConfigurationBuilder::with_client_certificate`]
+pub struct ClientCertificate {
+    /// The client's certificate chain.
+    pub cert_chain: Vec<CertificateDer<'static>>,
+    /// The client's private key.
+    pub priv_key: PrivateKeyDer<'static>,
+}
+
+impl ClientCertificate {
+    /// Client certificate from its chain, the certificate of the client first, and its private key
+    pub fn new(cert_chain: Vec<CertificateDer<'static>>, priv_key: PrivateKeyDer<'static>) -> Self {
+        Self {
+            cert_chain,
+            priv_key,
+        }
+    }
+}
+
+impl Clone for ClientCertificate {
+    fn clone(&self) -> Self {
+        Self {
+            cert_chain: self.cert_chain.clone(),
+            priv_key: self.priv_key.clone_key(),
+        }
+    }
+}
+
+impl fmt::Debug for ClientCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientCertificate")
+            .field("cert_chain", &self.cert_chain)
+            .finish_non_exhaustive()
+    }
 }
 
 /// Configuration of the Trust on first authentication scheme for server certificates
@@ -105,31 +153,201 @@ pub struct TrustOnFirstUseConfig {
     pub known_hosts: KnownHosts,
     /// verifier_behaviour stores the [`CertVerifierBehaviour`] that the certificate verifier will adopt for each possible [`CertVerificationStatus`]
     pub verifier_behaviour: HashMap<CertVerificationStatus, CertVerifierBehaviour>,
+    /// Overrides of `verifier_behaviour` for specific servers. Statuses missing from an override use `verifier_behaviour`.
+    pub server_behaviours:
+        HashMap<ServerName, HashMap<CertVerificationStatus, CertVerifierBehaviour>>,
+    /// If present, answers the [`CertVerifierBehaviour::RequestClientAction`] requests instead of raising a [`CertInteractionEvent`]
+    pub interaction_resolver: Option<CertInteractionResolver>,
 }
 
 impl Default for TrustOnFirstUseConfig {
-    /// Returns the default [`TrustOnFirstUseConfig`]
+    /// Returns the default [`TrustOnFirstUseConfig`], following the default [`TofuPolicy`]
     fn default() -> Self {
         TrustOnFirstUseConfig {
             known_hosts: KnownHosts::HostsFile(DEFAULT_KNOWN_HOSTS_FILE.to_string()),
+            verifier_behaviour: TofuPolicy::default().verifier_behaviour(),
+            server_behaviours: HashMap::new(),
+            interaction_resolver: None,
+        }
+    }
+}
+
+impl TrustOnFirstUseConfig {
+    /// Only trusts the certificate with `fingerprint` for `known_host`, such as the fingerprint of an [`crate::shared::invite::InviteCode`]. Any other certificate aborts the connection, and nothing is stored.
+    pub fn pinned(known_host: KnownHost, fingerprint: CertificateFingerprint) -> Self {
+        let abort = CertVerifierBehaviour::ImmediateAction(CertVerifierAction::AbortConnection);
+        TrustOnFirstUseConfig {
+            known_hosts: KnownHosts::Store(CertStore::from([(known_host, fingerprint)])),
             verifier_behaviour: HashMap::from([
-                (
-                    CertVerificationStatus::UnknownCertificate,
-                    CertVerifierBehaviour::ImmediateAction(CertVerifierAction::TrustAndStore),
-                ),
-                (
-                    CertVerificationStatus::UntrustedCertificate,
-                    CertVerifierBehaviour::RequestClientAction,
-                ),
+                (CertVerificationStatus::UnknownCertificate, abort.clone()),
+                (CertVerificationStatus::UntrustedCertificate, abort),
                 (
                     CertVerificationStatus::TrustedCertificate,
                     CertVerifierBehaviour::ImmediateAction(CertVerifierAction::TrustOnce),
                 ),
             ]),
+            server_behaviours: HashMap::new(),
+            interaction_resolver: None,
+        }
+    }
+
+    /// Sets the verifier behaviours of all the servers from a [`TofuPolicy`]
+    pub fn with_policy(mut self, policy: TofuPolicy) -> Self {
+        self.verifier_behaviour = policy.verifier_behaviour();
+        self
+    }
+
+    /// Sets the verifier behaviours of a specific server from a [`TofuPolicy`], overriding the behaviours used for the other servers
+    pub fn with_server_policy(mut self, server_name: ServerName, policy: TofuPolicy) -> Self {
+        self.server_behaviours
+            .insert(server_name, policy.verifier_behaviour());
+        self
+    }
+
+    /// Sets a [`CertInteractionResolver`] calling `resolver`
+    pub fn with_interaction_resolver(
+        mut self,
+        resolver: impl Fn(&CertVerificationStatus, &CertVerificationInfo) -> CertVerifierAction
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.interaction_resolver = Some(CertInteractionResolver::new(resolver));
+        self
+    }
+}
+
+/// Synchronous answer to the certificate interactions, for headless clients (bots, tests, dedicated tools) without any user to ask.
+///
+/// Called by the certificate verifier, from the async runtime, whenever the verifier behaviour is [`CertVerifierBehaviour::RequestClientAction`]. The returned action is applied immediately and no [`CertInteractionEvent`] is raised.
+///
+/// # Example
+///
+/// ```
+/// use bevy_quinnet::client::certificate::{
+///     CertVerificationStatus, CertVerifierAction, TrustOnFirstUseConfig,
+/// };
+/// let config = TrustOnFirstUseConfig::default().with_interaction_resolver(|status, _info| {
+///     match status {
+///         CertVerificationStatus::UntrustedCertificate => CertVerifierAction::AbortConnection,
+///         _ => CertVerifierAction::TrustAndStore,
+///     }
+/// });
+/// ```
+#[derive(Clone)]
+pub struct CertInteractionResolver(Arc<ResolverFn>);
+
+type ResolverFn =
+    dyn Fn(&CertVerificationStatus, &CertVerificationInfo) -> CertVerifierAction + Send + Sync;
+
+impl CertInteractionResolver {
+    /// Wraps a resolver function
+    pub fn new(
+        resolver: impl Fn(&CertVerificationStatus, &CertVerificationInfo) -> CertVerifierAction
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self(Arc::new(resolver))
+    }
+
+    /// Returns the action to apply for a certificate
+    pub fn resolve(
+        &self,
+        status: &CertVerificationStatus,
+        info: &CertVerificationInfo,
+    ) -> CertVerifierAction {
+        (self.0)(status, info)
+    }
+}
+
+impl fmt::Debug for CertInteractionResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CertInteractionResolver").finish()
+    }
+}
+
+/// How the client handles a server it connects to for the first time
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FirstUsePolicy {
+    /// Trust the certificate, store its fingerprint and continue the connection
+    AutoTrust,
+    /// Raise a [`CertInteractionEvent`] and wait for an action
+    Ask,
+}
+
+/// How the client handles a known server whose certificate does not match the stored fingerprint
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FingerprintMismatchPolicy {
+    /// Abort the connection
+    Abort,
+    /// Raise a [`CertInteractionEvent`] and wait for an action
+    Ask,
+    /// Trust the new certificate and replace the stored fingerprint
+    Replace,
+}
+
+/// Policy of the Trust on first use scheme, a shorthand for the verifier behaviours of a [`TrustOnFirstUseConfig`].
+///
+/// Certificates matching their stored fingerprint are always trusted.
+///
+/// # Example
+///
+/// ```
+/// use bevy_quinnet::client::certificate::{
+///     FingerprintMismatchPolicy, FirstUsePolicy, TofuPolicy, TrustOnFirstUseConfig,
+/// };
+/// let config = TrustOnFirstUseConfig::default().with_policy(TofuPolicy {
+///     first_use: FirstUsePolicy::Ask,
+///     on_mismatch: FingerprintMismatchPolicy::Abort,
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TofuPolicy {
+    /// Behaviour for a [`CertVerificationStatus::UnknownCertificate`]
+    pub first_use: FirstUsePolicy,
+    /// Behaviour for a [`CertVerificationStatus::UntrustedCertificate`]
+    pub on_mismatch: FingerprintMismatchPolicy,
+}
+
+impl Default for TofuPolicy {
+    fn default() -> Self {
+        Self {
+            first_use: FirstUsePolicy::AutoTrust,
+            on_mismatch: FingerprintMismatchPolicy::Ask,
         }
     }
 }
 
+impl TofuPolicy {
+    /// The [`CertVerifierBehaviour`] to adopt for each possible [`CertVerificationStatus`]
+    pub fn verifier_behaviour(&self) -> HashMap<CertVerificationStatus, CertVerifierBehaviour> {
+        let unknown = match self.first_use {
+            FirstUsePolicy::AutoTrust => {
+                CertVerifierBehaviour::ImmediateAction(CertVerifierAction::TrustAndStore)
+            }
+            FirstUsePolicy::Ask => CertVerifierBehaviour::RequestClientAction,
+        };
+        let untrusted = match self.on_mismatch {
+            FingerprintMismatchPolicy::Abort => {
+                CertVerifierBehaviour::ImmediateAction(CertVerifierAction::AbortConnection)
+            }
+            FingerprintMismatchPolicy::Ask => CertVerifierBehaviour::RequestClientAction,
+            FingerprintMismatchPolicy::Replace => {
+                CertVerifierBehaviour::ImmediateAction(CertVerifierAction::TrustAndStore)
+            }
+        };
+        HashMap::from([
+            (CertVerificationStatus::UnknownCertificate, unknown),
+            (CertVerificationStatus::UntrustedCertificate, untrusted),
+            (
+                CertVerificationStatus::TrustedCertificate,
+                CertVerifierBehaviour::ImmediateAction(CertVerifierAction::TrustOnce),
+            ),
+        ])
+    }
+}
+
 /// Status of the server's certificate verification.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum CertVerificationStatus {
@@ -146,6 +364,8 @@ pub enum CertVerificationStatus {
 pub struct CertVerificationInfo {
     /// Name of the server
     pub server_name: ServerName,
+    /// Port of the server
+    pub port: u16,
     /// Fingerprint of the received certificate
     pub fingerprint: CertificateFingerprint,
     /// If any, previously knwon fingerprint for this server
@@ -156,6 +376,22 @@ pub struct CertVerificationInfo {
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct ServerName(RustlsServerName<'static>);
 
+impl CertVerificationInfo {
+    /// Key of the server in a [`CertStore`]
+    pub fn known_host(&self) -> KnownHost {
+        KnownHost::new(self.server_name.clone(), self.port)
+    }
+}
+
+impl TryFrom<&str> for ServerName {
+    type Error = InvalidDnsNameError;
+
+    /// Parses a dns name or an ip address
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        Ok(ServerName(RustlsServerName::try_from(name)?.to_owned()))
+    }
+}
+
 impl fmt::Display for ServerName {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -184,101 +420,231 @@ pub enum CertVerifierAction {
     TrustAndStore,
 }
 
+/// Identity of a server in a [`CertStore`]: its name and port.
+///
+/// Entries without a port match the server on any port, such as the entries of known hosts files written by older versions.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct KnownHost {
+    /// Name of the server
+    pub server_name: ServerName,
+    /// Port of the server, `None` to match any port
+    pub port: Option<u16>,
+}
+
+impl KnownHost {
+    /// Server `server_name` on `port`
+    pub fn new(server_name: ServerName, port: u16) -> Self {
+        Self {
+            server_name,
+            port: Some(port),
+        }
+    }
+
+    /// Server `server_name` on any port
+    pub fn any_port(server_name: ServerName) -> Self {
+        Self {
+            server_name,
+            port: None,
+        }
+    }
+}
+
+impl fmt::Display for KnownHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "[{}]:{}", self.server_name, port),
+            None => write!(f, "{}", self.server_name),
+        }
+    }
+}
+
+impl FromStr for KnownHost {
+    type Err = InvalidHostFile;
+
+    /// Parses `[server_name]:port` or `server_name`
+    fn from_str(host: &str) -> Result<Self, Self::Err> {
+        match host.strip_prefix('[') {
+            Some(host) => {
+                let (server_name, port) = host.split_once("]:").ok_or(InvalidHostFile)?;
+                Ok(KnownHost::new(
+                    ServerName::try_from(server_name).map_err(|_| InvalidHostFile)?,
+                    port.parse().map_err(|_| InvalidHostFile)?,
+                ))
+            }
+            None => Ok(KnownHost::any_port(
+                ServerName::try_from(host).map_err(|_| InvalidHostFile)?,
+            )),
+        }
+    }
+}
+
 /// Certificate fingerprint storage
-pub type CertStore = HashMap<ServerName, CertificateFingerprint>;
+pub type CertStore = HashMap<KnownHost, CertificateFingerprint>;
+
+/// Fingerprint known for a server on a port, falling back to the entry of the server for any port
+fn lookup_fingerprint<'a>(
+    store: &'a CertStore,
+    server_name: &ServerName,
+    port: u16,
+) -> Option<&'a CertificateFingerprint> {
+    store
+        .get(&KnownHost::new(server_name.clone(), port))
+        .or_else(|| store.get(&KnownHost::any_port(server_name.clone())))
+}
 
-/// Certificate fingerprint storage as a value or as a file
+/// Known hosts file, to list, remove and export the trusted server certificates (e.g. from a server browser UI), the way an SSH known_hosts manager does.
+///
+/// Changes are only written to the file by [`KnownHostsStore::save`]. Connections read the file when they are opened, see [`KnownHosts::HostsFile`] and [`KnownHosts::EncryptedHostsFile`].
 #[derive(Debug, Clone)]
-pub enum KnownHosts {
-    /// Directly contains the server name to fingerprint mapping
-    Store(CertStore),
-    /// Path of a file caontaing the server name to fingerprint mapping.
-    HostsFile(String),
+pub struct KnownHostsStore {
+    file: HostsFile,
+    entries: CertStore,
 }
 
-/// Implementation of `ServerCertVerifier` that verifies everything as trustworthy.
-#[derive(Debug)]
-pub(crate) struct SkipServerVerification(Arc<rustls::crypto::CryptoProvider>);
+impl KnownHostsStore {
+    /// Loads the known hosts file, the store is empty if the file does not exist
+    pub fn open(file: impl Into<String>) -> Result<Self, KnownHostsError> {
+        Self::open_file(HostsFile {
+            path: file.into(),
+            cipher: None,
+        })
+    }
 
-impl SkipServerVerification {
-    pub(crate) fn new() -> Arc<Self> {
-        Arc::new(Self(Arc::new(rustls::crypto::ring::default_provider())))
+    /// Same as [`KnownHostsStore::open`] for a known hosts file encrypted with `cipher`
+    pub fn open_encrypted(
+        file: impl Into<String>,
+        cipher: Arc<dyn KnownHostsCipher>,
+    ) -> Result<Self, KnownHostsError> {
+        Self::open_file(HostsFile {
+            path: file.into(),
+            cipher: Some(cipher),
+        })
     }
-}
 
-impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &CertificateDer<'_>,
-        _intermediates: &[CertificateDer<'_>],
-        _server_name: &RustlsServerName<'_>,
-        _ocsp: &[u8],
-        _now: UnixTime,
-    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
-        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    fn open_file(file: HostsFile) -> Result<Self, KnownHostsError> {
+        let entries = match Path::new(&file.path).exists() {
+            true => load_known_hosts_from_file(&file)?,
+            false => CertStore::new(),
+        };
+        Ok(Self { file, entries })
     }
 
-    fn verify_tls12_signature(
-        &self,
-        message: &[u8],
-        cert: &CertificateDer<'_>,
-        dss: &rustls::DigitallySignedStruct,
-    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        rustls::crypto::verify_tls12_signature(
-            message,
-            cert,
-            dss,
-            &self.0.signature_verification_algorithms,
-        )
+    /// Path of the known hosts file
+    pub fn file(&self) -> &str {
+        &self.file.path
     }
 
-    fn verify_tls13_signature(
-        &self,
-        message: &[u8],
-        cert: &CertificateDer<'_>,
-        dss: &rustls::DigitallySignedStruct,
-    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        rustls::crypto::verify_tls13_signature(
-            message,
-            cert,
-            dss,
-            &self.0.signature_verification_algorithms,
-        )
+    /// Sets the cipher used by [`KnownHostsStore::save`], e.g. to encrypt an existing plaintext file. `None` saves the file in plaintext.
+    pub fn set_cipher(&mut self, cipher: Option<Arc<dyn KnownHostsCipher>>) {
+        self.file.cipher = cipher;
     }
 
-    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-        self.0.signature_verification_algorithms.supported_schemes()
+    /// All the trusted server certificates
+    pub fn entries(&self) -> &CertStore {
+        &self.entries
+    }
+
+    /// Fingerprint trusted for a server on a port, as used by the certificate verifier
+    pub fn get(&self, server_name: &ServerName, port: u16) -> Option<&CertificateFingerprint> {
+        lookup_fingerprint(&self.entries, server_name, port)
+    }
+
+    /// Trusts a certificate for a server, returns the previously trusted fingerprint
+    pub fn insert(
+        &mut self,
+        host: KnownHost,
+        fingerprint: CertificateFingerprint,
+    ) -> Option<CertificateFingerprint> {
+        self.entries.insert(host, fingerprint)
+    }
+
+    /// Removes an entry, returns its fingerprint
+    pub fn remove(&mut self, host: &KnownHost) -> Option<CertificateFingerprint> {
+        self.entries.remove(host)
+    }
+
+    /// Removes the entries of a server for all its ports
+    pub fn remove_server(&mut self, server_name: &ServerName) {
+        self.entries
+            .retain(|host, _| host.server_name != *server_name);
+    }
+
+    /// Writes the entries in the known hosts file format
+    pub fn export(&self, writer: impl Write) -> Result<(), KnownHostsError> {
+        write_known_hosts(writer, &self.entries)
+    }
+
+    /// Writes the entries to the known hosts file
+    pub fn save(&self) -> Result<(), KnownHostsError> {
+        store_known_hosts_to_file(&self.file, &self.entries)
+    }
+}
+
+impl From<KnownHostsStore> for KnownHosts {
+    fn from(store: KnownHostsStore) -> Self {
+        KnownHosts::Store(store.entries)
     }
 }
 
+/// Certificate fingerprint storage as a value or as a file
+#[derive(Debug, Clone)]
+pub enum KnownHosts {
+    /// Directly contains the server name to fingerprint mapping
+    Store(CertStore),
+    /// Path of a file caontaing the server name to fingerprint mapping.
+    HostsFile(String),
+    /// Same as [`KnownHosts::HostsFile`], the file is encrypted at rest with a [`KnownHostsCipher`]
+    EncryptedHostsFile {
+        /// Path of the file
+        file: String,
+        /// Cipher used to read and write the file
+        cipher: Arc<dyn KnownHostsCipher>,
+    },
+}
+
+/// Known hosts file, encrypted if it has a cipher
+#[derive(Debug, Clone)]
+pub(crate) struct HostsFile {
+    path: String,
+    cipher: Option<Arc<dyn KnownHostsCipher>>,
+}
+
 /// Implementation of `ServerCertVerifier` that follows the Trust on first use authentication scheme.
 #[derive(Debug)]
 pub(crate) struct TofuServerVerification {
     store: CertStore,
     verifier_behaviour: HashMap<CertVerificationStatus, CertVerifierBehaviour>,
+    server_behaviours: HashMap<ServerName, HashMap<CertVerificationStatus, CertVerifierBehaviour>>,
+    interaction_resolver: Option<CertInteractionResolver>,
     to_sync_client: mpsc::Sender<ClientAsyncMessage>,
 
     /// If present, the file where new fingerprints should be stored
-    hosts_file: Option<String>,
+    hosts_file: Option<HostsFile>,
+    /// Port of the server, part of its [`KnownHost`] key
+    server_port: u16,
 
     provider: Arc<rustls::crypto::CryptoProvider>,
 }
 
 impl TofuServerVerification {
+    /// Loads the known hosts of `config`
     pub(crate) fn new(
-        store: CertStore,
-        verifier_behaviour: HashMap<CertVerificationStatus, CertVerifierBehaviour>,
+        config: TrustOnFirstUseConfig,
+        server_port: u16,
         to_sync_client: mpsc::Sender<ClientAsyncMessage>,
-        hosts_file: Option<String>,
         provider: Arc<rustls::crypto::CryptoProvider>,
-    ) -> Arc<Self> {
-        Arc::new(Self {
+    ) -> Result<Arc<Self>, KnownHostsError> {
+        let (store, hosts_file) = load_known_hosts_store_from_config(config.known_hosts)?;
+        Ok(Arc::new(Self {
             store,
-            verifier_behaviour,
+            verifier_behaviour: config.verifier_behaviour,
+            server_behaviours: config.server_behaviours,
+            interaction_resolver: config.interaction_resolver,
             to_sync_client,
             hosts_file,
+            server_port,
             provider,
-        })
+        }))
     }
 
     fn apply_verifier_behaviour_for_status(
@@ -287,14 +653,20 @@ impl TofuServerVerification {
         cert_info: CertVerificationInfo,
     ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
         let behaviour = self
-            .verifier_behaviour
-            .get(&status)
+            .server_behaviours
+            .get(&cert_info.server_name)
+            .and_then(|behaviours| behaviours.get(&status))
+            .or_else(|| self.verifier_behaviour.get(&status))
             .unwrap_or(&DEFAULT_CERT_VERIFIER_BEHAVIOUR);
         match behaviour {
             CertVerifierBehaviour::ImmediateAction(action) => {
                 self.apply_verifier_immediate_action(action, status, cert_info)
             }
             CertVerifierBehaviour::RequestClientAction => {
+                if let Some(resolver) = &self.interaction_resolver {
+                    let action = resolver.resolve(&status, &cert_info);
+                    return self.apply_verifier_immediate_action(&action, status, cert_info);
+                }
                 let (action_sender, cert_action_recv) = oneshot::channel::<CertVerifierAction>();
                 self.to_sync_client
                     .try_send(ClientAsyncMessage::CertificateInteractionRequest {
@@ -324,16 +696,14 @@ impl TofuServerVerification {
             CertVerifierAction::AbortConnection => {
                 match self
                     .to_sync_client
-                    .try_send(ClientAsyncMessage::CertificateConnectionAbort {
-                        status: status,
-                        cert_info,
-                    }) {
-                    Ok(_) => Err(rustls::Error::General(format!(
-                        "CertVerifierAction requested to abort the connection"
-                    ))),
-                    Err(_) => Err(rustls::Error::General(format!(
-                        "Failed to signal CertificateConnectionAbort"
-                    ))),
+                    .try_send(ClientAsyncMessage::CertificateConnectionAbort { status, cert_info })
+                {
+                    Ok(_) => Err(rustls::Error::General(
+                        "CertVerifierAction requested to abort the connection".to_string(),
+                    )),
+                    Err(_) => Err(rustls::Error::General(
+                        "Failed to signal CertificateConnectionAbort".to_string(),
+                    )),
                 }
             }
             CertVerifierAction::TrustOnce => {
@@ -343,9 +713,8 @@ impl TofuServerVerification {
                 // If we need to store them to a file
                 if let Some(file) = &self.hosts_file {
                     let mut store_clone = self.store.clone();
-                    store_clone
-                        .insert(cert_info.server_name.clone(), cert_info.fingerprint.clone());
-                    if let Err(store_error) = store_known_hosts_to_file(&file, &store_clone) {
+                    store_clone.insert(cert_info.known_host(), cert_info.fingerprint.clone());
+                    if let Err(store_error) = store_known_hosts_to_file(file, &store_clone) {
                         return Err(rustls::Error::General(format!(
                             "Failed to store new certificate entry: {}",
                             store_error
@@ -358,9 +727,9 @@ impl TofuServerVerification {
                     .try_send(ClientAsyncMessage::CertificateTrustUpdate(cert_info))
                 {
                     Ok(_) => Ok(rustls::client::danger::ServerCertVerified::assertion()),
-                    Err(_) => Err(rustls::Error::General(format!(
-                        "Failed to signal new trusted certificate entry"
-                    ))),
+                    Err(_) => Err(rustls::Error::General(
+                        "Failed to signal new trusted certificate entry".to_string(),
+                    )),
                 }
             }
         }
@@ -379,9 +748,11 @@ impl rustls::client::danger::ServerCertVerifier for TofuServerVerification {
         // TODO Could add some optional validity checks on the cert content.
         let status;
         let server_name = ServerName(_server_name.to_owned());
-        let known_fingerprint = self.store.get(&server_name).cloned();
+        let known_fingerprint =
+            lookup_fingerprint(&self.store, &server_name, self.server_port).cloned();
         let cert_info = CertVerificationInfo {
             server_name,
+            port: self.server_port,
             fingerprint: CertificateFingerprint::from(_end_entity),
             known_fingerprint,
         };
@@ -432,61 +803,82 @@ impl rustls::client::danger::ServerCertVerifier for TofuServerVerification {
     }
 }
 
-fn store_known_hosts_to_file(file: &String, store: &CertStore) -> Result<(), Box<dyn Error>> {
-    let path = std::path::Path::new(file);
+fn write_known_hosts(mut writer: impl Write, store: &CertStore) -> Result<(), KnownHostsError> {
+    for (host, fingerprint) in store {
+        writeln!(writer, "{} {}", host, fingerprint)?;
+    }
+    Ok(())
+}
+
+fn store_known_hosts_to_file(file: &HostsFile, store: &CertStore) -> Result<(), KnownHostsError> {
+    let path = std::path::Path::new(&file.path);
     if let Some(prefix) = path.parent() {
         std::fs::create_dir_all(prefix)?;
     }
-    let mut store_file = File::create(path)?;
-    for entry in store {
-        writeln!(store_file, "{} {}", entry.0, entry.1)?;
+    match &file.cipher {
+        Some(cipher) => {
+            let mut content = Vec::new();
+            write_known_hosts(&mut content, store)?;
+            std::fs::write(path, cipher.seal(&content)?)?;
+            Ok(())
+        }
+        None => write_known_hosts(File::create(path)?, store),
     }
-    Ok(())
 }
 
 fn parse_known_host_line(
     line: String,
-) -> Result<(ServerName, CertificateFingerprint), Box<dyn Error>> {
+) -> Result<(KnownHost, CertificateFingerprint), InvalidHostFile> {
     let mut parts = line.split_whitespace();
 
-    let adr_str = parts.next().ok_or(InvalidHostFile)?;
-    let serv_name = ServerName(RustlsServerName::try_from(adr_str)?.to_owned());
+    let host = parts.next().ok_or(InvalidHostFile)?.parse()?;
 
     let fingerprint_b64 = parts.next().ok_or(InvalidHostFile)?;
-    let fingerprint_bytes = base64::decode(&fingerprint_b64)?;
+    let fingerprint_bytes = base64::decode(fingerprint_b64).map_err(|_| InvalidHostFile)?;
 
     match fingerprint_bytes.try_into() {
-        Ok(buf) => Ok((serv_name, CertificateFingerprint::new(buf))),
-        Err(_) => Err(Box::new(InvalidHostFile)),
+        Ok(buf) => Ok((host, CertificateFingerprint::new(buf))),
+        Err(_) => Err(InvalidHostFile),
     }
 }
 
-fn load_known_hosts_from_file(
-    file_path: String,
-) -> Result<(CertStore, Option<String>), Box<dyn Error>> {
+fn parse_known_hosts(reader: impl BufRead) -> Result<CertStore, KnownHostsError> {
     let mut store = HashMap::new();
-    for line in BufReader::new(File::open(&file_path)?).lines() {
+    for line in reader.lines() {
         let entry = parse_known_host_line(line?)?;
         store.insert(entry.0, entry.1);
     }
-    Ok((store, Some(file_path)))
+    Ok(store)
 }
 
-pub(crate) fn load_known_hosts_store_from_config(
-    known_host_config: KnownHosts,
-) -> Result<(CertStore, Option<String>), Box<dyn Error>> {
-    match known_host_config {
-        KnownHosts::Store(store) => Ok((store, None)),
-        KnownHosts::HostsFile(file) => {
-            if !Path::new(&file).exists() {
-                warn!(
-                    "Known hosts file `{}` not found, no known hosts loaded",
-                    file
-                );
-                Ok((HashMap::new(), Some(file)))
-            } else {
-                load_known_hosts_from_file(file)
-            }
+fn load_known_hosts_from_file(file: &HostsFile) -> Result<CertStore, KnownHostsError> {
+    match &file.cipher {
+        Some(cipher) => {
+            let content = cipher.open(&std::fs::read(&file.path)?)?;
+            parse_known_hosts(&content[..])
         }
+        None => parse_known_hosts(BufReader::new(File::open(&file.path)?)),
+    }
+}
+
+fn load_known_hosts_store_from_config(
+    known_host_config: KnownHosts,
+) -> Result<(CertStore, Option<HostsFile>), KnownHostsError> {
+    let file = match known_host_config {
+        KnownHosts::Store(store) => return Ok((store, None)),
+        KnownHosts::HostsFile(path) => HostsFile { path, cipher: None },
+        KnownHosts::EncryptedHostsFile { file, cipher } => HostsFile {
+            path: file,
+            cipher: Some(cipher),
+        },
+    };
+    if !Path::new(&file.path).exists() {
+        warn!(
+            "Known hosts file `{}` not found, no known hosts loaded",
+            file.path
+        );
+        Ok((HashMap::new(), Some(file)))
+    } else {
+        load_known_hosts_from_file(&file).map(|store| (store, Some(file)))
     }
 }
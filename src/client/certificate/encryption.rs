@@ -0,0 +1,91 @@
+use std::fmt;
+
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::client::KnownHostsError;
+
+/// Length of a [`KnownHostsKey`], in bytes
+pub const KNOWN_HOSTS_KEY_LEN: usize = 32;
+
+/// Header of the known hosts files encrypted by a [`KnownHostsKey`], also authenticated with their content
+const ENCRYPTED_HOSTS_FILE_HEADER: &[u8] = b"QUINNET-KNOWN-HOSTS-V1";
+
+/// Encryption at rest of a known hosts file, for platforms where plaintext per-user files are not acceptable.
+///
+/// Implemented by [`KnownHostsKey`] for a key provided by the application. Implement it to delegate the encryption to a platform keystore (OS keychain, console secure storage, ...).
+pub trait KnownHostsCipher: fmt::Debug + Send + Sync + 'static {
+    /// Encrypts the content of a known hosts file
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, KnownHostsError>;
+    /// Decrypts the content of a known hosts file
+    fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, KnownHostsError>;
+}
+
+/// Key provided by the application to encrypt a known hosts file with ChaCha20-Poly1305
+#[derive(Clone)]
+pub struct KnownHostsKey([u8; KNOWN_HOSTS_KEY_LEN]);
+
+impl KnownHostsKey {
+    /// Creates a key from its raw bytes
+    pub fn new(key: [u8; KNOWN_HOSTS_KEY_LEN]) -> Self {
+        Self(key)
+    }
+
+    fn aead_key(&self) -> Result<LessSafeKey, KnownHostsError> {
+        UnboundKey::new(&CHACHA20_POLY1305, &self.0)
+            .map(LessSafeKey::new)
+            .map_err(|_| KnownHostsError::Encryption)
+    }
+}
+
+impl fmt::Debug for KnownHostsKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KnownHostsKey(..)")
+    }
+}
+
+impl KnownHostsCipher for KnownHostsKey {
+    /// Output is the header, a random nonce, then the sealed content
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, KnownHostsError> {
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| KnownHostsError::Encryption)?;
+        let mut sealed = plaintext.to_vec();
+        self.aead_key()?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(ENCRYPTED_HOSTS_FILE_HEADER),
+                &mut sealed,
+            )
+            .map_err(|_| KnownHostsError::Encryption)?;
+        let mut output =
+            Vec::with_capacity(ENCRYPTED_HOSTS_FILE_HEADER.len() + NONCE_LEN + sealed.len());
+        output.extend_from_slice(ENCRYPTED_HOSTS_FILE_HEADER);
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&sealed);
+        Ok(output)
+    }
+
+    fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, KnownHostsError> {
+        let sealed = ciphertext
+            .strip_prefix(ENCRYPTED_HOSTS_FILE_HEADER)
+            .ok_or(KnownHostsError::Encryption)?;
+        if sealed.len() < NONCE_LEN {
+            return Err(KnownHostsError::Encryption);
+        }
+        let (nonce, sealed) = sealed.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| KnownHostsError::Encryption)?;
+        let mut opened = sealed.to_vec();
+        let len = self
+            .aead_key()?
+            .open_in_place(nonce, Aad::from(ENCRYPTED_HOSTS_FILE_HEADER), &mut opened)
+            .map_err(|_| KnownHostsError::Encryption)?
+            .len();
+        opened.truncate(len);
+        Ok(opened)
+    }
+}
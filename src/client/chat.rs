@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use crate::shared::{
+    channels::ChannelId,
+    chat::{ChatEvent, ChatRequest, ChatTarget},
+    ClientId, QuinnetSyncUpdate,
+};
+
+use super::{
+    connection::ClientSideConnection, ClientMessageSendError, ConnectionClosed, QuinnetClient,
+};
+
+/// Client side of the text chat of a [`crate::server::chat::ChatServer`]: sends the requests of the client and receives its [`ChatEvent`]s.
+#[derive(Resource, Debug)]
+pub struct ChatClient {
+    channel_id: ChannelId,
+    joined: HashSet<String>,
+}
+
+impl ChatClient {
+    /// Chat on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            joined: HashSet::new(),
+        }
+    }
+
+    /// Channel the chat messages are sent on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Chat channels joined by the client, as notified by the server
+    pub fn joined(&self) -> &HashSet<String> {
+        &self.joined
+    }
+
+    /// Asks the server to join a chat channel. A [`ChatEvent::Joined`] is received once joined
+    pub fn join(
+        &self,
+        connection: &mut ClientSideConnection,
+        channel: impl Into<String>,
+    ) -> Result<(), ClientMessageSendError> {
+        connection.send_message_on(self.channel_id, ChatRequest::Join(channel.into()))
+    }
+
+    /// Asks the server to leave a chat channel. A [`ChatEvent::Left`] is received once left
+    pub fn leave(
+        &self,
+        connection: &mut ClientSideConnection,
+        channel: impl Into<String>,
+    ) -> Result<(), ClientMessageSendError> {
+        connection.send_message_on(self.channel_id, ChatRequest::Leave(channel.into()))
+    }
+
+    /// Sends a message to the members of a chat channel joined by the client
+    pub fn send(
+        &self,
+        connection: &mut ClientSideConnection,
+        channel: impl Into<String>,
+        text: impl Into<String>,
+    ) -> Result<(), ClientMessageSendError> {
+        self.send_to(connection, ChatTarget::Channel(channel.into()), text)
+    }
+
+    /// Sends a message to a single client
+    pub fn whisper(
+        &self,
+        connection: &mut ClientSideConnection,
+        recipient: ClientId,
+        text: impl Into<String>,
+    ) -> Result<(), ClientMessageSendError> {
+        self.send_to(connection, ChatTarget::Whisper(recipient), text)
+    }
+
+    fn send_to(
+        &self,
+        connection: &mut ClientSideConnection,
+        target: ChatTarget,
+        text: impl Into<String>,
+    ) -> Result<(), ClientMessageSendError> {
+        connection.send_message_on(
+            self.channel_id,
+            ChatRequest::Send {
+                target,
+                text: text.into(),
+            },
+        )
+    }
+
+    /// Receives the chat events sent by the server since the last call, in their order.
+    ///
+    /// Messages that can't be deserialized are dropped. Will return an [`Err`] if the connection is closed.
+    pub fn receive(
+        &mut self,
+        connection: &mut ClientSideConnection,
+    ) -> Result<Vec<ChatEvent>, ConnectionClosed> {
+        let events: Vec<ChatEvent> = connection
+            .receive_all_on(self.channel_id)?
+            .filter_map(|payload| bincode::deserialize(&payload).ok())
+            .collect();
+        for event in events.iter() {
+            match event {
+                ChatEvent::Joined(channel) => {
+                    self.joined.insert(channel.clone());
+                }
+                ChatEvent::Left(channel) => {
+                    self.joined.remove(channel);
+                }
+                _ => (),
+            }
+        }
+        Ok(events)
+    }
+
+    /// Forgets the chat channels joined, such as when reconnecting
+    pub fn reset(&mut self) {
+        self.joined.clear();
+    }
+}
+
+/// Runs the client side of the text chat with a [`ChatClient`] resource, and raises a [`ChatEvent`] for each event received on the default connection.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetClientChatPlugin {
+    channel_id: ChannelId,
+    schedule: InternedScheduleLabel,
+}
+
+impl QuinnetClientChatPlugin {
+    /// Plugin running the chat on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            schedule: PreUpdate.intern(),
+        }
+    }
+
+    /// Receives the chat events in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetClientChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ChatEvent>()
+            .insert_resource(ChatClient::new(self.channel_id))
+            .add_systems(
+                self.schedule,
+                receive_chat_events
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetClient>),
+            );
+    }
+}
+
+fn receive_chat_events(
+    mut client: ResMut<QuinnetClient>,
+    mut chat: ResMut<ChatClient>,
+    mut chat_events: EventWriter<ChatEvent>,
+) {
+    let Some(connection) = client.get_connection_mut() else {
+        return;
+    };
+    if let Ok(events) = chat.receive(connection) {
+        chat_events.write_batch(events);
+    }
+}
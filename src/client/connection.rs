@@ -1,28 +1,34 @@
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeSet, HashMap},
     error::Error,
-    net::{AddrParseError, IpAddr, SocketAddr},
-    sync::Arc,
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::{Duration, Instant},
 };
 
 use bevy::{
-    log::{error, info, trace},
+    log::{debug, error, info, info_span, trace, warn},
     prelude::Event,
 };
 use bytes::Bytes;
-use quinn::{crypto::rustls::QuicClientConfig, ClientConfig, Endpoint};
-use quinn_proto::ConnectionStats;
+use futures::stream::{FuturesUnordered, StreamExt};
+use quinn::{
+    crypto::rustls::QuicClientConfig, default_runtime, ClientConfig, Endpoint, IdleTimeout,
+    TransportConfig,
+};
+use quinn_proto::{ConnectionStats, PathStats};
 
+use rustls::pki_types::ServerName;
 use rustls_platform_verifier::BuilderVerifierExt;
 use serde::Deserialize;
+use socket2::{Domain, Protocol, Socket, Type};
 use tokio::{
     runtime,
     sync::{
         broadcast,
-        mpsc::{
-            self,
-            error::{TryRecvError, TrySendError},
-        },
+        mpsc::{self, error::TrySendError},
     },
 };
 
@@ -32,33 +38,58 @@ mod client_id;
 #[cfg(feature = "shared-client-id")]
 use client_id::receive_client_id;
 
+#[cfg(feature = "testing")]
+use crate::testing::ScriptSlot;
+
 use crate::shared::{
+    buffer_pool::{BufferPool, BufferPoolStats, DEFAULT_BUFFER_CHUNK_SIZE},
+    certificate::SkipServerVerification,
     channels::{
-        spawn_recv_channels_tasks, spawn_send_channels_tasks_spawner, Channel, ChannelAsyncMessage,
-        ChannelId, ChannelKind, ChannelSyncMessage, ChannelsConfiguration, CloseReason, CloseRecv,
-        CloseSend,
+        ack::{AckTracker, MAX_ACKS_PER_CONTROL_MESSAGE},
+        compression::DictionaryNegotiation,
+        control::{control_channel_config, ControlMessage, CONTROL_CHANNEL_ID},
+        incoming::{incoming_channel, IncomingPayloads, IncomingRecv, IncomingSend},
+        queue::OutgoingQueue,
+        spawn_recv_channels_tasks, spawn_send_channels_tasks_spawner,
+        trace::MessageTrace,
+        Channel, ChannelAsyncMessage, ChannelConfig, ChannelEncryption, ChannelId, ChannelKind,
+        ChannelSyncMessage, ChannelsConfiguration, CloseReason, CloseRecv, CloseSend,
+        MessagePriority, SharedChannelConfigs, TrackedMessageId, PROTOCOL_HEADER_LEN,
     },
-    error::{AsyncChannelError, ChannelCloseError, ChannelCreationError},
+    close::{peer_close_code, CloseCode, CloseStage},
+    congestion::{CongestionEventKind, CongestionMonitor},
+    error::{AsyncChannelError, ChannelCloseError, ChannelCreationError, ChannelError},
+    forwarding::{ForwardedClient, ForwardingKey, MAX_FORWARDED_IDENTITY_LEN},
+    hardening::ReceiveHardening,
+    memory::{MemoryBudget, MemoryBudgetPolicy, SharedConnectionMemory},
+    profiling,
+    protocol::ProtocolChannel,
+    qos::QosConfiguration,
+    socket::SocketConfiguration,
+    stats_history::{StatsHistory, StatsHistoryConfig},
+    tick::{NetworkTick, SharedNetworkTick},
+    transport::{display_remote, TransportConnection},
     ClientId, InternalConnectionRef, DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE,
     DEFAULT_KILL_MESSAGE_QUEUE_SIZE, DEFAULT_MESSAGE_QUEUE_SIZE,
     DEFAULT_QCHANNEL_MESSAGES_CHANNEL_SIZE,
 };
 
 use super::{
-    certificate::{
-        load_known_hosts_store_from_config, CertificateVerificationMode, SkipServerVerification,
-        TofuServerVerification,
-    },
+    certificate::{CertificateVerificationMode, ClientCertificate, TofuServerVerification},
     error::{
         ClientMessageReceiveError, ClientMessageSendError, ClientPayloadSendError, ClientSendError,
     },
-    ClientAsyncMessage, ClientConnectionCloseError, ConnectionClosed, QuinnetConnectionError,
+    split::{check_message_size, ConnectionIo, ConnectionReceiver, ConnectionSender},
+    ClientAsyncMessage, ClientConfigurationError, ClientConnectionCloseError, ConnectionClosed,
+    QuinnetClientEvent, QuinnetConnectionError,
 };
 
 /// Alias type for a local id of a connection
 pub type ConnectionLocalId = u64;
 
 /// Connection event raised when the client just connected to the server. Raised in the CoreStage::PreUpdate stage.
+///
+/// No message of the connection is delivered before this event: messages received earlier are buffered and delivered from the frame of the event.
 #[derive(Event, Debug, Copy, Clone)]
 pub struct ConnectionEvent {
     /// Local id of the connection
@@ -83,153 +114,677 @@ pub struct ConnectionFailedEvent {
 pub struct ConnectionLostEvent {
     /// Local id of the connection
     pub id: ConnectionLocalId,
+    /// Application close code sent by the server, if it closed the connection. [`CloseCode::ProtocolMismatch`] for a connection closed by the client after a [`ProtocolMismatchEvent`].
+    pub close_code: Option<CloseCode>,
+}
+
+/// Event raised at each [`CloseStage`] reached by a connection closed by the client. Raised in the CoreStage::PreUpdate stage.
+///
+/// Not raised for the lost connections, whose peer has already closed the connection.
+///
+/// Still raised once the connection was removed by [`crate::client::QuinnetClient::close_connection`], until its [`CloseStage::Closed`] stage.
+#[derive(Event, Debug, Clone)]
+pub struct ConnectionCloseStageEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Stage reached by the connection
+    pub stage: CloseStage,
+    /// Time elapsed since the connection started to drain its channels
+    pub elapsed: Duration,
+}
+
+/// Event raised when the server transferred the connection to another server. Raised in the CoreStage::PreUpdate stage.
+///
+/// The connection leaves the server and reconnects to the target server, which receives the transfer token of the connection once connected. A [`ConnectionEvent`] or a [`ConnectionFailedEvent`] follows, no [`ConnectionLostEvent`] is raised for the left server.
+#[derive(Event, Debug, Clone)]
+pub struct ConnectionTransferEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Address of the target server
+    pub target_addr: SocketAddr,
+    /// Hostname used to verify the certificate of the target server
+    pub server_hostname: String,
+}
+
+/// Event raised when the server checking the protocol of its clients found that the protocol hash of the connection does not match its own, see [`crate::server::Endpoint::set_protocol_check`]. Raised in the CoreStage::PreUpdate stage.
+///
+/// The client and the server were likely built with different protocols. The client then closes the connection: followed by the [`ConnectionLostEvent`] of the connection, with [`CloseCode::ProtocolMismatch`].
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ProtocolMismatchEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Protocol hash of the connection, see [`ChannelsConfiguration::protocol_hash`]
+    pub local_hash: u64,
+    /// Protocol hash of the server
+    pub server_hash: u64,
+}
+
+/// Event raised when a connection opened with [`crate::client::QuinnetClient::open_connection_race`] finished racing its servers. Raised in the CoreStage::PreUpdate stage.
+///
+/// Followed by the [`ConnectionEvent`] or the [`ConnectionFailedEvent`] of the connection.
+#[derive(Event, Debug, Clone)]
+pub struct ConnectionRaceEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Attempt of each raced server, in the order of their configurations
+    pub attempts: Vec<RaceAttempt>,
+}
+
+/// Connection attempt to one of the servers raced by [`crate::client::QuinnetClient::open_connection_race`]
+#[derive(Debug, Clone)]
+pub struct RaceAttempt {
+    /// Address of the server
+    pub server_addr: SocketAddr,
+    /// How the attempt ended
+    pub outcome: RaceOutcome,
+}
+
+/// How a connection attempt of a race ended, see [`RaceAttempt`]
+#[derive(Debug, Clone)]
+pub enum RaceOutcome {
+    /// First to finish the handshake, the connection continues with this server
+    Won {
+        /// Time taken by the handshake since the start of the race
+        handshake: Duration,
+    },
+    /// Failed before the handshake of the winner finished
+    Failed(QuinnetConnectionError),
+    /// Still in progress when the winner finished its handshake, aborted
+    Aborted,
+}
+
+impl RaceOutcome {
+    /// Returns true if the attempt won the race
+    pub fn is_won(&self) -> bool {
+        matches!(self, RaceOutcome::Won { .. })
+    }
+}
+
+/// Raised when the server acknowledged a message sent with [`ClientSideConnection::send_unreliable_tracked`]. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct MessageAckedEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Channel the message was sent on
+    pub channel_id: ChannelId,
+    /// Id returned when sending the message
+    pub message_id: TrackedMessageId,
+}
+
+/// Raised when the server reset the stream of a reliable channel, which resumed on a new stream. Raised in the CoreStage::PreUpdate stage.
+///
+/// The message being sent is sent again on the new stream, but the messages sent before it on the reset stream may not have reached the server.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ChannelResumedEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Channel which resumed
+    pub channel_id: ChannelId,
+}
+
+/// Raised when the server left the liveness probe of a reliable channel unanswered, see [`ChannelConfig::liveness_probe`]. Raised once per unanswered probe, the channel is probed again once the server answers. Raised in the CoreStage::PreUpdate stage.
+///
+/// The connection itself may be alive: the stream of the channel is stuck, such as when the server stopped reading it.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ChannelUnresponsiveEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Channel which did not answer
+    pub channel_id: ChannelId,
+    /// Time waited for the answer to the probe
+    pub waited: Duration,
+}
+
+/// Raised when the dictionary of a compressed channel of the connection does not match the dictionary announced by the server for this channel, see [`ChannelConfig::compressed_with_dictionary`]. The channel is closed, as if by [`ClientSideConnection::close_channel`]. Raised in the CoreStage::PreUpdate stage.
+///
+/// The payloads compressed with another dictionary could not be decompressed: the channel is rejected instead of dropping them silently.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ChannelRejectedEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Channel which was closed
+    pub channel_id: ChannelId,
+    /// Id of the dictionary of the channel, `None` for a channel compressed without a dictionary
+    pub local_dictionary_id: Option<u32>,
+    /// Id of the dictionary announced by the server, `None` for a channel compressed without a dictionary
+    pub server_dictionary_id: Option<u32>,
+}
+
+/// Raised when a message could not be sent on a channel of the connection and was dropped, the channel and the connection stay open. Raised in the CoreStage::PreUpdate stage.
+///
+/// Errors are not reported while the client is lagging behind a flood of them.
+#[derive(Event, Debug, Clone)]
+pub struct ChannelErrorEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Channel of the message
+    pub channel_id: ChannelId,
+    /// What went wrong
+    pub error: ChannelError,
+}
+
+/// Raised when the congestion controller of the connection exits or re-enters slow start, or reduces its congestion window, if enabled with [`ClientSideConnection::set_congestion_events`]. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CongestionEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// What changed
+    pub kind: CongestionEventKind,
+    /// Stats of the path when the change was detected
+    pub path_stats: PathStats,
+}
+
+/// Raised when the max size of the datagrams to the server changes, usually when the path MTU discovery found a larger MTU or a black hole. Raised in the CoreStage::PreUpdate stage.
+///
+/// Also raised with the initial size once connected, and after each reconnection. See [`ClientSideConnection::max_unreliable_payload_size`] to budget the payloads of the unreliable channels.
+#[derive(Event, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MaxDatagramSizeChangedEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Max size of the datagrams before the change, `None` for the initial size
+    pub previous: Option<usize>,
+    /// Max size of the datagrams from now on, `None` if the datagrams are disabled
+    pub max_datagram_size: Option<usize>,
+}
+
+/// Raised when the connection exceeds its memory budget, or refuses a message because of it, see [`ClientSideConnection::set_memory_budget`]. Raised again once the connection went back under its budget and exceeds it again. Raised in the CoreStage::PreUpdate stage.
+///
+/// With [`MemoryBudgetPolicy::Disconnect`], the connection is then closed with [`CloseCode::MemoryBudgetExceeded`], as with [`ClientSideConnection::disconnect_with_code`].
+#[derive(Event, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryBudgetExceededEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Budget of the connection
+    pub budget: MemoryBudget,
+    /// Bytes buffered by the connection when the budget was exceeded
+    pub used: usize,
+}
+
+/// Raised when the server did not acknowledge a message sent with [`ClientSideConnection::send_unreliable_tracked`] within [`crate::shared::channels::MESSAGE_ACK_TIMEOUT`]. Raised in the CoreStage::PreUpdate stage.
+///
+/// The message may still have reached the server if its acknowledgement was delayed, a late acknowledgement is ignored: each tracked message gets either a [`MessageAckedEvent`] or a [`MessageLostEvent`].
+#[derive(Event, Debug, Copy, Clone)]
+pub struct MessageLostEvent {
+    /// Local id of the connection
+    pub id: ConnectionLocalId,
+    /// Channel the message was sent on
+    pub channel_id: ChannelId,
+    /// Id returned when sending the message
+    pub message_id: TrackedMessageId,
 }
 
-/// Configuration of a client connection, used when connecting to a server
+/// Idle timeout of the client connections when not set, see [`ClientEndpointConfigurationBuilder::with_idle_timeout`]. Same as quinn's default.
+pub const DEFAULT_CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Configuration of a client connection, used when connecting to a server. Built with [`ClientEndpointConfiguration::builder`].
 #[derive(Debug, Deserialize, Clone)]
 pub struct ClientEndpointConfiguration {
     server_addr: SocketAddr,
     server_hostname: String,
     local_bind_addr: SocketAddr,
+    #[serde(default)]
+    alpn_protocols: Vec<Vec<u8>>,
+    #[serde(default)]
+    transport: ClientTransportConfiguration,
+    #[serde(default)]
+    reuse_local_socket: bool,
+    #[serde(default)]
+    qos: QosConfiguration,
+    #[serde(default)]
+    socket: SocketConfiguration,
+    #[serde(skip)]
+    endpoint: Option<Endpoint>,
+    /// Endpoint bound by the first connection attempt, when the local socket is reused
+    #[serde(skip)]
+    local_socket: Arc<Mutex<Option<Endpoint>>>,
+    #[serde(skip)]
+    client_certificate: Option<ClientCertificate>,
+    #[serde(skip)]
+    forwarding: Option<(ForwardingKey, ForwardedClient)>,
+    #[serde(skip)]
+    tls_config: Option<Arc<rustls::ClientConfig>>,
+    #[serde(default)]
+    key_log: bool,
 }
 
 impl ClientEndpointConfiguration {
-    /// Creates a new ClientEndpointConfiguration
-    ///
-    /// # Arguments
-    ///
-    /// * `server_addr_str` - IP address and port of the server
-    /// * `local_bind_addr_str` - Local address and port to bind to separated by `:`. The address should usually be a wildcard like `0.0.0.0` (for an IPv4) or `[::]` (for an IPv6), which allow communication with any reachable IPv4 or IPv6 address. See [`std::net::SocketAddrV4`] and [`std::net::SocketAddrV6`] or [`quinn::Endpoint`] for more precision. For the local port to bind to, use 0 to get an OS-assigned port.
+    /// Starts building a configuration. Only the server is required, see [`ClientEndpointConfigurationBuilder::build`] for the other defaults.
     ///
     /// # Examples
     ///
-    /// Connect to an IPv4 server hosted on localhost (127.0.0.1), which is listening on port 6000. Use 0 as a local bind port to let the OS assign a port.
+    /// Connect to an IPv6 server hosted on localhost (::1), which is listening on port 6000. The local bind address defaults to `[::]:0`, letting the OS assign a port.
     /// ```
+    /// use std::net::Ipv6Addr;
     /// use bevy_quinnet::client::connection::ClientEndpointConfiguration;
-    /// let config = ClientEndpointConfiguration::from_strings(
-    ///                 "127.0.0.1:6000",
-    ///                 "0.0.0.0:0"
-    ///             );
-    /// ```
-    /// Connect to an IPv6 server hosted on localhost (::1), which is listening on port 6000. Use 0 as a local bind port to let the OS assign a port.
+    /// let config = ClientEndpointConfiguration::builder()
+    ///     .with_server_ip(Ipv6Addr::LOCALHOST, 6000)
+    ///     .build()
+    ///     .unwrap();
     /// ```
+    /// Connect to a server by its host name, resolved when building the configuration and used to verify its certificate, from an IPv4 local bind address.
+    /// ```no_run
+    /// use std::{net::Ipv4Addr, time::Duration};
     /// use bevy_quinnet::client::connection::ClientEndpointConfiguration;
-    /// let config = ClientEndpointConfiguration::from_strings(
-    ///                 "[::1]:6000",
-    ///                 "[::]:0"
-    ///             );
+    /// let config = ClientEndpointConfiguration::builder()
+    ///     .with_server_host("game.example.com", 6000)
+    ///     .with_local_bind_ip(Ipv4Addr::UNSPECIFIED, 0)
+    ///     .with_idle_timeout(Duration::from_secs(10))
+    ///     .with_keep_alive_interval(Duration::from_secs(2))
+    ///     .build()
+    ///     .unwrap();
     /// ```
-    pub fn from_strings(
-        server_addr_str: &str,
-        local_bind_addr_str: &str,
-    ) -> Result<Self, AddrParseError> {
-        let server_addr = server_addr_str.parse()?;
-        let local_bind_addr = local_bind_addr_str.parse()?;
-        Ok(Self::from_addrs(server_addr, local_bind_addr))
-    }
-
-    /// Same as [`ClientEndpointConfiguration::from_strings`], but with an additional `server_hostname` for certificate verification if it is not just the server IP.
-    pub fn from_strings_with_name(
-        server_addr_str: &str,
-        server_hostname: String,
-        local_bind_addr_str: &str,
-    ) -> Result<Self, AddrParseError> {
-        Ok(Self::from_addrs_with_name(
-            server_addr_str.parse()?,
-            server_hostname,
-            local_bind_addr_str.parse()?,
-        ))
+    pub fn builder() -> ClientEndpointConfigurationBuilder {
+        ClientEndpointConfigurationBuilder::default()
     }
 
-    /// Creates a new ClientEndpointConfiguration
-    ///
-    /// # Arguments
+    /// Address of the server
+    pub fn server_addr(&self) -> SocketAddr {
+        self.server_addr
+    }
+
+    /// Name of the server, sent to it (SNI) and used to verify its certificate
+    pub fn server_name(&self) -> &str {
+        &self.server_hostname
+    }
+
+    /// Local address and port the connection binds to, unused when connecting from an existing endpoint
+    pub fn local_bind_addr(&self) -> SocketAddr {
+        self.local_bind_addr
+    }
+
+    /// Returns true if the local socket is bound once and reused by the reconnections, see [`ClientEndpointConfigurationBuilder::with_reused_local_socket`]
+    pub fn reuses_local_socket(&self) -> bool {
+        self.reuse_local_socket
+    }
+
+    /// Traffic class of the packets of the connection, see [`ClientEndpointConfigurationBuilder::with_qos`]
+    pub fn qos(&self) -> QosConfiguration {
+        self.qos
+    }
+
+    /// Performance settings of the socket of the connection, see [`ClientEndpointConfigurationBuilder::with_socket`]
+    pub fn socket(&self) -> SocketConfiguration {
+        self.socket
+    }
+
+    /// Same configuration, without the local socket bound by the connection it was used for
+    pub(crate) fn detach_local_socket(mut self) -> Self {
+        self.local_socket = Arc::default();
+        self
+    }
+
+    /// Binds a new endpoint on the local bind address, or returns the endpoint bound by a previous attempt if the local socket is reused
+    fn bind_endpoint(&self) -> Result<Endpoint, QuinnetConnectionError> {
+        let mut local_socket = self
+            .local_socket
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(endpoint) = local_socket.as_ref() {
+            return Ok(endpoint.clone());
+        }
+        let endpoint =
+            bind_client_endpoint(self.local_bind_addr, self.qos, self.socket).map_err(|err| {
+                QuinnetConnectionError::LocalBindFailed(self.local_bind_addr, err.kind())
+            })?;
+        if self.reuse_local_socket {
+            *local_socket = Some(endpoint.clone());
+        }
+        Ok(endpoint)
+    }
+
+    /// Checks the configuration, as done by [`ClientEndpointConfigurationBuilder::build`]. Meant for the configurations deserialized from a file.
+    pub fn validate(&self) -> Result<(), ClientConfigurationError> {
+        if ServerName::try_from(self.server_hostname.as_str()).is_err() {
+            return Err(ClientConfigurationError::InvalidServerName(
+                self.server_hostname.clone(),
+            ));
+        }
+        if self.endpoint.is_none() && self.server_addr.is_ipv6() && self.local_bind_addr.is_ipv4() {
+            return Err(ClientConfigurationError::AddressFamilyMismatch {
+                server_addr: self.server_addr,
+                local_bind_addr: self.local_bind_addr,
+            });
+        }
+        if let Some(protocol) = self
+            .alpn_protocols
+            .iter()
+            .find(|protocol| protocol.is_empty() || protocol.len() > u8::MAX as usize)
+        {
+            return Err(ClientConfigurationError::InvalidAlpnProtocol(
+                protocol.len(),
+            ));
+        }
+        if let Some(size) = self.socket.invalid_max_udp_payload_size() {
+            return Err(ClientConfigurationError::InvalidMaxUdpPayloadSize(size));
+        }
+        self.transport.validate()
+    }
+}
+
+/// Same as [`Endpoint::client`], with a socket marking its packets with `qos` and tuned with `socket_config`
+fn bind_client_endpoint(
+    local_bind_addr: SocketAddr,
+    qos: QosConfiguration,
+    socket_config: SocketConfiguration,
+) -> io::Result<Endpoint> {
+    let socket = Socket::new(
+        Domain::for_address(local_bind_addr),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    if local_bind_addr.is_ipv6() {
+        if let Err(err) = socket.set_only_v6(false) {
+            debug!("Unable to make the client socket dual-stack: {}", err);
+        }
+    }
+    socket.bind(&local_bind_addr.into())?;
+    let socket = socket.into();
+    qos.apply(&socket)?;
+    socket_config.apply(&socket)?;
+    let runtime = default_runtime().ok_or_else(|| io::Error::other("no async runtime found"))?;
+    Endpoint::new_with_abstract_socket(
+        socket_config.endpoint_config(),
+        None,
+        socket_config.wrap_socket(socket, &qos, &runtime)?,
+        runtime,
+    )
+}
+
+/// QUIC transport settings of a client connection, quinn's defaults when unset
+#[derive(Debug, Default, Deserialize, Clone)]
+struct ClientTransportConfiguration {
+    idle_timeout: Option<Duration>,
+    keep_alive_interval: Option<Duration>,
+    initial_rtt: Option<Duration>,
+    datagram_receive_buffer_size: Option<usize>,
+    datagram_send_buffer_size: Option<usize>,
+}
+
+impl ClientTransportConfiguration {
+    fn idle_timeout(&self) -> Duration {
+        self.idle_timeout.unwrap_or(DEFAULT_CLIENT_IDLE_TIMEOUT)
+    }
+
+    fn validate(&self) -> Result<(), ClientConfigurationError> {
+        let idle_timeout = self.idle_timeout();
+        if IdleTimeout::try_from(idle_timeout).is_err() {
+            return Err(ClientConfigurationError::IdleTimeoutTooLarge(idle_timeout));
+        }
+        if let Some(keep_alive_interval) = self.keep_alive_interval {
+            if keep_alive_interval >= idle_timeout {
+                return Err(ClientConfigurationError::KeepAliveNotBelowIdleTimeout {
+                    keep_alive_interval,
+                    idle_timeout,
+                });
+            }
+        }
+        if self.initial_rtt == Some(Duration::ZERO) {
+            return Err(ClientConfigurationError::ZeroInitialRtt);
+        }
+        if self.datagram_receive_buffer_size == Some(0) || self.datagram_send_buffer_size == Some(0)
+        {
+            return Err(ClientConfigurationError::ZeroDatagramBuffer);
+        }
+        Ok(())
+    }
+
+    fn to_transport_config(&self) -> TransportConfig {
+        let mut transport = TransportConfig::default();
+        transport
+            .max_idle_timeout(IdleTimeout::try_from(self.idle_timeout()).ok())
+            .keep_alive_interval(self.keep_alive_interval);
+        if let Some(initial_rtt) = self.initial_rtt {
+            transport.initial_rtt(initial_rtt);
+        }
+        if let Some(size) = self.datagram_receive_buffer_size {
+            transport.datagram_receive_buffer_size(Some(size));
+        }
+        if let Some(size) = self.datagram_send_buffer_size {
+            transport.datagram_send_buffer_size(size);
+        }
+        transport
+    }
+}
+
+/// Server of a [`ClientEndpointConfigurationBuilder`]
+#[derive(Debug, Clone)]
+enum ServerTarget {
+    Addr(SocketAddr),
+    /// Resolved when building the configuration
+    Host(String, u16),
+}
+
+/// Builder of a [`ClientEndpointConfiguration`], see [`ClientEndpointConfiguration::builder`]
+#[derive(Debug, Default, Clone)]
+pub struct ClientEndpointConfigurationBuilder {
+    server: Option<ServerTarget>,
+    server_name: Option<String>,
+    local_bind_addr: Option<SocketAddr>,
+    reuse_local_socket: bool,
+    alpn_protocols: Vec<Vec<u8>>,
+    transport: ClientTransportConfiguration,
+    qos: QosConfiguration,
+    socket: SocketConfiguration,
+    endpoint: Option<Endpoint>,
+    client_certificate: Option<ClientCertificate>,
+    forwarding: Option<(ForwardingKey, ForwardedClient)>,
+    tls_config: Option<Arc<rustls::ClientConfig>>,
+    key_log: bool,
+}
+
+impl ClientEndpointConfigurationBuilder {
+    /// Connects to the server at `server_addr`
+    pub fn with_server_addr(mut self, server_addr: SocketAddr) -> Self {
+        self.server = Some(ServerTarget::Addr(server_addr));
+        self
+    }
+
+    /// Connects to the server at `server_ip`, listening on `server_port`
+    pub fn with_server_ip(self, server_ip: impl Into<IpAddr>, server_port: u16) -> Self {
+        self.with_server_addr(SocketAddr::new(server_ip.into(), server_port))
+    }
+
+    /// Connects to the server named `host` (a DNS name or an IP address), listening on `port`.
     ///
-    /// * `server_ip` - IP address of the server
-    /// * `server_port` - Port of the server
-    /// * `local_bind_ip` - Local IP address to bind to. The address should usually be a wildcard like `0.0.0.0` (for an IPv4) or `0:0:0:0:0:0:0:0` (for an IPv6), which allow communication with any reachable IPv4 or IPv6 address. See [`std::net::Ipv4Addr`] and [`std::net::Ipv6Addr`] for more precision.
-    /// * `local_bind_port` - Local port to bind to. Use 0 to get an OS-assigned port.
+    /// The host is resolved when building the configuration, which blocks on the system resolver, to its first address reachable from the local bind address. It is also the server name, unless set by [`ClientEndpointConfigurationBuilder::with_server_name`].
+    pub fn with_server_host(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.server = Some(ServerTarget::Host(host.into(), port));
+        self
+    }
+
+    /// Sends `server_name` to the server (SNI) and verifies its certificate against it, instead of the host or IP address of the server.
     ///
-    /// # Examples
+    /// Required when connecting by IP address to a server whose certificate carries a DNS name, or when connecting through a relay: the address is the relay's, the name is the final server's.
+    pub fn with_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Binds the connection to `local_bind_addr`. The address should usually be a wildcard like `0.0.0.0` (for an IPv4) or `[::]` (for an IPv6), which allow communication with any reachable IPv4 or IPv6 address. Use the port 0 to get an OS-assigned port.
     ///
-    /// Connect to an IPv4 server hosted on localhost (127.0.0.1), which is listening on port 6000. Use 0 as a local bind port to let the OS assign a port.
-    /// ```
-    /// use std::net::Ipv6Addr;
-    /// use bevy_quinnet::client::connection::ClientEndpointConfiguration;
-    /// let config = ClientEndpointConfiguration::from_ips(
-    ///                 Ipv6Addr::LOCALHOST,
-    ///                 6000,
-    ///                 Ipv6Addr::UNSPECIFIED,
-    ///                 0
-    ///             );
-    /// ```
-    pub fn from_ips(
-        server_ip: impl Into<IpAddr>,
-        server_port: u16,
-        local_bind_ip: impl Into<IpAddr>,
-        local_bind_port: u16,
-    ) -> Self {
-        Self::from_addrs(
-            SocketAddr::new(server_ip.into(), server_port),
-            SocketAddr::new(local_bind_ip.into(), local_bind_port),
-        )
+    /// Defaults to the wildcard address of the family of the server, with an OS-assigned port.
+    pub fn with_local_bind_addr(mut self, local_bind_addr: SocketAddr) -> Self {
+        self.local_bind_addr = Some(local_bind_addr);
+        self
     }
 
-    /// Same as [`ClientEndpointConfiguration::from_ips`], but with an additional `server_hostname` for certificate verification if it is not just the server IP.
-    pub fn from_ips_with_name(
-        server_ip: impl Into<IpAddr>,
-        server_port: u16,
-        server_hostname: String,
+    /// Same as [`ClientEndpointConfigurationBuilder::with_local_bind_addr`], from an IP address and a port
+    pub fn with_local_bind_ip(
+        self,
         local_bind_ip: impl Into<IpAddr>,
         local_bind_port: u16,
     ) -> Self {
-        Self::from_addrs_with_name(
-            SocketAddr::new(server_ip.into(), server_port),
-            server_hostname,
-            SocketAddr::new(local_bind_ip.into(), local_bind_port),
-        )
+        self.with_local_bind_addr(SocketAddr::new(local_bind_ip.into(), local_bind_port))
     }
 
-    /// Creates a new ClientEndpointConfiguration
+    /// Binds the local socket on the first connection attempt and keeps it for the reconnections and transfers of the connection, instead of binding a new socket on each attempt.
     ///
-    /// # Arguments
+    /// The connection keeps its local port, assigned by the OS on the first attempt when binding to the port 0, and the mapping a NAT made for it: required by hole punching and strict NAT setups. See [`ClientSideConnection::local_addr`].
+    pub fn with_reused_local_socket(mut self) -> Self {
+        self.reuse_local_socket = true;
+        self
+    }
+
+    /// Offers `alpn_protocols` to the server during the handshake, by order of preference.
     ///
-    /// * `server_addr` - IP address and port of the server
-    /// * `local_bind_addr` - Local address and port to bind to. For the local port to bind to, use 0 to get an OS-assigned port.
+    /// Required by servers configured with ALPN protocols, such as the servers sharing their endpoint with other QUIC services, see [`crate::server::ExternalEndpointConfiguration::with_protocol_routing`].
+    pub fn with_alpn_protocols(mut self, alpn_protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn_protocols = alpn_protocols;
+        self
+    }
+
+    /// Connects from `endpoint`, created by the app, instead of binding a new endpoint on the local bind address.
     ///
-    /// # Examples
+    /// The endpoint can be shared with other QUIC services and with other connections. Its default client configuration is left untouched: the connection uses its own configuration, built from its [`CertificateVerificationMode`].
+    pub fn with_endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    /// Presents `client_certificate` to the server during the handshake, for the servers authenticating their clients (mutual TLS), see [`crate::server::ServerEndpointConfiguration::with_client_authentication`].
     ///
-    /// Connect to an IPv4 server hosted on localhost (127.0.0.1), which is listening on port 6000. Use 0 as a local bind port to let the OS assign a port.
-    /// ```
-    /// use bevy_quinnet::client::connection::ClientEndpointConfiguration;
-    /// use std::{net::{IpAddr, Ipv4Addr, SocketAddr}};
-    /// let config = ClientEndpointConfiguration::from_addrs(
-    ///        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 6000),
-    ///        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0),
-    ///    );
-    /// ```
-    pub fn from_addrs(server_addr: SocketAddr, local_bind_addr: SocketAddr) -> Self {
-        Self {
-            server_addr,
-            server_hostname: server_addr.ip().to_string(),
-            local_bind_addr,
-        }
+    /// Lets a server connect to another server as an authenticated client, for example a gateway connecting to its zone servers, with [`CertificateVerificationMode::SignedBy`] to authenticate the zone servers in turn.
+    pub fn with_client_certificate(mut self, client_certificate: ClientCertificate) -> Self {
+        self.client_certificate = Some(client_certificate);
+        self
     }
 
-    /// Same as [`ClientEndpointConfiguration::from_addrs`], but with an additional `server_hostname` for certificate verification if it is not just the server IP.
-    pub fn from_addrs_with_name(
-        server_addr: SocketAddr,
-        server_hostname: String,
-        local_bind_addr: SocketAddr,
-    ) -> Self {
-        Self {
+    /// Uses `tls_config` as the TLS configuration of the connection instead of the one built by Quinnet, for specific security requirements: cipher suites, crypto provider, key log, certificate verifier...
+    ///
+    /// The [`CertificateVerificationMode`] and the client certificate of the connection are then ignored, `tls_config` verifies the server and authenticates the client by itself. The ALPN protocols of the connection are used if `tls_config` has none. The config must support TLS 1.3 with a QUIC initial cipher suite, otherwise the connection fails.
+    pub fn with_tls_config(mut self, tls_config: Arc<rustls::ClientConfig>) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// Logs the TLS secrets of the connection to the file named by the `SSLKEYLOGFILE` environment variable, in the NSS key log format, so that a packet capture of the connection can be decrypted by Wireshark. Nothing is logged if the variable is not set.
+    ///
+    /// For development only: anyone reading the file can decrypt the traffic. Not applied to a custom [`ClientEndpointConfigurationBuilder::with_tls_config`], whose `key_log` is left to the app.
+    pub fn with_key_log(mut self, key_log: bool) -> Self {
+        self.key_log = key_log;
+        self
+    }
+
+    /// Forwards `client` to the server: once connected, the connection presents a header describing `client`, signed with `key`. Used by a gateway opening a connection to an internal server for each of its clients.
+    ///
+    /// The server raises a [`crate::server::ClientForwardedEvent`] if it accepts the header, see [`crate::server::Endpoint::set_forwarding_key`]. The header is signed again on each connection, reconnections included.
+    pub fn with_forwarded_client(mut self, key: ForwardingKey, client: ForwardedClient) -> Self {
+        self.forwarding = Some((key, client));
+        self
+    }
+
+    /// Closes the connection after `idle_timeout` without receiving anything from the server. Defaults to [`DEFAULT_CLIENT_IDLE_TIMEOUT`].
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.transport.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Sends a keep-alive to the server after `keep_alive_interval` without sending anything, so that an inactive connection does not reach its idle timeout. Disabled by default, the servers sending their own keep-alives.
+    pub fn with_keep_alive_interval(mut self, keep_alive_interval: Duration) -> Self {
+        self.transport.keep_alive_interval = Some(keep_alive_interval);
+        self
+    }
+
+    /// Round-trip time assumed before the first measure, for example to connect faster to a server known to be far away
+    pub fn with_initial_rtt(mut self, initial_rtt: Duration) -> Self {
+        self.transport.initial_rtt = Some(initial_rtt);
+        self
+    }
+
+    /// Maximum number of bytes of datagrams received and waiting to be read by the unreliable channels, the oldest being dropped beyond
+    pub fn with_datagram_receive_buffer_size(mut self, size: usize) -> Self {
+        self.transport.datagram_receive_buffer_size = Some(size);
+        self
+    }
+
+    /// Maximum number of bytes of datagrams waiting to be sent by the unreliable channels, the oldest being dropped beyond
+    pub fn with_datagram_send_buffer_size(mut self, size: usize) -> Self {
+        self.transport.datagram_send_buffer_size = Some(size);
+        self
+    }
+
+    /// Marks the packets of the connection with the DSCP of `qos` and enables or disables ECN, see [`QosConfiguration`].
+    ///
+    /// Applied to the socket bound by the connection, an endpoint given with [`ClientEndpointConfigurationBuilder::with_endpoint`] keeps its own socket.
+    pub fn with_qos(mut self, qos: QosConfiguration) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Sets the performance settings of the socket of the connection, see [`SocketConfiguration`].
+    ///
+    /// Applied to the socket bound by the connection, except for its segmentation offload which also applies to an endpoint given with [`ClientEndpointConfigurationBuilder::with_endpoint`].
+    pub fn with_socket(mut self, socket: SocketConfiguration) -> Self {
+        self.socket = socket;
+        self
+    }
+
+    /// Builds the configuration.
+    ///
+    /// Will return an [`Err`] if:
+    /// - no server was given, or its host can't be resolved
+    /// - the server name is invalid
+    /// - the server can't be reached from the local bind address, or a local bind address is given along with an endpoint
+    /// - an ALPN protocol is invalid
+    /// - the maximum UDP payload size is out of bounds
+    /// - or the transport settings are inconsistent
+    pub fn build(self) -> Result<ClientEndpointConfiguration, ClientConfigurationError> {
+        if self.endpoint.is_some() && self.local_bind_addr.is_some() {
+            return Err(ClientConfigurationError::LocalBindWithEndpoint);
+        }
+        let (server_addr, host) = match self.server {
+            None => return Err(ClientConfigurationError::MissingServerAddress),
+            Some(ServerTarget::Addr(server_addr)) => (server_addr, None),
+            Some(ServerTarget::Host(host, port)) => {
+                let server_addr = resolve_server_host(&host, port, self.local_bind_addr)?;
+                (server_addr, Some(host))
+            }
+        };
+        let local_bind_addr = self.local_bind_addr.unwrap_or_else(|| {
+            let unspecified: IpAddr = match server_addr {
+                SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+                SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+            };
+            SocketAddr::new(unspecified, 0)
+        });
+        let config = ClientEndpointConfiguration {
             server_addr,
-            server_hostname,
+            server_hostname: self
+                .server_name
+                .or(host)
+                .unwrap_or_else(|| server_addr.ip().to_string()),
             local_bind_addr,
-        }
+            alpn_protocols: self.alpn_protocols,
+            transport: self.transport,
+            reuse_local_socket: self.reuse_local_socket,
+            qos: self.qos,
+            socket: self.socket,
+            endpoint: self.endpoint,
+            local_socket: Arc::default(),
+            client_certificate: self.client_certificate,
+            forwarding: self.forwarding,
+            tls_config: self.tls_config,
+            key_log: self.key_log,
+        };
+        config.validate()?;
+        Ok(config)
     }
 }
 
+/// Resolves `host` to its first address reachable from `local_bind_addr`
+fn resolve_server_host(
+    host: &str,
+    port: u16,
+    local_bind_addr: Option<SocketAddr>,
+) -> Result<SocketAddr, ClientConfigurationError> {
+    let unresolved = || ClientConfigurationError::UnresolvedServerHost(host.to_string());
+    (host, port)
+        .to_socket_addrs()
+        .map_err(|_| unresolved())?
+        .find(|addr| !matches!(local_bind_addr, Some(local) if local.is_ipv4() && addr.is_ipv6()))
+        .ok_or_else(unresolved)
+}
+
 /// Current state of a client connection
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum ConnectionState {
@@ -260,8 +815,8 @@ pub(crate) enum InternalConnectionState {
     Disconnected,
 }
 
-pub(crate) type MessageSend = mpsc::Sender<(ChannelId, Bytes)>;
-pub(crate) type MessageRecv = mpsc::Receiver<(ChannelId, Bytes)>;
+pub(crate) type MessageSend = IncomingSend;
+pub(crate) type MessageRecv = IncomingRecv;
 pub(crate) type ClientAsyncMsgSend = mpsc::Sender<ClientAsyncMessage>;
 pub(crate) type ClientAsyncMsgRecv = mpsc::Receiver<ClientAsyncMessage>;
 pub(crate) type ChannelAsyncMsgSend = mpsc::Sender<ChannelAsyncMessage>;
@@ -282,7 +837,7 @@ pub(crate) fn create_async_channels() -> (
     CloseRecv,
 ) {
     let (bytes_from_server_send, bytes_from_server_recv) =
-        mpsc::channel::<(ChannelId, Bytes)>(DEFAULT_MESSAGE_QUEUE_SIZE);
+        incoming_channel(DEFAULT_MESSAGE_QUEUE_SIZE);
     let (to_sync_client_send, to_sync_client_recv) =
         mpsc::channel::<ClientAsyncMessage>(DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE);
     let (from_channels_send, from_channels_recv) =
@@ -304,6 +859,16 @@ pub(crate) fn create_async_channels() -> (
     )
 }
 
+/// Async ends of the internal channels of a connection, used by the tasks driving the connection
+#[derive(Debug)]
+pub(crate) struct AsyncConnectionEnds {
+    pub(crate) bytes_from_server_send: MessageSend,
+    pub(crate) to_sync_client_send: ClientAsyncMsgSend,
+    pub(crate) from_channels_send: ChannelAsyncMsgSend,
+    pub(crate) to_channels_recv: ChannelSyncMsgRecv,
+    pub(crate) close_recv: CloseRecv,
+}
+
 /// A connection from a [`crate::client::QuinnetClient`] to a [`crate::server::QuinnetServer`]
 #[derive(Debug)]
 pub struct ClientSideConnection {
@@ -312,37 +877,60 @@ pub struct ClientSideConnection {
     /// handle to the async runtime
     runtime: runtime::Handle,
 
-    // Configuration
-    endpoint_config: ClientEndpointConfiguration,
-    cert_mode: CertificateVerificationMode,
+    // Configuration, `None` for connections over a custom transport
+    endpoint_config: Option<ClientEndpointConfiguration>,
+    cert_mode: Option<CertificateVerificationMode>,
     channels_config: ChannelsConfiguration,
 
     // State
     pub(crate) state: InternalConnectionState,
+    pub(crate) local_addr: Option<SocketAddr>,
 
-    channels: Vec<Option<Channel>>,
+    /// Channels and received payloads, shared with the halves of the connection
+    io: Arc<ConnectionIo>,
     available_channel_ids: BTreeSet<ChannelId>,
-    default_channel: Option<ChannelId>,
+    pub(crate) channels_configs: SharedChannelConfigs,
+    buffer_pool: BufferPool,
+    frame_coherent_receive: bool,
 
-    bytes_from_server_recv: mpsc::Receiver<(ChannelId, Bytes)>,
     close_sender: broadcast::Sender<CloseReason>,
+    /// Opened on the first control message sent to the server
+    control_channel: Option<Channel>,
+    /// Token to present to the server once connected, after a transfer
+    transfer_token: Option<Vec<u8>>,
+    /// Dictionaries of the compressed channels, announced to the server and checked against its own
+    dictionaries: DictionaryNegotiation,
+    /// Tracked messages sent to the server, waiting for their acknowledgement
+    acks: AckTracker,
+    /// Tracked messages acknowledged by the server, not yet reported
+    acked: Vec<(ChannelId, TrackedMessageId)>,
+    /// Last network tick read from the payloads of the server, see [`ChannelConfig::ticked`]
+    pub(crate) server_tick: SharedNetworkTick,
+    stats_history: Option<StatsHistory>,
+    congestion: Option<CongestionMonitor>,
+    /// Max datagram size reported by the last [`MaxDatagramSizeChangedEvent`]
+    last_max_datagram_size: Option<usize>,
+    /// Bytes buffered by the queues and the received payloads of the connection
+    memory: SharedConnectionMemory,
+    /// Set once a [`MemoryBudgetExceededEvent`] is raised, until the connection is back under its budget
+    memory_exceeded: bool,
 
     pub(crate) from_async_client_recv: mpsc::Receiver<ClientAsyncMessage>,
     pub(crate) to_channels_send: mpsc::Sender<ChannelSyncMessage>,
     pub(crate) from_channels_recv: mpsc::Receiver<ChannelAsyncMessage>,
 
-    /// Quinnet stats
-    received_messages_count: u64,
-    received_bytes_count: usize,
-    sent_bytes_count: usize,
+    /// Set for the connections driven by a [`crate::testing::ScriptedServer`], which receives the async ends of the reconnections
+    #[cfg(feature = "testing")]
+    pub(crate) script: Option<ScriptSlot>,
 }
 
 impl ClientSideConnection {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         local_id: ConnectionLocalId,
         runtime: runtime::Handle,
-        config: ClientEndpointConfiguration,
-        cert_mode: CertificateVerificationMode,
+        config: Option<ClientEndpointConfiguration>,
+        cert_mode: Option<CertificateVerificationMode>,
         channels_config: ChannelsConfiguration,
         bytes_from_server_recv: MessageRecv,
         close_sender: CloseSend,
@@ -350,27 +938,58 @@ impl ClientSideConnection {
         to_channels_send: ChannelSyncMsgSend,
         from_channels_recv: ChannelAsyncMsgRecv,
     ) -> Self {
+        let memory = SharedConnectionMemory::default();
         Self {
             local_id,
             runtime,
             state: InternalConnectionState::Connecting,
-            channels: Vec::new(),
-            default_channel: None,
+            local_addr: None,
+            io: Arc::new(ConnectionIo::new(IncomingPayloads::new(
+                bytes_from_server_recv,
+                memory.clone(),
+            ))),
             available_channel_ids: (0..255).collect(),
-            bytes_from_server_recv,
+            channels_configs: Arc::new(RwLock::new(Default::default())),
+            buffer_pool: BufferPool::new(DEFAULT_BUFFER_CHUNK_SIZE),
+            frame_coherent_receive: false,
             close_sender,
+            control_channel: None,
+            transfer_token: None,
+            dictionaries: DictionaryNegotiation::default(),
+            acks: AckTracker::default(),
+            acked: Vec::new(),
+            server_tick: SharedNetworkTick::default(),
+            stats_history: None,
+            congestion: None,
+            last_max_datagram_size: None,
+            memory,
+            memory_exceeded: false,
             from_async_client_recv,
             to_channels_send,
             from_channels_recv,
             endpoint_config: config,
             cert_mode,
             channels_config,
-            received_messages_count: 0,
-            received_bytes_count: 0,
-            sent_bytes_count: 0,
+            #[cfg(feature = "testing")]
+            script: None,
         }
     }
 
+    pub(crate) fn set_state(&mut self, state: InternalConnectionState) {
+        self.io.set_state(&state);
+        self.state = state;
+    }
+
+    /// Splits the connection into a sending and a receiving half, which can be used at the same time, for example as two resources: a Bevy system can then receive the messages of the connection while another one sends messages on it, without both of them needing the [`crate::client::QuinnetClient`] resource.
+    ///
+    /// The halves share the channels and the received payloads of the connection, which can still be used directly. They follow the connection as its channels are opened or closed and as it reconnects, and fail with [`ConnectionClosed`] once it is closed. Each call returns new halves of the same connection.
+    pub fn split(&self) -> (ConnectionSender, ConnectionReceiver) {
+        (
+            ConnectionSender::new(self.local_id, self.io.clone(), self.buffer_pool.sibling()),
+            ConnectionReceiver::new(self.local_id, self.io.clone()),
+        )
+    }
+
     /// Attempt to deserialise a message into type `T`.
     ///
     /// Will return an [`Err`] if:
@@ -414,18 +1033,77 @@ impl ClientSideConnection {
         channel_id: C,
         message: T,
     ) -> Result<(), ClientMessageSendError> {
-        match bincode::serialize(&message) {
-            Ok(payload) => Ok(self.send_payload_on(channel_id, payload)?),
-            Err(_) => Err(ClientMessageSendError::Serialization),
+        let channel_id = channel_id.into();
+        let payload = {
+            let _span = profiling::enter(|| {
+                info_span!(
+                    "quinnet_serialize",
+                    connection_id = self.local_id,
+                    channel_id
+                )
+            });
+            self.buffer_pool.serialize(&message)
+        };
+        match payload {
+            Some(payload) => Ok(self.send_payload_on(channel_id, payload)?),
+            None => Err(ClientMessageSendError::Serialization),
+        }
+    }
+
+    /// Same as [Self::send_message_on] but with a [`MessagePriority`]: on reliable channels, messages with a higher priority overtake the lower priority messages still waiting in the outgoing queue of the channel.
+    pub fn send_prioritized_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        message: T,
+        priority: MessagePriority,
+    ) -> Result<(), ClientMessageSendError> {
+        let channel_id = channel_id.into();
+        let payload = {
+            let _span = profiling::enter(|| {
+                info_span!(
+                    "quinnet_serialize",
+                    connection_id = self.local_id,
+                    channel_id
+                )
+            });
+            self.buffer_pool.serialize(&message)
+        };
+        match payload {
+            Some(payload) => Ok(self.send_prioritized_payload_on(channel_id, payload, priority)?),
+            None => Err(ClientMessageSendError::Serialization),
         }
     }
 
+    /// Same as [Self::send_message_on] on a channel declared with [`crate::protocol!`], only accepting the type of message declared for it
+    pub fn send_on<C: ProtocolChannel>(
+        &mut self,
+        channel: C,
+        message: &C::Message,
+    ) -> Result<(), ClientMessageSendError> {
+        self.send_message_on(channel, message)
+    }
+
+    /// Receives and deserializes all the messages sent by the server on a channel declared with [`crate::protocol!`], in their receiving order, see [Self::receive_all_on].
+    ///
+    /// Will return an [`Err`] if the connection is closed or if a message can't be deserialized into the type declared for the channel, the following messages are then dropped.
+    pub fn receive_on<C: ProtocolChannel>(
+        &mut self,
+        channel: C,
+    ) -> Result<Vec<C::Message>, ClientMessageReceiveError> {
+        self.receive_all_on(channel)?
+            .map(|payload| {
+                bincode::deserialize(&payload)
+                    .map_err(|_| ClientMessageReceiveError::Deserialization)
+            })
+            .collect()
+    }
+
     /// Same as [Self::send_message_on] but on the default channel
     pub fn send_message<T: serde::Serialize>(
         &mut self,
         message: T,
     ) -> Result<(), ClientMessageSendError> {
-        match self.default_channel {
+        match self.io.default_channel() {
             Some(channel) => self.send_message_on(channel, message),
             None => Err(ClientMessageSendError::NoDefaultChannel),
         }
@@ -454,7 +1132,7 @@ impl ClientSideConnection {
         &mut self,
         payload: T,
     ) -> Result<(), ClientPayloadSendError> {
-        match self.default_channel {
+        match self.io.default_channel() {
             Some(channel) => Ok(self.send_payload_on(channel, payload)?),
             None => Err(ClientPayloadSendError::NoDefaultChannel),
         }
@@ -471,19 +1149,59 @@ impl ClientSideConnection {
         channel_id: C,
         payload: T,
     ) -> Result<(), ClientSendError> {
-        let channel_id = channel_id.into();
-        match &self.state {
-            InternalConnectionState::Disconnected => Err(ClientSendError::ConnectionClosed),
-            _ => match self.channels.get(channel_id as usize) {
-                Some(Some(channel)) => {
-                    let bytes = payload.into();
-                    self.sent_bytes_count += bytes.len();
-                    Ok(channel.send_payload(bytes)?)
-                }
-                Some(None) => Err(ClientSendError::ChannelClosed),
-                None => Err(ClientSendError::InvalidChannelId(channel_id)),
-            },
+        self.send_payload_on_with_priority(channel_id.into(), payload.into(), None, false)
+            .map(|_| ())
+    }
+
+    /// Same as [Self::send_payload_on] but with a [`MessagePriority`]: on reliable channels, payloads with a higher priority overtake the lower priority payloads still waiting in the outgoing queue of the channel.
+    pub fn send_prioritized_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        payload: T,
+        priority: MessagePriority,
+    ) -> Result<(), ClientSendError> {
+        self.send_payload_on_with_priority(channel_id.into(), payload.into(), Some(priority), false)
+            .map(|_| ())
+    }
+
+    /// Sends the payload on the specified [`crate::shared::channels::ChannelKind::Unreliable`] channel, which must be [`ChannelConfig::acknowledged`]. Returns the id of the message, reported by a [`MessageAckedEvent`] once the server received it, or by a [`MessageLostEvent`] if it did not acknowledge it within [`crate::shared::channels::MESSAGE_ACK_TIMEOUT`].
+    ///
+    /// Meant for the messages whose loss the game wants to react to, such as a one-off input, without the latency of a reliable channel.
+    pub fn send_unreliable_tracked<T: Into<Bytes>, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        payload: T,
+    ) -> Result<TrackedMessageId, ClientSendError> {
+        self.send_payload_on_with_priority(channel_id.into(), payload.into(), None, true)
+            .map(|tracked| tracked.expect("Tracked sends should return their message id"))
+    }
+
+    /// Returns the id of the message if `tracked`
+    fn send_payload_on_with_priority(
+        &mut self,
+        channel_id: ChannelId,
+        bytes: Bytes,
+        priority: Option<MessagePriority>,
+        tracked: bool,
+    ) -> Result<Option<TrackedMessageId>, ClientSendError> {
+        if !tracked {
+            return self
+                .io
+                .send_payload(channel_id, bytes, priority)
+                .map(|_| None);
         }
+        let channel = self.io.channel(channel_id)?;
+        if !channel.is_acknowledged() {
+            return Err(ClientSendError::ChannelNotAcknowledged(channel_id));
+        }
+        check_message_size(&channel, &bytes)?;
+        self.io.count_sent(bytes.len());
+        let id = self.acks.track(channel_id, Instant::now());
+        if let Err(err) = channel.send_tracked_payload(id, bytes, self.io.deferred_flush()) {
+            self.acks.forget(id);
+            return Err(err.into());
+        }
+        Ok(Some(id))
     }
 
     /// Same as [Self::send_payload] but will log the error instead of returning it
@@ -493,14 +1211,77 @@ impl ClientSideConnection {
         }
     }
 
-    /// Same as [Self::send_payload_on] but will log the error instead of returning it
-    pub fn try_send_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
-        &mut self,
-        channel_id: C,
-        payload: T,
-    ) {
-        if let Err(err) = self.send_payload_on(channel_id, payload) {
-            error!("try_send_payload_on: {}", err);
+    /// Same as [Self::send_payload_on] but will log the error instead of returning it
+    pub fn try_send_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        payload: T,
+    ) {
+        if let Err(err) = self.send_payload_on(channel_id, payload) {
+            error!("try_send_payload_on: {}", err);
+        }
+    }
+
+    /// When enabled, the messages sent to the server are held until the next flush: [`ClientSideConnection::flush`] or the flush system of the [`QuinnetClientPlugin`](crate::client::QuinnetClientPlugin), which runs in the [`QuinnetFlush`](crate::shared::QuinnetFlush) set.
+    ///
+    /// Messages sent during a frame (or a fixed tick) then leave together. Disabled by default: messages are handed to the async tasks as soon as they are sent.
+    pub fn set_deferred_flush(&mut self, deferred: bool) {
+        self.io.set_deferred_flush(deferred);
+    }
+
+    /// Returns true if the messages are held until the next flush, see [`ClientSideConnection::set_deferred_flush`]
+    pub fn deferred_flush(&self) -> bool {
+        self.io.deferred_flush()
+    }
+
+    /// When enabled, the messages received from the server are captured once per sync update of the client, in the CoreStage::PreUpdate stage, and the receive methods only return the messages of the last capture.
+    ///
+    /// All the systems of a frame then see the same set of messages, whatever their order, instead of the messages arriving while the frame runs. Disabled by default: the receive methods return the messages as soon as they arrive.
+    pub fn set_frame_coherent_receive(&mut self, enabled: bool) {
+        self.frame_coherent_receive = enabled;
+        self.io.incoming().set_frozen(enabled);
+    }
+
+    /// Returns true if the received messages are captured once per sync update, see [`ClientSideConnection::set_frame_coherent_receive`]
+    pub fn frame_coherent_receive(&self) -> bool {
+        self.frame_coherent_receive
+    }
+
+    /// Captures the messages of the frame, see [`ClientSideConnection::set_frame_coherent_receive`]
+    pub(crate) fn capture_received(&mut self) {
+        self.io.incoming().capture();
+    }
+
+    /// Sends the messages deferred until the next flush
+    pub fn flush(&self) {
+        self.io.flush();
+    }
+
+    /// Returns the ids of the channels opened on this connection, in increasing order
+    pub fn channel_ids(&self) -> Vec<ChannelId> {
+        self.io
+            .channels()
+            .iter()
+            .flatten()
+            .map(|channel| channel.id())
+            .collect()
+    }
+
+    /// Returns how many messages are waiting in the outgoing queue of the channel, `None` if the channel does not exist or is closed
+    pub fn pending_messages_count<C: Into<ChannelId>>(&self, channel_id: C) -> Option<usize> {
+        match self.io.channels().get(channel_id.into() as usize) {
+            Some(Some(channel)) => Some(channel.pending_messages_count()),
+            _ => None,
+        }
+    }
+
+    /// Discards the messages waiting in the outgoing queue of the channel, for example when a scene change makes the queued snapshots worthless.
+    ///
+    /// Returns how many messages were discarded, `None` if the channel does not exist or is closed. Messages already handed to the transport are still delivered.
+    pub fn clear_pending_messages<C: Into<ChannelId>>(&mut self, channel_id: C) -> Option<usize> {
+        match self.io.channels().get(channel_id.into() as usize) {
+            Some(Some(channel)) => Some(channel.clear_pending_messages()),
+            _ => None,
         }
     }
 
@@ -509,21 +1290,29 @@ impl ClientSideConnection {
     /// - Returns an [`Ok`] result containg [`Some`] if there is a message from the server in the message buffer
     /// - Returns an [`Ok`] result containg [`None`] if there is no message from the server in the message buffer
     /// - Can return an [`Err`] if the connection is closed
+    ///
+    /// No payload is returned before the [ConnectionEvent] of the connection is raised: payloads received earlier stay buffered until then.
     pub fn receive_payload(&mut self) -> Result<Option<(ChannelId, Bytes)>, ConnectionClosed> {
-        match &self.state {
-            InternalConnectionState::Disconnected => Err(ConnectionClosed),
-            _ => match self.bytes_from_server_recv.try_recv() {
-                Ok(msg_payload) => {
-                    self.received_bytes_count += msg_payload.1.len();
-                    self.received_messages_count += 1;
-                    Ok(Some(msg_payload))
-                }
-                Err(err) => match err {
-                    TryRecvError::Empty => Ok(None),
-                    TryRecvError::Disconnected => Err(ConnectionClosed),
-                },
-            },
-        }
+        self.io.receive_payload()
+    }
+
+    /// Receives all the payloads sent by the server on the specified channel, in their receiving order.
+    ///
+    /// Payloads received on other channels stay available to the other receive methods. Cheaper than calling [Self::receive_payload] in a loop when reading a lot of messages.
+    ///
+    /// Can return an [`Err`] if the connection is closed
+    pub fn receive_all_on<C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+    ) -> Result<impl Iterator<Item = Bytes>, ConnectionClosed> {
+        Ok(self.io.receive_all_on(channel_id.into())?.into_iter())
+    }
+
+    /// Receives all the payloads sent by the server, grouped by channel and in their receiving order in each channel.
+    ///
+    /// Can return an [`Err`] if the connection is closed
+    pub fn drain_payloads(&mut self) -> Result<HashMap<ChannelId, Vec<Bytes>>, ConnectionClosed> {
+        self.io.drain_payloads()
     }
 
     /// Same as [Self::receive_payload] but will log the error instead of returning it
@@ -544,7 +1333,7 @@ impl ClientSideConnection {
         match &self.state {
             &InternalConnectionState::Disconnected => Ok(()),
             _ => {
-                self.state = InternalConnectionState::Disconnected;
+                self.set_state(InternalConnectionState::Disconnected);
                 match self.close_sender.send(reason) {
                     Ok(_) => Ok(()),
                     Err(_) => {
@@ -559,8 +1348,18 @@ impl ClientSideConnection {
     /// Immediately prevents new messages from being sent on the connection and signal the connection to closes all its background tasks.
     ///
     /// Before trully closing, the connection will wait for all buffered messages in all its opened channels to be properly sent according to their respective channel type.
+    ///
+    /// The server receives [`CloseCode::Closed`] as the application close code of the connection.
     pub fn disconnect(&mut self) -> Result<(), ClientConnectionCloseError> {
-        self.internal_disconnect(CloseReason::LocalOrder)
+        self.disconnect_with_code(CloseCode::Closed)
+    }
+
+    /// Same as [Self::disconnect] but the server receives `code` as the application close code of the connection
+    pub fn disconnect_with_code(
+        &mut self,
+        code: CloseCode,
+    ) -> Result<(), ClientConnectionCloseError> {
+        self.internal_disconnect(CloseReason::LocalOrder(code))
     }
 
     /// Same as [Self::disconnect] but will log the error instead of returning it
@@ -582,7 +1381,12 @@ impl ClientSideConnection {
         (&self.state).into()
     }
 
-    /// See [quinn::Connection::max_datagram_size]
+    /// Returns the most recent network tick read from the payloads received from the server on the ticked channels, see [`ChannelConfig::ticked`]. `None` until such a payload is received from a server with a tick, see [`crate::server::Endpoint::set_network_tick`]
+    pub fn server_tick(&self) -> Option<NetworkTick> {
+        self.server_tick.get()
+    }
+
+    /// See [quinn::Connection::max_datagram_size]. For custom transports, see [`TransportConnection::max_datagram_size`].
     pub fn max_datagram_size(&self) -> Option<usize> {
         match &self.state {
             InternalConnectionState::Connected(connection, _) => connection.max_datagram_size(),
@@ -590,7 +1394,22 @@ impl ClientSideConnection {
         }
     }
 
-    /// Returns statistics about the current connection if connected.
+    /// Max size of a payload sent on the unreliable channel `channel_id` of this connection, once the headers of its options are deducted, see [`ChannelConfig::datagram_overhead`]. `None` if not connected, if the datagrams are disabled, or if `channel_id` is not an opened unreliable channel. Changes are reported by a [`MaxDatagramSizeChangedEvent`].
+    ///
+    /// The payloads of a compressed channel must fit once compressed.
+    pub fn max_unreliable_payload_size(&self, channel_id: ChannelId) -> Option<usize> {
+        let overhead = self
+            .channels_configs
+            .read()
+            .ok()?
+            .get(&channel_id)
+            .filter(|config| matches!(config.kind(), ChannelKind::Unreliable))
+            .map(ChannelConfig::datagram_overhead)?;
+        self.max_datagram_size()
+            .map(|size| size.saturating_sub(PROTOCOL_HEADER_LEN + overhead))
+    }
+
+    /// Returns statistics about the current connection if connected. Zeroed for custom transports without statistics.
     pub fn connection_stats(&self) -> Option<ConnectionStats> {
         match &self.state {
             InternalConnectionState::Connected(connection, _) => Some(connection.stats()),
@@ -598,33 +1417,244 @@ impl ClientSideConnection {
         }
     }
 
+    /// Returns a clone of the underlying [`quinn::Connection`] if connected, `None` for custom transports.
+    ///
+    /// Escape hatch to open streams for custom sub-protocols next to the Quinnet channels: the connection lifecycle stays managed by Quinnet. The unidirectional streams and the datagrams received are read by the Quinnet channels, custom sub-protocols should use bidirectional streams. With the `shared-client-id` feature, the first bidirectional stream opened by the server carries the client id and is read by the client connection.
+    #[cfg(feature = "raw")]
+    pub fn quic_connection(&self) -> Option<quinn::Connection> {
+        match &self.state {
+            InternalConnectionState::Connected(connection, _) => connection.quic_connection(),
+            _ => None,
+        }
+    }
+
+    /// Returns statistics about the buffers used to serialize messages and frame unreliable datagrams on this connection
+    pub fn buffer_pool_stats(&self) -> BufferPoolStats {
+        self.buffer_pool.stats()
+    }
+
+    /// Serializes the messages in buffers split off large pooled chunks, `false` to allocate a buffer of the exact size of each message instead. Enabled by default.
+    ///
+    /// A pooled chunk is only freed once all the messages serialized in it are dropped. Disable the pooling when the sent messages are kept alive long-term, so that each of them does not keep a whole chunk alive.
+    pub fn set_buffer_pooling(&mut self, enabled: bool) {
+        self.buffer_pool.set_pooling(enabled);
+    }
+
+    /// Returns whether the messages are serialized in pooled buffers, see [`ClientSideConnection::set_buffer_pooling`]
+    pub fn buffer_pooling(&self) -> bool {
+        self.buffer_pool.pooling()
+    }
+
+    /// Keeps a history of the sampled stats of the connection, `None` to disable it and drop the history. Disabled by default.
+    ///
+    /// Samples are taken during each sync update while connected. Changing the configuration restarts the history, as does a reconnection.
+    pub fn set_stats_history(&mut self, config: Option<StatsHistoryConfig>) {
+        self.stats_history = config.map(StatsHistory::new);
+    }
+
+    /// Returns the sampled stats of the connection, `None` unless enabled with [`ClientSideConnection::set_stats_history`]
+    pub fn stats_history(&self) -> Option<&StatsHistory> {
+        self.stats_history.as_ref()
+    }
+
+    /// Raises a [`CongestionEvent`] when the congestion controller of the connection changes state, see [`CongestionMonitor`]. Disabled by default.
+    ///
+    /// The stats of the connection are polled during each sync update while connected. A reconnection starts over in slow start.
+    pub fn set_congestion_events(&mut self, enabled: bool) {
+        self.congestion = enabled.then(CongestionMonitor::default);
+    }
+
+    /// Returns whether the [`CongestionEvent`]s are raised, see [`ClientSideConnection::set_congestion_events`]
+    pub fn congestion_events(&self) -> bool {
+        self.congestion.is_some()
+    }
+
+    /// Sets the memory budget of the connection, `None` to disable it. Disabled by default.
+    ///
+    /// Kept across reconnections. A [`MemoryBudgetExceededEvent`] is raised when the connection exceeds it.
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.memory.set_budget(budget);
+    }
+
+    /// Returns the memory budget of the connection, if any, see [`ClientSideConnection::set_memory_budget`]
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        self.memory.budget()
+    }
+
+    /// Bytes buffered by the connection: the payloads waiting in the outgoing queues of its channels, and the received payloads not read yet
+    pub fn memory_usage(&self) -> usize {
+        self.memory.used()
+    }
+
+    pub(crate) fn check_memory_budget(&mut self) -> Option<QuinnetClientEvent> {
+        let Some((budget, used)) = self.memory.check() else {
+            self.memory_exceeded = false;
+            return None;
+        };
+        if std::mem::replace(&mut self.memory_exceeded, true) {
+            return None;
+        }
+        if budget.policy() == MemoryBudgetPolicy::Disconnect {
+            if let Err(err) = self.disconnect_with_code(CloseCode::MemoryBudgetExceeded) {
+                error!("Failed to properly close clonnection: {}", err);
+            }
+        }
+        Some(QuinnetClientEvent::MemoryBudgetExceeded(
+            MemoryBudgetExceededEvent {
+                id: self.local_id,
+                budget,
+                used,
+            },
+        ))
+    }
+
+    /// Sends the liveness probes due on the channels, returns the channels which left their probe unanswered
+    pub(crate) fn poll_liveness(&mut self, now: Instant) -> Vec<QuinnetClientEvent> {
+        if !matches!(self.state, InternalConnectionState::Connected(..)) {
+            return Vec::new();
+        }
+        let local_id = self.local_id;
+        self.io
+            .channels()
+            .iter()
+            .flatten()
+            .filter_map(|channel| {
+                channel.poll_liveness(now).map(|waited| {
+                    QuinnetClientEvent::ChannelUnresponsive(ChannelUnresponsiveEvent {
+                        id: local_id,
+                        channel_id: channel.id(),
+                        waited,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Announces the compressed channels opened since the last call to the server, closes the channels whose dictionary does not match the dictionary of the server
+    pub(crate) fn negotiate_dictionaries(&mut self) -> Vec<QuinnetClientEvent> {
+        if !matches!(self.state, InternalConnectionState::Connected(..)) {
+            return Vec::new();
+        }
+        for announcement in self.dictionaries.take_announcements() {
+            if let Err(err) = self.send_control(announcement) {
+                error!(
+                    "Connection {} failed to announce a compressed channel: {}",
+                    self.local_id, err
+                );
+            }
+        }
+        let mut events = Vec::new();
+        for mismatch in self.dictionaries.take_mismatches() {
+            warn!(
+                "Connection {}: dictionary {:?} of channel {} does not match the dictionary {:?} of the server, closing the channel",
+                self.local_id,
+                mismatch.local_dictionary_id,
+                mismatch.channel_id,
+                mismatch.peer_dictionary_id
+            );
+            if let Err(err) = self.close_channel(mismatch.channel_id) {
+                trace!(
+                    "Connection {} failed to close channel {}: {}",
+                    self.local_id,
+                    mismatch.channel_id,
+                    err
+                );
+            }
+            events.push(QuinnetClientEvent::ChannelRejected(ChannelRejectedEvent {
+                id: self.local_id,
+                channel_id: mismatch.channel_id,
+                local_dictionary_id: mismatch.local_dictionary_id,
+                server_dictionary_id: mismatch.peer_dictionary_id,
+            }));
+        }
+        events
+    }
+
+    pub(crate) fn update_max_datagram_size(&mut self) -> Option<QuinnetClientEvent> {
+        let InternalConnectionState::Connected(connection, _) = &self.state else {
+            return None;
+        };
+        let max_datagram_size = connection.max_datagram_size();
+        if max_datagram_size == self.last_max_datagram_size {
+            return None;
+        }
+        let previous = std::mem::replace(&mut self.last_max_datagram_size, max_datagram_size);
+        Some(QuinnetClientEvent::MaxDatagramSizeChanged(
+            MaxDatagramSizeChangedEvent {
+                id: self.local_id,
+                previous,
+                max_datagram_size,
+            },
+        ))
+    }
+
+    pub(crate) fn sample_stats(&mut self, now: Instant) -> Vec<QuinnetClientEvent> {
+        let InternalConnectionState::Connected(connection, _) = &self.state else {
+            return Vec::new();
+        };
+        if self.stats_history.is_none() && self.congestion.is_none() {
+            return Vec::new();
+        }
+        let stats = connection.stats();
+        if let Some(history) = &mut self.stats_history {
+            history.sample(now, stats);
+        }
+        let Some(monitor) = &mut self.congestion else {
+            return Vec::new();
+        };
+        monitor
+            .update(stats.path)
+            .into_iter()
+            .map(|kind| {
+                QuinnetClientEvent::Congestion(CongestionEvent {
+                    id: self.local_id,
+                    kind,
+                    path_stats: stats.path,
+                })
+            })
+            .collect()
+    }
+
     /// Returns how many messages were read from this connection currently
     pub fn received_messages_count(&self) -> u64 {
-        self.received_messages_count
+        self.io.received_messages_count()
     }
 
     /// Returns how many bytes were received on this connection since the last time it was cleared and reset this value to 0
     pub fn clear_received_bytes_count(&mut self) -> usize {
-        let bytes_count = self.received_bytes_count;
-        self.received_bytes_count = 0;
-        bytes_count
+        self.io.clear_received_bytes_count()
     }
 
     /// Returns how many bytes were received on this connection since the last time it was cleared
     pub fn received_bytes_count(&self) -> usize {
-        self.received_bytes_count
+        self.io.received_bytes_count()
+    }
+
+    /// Removes the traces of the messages received from the server on traced channels, in their receiving order, see [`ChannelConfig::traced`].
+    ///
+    /// A trace is kept once its message is received by Quinnet, before the application reads it. Up to [`crate::shared::channels::MAX_BUFFERED_TRACES`] traces are kept, the oldest are dropped first.
+    pub fn drain_message_traces(&mut self) -> Vec<MessageTrace> {
+        self.io.incoming().drain_traces()
     }
 
     /// Returns how many bytes were received on this connection since the last time it was cleared and reset this value to 0
     pub fn clear_sent_bytes_count(&mut self) -> usize {
-        let bytes_count = self.sent_bytes_count;
-        self.sent_bytes_count = 0;
-        bytes_count
+        self.io.clear_sent_bytes_count()
     }
 
     /// Returns how many bytes were received on this connection since the last time it was cleared
     pub fn sent_bytes_count(&self) -> usize {
-        self.sent_bytes_count
+        self.io.sent_bytes_count()
+    }
+
+    /// Returns the local address the connection is bound to, once connected.
+    ///
+    /// When the connection was configured to bind on port 0, this contains the port assigned by the OS.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.state {
+            InternalConnectionState::Connected(_, _) => self.local_addr,
+            _ => None,
+        }
     }
 
     /// Returns the client_id assigned to this client by the server.
@@ -641,117 +1671,164 @@ impl ClientSideConnection {
     ///
     /// This uses the initial connection configuration. Notably, channels opened by calling [`Self::open_channel`] on the connection after it was initially opened won't be automatically re-opened.
     ///
-    /// Does nothing if the connection state is not [`ConnectionState::Disconnected`]
+    /// Does nothing if the connection state is not [`ConnectionState::Disconnected`], or if the connection was opened over a custom transport with [`crate::client::QuinnetClient::open_transport_connection`].
+    ///
+    /// A scripted connection, opened with `QuinnetClient::open_scripted_connection` (`testing` feature), is reset and waits for its script again.
     pub fn reconnect(&mut self) -> Result<(), AsyncChannelError> {
-        match &self.state {
-            InternalConnectionState::Disconnected => {
-                let (
-                    bytes_from_server_send,
-                    bytes_from_server_recv,
-                    to_sync_client_send,
-                    to_sync_client_recv,
-                    from_channels_send,
-                    from_channels_recv,
-                    to_channels_send,
-                    to_channels_recv,
-                    close_send,
-                    close_recv,
-                ) = create_async_channels();
-
-                // Connection state reset
-                self.state = InternalConnectionState::Connecting;
-                self.channels = Vec::with_capacity(self.channels_config.configs().len());
-                self.default_channel = None;
-                self.available_channel_ids = (0..255).collect();
-                self.bytes_from_server_recv = bytes_from_server_recv;
-                self.close_sender = close_send;
-                self.from_async_client_recv = to_sync_client_recv;
-                self.to_channels_send = to_channels_send;
-                self.from_channels_recv = from_channels_recv;
-                // Connection stats reset
-                self.received_messages_count = 0;
-                self.received_bytes_count = 0;
-                self.sent_bytes_count = 0;
-
-                // Open default channels
-                self.open_configured_channels(self.channels_config.clone())?;
-
-                // Async connection
-                let local_id = self.local_id;
-                let endpoint_config = self.endpoint_config.clone();
-                let cert_mode = self.cert_mode.clone();
-                self.runtime.spawn(async move {
-                    async_connection_task(
-                        local_id,
-                        endpoint_config,
-                        cert_mode,
-                        to_sync_client_send,
-                        bytes_from_server_send,
-                        to_channels_recv,
-                        from_channels_send,
-                        close_recv,
-                    )
-                    .await
-                });
-            }
-            _ => (),
+        if !matches!(self.state, InternalConnectionState::Disconnected) {
+            return Ok(());
         }
+        #[cfg(feature = "testing")]
+        if let Some(script) = self.script.clone() {
+            let ends = self.reset_async_channels()?;
+            *script.lock().expect("Script lock should not be poisoned") = Some(ends);
+            return Ok(());
+        }
+        let (Some(endpoint_config), Some(cert_mode)) =
+            (self.endpoint_config.clone(), self.cert_mode.clone())
+        else {
+            return Ok(());
+        };
+        let ends = self.reset_async_channels()?;
+
+        // Async connection
+        let local_id = self.local_id;
+        let channels_configs = self.channels_configs.clone();
+        let server_tick = self.server_tick.clone();
+        let connect = connect_quic(
+            local_id,
+            endpoint_config,
+            cert_mode,
+            ends.to_sync_client_send.clone(),
+        );
+        self.runtime.spawn(async move {
+            async_connection_task(local_id, connect, ends, channels_configs, server_tick).await
+        });
         Ok(())
     }
 
+    /// Resets the connection to a new connection attempt, returns the async ends of its new internal channels
+    fn reset_async_channels(&mut self) -> Result<AsyncConnectionEnds, AsyncChannelError> {
+        let (
+            bytes_from_server_send,
+            bytes_from_server_recv,
+            to_sync_client_send,
+            to_sync_client_recv,
+            from_channels_send,
+            from_channels_recv,
+            to_channels_send,
+            to_channels_recv,
+            close_send,
+            close_recv,
+        ) = create_async_channels();
+
+        // Connection state reset
+        self.set_state(InternalConnectionState::Connecting);
+        self.available_channel_ids = (0..255).collect();
+        self.channels_configs = Arc::new(RwLock::new(Default::default()));
+        self.server_tick = SharedNetworkTick::default();
+        let mut incoming = IncomingPayloads::new(bytes_from_server_recv, self.memory.clone());
+        incoming.set_frozen(self.frame_coherent_receive);
+        self.io.reset(incoming);
+        self.close_sender = close_send;
+        self.control_channel = None;
+        self.dictionaries = DictionaryNegotiation::default();
+        self.from_async_client_recv = to_sync_client_recv;
+        self.to_channels_send = to_channels_send;
+        self.from_channels_recv = from_channels_recv;
+        // Connection stats reset
+        if let Some(history) = &mut self.stats_history {
+            history.clear();
+        }
+        if let Some(monitor) = &mut self.congestion {
+            *monitor = CongestionMonitor::default();
+        }
+        self.last_max_datagram_size = None;
+        self.memory_exceeded = false;
+
+        // Open default channels
+        self.open_configured_channels(self.channels_config.clone())?;
+
+        Ok(AsyncConnectionEnds {
+            bytes_from_server_send,
+            to_sync_client_send,
+            from_channels_send,
+            to_channels_recv,
+            close_recv,
+        })
+    }
+
     pub(crate) fn open_configured_channels(
         &mut self,
         channels_config: ChannelsConfiguration,
     ) -> Result<(), AsyncChannelError> {
-        for channel_type in channels_config.configs() {
-            self.unchecked_open_channel(*channel_type)?;
+        for channel_config in channels_config.configs() {
+            self.unchecked_open_channel(channel_config.clone())?;
         }
         Ok(())
     }
 
-    /// Returns the configuration used by this connection
-    pub fn endpoint_configuration(&self) -> &ClientEndpointConfiguration {
-        &self.endpoint_config
+    /// Returns the configuration used by this connection, `None` if it was opened over a custom transport
+    pub fn endpoint_configuration(&self) -> Option<&ClientEndpointConfiguration> {
+        self.endpoint_config.as_ref()
     }
 
-    /// Returns the certificate verification configuration used by this connection
-    pub fn certificate_verification_mode(&self) -> &CertificateVerificationMode {
-        &self.cert_mode
+    /// Returns the certificate verification configuration used by this connection, `None` if it was opened over a custom transport
+    pub fn certificate_verification_mode(&self) -> Option<&CertificateVerificationMode> {
+        self.cert_mode.as_ref()
     }
 
-    /// Opens a channel of the requested [ChannelKind] and returns its [ChannelId].
+    /// Opens a channel of the requested [ChannelConfig] (or [`ChannelKind`](crate::shared::channels::ChannelKind)) and returns its [ChannelId].
     ///
     /// If no channels were previously opened, the opened channel will be the new default channel.
     ///
     /// Can fail if the Connection is closed.
-    pub fn open_channel(
+    pub fn open_channel<T: Into<ChannelConfig>>(
         &mut self,
-        channel_type: ChannelKind,
+        channel_config: T,
+    ) -> Result<ChannelId, ChannelCreationError> {
+        self.checked_open_channel(channel_config.into())
+    }
+
+    /// Same as [Self::open_channel], but payloads sent on this channel will be encrypted with the given [ChannelEncryption].
+    ///
+    /// The server must enable the same [ChannelEncryption] on the same [ChannelId] to be able to read them.
+    pub fn open_encrypted_channel<T: Into<ChannelConfig>>(
+        &mut self,
+        channel_config: T,
+        encryption: ChannelEncryption,
+    ) -> Result<ChannelId, ChannelCreationError> {
+        self.checked_open_channel(channel_config.into().encrypted(encryption))
+    }
+
+    fn checked_open_channel(
+        &mut self,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, ChannelCreationError> {
         let channel_id = match self.available_channel_ids.pop_first() {
             Some(channel_id) => channel_id,
             None => return Err(ChannelCreationError::MaxChannelsCountReached),
         };
-        Ok(self.internal_open_channel(channel_id, channel_type)?)
+        Ok(self.internal_open_channel(channel_id, channel_config)?)
     }
 
     fn unchecked_open_channel(
         &mut self,
-        channel_type: ChannelKind,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, AsyncChannelError> {
         let channel_id = self.available_channel_ids.pop_first().unwrap();
-        Ok(self.internal_open_channel(channel_id, channel_type)?)
+        self.internal_open_channel(channel_id, channel_config)
     }
 
     fn internal_open_channel(
         &mut self,
         channel_id: ChannelId,
-        channel_type: ChannelKind,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, AsyncChannelError> {
-        match self.create_channel(channel_id, channel_type) {
+        match self.create_channel(channel_id, channel_config) {
             Ok(channel_id) => {
-                if self.default_channel.is_none() {
-                    self.default_channel = Some(channel_id);
+                if self.io.default_channel().is_none() {
+                    self.io.set_default_channel(Some(channel_id));
                 }
                 Ok(channel_id)
             }
@@ -765,19 +1842,24 @@ impl ClientSideConnection {
 
     /// Closes the channel with the corresponding [ChannelId].
     ///
-    /// No new messages will be able to be sent on this channel, however, the channel will properly try to send all the messages that were previously pushed to it, according to its [ChannelKind], before fully closing.
+    /// No new messages will be able to be sent on this channel, however, the channel will properly try to send all the messages that were previously pushed to it, according to its [`ChannelKind`](crate::shared::channels::ChannelKind), before fully closing.
     ///
     /// If the closed channel is the current default channel, the default channel gets set to `None`.
     ///
     /// Can fail if the [ChannelId] is unknown, or if the channel is already closed.
     pub fn close_channel(&mut self, channel_id: ChannelId) -> Result<(), ChannelCloseError> {
-        if (channel_id as usize) < self.channels.len() {
-            match self.channels[channel_id as usize].take() {
+        let mut channels = self.io.channels_mut();
+        if (channel_id as usize) < channels.len() {
+            match channels[channel_id as usize].take() {
                 Some(channel) => {
-                    if Some(channel_id) == self.default_channel {
-                        self.default_channel = None;
+                    if Some(channel_id) == self.io.default_channel() {
+                        self.io.set_default_channel(None);
                     }
                     self.available_channel_ids.insert(channel_id);
+                    if let Ok(mut channels_configs) = self.channels_configs.write() {
+                        channels_configs.remove(&channel_id);
+                    }
+                    self.dictionaries.closed(channel_id);
                     channel.close()
                 }
                 None => Err(ChannelCloseError::ChannelAlreadyClosed),
@@ -789,21 +1871,48 @@ impl ClientSideConnection {
 
     /// Set the default channel
     pub fn set_default_channel(&mut self, channel_id: ChannelId) {
-        self.default_channel = Some(channel_id);
+        self.io.set_default_channel(Some(channel_id));
     }
 
     /// Get the default Channel Id
     pub fn get_default_channel(&self) -> Option<ChannelId> {
-        self.default_channel
+        self.io.default_channel()
     }
 
     fn create_channel(
         &mut self,
         channel_id: ChannelId,
-        channel_type: ChannelKind,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, AsyncChannelError> {
-        let (bytes_to_channel_send, bytes_to_channel_recv) =
-            mpsc::channel::<Bytes>(DEFAULT_MESSAGE_QUEUE_SIZE);
+        let channel = Some(Arc::new(
+            self.create_unregistered_channel(channel_id, &channel_config)?,
+        ));
+        self.dictionaries.opened(channel_id, &channel_config);
+        if let Ok(mut channels_configs) = self.channels_configs.write() {
+            channels_configs.insert(channel_id, channel_config);
+        }
+        let mut channels = self.io.channels_mut();
+        if (channel_id as usize) < channels.len() {
+            channels[channel_id as usize] = channel;
+        } else {
+            for _ in channels.len()..channel_id as usize {
+                channels.push(None);
+            }
+            channels.push(channel);
+        }
+
+        Ok(channel_id)
+    }
+
+    fn create_unregistered_channel(
+        &mut self,
+        channel_id: ChannelId,
+        channel_config: &ChannelConfig,
+    ) -> Result<Channel, AsyncChannelError> {
+        let queue = Arc::new(OutgoingQueue::new(
+            DEFAULT_MESSAGE_QUEUE_SIZE,
+            self.memory.clone(),
+        ));
         let (channel_close_send, channel_close_recv) =
             mpsc::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
 
@@ -811,90 +1920,376 @@ impl ClientSideConnection {
             .to_channels_send
             .try_send(ChannelSyncMessage::CreateChannel {
                 id: channel_id,
-                kind: channel_type,
-                bytes_to_channel_recv,
+                config: channel_config.clone(),
+                queue: queue.clone(),
+                buffers: self.buffer_pool.sibling(),
                 channel_close_recv,
             }) {
-            Ok(_) => {
-                let channel = Some(Channel::new(
+            Ok(_) => Ok(Channel::new(
+                channel_id,
+                channel_config,
+                queue,
+                channel_close_send,
+            )),
+            Err(err) => match err {
+                TrySendError::Full(_) => Err(AsyncChannelError::FullQueue),
+                TrySendError::Closed(_) => Err(AsyncChannelError::InternalChannelClosed),
+            },
+        }
+    }
+
+    fn send_control(&mut self, message: ControlMessage) -> Result<(), AsyncChannelError> {
+        let channel = match &self.control_channel {
+            Some(channel) => channel,
+            None => {
+                let channel = self
+                    .create_unregistered_channel(CONTROL_CHANNEL_ID, &control_channel_config())?;
+                self.control_channel.insert(channel)
+            }
+        };
+        channel.send_payload(message.encode(), None, false)
+    }
+
+    /// Handles the control messages received from the server, returns the transfer of the connection and its protocol mismatch, if any
+    pub(crate) fn handle_control_messages(
+        &mut self,
+    ) -> (
+        Option<ConnectionTransferEvent>,
+        Option<ProtocolMismatchEvent>,
+    ) {
+        let mut transfer = None;
+        let mut mismatch = None;
+        let control = self.io.incoming().take_control();
+        for payload in control {
+            match ControlMessage::decode(&payload) {
+                Some(ControlMessage::Redirect {
+                    target_addr,
+                    server_hostname,
+                    token,
+                }) => {
+                    if let Some(event) = self.follow_redirect(target_addr, server_hostname, token) {
+                        transfer = Some(event);
+                    }
+                }
+                Some(ControlMessage::Acks(ids)) => {
+                    let acked = self.acks.acknowledge(&ids);
+                    self.acked.extend(acked);
+                }
+                Some(ControlMessage::ChannelProbe {
+                    channel_id,
+                    sequence,
+                }) => {
+                    if let Err(err) = self.send_control(ControlMessage::ChannelProbeAck {
+                        channel_id,
+                        sequence,
+                    }) {
+                        error!(
+                            "Connection {} failed to answer the liveness probe of channel {}: {}",
+                            self.local_id, channel_id, err
+                        );
+                    }
+                }
+                Some(ControlMessage::ChannelProbeAck {
                     channel_id,
-                    bytes_to_channel_send,
-                    channel_close_send,
-                ));
-                if (channel_id as usize) < self.channels.len() {
-                    self.channels[channel_id as usize] = channel;
-                } else {
-                    for _ in self.channels.len()..channel_id as usize {
-                        self.channels.push(None);
+                    sequence,
+                }) => {
+                    if let Some(Some(channel)) = self.io.channels().get(channel_id as usize) {
+                        channel.answer_probe(sequence, Instant::now());
                     }
-                    self.channels.push(channel);
                 }
+                Some(ControlMessage::ChannelOpened {
+                    channel_id,
+                    dictionary_id,
+                }) => self.dictionaries.announced(channel_id, dictionary_id),
+                Some(ControlMessage::ProtocolMismatch { server_hash }) => {
+                    let local_hash = self.channels_config.protocol_hash();
+                    warn!(
+                        "Connection {}: protocol hash {:x} does not match the hash {:x} of the server",
+                        self.local_id, local_hash, server_hash
+                    );
+                    mismatch = Some(ProtocolMismatchEvent {
+                        id: self.local_id,
+                        local_hash,
+                        server_hash,
+                    });
+                }
+                _ => warn!(
+                    "Connection {} received an invalid control message",
+                    self.local_id
+                ),
+            }
+        }
+        (transfer, mismatch)
+    }
 
-                Ok(channel_id)
+    /// Acknowledges to the server the tracked messages received from it, returns the acknowledged and lost tracked messages sent to the server
+    pub(crate) fn update_acks(&mut self, now: Instant) -> Vec<QuinnetClientEvent> {
+        let acks = self.io.incoming().take_acks();
+        for ids in acks.chunks(MAX_ACKS_PER_CONTROL_MESSAGE) {
+            if let Err(err) = self.send_control(ControlMessage::Acks(ids.to_vec())) {
+                error!(
+                    "Connection {} failed to acknowledge the messages of the server: {}",
+                    self.local_id, err
+                );
+            }
+        }
+        let acked = self.acked.drain(..).map(|(channel_id, message_id)| {
+            QuinnetClientEvent::MessageAcked(MessageAckedEvent {
+                id: self.local_id,
+                channel_id,
+                message_id,
+            })
+        });
+        let lost = self
+            .acks
+            .expire(now)
+            .into_iter()
+            .map(|(channel_id, message_id)| {
+                QuinnetClientEvent::MessageLost(MessageLostEvent {
+                    id: self.local_id,
+                    channel_id,
+                    message_id,
+                })
+            });
+        acked.chain(lost).collect()
+    }
+
+    fn follow_redirect(
+        &mut self,
+        target_addr: SocketAddr,
+        server_hostname: String,
+        token: Vec<u8>,
+    ) -> Option<ConnectionTransferEvent> {
+        let Some(endpoint_config) = &mut self.endpoint_config else {
+            warn!(
+                "Connection {} can't be transferred, it does not use an endpoint configuration",
+                self.local_id
+            );
+            return None;
+        };
+        info!(
+            "Connection {} transferred to server {} ({})",
+            self.local_id, target_addr, server_hostname
+        );
+        endpoint_config.server_addr = target_addr;
+        endpoint_config.server_hostname = server_hostname.clone();
+        if let Err(err) = self.disconnect_with_code(CloseCode::Transferred) {
+            trace!("Connection {} already closed: {}", self.local_id, err);
+        }
+        self.transfer_token = Some(token);
+        if let Err(err) = self.reconnect() {
+            error!(
+                "Connection {} failed to connect to its transfer target: {}",
+                self.local_id, err
+            );
+        }
+        Some(ConnectionTransferEvent {
+            id: self.local_id,
+            target_addr,
+            server_hostname,
+        })
+    }
+
+    /// Keeps the configuration of the server which won the race of the connection, to reconnect to it
+    pub(crate) fn keep_race_winner(&mut self, winner: ClientEndpointConfiguration) {
+        self.endpoint_config = Some(winner);
+    }
+
+    /// Presents the signed header of the forwarded client to the server the connection just connected to
+    pub(crate) fn present_forwarded_client(&mut self) {
+        let Some((key, client)) = self
+            .endpoint_config
+            .as_ref()
+            .and_then(|config| config.forwarding.as_ref())
+        else {
+            return;
+        };
+        if client.identity().len() > MAX_FORWARDED_IDENTITY_LEN {
+            error!(
+                "Connection {} failed to forward its client: identity of {} bytes",
+                self.local_id,
+                client.identity().len()
+            );
+            return;
+        }
+        let header = key.sign(client);
+        if let Err(err) = self.send_control(ControlMessage::ForwardedClient(header)) {
+            error!(
+                "Connection {} failed to forward its client: {}",
+                self.local_id, err
+            );
+        }
+    }
+
+    /// Presents the protocol hash of the connection to the server it just connected to, see [`crate::server::Endpoint::set_protocol_check`]
+    pub(crate) fn present_protocol_hash(&mut self) {
+        let hash = self.channels_config.protocol_hash();
+        if let Err(err) = self.send_control(ControlMessage::ProtocolHash(hash)) {
+            error!(
+                "Connection {} failed to present its protocol hash: {}",
+                self.local_id, err
+            );
+        }
+    }
+
+    /// Presents the token of a transfer to the server the connection just connected to
+    pub(crate) fn present_transfer_token(&mut self) {
+        if let Some(token) = self.transfer_token.take() {
+            if let Err(err) = self.send_control(ControlMessage::PresentToken(token)) {
+                error!(
+                    "Connection {} failed to present its transfer token: {}",
+                    self.local_id, err
+                );
             }
-            Err(err) => match err {
-                TrySendError::Full(_) => Err(AsyncChannelError::FullQueue),
-                TrySendError::Closed(_) => Err(AsyncChannelError::InternalChannelClosed),
-            },
         }
     }
 }
 
-pub(crate) async fn async_connection_task(
+/// Connects to a server over QUIC and returns the connection with the local address of its endpoint
+pub(crate) async fn connect_quic(
     local_id: ConnectionLocalId,
     endpoint_config: ClientEndpointConfiguration,
     cert_mode: CertificateVerificationMode,
     to_sync_client_send: ClientAsyncMsgSend,
-    bytes_from_server_send: MessageSend,
-    to_channels_recv: ChannelSyncMsgRecv,
-    from_channels_send: ChannelAsyncMsgSend,
-    close_recv: CloseRecv,
-) {
+) -> Result<(quinn::Connection, Option<SocketAddr>), QuinnetConnectionError> {
     info!(
         "Connection {} trying to connect to server on: {} ...",
         local_id, endpoint_config.server_addr
     );
 
-    let client_cfg = configure_client(cert_mode, to_sync_client_send.clone())
+    let endpoint = match &endpoint_config.endpoint {
+        Some(endpoint) => endpoint.clone(),
+        None => endpoint_config.bind_endpoint()?,
+    };
+    let client_cfg = configure_client(cert_mode, &endpoint_config, to_sync_client_send)
         .expect("Failed to configure client");
 
-    let mut endpoint = Endpoint::client(endpoint_config.local_bind_addr)
-        .expect("Failed to create client endpoint");
-    endpoint.set_default_client_config(client_cfg);
+    let local_addr = endpoint
+        .local_addr()
+        .expect("Failed to retrieve the client endpoint local address");
 
     let connection = endpoint
-        .connect(
+        .connect_with(
+            client_cfg,
             endpoint_config.server_addr,
             &endpoint_config.server_hostname,
         )
         .expect("Failed to connect: configuration error")
+        .await?;
+    Ok((connection, Some(local_addr)))
+}
+
+/// Connects concurrently to the servers of `endpoint_configs` and returns the connection of the first one to finish its handshake, dropping the other attempts aborts them.
+///
+/// Reports the attempts to the sync client before returning.
+pub(crate) async fn race_connect_quic(
+    local_id: ConnectionLocalId,
+    endpoint_configs: Vec<ClientEndpointConfiguration>,
+    cert_mode: CertificateVerificationMode,
+    to_sync_client_send: ClientAsyncMsgSend,
+) -> Result<(quinn::Connection, Option<SocketAddr>), QuinnetConnectionError> {
+    let start = Instant::now();
+    let mut attempts: Vec<_> = endpoint_configs
+        .iter()
+        .map(|config| RaceAttempt {
+            server_addr: config.server_addr,
+            outcome: RaceOutcome::Aborted,
+        })
+        .collect();
+    let mut pending: FuturesUnordered<_> = endpoint_configs
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(index, config)| {
+            let connect = connect_quic(
+                local_id,
+                config,
+                cert_mode.clone(),
+                to_sync_client_send.clone(),
+            );
+            async move { (index, connect.await) }
+        })
+        .collect();
+
+    let mut winner = None;
+    while let Some((index, result)) = pending.next().await {
+        match result {
+            Ok(connected) => {
+                attempts[index].outcome = RaceOutcome::Won {
+                    handshake: start.elapsed(),
+                };
+                winner = Some((index, connected));
+                break;
+            }
+            Err(err) => attempts[index].outcome = RaceOutcome::Failed(err),
+        }
+    }
+    drop(pending);
+
+    match &winner {
+        Some((index, _)) => info!(
+            "Connection {} won the race with server {} in {:?}",
+            local_id,
+            endpoint_configs[*index].server_addr,
+            start.elapsed()
+        ),
+        None => warn!(
+            "Connection {} could not connect to any of its {} raced servers",
+            local_id,
+            endpoint_configs.len()
+        ),
+    }
+    // The sync client may already be gone, the race result is then dropped with it
+    let _ = to_sync_client_send
+        .send(ClientAsyncMessage::RaceFinished {
+            winner: winner
+                .as_ref()
+                .map(|(index, _)| Box::new(endpoint_configs[*index].clone())),
+            attempts,
+        })
         .await;
-    match connection {
+    winner
+        .map(|(_, connected)| connected)
+        .ok_or(QuinnetConnectionError::NoRaceWinner)
+}
+
+/// Waits for `connect` to establish the transport connection, then runs the channels tasks on it
+pub(crate) async fn async_connection_task<C: TransportConnection>(
+    local_id: ConnectionLocalId,
+    connect: impl Future<Output = Result<(C, Option<SocketAddr>), QuinnetConnectionError>>,
+    ends: AsyncConnectionEnds,
+    channels_configs: SharedChannelConfigs,
+    server_tick: SharedNetworkTick,
+) {
+    let AsyncConnectionEnds {
+        bytes_from_server_send,
+        to_sync_client_send,
+        from_channels_send,
+        to_channels_recv,
+        close_recv,
+    } = ends;
+    match connect.await {
         Err(e) => {
             error!("Connection {}, error while connecting: {}", local_id, e);
-            // Signal connection failure
-            to_sync_client_send
-                .send(ClientAsyncMessage::ConnectionFailed(
-                    QuinnetConnectionError::from(e),
-                ))
-                .await
-                .expect("Failed to signal connection failure to sync client");
-        }
-        Ok(connection_handle) => {
+            // Signal connection failure, unless the sync client is already gone
+            let _ = to_sync_client_send
+                .send(ClientAsyncMessage::ConnectionFailed(e))
+                .await;
+        }
+        Ok((connection_handle, local_addr)) => {
             // Spawn a task to listen for the underlying connection being closed
             {
                 let conn = connection_handle.clone();
                 let to_sync_client = to_sync_client_send.clone();
                 tokio::spawn(async move {
-                    let _conn_err = conn.closed().await;
-                    info!("Connection {} closed: {}", local_id, _conn_err);
-                    // If we requested the connection to close, channel may have been closed already.
-                    if !to_sync_client.is_closed() {
-                        to_sync_client
-                            .send(ClientAsyncMessage::ConnectionClosed)
-                            .await
-                            .expect("Failed to signal connection closed in async connection");
-                    }
+                    let conn_err = conn.closed().await;
+                    info!("Connection {} closed: {}", local_id, conn_err);
+                    // If we requested the connection to close, or the sync client was dropped, channel may have been closed already.
+                    let _ = to_sync_client
+                        .send(ClientAsyncMessage::ConnectionClosed(peer_close_code(
+                            &conn_err,
+                        )))
+                        .await;
                 })
             };
 
@@ -903,13 +2298,21 @@ pub(crate) async fn async_connection_task(
                 local_id,
                 close_recv.resubscribe(),
                 bytes_from_server_send,
+                channels_configs,
+                ReceiveHardening::lenient(),
+                server_tick,
             );
 
+            let to_sync_client = to_sync_client_send.clone();
             spawn_send_channels_tasks_spawner(
                 connection_handle.clone(),
+                local_id,
                 close_recv.resubscribe(),
                 to_channels_recv,
                 from_channels_send,
+                Box::new(move |stage, elapsed| {
+                    let _ = to_sync_client.try_send(ClientAsyncMessage::CloseStage(stage, elapsed));
+                }),
             );
 
             #[cfg(not(feature = "shared-client-id"))]
@@ -917,6 +2320,7 @@ pub(crate) async fn async_connection_task(
                 connection_handle.clone(),
                 local_id,
                 None,
+                local_addr,
                 to_sync_client_send,
             )
             .await;
@@ -928,6 +2332,7 @@ pub(crate) async fn async_connection_task(
                         connection_handle.clone(),
                         local_id,
                         Some(client_id),
+                        local_addr,
                         to_sync_client_send,
                     )
                     .await
@@ -937,11 +2342,10 @@ pub(crate) async fn async_connection_task(
                         "Connection {}, error while retrieving client_id: {}",
                         local_id, e
                     );
-                    // Signal connection failure
-                    to_sync_client_send
+                    // Signal connection failure, unless the sync client is already gone
+                    let _ = to_sync_client_send
                         .send(ClientAsyncMessage::ConnectionFailed(e))
-                        .await
-                        .expect("Failed to signal connection failure to sync client");
+                        .await;
                 }
                 client_id::ClientIdReception::Interrupted => trace!(
                     "Connection {}, reception of client_id was interrupted",
@@ -952,38 +2356,83 @@ pub(crate) async fn async_connection_task(
     }
 }
 
-async fn signal_connection(
-    connection_handle: quinn::Connection,
+async fn signal_connection<C: TransportConnection>(
+    connection_handle: C,
     connection_id: ConnectionLocalId,
     client_id: Option<ClientId>,
+    local_addr: Option<SocketAddr>,
     to_sync_client_send: mpsc::Sender<ClientAsyncMessage>,
 ) {
     // Signal connection
-    to_sync_client_send
+    if to_sync_client_send
         .send(ClientAsyncMessage::Connected(
-            connection_handle.clone(),
+            Arc::new(connection_handle.clone()),
             client_id,
+            local_addr,
         ))
         .await
-        .expect("Failed to signal connection to sync client");
+        .is_err()
+    {
+        // The sync client was dropped while connecting
+        connection_handle.close(CloseCode::Closed);
+        return;
+    }
 
     info!(
         "Connection {} connected to {} with client_id {:?}",
         connection_id,
-        connection_handle.remote_address(),
+        display_remote(&connection_handle),
         client_id
     );
 }
 
 fn configure_client(
     cert_mode: CertificateVerificationMode,
+    endpoint_config: &ClientEndpointConfiguration,
     to_sync_client: mpsc::Sender<ClientAsyncMessage>,
 ) -> Result<ClientConfig, Box<dyn Error>> {
-    let mut crypto = match cert_mode {
+    let crypto = match &endpoint_config.tls_config {
+        Some(tls_config) => {
+            let mut crypto = rustls::ClientConfig::clone(tls_config);
+            if crypto.alpn_protocols.is_empty() {
+                crypto.alpn_protocols = endpoint_config.alpn_protocols.clone();
+            }
+            crypto
+        }
+        None => {
+            let mut crypto = client_crypto(
+                cert_mode,
+                endpoint_config.server_addr.port(),
+                endpoint_config.alpn_protocols.clone(),
+                endpoint_config.client_certificate.clone(),
+                to_sync_client,
+            )?;
+            if endpoint_config.key_log {
+                crypto.key_log = Arc::new(rustls::KeyLogFile::new());
+            }
+            crypto
+        }
+    };
+
+    let mut client_config = ClientConfig::new(Arc::new(QuicClientConfig::try_from(crypto)?));
+    let mut transport = endpoint_config.transport.to_transport_config();
+    endpoint_config.socket.configure_transport(&mut transport);
+    client_config.transport_config(Arc::new(transport));
+    Ok(client_config)
+}
+
+/// TLS configuration of the connections without a custom one, see [`ClientEndpointConfigurationBuilder::with_tls_config`]
+fn client_crypto(
+    cert_mode: CertificateVerificationMode,
+    server_port: u16,
+    alpn_protocols: Vec<Vec<u8>>,
+    client_certificate: Option<ClientCertificate>,
+    to_sync_client: mpsc::Sender<ClientAsyncMessage>,
+) -> Result<rustls::ClientConfig, Box<dyn Error>> {
+    let builder = match cert_mode {
         CertificateVerificationMode::SkipVerification => rustls::ClientConfig::builder()
             .dangerous()
-            .with_custom_certificate_verifier(SkipServerVerification::new())
-            .with_no_client_auth(),
+            .with_custom_certificate_verifier(SkipServerVerification::new()),
         CertificateVerificationMode::SignedByCertificateAuthority => {
             // Using Quinn's helper `ClientConfig::with_platform_verifier` does not let us specify the CryptoProvider used,
             // and relies on the per-process default one (https://docs.rs/rustls/latest/rustls/crypto/struct.CryptoProvider.html#using-the-per-process-default-cryptoprovider) which may not be set.
@@ -995,27 +2444,36 @@ fn configure_client(
             .unwrap()
             // We use `rustls-platform-verifier::with_platform_verifier` directly instead (used internally by Quinn).
             .with_platform_verifier()
-            .with_no_client_auth()
-        }
-        CertificateVerificationMode::TrustOnFirstUse(config) => {
-            let (store, store_file) = load_known_hosts_store_from_config(config.known_hosts)?;
-            rustls::ClientConfig::builder()
-                .dangerous()
-                .with_custom_certificate_verifier(TofuServerVerification::new(
-                    store,
-                    config.verifier_behaviour,
-                    to_sync_client,
-                    store_file,
-                    Arc::new(rustls::crypto::ring::default_provider()),
-                ))
-                .with_no_client_auth()
+        }
+        CertificateVerificationMode::TrustOnFirstUse(config) => rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(TofuServerVerification::new(
+                config,
+                server_port,
+                to_sync_client,
+                Arc::new(rustls::crypto::ring::default_provider()),
+            )?),
+        CertificateVerificationMode::SignedBy(authorities) => {
+            let mut roots = rustls::RootCertStore::empty();
+            for authority in authorities {
+                roots.add(authority)?;
+            }
+            rustls::ClientConfig::builder_with_provider(
+                rustls::crypto::ring::default_provider().into(),
+            )
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .unwrap()
+            .with_root_certificates(roots)
         }
     };
+    let mut crypto = match client_certificate {
+        Some(client_certificate) => builder
+            .with_client_auth_cert(client_certificate.cert_chain, client_certificate.priv_key)?,
+        None => builder.with_no_client_auth(),
+    };
 
     // Quinn defaults to true
     crypto.enable_early_data = true;
-
-    Ok(ClientConfig::new(Arc::new(QuicClientConfig::try_from(
-        crypto,
-    )?)))
+    crypto.alpn_protocols = alpn_protocols;
+    Ok(crypto)
 }
@@ -5,7 +5,7 @@ use tokio_util::codec::{FramedRead, LengthDelimitedCodec};
 
 use crate::{
     client::QuinnetConnectionError,
-    shared::{ClientId, CLIENT_ID_LEN},
+    shared::{close::peer_close_code, transport::TransportConnection, ClientId, CLIENT_ID_LEN},
 };
 
 use super::CloseRecv;
@@ -16,8 +16,8 @@ pub(crate) enum ClientIdReception {
     Failed(QuinnetConnectionError),
 }
 
-pub(crate) async fn receive_client_id(
-    connection_handle: quinn::Connection,
+pub(crate) async fn receive_client_id<C: TransportConnection>(
+    connection_handle: C,
     mut close_recv: CloseRecv,
 ) -> ClientIdReception {
     let mut client_id = None;
@@ -28,14 +28,21 @@ pub(crate) async fn receive_client_id(
             ClientIdReception::Interrupted
         }
         _ = async {
-            if let Ok((_, recv)) = connection_handle.accept_bi().await {
-                let mut frame_recv = FramedRead::new(recv, LengthDelimitedCodec::new());
-                if let Some(Ok(mut msg_bytes)) = frame_recv.next().await {
-                    if msg_bytes.len() >= CLIENT_ID_LEN {
-                        let client_id_value = msg_bytes.get_uint(CLIENT_ID_LEN);
-                        client_id =  Some(client_id_value);
-                    } else {
-                        err = QuinnetConnectionError::InvalidClientId;
+            match connection_handle.accept_bi().await {
+                Err(transport_err) => {
+                    if let Some(code) = peer_close_code(&transport_err) {
+                        err = QuinnetConnectionError::ConnectionClosed(code);
+                    }
+                }
+                Ok(recv) => {
+                    let mut frame_recv = FramedRead::new(recv, LengthDelimitedCodec::new());
+                    if let Some(Ok(mut msg_bytes)) = frame_recv.next().await {
+                        if msg_bytes.len() >= CLIENT_ID_LEN {
+                            let client_id_value = msg_bytes.get_uint(CLIENT_ID_LEN);
+                            client_id =  Some(client_id_value);
+                        } else {
+                            err = QuinnetConnectionError::InvalidClientId;
+                        }
                     }
                 }
             }
@@ -1,6 +1,9 @@
-use std::sync::PoisonError;
+use std::{net::SocketAddr, sync::PoisonError, time::Duration};
 
-use crate::shared::{channels::ChannelId, error::AsyncChannelError};
+use crate::shared::{
+    channels::ChannelId,
+    error::{AsyncChannelError, InviteCodeError},
+};
 
 use super::connection::ConnectionLocalId;
 
@@ -16,6 +19,17 @@ pub enum ClientSendError {
     /// A channel is closed
     #[error("Channel is closed")]
     ChannelClosed,
+    /// A payload exceeds the maximum message size of its channel
+    #[error("Payload of {size} bytes exceeds the maximum message size of the channel ({max_message_size} bytes)")]
+    PayloadTooLarge {
+        /// Size of the payload
+        size: usize,
+        /// Maximum message size of the channel
+        max_message_size: usize,
+    },
+    /// A channel does not acknowledge its messages, see [`crate::shared::channels::ChannelConfig::acknowledged`]
+    #[error("Channel with id `{0}` does not acknowledge its messages")]
+    ChannelNotAcknowledged(ChannelId),
     /// Quinnet async channel error
     #[error("Quinnet async channel error")]
     ChannelSendError(#[from] AsyncChannelError),
@@ -73,11 +87,87 @@ pub enum ClientConnectionCloseError {
     InvalidConnectionId(ConnectionLocalId),
 }
 
+/// Error when building a [`super::connection::ClientEndpointConfiguration`], see [`super::connection::ClientEndpointConfigurationBuilder::build`]
+#[derive(thiserror::Error, Debug)]
+pub enum ClientConfigurationError {
+    /// No server address or host was given
+    #[error("No server address or host was given")]
+    MissingServerAddress,
+    /// The server host could not be resolved to an address reachable from the local bind address
+    #[error("Server host `{0}` could not be resolved")]
+    UnresolvedServerHost(String),
+    /// The server name is not a valid DNS name or IP address
+    #[error("Server name `{0}` is not a valid DNS name or IP address")]
+    InvalidServerName(String),
+    /// An IPv6 server can't be reached from an IPv4 local bind address
+    #[error("Server address `{server_addr}` can't be reached from local bind address `{local_bind_addr}`")]
+    AddressFamilyMismatch {
+        /// Address of the server
+        server_addr: SocketAddr,
+        /// Local bind address
+        local_bind_addr: SocketAddr,
+    },
+    /// A local bind address was given along with an existing endpoint, which is already bound
+    #[error("A local bind address can't be used along with an existing endpoint")]
+    LocalBindWithEndpoint,
+    /// An ALPN protocol is empty or longer than 255 bytes
+    #[error("ALPN protocol of {0} bytes is invalid, it must be 1 to 255 bytes long")]
+    InvalidAlpnProtocol(usize),
+    /// The idle timeout exceeds the maximum supported by QUIC
+    #[error("Idle timeout of {0:?} is too large")]
+    IdleTimeoutTooLarge(Duration),
+    /// The keep-alive interval does not leave time for a keep-alive before the idle timeout
+    #[error("Keep-alive interval of {keep_alive_interval:?} must be shorter than the idle timeout of {idle_timeout:?}")]
+    KeepAliveNotBelowIdleTimeout {
+        /// Keep-alive interval
+        keep_alive_interval: Duration,
+        /// Idle timeout
+        idle_timeout: Duration,
+    },
+    /// The initial round-trip time estimate is zero
+    #[error("Initial round-trip time must not be zero")]
+    ZeroInitialRtt,
+    /// A datagram buffer size is zero, which would disable the unreliable channels
+    #[error("Datagram buffer sizes must not be zero")]
+    ZeroDatagramBuffer,
+    /// The maximum UDP payload size of the socket is out of bounds, see [`crate::shared::socket::SocketConfiguration::with_max_udp_payload_size`]
+    #[error("Max UDP payload size of {0} bytes is out of bounds")]
+    InvalidMaxUdpPayloadSize(u16),
+}
+
+/// Error while opening a connection from an invite code, see [`super::QuinnetClient::open_connection_from_invite`]
+#[derive(thiserror::Error, Debug)]
+pub enum InviteConnectionError {
+    /// The invite code is invalid
+    #[error("Invalid invite code: {0}")]
+    InvalidInvite(#[from] InviteCodeError),
+    /// The endpoint configuration could not be built from the invite
+    #[error("Invalid configuration: {0}")]
+    Configuration(#[from] ClientConfigurationError),
+    /// Quinnet async channel error
+    #[error("Quinnet async channel error")]
+    AsyncChannelError(#[from] AsyncChannelError),
+}
+
 #[derive(thiserror::Error, Debug)]
 /// An host file is invalid
 #[error("The hosts file is invalid")]
 pub struct InvalidHostFile;
 
+/// Error while reading or writing a known hosts file
+#[derive(thiserror::Error, Debug)]
+pub enum KnownHostsError {
+    /// The file could not be read or written
+    #[error("Known hosts file IO error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file content is invalid
+    #[error("The hosts file is invalid")]
+    InvalidHostFile(#[from] InvalidHostFile),
+    /// The file could not be encrypted or decrypted
+    #[error("The hosts file could not be encrypted or decrypted")]
+    Encryption,
+}
+
 /// Error while applying a certificate action
 #[derive(thiserror::Error, Debug)]
 pub enum CertificateInteractionError {
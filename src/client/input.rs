@@ -0,0 +1,86 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::shared::{
+    channels::ChannelId,
+    input::{InputTick, DEFAULT_INPUT_HISTORY},
+};
+
+use super::{connection::ClientSideConnection, ClientMessageSendError};
+
+/// Stream of the inputs of a client, one per tick, sent to the server on an unreliable channel.
+///
+/// Each message carries the inputs of the last ticks, so that a lost message is covered by the next ones without waiting for a retransmission. The server side is [`crate::server::input::InputReceiver`], which dedupes them.
+///
+/// The inputs of consecutive ticks being similar, a compressed channel ([`crate::shared::channels::ChannelConfig::compressed`]) keeps the messages small.
+#[derive(Resource, Debug)]
+pub struct InputStream<T> {
+    channel_id: ChannelId,
+    history: usize,
+    last_tick: Option<InputTick>,
+    /// Inputs of the ticks ending at `last_tick`, oldest first
+    inputs: VecDeque<T>,
+}
+
+impl<T: Serialize> InputStream<T> {
+    /// Stream sending its inputs on `channel_id`, carrying the inputs of the last [`DEFAULT_INPUT_HISTORY`] ticks in each message
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            history: DEFAULT_INPUT_HISTORY,
+            last_tick: None,
+            inputs: VecDeque::new(),
+        }
+    }
+
+    /// Carries the inputs of the last `ticks` ticks in each message, at least 1
+    pub fn with_history(mut self, ticks: usize) -> Self {
+        self.history = ticks.max(1);
+        self
+    }
+
+    /// Channel the inputs are sent on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Number of ticks of inputs carried by each message
+    pub fn history(&self) -> usize {
+        self.history
+    }
+
+    /// Tick of the last input sent, if any
+    pub fn last_tick(&self) -> Option<InputTick> {
+        self.last_tick
+    }
+
+    /// Sends the input of `tick` on the stream's channel, along with the inputs of the previous ticks.
+    ///
+    /// A tick not following the last tick sent restarts the history: the inputs of the missing ticks are unknown to the server.
+    ///
+    /// Will return an [`Err`] if the message can't be sent, see [`ClientSideConnection::send_message_on`]. The input is kept in the history either way.
+    pub fn send(
+        &mut self,
+        connection: &mut ClientSideConnection,
+        tick: InputTick,
+        input: T,
+    ) -> Result<(), ClientMessageSendError> {
+        if self.last_tick.and_then(|last| last.checked_add(1)) != Some(tick) {
+            self.inputs.clear();
+        }
+        if self.inputs.len() == self.history {
+            self.inputs.pop_front();
+        }
+        self.inputs.push_back(input);
+        self.last_tick = Some(tick);
+        connection.send_message_on(self.channel_id, (tick, &self.inputs))
+    }
+
+    /// Forgets the inputs sent, the next input restarts the history
+    pub fn reset(&mut self) {
+        self.last_tick = None;
+        self.inputs.clear();
+    }
+}
@@ -0,0 +1,136 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::shared::{
+    channels::ChannelId,
+    lockstep::{LockstepBundle, LockstepTick},
+    QuinnetSyncUpdate,
+};
+
+use super::{
+    connection::ClientSideConnection, ClientMessageSendError, ConnectionClosed, QuinnetClient,
+};
+
+/// Client side of a lockstep simulation, on a channel: sends the inputs of the client for each tick to the [`crate::server::lockstep::LockstepServer`] and receives the [`LockstepBundle`]s of all the clients' inputs.
+///
+/// The simulation should only be stepped to a tick once its bundle is received. The channel must be reliable and ordered, and dedicated to the lockstep simulation.
+#[derive(Resource, Debug)]
+pub struct LockstepClient<T> {
+    channel_id: ChannelId,
+    last_tick: Option<LockstepTick>,
+    _input: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> LockstepClient<T> {
+    /// Lockstep simulation on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            last_tick: None,
+            _input: PhantomData,
+        }
+    }
+
+    /// Channel the inputs are sent and the bundles are received on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Tick of the last bundle received, if any
+    pub fn last_tick(&self) -> Option<LockstepTick> {
+        self.last_tick
+    }
+
+    /// Sends the input of the client for `tick`. Only the first input sent for a tick is used by the server, and inputs for ticks already released are dropped.
+    ///
+    /// Will return an [`Err`] if the message can't be sent, see [`ClientSideConnection::send_message_on`].
+    pub fn send(
+        &self,
+        connection: &mut ClientSideConnection,
+        tick: LockstepTick,
+        input: &T,
+    ) -> Result<(), ClientMessageSendError> {
+        connection.send_message_on(self.channel_id, (tick, input))
+    }
+
+    /// Receives the bundles released by the server since the last call, in tick order.
+    ///
+    /// Messages that can't be deserialized are dropped. Will return an [`Err`] if the connection is closed.
+    pub fn receive(
+        &mut self,
+        connection: &mut ClientSideConnection,
+    ) -> Result<Vec<LockstepBundle<T>>, ConnectionClosed> {
+        let bundles: Vec<LockstepBundle<T>> = connection
+            .receive_all_on(self.channel_id)?
+            .filter_map(|payload| bincode::deserialize(&payload).ok())
+            .collect();
+        if let Some(bundle) = bundles.last() {
+            self.last_tick = Some(bundle.tick);
+        }
+        Ok(bundles)
+    }
+
+    /// Forgets the last tick received, such as when reconnecting
+    pub fn reset(&mut self) {
+        self.last_tick = None;
+    }
+}
+
+/// Runs the client side of a lockstep simulation on a channel with a [`LockstepClient`] resource, and raises a [`LockstepBundle`] event for each bundle received on the default connection.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default. In an app also running the [`crate::server::lockstep::QuinnetServerLockstepPlugin`] for the same input type, the bundles of both are raised.
+pub struct QuinnetClientLockstepPlugin<T> {
+    channel_id: ChannelId,
+    schedule: InternedScheduleLabel,
+    _input: PhantomData<fn() -> T>,
+}
+
+impl<T> QuinnetClientLockstepPlugin<T> {
+    /// Plugin running the lockstep simulation on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            schedule: PreUpdate.intern(),
+            _input: PhantomData,
+        }
+    }
+
+    /// Receives the bundles in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + 'static> Plugin
+    for QuinnetClientLockstepPlugin<T>
+{
+    fn build(&self, app: &mut App) {
+        app.add_event::<LockstepBundle<T>>()
+            .insert_resource(LockstepClient::<T>::new(self.channel_id))
+            .add_systems(
+                self.schedule,
+                receive_lockstep_bundles::<T>
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetClient>),
+            );
+    }
+}
+
+fn receive_lockstep_bundles<T: Serialize + DeserializeOwned + Send + Sync + 'static>(
+    mut client: ResMut<QuinnetClient>,
+    mut lockstep: ResMut<LockstepClient<T>>,
+    mut bundle_events: EventWriter<LockstepBundle<T>>,
+) {
+    let Some(connection) = client.get_connection_mut() else {
+        return;
+    };
+    if let Ok(bundles) = lockstep.receive(connection) {
+        bundle_events.write_batch(bundles);
+    }
+}
@@ -0,0 +1,217 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use crate::shared::{
+    channels::ChannelId,
+    master::{
+        MasterRequest, MasterResponse, ServerInfo, ServerListing, DEFAULT_MASTER_HEARTBEAT_INTERVAL,
+    },
+    QuinnetSyncUpdate,
+};
+
+use super::{
+    connection::{ConnectionLocalId, ConnectionState},
+    ClientMessageSendError, ClientSendError, ConnectionClosed, QuinnetClient,
+};
+
+/// Raised when a [`ServerBrowser`] received the list of the servers. Raised in the CoreStage::PreUpdate stage by the [`QuinnetMasterClientPlugin`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ServerListReceivedEvent {
+    /// Servers listed by the master server
+    pub listings: Vec<ServerListing>,
+}
+
+/// Registration of a game server to a [`crate::server::master::MasterServer`], over a connection of a Quinnet client opened by the game server to the master server.
+///
+/// The info of the server is sent when the connection is established and then at each heartbeat, with its latest changes, see [`MasterRegistration::info_mut`]. Updated by the [`QuinnetMasterClientPlugin`] when inserted as a resource.
+#[derive(Resource, Debug)]
+pub struct MasterRegistration {
+    connection_id: ConnectionLocalId,
+    channel_id: ChannelId,
+    info: ServerInfo,
+    heartbeat_interval: Duration,
+    last_heartbeat: Option<Instant>,
+}
+
+impl MasterRegistration {
+    /// Registers `info` on `channel_id` of the connection `connection_id` to the master server, with a heartbeat every [`DEFAULT_MASTER_HEARTBEAT_INTERVAL`]
+    pub fn new<C: Into<ChannelId>>(
+        connection_id: ConnectionLocalId,
+        channel_id: C,
+        info: ServerInfo,
+    ) -> Self {
+        Self {
+            connection_id,
+            channel_id: channel_id.into(),
+            info,
+            heartbeat_interval: DEFAULT_MASTER_HEARTBEAT_INTERVAL,
+            last_heartbeat: None,
+        }
+    }
+
+    /// Sends a heartbeat every `heartbeat_interval`, which should be shorter than the listing timeout of the master server
+    pub fn with_heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    /// Connection to the master server
+    pub fn connection_id(&self) -> ConnectionLocalId {
+        self.connection_id
+    }
+
+    /// Registered info of the server
+    pub fn info(&self) -> &ServerInfo {
+        &self.info
+    }
+
+    /// Registered info of the server as mut, such as to update its number of players. The changes are sent with the next heartbeat
+    pub fn info_mut(&mut self) -> &mut ServerInfo {
+        &mut self.info
+    }
+
+    /// Registers the server once its connection is established, and sends the heartbeats when due. Registers again after a reconnection.
+    pub fn update(&mut self, client: &mut QuinnetClient) {
+        let Some(connection) = client
+            .get_connection_mut_by_id(self.connection_id)
+            .filter(|connection| connection.state() == ConnectionState::Connected)
+        else {
+            self.last_heartbeat = None;
+            return;
+        };
+        if self
+            .last_heartbeat
+            .is_some_and(|last_heartbeat| last_heartbeat.elapsed() < self.heartbeat_interval)
+        {
+            return;
+        }
+        self.last_heartbeat = Some(Instant::now());
+        connection.try_send_message_on(self.channel_id, MasterRequest::Register(self.info.clone()));
+    }
+
+    /// Removes the server from the list of the master server
+    pub fn unregister(&mut self, client: &mut QuinnetClient) -> Result<(), ClientMessageSendError> {
+        let connection = client
+            .get_connection_mut_by_id(self.connection_id)
+            .ok_or(ClientSendError::ConnectionClosed)?;
+        connection.send_message_on(self.channel_id, MasterRequest::Unregister)
+    }
+}
+
+/// Browser of the servers listed by a [`crate::server::master::MasterServer`], over a connection of a Quinnet client to the master server.
+///
+/// Updated by the [`QuinnetMasterClientPlugin`] when inserted as a resource.
+#[derive(Resource, Debug)]
+pub struct ServerBrowser {
+    connection_id: ConnectionLocalId,
+    channel_id: ChannelId,
+    listings: Vec<ServerListing>,
+}
+
+impl ServerBrowser {
+    /// Browser querying the master server on `channel_id` of the connection `connection_id`
+    pub fn new<C: Into<ChannelId>>(connection_id: ConnectionLocalId, channel_id: C) -> Self {
+        Self {
+            connection_id,
+            channel_id: channel_id.into(),
+            listings: Vec::new(),
+        }
+    }
+
+    /// Connection to the master server
+    pub fn connection_id(&self) -> ConnectionLocalId {
+        self.connection_id
+    }
+
+    /// Servers of the last list received
+    pub fn listings(&self) -> &[ServerListing] {
+        &self.listings
+    }
+
+    /// Asks the master server for the list of the servers
+    pub fn refresh(&self, client: &mut QuinnetClient) -> Result<(), ClientMessageSendError> {
+        let connection = client
+            .get_connection_mut_by_id(self.connection_id)
+            .ok_or(ClientSendError::ConnectionClosed)?;
+        connection.send_message_on(self.channel_id, MasterRequest::Query)
+    }
+
+    /// Receives the lists sent by the master server. Returns the last list received since the last call, if any.
+    ///
+    /// Messages that can't be deserialized are dropped. Will return an [`Err`] if the connection is closed.
+    pub fn receive(
+        &mut self,
+        client: &mut QuinnetClient,
+    ) -> Result<Option<Vec<ServerListing>>, ConnectionClosed> {
+        let connection = client
+            .get_connection_mut_by_id(self.connection_id)
+            .ok_or(ConnectionClosed)?;
+        let Some(MasterResponse::List(listings)) = connection
+            .receive_all_on(self.channel_id)?
+            .filter_map(|payload| bincode::deserialize(&payload).ok())
+            .last()
+        else {
+            return Ok(None);
+        };
+        self.listings.clone_from(&listings);
+        Ok(Some(listings))
+    }
+}
+
+/// Updates the [`MasterRegistration`] and [`ServerBrowser`] resources when they exist, and raises a [`ServerListReceivedEvent`] for each list received.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetMasterClientPlugin {
+    schedule: InternedScheduleLabel,
+}
+
+impl Default for QuinnetMasterClientPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: PreUpdate.intern(),
+        }
+    }
+}
+
+impl QuinnetMasterClientPlugin {
+    /// Updates the resources in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetMasterClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ServerListReceivedEvent>().add_systems(
+            self.schedule,
+            (
+                update_master_registration.run_if(resource_exists::<MasterRegistration>),
+                receive_server_list.run_if(resource_exists::<ServerBrowser>),
+            )
+                .after(QuinnetSyncUpdate)
+                .run_if(resource_exists::<QuinnetClient>),
+        );
+    }
+}
+
+fn update_master_registration(
+    mut client: ResMut<QuinnetClient>,
+    mut registration: ResMut<MasterRegistration>,
+) {
+    registration.update(&mut client);
+}
+
+fn receive_server_list(
+    mut client: ResMut<QuinnetClient>,
+    mut browser: ResMut<ServerBrowser>,
+    mut list_events: EventWriter<ServerListReceivedEvent>,
+) {
+    if let Ok(Some(listings)) = browser.receive(&mut client) {
+        list_events.write(ServerListReceivedEvent { listings });
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::{
+    math::{Quat, Vec2, Vec3},
+    prelude::*,
+};
+
+/// Tick of the server a snapshot was taken at
+pub type SnapshotTick = u32;
+
+/// Default number of snapshots kept by a [`SnapshotBuffer`]
+pub const DEFAULT_SNAPSHOT_CAPACITY: usize = 32;
+/// Default delay of the rendering behind the server, in ticks, see [`SnapshotBuffer::with_interpolation_delay`]
+pub const DEFAULT_INTERPOLATION_DELAY_TICKS: f64 = 2.;
+
+/// State which can be blended between two snapshots
+pub trait Interpolate {
+    /// Returns the state at `t` between `self` (0) and `to` (1)
+    fn interpolate(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self + (to - self) * t as f64
+    }
+}
+
+impl Interpolate for Vec2 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(*to, t)
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(*to, t)
+    }
+}
+
+impl Interpolate for Quat {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.slerp(*to, t)
+    }
+}
+
+impl Interpolate for Transform {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        Transform {
+            translation: self.translation.interpolate(&to.translation, t),
+            rotation: self.rotation.interpolate(&to.rotation, t),
+            scale: self.scale.interpolate(&to.scale, t),
+        }
+    }
+}
+
+/// Snapshots of a state received from the server, ordered by the tick they were taken at, sampled at render time for a smooth rendering between the snapshots.
+///
+/// The rendering runs [`SnapshotBuffer::with_interpolation_delay`] ticks behind the server, so that the snapshots surrounding the render time have usually been received, even when some are lost or late. Can be used as a resource for a global state, or as a component for the state of an entity.
+#[derive(Resource, Component, Debug, Clone)]
+pub struct SnapshotBuffer<T> {
+    tick_duration: Duration,
+    interpolation_delay: f64,
+    capacity: usize,
+    /// Ordered by tick, without duplicates
+    snapshots: VecDeque<(SnapshotTick, T)>,
+}
+
+impl<T> SnapshotBuffer<T> {
+    /// Buffer of the snapshots of a server ticking every `tick_duration`, keeping [`DEFAULT_SNAPSHOT_CAPACITY`] snapshots and rendering [`DEFAULT_INTERPOLATION_DELAY_TICKS`] behind the server
+    pub fn new(tick_duration: Duration) -> Self {
+        Self {
+            tick_duration,
+            interpolation_delay: DEFAULT_INTERPOLATION_DELAY_TICKS,
+            capacity: DEFAULT_SNAPSHOT_CAPACITY,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    /// Keeps the last `capacity` snapshots, at least 2
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(2);
+        self
+    }
+
+    /// Renders `ticks` ticks behind the server. A higher delay hides more losses and jitter, at the cost of a later rendering
+    pub fn with_interpolation_delay(mut self, ticks: f64) -> Self {
+        self.interpolation_delay = ticks.max(0.);
+        self
+    }
+
+    /// Inserts the snapshot taken by the server at `tick`, in tick order.
+    ///
+    /// Returns false if the snapshot was dropped: a snapshot of this tick is already buffered, or the buffer is full of newer snapshots.
+    pub fn insert(&mut self, tick: SnapshotTick, snapshot: T) -> bool {
+        let index = self
+            .snapshots
+            .partition_point(|(buffered_tick, _)| *buffered_tick < tick);
+        if self
+            .snapshots
+            .get(index)
+            .is_some_and(|(buffered_tick, _)| *buffered_tick == tick)
+        {
+            return false;
+        }
+        if self.snapshots.len() >= self.capacity {
+            if index == 0 {
+                return false;
+            }
+            self.snapshots.pop_front();
+            self.snapshots.insert(index - 1, (tick, snapshot));
+        } else {
+            self.snapshots.insert(index, (tick, snapshot));
+        }
+        true
+    }
+
+    /// Tick of the latest snapshot, if any
+    pub fn latest_tick(&self) -> Option<SnapshotTick> {
+        self.snapshots.back().map(|(tick, _)| *tick)
+    }
+
+    /// Latest snapshot, if any
+    pub fn latest(&self) -> Option<&T> {
+        self.snapshots.back().map(|(_, snapshot)| snapshot)
+    }
+
+    /// Number of buffered snapshots
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Returns true if no snapshot is buffered
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Drops all the snapshots, such as when reconnecting
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Tick rendered at `server_time`, the time elapsed on the server since its tick 0: the current tick of the server minus the interpolation delay, fractional between two ticks.
+    ///
+    /// The buffer does not synchronize clocks: `server_time` is supplied by the caller, for example from the latest tick received from the server (see [`crate::client::connection::ClientSideConnection::server_tick`]) and the time elapsed since.
+    pub fn render_tick(&self, server_time: Duration) -> f64 {
+        server_time.as_secs_f64() / self.tick_duration.as_secs_f64() - self.interpolation_delay
+    }
+}
+
+impl<T: Interpolate + Clone> SnapshotBuffer<T> {
+    /// Samples the state at a fractional tick, interpolated between the surrounding snapshots.
+    ///
+    /// Before the oldest snapshot, returns the oldest one. After the latest snapshot, returns the latest one: the state is held rather than extrapolated. `None` if the buffer is empty.
+    pub fn sample(&self, tick: f64) -> Option<T> {
+        let (first_tick, first) = self.snapshots.front()?;
+        if tick <= *first_tick as f64 {
+            return Some(first.clone());
+        }
+        let next = self
+            .snapshots
+            .partition_point(|(buffered_tick, _)| (*buffered_tick as f64) <= tick);
+        let Some((to_tick, to)) = self.snapshots.get(next) else {
+            return self.latest().cloned();
+        };
+        let (from_tick, from) = &self.snapshots[next - 1];
+        let t = (tick - *from_tick as f64) / (*to_tick - *from_tick) as f64;
+        Some(from.interpolate(to, t as f32))
+    }
+
+    /// Samples the state rendered at `server_time`, see [`SnapshotBuffer::render_tick`] and [`SnapshotBuffer::sample`]
+    pub fn sample_at(&self, server_time: Duration) -> Option<T> {
+        self.sample(self.render_tick(server_time))
+    }
+}
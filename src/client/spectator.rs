@@ -0,0 +1,129 @@
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+use bytes::Bytes;
+
+use crate::shared::{channels::ChannelId, spectator::SpectatorMessage, QuinnetSyncUpdate};
+
+use super::{connection::ClientSideConnection, ConnectionClosed, QuinnetClient};
+
+/// Raised on a spectator when its snapshot was received, before any live update. Raised in the CoreStage::PreUpdate stage by the [`QuinnetClientSpectatorPlugin`].
+#[derive(Event, Debug, Clone)]
+pub struct SpectatorSnapshotEvent {
+    /// Parts of the snapshot, in their order
+    pub parts: Vec<Bytes>,
+}
+
+/// Spectator side of a [`crate::server::spectator::SpectatorStream`]: receives the snapshot of the state and acknowledges it, after which the server sends the live updates on their channels.
+///
+/// The live updates are received as usual, they are only sent once the snapshot was received.
+#[derive(Resource, Debug)]
+pub struct SpectatorClient {
+    snapshot_channel_id: ChannelId,
+    parts: Vec<Bytes>,
+    synchronized: bool,
+}
+
+impl SpectatorClient {
+    /// Spectator receiving its snapshot on `snapshot_channel_id`
+    pub fn new<C: Into<ChannelId>>(snapshot_channel_id: C) -> Self {
+        Self {
+            snapshot_channel_id: snapshot_channel_id.into(),
+            parts: Vec::new(),
+            synchronized: false,
+        }
+    }
+
+    /// Channel the snapshot is received on
+    pub fn snapshot_channel_id(&self) -> ChannelId {
+        self.snapshot_channel_id
+    }
+
+    /// Returns true once the snapshot was received and acknowledged
+    pub fn is_synchronized(&self) -> bool {
+        self.synchronized
+    }
+
+    /// Receives the parts of the snapshot. Returns the parts of the snapshot once complete, and acknowledges it to the server. A new snapshot sent by the server restarts the synchronization.
+    ///
+    /// Messages that can't be deserialized are dropped. Will return an [`Err`] if the connection is closed.
+    pub fn receive(
+        &mut self,
+        connection: &mut ClientSideConnection,
+    ) -> Result<Option<Vec<Bytes>>, ConnectionClosed> {
+        let mut snapshot = None;
+        for payload in connection.receive_all_on(self.snapshot_channel_id)? {
+            match bincode::deserialize(&payload) {
+                Ok(SpectatorMessage::Part(part)) => {
+                    self.synchronized = false;
+                    self.parts.push(part.into());
+                }
+                Ok(SpectatorMessage::End) => {
+                    self.synchronized = true;
+                    snapshot = Some(std::mem::take(&mut self.parts));
+                    connection
+                        .try_send_message_on(self.snapshot_channel_id, SpectatorMessage::Received);
+                }
+                _ => (),
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Drops the parts received, such as when reconnecting
+    pub fn reset(&mut self) {
+        self.parts.clear();
+        self.synchronized = false;
+    }
+}
+
+/// Receives the snapshot of a spectator with a [`SpectatorClient`] resource, on the default connection, and raises a [`SpectatorSnapshotEvent`] once it is complete.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetClientSpectatorPlugin {
+    snapshot_channel_id: ChannelId,
+    schedule: InternedScheduleLabel,
+}
+
+impl QuinnetClientSpectatorPlugin {
+    /// Plugin receiving the snapshot on `snapshot_channel_id`
+    pub fn new<C: Into<ChannelId>>(snapshot_channel_id: C) -> Self {
+        Self {
+            snapshot_channel_id: snapshot_channel_id.into(),
+            schedule: PreUpdate.intern(),
+        }
+    }
+
+    /// Receives the snapshot in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetClientSpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpectatorSnapshotEvent>()
+            .insert_resource(SpectatorClient::new(self.snapshot_channel_id))
+            .add_systems(
+                self.schedule,
+                receive_spectator_snapshot
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetClient>),
+            );
+    }
+}
+
+fn receive_spectator_snapshot(
+    mut client: ResMut<QuinnetClient>,
+    mut spectator: ResMut<SpectatorClient>,
+    mut snapshot_events: EventWriter<SpectatorSnapshotEvent>,
+) {
+    let Some(connection) = client.get_connection_mut() else {
+        return;
+    };
+    if let Ok(Some(parts)) = spectator.receive(connection) {
+        snapshot_events.write(SpectatorSnapshotEvent { parts });
+    }
+}
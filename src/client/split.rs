@@ -0,0 +1,494 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use bevy::{
+    log::{error, info_span},
+    prelude::Resource,
+};
+use bytes::Bytes;
+
+use crate::shared::{
+    buffer_pool::BufferPool,
+    channels::{incoming::IncomingPayloads, Channel, ChannelId, MessagePriority},
+    profiling,
+    protocol::ProtocolChannel,
+};
+
+use super::{
+    connection::{ConnectionLocalId, InternalConnectionState},
+    error::{
+        ClientMessageReceiveError, ClientMessageSendError, ClientPayloadSendError, ClientSendError,
+    },
+    ConnectionClosed,
+};
+
+const CONNECTING: u8 = 0;
+const CONNECTED: u8 = 1;
+const DISCONNECTED: u8 = 2;
+
+/// Channels and received payloads of a connection, shared by the connection and its halves, see [`crate::client::connection::ClientSideConnection::split`]
+#[derive(Debug)]
+pub(crate) struct ConnectionIo {
+    status: AtomicU8,
+    channels: RwLock<Vec<Option<Arc<Channel>>>>,
+    default_channel: RwLock<Option<ChannelId>>,
+    deferred_flush: AtomicBool,
+    incoming: Mutex<IncomingPayloads>,
+    received_messages_count: AtomicU64,
+    received_bytes_count: AtomicUsize,
+    sent_bytes_count: AtomicUsize,
+}
+
+impl ConnectionIo {
+    pub(crate) fn new(incoming: IncomingPayloads) -> Self {
+        Self {
+            status: AtomicU8::new(CONNECTING),
+            channels: RwLock::new(Vec::new()),
+            default_channel: RwLock::new(None),
+            deferred_flush: AtomicBool::new(false),
+            incoming: Mutex::new(incoming),
+            received_messages_count: AtomicU64::new(0),
+            received_bytes_count: AtomicUsize::new(0),
+            sent_bytes_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Mirrors the state of the connection, read by the halves
+    pub(crate) fn set_state(&self, state: &InternalConnectionState) {
+        let status = match state {
+            InternalConnectionState::Connecting => CONNECTING,
+            InternalConnectionState::Connected(..) => CONNECTED,
+            InternalConnectionState::Disconnected => DISCONNECTED,
+        };
+        self.status.store(status, Ordering::Relaxed);
+    }
+
+    fn status(&self) -> u8 {
+        self.status.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn incoming(&self) -> MutexGuard<'_, IncomingPayloads> {
+        self.incoming.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn channels(&self) -> RwLockReadGuard<'_, Vec<Option<Arc<Channel>>>> {
+        self.channels.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn channels_mut(&self) -> RwLockWriteGuard<'_, Vec<Option<Arc<Channel>>>> {
+        self.channels
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn default_channel(&self) -> Option<ChannelId> {
+        *self
+            .default_channel
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn set_default_channel(&self, channel_id: Option<ChannelId>) {
+        *self
+            .default_channel
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = channel_id;
+    }
+
+    pub(crate) fn deferred_flush(&self) -> bool {
+        self.deferred_flush.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_deferred_flush(&self, deferred: bool) {
+        self.deferred_flush.store(deferred, Ordering::Relaxed);
+    }
+
+    /// Returns the open channel `channel_id`, if the connection is not closed
+    pub(crate) fn channel(&self, channel_id: ChannelId) -> Result<Arc<Channel>, ClientSendError> {
+        if self.status() == DISCONNECTED {
+            return Err(ClientSendError::ConnectionClosed);
+        }
+        match self.channels().get(channel_id as usize) {
+            Some(Some(channel)) => Ok(channel.clone()),
+            Some(None) => Err(ClientSendError::ChannelClosed),
+            None => Err(ClientSendError::InvalidChannelId(channel_id)),
+        }
+    }
+
+    pub(crate) fn send_payload(
+        &self,
+        channel_id: ChannelId,
+        bytes: Bytes,
+        priority: Option<MessagePriority>,
+    ) -> Result<(), ClientSendError> {
+        let channel = self.channel(channel_id)?;
+        check_message_size(&channel, &bytes)?;
+        self.count_sent(bytes.len());
+        Ok(channel.send_payload(bytes, priority, self.deferred_flush())?)
+    }
+
+    pub(crate) fn flush(&self) {
+        for channel in self.channels().iter().flatten() {
+            channel.flush();
+        }
+    }
+
+    pub(crate) fn receive_payload(&self) -> Result<Option<(ChannelId, Bytes)>, ConnectionClosed> {
+        match self.status() {
+            DISCONNECTED => Err(ConnectionClosed),
+            CONNECTING => Ok(None),
+            _ => match self.incoming().try_recv() {
+                Ok(Some(payload)) => {
+                    self.count_received(1, payload.1.len());
+                    Ok(Some(payload))
+                }
+                Ok(None) => Ok(None),
+                Err(_) => Err(ConnectionClosed),
+            },
+        }
+    }
+
+    pub(crate) fn receive_all_on(
+        &self,
+        channel_id: ChannelId,
+    ) -> Result<Vec<Bytes>, ConnectionClosed> {
+        match self.status() {
+            DISCONNECTED => Err(ConnectionClosed),
+            CONNECTING => Ok(Vec::new()),
+            _ => match self.incoming().drain_channel(channel_id) {
+                Ok(payloads) => {
+                    self.count_received(
+                        payloads.len() as u64,
+                        payloads.iter().map(Bytes::len).sum(),
+                    );
+                    Ok(payloads)
+                }
+                Err(_) => Err(ConnectionClosed),
+            },
+        }
+    }
+
+    pub(crate) fn drain_payloads(
+        &self,
+    ) -> Result<HashMap<ChannelId, Vec<Bytes>>, ConnectionClosed> {
+        match self.status() {
+            DISCONNECTED => Err(ConnectionClosed),
+            CONNECTING => Ok(HashMap::new()),
+            _ => match self.incoming().drain() {
+                Ok(payloads) => {
+                    for channel_payloads in payloads.values() {
+                        self.count_received(
+                            channel_payloads.len() as u64,
+                            channel_payloads.iter().map(Bytes::len).sum(),
+                        );
+                    }
+                    Ok(payloads)
+                }
+                Err(_) => Err(ConnectionClosed),
+            },
+        }
+    }
+
+    pub(crate) fn count_sent(&self, bytes: usize) {
+        self.sent_bytes_count.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn count_received(&self, messages: u64, bytes: usize) {
+        self.received_messages_count
+            .fetch_add(messages, Ordering::Relaxed);
+        self.received_bytes_count
+            .fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn received_messages_count(&self) -> u64 {
+        self.received_messages_count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn received_bytes_count(&self) -> usize {
+        self.received_bytes_count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn clear_received_bytes_count(&self) -> usize {
+        self.received_bytes_count.swap(0, Ordering::Relaxed)
+    }
+
+    pub(crate) fn sent_bytes_count(&self) -> usize {
+        self.sent_bytes_count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn clear_sent_bytes_count(&self) -> usize {
+        self.sent_bytes_count.swap(0, Ordering::Relaxed)
+    }
+
+    /// Resets the channels, the received payloads and the stats for a new connection attempt
+    pub(crate) fn reset(&self, incoming: IncomingPayloads) {
+        self.channels_mut().clear();
+        self.set_default_channel(None);
+        *self.incoming() = incoming;
+        self.received_messages_count.store(0, Ordering::Relaxed);
+        self.received_bytes_count.store(0, Ordering::Relaxed);
+        self.sent_bytes_count.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Returns an error if the payload exceeds the maximum message size of the channel
+pub(crate) fn check_message_size(channel: &Channel, bytes: &Bytes) -> Result<(), ClientSendError> {
+    match channel.max_message_size() {
+        Some(max_message_size) if bytes.len() > max_message_size => {
+            Err(ClientSendError::PayloadTooLarge {
+                size: bytes.len(),
+                max_message_size,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Sending half of a [`crate::client::connection::ClientSideConnection`], see [`crate::client::connection::ClientSideConnection::split`].
+///
+/// Sends on the channels of the connection as they are opened and closed, and fails with [`ClientSendError::ConnectionClosed`] once the connection is closed. Honors [`crate::client::connection::ClientSideConnection::set_deferred_flush`]. Tracked messages are only sent by the connection itself.
+#[derive(Resource, Debug)]
+pub struct ConnectionSender {
+    id: ConnectionLocalId,
+    io: Arc<ConnectionIo>,
+    buffer_pool: BufferPool,
+}
+
+impl ConnectionSender {
+    pub(crate) fn new(
+        id: ConnectionLocalId,
+        io: Arc<ConnectionIo>,
+        buffer_pool: BufferPool,
+    ) -> Self {
+        Self {
+            id,
+            io,
+            buffer_pool,
+        }
+    }
+
+    /// Local id of the connection
+    pub fn id(&self) -> ConnectionLocalId {
+        self.id
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::send_message_on`]
+    pub fn send_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), ClientMessageSendError> {
+        self.send_serialized(channel_id.into(), &message, None)
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::send_prioritized_message_on`]
+    pub fn send_prioritized_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        message: T,
+        priority: MessagePriority,
+    ) -> Result<(), ClientMessageSendError> {
+        self.send_serialized(channel_id.into(), &message, Some(priority))
+    }
+
+    fn send_serialized<T: serde::Serialize>(
+        &mut self,
+        channel_id: ChannelId,
+        message: &T,
+        priority: Option<MessagePriority>,
+    ) -> Result<(), ClientMessageSendError> {
+        let payload = {
+            let _span = profiling::enter(|| {
+                info_span!("quinnet_serialize", connection_id = self.id, channel_id)
+            });
+            self.buffer_pool.serialize(message)
+        };
+        match payload {
+            Some(payload) => Ok(self.io.send_payload(channel_id, payload, priority)?),
+            None => Err(ClientMessageSendError::Serialization),
+        }
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::send_on`]
+    pub fn send_on<C: ProtocolChannel>(
+        &mut self,
+        channel: C,
+        message: &C::Message,
+    ) -> Result<(), ClientMessageSendError> {
+        self.send_message_on(channel, message)
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::send_message`]
+    pub fn send_message<T: serde::Serialize>(
+        &mut self,
+        message: T,
+    ) -> Result<(), ClientMessageSendError> {
+        match self.io.default_channel() {
+            Some(channel) => self.send_message_on(channel, message),
+            None => Err(ClientMessageSendError::NoDefaultChannel),
+        }
+    }
+
+    /// Same as [Self::send_message] but will log the error instead of returning it
+    pub fn try_send_message<T: serde::Serialize>(&mut self, message: T) {
+        if let Err(err) = self.send_message(message) {
+            error!("try_send_message: {}", err);
+        }
+    }
+
+    /// Same as [Self::send_message_on] but will log the error instead of returning it
+    pub fn try_send_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        message: T,
+    ) {
+        if let Err(err) = self.send_message_on(channel_id, message) {
+            error!("try_send_message_on: {}", err);
+        }
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::send_payload_on`]
+    pub fn send_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &self,
+        channel_id: C,
+        payload: T,
+    ) -> Result<(), ClientSendError> {
+        self.io
+            .send_payload(channel_id.into(), payload.into(), None)
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::send_prioritized_payload_on`]
+    pub fn send_prioritized_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &self,
+        channel_id: C,
+        payload: T,
+        priority: MessagePriority,
+    ) -> Result<(), ClientSendError> {
+        self.io
+            .send_payload(channel_id.into(), payload.into(), Some(priority))
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::send_payload`]
+    pub fn send_payload<T: Into<Bytes>>(&self, payload: T) -> Result<(), ClientPayloadSendError> {
+        match self.io.default_channel() {
+            Some(channel) => Ok(self.send_payload_on(channel, payload)?),
+            None => Err(ClientPayloadSendError::NoDefaultChannel),
+        }
+    }
+
+    /// Same as [Self::send_payload] but will log the error instead of returning it
+    pub fn try_send_payload<T: Into<Bytes>>(&self, payload: T) {
+        if let Err(err) = self.send_payload(payload) {
+            error!("try_send_payload: {}", err);
+        }
+    }
+
+    /// Same as [Self::send_payload_on] but will log the error instead of returning it
+    pub fn try_send_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &self,
+        channel_id: C,
+        payload: T,
+    ) {
+        if let Err(err) = self.send_payload_on(channel_id, payload) {
+            error!("try_send_payload_on: {}", err);
+        }
+    }
+
+    /// Sends the messages deferred until the next flush, see [`crate::client::connection::ClientSideConnection::flush`]
+    pub fn flush(&self) {
+        self.io.flush();
+    }
+}
+
+/// Receiving half of a [`crate::client::connection::ClientSideConnection`], see [`crate::client::connection::ClientSideConnection::split`].
+///
+/// Reads the same received payloads as the connection: a payload is returned once, by whichever of them reads it first. Honors [`crate::client::connection::ClientSideConnection::set_frame_coherent_receive`].
+#[derive(Resource, Debug)]
+pub struct ConnectionReceiver {
+    id: ConnectionLocalId,
+    io: Arc<ConnectionIo>,
+}
+
+impl ConnectionReceiver {
+    pub(crate) fn new(id: ConnectionLocalId, io: Arc<ConnectionIo>) -> Self {
+        Self { id, io }
+    }
+
+    /// Local id of the connection
+    pub fn id(&self) -> ConnectionLocalId {
+        self.id
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::receive_payload`]
+    pub fn receive_payload(&mut self) -> Result<Option<(ChannelId, Bytes)>, ConnectionClosed> {
+        self.io.receive_payload()
+    }
+
+    /// Same as [Self::receive_payload] but will log the error instead of returning it
+    pub fn try_receive_payload(&mut self) -> Option<(ChannelId, Bytes)> {
+        match self.receive_payload() {
+            Ok(payload) => payload,
+            Err(err) => {
+                error!("try_receive_payload: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::receive_message`]
+    pub fn receive_message<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<(ChannelId, T)>, ClientMessageReceiveError> {
+        match self.receive_payload()? {
+            Some((channel_id, payload)) => match bincode::deserialize(&payload) {
+                Ok(msg) => Ok(Some((channel_id, msg))),
+                Err(_) => Err(ClientMessageReceiveError::Deserialization),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Same as [Self::receive_message] but will log the error instead of returning it
+    pub fn try_receive_message<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Option<(ChannelId, T)> {
+        match self.receive_message() {
+            Ok(message) => message,
+            Err(err) => {
+                error!("try_receive_message: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::receive_all_on`]
+    pub fn receive_all_on<C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+    ) -> Result<impl Iterator<Item = Bytes>, ConnectionClosed> {
+        Ok(self.io.receive_all_on(channel_id.into())?.into_iter())
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::receive_on`]
+    pub fn receive_on<C: ProtocolChannel>(
+        &mut self,
+        channel: C,
+    ) -> Result<Vec<C::Message>, ClientMessageReceiveError> {
+        self.receive_all_on(channel)?
+            .map(|payload| {
+                bincode::deserialize(&payload)
+                    .map_err(|_| ClientMessageReceiveError::Deserialization)
+            })
+            .collect()
+    }
+
+    /// Same as [`crate::client::connection::ClientSideConnection::drain_payloads`]
+    pub fn drain_payloads(&mut self) -> Result<HashMap<ChannelId, Vec<Bytes>>, ConnectionClosed> {
+        self.io.drain_payloads()
+    }
+}
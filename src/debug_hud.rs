@@ -0,0 +1,250 @@
+use std::{fmt::Write, time::Duration};
+
+use bevy::prelude::*;
+
+#[cfg(feature = "client")]
+use crate::client::QuinnetClient;
+#[cfg(feature = "server")]
+use crate::server::QuinnetServer;
+
+/// Default period between two refreshes of the HUD
+pub const DEFAULT_HUD_REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+/// Window of the bandwidth usage of the clients reported by the HUD
+#[cfg(feature = "server")]
+const HUD_BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+
+/// Renders the live stats of the [`QuinnetClient`] and [`QuinnetServer`] of the app as a text overlay in the top-left corner of the window: for each connection its state, round-trip time, losses, traffic and buffers, and the outgoing queue of each of its channels.
+///
+/// The HUD only reads the public stats of the client and server, the same reports are available without any UI with [`client_report`] and [`server_report`]. It is drawn by `bevy_ui`: the app needs a camera. The HUD is toggled with the [`QuinnetDebugHud`] resource.
+#[derive(Debug, Clone)]
+pub struct QuinnetDebugHudPlugin {
+    refresh_interval: Duration,
+    visible: bool,
+}
+
+impl Default for QuinnetDebugHudPlugin {
+    fn default() -> Self {
+        Self {
+            refresh_interval: DEFAULT_HUD_REFRESH_INTERVAL,
+            visible: true,
+        }
+    }
+}
+
+impl QuinnetDebugHudPlugin {
+    /// Refreshes the HUD every `refresh_interval` instead of every [`DEFAULT_HUD_REFRESH_INTERVAL`]
+    pub fn with_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    /// The HUD starts hidden, see [`QuinnetDebugHud::visible`]
+    pub fn hidden(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+}
+
+/// State of the HUD of the [`QuinnetDebugHudPlugin`]
+#[derive(Resource, Debug)]
+pub struct QuinnetDebugHud {
+    /// Whether the HUD is displayed. The stats are not collected while it is hidden.
+    pub visible: bool,
+    refresh: Timer,
+}
+
+impl QuinnetDebugHud {
+    /// Shows the HUD if hidden, hides it otherwise
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+/// Marker of the text entity of the HUD
+#[derive(Component)]
+struct QuinnetDebugHudText;
+
+impl Plugin for QuinnetDebugHudPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(QuinnetDebugHud {
+            visible: self.visible,
+            refresh: Timer::new(self.refresh_interval, TimerMode::Repeating),
+        })
+        .add_systems(Startup, spawn_hud)
+        .add_systems(Update, update_hud);
+    }
+}
+
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(4.),
+            left: Val::Px(4.),
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.6)),
+        Text::default(),
+        TextFont {
+            font_size: 12.,
+            ..default()
+        },
+        GlobalZIndex(i32::MAX),
+        QuinnetDebugHudText,
+    ));
+}
+
+fn update_hud(
+    time: Res<Time<Real>>,
+    mut hud: ResMut<QuinnetDebugHud>,
+    #[cfg(feature = "client")] client: Option<Res<QuinnetClient>>,
+    #[cfg(feature = "server")] server: Option<Res<QuinnetServer>>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<QuinnetDebugHudText>>,
+) {
+    let refreshed = hud.refresh.tick(time.delta()).just_finished();
+    for (mut text, mut visibility) in &mut texts {
+        let expected_visibility = match hud.visible {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+        visibility.set_if_neq(expected_visibility);
+        if !hud.visible || !refreshed {
+            continue;
+        }
+        let mut report = String::new();
+        #[cfg(feature = "client")]
+        if let Some(client) = &client {
+            report.push_str(&client_report(client));
+        }
+        #[cfg(feature = "server")]
+        if let Some(server) = &server {
+            report.push_str(&server_report(server));
+        }
+        text.0 = report;
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1_024 => format!("{} B", bytes),
+        1_024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1_024.),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.),
+    }
+}
+
+fn write_transport_stats(report: &mut String, stats: &quinn_proto::ConnectionStats) {
+    let _ = writeln!(
+        report,
+        "    rtt {:.1} ms | cwnd {} | lost {} packets | udp tx {} rx {}",
+        stats.path.rtt.as_secs_f64() * 1_000.,
+        format_bytes(stats.path.cwnd),
+        stats.path.lost_packets,
+        format_bytes(stats.udp_tx.bytes),
+        format_bytes(stats.udp_rx.bytes),
+    );
+}
+
+/// Text report of the stats of the connections of `client`, as displayed by the [`QuinnetDebugHudPlugin`]
+#[cfg(feature = "client")]
+pub fn client_report(client: &QuinnetClient) -> String {
+    let mut report = String::from("Client\n");
+    for (id, connection) in client.connections() {
+        let _ = writeln!(
+            report,
+            "  connection {} {:?}, client id {:?}",
+            id,
+            connection.state(),
+            connection.client_id()
+        );
+        if let Some(stats) = connection.connection_stats() {
+            write_transport_stats(&mut report, &stats);
+        }
+        let buffers = connection.buffer_pool_stats();
+        let _ = writeln!(
+            report,
+            "    messages received {} | received {} sent {} | buffers {} in {} chunks, {} reused",
+            connection.received_messages_count(),
+            format_bytes(connection.received_bytes_count() as u64),
+            format_bytes(connection.sent_bytes_count() as u64),
+            format_bytes(buffers.allocated_bytes),
+            buffers.allocated_chunks,
+            buffers.reused,
+        );
+        for channel_id in connection.channel_ids() {
+            let _ = writeln!(
+                report,
+                "    channel {}: {} pending",
+                channel_id,
+                connection
+                    .pending_messages_count(channel_id)
+                    .unwrap_or_default()
+            );
+        }
+    }
+    report
+}
+
+/// Text report of the stats of the endpoint of `server` and of its clients, as displayed by the [`QuinnetDebugHudPlugin`]
+#[cfg(feature = "server")]
+pub fn server_report(server: &QuinnetServer) -> String {
+    let Some(endpoint) = server.get_endpoint() else {
+        return String::from("Server not listening\n");
+    };
+    let mut report = String::new();
+    let stats = endpoint.endpoint_stats();
+    let buffers = endpoint.buffer_pool_stats();
+    let _ = writeln!(
+        report,
+        "Server {}{} | {} clients | {} connections, {} disconnections | messages received {} | buffers {} in {} chunks, {} reused",
+        endpoint.local_addr(),
+        match endpoint.is_accepting() {
+            true => "",
+            false => " (not accepting)",
+        },
+        endpoint.clients().len(),
+        stats.connect_count(),
+        stats.disconnect_count(),
+        stats.received_messages_count(),
+        format_bytes(buffers.allocated_bytes),
+        buffers.allocated_chunks,
+        buffers.reused,
+    );
+    let mut client_ids = endpoint.clients();
+    client_ids.sort_unstable();
+    for client_id in client_ids {
+        let Some(connection) = endpoint.get_connection(client_id) else {
+            continue;
+        };
+        let _ = writeln!(
+            report,
+            "  client {} {}",
+            client_id,
+            connection
+                .client_address()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default()
+        );
+        write_transport_stats(&mut report, &connection.connection_stats());
+        let usage = connection.bandwidth_usage(HUD_BANDWIDTH_WINDOW);
+        let _ = writeln!(
+            report,
+            "    in {}/s out {}/s | received {} sent {}",
+            format_bytes(usage.inbound_bytes),
+            format_bytes(usage.outbound_bytes),
+            format_bytes(connection.received_bytes_count() as u64),
+            format_bytes(connection.sent_bytes_count() as u64),
+        );
+        for channel_id in connection.channel_ids() {
+            let _ = writeln!(
+                report,
+                "    channel {}: {} pending",
+                channel_id,
+                connection
+                    .pending_messages_count(channel_id)
+                    .unwrap_or_default()
+            );
+        }
+    }
+    report
+}
@@ -3,11 +3,26 @@
 //! A Client/Server game networking plugin using QUIC, for the Bevy game engine.
 //! See the repository at <https://github.com/Henauxg/bevy_quinnet>
 
+/// Headless client connections without the Bevy plugin layer, for bots and tools
+#[cfg(feature = "no-bevy")]
+pub mod bot;
 /// Client features
 #[cfg(feature = "client")]
 pub mod client;
+/// Network debug HUD
+#[cfg(feature = "debug-hud")]
+pub mod debug_hud;
+/// Load test harness of a server endpoint
+#[cfg(feature = "loadtest")]
+pub mod loadtest;
+/// Replication of the Bevy world across the network
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod replication;
 /// Server features
 #[cfg(feature = "server")]
 pub mod server;
 /// Shared features between client & server
 pub mod shared;
+/// Scripted peers for deterministic tests
+#[cfg(all(feature = "testing", any(feature = "client", feature = "server")))]
+pub mod testing;
@@ -0,0 +1,629 @@
+//! Load test harness: spins up synthetic clients against a local server endpoint, sends messages with a configurable pattern and reports the throughput and the latency distribution.
+//!
+//! Meant to measure regressions of the channels tasks and to plan the capacity of a server. Also available as the `quinnet-loadtest` binary:
+//!
+//! `cargo run --release --features loadtest --bin quinnet-loadtest -- --clients 64 --pattern echo`
+
+use std::{
+    fmt,
+    net::Ipv4Addr,
+    str::FromStr,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use bevy::ecs::world::{FromWorld, World};
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::{
+    client::{
+        certificate::CertificateVerificationMode,
+        connection::{ClientEndpointConfiguration, ConnectionLocalId, ConnectionState},
+        ClientConfigurationError, QuinnetClient,
+    },
+    server::{
+        certificate::CertificateRetrievalMode, EndpointStartError, QuinnetServer,
+        ServerEndpointConfiguration,
+    },
+    shared::{
+        channels::{ChannelId, ChannelKind, ChannelsConfiguration},
+        error::AsyncChannelError,
+        transport::memory::MemoryConnection,
+    },
+};
+
+/// Maximum duration to connect all the clients before giving up
+pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Size of the send timestamp at the start of each payload, the minimum message size
+pub const TIMESTAMP_LEN: usize = 8;
+
+const CHANNEL_ID: ChannelId = 0;
+
+/// Error while running a load test
+#[derive(thiserror::Error, Debug)]
+pub enum LoadTestError {
+    /// The server endpoint could not start
+    #[error("Failed to start the server endpoint")]
+    EndpointStart(#[from] EndpointStartError),
+    /// The configuration of the client connections is invalid
+    #[error("Invalid client configuration")]
+    ClientConfiguration(#[from] ClientConfigurationError),
+    /// A client connection could not be opened
+    #[error("Failed to open a client connection")]
+    ConnectionOpen(#[from] AsyncChannelError),
+    /// The clients did not all connect in time
+    #[error("Only {connected} of the {expected} clients connected in time")]
+    ConnectionTimeout {
+        /// Number of clients connected to the server
+        connected: usize,
+        /// Number of clients of the load test
+        expected: usize,
+    },
+    /// A client lost its connection during the load test
+    #[error("A client lost its connection during the load test")]
+    ConnectionLost,
+}
+
+/// Error while parsing a load test option
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid value `{0}`")]
+pub struct InvalidOptionValue(String);
+
+/// Transport used by the synthetic clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadTestTransport {
+    /// QUIC over the loopback interface
+    Quic,
+    /// In-process [`MemoryConnection`]s, which isolate the cost of the channels tasks from QUIC
+    Memory,
+}
+
+impl FromStr for LoadTestTransport {
+    type Err = InvalidOptionValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quic" => Ok(LoadTestTransport::Quic),
+            "memory" => Ok(LoadTestTransport::Memory),
+            _ => Err(InvalidOptionValue(s.to_string())),
+        }
+    }
+}
+
+/// Direction of the messages of a load test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessagePattern {
+    /// Each client sends messages to the server. The latency is measured when the server receives them.
+    Upload,
+    /// The server broadcasts messages to all the clients. The latency is measured when each client receives them.
+    Broadcast,
+    /// Each client sends messages that the server sends back to it. The latency is the round trip.
+    Echo,
+}
+
+impl FromStr for MessagePattern {
+    type Err = InvalidOptionValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upload" => Ok(MessagePattern::Upload),
+            "broadcast" => Ok(MessagePattern::Broadcast),
+            "echo" => Ok(MessagePattern::Echo),
+            _ => Err(InvalidOptionValue(s.to_string())),
+        }
+    }
+}
+
+/// Configuration of a load test, see [`run`]
+#[derive(Debug, Clone)]
+pub struct LoadTestConfiguration {
+    clients: usize,
+    pattern: MessagePattern,
+    channel: ChannelKind,
+    message_size: usize,
+    rate: f64,
+    duration: Duration,
+    tick: Duration,
+    drain_timeout: Duration,
+    transport: LoadTestTransport,
+}
+
+impl Default for LoadTestConfiguration {
+    fn default() -> Self {
+        Self {
+            clients: 16,
+            pattern: MessagePattern::Echo,
+            channel: ChannelKind::default(),
+            message_size: 64,
+            rate: 30.,
+            duration: Duration::from_secs(5),
+            tick: Duration::from_millis(1),
+            drain_timeout: Duration::from_secs(1),
+            transport: LoadTestTransport::Quic,
+        }
+    }
+}
+
+impl LoadTestConfiguration {
+    /// Load test with `clients` synthetic clients, echoing 64 bytes messages 30 times per second over QUIC for 5 seconds, on an [`ChannelKind::OrderedReliable`] channel
+    pub fn new(clients: usize) -> Self {
+        Self {
+            clients,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the number of synthetic clients
+    pub fn with_clients(mut self, clients: usize) -> Self {
+        self.clients = clients;
+        self
+    }
+
+    /// Sets the direction of the messages
+    pub fn with_pattern(mut self, pattern: MessagePattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Sets the kind of the channel carrying the messages
+    pub fn with_channel(mut self, channel: ChannelKind) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Sets the size of the messages, at least [`TIMESTAMP_LEN`] bytes
+    pub fn with_message_size(mut self, message_size: usize) -> Self {
+        self.message_size = message_size.max(TIMESTAMP_LEN);
+        self
+    }
+
+    /// Sets the number of messages sent per second, by each client, or by the server with [`MessagePattern::Broadcast`]
+    pub fn with_rate(mut self, rate: f64) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Sets the duration of the sending phase
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets the sleep between two updates of the server and the clients, like the frame time of an app. The measured latencies include it. Defaults to 1ms.
+    pub fn with_tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    /// Sets how long to wait for the messages still in flight after the sending phase, without any delivery. Defaults to 1s.
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// Sets the transport of the clients
+    pub fn with_transport(mut self, transport: LoadTestTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Number of synthetic clients
+    pub fn clients(&self) -> usize {
+        self.clients
+    }
+
+    /// Direction of the messages
+    pub fn pattern(&self) -> MessagePattern {
+        self.pattern
+    }
+
+    /// Kind of the channel carrying the messages
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    /// Size of the messages
+    pub fn message_size(&self) -> usize {
+        self.message_size
+    }
+
+    /// Messages sent per second, by each client or by the server
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Duration of the sending phase
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Transport of the clients
+    pub fn transport(&self) -> LoadTestTransport {
+        self.transport
+    }
+}
+
+/// Distribution of the latencies measured during a load test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyDistribution {
+    /// Number of measured latencies
+    pub samples: usize,
+    /// Lowest latency
+    pub min: Duration,
+    /// Mean latency
+    pub mean: Duration,
+    /// Median latency
+    pub p50: Duration,
+    /// 90th percentile
+    pub p90: Duration,
+    /// 99th percentile
+    pub p99: Duration,
+    /// Highest latency
+    pub max: Duration,
+}
+
+impl LatencyDistribution {
+    fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+        Some(Self {
+            samples: samples.len(),
+            min: samples[0],
+            mean: samples.iter().sum::<Duration>() / samples.len() as u32,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+/// Results of a load test
+#[derive(Debug, Clone)]
+pub struct LoadTestReport {
+    /// Configuration of the load test
+    pub config: LoadTestConfiguration,
+    /// Messages sent by their origin: the clients, or the server with [`MessagePattern::Broadcast`]
+    pub sent: u64,
+    /// Messages which could not be sent, when the outgoing queues are full
+    pub send_errors: u64,
+    /// Messages expected at their final destination
+    pub expected: u64,
+    /// Messages received at their final destination
+    pub delivered: u64,
+    /// Bytes received at the final destination, payloads only
+    pub delivered_bytes: u64,
+    /// Time from the start of the sending phase to the last delivery
+    pub elapsed: Duration,
+    /// Time spent updating the server and handling its messages, the async tasks excluded
+    pub server_time: Duration,
+    /// Latencies of the delivered messages
+    pub latency: Option<LatencyDistribution>,
+}
+
+impl LoadTestReport {
+    /// Messages delivered per second
+    pub fn throughput(&self) -> f64 {
+        self.delivered as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Payload bytes delivered per second
+    pub fn bytes_throughput(&self) -> f64 {
+        self.delivered_bytes as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Share of the elapsed time spent updating the server, see [`LoadTestReport::server_time`]
+    pub fn server_load(&self) -> f64 {
+        self.server_time.as_secs_f64() / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for LoadTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} clients, {:?} of {} bytes at {} msg/s on {:?} over {:?}, for {:?}",
+            self.config.clients,
+            self.config.pattern,
+            self.config.message_size,
+            self.config.rate,
+            self.config.channel,
+            self.config.transport,
+            self.config.duration
+        )?;
+        writeln!(f, "  sent        {} messages", self.sent)?;
+        writeln!(
+            f,
+            "  delivered   {} of {} messages ({} send errors)",
+            self.delivered, self.expected, self.send_errors
+        )?;
+        writeln!(
+            f,
+            "  throughput  {:.1} msg/s, {:.1} KiB/s",
+            self.throughput(),
+            self.bytes_throughput() / 1024.
+        )?;
+        match &self.latency {
+            Some(latency) => writeln!(
+                f,
+                "  latency     min {:.2?}, mean {:.2?}, p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, max {:.2?}",
+                latency.min, latency.mean, latency.p50, latency.p90, latency.p99, latency.max
+            )?,
+            None => writeln!(f, "  latency     no message delivered")?,
+        }
+        write!(
+            f,
+            "  server      {:.1?} busy ({:.1}% of the elapsed time)",
+            self.server_time,
+            self.server_load() * 100.
+        )
+    }
+}
+
+/// Payload carrying its send time, padded to the message size
+fn timestamped_payload(start: Instant, size: usize) -> Bytes {
+    let mut payload = BytesMut::with_capacity(size);
+    payload.put_u64_le(start.elapsed().as_nanos() as u64);
+    payload.resize(size, 0);
+    payload.freeze()
+}
+
+fn latency(start: Instant, payload: &[u8]) -> Option<Duration> {
+    let sent_at = u64::from_le_bytes(payload.get(..TIMESTAMP_LEN)?.try_into().ok()?);
+    Some(
+        start
+            .elapsed()
+            .saturating_sub(Duration::from_nanos(sent_at)),
+    )
+}
+
+/// Number of messages due at `elapsed` to keep up with `rate`, minus the `sent` ones
+fn due_messages(rate: f64, elapsed: Duration, sent: u64) -> u64 {
+    ((elapsed.as_secs_f64() * rate) as u64).saturating_sub(sent)
+}
+
+struct LoadTest {
+    /// Owns the async runtime of the server and the client
+    _world: World,
+    config: LoadTestConfiguration,
+    server: QuinnetServer,
+    client: QuinnetClient,
+    connections: Vec<ConnectionLocalId>,
+    /// Messages sent by each client, or by the server
+    sent: Vec<u64>,
+    send_errors: u64,
+    delivered: u64,
+    delivered_bytes: u64,
+    latencies: Vec<Duration>,
+    last_delivery: Option<Instant>,
+    server_time: Duration,
+}
+
+impl LoadTest {
+    fn start(config: LoadTestConfiguration) -> Result<Self, LoadTestError> {
+        let mut world = World::new();
+        let mut server = QuinnetServer::from_world(&mut world);
+        let mut client = QuinnetClient::from_world(&mut world);
+        let channels_config = ChannelsConfiguration::from_types(vec![config.channel])
+            .expect("a single channel is always valid");
+
+        server.start_endpoint(
+            ServerEndpointConfiguration::from_ip(Ipv4Addr::LOCALHOST, 0),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: Ipv4Addr::LOCALHOST.to_string(),
+            },
+            channels_config.clone(),
+        )?;
+        let port = server.endpoint().local_addr().port();
+
+        let mut connections = Vec::with_capacity(config.clients);
+        for _ in 0..config.clients {
+            let connection_id = match config.transport {
+                LoadTestTransport::Quic => client.open_connection(
+                    ClientEndpointConfiguration::builder()
+                        .with_server_ip(Ipv4Addr::LOCALHOST, port)
+                        .build()?,
+                    CertificateVerificationMode::SkipVerification,
+                    channels_config.clone(),
+                )?,
+                LoadTestTransport::Memory => {
+                    let (client_end, server_end) = MemoryConnection::pair();
+                    server.endpoint().add_transport_connection(server_end);
+                    client.open_transport_connection(client_end, channels_config.clone())?
+                }
+            };
+            connections.push(connection_id);
+        }
+
+        let sent = match config.pattern {
+            MessagePattern::Broadcast => vec![0],
+            MessagePattern::Upload | MessagePattern::Echo => vec![0; config.clients],
+        };
+        let mut load_test = Self {
+            _world: world,
+            config,
+            server,
+            client,
+            connections,
+            sent,
+            send_errors: 0,
+            delivered: 0,
+            delivered_bytes: 0,
+            latencies: Vec::new(),
+            last_delivery: None,
+            server_time: Duration::ZERO,
+        };
+        load_test.wait_for_connections()?;
+        Ok(load_test)
+    }
+
+    fn wait_for_connections(&mut self) -> Result<(), LoadTestError> {
+        let start = Instant::now();
+        loop {
+            self.server.pump();
+            self.client.pump();
+            let connected = self.server.endpoint().clients().len();
+            let clients_connected = self
+                .client
+                .connections()
+                .all(|(_, connection)| connection.state() == ConnectionState::Connected);
+            if connected == self.config.clients && clients_connected {
+                return Ok(());
+            }
+            if start.elapsed() > CONNECTION_TIMEOUT {
+                return Err(LoadTestError::ConnectionTimeout {
+                    connected,
+                    expected: self.config.clients,
+                });
+            }
+            sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn send(&mut self, start: Instant) {
+        let elapsed = start.elapsed();
+        let size = self.config.message_size;
+        match self.config.pattern {
+            MessagePattern::Upload | MessagePattern::Echo => {
+                for (index, connection_id) in self.connections.iter().enumerate() {
+                    let connection = self
+                        .client
+                        .get_connection_mut_by_id(*connection_id)
+                        .unwrap();
+                    for _ in 0..due_messages(self.config.rate, elapsed, self.sent[index]) {
+                        self.sent[index] += 1;
+                        if connection
+                            .send_payload_on(CHANNEL_ID, timestamped_payload(start, size))
+                            .is_err()
+                        {
+                            self.send_errors += 1;
+                        }
+                    }
+                }
+            }
+            MessagePattern::Broadcast => {
+                let update_start = Instant::now();
+                for _ in 0..due_messages(self.config.rate, elapsed, self.sent[0]) {
+                    self.sent[0] += 1;
+                    if self
+                        .server
+                        .endpoint_mut()
+                        .broadcast_payload_on(CHANNEL_ID, timestamped_payload(start, size))
+                        .is_err()
+                    {
+                        self.send_errors += 1;
+                    }
+                }
+                self.server_time += update_start.elapsed();
+            }
+        }
+    }
+
+    fn update_server(&mut self, start: Instant) {
+        let update_start = Instant::now();
+        self.server.pump();
+        let endpoint = self.server.endpoint_mut();
+        for client_id in endpoint.clients() {
+            while let Some((channel_id, payload)) = endpoint.try_receive_payload_from(client_id) {
+                match self.config.pattern {
+                    MessagePattern::Upload => {
+                        self.delivered += 1;
+                        self.delivered_bytes += payload.len() as u64;
+                        self.latencies.extend(latency(start, &payload));
+                        self.last_delivery = Some(Instant::now());
+                    }
+                    MessagePattern::Echo => {
+                        if endpoint
+                            .send_payload_on(client_id, channel_id, payload)
+                            .is_err()
+                        {
+                            self.send_errors += 1;
+                        }
+                    }
+                    MessagePattern::Broadcast => (),
+                }
+            }
+        }
+        self.server_time += update_start.elapsed();
+    }
+
+    fn update_clients(&mut self, start: Instant) -> Result<(), LoadTestError> {
+        self.client.pump();
+        for connection_id in &self.connections {
+            let connection = self
+                .client
+                .get_connection_mut_by_id(*connection_id)
+                .unwrap();
+            while let Some((_, payload)) = connection
+                .receive_payload()
+                .map_err(|_| LoadTestError::ConnectionLost)?
+            {
+                self.delivered += 1;
+                self.delivered_bytes += payload.len() as u64;
+                self.latencies.extend(latency(start, &payload));
+                self.last_delivery = Some(Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    fn expected(&self) -> u64 {
+        let sent: u64 = self.sent.iter().sum();
+        match self.config.pattern {
+            MessagePattern::Upload | MessagePattern::Echo => sent,
+            MessagePattern::Broadcast => sent * self.config.clients as u64,
+        }
+    }
+
+    fn run(mut self) -> Result<LoadTestReport, LoadTestError> {
+        let start = Instant::now();
+        while start.elapsed() < self.config.duration {
+            self.send(start);
+            self.update_server(start);
+            self.update_clients(start)?;
+            sleep(self.config.tick);
+        }
+        let sending_end = Instant::now();
+        // Wait for the messages in flight, as long as some are still delivered
+        while self.delivered < self.expected()
+            && self
+                .last_delivery
+                .map_or(sending_end, |last_delivery| last_delivery.max(sending_end))
+                .elapsed()
+                < self.config.drain_timeout
+        {
+            self.update_server(start);
+            self.update_clients(start)?;
+            sleep(self.config.tick);
+        }
+
+        self.client.close_all_connections();
+        let _ = self.server.stop_endpoint();
+
+        Ok(LoadTestReport {
+            sent: self.sent.iter().sum(),
+            send_errors: self.send_errors,
+            expected: self.expected(),
+            delivered: self.delivered,
+            delivered_bytes: self.delivered_bytes,
+            elapsed: self
+                .last_delivery
+                .map_or(sending_end, |last_delivery| last_delivery.max(sending_end))
+                - start,
+            server_time: self.server_time,
+            latency: LatencyDistribution::from_samples(self.latencies),
+            config: self.config,
+        })
+    }
+}
+
+/// Connects the synthetic clients of `config` to a new local endpoint, runs the load test and returns its results.
+///
+/// The server and the clients share the calling thread, like a listen server and its clients would, while their async tasks run on the runtimes of the [`QuinnetServer`] and the [`QuinnetClient`].
+pub fn run(config: LoadTestConfiguration) -> Result<LoadTestReport, LoadTestError> {
+    LoadTest::start(config)?.run()
+}
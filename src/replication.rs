@@ -0,0 +1,142 @@
+use bevy::{ecs::component::Mutable, prelude::*};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::shared::{channels::ChannelId, QuinnetFlush, QuinnetSyncUpdate};
+
+/// Spawn & despawn of the entities of the server on the clients
+pub mod entities;
+/// Mirroring of Bevy events to the remote peers
+pub mod events;
+/// Scheduling of the bandwidth-limited updates by accumulated priority
+#[cfg(feature = "server")]
+pub mod priority;
+/// Upload of the components of client-owned entities to the server
+pub mod upload;
+/// Per-client visibility of the entities
+pub mod visibility;
+
+pub use entities::{Replicated, ServerEntity, ServerEntityMap};
+#[cfg(feature = "server")]
+pub use events::FromClient;
+pub use events::ReplicationDirection;
+#[cfg(feature = "server")]
+pub use priority::{PriorityAccumulators, ReplicationPriority};
+pub use upload::{ClientAuthority, ClientOwner, ComponentUpload, UploadRejectedEvent};
+pub use visibility::NetworkVisibility;
+
+/// Replication methods of the [`App`]
+pub trait ReplicationAppExt {
+    /// Mirrors the Bevy events of type `E` across the network: the events sent locally are serialized on `channel_id` and re-emitted as `E` on the remote peers, according to `direction`. The server also raises a [`FromClient`] event for each event received from a client.
+    ///
+    /// Works with the [`crate::client::QuinnetClientPlugin`], the [`crate::server::QuinnetServerPlugin`] or both. The events are received after the [`QuinnetSyncUpdate`] set in `PreUpdate` and sent before the [`QuinnetFlush`] set in `PostUpdate`, events sent later in the frame are sent on the next one.
+    ///
+    /// The channel must be opened on both sides and dedicated to the event: all the messages received on it are consumed. Calling it again for the same event type only updates its direction and channel.
+    fn replicate_event<E>(
+        &mut self,
+        direction: ReplicationDirection,
+        channel_id: impl Into<ChannelId>,
+    ) -> &mut Self
+    where
+        E: Event + Clone + Serialize + DeserializeOwned;
+
+    /// Spawns the [`Replicated`] entities of the server on the clients and despawns them when they are despawned or lose their [`Replicated`] component.
+    ///
+    /// The spawns & despawns are sent in their order on `channel_id`, which must be a reliable ordered channel dedicated to them, distinct from the channels of the component updates: an update received for an entity is never about a despawned entity whose id was reused. The entities are only spawned on the clients they are visible to according to their [`NetworkVisibility`], when they are spawned or when a client connects.
+    ///
+    /// On the client, the replicated entities are spawned with a [`ServerEntity`] component, and the [`ServerEntityMap`] resource maps the server entities to the client entities and back. The messages referencing server entities can be mapped with it.
+    fn replicate_entities(&mut self, channel_id: impl Into<ChannelId>) -> &mut Self;
+
+    /// Sends the component `C` of the client-owned entities from the clients to the server, the reverse of the replication of the server: for client-authoritative state such as the transform of the player in casual games.
+    ///
+    /// On the client, the components of the replicated entities marked with [`ClientAuthority`] are sent when they change, at most once per interval of the [`ComponentUpload`]. On the server, an uploaded component is only applied to the entity if the entity has the [`ClientOwner`] of the sender and the validator of the [`ComponentUpload`] accepts it, otherwise an [`UploadRejectedEvent`] is raised. Calling it again for the same component replaces its configuration.
+    fn upload_component<C>(&mut self, upload: ComponentUpload<C>) -> &mut Self
+    where
+        C: Component<Mutability = Mutable> + Clone + Serialize + DeserializeOwned;
+
+    /// Accumulates the [`ReplicationPriority`] of the [`Replicated`] entities for each client they are visible to at each frame, in the [`PriorityAccumulators`] resource of the server, to pick the updates sent within a bandwidth budget
+    #[cfg(feature = "server")]
+    fn accumulate_priorities(&mut self) -> &mut Self;
+}
+
+impl ReplicationAppExt for App {
+    fn replicate_event<E>(
+        &mut self,
+        direction: ReplicationDirection,
+        channel_id: impl Into<ChannelId>,
+    ) -> &mut Self
+    where
+        E: Event + Clone + Serialize + DeserializeOwned,
+    {
+        events::add_event_replication::<E>(self, direction, channel_id.into());
+        self
+    }
+
+    fn upload_component<C>(&mut self, upload: ComponentUpload<C>) -> &mut Self
+    where
+        C: Component<Mutability = Mutable> + Clone + Serialize + DeserializeOwned,
+    {
+        let registered = self.world().contains_resource::<ComponentUpload<C>>();
+        self.insert_resource(upload);
+        if registered {
+            return self;
+        }
+
+        #[cfg(feature = "client")]
+        self.add_systems(
+            PostUpdate,
+            upload::upload_components::<C>.before(QuinnetFlush),
+        );
+        #[cfg(feature = "server")]
+        {
+            self.add_event::<UploadRejectedEvent>();
+            self.add_systems(
+                PreUpdate,
+                upload::receive_uploaded_components::<C>.after(QuinnetSyncUpdate),
+            );
+        }
+        self
+    }
+
+    #[cfg(feature = "server")]
+    fn accumulate_priorities(&mut self) -> &mut Self {
+        if self.world().contains_resource::<PriorityAccumulators>() {
+            return self;
+        }
+        self.init_resource::<PriorityAccumulators>();
+        self.add_systems(
+            PreUpdate,
+            priority::accumulate_priorities.after(QuinnetSyncUpdate),
+        );
+        self
+    }
+
+    fn replicate_entities(&mut self, channel_id: impl Into<ChannelId>) -> &mut Self {
+        let channel_id = channel_id.into();
+        if let Some(mut replication) = self
+            .world_mut()
+            .get_resource_mut::<entities::EntityReplication>()
+        {
+            replication.channel_id = channel_id;
+            return self;
+        }
+
+        self.insert_resource(entities::EntityReplication { channel_id });
+        #[cfg(feature = "client")]
+        {
+            self.init_resource::<ServerEntityMap>();
+            self.add_systems(
+                PreUpdate,
+                entities::receive_entity_changes.after(QuinnetSyncUpdate),
+            );
+        }
+        #[cfg(feature = "server")]
+        {
+            self.add_event::<crate::server::ConnectionEvent>();
+            self.add_systems(
+                PostUpdate,
+                entities::send_entity_changes.before(QuinnetFlush),
+            );
+        }
+        self
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "client")]
+use crate::client::{connection::ConnectionState, QuinnetClient};
+use crate::shared::channels::ChannelId;
+#[cfg(feature = "server")]
+use crate::{
+    replication::visibility::NetworkVisibility,
+    server::{ConnectionEvent, QuinnetServer},
+};
+
+/// Marks an entity of the server to be spawned and despawned on the clients, see [`crate::replication::ReplicationAppExt::replicate_entities`]
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Replicated;
+
+/// Entity of the server a replicated entity of the client was spawned for
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ServerEntity(pub Entity);
+
+/// Mapping between the replicated entities of the server and their entities on the client.
+///
+/// The mappings are added when the spawn of an entity is received and removed when its despawn is received, before the client entity is despawned. They are all cleared, and their client entities despawned, when the client is no longer connected.
+#[derive(Resource, Debug, Default)]
+pub struct ServerEntityMap {
+    server_to_client: HashMap<Entity, Entity>,
+    client_to_server: HashMap<Entity, Entity>,
+}
+
+impl ServerEntityMap {
+    /// Returns the client entity spawned for the server entity, if any
+    pub fn client_entity(&self, server_entity: Entity) -> Option<Entity> {
+        self.server_to_client.get(&server_entity).copied()
+    }
+
+    /// Returns the server entity a client entity was spawned for, if any
+    pub fn server_entity(&self, client_entity: Entity) -> Option<Entity> {
+        self.client_to_server.get(&client_entity).copied()
+    }
+
+    /// Iterates over the mappings, as `(server_entity, client_entity)`
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.server_to_client
+            .iter()
+            .map(|(server_entity, client_entity)| (*server_entity, *client_entity))
+    }
+
+    /// Returns the number of replicated entities
+    pub fn len(&self) -> usize {
+        self.server_to_client.len()
+    }
+
+    /// Returns true if no entity is replicated
+    pub fn is_empty(&self) -> bool {
+        self.server_to_client.is_empty()
+    }
+
+    #[cfg(feature = "client")]
+    fn insert(&mut self, server_entity: Entity, client_entity: Entity) {
+        self.server_to_client.insert(server_entity, client_entity);
+        self.client_to_server.insert(client_entity, server_entity);
+    }
+
+    #[cfg(feature = "client")]
+    fn remove(&mut self, server_entity: Entity) -> Option<Entity> {
+        let client_entity = self.server_to_client.remove(&server_entity)?;
+        self.client_to_server.remove(&client_entity);
+        Some(client_entity)
+    }
+
+    #[cfg(feature = "client")]
+    fn clear(&mut self) -> impl Iterator<Item = Entity> + '_ {
+        self.client_to_server.clear();
+        self.server_to_client
+            .drain()
+            .map(|(_, client_entity)| client_entity)
+    }
+}
+
+/// Spawns & despawns, sent in their order on the reliable ordered channel of the entities
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum EntityMessage {
+    Spawn(u64),
+    Despawn(u64),
+}
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub(crate) struct EntityReplication {
+    pub(crate) channel_id: ChannelId,
+}
+
+#[cfg(feature = "server")]
+pub(crate) fn send_entity_changes(
+    replication: Res<EntityReplication>,
+    server: Option<ResMut<QuinnetServer>>,
+    mut connections: EventReader<ConnectionEvent>,
+    spawned: Query<(Entity, Option<&NetworkVisibility>), Added<Replicated>>,
+    replicated: Query<(Entity, Option<&NetworkVisibility>), With<Replicated>>,
+    mut despawned: RemovedComponents<Replicated>,
+) {
+    let Some(endpoint) = server.and_then(|server| server.into_inner().get_endpoint_mut()) else {
+        return;
+    };
+    let channel_id = replication.channel_id;
+
+    // Despawns first: a spawn and a despawn of the same frame can't be about the same entity
+    for entity in despawned.read() {
+        endpoint.try_broadcast_message_on(channel_id, EntityMessage::Despawn(entity.to_bits()));
+    }
+    for (entity, visibility) in spawned.iter() {
+        let message = EntityMessage::Spawn(entity.to_bits());
+        match visibility {
+            Some(visibility) => {
+                endpoint.try_broadcast_visible_message_on(visibility, channel_id, message)
+            }
+            None => endpoint.try_broadcast_message_on(channel_id, message),
+        }
+    }
+    // The clients ignore the spawns of the entities they already know
+    for connection in connections.read() {
+        for (entity, visibility) in replicated.iter() {
+            if visibility.is_some_and(|visibility| !visibility.is_visible_to(connection.id)) {
+                continue;
+            }
+            endpoint.try_send_message_on(
+                connection.id,
+                channel_id,
+                EntityMessage::Spawn(entity.to_bits()),
+            );
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+pub(crate) fn receive_entity_changes(
+    mut commands: Commands,
+    replication: Res<EntityReplication>,
+    client: Option<ResMut<QuinnetClient>>,
+    mut entity_map: ResMut<ServerEntityMap>,
+) {
+    let Some(connection) = client
+        .and_then(|client| client.into_inner().get_connection_mut())
+        .filter(|connection| connection.state() == ConnectionState::Connected)
+    else {
+        for client_entity in entity_map.clear() {
+            commands.entity(client_entity).try_despawn();
+        }
+        return;
+    };
+    let Ok(payloads) = connection.receive_all_on(replication.channel_id) else {
+        return;
+    };
+    for payload in payloads {
+        match bincode::deserialize::<EntityMessage>(&payload) {
+            Ok(EntityMessage::Spawn(bits)) => {
+                let Ok(server_entity) = Entity::try_from_bits(bits) else {
+                    warn!("Received the spawn of an invalid server entity: {}", bits);
+                    continue;
+                };
+                if entity_map.client_entity(server_entity).is_none() {
+                    let client_entity = commands.spawn(ServerEntity(server_entity)).id();
+                    entity_map.insert(server_entity, client_entity);
+                }
+            }
+            Ok(EntityMessage::Despawn(bits)) => {
+                let Ok(server_entity) = Entity::try_from_bits(bits) else {
+                    warn!("Received the despawn of an invalid server entity: {}", bits);
+                    continue;
+                };
+                if let Some(client_entity) = entity_map.remove(server_entity) {
+                    commands.entity(client_entity).try_despawn();
+                }
+            }
+            Err(_) => warn!(
+                "Failed to deserialize an entity message received on channel {}",
+                replication.channel_id
+            ),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+
+use bevy::{ecs::event::EventId, prelude::*};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "client")]
+use crate::client::{connection::ConnectionState, QuinnetClient};
+#[cfg(feature = "server")]
+use crate::server::QuinnetServer;
+#[cfg(feature = "server")]
+use crate::shared::ClientId;
+use crate::shared::{channels::ChannelId, QuinnetFlush, QuinnetSyncUpdate};
+
+/// Peers a replicated event is sent to, see [`crate::replication::ReplicationAppExt::replicate_event`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplicationDirection {
+    /// The events sent by a client are re-emitted on the server
+    ClientToServer,
+    /// The events sent by the server are re-emitted on all its clients
+    ServerToClient,
+    /// Both of the above. The events received from a client are not forwarded to the other clients
+    Bidirectional,
+}
+
+impl ReplicationDirection {
+    fn clients_send(self) -> bool {
+        self != ReplicationDirection::ServerToClient
+    }
+
+    fn server_sends(self) -> bool {
+        self != ReplicationDirection::ClientToServer
+    }
+}
+
+/// Replicated event received from a client, raised on the server alongside the re-emitted event itself
+#[cfg(feature = "server")]
+#[derive(Event, Debug, Clone)]
+pub struct FromClient<E> {
+    /// Id of the client who sent the event
+    pub client_id: ClientId,
+    /// Received event
+    pub event: E,
+}
+
+#[derive(Resource)]
+struct ReplicatedEvent<E: Event> {
+    direction: ReplicationDirection,
+    channel_id: ChannelId,
+    /// Events re-emitted since the last send, not to be sent back
+    received: HashSet<EventId<E>>,
+}
+
+pub(crate) fn add_event_replication<E>(
+    app: &mut App,
+    direction: ReplicationDirection,
+    channel_id: ChannelId,
+) where
+    E: Event + Clone + Serialize + DeserializeOwned,
+{
+    if let Some(mut replicated) = app.world_mut().get_resource_mut::<ReplicatedEvent<E>>() {
+        replicated.direction = direction;
+        replicated.channel_id = channel_id;
+        return;
+    }
+
+    app.add_event::<E>();
+    #[cfg(feature = "server")]
+    app.add_event::<FromClient<E>>();
+    app.insert_resource(ReplicatedEvent::<E> {
+        direction,
+        channel_id,
+        received: HashSet::new(),
+    });
+    app.add_systems(
+        PreUpdate,
+        receive_replicated_events::<E>.after(QuinnetSyncUpdate),
+    );
+    app.add_systems(PostUpdate, send_replicated_events::<E>.before(QuinnetFlush));
+}
+
+fn receive_replicated_events<E: Event + Clone + DeserializeOwned>(
+    mut replicated: ResMut<ReplicatedEvent<E>>,
+    mut events: EventWriter<E>,
+    #[cfg(feature = "client")] client: Option<ResMut<QuinnetClient>>,
+    #[cfg(feature = "server")] server: Option<ResMut<QuinnetServer>>,
+    #[cfg(feature = "server")] mut from_clients: EventWriter<FromClient<E>>,
+) {
+    let replicated = &mut *replicated;
+    #[cfg(feature = "client")]
+    if replicated.direction.server_sends() {
+        if let Some(connection) = client
+            .and_then(|client| client.into_inner().get_connection_mut())
+            .filter(|connection| connection.state() == ConnectionState::Connected)
+        {
+            if let Ok(payloads) = connection.receive_all_on(replicated.channel_id) {
+                for payload in payloads {
+                    match bincode::deserialize::<E>(&payload) {
+                        Ok(event) => {
+                            replicated.received.insert(events.write(event));
+                        }
+                        Err(_) => warn!(
+                            "Failed to deserialize a replicated event received on channel {}",
+                            replicated.channel_id
+                        ),
+                    }
+                }
+            }
+        }
+    }
+    #[cfg(feature = "server")]
+    if replicated.direction.clients_send() {
+        if let Some(endpoint) = server.and_then(|server| server.into_inner().get_endpoint_mut()) {
+            for client_id in endpoint.clients() {
+                let Ok(payloads) = endpoint.receive_all_from_on(client_id, replicated.channel_id)
+                else {
+                    continue;
+                };
+                for payload in payloads {
+                    match bincode::deserialize::<E>(&payload) {
+                        Ok(event) => {
+                            from_clients.write(FromClient {
+                                client_id,
+                                event: event.clone(),
+                            });
+                            replicated.received.insert(events.write(event));
+                        }
+                        Err(_) => warn!(
+                            "Failed to deserialize a replicated event received from client {} on channel {}",
+                            client_id, replicated.channel_id
+                        ),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn send_replicated_events<E: Event + Serialize>(
+    mut replicated: ResMut<ReplicatedEvent<E>>,
+    mut events: EventReader<E>,
+    #[cfg(feature = "client")] mut client: Option<ResMut<QuinnetClient>>,
+    #[cfg(feature = "server")] mut server: Option<ResMut<QuinnetServer>>,
+) {
+    for (event, id) in events.read_with_id() {
+        if replicated.received.contains(&id) {
+            continue;
+        }
+        #[cfg(feature = "client")]
+        if replicated.direction.clients_send() {
+            if let Some(connection) = client
+                .as_mut()
+                .and_then(|client| client.get_connection_mut())
+                .filter(|connection| connection.state() == ConnectionState::Connected)
+            {
+                connection.try_send_message_on(replicated.channel_id, event);
+            }
+        }
+        #[cfg(feature = "server")]
+        if replicated.direction.server_sends() {
+            if let Some(endpoint) = server.as_mut().and_then(|server| server.get_endpoint_mut()) {
+                endpoint.try_broadcast_message_on(replicated.channel_id, event);
+            }
+        }
+    }
+    replicated.received.clear();
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    replication::{entities::Replicated, visibility::NetworkVisibility},
+    server::QuinnetServer,
+    shared::ClientId,
+};
+
+/// Priority accumulated by a [`Replicated`] entity at each tick for each client it is visible to, see [`PriorityAccumulators`]. 1 for the entities without this component.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ReplicationPriority(pub f32);
+
+impl Default for ReplicationPriority {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Priority accumulators of the replicated entities, per client: the eventual consistency scheduling of the bandwidth-limited updates.
+///
+/// At each tick, every entity visible to a client accumulates its [`ReplicationPriority`] for this client. The updates sent to the client are then picked with [`PriorityAccumulators::select`], which returns the entities with the highest accumulated priorities, the most "starved" ones, and resets their accumulators. Low priority entities are eventually sent once they waited long enough, rather than never (full broadcast over budget) or regardless of their importance (round-robin).
+///
+/// Updated in `PreUpdate` after the [`crate::shared::QuinnetSyncUpdate`] set, see [`crate::replication::ReplicationAppExt::accumulate_priorities`].
+#[derive(Resource, Debug, Default)]
+pub struct PriorityAccumulators {
+    clients: HashMap<ClientId, HashMap<Entity, f32>>,
+}
+
+impl PriorityAccumulators {
+    /// Adds `priority` to the accumulator of the entity for the client
+    pub fn accumulate(&mut self, client_id: ClientId, entity: Entity, priority: f32) {
+        *self
+            .clients
+            .entry(client_id)
+            .or_default()
+            .entry(entity)
+            .or_default() += priority;
+    }
+
+    /// Accumulated priority of the entity for the client, 0 if unknown
+    pub fn priority(&self, client_id: ClientId, entity: Entity) -> f32 {
+        self.clients
+            .get(&client_id)
+            .and_then(|entities| entities.get(&entity))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the `count` entities with the highest accumulated priorities for the client, highest first, and resets their accumulators
+    pub fn select(&mut self, client_id: ClientId, count: usize) -> Vec<Entity> {
+        self.select_within(client_id, count, |_| 1)
+    }
+
+    /// Returns the entities with the highest accumulated priorities for the client, highest first, whose `cost` (such as the size of their update in bytes) fits in `budget`, and resets their accumulators.
+    ///
+    /// An entity exceeding the remaining budget is skipped, a cheaper entity with a lower priority can still be selected.
+    pub fn select_within<F: FnMut(Entity) -> usize>(
+        &mut self,
+        client_id: ClientId,
+        mut budget: usize,
+        mut cost: F,
+    ) -> Vec<Entity> {
+        let Some(entities) = self.clients.get_mut(&client_id) else {
+            return Vec::new();
+        };
+        let mut candidates: Vec<(Entity, f32)> = entities
+            .iter()
+            .filter(|(_, priority)| **priority > 0.)
+            .map(|(entity, priority)| (*entity, *priority))
+            .collect();
+        candidates.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let mut selected = Vec::new();
+        for (entity, _) in candidates {
+            if budget == 0 {
+                break;
+            }
+            let entity_cost = cost(entity);
+            if entity_cost > budget {
+                continue;
+            }
+            budget -= entity_cost;
+            entities.insert(entity, 0.);
+            selected.push(entity);
+        }
+        selected
+    }
+
+    /// Resets the accumulator of the entity for the client, such as when its update was sent by other means
+    pub fn reset(&mut self, client_id: ClientId, entity: Entity) {
+        if let Some(priority) = self
+            .clients
+            .get_mut(&client_id)
+            .and_then(|entities| entities.get_mut(&entity))
+        {
+            *priority = 0.;
+        }
+    }
+
+    /// Forgets the accumulators of an entity
+    pub fn remove_entity(&mut self, entity: Entity) {
+        for entities in self.clients.values_mut() {
+            entities.remove(&entity);
+        }
+    }
+
+    /// Forgets the accumulators of a client
+    pub fn remove_client(&mut self, client_id: ClientId) {
+        self.clients.remove(&client_id);
+    }
+}
+
+type PrioritizedEntity = (
+    Entity,
+    Option<&'static ReplicationPriority>,
+    Option<&'static NetworkVisibility>,
+);
+
+pub(crate) fn accumulate_priorities(
+    server: Option<Res<QuinnetServer>>,
+    mut accumulators: ResMut<PriorityAccumulators>,
+    entities: Query<PrioritizedEntity, With<Replicated>>,
+    mut despawned: RemovedComponents<Replicated>,
+) {
+    for entity in despawned.read() {
+        accumulators.remove_entity(entity);
+    }
+    let Some(endpoint) = server.as_ref().and_then(|server| server.get_endpoint()) else {
+        return;
+    };
+    let client_ids = endpoint.clients();
+    accumulators
+        .clients
+        .retain(|client_id, _| client_ids.contains(client_id));
+    for (entity, priority, visibility) in entities.iter() {
+        let priority = priority.copied().unwrap_or_default().0;
+        for &client_id in client_ids.iter() {
+            match visibility.is_none_or(|visibility| visibility.is_visible_to(client_id)) {
+                true => accumulators.accumulate(client_id, entity, priority),
+                false => accumulators.reset(client_id, entity),
+            }
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::time::Duration;
+#[cfg(feature = "client")]
+use std::{collections::HashSet, time::Instant};
+
+#[cfg(feature = "server")]
+use bevy::ecs::component::Mutable;
+use bevy::prelude::*;
+#[cfg(feature = "server")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "server")]
+use crate::server::QuinnetServer;
+use crate::shared::{channels::ChannelId, ClientId};
+#[cfg(feature = "client")]
+use crate::{
+    client::{connection::ConnectionState, QuinnetClient},
+    replication::entities::ServerEntity,
+};
+
+/// Default period between two uploads of a component, see [`ComponentUpload::with_interval`]
+pub const DEFAULT_UPLOAD_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Marks a replicated entity of the client whose uploaded components are sent to the server, see [`crate::replication::ReplicationAppExt::upload_component`]
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ClientAuthority;
+
+/// Client allowed to upload the components of an entity of the server, see [`crate::replication::ReplicationAppExt::upload_component`]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientOwner(pub ClientId);
+
+/// Reason why the server rejected an uploaded component
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadRejection {
+    /// The entity does not exist on the server
+    UnknownEntity,
+    /// The entity is not owned by the client, see [`ClientOwner`]
+    NotOwner,
+    /// The validator of the [`ComponentUpload`] refused the component
+    Invalid,
+    /// The upload could not be deserialized
+    Deserialization,
+}
+
+/// Raised on the server when an uploaded component is rejected
+#[derive(Event, Debug, Clone)]
+pub struct UploadRejectedEvent {
+    /// Id of the client who uploaded the component
+    pub client_id: ClientId,
+    /// Entity of the server the component was uploaded for, `None` if the upload could not be deserialized
+    pub entity: Option<Entity>,
+    /// Reason of the rejection
+    pub reason: UploadRejection,
+}
+
+/// Validates a component uploaded by a client: receives the id of the client, the entity, its current value of the component if any and the uploaded value. The component is applied if it returns true.
+pub type UploadValidator<C> =
+    Box<dyn Fn(ClientId, Entity, Option<&C>, &C) -> bool + Send + Sync + 'static>;
+
+/// Configuration of the upload of a component from the clients to the server, see [`crate::replication::ReplicationAppExt::upload_component`]
+#[derive(Resource)]
+pub struct ComponentUpload<C> {
+    pub(crate) channel_id: ChannelId,
+    pub(crate) interval: Duration,
+    pub(crate) validator: Option<UploadValidator<C>>,
+}
+
+impl<C: Component> ComponentUpload<C> {
+    /// Uploads the component on `channel_id`, which must be dedicated to it, every [`DEFAULT_UPLOAD_INTERVAL`], without validation
+    pub fn new(channel_id: impl Into<ChannelId>) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            interval: DEFAULT_UPLOAD_INTERVAL,
+            validator: None,
+        }
+    }
+
+    /// Sets the minimum period between two uploads. Only the components changed since the previous upload are sent
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the [`UploadValidator`] of the server, run for each uploaded component before applying it. Rejected components raise an [`UploadRejectedEvent`]
+    pub fn with_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(ClientId, Entity, Option<&C>, &C) -> bool + Send + Sync + 'static,
+    {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Returns the channel the component is uploaded on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Returns the minimum period between two uploads
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+/// Components of the entities of the server, sent at once
+#[derive(Serialize, Deserialize)]
+struct UploadMessage<C> {
+    components: Vec<(u64, C)>,
+}
+
+#[cfg(feature = "client")]
+#[derive(Default)]
+pub(crate) struct UploadState {
+    last_upload: Option<Instant>,
+    changed: HashSet<Entity>,
+}
+
+#[cfg(feature = "client")]
+pub(crate) fn upload_components<C: Component + Clone + Serialize>(
+    upload: Res<ComponentUpload<C>>,
+    client: Option<ResMut<QuinnetClient>>,
+    changed: Query<Entity, (Changed<C>, With<ClientAuthority>)>,
+    components: Query<(&ServerEntity, &C), With<ClientAuthority>>,
+    mut state: Local<UploadState>,
+) {
+    state.changed.extend(changed.iter());
+    if state
+        .last_upload
+        .is_some_and(|last_upload| last_upload.elapsed() < upload.interval)
+    {
+        return;
+    }
+    let Some(connection) = client
+        .and_then(|client| client.into_inner().get_connection_mut())
+        .filter(|connection| connection.state() == ConnectionState::Connected)
+    else {
+        return;
+    };
+    let message = UploadMessage {
+        components: state
+            .changed
+            .drain()
+            .filter_map(|entity| components.get(entity).ok())
+            .map(|(server_entity, component)| (server_entity.0.to_bits(), component.clone()))
+            .collect(),
+    };
+    if message.components.is_empty() {
+        return;
+    }
+    state.last_upload = Some(Instant::now());
+    connection.try_send_message_on(upload.channel_id, message);
+}
+
+#[cfg(feature = "server")]
+pub(crate) fn receive_uploaded_components<C: Component<Mutability = Mutable> + DeserializeOwned>(
+    mut commands: Commands,
+    upload: Res<ComponentUpload<C>>,
+    server: Option<ResMut<QuinnetServer>>,
+    mut entities: Query<(Option<&ClientOwner>, Option<&mut C>)>,
+    mut rejections: EventWriter<UploadRejectedEvent>,
+) {
+    let Some(endpoint) = server.and_then(|server| server.into_inner().get_endpoint_mut()) else {
+        return;
+    };
+    for client_id in endpoint.clients() {
+        let Ok(payloads) = endpoint.receive_all_from_on(client_id, upload.channel_id) else {
+            continue;
+        };
+        for payload in payloads {
+            let Ok(message) = bincode::deserialize::<UploadMessage<C>>(&payload) else {
+                rejections.write(UploadRejectedEvent {
+                    client_id,
+                    entity: None,
+                    reason: UploadRejection::Deserialization,
+                });
+                continue;
+            };
+            for (bits, component) in message.components {
+                let entity = Entity::try_from_bits(bits).ok();
+                if let Err(reason) = apply_upload(
+                    &mut commands,
+                    &upload,
+                    &mut entities,
+                    client_id,
+                    entity,
+                    component,
+                ) {
+                    rejections.write(UploadRejectedEvent {
+                        client_id,
+                        entity,
+                        reason,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+fn apply_upload<C: Component<Mutability = Mutable>>(
+    commands: &mut Commands,
+    upload: &ComponentUpload<C>,
+    entities: &mut Query<(Option<&ClientOwner>, Option<&mut C>)>,
+    client_id: ClientId,
+    entity: Option<Entity>,
+    component: C,
+) -> Result<(), UploadRejection> {
+    let entity = entity.ok_or(UploadRejection::UnknownEntity)?;
+    let Ok((owner, current)) = entities.get_mut(entity) else {
+        return Err(UploadRejection::UnknownEntity);
+    };
+    if owner != Some(&ClientOwner(client_id)) {
+        return Err(UploadRejection::NotOwner);
+    }
+    if let Some(validator) = &upload.validator {
+        if !validator(client_id, entity, current.as_deref(), &component) {
+            return Err(UploadRejection::Invalid);
+        }
+    }
+    match current {
+        Some(mut current) => *current = component,
+        None => {
+            commands.entity(entity).insert(component);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+
+use bevy::prelude::Component;
+
+use crate::shared::ClientId;
+
+/// Clients allowed to see an entity, for per-client visibility such as fog of war or anti-cheat culling.
+///
+/// Honored by the visibility-aware broadcasts of the server, such as [`crate::server::Endpoint::broadcast_visible_message_on`]: the messages about the entity are only sent to the clients it is visible to. An entity without this component is visible to all the clients. The ids of disconnected clients are not removed from the sets.
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub enum NetworkVisibility {
+    /// Visible to all the clients
+    #[default]
+    Public,
+    /// Only visible to these clients
+    Only(HashSet<ClientId>),
+    /// Visible to all the clients but these
+    Except(HashSet<ClientId>),
+}
+
+impl NetworkVisibility {
+    /// Visible to no client, until shown with [`NetworkVisibility::show`]
+    pub fn hidden() -> Self {
+        Self::Only(HashSet::new())
+    }
+
+    /// Only visible to the specified clients
+    pub fn only<I: IntoIterator<Item = ClientId>>(client_ids: I) -> Self {
+        Self::Only(client_ids.into_iter().collect())
+    }
+
+    /// Visible to all the clients but the specified ones
+    pub fn except<I: IntoIterator<Item = ClientId>>(client_ids: I) -> Self {
+        Self::Except(client_ids.into_iter().collect())
+    }
+
+    /// Returns true if the entity is visible to the client
+    pub fn is_visible_to(&self, client_id: ClientId) -> bool {
+        match self {
+            Self::Public => true,
+            Self::Only(client_ids) => client_ids.contains(&client_id),
+            Self::Except(client_ids) => !client_ids.contains(&client_id),
+        }
+    }
+
+    /// Makes the entity visible to the client
+    pub fn show(&mut self, client_id: ClientId) {
+        match self {
+            Self::Public => (),
+            Self::Only(client_ids) => {
+                client_ids.insert(client_id);
+            }
+            Self::Except(client_ids) => {
+                client_ids.remove(&client_id);
+            }
+        }
+    }
+
+    /// Hides the entity from the client
+    pub fn hide(&mut self, client_id: ClientId) {
+        match self {
+            Self::Public => *self = Self::except([client_id]),
+            Self::Only(client_ids) => {
+                client_ids.remove(&client_id);
+            }
+            Self::Except(client_ids) => {
+                client_ids.insert(client_id);
+            }
+        }
+    }
+
+    /// Filters the clients the entity is visible to
+    pub fn visible_clients<'a, I>(&'a self, client_ids: I) -> impl Iterator<Item = ClientId> + 'a
+    where
+        I: IntoIterator<Item = ClientId>,
+        I::IntoIter: 'a,
+    {
+        client_ids
+            .into_iter()
+            .filter(|client_id| self.is_visible_to(*client_id))
+    }
+}
@@ -1,71 +1,540 @@
 use std::{
-    collections::{BTreeSet, HashMap, HashSet},
+    collections::{BTreeSet, HashMap},
+    future::Future,
     net::{AddrParseError, IpAddr, SocketAddr, UdpSocket},
-    sync::Arc,
+    num::NonZeroUsize,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
-use bevy::prelude::*;
+use bevy::{
+    ecs::{
+        schedule::{InternedScheduleLabel, ScheduleLabel},
+        system::SystemParam,
+    },
+    prelude::*,
+};
 use bytes::Bytes;
-use quinn::{default_runtime, Endpoint as QuinnEndpoint, EndpointConfig, ServerConfig};
-use quinn_proto::ConnectionStats;
+use quinn::{
+    crypto::rustls::QuicServerConfig, default_runtime, Endpoint as QuinnEndpoint, ServerConfig,
+};
+use quinn_proto::{ConnectionStats, PathStats};
+use rustls::pki_types::CertificateDer;
 use serde::Deserialize;
 use tokio::{
     runtime,
     sync::{
         broadcast::{self},
-        mpsc::{
-            self,
-            error::{TryRecvError, TrySendError},
-        },
+        mpsc::{self, error::TrySendError},
     },
 };
 
 use crate::{
-    server::certificate::{retrieve_certificate, CertificateRetrievalMode, ServerCertificate},
+    replication::visibility::NetworkVisibility,
+    server::certificate::{
+        retrieve_certificate, CertificateRetrievalMode, ClientAuthentication, ServerCertificate,
+    },
     shared::{
+        buffer_pool::{BufferPool, BufferPoolStats, DEFAULT_BUFFER_CHUNK_SIZE},
         channels::{
-            spawn_recv_channels_tasks, spawn_send_channels_tasks_spawner, Channel,
-            ChannelAsyncMessage, ChannelId, ChannelKind, ChannelSyncMessage, ChannelsConfiguration,
-            CloseReason,
+            ack::{AckTracker, MAX_ACKS_PER_CONTROL_MESSAGE},
+            compression::DictionaryNegotiation,
+            control::{control_channel_config, ControlMessage, CONTROL_CHANNEL_ID},
+            incoming::{incoming_channel, IncomingPayloads, IncomingRecv},
+            queue::OutgoingQueue,
+            spawn_recv_channels_tasks, spawn_send_channels_tasks_spawner,
+            trace::MessageTrace,
+            Channel, ChannelAsyncMessage, ChannelConfig, ChannelEncryption, ChannelId, ChannelKind,
+            ChannelSyncMessage, ChannelsConfiguration, CloseReason, MessagePriority,
+            SharedChannelConfigs, TrackedMessageId, PROTOCOL_HEADER_LEN,
+        },
+        close::{CloseCode, CloseStage},
+        congestion::{CongestionEventKind, CongestionMonitor},
+        error::{
+            AsyncChannelError, ChannelCloseError, ChannelCreationError, ChannelError,
+            ForwardingError,
         },
-        error::{AsyncChannelError, ChannelCloseError, ChannelCreationError},
-        AsyncRuntime, ClientId, InternalConnectionRef, QuinnetSyncUpdate,
-        DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE, DEFAULT_KEEP_ALIVE_INTERVAL_S,
+        forwarding::{ForwardedClient, ForwardingKey},
+        hardening::{HardeningConfiguration, ProtocolViolation, ReceiveHardening},
+        memory::{MemoryBudget, MemoryBudgetPolicy, SharedConnectionMemory},
+        par_map_connections, profiling,
+        protocol::ProtocolChannel,
+        qos::QosConfiguration,
+        socket::{SocketConfiguration, SocketRelease},
+        stats_history::{StatsHistory, StatsHistoryConfig},
+        stun::{query_external_address, DEFAULT_STUN_ATTEMPTS, DEFAULT_STUN_TIMEOUT},
+        tick::{NetworkTick, SharedNetworkTick},
+        transport::{
+            display_remote, memory::MemoryTransportError, TransportConnection, TransportError,
+        },
+        AsyncRuntime, ClientId, InternalConnectionRef, QuinnetFlush, QuinnetShutdown,
+        QuinnetSyncUpdate, DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE, DEFAULT_KEEP_ALIVE_INTERVAL_S,
         DEFAULT_KILL_MESSAGE_QUEUE_SIZE, DEFAULT_MESSAGE_QUEUE_SIZE,
-        DEFAULT_QCHANNEL_MESSAGES_CHANNEL_SIZE,
+        DEFAULT_QCHANNEL_MESSAGES_CHANNEL_SIZE, QUINNET_ALPN,
     },
 };
 
 #[cfg(feature = "shared-client-id")]
 use crate::server::client_id::spawn_client_id_sender;
 
+#[cfg(feature = "testing")]
+use crate::testing::{ScriptedClient, ScriptedTransport};
+
 #[cfg(feature = "shared-client-id")]
 mod client_id;
 
+mod client_data;
+use client_data::ClientData;
+
 mod error;
 pub use error::*;
 
+/// Module for the server's bandwidth accounting per client
+pub mod bandwidth;
+/// Module for the chunked synchronization of a baseline state to the clients
+pub mod baseline;
 /// Module for the server's certificate features
 pub mod certificate;
+/// Module for the server of the text chat
+pub mod chat;
+/// Module for the artificial network conditions applied to specific clients
+pub mod conditions;
+/// Module for the allocation of the clients ids
+pub mod id_allocation;
+/// Module for the server's idle clients detection
+pub mod idle;
+/// Module for the server's side of the clients' input streams
+pub mod input;
+/// Module for the server's side of the lockstep simulations
+pub mod lockstep;
+/// Module for the master server listing the game servers
+pub mod master;
+/// Module for the routing of the clients' payloads to other worlds or threads
+pub mod routing;
+/// Module for the send rates adapted to the connection of each client
+pub mod send_rate;
+/// Module for the server's slow clients detection
+pub mod slow_clients;
+/// Module for the server's streams to spectators
+pub mod spectator;
+/// Module for the server's health/status responder
+pub mod status;
+/// Module for the server's fixed tick driver
+pub mod tick;
+/// Module for the receive timestamps of the clients' messages
+pub mod timestamp;
+/// Module for the transfer of clients between servers
+pub mod transfer;
+use bandwidth::{BandwidthLimit, BandwidthTracker, BandwidthUsage, ClientBandwidthExceededEvent};
+use conditions::{ClientConditions, Conditioner, HeldPayload};
+use id_allocation::{ClientIdPolicy, ClientIds};
+use idle::{ClientActivity, ClientIdleEvent, IdleDetection};
+use routing::{PayloadRoute, RoutedPayload};
+use slow_clients::{
+    BacklogChange, ClientBacklog, SlowClientDetection, SlowClientEvent, SlowClientRecoveredEvent,
+};
+use status::{status_connection_task, StatusConfiguration, StatusState};
+use timestamp::ReceiveTimestamp;
+use transfer::{
+    ClientTransferEvent, ClientTransferRejectedEvent, TransferKey, TransferState, TransferTarget,
+    TransferTicket, TRANSFER_CLOSE_DELAY,
+};
+/// Module for the server's NAT-PMP port mapping features
+#[cfg(feature = "port-mapping")]
+pub mod port_mapping;
+#[cfg(feature = "port-mapping")]
+use port_mapping::{
+    port_mapping_task, PortMapping, PortMappingConfiguration, PortMappingFailedEvent,
+    PortMappingSucceededEvent,
+};
+
+/// Delay given to a client notified of its protocol mismatch to close its connection, see [`Endpoint::set_protocol_check`]
+const PROTOCOL_MISMATCH_CLOSE_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest time [`QuinnetServer::stop_endpoint`] waits for the closing connections to reach their clients, before closing the sockets of the endpoint
+pub const ENDPOINT_STOP_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// Connection event raised when a client just connected to the server. Raised in the CoreStage::PreUpdate stage.
-#[derive(Event, Debug, Copy, Clone)]
+///
+/// No message of the client is delivered before this event: the client id is unknown to the endpoint until then.
+#[derive(Event, Debug, Clone)]
 pub struct ConnectionEvent {
     /// Id of the client who connected
     pub id: ClientId,
+    /// Server name (SNI) sent by the client, see [`Endpoint::set_virtual_host`]
+    pub server_name: Option<String>,
 }
 
 /// ConnectionLost event raised when a client is considered disconnected from the server. Raised in the CoreStage::PreUpdate stage.
-#[derive(Event, Debug, Copy, Clone)]
+///
+/// Raised exactly once per connected client, whichever side closed the connection, including when the endpoint is stopped.
+#[derive(Event, Debug, Clone)]
 pub struct ConnectionLostEvent {
     /// Id of the client who lost connection
     pub id: ClientId,
+    /// Why the client was disconnected
+    pub reason: DisconnectReason,
+}
+
+/// Raised when an incoming QUIC connection reaches the endpoint, before its handshake, whether it is accepted or refused. Raised in the CoreStage::PreUpdate stage.
+///
+/// With [`ServerEndpointConfiguration::with_address_validation`], raised once the address of the connection is validated. Dropped while the server lags behind, such as during a connection storm: see [`EndpointStats`] for exact counts.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ConnectionAttemptEvent {
+    /// Address the connection comes from
+    pub remote_addr: SocketAddr,
+}
+
+/// Raised when the handshake of an incoming QUIC connection failed: rejected by TLS, timed out, closed by the peer... Raised in the CoreStage::PreUpdate stage.
+///
+/// Dropped while the server lags behind, such as during a connection storm: see [`EndpointStats::failed_handshakes`] for an exact count.
+#[derive(Event, Debug, Clone)]
+pub struct HandshakeFailedEvent {
+    /// Address the connection came from
+    pub remote_addr: SocketAddr,
+    /// Why the handshake failed
+    pub error: quinn::ConnectionError,
+}
+
+impl HandshakeFailedEvent {
+    /// Returns the TLS alert which failed the handshake, sent by either side, such as `48` (unknown CA) for a client which does not trust the certificate of the server
+    pub fn tls_alert(&self) -> Option<u8> {
+        let code = match &self.error {
+            quinn::ConnectionError::TransportError(err) => err.code,
+            quinn::ConnectionError::ConnectionClosed(close) => close.error_code,
+            _ => return None,
+        };
+        // The crypto errors are in the 0x100-0x1ff range, see RFC 9000 section 20.1
+        match u64::from(code) {
+            code @ 0x100..=0x1ff => Some((code - 0x100) as u8),
+            _ => None,
+        }
+    }
+}
+
+/// Event raised at each [`CloseStage`] reached by the connection of a client, once disconnected by the server. Raised in the CoreStage::PreUpdate stage.
+///
+/// Not raised for the lost clients, nor for the stages reached after the endpoint was stopped.
+#[derive(Event, Debug, Clone)]
+pub struct ClientCloseStageEvent {
+    /// Id of the disconnected client
+    pub id: ClientId,
+    /// Stage reached by the connection
+    pub stage: CloseStage,
+    /// Time elapsed since the connection started to drain its channels
+    pub elapsed: Duration,
+}
+
+/// Event raised when the disconnect hook of a client completed, see [`Endpoint::set_disconnect_hook`]. Raised in the CoreStage::PreUpdate stage, after the [`ConnectionLostEvent`] of the client.
+///
+/// Not raised for the hooks completing after the endpoint was stopped.
+#[derive(Event, Debug, Clone)]
+pub struct DisconnectHookCompletedEvent {
+    /// Id of the disconnected client
+    pub id: ClientId,
+    /// Why the client was disconnected
+    pub reason: DisconnectReason,
+}
+
+/// Future returned by a disconnect hook, see [`Endpoint::set_disconnect_hook`]
+pub type DisconnectHookFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+type DisconnectHook = Arc<dyn Fn(ClientId, DisconnectReason) -> DisconnectHookFuture + Send + Sync>;
+
+/// Reason of a client disconnection, carried by [`ConnectionLostEvent`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The client closed the connection, with an application close code and reason
+    ClosedByClient {
+        /// Application close code sent by the client
+        code: CloseCode,
+        /// Reason sent by the client
+        reason: Bytes,
+    },
+    /// The connection timed out
+    TimedOut,
+    /// The client was disconnected by the server, see [`Endpoint::disconnect_client`]
+    DisconnectedByServer,
+    /// The endpoint was stopped while the client was connected
+    EndpointStopped,
+    /// The client exceeded its protocol violations budget, see [`HardeningConfiguration::with_decode_error_budget`]
+    ProtocolViolation,
+    /// The client was disconnected after being idle, see [`IdleDetection::kick`]
+    Idle,
+    /// The client was transferred to another server, see [`Endpoint::transfer_client`]
+    Transferred,
+    /// The protocol hash of the client did not match the hash of the server, see [`Endpoint::set_protocol_check`]
+    ProtocolMismatch,
+    /// The connection of the client exceeded its memory budget, see [`Endpoint::set_memory_budget`]
+    MemoryBudgetExceeded,
+    /// The client stayed slow for too long, see [`SlowClientDetection::disconnect_after`]
+    SlowClient,
+    /// The connection was lost because of a transport or protocol error
+    Error(String),
+}
+
+impl DisconnectReason {
+    fn from_transport_error(err: &TransportError) -> Self {
+        let TransportError::ConnectionLost(err) = err else {
+            return DisconnectReason::Error(err.to_string());
+        };
+        if let Some(err) = err.downcast_ref::<quinn::ConnectionError>() {
+            return match err {
+                quinn::ConnectionError::ApplicationClosed(close) => {
+                    DisconnectReason::ClosedByClient {
+                        code: close.error_code.into_inner().into(),
+                        reason: close.reason.clone(),
+                    }
+                }
+                quinn::ConnectionError::TimedOut => DisconnectReason::TimedOut,
+                quinn::ConnectionError::LocallyClosed => DisconnectReason::DisconnectedByServer,
+                err => DisconnectReason::Error(err.to_string()),
+            };
+        }
+        match err.downcast_ref::<MemoryTransportError>() {
+            // An in-memory connection closed by the client
+            Some(MemoryTransportError::ApplicationClosed(code)) => {
+                DisconnectReason::ClosedByClient {
+                    code: *code,
+                    reason: code.reason(),
+                }
+            }
+            Some(MemoryTransportError::Closed) => DisconnectReason::ClosedByClient {
+                code: CloseCode::Closed,
+                reason: Bytes::new(),
+            },
+            _ => DisconnectReason::Error(err.to_string()),
+        }
+    }
+}
+
+/// Raised when a message sent to a group of clients with one of the `try_` group & broadcast methods could not be sent to one of the clients, such as [`Endpoint::try_broadcast_message`]. Raised in the CoreStage::PreUpdate stage.
+///
+/// The message is still sent to the other clients.
+#[derive(Event, Debug)]
+pub struct ClientSendFailedEvent {
+    /// Id of the client the message could not be sent to
+    pub client_id: ClientId,
+    /// Channel the message was sent on
+    pub channel_id: ChannelId,
+    /// Why the message could not be sent
+    pub error: ServerSendError,
+}
+
+/// Raised when a client acknowledged a message sent with [`Endpoint::send_unreliable_tracked`]. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct MessageAckedEvent {
+    /// Id of the client who received the message
+    pub id: ClientId,
+    /// Channel the message was sent on
+    pub channel_id: ChannelId,
+    /// Id returned when sending the message
+    pub message_id: TrackedMessageId,
+}
+
+/// Raised when a client did not acknowledge a message sent with [`Endpoint::send_unreliable_tracked`] within [`crate::shared::channels::MESSAGE_ACK_TIMEOUT`]. Raised in the CoreStage::PreUpdate stage.
+///
+/// The message may still have reached the client if its acknowledgement was delayed, a late acknowledgement is ignored: each tracked message gets either a [`MessageAckedEvent`] or a [`MessageLostEvent`]. Neither is raised for the messages still in flight when the client disconnects.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct MessageLostEvent {
+    /// Id of the client the message was sent to
+    pub id: ClientId,
+    /// Channel the message was sent on
+    pub channel_id: ChannelId,
+    /// Id returned when sending the message
+    pub message_id: TrackedMessageId,
+}
+
+/// Raised when a client reset the stream of a reliable channel, which resumed on a new stream. Raised in the CoreStage::PreUpdate stage.
+///
+/// The message being sent is sent again on the new stream, but the messages sent before it on the reset stream may not have reached the client.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ChannelResumedEvent {
+    /// Id of the client who reset the stream
+    pub id: ClientId,
+    /// Channel which resumed
+    pub channel_id: ChannelId,
+}
+
+/// Raised when a client left the liveness probe of a reliable channel unanswered, see [`ChannelConfig::liveness_probe`]. Raised once per unanswered probe, the channel is probed again once the client answers. Raised in the CoreStage::PreUpdate stage.
+///
+/// The connection itself may be alive: the stream of the channel is stuck, such as when the client stopped reading it.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ChannelUnresponsiveEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// Channel which did not answer
+    pub channel_id: ChannelId,
+    /// Time waited for the answer to the probe
+    pub waited: Duration,
+}
+
+/// Raised when the dictionary announced by a client for a compressed channel does not match the dictionary of the channel on the server, see [`ChannelConfig::compressed_with_dictionary`]. The channel is closed for this client only, the other clients keep it. Raised in the CoreStage::PreUpdate stage.
+///
+/// The payloads compressed with another dictionary could not be decompressed: the channel is rejected instead of dropping them silently.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ChannelRejectedEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// Channel which was closed
+    pub channel_id: ChannelId,
+    /// Id of the dictionary announced by the client, `None` for a channel compressed without a dictionary
+    pub client_dictionary_id: Option<u32>,
+    /// Id of the dictionary of the channel on the server, `None` for a channel compressed without a dictionary
+    pub server_dictionary_id: Option<u32>,
+}
+
+/// Raised when a message could not be sent on a channel to a client and was dropped, the channel and the connection stay open. Raised in the CoreStage::PreUpdate stage.
+///
+/// Errors are not reported while the server is lagging behind a flood of them.
+#[derive(Event, Debug, Clone)]
+pub struct ChannelErrorEvent {
+    /// Id of the client the message was sent to
+    pub id: ClientId,
+    /// Channel of the message
+    pub channel_id: ChannelId,
+    /// What went wrong
+    pub error: ChannelError,
+}
+
+/// Raised when the congestion controller of the connection to a client exits or re-enters slow start, or reduces its congestion window, if enabled with [`Endpoint::set_congestion_events`]. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CongestionEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// What changed
+    pub kind: CongestionEventKind,
+    /// Stats of the path when the change was detected
+    pub path_stats: PathStats,
+}
+
+/// Raised when the max size of the datagrams to a client changes, usually when the path MTU discovery found a larger MTU or a black hole. Raised in the CoreStage::PreUpdate stage.
+///
+/// Also raised with the initial size once the client is connected. See [`ServerSideConnection::max_unreliable_payload_size`] to budget the payloads of the unreliable channels.
+#[derive(Event, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MaxDatagramSizeChangedEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// Max size of the datagrams before the change, `None` for the initial size
+    pub previous: Option<usize>,
+    /// Max size of the datagrams from now on, `None` if the datagrams are disabled
+    pub max_datagram_size: Option<usize>,
+}
+
+/// Raised when the connection of a client exceeds its memory budget, or refuses a message because of it, see [`Endpoint::set_memory_budget`]. Raised again once the connection went back under its budget and exceeds it again. Raised in the CoreStage::PreUpdate stage.
+///
+/// With [`MemoryBudgetPolicy::Disconnect`], the client is then disconnected with [`CloseCode::MemoryBudgetExceeded`] and a [`ConnectionLostEvent`] with [`DisconnectReason::MemoryBudgetExceeded`].
+#[derive(Event, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryBudgetExceededEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// Budget of the connection
+    pub budget: MemoryBudget,
+    /// Bytes buffered by the connection when the budget was exceeded
+    pub used: usize,
+}
+
+/// Raised when a client sent a malformed frame or payload, which was dropped. Raised in the CoreStage::PreUpdate stage.
+///
+/// See [`ServerEndpointConfiguration::with_hardening`]. Violations are not reported while the server is lagging behind a flood of them, they are still dropped.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ProtocolViolationEvent {
+    /// Id of the client who sent the malformed traffic
+    pub id: ClientId,
+    /// What was wrong with the traffic
+    pub violation: ProtocolViolation,
+    /// Number of violations committed by the client so far, this one included
+    pub count: u32,
+}
+
+/// Raised when the protocol hash of a client does not match the hash of the server, see [`Endpoint::set_protocol_check`]. Raised in the CoreStage::PreUpdate stage.
+///
+/// The client is then disconnected with [`CloseCode::ProtocolMismatch`], instead of failing later on messages it can not decode: the [`ConnectionLostEvent`] of the client follows, with [`DisconnectReason::ProtocolMismatch`].
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ProtocolMismatchEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// Protocol hash of the client, see [`ChannelsConfiguration::protocol_hash`]
+    pub client_hash: u64,
+    /// Protocol hash of the channels given to the client: the endpoint channels, or its virtual host's
+    pub server_hash: u64,
+}
+
+/// Raised when a client forwarded by a gateway presented a valid forwarding header, see [`Endpoint::set_forwarding_key`]. Raised in the CoreStage::PreUpdate stage.
+///
+/// The header is presented right after connecting: this event follows the [`ConnectionEvent`] of the client.
+#[derive(Event, Debug, Clone)]
+pub struct ClientForwardedEvent {
+    /// Id of the client on this server
+    pub id: ClientId,
+    /// Original client, as seen by the gateway
+    pub client: ForwardedClient,
+}
+
+/// Raised when a client presented a forwarding header which was rejected. Raised in the CoreStage::PreUpdate stage.
+///
+/// The client stays connected, the app decides whether to disconnect it.
+#[derive(Event, Debug, Clone)]
+pub struct ClientForwardingRejectedEvent {
+    /// Id of the client on this server
+    pub id: ClientId,
+    /// Why the header was rejected
+    pub err: ForwardingError,
+}
+
+/// Raised when the server endpoint started listening. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct EndpointStartedEvent {
+    /// Local address the endpoint is bound to
+    pub local_addr: SocketAddr,
+}
+
+/// Raised when the server endpoint was stopped. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct EndpointStoppedEvent;
+
+/// Raised when the external address of the server endpoint was discovered, see [`ServerEndpointConfiguration::with_stun_server`]. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ExternalAddressDiscoveredEvent {
+    /// Address of the endpoint as seen from outside its network
+    pub external_addr: SocketAddr,
 }
 
 /// Configuration of the server, used when the server starts an Endpoint
 #[derive(Debug, Deserialize, Clone)]
 pub struct ServerEndpointConfiguration {
     local_bind_addr: SocketAddr,
+    #[serde(default)]
+    stun_server: Option<SocketAddr>,
+    #[cfg(feature = "port-mapping")]
+    #[serde(default)]
+    port_mapping: Option<PortMappingConfiguration>,
+    #[serde(default)]
+    hardening: HardeningConfiguration,
+    #[serde(default)]
+    status: Option<StatusConfiguration>,
+    #[serde(default)]
+    qos: QosConfiguration,
+    #[serde(default)]
+    socket: SocketConfiguration,
+    #[serde(default = "default_shards")]
+    shards: NonZeroUsize,
+    #[serde(default)]
+    address_validation: bool,
+    #[serde(skip)]
+    client_authentication: Option<ClientAuthentication>,
+    #[serde(skip)]
+    tls_config: Option<Arc<rustls::ServerConfig>>,
+    #[serde(default)]
+    key_log: bool,
+}
+
+fn default_shards() -> NonZeroUsize {
+    NonZeroUsize::MIN
 }
 
 impl ServerEndpointConfiguration {
@@ -89,7 +558,7 @@ impl ServerEndpointConfiguration {
     /// ```
     pub fn from_string(local_bind_addr_str: &str) -> Result<Self, AddrParseError> {
         let local_bind_addr = local_bind_addr_str.parse()?;
-        Ok(Self { local_bind_addr })
+        Ok(Self::from_addr(local_bind_addr))
     }
 
     /// Creates a new ServerEndpointConfiguration
@@ -108,9 +577,7 @@ impl ServerEndpointConfiguration {
     /// let config = ServerEndpointConfiguration::from_ip(Ipv6Addr::UNSPECIFIED, 6000);
     /// ```
     pub fn from_ip(local_bind_ip: impl Into<IpAddr>, local_bind_port: u16) -> Self {
-        Self {
-            local_bind_addr: SocketAddr::new(local_bind_ip.into(), local_bind_port),
-        }
+        Self::from_addr(SocketAddr::new(local_bind_ip.into(), local_bind_port))
     }
 
     /// Creates a new ServerEndpointConfiguration
@@ -131,14 +598,207 @@ impl ServerEndpointConfiguration {
     ///       );
     /// ```
     pub fn from_addr(local_bind_addr: SocketAddr) -> Self {
-        Self { local_bind_addr }
+        Self {
+            local_bind_addr,
+            stun_server: None,
+            #[cfg(feature = "port-mapping")]
+            port_mapping: None,
+            hardening: HardeningConfiguration::default(),
+            status: None,
+            qos: QosConfiguration::default(),
+            socket: SocketConfiguration::default(),
+            shards: default_shards(),
+            address_validation: false,
+            client_authentication: None,
+            tls_config: None,
+            key_log: false,
+        }
+    }
+
+    /// Sets the defenses of the endpoint against malformed traffic from its clients, see [`HardeningConfiguration`].
+    ///
+    /// Each rejected frame or payload raises a [`ProtocolViolationEvent`].
+    pub fn with_hardening(mut self, hardening: HardeningConfiguration) -> Self {
+        self.hardening = hardening;
+        self
+    }
+
+    /// Serves a health/status responder on the endpoint, to the connections negotiating the ALPN protocol of `status`, see [`StatusConfiguration`].
+    ///
+    /// The endpoint then advertises both [`crate::shared::QUINNET_ALPN`] and the status protocol: its Quinnet clients must offer [`crate::shared::QUINNET_ALPN`].
+    pub fn with_status(mut self, status: StatusConfiguration) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Requires the clients to present a certificate signed by one of the authorities of `client_authentication` (mutual TLS), see [`ClientAuthentication`].
+    ///
+    /// The certificates of a client are available with [`ServerSideConnection::peer_certificates`].
+    pub fn with_client_authentication(
+        mut self,
+        client_authentication: ClientAuthentication,
+    ) -> Self {
+        self.client_authentication = Some(client_authentication);
+        self
+    }
+
+    /// Uses `tls_config` as the TLS configuration of the endpoint instead of the one built by Quinnet, for specific security requirements: cipher suites, crypto provider, key log, certificate resolver...
+    ///
+    /// The certificate and the [`ClientAuthentication`] of the endpoint are then ignored, `tls_config` presents its own. The ALPN protocols of the endpoint are used if `tls_config` has none. The config must support TLS 1.3 with a QUIC initial cipher suite, otherwise the endpoint fails to start with [`EndpointStartError::UnsupportedTlsConfig`].
+    pub fn with_tls_config(mut self, tls_config: Arc<rustls::ServerConfig>) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// Logs the TLS secrets of the connections to the file named by the `SSLKEYLOGFILE` environment variable, in the NSS key log format, so that a packet capture of the endpoint can be decrypted by Wireshark. Nothing is logged if the variable is not set.
+    ///
+    /// For development only: anyone reading the file can decrypt the traffic. Not applied to a custom [`ServerEndpointConfiguration::with_tls_config`], whose `key_log` is left to the app.
+    pub fn with_key_log(mut self, key_log: bool) -> Self {
+        self.key_log = key_log;
+        self
+    }
+
+    /// Marks the packets sent by the endpoint with the DSCP of `qos` and enables or disables ECN, see [`QosConfiguration`]
+    pub fn with_qos(mut self, qos: QosConfiguration) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Sets the performance settings of the socket of the endpoint, see [`SocketConfiguration`]
+    pub fn with_socket(mut self, socket: SocketConfiguration) -> Self {
+        self.socket = socket;
+        self
+    }
+
+    /// Shards the endpoint across `shards` sockets bound on the same address with `SO_REUSEPORT`, each driven by its own quinn endpoint, for very high client counts. Linux only, other platforms fail to start a sharded endpoint with [`EndpointStartError::ShardingUnsupported`].
+    ///
+    /// The OS balances the clients among the shards by their address. The shards are still presented as a single [`Endpoint`], sharing its channels and [`ClientId`] space, see [`ServerSideConnection::shard`] and [`Endpoint::shard_client_counts`]. A client whose address changes, such as after a NAT rebinding, may reach another shard which doesn't know its connection and resets it.
+    pub fn with_shards(mut self, shards: NonZeroUsize) -> Self {
+        self.shards = shards;
+        self
+    }
+
+    /// Validates the address of the incoming connections before their handshake, by answering their first packet with a QUIC Retry. Disabled by default.
+    ///
+    /// Costs a round trip to each connection, but a spoofed address cannot start a handshake: useful against connection storms. The retries sent are counted by [`EndpointStats::retries_sent`].
+    pub fn with_address_validation(mut self) -> Self {
+        self.address_validation = true;
+        self
+    }
+
+    /// Queries `stun_server` when the endpoint starts, to discover the external address of the endpoint.
+    ///
+    /// On success, the address is available with [`Endpoint::external_addr`] and an [`ExternalAddressDiscoveredEvent`] is raised. The query is done before the endpoint starts accepting connections, see [`crate::shared::stun::query_external_address`].
+    pub fn with_stun_server(mut self, stun_server: SocketAddr) -> Self {
+        self.stun_server = Some(stun_server);
+        self
+    }
+
+    /// Requests a port mapping on the gateway when the endpoint starts, and removes it when the endpoint stops.
+    ///
+    /// A [`PortMappingSucceededEvent`] or [`PortMappingFailedEvent`] is raised once the gateway answered.
+    #[cfg(feature = "port-mapping")]
+    pub fn with_port_mapping(mut self, port_mapping: PortMappingConfiguration) -> Self {
+        self.port_mapping = Some(port_mapping);
+        self
+    }
+}
+
+/// Configuration of a server endpoint started on a [`quinn::Endpoint`] created by the app, see [`QuinnetServer::start_external_endpoint`]
+#[derive(Debug, Clone)]
+pub struct ExternalEndpointConfiguration {
+    endpoint: QuinnEndpoint,
+    hardening: HardeningConfiguration,
+    routing: Option<ProtocolRouting>,
+    status: Option<StatusConfiguration>,
+}
+
+#[derive(Debug, Clone)]
+struct ProtocolRouting {
+    quinnet_protocols: Vec<Vec<u8>>,
+    other_connections: mpsc::UnboundedSender<quinn::Connection>,
+}
+
+impl ProtocolRouting {
+    fn routes_to_quinnet(&self, protocol: Option<&Vec<u8>>) -> bool {
+        match protocol {
+            Some(protocol) => self.quinnet_protocols.contains(protocol),
+            None => true,
+        }
+    }
+
+    /// Returns false if the connection was refused, with no service for its protocol
+    fn forward(&self, connection: quinn::Connection) -> bool {
+        match self.other_connections.send(connection) {
+            Ok(()) => true,
+            Err(err) => {
+                debug!(
+                    "Refused a connection from {}: no service for its protocol",
+                    err.0.remote_address()
+                );
+                TransportConnection::close(&err.0, CloseCode::ProtocolMismatch);
+                false
+            }
+        }
+    }
+}
+
+impl ExternalEndpointConfiguration {
+    /// Creates a new ExternalEndpointConfiguration
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - Endpoint to accept the clients on. Its server configuration, see [`quinn::Endpoint::set_server_config`], is left to the app: it must be set for the endpoint to accept incoming connections.
+    pub fn new(endpoint: QuinnEndpoint) -> Self {
+        Self {
+            endpoint,
+            hardening: HardeningConfiguration::default(),
+            routing: None,
+            status: None,
+        }
+    }
+
+    /// Sets the defenses of the endpoint against malformed traffic from its clients, see [`ServerEndpointConfiguration::with_hardening`].
+    pub fn with_hardening(mut self, hardening: HardeningConfiguration) -> Self {
+        self.hardening = hardening;
+        self
+    }
+
+    /// Serves a health/status responder on the endpoint, see [`ServerEndpointConfiguration::with_status`]. The ALPN protocol of `status` must be part of the server configuration of the endpoint.
+    ///
+    /// The status connections are answered before any protocol routing.
+    pub fn with_status(mut self, status: StatusConfiguration) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Shares the incoming connections of the endpoint with other services, by their negotiated ALPN protocol.
+    ///
+    /// The connections which negotiated one of `quinnet_protocols`, or no protocol at all, are handled by Quinnet. The other ones are sent on `other_connections`, for example to an HTTP/3 server running on the same endpoint. When `other_connections` is closed, these connections are closed with [`CloseCode::ProtocolMismatch`].
+    pub fn with_protocol_routing(
+        mut self,
+        quinnet_protocols: Vec<Vec<u8>>,
+        other_connections: mpsc::UnboundedSender<quinn::Connection>,
+    ) -> Self {
+        self.routing = Some(ProtocolRouting {
+            quinnet_protocols,
+            other_connections,
+        });
+        self
     }
 }
 
 #[derive(Debug)]
 pub(crate) enum ServerAsyncMessage {
-    ClientConnected(ServerSideConnection),
-    ClientConnectionClosed(ClientId), // TODO Might add a ConnectionError
+    ConnectionAttempt(SocketAddr),
+    HandshakeFailed(SocketAddr, quinn::ConnectionError),
+    ClientConnected(Box<ServerSideConnection>),
+    ClientConnectionClosed(ClientId, DisconnectReason),
+    ClientCloseStage(ClientId, CloseStage, Duration),
+    DisconnectHookCompleted(ClientId, DisconnectReason),
+    ExternalAddressDiscovered(SocketAddr),
+    #[cfg(feature = "port-mapping")]
+    PortMapping(Result<PortMapping, PortMappingError>),
 }
 
 #[derive(Debug, Clone)]
@@ -152,7 +812,8 @@ pub struct ServerSideConnection {
     connection_handle: InternalConnectionRef,
 
     channels: Vec<Option<Channel>>,
-    bytes_from_client_recv: mpsc::Receiver<(ChannelId, Bytes)>,
+    channels_configs: SharedChannelConfigs,
+    bytes_from_client_recv: IncomingPayloads,
     close_sender: broadcast::Sender<CloseReason>,
 
     pub(crate) to_connection_send: mpsc::Sender<ServerSyncMessage>,
@@ -161,20 +822,81 @@ pub struct ServerSideConnection {
 
     received_bytes_count: usize,
     sent_bytes_count: usize,
+    deferred_flush: bool,
+    server_name: Option<String>,
+    /// The channels of the connection come from a virtual host instead of the endpoint
+    virtual_host: bool,
+    protocol_violations: u32,
+    activity: ClientActivity,
+    bandwidth: BandwidthTracker,
+    /// Opened on the first control message sent to the client
+    control_channel: Option<Channel>,
+    /// Set once the client is transferred, it is disconnected if still connected at this instant
+    transfer_deadline: Option<Instant>,
+    /// Set once the client is notified of its protocol mismatch, it is disconnected if still connected at this instant
+    mismatch_deadline: Option<Instant>,
+    /// Set once the client presented a valid forwarding header
+    forwarded_client: Option<ForwardedClient>,
+    /// Dictionaries of the compressed channels, announced to the client and checked against its own
+    dictionaries: DictionaryNegotiation,
+    conditioner: Option<Conditioner>,
+    /// Tracked messages sent to the client, waiting for their acknowledgement
+    acks: AckTracker,
+    /// Shard of the endpoint the client connected to
+    shard: usize,
+    /// Instance the client is assigned to, see [`Endpoint::assign_instance`]
+    instance: Option<String>,
+    data: ClientData,
+    /// Network tick of the endpoint, stamped on the payloads of the ticked channels
+    network_tick: SharedNetworkTick,
+    stats_history: Option<StatsHistory>,
+    congestion: Option<CongestionMonitor>,
+    /// Max datagram size reported by the last [`MaxDatagramSizeChangedEvent`]
+    last_max_datagram_size: Option<usize>,
+    /// Bytes buffered by the queues and the received payloads of the connection
+    memory: SharedConnectionMemory,
+    /// Set once a [`MemoryBudgetExceededEvent`] is raised, until the connection is back under its budget
+    memory_exceeded: bool,
+    backlog: ClientBacklog,
 }
 
 impl ServerSideConnection {
     fn new(
         connection_handle: InternalConnectionRef,
-        bytes_from_client_recv: mpsc::Receiver<(ChannelId, Bytes)>,
+        channels_configs: SharedChannelConfigs,
+        bytes_from_client_recv: IncomingRecv,
         close_sender: broadcast::Sender<CloseReason>,
         to_connection_send: mpsc::Sender<ServerSyncMessage>,
         from_channels_recv: mpsc::Receiver<ChannelAsyncMessage>,
         to_channels_send: mpsc::Sender<ChannelSyncMessage>,
     ) -> Self {
+        let memory = SharedConnectionMemory::default();
         Self {
+            server_name: connection_handle.server_name(),
+            virtual_host: false,
+            protocol_violations: 0,
+            activity: ClientActivity::new(),
+            bandwidth: BandwidthTracker::default(),
+            control_channel: None,
+            transfer_deadline: None,
+            mismatch_deadline: None,
+            forwarded_client: None,
+            dictionaries: DictionaryNegotiation::default(),
+            conditioner: None,
+            acks: AckTracker::default(),
+            shard: 0,
+            instance: None,
+            data: ClientData::default(),
+            network_tick: SharedNetworkTick::default(),
+            stats_history: None,
+            congestion: None,
+            last_max_datagram_size: None,
+            memory_exceeded: false,
+            backlog: ClientBacklog::default(),
             connection_handle,
-            bytes_from_client_recv,
+            channels_configs,
+            bytes_from_client_recv: IncomingPayloads::new(bytes_from_client_recv, memory.clone()),
+            memory,
             close_sender,
             to_connection_send,
             to_channels_send,
@@ -182,16 +904,28 @@ impl ServerSideConnection {
             channels: Vec::new(),
             received_bytes_count: 0,
             sent_bytes_count: 0,
+            deferred_flush: false,
         }
     }
 
+    fn on_shard(mut self, shard: usize) -> Self {
+        self.shard = shard;
+        self
+    }
+
     /// Immediately prevents new messages from being sent on the channel and signal the channel to closes all its background tasks.
     /// Before trully closing, the channel will wait for all buffered messages to be properly sent according to the channel type.
     /// Can fail if the [ChannelId] is unknown, or if the channel is already closed.
     pub(crate) fn close_channel(&mut self, channel_id: ChannelId) -> Result<(), ChannelCloseError> {
         if (channel_id as usize) < self.channels.len() {
             match self.channels[channel_id as usize].take() {
-                Some(channel) => channel.close(),
+                Some(channel) => {
+                    if let Ok(mut channels_configs) = self.channels_configs.write() {
+                        channels_configs.remove(&channel_id);
+                    }
+                    self.dictionaries.closed(channel_id);
+                    channel.close()
+                }
                 None => Err(ChannelCloseError::ChannelAlreadyClosed),
             }
         } else {
@@ -202,20 +936,24 @@ impl ServerSideConnection {
     pub(crate) fn create_connection_channel(
         &mut self,
         id: ChannelId,
-        kind: ChannelKind,
+        config: ChannelConfig,
+        buffers: BufferPool,
     ) -> Result<(), AsyncChannelError> {
-        let channel = self.create_unregistered_connection_channel(id, kind)?;
-        self.register_connection_channel(channel);
+        let channel = self.create_unregistered_connection_channel(id, config.clone(), buffers)?;
+        self.register_connection_channel(channel, config);
         Ok(())
     }
 
     pub(crate) fn create_unregistered_connection_channel(
         &mut self,
         id: ChannelId,
-        kind: ChannelKind,
+        config: ChannelConfig,
+        buffers: BufferPool,
     ) -> Result<Channel, AsyncChannelError> {
-        let (bytes_to_channel_send, bytes_to_channel_recv) =
-            mpsc::channel::<Bytes>(DEFAULT_MESSAGE_QUEUE_SIZE);
+        let queue = Arc::new(OutgoingQueue::new(
+            DEFAULT_MESSAGE_QUEUE_SIZE,
+            self.memory.clone(),
+        ));
         let (channel_close_send, channel_close_recv) =
             mpsc::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
 
@@ -223,11 +961,18 @@ impl ServerSideConnection {
             .to_channels_send
             .try_send(ChannelSyncMessage::CreateChannel {
                 id,
-                kind,
-                bytes_to_channel_recv,
+                config: config.clone(),
+                queue: queue.clone(),
+                buffers,
                 channel_close_recv,
             }) {
-            Ok(_) => Ok(Channel::new(id, bytes_to_channel_send, channel_close_send)),
+            Ok(_) => {
+                let channel = Channel::new(id, &config, queue, channel_close_send);
+                Ok(match config.is_ticked() {
+                    true => channel.with_network_tick(self.network_tick.clone()),
+                    false => channel,
+                })
+            }
             Err(err) => match err {
                 TrySendError::Full(_) => Err(AsyncChannelError::FullQueue),
                 TrySendError::Closed(_) => Err(AsyncChannelError::InternalChannelClosed),
@@ -235,7 +980,30 @@ impl ServerSideConnection {
         }
     }
 
-    pub(crate) fn register_connection_channel(&mut self, channel: Channel) {
+    fn send_control(
+        &mut self,
+        message: ControlMessage,
+        buffers: BufferPool,
+    ) -> Result<(), AsyncChannelError> {
+        let channel = match &self.control_channel {
+            Some(channel) => channel,
+            None => {
+                let channel = self.create_unregistered_connection_channel(
+                    CONTROL_CHANNEL_ID,
+                    control_channel_config(),
+                    buffers,
+                )?;
+                self.control_channel.insert(channel)
+            }
+        };
+        channel.send_payload(message.encode(), None, false)
+    }
+
+    pub(crate) fn register_connection_channel(&mut self, channel: Channel, config: ChannelConfig) {
+        self.dictionaries.opened(channel.id(), &config);
+        if let Ok(mut channels_configs) = self.channels_configs.write() {
+            channels_configs.insert(channel.id(), config);
+        }
         let channel_index = channel.id() as usize;
         if channel_index < self.channels.len() {
             self.channels[channel_index] = Some(channel);
@@ -248,7 +1016,10 @@ impl ServerSideConnection {
 
     /// Signal the connection to closes all its background tasks. Before trully closing, the connection will wait for all buffered messages in all its opened channels to be properly sent according to their respective channel type.
     pub(crate) fn close(&mut self) -> Result<(), EndpointConnectionAlreadyClosed> {
-        match self.close_sender.send(CloseReason::LocalOrder) {
+        match self
+            .close_sender
+            .send(CloseReason::LocalOrder(CloseCode::Closed))
+        {
             Ok(_) => Ok(()),
             Err(_) => {
                 // The only possible error for a send is that there is no active receivers, meaning that the tasks are already terminated.
@@ -264,64 +1035,384 @@ impl ServerSideConnection {
         }
     }
 
-    /// See [quinn::Connection::max_datagram_size]
-    pub fn max_datagram_size(&self) -> Option<usize> {
-        self.connection_handle.max_datagram_size()
+    /// Server name (SNI) sent by the client during the handshake, `None` if the client connected with an IP address or on a custom transport without one
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
     }
 
-    /// Returns statistics about a client connection
-    pub fn connection_stats(&self) -> ConnectionStats {
-        self.connection_handle.stats()
+    /// Shard of the endpoint the client connected to, see [`ServerEndpointConfiguration::with_shards`]. 0 for the unsharded endpoints and the custom transports.
+    pub fn shard(&self) -> usize {
+        self.shard
     }
 
-    /// Returns how many bytes were received on this connection since the last time it was cleared and reset this value to 0
-    pub fn clear_received_bytes_count(&mut self) -> usize {
-        let bytes_count = self.received_bytes_count;
-        self.received_bytes_count = 0;
-        bytes_count
+    /// Instance the client is assigned to, see [`Endpoint::assign_instance`]
+    pub fn instance(&self) -> Option<&str> {
+        self.instance.as_deref()
     }
 
-    /// Returns how many bytes were received on this connection since the last time it was cleared
-    pub fn received_bytes_count(&self) -> usize {
-        self.received_bytes_count
+    /// Address of the client, `None` for custom transports without addresses
+    pub fn remote_address(&self) -> Option<SocketAddr> {
+        self.connection_handle.remote_address()
     }
 
-    /// Returns how many bytes were received on this connection since the last time it was cleared and reset this value to 0
-    pub fn clear_sent_bytes_count(&mut self) -> usize {
-        let bytes_count = self.sent_bytes_count;
-        self.sent_bytes_count = 0;
-        bytes_count
+    /// Validates the forwarding header presented by the client, only accepted once
+    fn forward(
+        &mut self,
+        key: Option<&ForwardingKey>,
+        header: &[u8],
+    ) -> Result<ForwardedClient, ForwardingError> {
+        let key = key.ok_or(ForwardingError::NoForwardingKey)?;
+        if self.forwarded_client.is_some() {
+            return Err(ForwardingError::AlreadyForwarded);
+        }
+        let client = key.verify(header)?;
+        self.forwarded_client = Some(client.clone());
+        Ok(client)
     }
 
-    /// Returns how many bytes were received on this connection since the last time it was cleared
-    pub fn sent_bytes_count(&self) -> usize {
-        self.sent_bytes_count
+    /// Original client of the connection if it was forwarded by a gateway, see [`Endpoint::set_forwarding_key`]
+    pub fn forwarded_client(&self) -> Option<&ForwardedClient> {
+        self.forwarded_client.as_ref()
     }
-}
 
-/// By default, when starting an [Endpoint], Quinnet creates 1 channel instance of each [ChannelKind], each with their own [ChannelId].
-/// Among those, there is a `default` channel which will be used when you don't specify the channel. At startup, this default channel is a [ChannelKind::OrderedReliable] channel.
-pub struct Endpoint {
-    clients: HashMap<ClientId, ServerSideConnection>,
-    client_id_gen: ClientId,
+    /// Address of the original client if the connection was forwarded by a gateway, else [`ServerSideConnection::remote_address`]
+    pub fn client_address(&self) -> Option<SocketAddr> {
+        match &self.forwarded_client {
+            Some(client) => Some(client.client_addr()),
+            None => self.remote_address(),
+        }
+    }
 
-    opened_channels: HashMap<ChannelId, ChannelKind>,
-    available_channel_ids: BTreeSet<ChannelId>,
-    default_channel: Option<ChannelId>,
+    /// See [quinn::Connection::max_datagram_size]. For custom transports, see [`TransportConnection::max_datagram_size`].
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        self.connection_handle.max_datagram_size()
+    }
 
-    close_sender: broadcast::Sender<()>,
+    /// Max size of a payload sent on the unreliable channel `channel_id` to this client, once the headers of its options are deducted, see [`ChannelConfig::datagram_overhead`]. `None` if not connected, if the datagrams are disabled, or if `channel_id` is not an opened unreliable channel. Changes are reported by a [`MaxDatagramSizeChangedEvent`].
+    ///
+    /// The payloads of a compressed channel must fit once compressed.
+    pub fn max_unreliable_payload_size(&self, channel_id: ChannelId) -> Option<usize> {
+        let overhead = self
+            .channels_configs
+            .read()
+            .ok()?
+            .get(&channel_id)
+            .filter(|config| matches!(config.kind(), ChannelKind::Unreliable))
+            .map(ChannelConfig::datagram_overhead)?;
+        self.max_datagram_size()
+            .map(|size| size.saturating_sub(PROTOCOL_HEADER_LEN + overhead))
+    }
 
-    from_async_endpoint_recv: mpsc::Receiver<ServerAsyncMessage>,
+    /// Bytes buffered by the connection: the payloads waiting in the outgoing queues of its channels, and the received payloads not read yet. Bounded by [`Endpoint::set_memory_budget`].
+    pub fn memory_usage(&self) -> usize {
+        self.memory.used()
+    }
 
-    stats: EndpointStats,
-}
+    /// Bytes of the messages waiting in the outgoing queues of the reliable channels of the client. Grows while the client reads its messages slower than the server sends them, see [`Endpoint::set_slow_client_detection`].
+    pub fn pending_reliable_bytes(&self) -> usize {
+        self.channels
+            .iter()
+            .flatten()
+            .filter(|channel| !channel.is_unreliable())
+            .map(Channel::pending_bytes)
+            .sum()
+    }
 
-/// Basic quinnet stats about this server endpoint
-#[derive(Default)]
-pub struct EndpointStats {
-    received_messages_count: u64,
-    connect_count: u32,
+    /// Returns true while the client is slow, see [`Endpoint::set_slow_client_detection`]
+    pub fn is_slow(&self) -> bool {
+        self.backlog.is_slow()
+    }
+
+    /// Applies artificial network conditions to the messages exchanged with this client from now on, `None` to restore its real link. Held messages are then delivered immediately.
+    ///
+    /// Can be changed at any time, for example from an admin command during a playtest.
+    pub fn set_conditions(&mut self, conditions: Option<ClientConditions>) {
+        match (conditions, &mut self.conditioner) {
+            (Some(conditions), Some(conditioner)) => conditioner.set_conditions(conditions),
+            (Some(conditions), None) => self.conditioner = Some(Conditioner::new(conditions)),
+            (None, _) => {
+                if let Some(mut conditioner) = self.conditioner.take() {
+                    self.bytes_from_client_recv.stop_holding();
+                    for held in conditioner.release_all_outbound() {
+                        if let Err(err) = self.send_held(held) {
+                            error!("Failed to send a held message: {}", err);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Artificial network conditions applied to this client, see [`ServerSideConnection::set_conditions`]
+    pub fn conditions(&self) -> Option<&ClientConditions> {
+        self.conditioner.as_ref().map(Conditioner::conditions)
+    }
+
+    /// Applies the conditions of the client: holds the newly received messages and sends the held messages due at `now`. Returns the messages that could not be sent.
+    fn apply_conditions(&mut self, now: Instant) -> Vec<(ChannelId, ServerSendError)> {
+        let Some(conditioner) = self.conditioner.as_mut() else {
+            return Vec::new();
+        };
+        let channels_configs = &self.channels_configs;
+        self.bytes_from_client_recv.hold(now, |channel_id| {
+            conditioner.inbound_due(is_lossy(channels_configs, channel_id), now)
+        });
+        let released = conditioner.release_outbound(now);
+
+        let mut failures = Vec::new();
+        for held in released {
+            let channel_id = held.channel_id;
+            if let Err(err) = self.send_held(held) {
+                failures.push((channel_id, err));
+            }
+        }
+        failures
+    }
+
+    fn send_held(&self, held: HeldPayload) -> Result<(), ServerSendError> {
+        match self.channels.get(held.channel_id as usize) {
+            Some(Some(channel)) => match held.tracked {
+                Some(id) => {
+                    Ok(channel.send_tracked_payload(id, held.payload, self.deferred_flush)?)
+                }
+                None => {
+                    Ok(channel.send_payload(held.payload, held.priority, self.deferred_flush)?)
+                }
+            },
+            Some(None) => Err(ServerSendError::ChannelClosed),
+            None => Err(ServerSendError::InvalidChannelId(held.channel_id)),
+        }
+    }
+
+    /// Returns statistics about a client connection. Zeroed for custom transports without statistics.
+    pub fn connection_stats(&self) -> ConnectionStats {
+        self.connection_handle.stats()
+    }
+
+    /// Certificate chain presented by the client during the handshake, its first certificate being the certificate of the client. `None` if the client did not present any, see [`ServerEndpointConfiguration::with_client_authentication`], or for custom transports without certificates.
+    ///
+    /// The chain was verified against the authorities of the [`ClientAuthentication`] of the endpoint.
+    pub fn peer_certificates(&self) -> Option<Vec<CertificateDer<'static>>> {
+        self.connection_handle.peer_certificates()
+    }
+
+    /// Returns a clone of the underlying [`quinn::Connection`] of the client, `None` for custom transports. See [`crate::client::connection::ClientSideConnection::quic_connection`] for the limits of this escape hatch.
+    #[cfg(feature = "raw")]
+    pub fn quic_connection(&self) -> Option<quinn::Connection> {
+        self.connection_handle.quic_connection()
+    }
+
+    /// Returns the ids of the channels opened on this connection, in increasing order
+    pub fn channel_ids(&self) -> Vec<ChannelId> {
+        self.channels
+            .iter()
+            .flatten()
+            .map(|channel| channel.id())
+            .collect()
+    }
+
+    /// Returns how many messages are waiting in the outgoing queue of the channel, `None` if the channel does not exist or is closed
+    pub fn pending_messages_count<C: Into<ChannelId>>(&self, channel_id: C) -> Option<usize> {
+        match self.channels.get(channel_id.into() as usize) {
+            Some(Some(channel)) => Some(channel.pending_messages_count()),
+            _ => None,
+        }
+    }
+
+    /// Discards the messages waiting in the outgoing queue of the channel, for example when a scene change makes the queued snapshots worthless.
+    ///
+    /// Returns how many messages were discarded, `None` if the channel does not exist or is closed. Messages already handed to the transport are still delivered.
+    pub fn clear_pending_messages<C: Into<ChannelId>>(&mut self, channel_id: C) -> Option<usize> {
+        match self.channels.get(channel_id.into() as usize) {
+            Some(Some(channel)) => Some(channel.clear_pending_messages()),
+            _ => None,
+        }
+    }
+
+    /// Sends the messages deferred until the next flush, see [`Endpoint::set_deferred_flush`]
+    pub fn flush(&self) {
+        for channel in self.channels.iter().flatten() {
+            channel.flush();
+        }
+    }
+
+    /// Returns how many bytes were received on this connection since the last time it was cleared and reset this value to 0
+    pub fn clear_received_bytes_count(&mut self) -> usize {
+        let bytes_count = self.received_bytes_count;
+        self.received_bytes_count = 0;
+        bytes_count
+    }
+
+    /// Returns how many bytes were received on this connection since the last time it was cleared
+    pub fn received_bytes_count(&self) -> usize {
+        self.received_bytes_count
+    }
+
+    /// Removes the traces of the messages received from the client on traced channels, in their receiving order, see [`ChannelConfig::traced`].
+    ///
+    /// A trace is kept once its message is received by Quinnet, before the application reads it. Up to [`crate::shared::channels::MAX_BUFFERED_TRACES`] traces are kept, the oldest are dropped first.
+    pub fn drain_message_traces(&mut self) -> Vec<MessageTrace> {
+        self.bytes_from_client_recv.drain_traces()
+    }
+
+    /// Returns how many bytes were received on this connection since the last time it was cleared and reset this value to 0
+    pub fn clear_sent_bytes_count(&mut self) -> usize {
+        let bytes_count = self.sent_bytes_count;
+        self.sent_bytes_count = 0;
+        bytes_count
+    }
+
+    /// Returns how many bytes were received on this connection since the last time it was cleared
+    pub fn sent_bytes_count(&self) -> usize {
+        self.sent_bytes_count
+    }
+
+    /// Returns the bytes exchanged with the client over the last `window`.
+    ///
+    /// Bytes are received when read with the `receive_*` methods of the [`Endpoint`], and sent when queued on a channel. Usage is accounted with a resolution of [`bandwidth::BANDWIDTH_BUCKET_DURATION`], and windows longer than the kept history, see [`bandwidth::DEFAULT_BANDWIDTH_HISTORY`], are truncated to it.
+    pub fn bandwidth_usage(&self, window: Duration) -> BandwidthUsage {
+        self.bandwidth.usage(window, Instant::now())
+    }
+
+    /// Returns the sampled stats of the connection, `None` unless enabled with [`Endpoint::set_stats_history`]
+    pub fn stats_history(&self) -> Option<&StatsHistory> {
+        self.stats_history.as_ref()
+    }
+
+    /// Round-trip time used to estimate when the client sent its messages, see [`ReceiveTimestamp`]
+    fn round_trip_time(&self) -> Duration {
+        let added_latency = self
+            .conditions()
+            .map(ClientConditions::latency)
+            .unwrap_or_default();
+        self.connection_handle.stats().path.rtt + 2 * added_latency
+    }
+
+    fn count_received(&mut self, channel_id: ChannelId, bytes: usize) {
+        self.received_bytes_count += bytes;
+        self.bandwidth.record_inbound(bytes);
+        self.activity.record(channel_id);
+    }
+}
+
+/// By default, when starting an [Endpoint], Quinnet creates 1 channel instance of each [`ChannelKind`](crate::shared::channels::ChannelKind), each with their own [ChannelId].
+/// Among those, there is a `default` channel which will be used when you don't specify the channel. At startup, this default channel is a [`ChannelKind::OrderedReliable`](crate::shared::channels::ChannelKind::OrderedReliable) channel.
+pub struct Endpoint {
+    local_addr: SocketAddr,
+    external_addr: Option<SocketAddr>,
+    #[cfg(feature = "port-mapping")]
+    port_mapping: Option<PortMapping>,
+    clients: HashMap<ClientId, ServerSideConnection>,
+    client_ids: ClientIds,
+
+    opened_channels: HashMap<ChannelId, ChannelConfig>,
+    available_channel_ids: BTreeSet<ChannelId>,
+    default_channel: Option<ChannelId>,
+    /// Channels of the clients connecting with a given server name, instead of the endpoint channels
+    virtual_hosts: HashMap<String, ChannelsConfiguration>,
+    hardening: HardeningConfiguration,
+    idle_detection: Option<IdleDetection>,
+    bandwidth_limits: Vec<BandwidthLimit>,
+    stats_history: Option<StatsHistoryConfig>,
+    congestion_events: bool,
+    memory_budget: Option<MemoryBudget>,
+    slow_client_detection: Option<SlowClientDetection>,
+    buffer_pool: BufferPool,
+    deferred_flush: bool,
+    frame_coherent_receive: bool,
+    /// Clients removed since the last sync update, waiting for their [`ConnectionLostEvent`]
+    disconnected_clients: Vec<(ClientId, DisconnectReason)>,
+    /// Failed sends of the group & broadcast `try_` methods since the last sync update
+    send_failures: Vec<ClientSendFailedEvent>,
+    disconnect_hook: Option<DisconnectHook>,
+    protocol_check: bool,
+    /// Protocol hash of the channels the endpoint was started with
+    protocol_hash: u64,
+    /// Senders of the payloads of the routed channels, see [`Endpoint::route_channels`]
+    routes: HashMap<ChannelId, std::sync::mpsc::Sender<RoutedPayload>>,
+    /// Senders of the payloads of the clients of each instance, see [`Endpoint::open_instance`]
+    instances: HashMap<String, std::sync::mpsc::Sender<RoutedPayload>>,
+    /// Stamped on the payloads of the ticked channels, see [`Endpoint::set_network_tick`]
+    network_tick: SharedNetworkTick,
+
+    close_sender: broadcast::Sender<()>,
+    accepting: Arc<AtomicBool>,
+    status: Option<Arc<StatusState>>,
+    transfer: TransferState,
+    forwarding_key: Option<ForwardingKey>,
+
+    runtime: runtime::Handle,
+    to_sync_endpoint_send: mpsc::Sender<ServerAsyncMessage>,
+    from_async_endpoint_recv: mpsc::Receiver<ServerAsyncMessage>,
+    /// Number of sockets the endpoint is sharded across, see [`ServerEndpointConfiguration::with_shards`]
+    shards: usize,
+    /// Releases of the sockets of the shards, empty for the external endpoints and the custom transports
+    sockets: Vec<SocketRelease>,
+
+    stats: EndpointStats,
+}
+
+/// Reason why an incoming connection was refused by the server, see [`EndpointStats::refused_connections`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RefusalReason {
+    /// The endpoint was not accepting new connections, see [`Endpoint::set_accepting`]
+    NotAccepting,
+    /// The connection negotiated a protocol with no service to handle it, see [`ExternalEndpointConfiguration::with_protocol_routing`]
+    NoService,
+    /// No client id was available for the connection, see [`id_allocation`]
+    NoClientId,
+}
+
+impl RefusalReason {
+    const COUNT: usize = 3;
+
+    fn index(self) -> usize {
+        match self {
+            RefusalReason::NotAccepting => 0,
+            RefusalReason::NoService => 1,
+            RefusalReason::NoClientId => 2,
+        }
+    }
+}
+
+/// Counters of the incoming connections, shared by the accept tasks of an endpoint and its [`EndpointStats`]
+#[derive(Debug, Default)]
+struct HandshakeCounters {
+    pending: AtomicU32,
+    failed: AtomicU64,
+    retries_sent: AtomicU64,
+    refused: [AtomicU64; RefusalReason::COUNT],
+}
+
+impl HandshakeCounters {
+    fn refuse(&self, reason: RefusalReason) {
+        self.refused[reason.index()].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Decrements the pending handshakes of an endpoint when dropped, whatever the outcome of the handshake
+struct PendingHandshake(Arc<HandshakeCounters>);
+
+impl PendingHandshake {
+    fn new(counters: Arc<HandshakeCounters>) -> Self {
+        counters.pending.fetch_add(1, Ordering::Relaxed);
+        Self(counters)
+    }
+}
+
+impl Drop for PendingHandshake {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Basic quinnet stats about this server endpoint
+#[derive(Default)]
+pub struct EndpointStats {
+    received_messages_count: u64,
+    connect_count: u32,
     disconnect_count: u32,
+    handshakes: Arc<HandshakeCounters>,
 }
 impl EndpointStats {
     /// Returns how many messages were received (read) on this endpoint
@@ -336,30 +1427,381 @@ impl EndpointStats {
     pub fn disconnect_count(&self) -> u32 {
         self.disconnect_count
     }
+    /// Returns how many handshakes of incoming connections are in progress on this endpoint
+    pub fn pending_handshakes(&self) -> u32 {
+        self.handshakes.pending.load(Ordering::Relaxed)
+    }
+    /// Returns how many handshakes of incoming connections failed on this endpoint: timed out, rejected by TLS, reset by the peer...
+    pub fn failed_handshakes(&self) -> u64 {
+        self.handshakes.failed.load(Ordering::Relaxed)
+    }
+    /// Returns how many QUIC Retry packets were sent to validate the address of incoming connections, see [`ServerEndpointConfiguration::with_address_validation`]
+    pub fn retries_sent(&self) -> u64 {
+        self.handshakes.retries_sent.load(Ordering::Relaxed)
+    }
+    /// Returns how many incoming connections were refused on this endpoint for `reason`
+    pub fn refused_connections(&self, reason: RefusalReason) -> u64 {
+        self.handshakes.refused[reason.index()].load(Ordering::Relaxed)
+    }
+    /// Returns how many incoming connections were refused on this endpoint, whatever the reason
+    pub fn refused_connections_count(&self) -> u64 {
+        self.handshakes
+            .refused
+            .iter()
+            .map(|refused| refused.load(Ordering::Relaxed))
+            .sum()
+    }
 }
 
 impl Endpoint {
     fn new(
+        local_addr: SocketAddr,
+        hardening: HardeningConfiguration,
         endpoint_close_send: broadcast::Sender<()>,
+        accepting: Arc<AtomicBool>,
+        runtime: runtime::Handle,
+        to_sync_endpoint_send: mpsc::Sender<ServerAsyncMessage>,
         from_async_endpoint_recv: mpsc::Receiver<ServerAsyncMessage>,
     ) -> Self {
         Self {
+            local_addr,
+            external_addr: None,
+            #[cfg(feature = "port-mapping")]
+            port_mapping: None,
             clients: HashMap::new(),
-            client_id_gen: 0,
+            client_ids: ClientIds::default(),
             opened_channels: HashMap::new(),
             default_channel: None,
             available_channel_ids: (0..255).collect(),
+            virtual_hosts: HashMap::new(),
+            hardening,
+            idle_detection: None,
+            bandwidth_limits: Vec::new(),
+            stats_history: None,
+            congestion_events: false,
+            memory_budget: None,
+            slow_client_detection: None,
+            buffer_pool: BufferPool::new(DEFAULT_BUFFER_CHUNK_SIZE),
+            deferred_flush: false,
+            frame_coherent_receive: false,
+            disconnected_clients: Vec::new(),
+            send_failures: Vec::new(),
+            disconnect_hook: None,
+            protocol_check: false,
+            protocol_hash: 0,
+            network_tick: SharedNetworkTick::default(),
+            routes: HashMap::new(),
+            instances: HashMap::new(),
             close_sender: endpoint_close_send,
+            accepting,
+            status: None,
+            transfer: TransferState::default(),
+            forwarding_key: None,
+            runtime,
+            to_sync_endpoint_send,
             from_async_endpoint_recv,
+            shards: 1,
+            sockets: Vec::new(),
             stats: default(),
         }
     }
 
+    /// Returns the local address the endpoint is bound to.
+    ///
+    /// When the endpoint was configured to bind on port 0, this contains the port assigned by the OS.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the external address of the endpoint, if discovered.
+    ///
+    /// See [`ServerEndpointConfiguration::with_stun_server`]
+    pub fn external_addr(&self) -> Option<SocketAddr> {
+        self.external_addr
+    }
+
+    /// Returns the port mapping currently established on the gateway, if any.
+    ///
+    /// See [`ServerEndpointConfiguration::with_port_mapping`]
+    #[cfg(feature = "port-mapping")]
+    pub fn port_mapping(&self) -> Option<PortMapping> {
+        self.port_mapping
+    }
+
+    /// Returns the number of sockets the endpoint is sharded across, see [`ServerEndpointConfiguration::with_shards`]
+    pub fn shard_count(&self) -> usize {
+        self.shards
+    }
+
+    /// Returns the number of clients connected to each shard, to monitor their balancing, see [`ServerSideConnection::shard`]
+    pub fn shard_client_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.shards];
+        for connection in self.clients.values() {
+            counts[connection.shard] += 1;
+        }
+        counts
+    }
+
     /// Returns a vec of all connected client ids
     pub fn clients(&self) -> Vec<ClientId> {
         self.clients.keys().cloned().collect()
     }
 
+    /// Returns a vec of the ids of the clients connected with the server name `server_name`, see [`Endpoint::set_virtual_host`]
+    pub fn host_clients(&self, server_name: &str) -> Vec<ClientId> {
+        self.clients
+            .iter()
+            .filter(|(_, connection)| connection.server_name() == Some(server_name))
+            .map(|(client_id, _)| *client_id)
+            .collect()
+    }
+
+    /// Registers a virtual host: clients connecting with the server name (SNI) `server_name` get the channels of `channels_config` instead of the endpoint channels, with their [`ChannelId`] in the same order as in the configuration.
+    ///
+    /// This allows one endpoint to host multiple shards behind different hostnames. Channels opened and closed on the endpoint with [`Endpoint::open_channel`] and [`Endpoint::close_channel`] do not apply to the clients of a virtual host. Only applies to the clients connecting after this call.
+    pub fn set_virtual_host(
+        &mut self,
+        server_name: impl Into<String>,
+        channels_config: ChannelsConfiguration,
+    ) {
+        self.virtual_hosts
+            .insert(server_name.into(), channels_config);
+    }
+
+    /// Removes a virtual host, see [`Endpoint::set_virtual_host`]. The clients connecting afterwards with this server name get the endpoint channels, already connected clients keep their channels.
+    pub fn remove_virtual_host(&mut self, server_name: &str) -> Option<ChannelsConfiguration> {
+        self.virtual_hosts.remove(server_name)
+    }
+
+    /// Sets the application-level idle detection of the clients, `None` to disable it. Disabled by default.
+    ///
+    /// The idle clients raise a [`ClientIdleEvent`] and are disconnected if the detection [`IdleDetection::kick`]s them.
+    pub fn set_idle_detection(&mut self, idle_detection: Option<IdleDetection>) {
+        self.idle_detection = idle_detection;
+    }
+
+    /// Returns the idle detection of the clients, if enabled, see [`Endpoint::set_idle_detection`]
+    pub fn idle_detection(&self) -> Option<&IdleDetection> {
+        self.idle_detection.as_ref()
+    }
+
+    /// Keeps a history of the sampled stats of each client, `None` to disable it and drop the histories. Disabled by default.
+    ///
+    /// Samples are taken during each sync update, see [`ServerSideConnection::stats_history`]. Changing the configuration restarts the histories.
+    pub fn set_stats_history(&mut self, config: Option<StatsHistoryConfig>) {
+        self.stats_history = config;
+        for connection in self.clients.values_mut() {
+            connection.stats_history = config.map(StatsHistory::new);
+        }
+    }
+
+    /// Returns the configuration of the stats history of the clients, if enabled, see [`Endpoint::set_stats_history`]
+    pub fn stats_history_config(&self) -> Option<StatsHistoryConfig> {
+        self.stats_history
+    }
+
+    /// Raises a [`CongestionEvent`] when the congestion controller of a client changes state, see [`CongestionMonitor`]. Disabled by default.
+    ///
+    /// The stats of the clients are polled during each sync update.
+    pub fn set_congestion_events(&mut self, enabled: bool) {
+        self.congestion_events = enabled;
+        for connection in self.clients.values_mut() {
+            connection.congestion = enabled.then(CongestionMonitor::default);
+        }
+    }
+
+    /// Returns whether the [`CongestionEvent`]s are raised, see [`Endpoint::set_congestion_events`]
+    pub fn congestion_events(&self) -> bool {
+        self.congestion_events
+    }
+
+    /// Sets the memory budget of the connection of each client, `None` to disable it. Disabled by default.
+    ///
+    /// Applies to the connected clients and to the new ones. A [`MemoryBudgetExceededEvent`] is raised when the connection of a client exceeds it, see [`ServerSideConnection::memory_usage`].
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.memory_budget = budget;
+        for connection in self.clients.values() {
+            connection.memory.set_budget(budget);
+        }
+    }
+
+    /// Returns the memory budget of the connection of each client, if any, see [`Endpoint::set_memory_budget`]
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        self.memory_budget
+    }
+
+    /// Sets the detection of the clients reading their messages slower than the server sends them, `None` to disable it. Disabled by default.
+    ///
+    /// The slow clients raise a [`SlowClientEvent`], and a [`SlowClientRecoveredEvent`] once they recover. Disabling the detection forgets the slow clients, without raising their [`SlowClientRecoveredEvent`].
+    pub fn set_slow_client_detection(&mut self, detection: Option<SlowClientDetection>) {
+        if detection.is_none() {
+            for connection in self.clients.values_mut() {
+                connection.backlog = ClientBacklog::default();
+            }
+        }
+        self.slow_client_detection = detection;
+    }
+
+    /// Returns the detection of the slow clients, if enabled, see [`Endpoint::set_slow_client_detection`]
+    pub fn slow_client_detection(&self) -> Option<&SlowClientDetection> {
+        self.slow_client_detection.as_ref()
+    }
+
+    /// Sets how the ids of the new clients are allocated. [`ClientIdPolicy::Sequential`] by default.
+    ///
+    /// Already connected clients keep their ids. See [`id_allocation::client_id_generation`] to get the generation of an id given by [`ClientIdPolicy::Generational`].
+    pub fn set_client_id_policy(&mut self, policy: ClientIdPolicy) {
+        self.client_ids.set_policy(policy);
+    }
+
+    /// Returns how the ids of the new clients are allocated, see [`Endpoint::set_client_id_policy`]
+    pub fn client_id_policy(&self) -> &ClientIdPolicy {
+        self.client_ids.policy()
+    }
+
+    /// Enables the check of the protocol hash of the connecting clients. Disabled by default.
+    ///
+    /// Each client sends the [`ChannelsConfiguration::protocol_hash`] of the channels it connected with. When enabled, a client whose hash does not match the hash of the channels the endpoint was started with (or of its virtual host, see [`Endpoint::set_virtual_host`]) raises a [`ProtocolMismatchEvent`] and is disconnected with [`CloseCode::ProtocolMismatch`]. The client is notified first and closes the connection itself, so that it knows the hash of the server: a client still connected a few seconds later is disconnected. Channels opened or closed later with [`Endpoint::open_channel`] and [`Endpoint::close_channel`] are not part of the hash.
+    pub fn set_protocol_check(&mut self, enabled: bool) {
+        self.protocol_check = enabled;
+    }
+
+    /// Returns true if the protocol hash of the connecting clients is checked, see [`Endpoint::set_protocol_check`]
+    pub fn protocol_check(&self) -> bool {
+        self.protocol_check
+    }
+
+    /// Returns the protocol hash of the channels the endpoint was started with, see [`Endpoint::set_protocol_check`]
+    pub fn protocol_hash(&self) -> u64 {
+        self.protocol_hash
+    }
+
+    /// Sets the current network tick of the server, stamped on the payloads sent from now on on the ticked channels of the clients, see [`ChannelConfig::ticked`]. The clients keep the most recent tick they receive, the tick is expected to increase. See [`crate::server::tick::QuinnetServerTickPlugin`] to drive it from a fixed schedule.
+    pub fn set_network_tick(&mut self, tick: NetworkTick) {
+        self.network_tick.set(tick);
+    }
+
+    /// Returns the current network tick of the server, if set
+    pub fn network_tick(&self) -> Option<NetworkTick> {
+        self.network_tick.get()
+    }
+
+    /// Protocol hash expected from a client, `None` if its virtual host was removed since it connected
+    fn expected_protocol_hash(&self, connection: &ServerSideConnection) -> Option<u64> {
+        match connection.virtual_host {
+            true => connection
+                .server_name()
+                .and_then(|server_name| self.virtual_hosts.get(server_name))
+                .map(ChannelsConfiguration::protocol_hash),
+            false => Some(self.protocol_hash),
+        }
+    }
+
+    /// Sets a hook run on the async runtime for each disconnected client, for example to persist its stats, replacing the previous hook. A [`DisconnectHookCompletedEvent`] is raised once the future it returns completes.
+    ///
+    /// The hook is called when the [`ConnectionLostEvent`] of the client is raised, including when the endpoint is stopped, whatever the reason of the disconnection.
+    pub fn set_disconnect_hook<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(ClientId, DisconnectReason) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.disconnect_hook = Some(Arc::new(move |client_id, reason| {
+            Box::pin(hook(client_id, reason))
+        }));
+    }
+
+    /// Removes the disconnect hook, see [`Endpoint::set_disconnect_hook`]. The hooks already running are not cancelled.
+    pub fn remove_disconnect_hook(&mut self) {
+        self.disconnect_hook = None;
+    }
+
+    /// Runs the disconnect hook, if any, for a client whose [`ConnectionLostEvent`] is raised
+    fn run_disconnect_hook(&self, client_id: ClientId, reason: &DisconnectReason) {
+        let Some(hook) = &self.disconnect_hook else {
+            return;
+        };
+        let finalization = hook(client_id, reason.clone());
+        let to_sync_endpoint_send = self.to_sync_endpoint_send.clone();
+        let reason = reason.clone();
+        self.runtime.spawn(async move {
+            finalization.await;
+            let _ = to_sync_endpoint_send
+                .send(ServerAsyncMessage::DisconnectHookCompleted(
+                    client_id, reason,
+                ))
+                .await;
+        });
+    }
+
+    /// Sets the bandwidth limits of each client, replacing the previous ones. None by default.
+    ///
+    /// Usage is checked during each sync update, a [`ClientBandwidthExceededEvent`] is raised when a client crosses a limit. See [`ServerSideConnection::bandwidth_usage`] for how bytes are accounted.
+    pub fn set_bandwidth_limits(&mut self, limits: impl IntoIterator<Item = BandwidthLimit>) {
+        self.bandwidth_limits = limits.into_iter().collect();
+        for connection in self.clients.values_mut() {
+            connection.bandwidth.reset_limits();
+        }
+    }
+
+    /// Returns the bandwidth limits of each client, see [`Endpoint::set_bandwidth_limits`]
+    pub fn bandwidth_limits(&self) -> &[BandwidthLimit] {
+        &self.bandwidth_limits
+    }
+
+    /// Sets the key signing the transfer tokens issued by this endpoint and validating the tokens presented to it, `None` to disable transfers. Disabled by default.
+    ///
+    /// Servers transferring clients to each other must use the same key, see [`Endpoint::transfer_client`].
+    pub fn set_transfer_key(&mut self, key: Option<TransferKey>) {
+        self.transfer.set_key(key);
+    }
+
+    /// Returns the key of the transfer tokens, if any, see [`Endpoint::set_transfer_key`]
+    pub fn transfer_key(&self) -> Option<&TransferKey> {
+        self.transfer.key()
+    }
+
+    /// Sets the key validating the forwarding headers presented by the connections of a gateway, `None` to refuse them. Disabled by default.
+    ///
+    /// A valid header raises a [`ClientForwardedEvent`] and sets the [`ServerSideConnection::forwarded_client`] of the connection, so that an internal server behind a gateway knows the address and identity of the original client, see [`crate::client::connection::ClientEndpointConfigurationBuilder::with_forwarded_client`].
+    pub fn set_forwarding_key(&mut self, key: Option<ForwardingKey>) {
+        self.forwarding_key = key;
+    }
+
+    /// Returns the key of the forwarding headers, if any, see [`Endpoint::set_forwarding_key`]
+    pub fn forwarding_key(&self) -> Option<&ForwardingKey> {
+        self.forwarding_key.as_ref()
+    }
+
+    /// Transfers a client to another server, such as the server of another zone of the world.
+    ///
+    /// The client receives a token signed with the [`TransferKey`] of the endpoint, carrying `payload` (for example the session of the player, up to [`transfer::MAX_TRANSFER_PAYLOAD_LEN`] bytes). It then leaves this server, raises a [`crate::client::connection::ConnectionTransferEvent`], connects to `target` and presents the token, which raises a [`ClientTransferEvent`] on the target server if valid.
+    ///
+    /// A [`ConnectionLostEvent`] with [`DisconnectReason::Transferred`] is raised once the client left. A client still connected a few seconds later is disconnected with [`CloseCode::Transferred`].
+    pub fn transfer_client(
+        &mut self,
+        client_id: ClientId,
+        target: TransferTarget,
+        payload: impl Into<Vec<u8>>,
+    ) -> Result<TransferTicket, ServerTransferError> {
+        let Some(connection) = self.clients.get_mut(&client_id) else {
+            return Err(ServerTransferError::UnknownClient(client_id));
+        };
+        if connection.transfer_deadline.is_some() {
+            return Err(ServerTransferError::AlreadyTransferring(client_id));
+        }
+        let (ticket, token) = self.transfer.issue(client_id, &target, payload.into())?;
+        connection.send_control(
+            ControlMessage::Redirect {
+                target_addr: target.addr(),
+                server_hostname: target.server_hostname().to_string(),
+                token,
+            },
+            self.buffer_pool.sibling(),
+        )?;
+        connection.transfer_deadline = Some(Instant::now() + TRANSFER_CLOSE_DELAY);
+        Ok(ticket)
+    }
+
     /// Attempt to deserialise a message into type `T`.
     ///
     /// Will return [`Err`] if:
@@ -405,20 +1847,204 @@ impl Endpoint {
     ) -> Result<Option<(ChannelId, Bytes)>, ServerReceiveError> {
         match self.clients.get_mut(&client_id) {
             Some(client) => match client.bytes_from_client_recv.try_recv() {
-                Ok(msg) => {
+                Ok(Some(msg)) => {
                     self.stats.received_messages_count += 1;
-                    client.received_bytes_count += msg.1.len();
+                    client.count_received(msg.0, msg.1.len());
                     Ok(Some(msg))
                 }
-                Err(err) => match err {
-                    TryRecvError::Empty => Ok(None),
-                    TryRecvError::Disconnected => Err(ServerReceiveError::ConnectionClosed),
-                },
+                Ok(None) => Ok(None),
+                Err(_) => Err(ServerReceiveError::ConnectionClosed),
+            },
+            None => Err(ServerReceiveError::UnknownClient(client_id)),
+        }
+    }
+
+    /// Receives all the payloads sent by the specified client on the specified channel, in their receiving order.
+    ///
+    /// Payloads received on other channels stay available to the other receive methods.
+    ///
+    /// Can return an [`Err`] if:
+    /// - the connection is closed
+    /// - the client id is not valid
+    pub fn receive_all_from_on<C: Into<ChannelId>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: C,
+    ) -> Result<impl Iterator<Item = Bytes>, ServerReceiveError> {
+        let channel_id = channel_id.into();
+        match self.clients.get_mut(&client_id) {
+            Some(client) => match client.bytes_from_client_recv.drain_channel(channel_id) {
+                Ok(payloads) => {
+                    self.stats.received_messages_count += payloads.len() as u64;
+                    if !payloads.is_empty() {
+                        client.count_received(
+                            channel_id,
+                            payloads.iter().map(Bytes::len).sum::<usize>(),
+                        );
+                    }
+                    Ok(payloads.into_iter())
+                }
+                Err(_) => Err(ServerReceiveError::ConnectionClosed),
             },
             None => Err(ServerReceiveError::UnknownClient(client_id)),
         }
     }
 
+    /// Same as [`Endpoint::receive_message_from`], with the [`ReceiveTimestamp`] of the message
+    pub fn receive_timestamped_message_from<T: serde::de::DeserializeOwned>(
+        &mut self,
+        client_id: ClientId,
+    ) -> Result<Option<(ChannelId, T, ReceiveTimestamp)>, ServerMessageReceiveError> {
+        match self.receive_timestamped_payload_from(client_id)? {
+            Some((channel_id, payload, timestamp)) => match bincode::deserialize(&payload) {
+                Ok(msg) => Ok(Some((channel_id, msg, timestamp))),
+                Err(_) => Err(ServerMessageReceiveError::Deserialization),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Same as [`Endpoint::receive_payload_from`], with the [`ReceiveTimestamp`] of the payload: when it arrived from the network and when the client sent it, for lag compensation
+    pub fn receive_timestamped_payload_from(
+        &mut self,
+        client_id: ClientId,
+    ) -> Result<Option<(ChannelId, Bytes, ReceiveTimestamp)>, ServerReceiveError> {
+        match self.clients.get_mut(&client_id) {
+            Some(client) => match client.bytes_from_client_recv.try_recv_timestamped() {
+                Ok(Some((channel_id, payload, received_at))) => {
+                    self.stats.received_messages_count += 1;
+                    client.count_received(channel_id, payload.len());
+                    let timestamp = ReceiveTimestamp::new(received_at, client.round_trip_time());
+                    Ok(Some((channel_id, payload, timestamp)))
+                }
+                Ok(None) => Ok(None),
+                Err(_) => Err(ServerReceiveError::ConnectionClosed),
+            },
+            None => Err(ServerReceiveError::UnknownClient(client_id)),
+        }
+    }
+
+    /// Same as [`Endpoint::receive_all_from_on`], with the [`ReceiveTimestamp`] of each payload
+    pub fn receive_all_timestamped_from_on<C: Into<ChannelId>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: C,
+    ) -> Result<impl Iterator<Item = (Bytes, ReceiveTimestamp)>, ServerReceiveError> {
+        let channel_id = channel_id.into();
+        match self.clients.get_mut(&client_id) {
+            Some(client) => match client
+                .bytes_from_client_recv
+                .drain_channel_timestamped(channel_id)
+            {
+                Ok(payloads) => {
+                    self.stats.received_messages_count += payloads.len() as u64;
+                    if !payloads.is_empty() {
+                        client.count_received(
+                            channel_id,
+                            payloads
+                                .iter()
+                                .map(|(payload, _)| payload.len())
+                                .sum::<usize>(),
+                        );
+                    }
+                    let round_trip_time = client.round_trip_time();
+                    Ok(payloads.into_iter().map(move |(payload, received_at)| {
+                        (payload, ReceiveTimestamp::new(received_at, round_trip_time))
+                    }))
+                }
+                Err(_) => Err(ServerReceiveError::ConnectionClosed),
+            },
+            None => Err(ServerReceiveError::UnknownClient(client_id)),
+        }
+    }
+
+    /// Receives all the payloads sent by the specified client, grouped by channel and in their receiving order in each channel.
+    ///
+    /// Can return an [`Err`] if:
+    /// - the connection is closed
+    /// - the client id is not valid
+    pub fn drain_payloads_from(
+        &mut self,
+        client_id: ClientId,
+    ) -> Result<HashMap<ChannelId, Vec<Bytes>>, ServerReceiveError> {
+        match self.clients.get_mut(&client_id) {
+            Some(client) => match client.bytes_from_client_recv.drain() {
+                Ok(payloads) => {
+                    for (channel_id, channel_payloads) in payloads.iter() {
+                        self.stats.received_messages_count += channel_payloads.len() as u64;
+                        client.count_received(
+                            *channel_id,
+                            channel_payloads.iter().map(Bytes::len).sum::<usize>(),
+                        );
+                    }
+                    Ok(payloads)
+                }
+                Err(_) => Err(ServerReceiveError::ConnectionClosed),
+            },
+            None => Err(ServerReceiveError::UnknownClient(client_id)),
+        }
+    }
+
+    /// Receives the payloads sent by all the clients, in the order they arrived from the network across all the clients and channels.
+    ///
+    /// Meant for the servers routing all the payloads from a central place, instead of polling each client on each channel. Closed connections are skipped, their loss is reported by a [`ConnectionLostEvent`].
+    pub fn drain_received(&mut self) -> impl Iterator<Item = (ClientId, ChannelId, Bytes)> {
+        let mut received = Vec::new();
+        for (client_id, client) in self.clients.iter_mut() {
+            while let Ok(Some((channel_id, payload, received_at))) =
+                client.bytes_from_client_recv.try_recv_timestamped()
+            {
+                client.count_received(channel_id, payload.len());
+                received.push((received_at, *client_id, channel_id, payload));
+            }
+        }
+        self.stats.received_messages_count += received.len() as u64;
+        // Stable, the payloads of a client keep their receiving order
+        received.sort_by_key(|(received_at, _, _, _)| *received_at);
+        received
+            .into_iter()
+            .map(|(_, client_id, channel_id, payload)| (client_id, channel_id, payload))
+    }
+
+    /// Receives the payloads sent by all the clients, processing the clients in parallel.
+    ///
+    /// `handler` is called for each payload, in the receiving order of each client. Clients are spread over the threads of Bevy's [`ComputeTaskPool`](bevy::tasks::ComputeTaskPool), so that the payloads of hundreds of clients are not all processed on one thread. Closed connections are skipped, their loss is reported by a [`ConnectionLostEvent`].
+    pub fn par_receive_payloads<F>(&mut self, handler: F)
+    where
+        F: Fn(ClientId, ChannelId, Bytes) + Send + Sync,
+    {
+        let received_counts = par_map_connections(self.clients.iter_mut(), |client_id, client| {
+            let mut received_count = 0;
+            while let Ok(Some((channel_id, payload))) = client.bytes_from_client_recv.try_recv() {
+                received_count += 1;
+                client.count_received(channel_id, payload.len());
+                handler(client_id, channel_id, payload);
+            }
+            received_count
+        });
+        self.stats.received_messages_count += received_counts.iter().sum::<u64>();
+    }
+
+    /// Same as [`Endpoint::par_receive_payloads`], deserializing the payloads into messages of type `T`.
+    ///
+    /// Payloads that can't be deserialized are logged and skipped.
+    pub fn par_receive_messages<T, F>(&mut self, handler: F)
+    where
+        T: serde::de::DeserializeOwned,
+        F: Fn(ClientId, ChannelId, T) + Send + Sync,
+    {
+        self.par_receive_payloads(|client_id, channel_id, payload| {
+            match bincode::deserialize(&payload) {
+                Ok(message) => handler(client_id, channel_id, message),
+                Err(_) => error!(
+                    "par_receive_messages from client {}: {}",
+                    client_id,
+                    ServerMessageReceiveError::Deserialization
+                ),
+            }
+        });
+    }
+
     /// [`Endpoint::receive_payload_from`] that logs the error instead of returning a result.
     pub fn try_receive_payload_from(&mut self, client_id: ClientId) -> Option<(ChannelId, Bytes)> {
         match self.receive_payload_from(client_id) {
@@ -455,9 +2081,70 @@ impl Endpoint {
         channel_id: C,
         message: T,
     ) -> Result<(), ServerMessageSendError> {
-        match bincode::serialize(&message) {
-            Ok(payload) => Ok(self.send_payload_on(client_id, channel_id, payload)?),
-            Err(_) => Err(ServerMessageSendError::Serialization),
+        let channel_id = channel_id.into();
+        let payload = {
+            let _span = profiling::enter(|| info_span!("quinnet_serialize", client_id, channel_id));
+            self.buffer_pool.serialize(&message)
+        };
+        match payload {
+            Some(payload) => Ok(self.send_payload_on(client_id, channel_id, payload)?),
+            None => Err(ServerMessageSendError::Serialization),
+        }
+    }
+
+    /// Same as [Endpoint::send_message_on] on a channel declared with [`crate::protocol!`], only accepting the type of message declared for it
+    pub fn send_on<C: ProtocolChannel>(
+        &mut self,
+        client_id: ClientId,
+        channel: C,
+        message: &C::Message,
+    ) -> Result<(), ServerMessageSendError> {
+        self.send_message_on(client_id, channel, message)
+    }
+
+    /// Same as [Endpoint::broadcast_message_on] on a channel declared with [`crate::protocol!`], only accepting the type of message declared for it
+    pub fn broadcast_on<C: ProtocolChannel>(
+        &mut self,
+        channel: C,
+        message: &C::Message,
+    ) -> Result<(), ServerGroupMessageSendError> {
+        self.broadcast_message_on(channel, message)
+    }
+
+    /// Receives and deserializes all the messages sent by the specified client on a channel declared with [`crate::protocol!`], in their receiving order, see [Endpoint::receive_all_from_on].
+    ///
+    /// Will return an [`Err`] if the client is unknown or disconnected, or if a message can't be deserialized into the type declared for the channel, the following messages are then dropped.
+    pub fn receive_from_on<C: ProtocolChannel>(
+        &mut self,
+        client_id: ClientId,
+        channel: C,
+    ) -> Result<Vec<C::Message>, ServerMessageReceiveError> {
+        self.receive_all_from_on(client_id, channel)?
+            .map(|payload| {
+                bincode::deserialize(&payload)
+                    .map_err(|_| ServerMessageReceiveError::Deserialization)
+            })
+            .collect()
+    }
+
+    /// Same as [Endpoint::send_message_on] but with a [`MessagePriority`]: on reliable channels, messages with a higher priority overtake the lower priority messages still waiting in the outgoing queue of the channel.
+    pub fn send_prioritized_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: C,
+        message: T,
+        priority: MessagePriority,
+    ) -> Result<(), ServerMessageSendError> {
+        let channel_id = channel_id.into();
+        let payload = {
+            let _span = profiling::enter(|| info_span!("quinnet_serialize", client_id, channel_id));
+            self.buffer_pool.serialize(&message)
+        };
+        match payload {
+            Some(payload) => {
+                Ok(self.send_prioritized_payload_on(client_id, channel_id, payload, priority)?)
+            }
+            None => Err(ServerMessageSendError::Serialization),
         }
     }
 
@@ -496,6 +2183,8 @@ impl Endpoint {
 
     /// Sends the message to the specified clients on the specified channel.
     ///
+    /// As with [Endpoint::broadcast_message_on], the message is serialized only once and the payload is shared by all the clients.
+    ///
     /// Tries to send to each client before returning. Returns an [`Err`] if sending failed for at least 1 client. Information about the failed sendings will be available in the [`ServerGroupMessageSendError`].
     pub fn send_group_message_on<
         'a,
@@ -509,10 +2198,13 @@ impl Endpoint {
         message: T,
     ) -> Result<(), ServerGroupMessageSendError> {
         let channel_id = channel_id.into();
-        let Ok(payload) = bincode::serialize(&message) else {
+        let bytes = {
+            let _span = profiling::enter(|| info_span!("quinnet_serialize", channel_id));
+            self.buffer_pool.serialize(&message)
+        };
+        let Some(bytes) = bytes else {
             return Err(ServerGroupMessageSendError::Serialization);
         };
-        let bytes = Bytes::from(payload);
         let mut errs = vec![];
         for &client_id in client_ids {
             if let Err(e) = self.send_payload_on(client_id, channel_id, bytes.clone()) {
@@ -525,18 +2217,89 @@ impl Endpoint {
         }
     }
 
-    /// Same as [Endpoint::send_group_message] but will log the error instead of returning it
+    /// Sends a message built for each client by `message_fn` to the specified clients on the specified channel.
+    ///
+    /// Useful to send per-client variations of a message (e.g. omitting the entity of the receiving player). Messages are serialized in pooled buffers, see [Endpoint::buffer_pool_stats].
+    ///
+    /// Tries to send to each client before returning. Returns an [`Err`] if sending failed for at least 1 client, or as soon as a message fails to serialize. Information about the failed sendings will be available in the [`ServerGroupMessageSendError`].
+    pub fn send_group_message_with<
+        'a,
+        I: Iterator<Item = &'a ClientId>,
+        T: serde::Serialize,
+        C: Into<ChannelId>,
+        F: FnMut(ClientId) -> T,
+    >(
+        &mut self,
+        client_ids: I,
+        channel_id: C,
+        mut message_fn: F,
+    ) -> Result<(), ServerGroupMessageSendError> {
+        let channel_id = channel_id.into();
+        let mut errs = vec![];
+        for &client_id in client_ids {
+            let payload = {
+                let _span =
+                    profiling::enter(|| info_span!("quinnet_serialize", client_id, channel_id));
+                self.buffer_pool.serialize(&message_fn(client_id))
+            };
+            let Some(payload) = payload else {
+                return Err(ServerGroupMessageSendError::Serialization);
+            };
+            if let Err(e) = self.send_payload_on(client_id, channel_id, payload) {
+                errs.push((client_id, e));
+            }
+        }
+        match errs.is_empty() {
+            true => Ok(()),
+            false => Err(ServerGroupSendError(errs).into()),
+        }
+    }
+
+    /// Same as [Endpoint::send_group_message_with] but does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
+    pub fn try_send_group_message_with<
+        'a,
+        I: Iterator<Item = &'a ClientId>,
+        T: serde::Serialize,
+        C: Into<ChannelId>,
+        F: FnMut(ClientId) -> T,
+    >(
+        &mut self,
+        client_ids: I,
+        channel_id: C,
+        message_fn: F,
+    ) {
+        let channel_id = channel_id.into();
+        match self.send_group_message_with(client_ids, channel_id, message_fn) {
+            Ok(_) => (),
+            Err(ServerGroupMessageSendError::GroupSendError(err)) => {
+                self.report_send_failures(channel_id, err)
+            }
+            Err(err) => error!("try_send_group_message_with: {}", err),
+        }
+    }
+
+    /// Same as [Endpoint::send_group_message] but does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
     pub fn try_send_group_message<'a, I: Iterator<Item = &'a ClientId>, T: serde::Serialize>(
         &mut self,
         client_ids: I,
         message: T,
     ) {
-        if let Err(err) = self.send_group_message(client_ids, message) {
-            error!("try_send_group_message: {}", err);
+        match self.default_channel {
+            Some(channel) => self.try_send_group_message_on(client_ids, channel, message),
+            None => error!(
+                "try_send_group_message: {}",
+                ServerGroupMessageSendError::NoDefaultChannel
+            ),
         }
     }
 
-    /// Same as [Endpoint::send_group_message_on] but will log the error instead of returning it
+    /// Same as [Endpoint::send_group_message_on] but does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
     pub fn try_send_group_message_on<
         'a,
         I: Iterator<Item = &'a ClientId>,
@@ -548,8 +2311,13 @@ impl Endpoint {
         channel_id: C,
         message: T,
     ) {
-        if let Err(err) = self.send_group_message_on(client_ids, channel_id, message) {
-            error!("try_send_group_message: {}", err);
+        let channel_id = channel_id.into();
+        match self.send_group_message_on(client_ids, channel_id, message) {
+            Ok(_) => (),
+            Err(ServerGroupMessageSendError::GroupSendError(err)) => {
+                self.report_send_failures(channel_id, err)
+            }
+            Err(err) => error!("try_send_group_message: {}", err),
         }
     }
 
@@ -565,14 +2333,20 @@ impl Endpoint {
         }
     }
 
-    /// [`Endpoint::send_group_payload`] that logs the error instead of returning a result.
+    /// [`Endpoint::send_group_payload`] that does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
     pub fn try_send_group_payload<'a, I: Iterator<Item = &'a ClientId>, T: Into<Bytes>>(
         &mut self,
         client_ids: I,
         payload: T,
     ) {
-        if let Err(err) = self.send_group_payload(client_ids, payload) {
-            error!("try_send_group_payload: {}", err);
+        match self.default_channel {
+            Some(channel) => self.try_send_group_payload_on(client_ids, channel, payload),
+            None => error!(
+                "try_send_group_payload: {}",
+                ServerGroupPayloadSendError::NoDefaultChannel
+            ),
         }
     }
 
@@ -606,7 +2380,9 @@ impl Endpoint {
         }
     }
 
-    /// [`Endpoint::send_group_payload_on`] that logs the error instead of returning a result.
+    /// [`Endpoint::send_group_payload_on`] that does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
     pub fn try_send_group_payload_on<
         'a,
         I: Iterator<Item = &'a ClientId>,
@@ -618,8 +2394,13 @@ impl Endpoint {
         channel_id: C,
         payload: T,
     ) {
-        if let Err(err) = self.send_group_payload_on(client_ids, channel_id, payload) {
-            error!("try_send_group_payload_on: {}", err);
+        let channel_id = channel_id.into();
+        match self.send_group_payload_on(client_ids, channel_id, payload) {
+            Ok(_) => (),
+            Err(ServerGroupPayloadSendError::GroupSendError(err)) => {
+                self.report_send_failures(channel_id, err)
+            }
+            Err(err) => error!("try_send_group_payload_on: {}", err),
         }
     }
 
@@ -629,38 +2410,95 @@ impl Endpoint {
         message: T,
     ) -> Result<(), ServerGroupMessageSendError> {
         match self.default_channel {
-            Some(channel) => self.broadcast_message_on(channel, message),
-            None => Err(ServerGroupMessageSendError::NoDefaultChannel),
+            Some(channel) => self.broadcast_message_on(channel, message),
+            None => Err(ServerGroupMessageSendError::NoDefaultChannel),
+        }
+    }
+
+    /// Same as [Endpoint::broadcast_payload_on] but will serialize the message to a payload before.
+    ///
+    /// The message is serialized only once: all the clients share the same payload, which is reference counted and not copied. Prefer this to sending the same message to each client in a loop.
+    pub fn broadcast_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), ServerGroupMessageSendError> {
+        let channel_id = channel_id.into();
+        let payload = {
+            let _span = profiling::enter(|| info_span!("quinnet_serialize", channel_id));
+            self.buffer_pool.serialize(&message)
+        };
+        match payload {
+            Some(payload) => Ok(self.broadcast_payload_on(channel_id, payload)?),
+            None => Err(ServerGroupMessageSendError::Serialization),
+        }
+    }
+
+    /// Same as [Endpoint::broadcast_message] but does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
+    pub fn try_broadcast_message<T: serde::Serialize>(&mut self, message: T) {
+        match self.default_channel {
+            Some(channel) => self.try_broadcast_message_on(channel, message),
+            None => error!(
+                "try_broadcast_message: {}",
+                ServerGroupMessageSendError::NoDefaultChannel
+            ),
         }
     }
 
-    /// Same as [Endpoint::broadcast_payload_on] but will serialize the message to a payload before
-    pub fn broadcast_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+    /// Same as [Endpoint::broadcast_message_on] but does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
+    pub fn try_broadcast_message_on<T: serde::Serialize, C: Into<ChannelId>>(
         &mut self,
         channel_id: C,
         message: T,
-    ) -> Result<(), ServerGroupMessageSendError> {
-        match bincode::serialize(&message) {
-            Ok(payload) => Ok(self.broadcast_payload_on(channel_id, payload)?),
-            Err(_) => Err(ServerGroupMessageSendError::Serialization),
+    ) {
+        let channel_id = channel_id.into();
+        match self.broadcast_message_on(channel_id, message) {
+            Ok(_) => (),
+            Err(ServerGroupMessageSendError::GroupSendError(err)) => {
+                self.report_send_failures(channel_id, err)
+            }
+            Err(err) => error!("try_broadcast_message: {}", err),
         }
     }
 
-    /// Same as [Endpoint::broadcast_message] but will log the error instead of returning it
-    pub fn try_broadcast_message<T: serde::Serialize>(&mut self, message: T) {
-        if let Err(err) = self.broadcast_message(message) {
-            error!("try_broadcast_message: {}", err);
+    /// Same as [Endpoint::broadcast_message_on] but only sends the message to the clients an entity is visible to, see [`NetworkVisibility`].
+    ///
+    /// The message is serialized only once. Returns `Ok` without sending anything if the entity is visible to no connected client.
+    pub fn broadcast_visible_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+        &mut self,
+        visibility: &NetworkVisibility,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), ServerGroupMessageSendError> {
+        let client_ids: Vec<ClientId> = visibility
+            .visible_clients(self.clients.keys().copied())
+            .collect();
+        match client_ids.is_empty() {
+            true => Ok(()),
+            false => self.send_group_message_on(client_ids.iter(), channel_id, message),
         }
     }
 
-    /// Same as [Endpoint::broadcast_message_on] but will log the error instead of returning it
-    pub fn try_broadcast_message_on<T: serde::Serialize, C: Into<ChannelId>>(
+    /// Same as [Endpoint::broadcast_visible_message_on] but does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
+    pub fn try_broadcast_visible_message_on<T: serde::Serialize, C: Into<ChannelId>>(
         &mut self,
+        visibility: &NetworkVisibility,
         channel_id: C,
         message: T,
     ) {
-        if let Err(err) = self.broadcast_message_on(channel_id, message) {
-            error!("try_broadcast_message: {}", err);
+        let channel_id = channel_id.into();
+        match self.broadcast_visible_message_on(visibility, channel_id, message) {
+            Ok(_) => (),
+            Err(ServerGroupMessageSendError::GroupSendError(err)) => {
+                self.report_send_failures(channel_id, err)
+            }
+            Err(err) => error!("try_broadcast_visible_message: {}", err),
         }
     }
 
@@ -688,9 +2526,13 @@ impl Endpoint {
 
         let mut errs = vec![];
         for (&client_id, server_side_connection) in self.clients.iter_mut() {
-            if let Err(e) =
-                Self::internal_send_payload(server_side_connection, channel_id, payload.clone())
-            {
+            if let Err(e) = Self::internal_send_payload(
+                server_side_connection,
+                channel_id,
+                payload.clone(),
+                None,
+                false,
+            ) {
                 errs.push((client_id, e.into()));
             }
         }
@@ -700,21 +2542,41 @@ impl Endpoint {
         }
     }
 
-    /// Same as [Endpoint::broadcast_payload] but will log the error instead of returning it
+    /// Same as [Endpoint::broadcast_payload] but does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the message could not be sent to, other errors are logged.
     pub fn try_broadcast_payload<T: Into<Bytes>>(&mut self, payload: T) {
-        if let Err(err) = self.broadcast_payload(payload) {
-            error!("try_broadcast_payload: {}", err);
+        match self.default_channel {
+            Some(channel) => self.try_broadcast_payload_on(channel, payload),
+            None => error!(
+                "try_broadcast_payload: {}",
+                ServerGroupPayloadSendError::NoDefaultChannel
+            ),
         }
     }
 
-    /// Same as [Endpoint::broadcast_payload_on] but will log the error instead of returning it
+    /// Same as [Endpoint::broadcast_payload_on] but does not return the error.
+    ///
+    /// Raises a [`ClientSendFailedEvent`] for each client the payload could not be sent to.
     pub fn try_broadcast_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
         &mut self,
         channel_id: C,
         payload: T,
     ) {
+        let channel_id = channel_id.into();
         if let Err(err) = self.broadcast_payload_on(channel_id, payload) {
-            error!("try_broadcast_payload_on: {}", err);
+            self.report_send_failures(channel_id, err);
+        }
+    }
+
+    /// The [`ClientSendFailedEvent`]s are raised during the next sync update
+    fn report_send_failures(&mut self, channel_id: ChannelId, err: ServerGroupSendError) {
+        for (client_id, error) in err.0 {
+            self.send_failures.push(ClientSendFailedEvent {
+                client_id,
+                channel_id,
+                error,
+            });
         }
     }
 
@@ -741,24 +2603,115 @@ impl Endpoint {
         client_id: ClientId,
         channel_id: C,
         payload: T,
+    ) -> Result<(), ServerSendError> {
+        self.send_payload_on_with_priority(client_id, channel_id.into(), payload.into(), None)
+    }
+
+    /// Same as [Endpoint::send_payload_on] but with a [`MessagePriority`]: on reliable channels, payloads with a higher priority overtake the lower priority payloads still waiting in the outgoing queue of the channel.
+    pub fn send_prioritized_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: C,
+        payload: T,
+        priority: MessagePriority,
+    ) -> Result<(), ServerSendError> {
+        self.send_payload_on_with_priority(
+            client_id,
+            channel_id.into(),
+            payload.into(),
+            Some(priority),
+        )
+    }
+
+    fn send_payload_on_with_priority(
+        &mut self,
+        client_id: ClientId,
+        channel_id: ChannelId,
+        payload: Bytes,
+        priority: Option<MessagePriority>,
     ) -> Result<(), ServerSendError> {
         if let Some(client_connection) = self.clients.get_mut(&client_id) {
-            let channel_id = channel_id.into();
-            Self::internal_send_payload(client_connection, channel_id, payload.into())
+            Self::internal_send_payload(client_connection, channel_id, payload, priority, false)
+                .map(|_| ())
         } else {
             Err(ServerSendError::UnknownClient(client_id))
         }
     }
 
+    /// Sends the payload to the specified client on the specified [`ChannelKind::Unreliable`] channel, which must be [`ChannelConfig::acknowledged`]. Returns the id of the message, reported by a [`MessageAckedEvent`] once the client received it, or by a [`MessageLostEvent`] if it did not acknowledge it within [`crate::shared::channels::MESSAGE_ACK_TIMEOUT`].
+    ///
+    /// Meant for the messages whose loss the game wants to react to, such as a one-off hit confirmation, without the latency of a reliable channel.
+    pub fn send_unreliable_tracked<T: Into<Bytes>, C: Into<ChannelId>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: C,
+        payload: T,
+    ) -> Result<TrackedMessageId, ServerSendError> {
+        let Some(client_connection) = self.clients.get_mut(&client_id) else {
+            return Err(ServerSendError::UnknownClient(client_id));
+        };
+        let channel_id = channel_id.into();
+        Self::internal_send_payload(client_connection, channel_id, payload.into(), None, true)
+            .map(|tracked| tracked.expect("Tracked sends should return their message id"))
+    }
+
+    /// Returns the id of the message if `tracked`
     fn internal_send_payload(
         client_connection: &mut ServerSideConnection,
         channel_id: ChannelId,
         payload: Bytes,
-    ) -> Result<(), ServerSendError> {
+        priority: Option<MessagePriority>,
+        tracked: bool,
+    ) -> Result<Option<TrackedMessageId>, ServerSendError> {
         match client_connection.channels.get(channel_id as usize) {
             Some(Some(channel)) => {
+                if tracked && !channel.is_acknowledged() {
+                    return Err(ServerSendError::ChannelNotAcknowledged(channel_id));
+                }
+                if let Some(max_message_size) = channel.max_message_size() {
+                    if payload.len() > max_message_size {
+                        return Err(ServerSendError::PayloadTooLarge {
+                            size: payload.len(),
+                            max_message_size,
+                        });
+                    }
+                }
+                let now = Instant::now();
+                let tracked = tracked.then(|| client_connection.acks.track(channel_id, now));
+                if channel.is_unreliable() && client_connection.backlog.skips_unreliable() {
+                    // Dropped as if lost, a tracked message is reported as lost once it times out
+                    return Ok(tracked);
+                }
                 client_connection.sent_bytes_count += payload.len();
-                Ok(channel.send_payload(payload)?)
+                client_connection.bandwidth.record_outbound(payload.len());
+                if let Some(conditioner) = client_connection.conditioner.as_mut() {
+                    conditioner.hold_outbound(
+                        is_lossy(&client_connection.channels_configs, channel_id),
+                        now,
+                        HeldPayload {
+                            channel_id,
+                            payload,
+                            priority,
+                            tracked,
+                        },
+                    );
+                    return Ok(tracked);
+                }
+                let sent = match tracked {
+                    Some(id) => {
+                        channel.send_tracked_payload(id, payload, client_connection.deferred_flush)
+                    }
+                    None => {
+                        channel.send_payload(payload, priority, client_connection.deferred_flush)
+                    }
+                };
+                if let Err(err) = sent {
+                    if let Some(id) = tracked {
+                        client_connection.acks.forget(id);
+                    }
+                    return Err(err.into());
+                }
+                Ok(tracked)
             }
             Some(None) => return Err(ServerSendError::ChannelClosed),
             None => return Err(ServerSendError::InvalidChannelId(channel_id)),
@@ -786,23 +2739,32 @@ impl Endpoint {
         }
     }
 
+    /// The [`ConnectionLostEvent`] of the client is raised during the next sync update
     fn internal_disconnect_client(
         &mut self,
         client_id: ClientId,
-        reason: CloseReason,
+        close_reason: CloseReason,
+        disconnect_reason: DisconnectReason,
     ) -> Result<(), ServerDisconnectError> {
         match self.clients.remove(&client_id) {
-            Some(client_connection) => match client_connection.close_sender.send(reason) {
-                Ok(_) => Ok(()),
-                Err(_) => Err(ServerDisconnectError::ClientAlreadyDisconnected(client_id)),
-            },
+            Some(client_connection) => {
+                self.client_ids.release(client_id);
+                self.disconnected_clients
+                    .push((client_id, disconnect_reason));
+                match client_connection.close_sender.send(close_reason) {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(ServerDisconnectError::ClientAlreadyDisconnected(client_id)),
+                }
+            }
             None => Err(ServerDisconnectError::UnknownClient(client_id)),
         }
     }
 
     /// Logical "Disconnect", the client already closed/lost the connection.
-    fn try_disconnect_closed_client(&mut self, client_id: ClientId) {
-        if let Err(err) = self.internal_disconnect_client(client_id, CloseReason::PeerClosed) {
+    fn try_disconnect_closed_client(&mut self, client_id: ClientId, reason: DisconnectReason) {
+        if let Err(err) =
+            self.internal_disconnect_client(client_id, CloseReason::PeerClosed, reason)
+        {
             error!(
                 "Failed to properly disconnect client {}: {}",
                 client_id, err
@@ -815,8 +2777,23 @@ impl Endpoint {
     /// Disconnecting a client immediately prevents new messages from being sent on its connection and signal the underlying connection to closes all its background tasks. Before trully closing, the connection will wait for all buffered messages in all its opened channels to be properly sent according to their respective channel type.
     ///
     /// This may fail if no client if found for client_id, or if the client is already disconnected.
+    ///
+    /// The client receives [`CloseCode::Kicked`]. A [`ConnectionLostEvent`] with [`DisconnectReason::DisconnectedByServer`] is raised during the next sync update.
     pub fn disconnect_client(&mut self, client_id: ClientId) -> Result<(), ServerDisconnectError> {
-        self.internal_disconnect_client(client_id, CloseReason::LocalOrder)
+        self.disconnect_client_with_code(client_id, CloseCode::Kicked)
+    }
+
+    /// Same as [Endpoint::disconnect_client] but the client receives `code` as the application close code of the connection
+    pub fn disconnect_client_with_code(
+        &mut self,
+        client_id: ClientId,
+        code: CloseCode,
+    ) -> Result<(), ServerDisconnectError> {
+        self.internal_disconnect_client(
+            client_id,
+            CloseReason::LocalOrder(code),
+            DisconnectReason::DisconnectedByServer,
+        )
     }
 
     /// Same as [Endpoint::disconnect_client] but errors are logged instead of returned
@@ -829,10 +2806,18 @@ impl Endpoint {
         }
     }
 
-    /// Disconnects all connect clients
+    /// Disconnects all connect clients, they receive [`CloseCode::Kicked`]
     pub fn disconnect_all_clients(&mut self) {
-        for (_, client_connection) in self.clients.drain() {
-            let _ = client_connection.close_sender.send(CloseReason::LocalOrder);
+        self.disconnect_all_clients_with(CloseCode::Kicked, DisconnectReason::DisconnectedByServer);
+    }
+
+    fn disconnect_all_clients_with(&mut self, code: CloseCode, reason: DisconnectReason) {
+        for (client_id, client_connection) in self.clients.drain() {
+            self.client_ids.release(client_id);
+            self.disconnected_clients.push((client_id, reason.clone()));
+            let _ = client_connection
+                .close_sender
+                .send(CloseReason::LocalOrder(code));
         }
     }
 
@@ -844,6 +2829,12 @@ impl Endpoint {
         }
     }
 
+    /// Returns a clone of the underlying [`quinn::Connection`] of a client if connected over QUIC, see [`ServerSideConnection::quic_connection`]
+    #[cfg(feature = "raw")]
+    pub fn get_quic_connection(&self, client_id: ClientId) -> Option<quinn::Connection> {
+        self.clients.get(&client_id)?.quic_connection()
+    }
+
     /// Returns a mutable reference to a client connection if it exists
     pub fn get_connection_mut(&mut self, client_id: ClientId) -> Option<&mut ServerSideConnection> {
         match self.clients.get_mut(&client_id) {
@@ -860,25 +2851,292 @@ impl Endpoint {
         }
     }
 
+    /// Routes the payloads received from all the clients on `channel_ids` to the returned [`PayloadRoute`], replacing the previous routes of these channels.
+    ///
+    /// The payloads are routed during each sync update, they are not available to the receive methods of the endpoint anymore. This allows a sub-app or another thread running the simulation to receive them directly, without a system of the main world forwarding them.
+    pub fn route_channels(
+        &mut self,
+        channel_ids: impl IntoIterator<Item = ChannelId>,
+    ) -> PayloadRoute {
+        let (send, route) = PayloadRoute::new();
+        for channel_id in channel_ids {
+            self.routes.insert(channel_id, send.clone());
+        }
+        route
+    }
+
+    /// Stops routing the payloads of `channel_id`, see [`Endpoint::route_channels`]. Its payloads are delivered by the receive methods of the endpoint again.
+    pub fn remove_route(&mut self, channel_id: ChannelId) {
+        self.routes.remove(&channel_id);
+    }
+
+    /// Returns the ids of the routed channels, see [`Endpoint::route_channels`]
+    pub fn routed_channels(&self) -> Vec<ChannelId> {
+        self.routes.keys().cloned().collect()
+    }
+
+    /// Opens the instance `name` and returns the [`PayloadRoute`] of its clients, replacing the previous route of the instance.
+    ///
+    /// An instance isolates a group of clients, such as the players of a match, so that one process can host several matches: all the payloads of the clients assigned to it with [`Endpoint::assign_instance`] are routed during each sync update, typically to a sub-app running the match. The routes of [`Endpoint::route_channels`] only apply to the clients outside of the instances. The messages are sent to the clients of an instance with [`Endpoint::send_group_message`] on [`Endpoint::instance_clients`].
+    ///
+    /// Dropping the [`PayloadRoute`] closes the instance, like [`Endpoint::close_instance`].
+    pub fn open_instance(&mut self, name: impl Into<String>) -> PayloadRoute {
+        let (send, route) = PayloadRoute::new();
+        self.instances.insert(name.into(), send);
+        route
+    }
+
+    /// Closes the instance `name`, see [`Endpoint::open_instance`]. Its clients are unassigned, their payloads are delivered by the endpoint again.
+    pub fn close_instance(&mut self, name: &str) {
+        if self.instances.remove(name).is_none() {
+            return;
+        }
+        for connection in self.clients.values_mut() {
+            if connection.instance.as_deref() == Some(name) {
+                connection.instance = None;
+            }
+        }
+    }
+
+    /// Returns the names of the open instances, see [`Endpoint::open_instance`]
+    pub fn instances(&self) -> Vec<String> {
+        self.instances.keys().cloned().collect()
+    }
+
+    /// Assigns the client to the open instance `name`, see [`Endpoint::open_instance`]. Returns the instance the client was previously assigned to.
+    ///
+    /// The payloads already received from the client are routed to its new instance by the next sync update.
+    pub fn assign_instance(
+        &mut self,
+        client_id: ClientId,
+        name: &str,
+    ) -> Result<Option<String>, ServerInstanceError> {
+        if !self.instances.contains_key(name) {
+            return Err(ServerInstanceError::UnknownInstance(name.to_string()));
+        }
+        match self.clients.get_mut(&client_id) {
+            Some(connection) => Ok(connection.instance.replace(name.to_string())),
+            None => Err(ServerInstanceError::UnknownClient(client_id)),
+        }
+    }
+
+    /// Removes the client from its instance, its payloads are delivered by the endpoint again. Returns the instance the client was assigned to.
+    pub fn unassign_instance(&mut self, client_id: ClientId) -> Option<String> {
+        self.clients.get_mut(&client_id)?.instance.take()
+    }
+
+    /// Returns the instance the client is assigned to, see [`Endpoint::assign_instance`]
+    pub fn client_instance(&self, client_id: ClientId) -> Option<&str> {
+        self.clients.get(&client_id)?.instance()
+    }
+
+    /// Returns the ids of the clients assigned to the instance `name`, see [`Endpoint::assign_instance`]
+    pub fn instance_clients(&self, name: &str) -> Vec<ClientId> {
+        self.clients
+            .iter()
+            .filter(|(_, connection)| connection.instance.as_deref() == Some(name))
+            .map(|(client_id, _)| *client_id)
+            .collect()
+    }
+
+    /// Sends the received payloads of the clients of the instances and of the routed channels to their route. The routes whose [`PayloadRoute`] was dropped are removed.
+    fn route_payloads(&mut self) {
+        if self.routes.is_empty() && self.instances.is_empty() {
+            return;
+        }
+        let mut dropped_routes = Vec::new();
+        let mut dropped_instances = Vec::new();
+        for (client_id, connection) in self.clients.iter_mut() {
+            if let Some(instance) = connection.instance.clone() {
+                let Some(route) = self.instances.get(&instance) else {
+                    continue;
+                };
+                while let Ok(Some((channel_id, payload, received_at))) =
+                    connection.bytes_from_client_recv.try_recv_timestamped()
+                {
+                    let len = payload.len();
+                    let routed = RoutedPayload {
+                        client_id: *client_id,
+                        channel_id,
+                        payload,
+                        received_at,
+                    };
+                    match route.send(routed) {
+                        Ok(()) => {
+                            self.stats.received_messages_count += 1;
+                            connection.count_received(channel_id, len);
+                        }
+                        Err(std::sync::mpsc::SendError(routed)) => {
+                            // Delivered by the endpoint once the instance is closed
+                            connection.bytes_from_client_recv.requeue(
+                                channel_id,
+                                routed.payload,
+                                received_at,
+                            );
+                            dropped_instances.push(instance);
+                            break;
+                        }
+                    }
+                }
+                continue;
+            }
+            for (channel_id, route) in self.routes.iter() {
+                let Ok(payloads) = connection
+                    .bytes_from_client_recv
+                    .drain_channel_timestamped(*channel_id)
+                else {
+                    continue;
+                };
+                for (payload, received_at) in payloads {
+                    self.stats.received_messages_count += 1;
+                    connection.count_received(*channel_id, payload.len());
+                    let routed = RoutedPayload {
+                        client_id: *client_id,
+                        channel_id: *channel_id,
+                        payload,
+                        received_at,
+                    };
+                    if route.send(routed).is_err() {
+                        dropped_routes.push(*channel_id);
+                        break;
+                    }
+                }
+            }
+        }
+        for channel_id in dropped_routes {
+            self.routes.remove(&channel_id);
+        }
+        for name in dropped_instances {
+            self.close_instance(&name);
+        }
+    }
+
+    /// Attaches `value` to the client, replacing and returning its previous value of type `T`.
+    ///
+    /// A client holds at most one value per type, dropped when the client disconnects: per-client state such as authentication info or throttling counters does not need a map kept in sync with the connections.
+    pub fn set_client_data<T: Send + Sync + 'static>(
+        &mut self,
+        client_id: ClientId,
+        value: T,
+    ) -> Result<Option<T>, ServerClientDataError> {
+        match self.clients.get_mut(&client_id) {
+            Some(connection) => Ok(connection.data.insert(value)),
+            None => Err(ServerClientDataError::UnknownClient(client_id)),
+        }
+    }
+
+    /// Returns the value of type `T` attached to the client, see [`Endpoint::set_client_data`]
+    pub fn get_client_data<T: Send + Sync + 'static>(&self, client_id: ClientId) -> Option<&T> {
+        self.clients.get(&client_id)?.data.get()
+    }
+
+    /// Returns a mutable reference to the value of type `T` attached to the client, see [`Endpoint::set_client_data`]
+    pub fn get_client_data_mut<T: Send + Sync + 'static>(
+        &mut self,
+        client_id: ClientId,
+    ) -> Option<&mut T> {
+        self.clients.get_mut(&client_id)?.data.get_mut()
+    }
+
+    /// Detaches and returns the value of type `T` attached to the client, see [`Endpoint::set_client_data`]
+    pub fn remove_client_data<T: Send + Sync + 'static>(
+        &mut self,
+        client_id: ClientId,
+    ) -> Option<T> {
+        self.clients.get_mut(&client_id)?.data.remove()
+    }
+
     /// Returns statistics about the server's endpoint
     pub fn endpoint_stats(&self) -> &EndpointStats {
         &self.stats
     }
 
-    /// Opens a channel of the requested [ChannelKind] and returns its [ChannelId].
+    /// When enabled, the messages sent to the clients are held until the next flush: [`Endpoint::flush`] or the flush system of the [`QuinnetServerPlugin`], which runs in the [`QuinnetFlush`] set.
+    ///
+    /// Messages sent during a frame (or a fixed tick) then leave together. Disabled by default: messages are handed to the async tasks as soon as they are sent.
+    pub fn set_deferred_flush(&mut self, deferred: bool) {
+        self.deferred_flush = deferred;
+        for client in self.clients.values_mut() {
+            client.deferred_flush = deferred;
+        }
+    }
+
+    /// Returns true if the messages are held until the next flush, see [`Endpoint::set_deferred_flush`]
+    pub fn deferred_flush(&self) -> bool {
+        self.deferred_flush
+    }
+
+    /// When enabled, the messages received from the clients are captured once per sync update of the server, in the CoreStage::PreUpdate stage, and the receive methods only return the messages of the last capture.
+    ///
+    /// All the systems of a frame then see the same set of messages, whatever their order, instead of the messages arriving while the frame runs. Disabled by default: the receive methods return the messages as soon as they arrive.
+    pub fn set_frame_coherent_receive(&mut self, enabled: bool) {
+        self.frame_coherent_receive = enabled;
+        for client in self.clients.values_mut() {
+            client.bytes_from_client_recv.set_frozen(enabled);
+        }
+    }
+
+    /// Returns true if the received messages are captured once per sync update, see [`Endpoint::set_frame_coherent_receive`]
+    pub fn frame_coherent_receive(&self) -> bool {
+        self.frame_coherent_receive
+    }
+
+    /// Sends the messages deferred until the next flush, to all the clients
+    pub fn flush(&self) {
+        for client in self.clients.values() {
+            client.flush();
+        }
+    }
+
+    /// Returns statistics about the buffers used to serialize messages and frame unreliable datagrams, for all the clients of the endpoint
+    pub fn buffer_pool_stats(&self) -> BufferPoolStats {
+        self.buffer_pool.stats()
+    }
+
+    /// Serializes the messages in buffers split off large pooled chunks, `false` to allocate a buffer of the exact size of each message instead. Enabled by default.
+    ///
+    /// A pooled chunk is only freed once all the messages serialized in it are dropped. Disable the pooling when the sent messages are kept alive long-term, such as retained for retransmission or in a replay log, so that each of them does not keep a whole chunk alive.
+    pub fn set_buffer_pooling(&mut self, enabled: bool) {
+        self.buffer_pool.set_pooling(enabled);
+    }
+
+    /// Returns whether the messages are serialized in pooled buffers, see [`Endpoint::set_buffer_pooling`]
+    pub fn buffer_pooling(&self) -> bool {
+        self.buffer_pool.pooling()
+    }
+
+    /// Opens a channel of the requested [ChannelConfig] (or [`ChannelKind`](crate::shared::channels::ChannelKind)) and returns its [ChannelId].
     ///
     /// If no channels were previously opened, the opened channel will be the new default channel.
     ///
     /// Can fail if the Endpoint is closed or if too many channels are already opened.
-    pub fn open_channel(
+    pub fn open_channel<T: Into<ChannelConfig>>(
+        &mut self,
+        channel_config: T,
+    ) -> Result<ChannelId, ChannelCreationError> {
+        self.checked_open_channel(channel_config.into())
+    }
+
+    /// Same as [Endpoint::open_channel], but payloads sent on this channel will be encrypted with the given [ChannelEncryption].
+    ///
+    /// The clients must enable the same [ChannelEncryption] on the same [ChannelId] to be able to read them.
+    pub fn open_encrypted_channel<T: Into<ChannelConfig>>(
+        &mut self,
+        channel_config: T,
+        encryption: ChannelEncryption,
+    ) -> Result<ChannelId, ChannelCreationError> {
+        self.checked_open_channel(channel_config.into().encrypted(encryption))
+    }
+
+    fn checked_open_channel(
         &mut self,
-        channel_type: ChannelKind,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, ChannelCreationError> {
         let channel_id = match self.available_channel_ids.pop_first() {
             Some(channel_id) => channel_id,
             None => return Err(ChannelCreationError::MaxChannelsCountReached),
         };
-        match self.create_endpoint_channel(channel_id, channel_type) {
+        match self.create_endpoint_channel(channel_id, channel_config) {
             Ok(channel_id) => Ok(channel_id),
             Err(err) => {
                 self.available_channel_ids.insert(channel_id);
@@ -890,10 +3148,10 @@ impl Endpoint {
     /// Assumes presence of available ids in `available_channel_ids`
     fn unchecked_open_channel(
         &mut self,
-        channel_type: ChannelKind,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, AsyncChannelError> {
         let channel_id = self.available_channel_ids.pop_first().unwrap();
-        match self.create_endpoint_channel(channel_id, channel_type) {
+        match self.create_endpoint_channel(channel_id, channel_config) {
             Ok(channel_id) => Ok(channel_id),
             Err(err) => {
                 self.available_channel_ids.insert(channel_id);
@@ -906,18 +3164,18 @@ impl Endpoint {
     fn create_endpoint_channel(
         &mut self,
         channel_id: ChannelId,
-        channel_type: ChannelKind,
+        channel_config: ChannelConfig,
     ) -> Result<ChannelId, AsyncChannelError> {
         let unregistered_channels =
-            self.create_unregistered_endpoint_channels(channel_id, channel_type)?;
+            self.create_unregistered_endpoint_channels(channel_id, &channel_config)?;
         // Only commit the changes once all channels have been confirmed to be created.
         for (client_id, channel) in unregistered_channels {
             self.clients
                 .get_mut(&client_id)
                 .unwrap()
-                .register_connection_channel(channel);
+                .register_connection_channel(channel, channel_config.clone());
         }
-        self.opened_channels.insert(channel_id, channel_type);
+        self.opened_channels.insert(channel_id, channel_config);
         if self.default_channel.is_none() {
             self.default_channel = Some(channel_id);
         }
@@ -927,13 +3185,20 @@ impl Endpoint {
     fn create_unregistered_endpoint_channels(
         &mut self,
         channel_id: ChannelId,
-        channel_type: ChannelKind,
+        channel_config: &ChannelConfig,
     ) -> Result<HashMap<ClientId, Channel>, AsyncChannelError> {
         let mut unregistered_channels = HashMap::new();
-        for (&client_id, client_connection) in self.clients.iter_mut() {
+        for (&client_id, client_connection) in self
+            .clients
+            .iter_mut()
+            .filter(|(_, connection)| !connection.virtual_host)
+        {
             // Unregistered channels are dropped here on error, created async tasks are closing too.
-            let channel = client_connection
-                .create_unregistered_connection_channel(channel_id, channel_type)?;
+            let channel = client_connection.create_unregistered_connection_channel(
+                channel_id,
+                channel_config.clone(),
+                self.buffer_pool.sibling(),
+            )?;
             unregistered_channels.insert(client_id, channel);
         }
         Ok(unregistered_channels)
@@ -941,7 +3206,7 @@ impl Endpoint {
 
     /// Closes the channel with the corresponding [ChannelId].
     ///
-    /// No new messages will be able to be sent on this channel, however, the channel will properly try to send all the messages that were previously pushed to it, according to its [ChannelKind], before fully closing.
+    /// No new messages will be able to be sent on this channel, however, the channel will properly try to send all the messages that were previously pushed to it, according to its [`ChannelKind`](crate::shared::channels::ChannelKind), before fully closing.
     ///
     /// If the closed channel is the current default channel, the default channel gets set to `None`.
     ///
@@ -952,8 +3217,16 @@ impl Endpoint {
                 if Some(channel_id) == self.default_channel {
                     self.default_channel = None;
                 }
-                for (_, connection) in self.clients.iter_mut() {
-                    connection.close_channel(channel_id)?;
+                for (_, connection) in self
+                    .clients
+                    .iter_mut()
+                    .filter(|(_, connection)| !connection.virtual_host)
+                {
+                    match connection.close_channel(channel_id) {
+                        // Rejected by the client, see [`ChannelRejectedEvent`]
+                        Err(ChannelCloseError::ChannelAlreadyClosed) => (),
+                        result => result?,
+                    }
                 }
                 self.available_channel_ids.insert(channel_id);
                 Ok(())
@@ -972,6 +3245,91 @@ impl Endpoint {
         self.default_channel
     }
 
+    /// Sets whether the endpoint accepts new connections. Enabled by default.
+    ///
+    /// While disabled, incoming connections are closed with [`CloseCode::NotAccepting`] once their handshake completes, raised as a [`crate::client::connection::ConnectionFailedEvent`] with [`crate::client::QuinnetConnectionError::ConnectionClosed`] on the client side. Connected clients are not affected and the endpoint socket stays bound.
+    pub fn set_accepting(&mut self, accepting: bool) {
+        self.accepting.store(accepting, Ordering::Relaxed);
+    }
+
+    /// Returns true if the endpoint currently accepts new connections, see [`Endpoint::set_accepting`]
+    pub fn is_accepting(&self) -> bool {
+        self.accepting.load(Ordering::Relaxed)
+    }
+
+    /// Adds a client connected through a custom [`TransportConnection`], such as the server end of a [`crate::shared::transport::memory::MemoryConnection`], to this endpoint.
+    ///
+    /// The client gets a [`ClientId`] from the same id space as the QUIC clients and a [`ConnectionEvent`] is raised once it is accepted. It is then handled like any other client, game logic does not need to know which transport a client uses.
+    ///
+    /// If the endpoint is not accepting new connections, see [`Endpoint::set_accepting`], the transport connection is closed instead.
+    pub fn add_transport_connection<C: TransportConnection>(&self, connection: C) {
+        if !self.is_accepting() {
+            debug!(
+                "Refused a connection from {}: endpoint is not accepting new connections",
+                display_remote(&connection)
+            );
+            connection.close(CloseCode::NotAccepting);
+            self.stats.handshakes.refuse(RefusalReason::NotAccepting);
+            return;
+        }
+        self.runtime.spawn(client_connection_task(
+            connection,
+            ConnectionHandling {
+                to_sync_endpoint_send: self.to_sync_endpoint_send.clone(),
+                hardening: self.hardening.clone(),
+                shard: 0,
+                handshakes: self.stats.handshakes.clone(),
+                address_validation: false,
+            },
+        ));
+    }
+
+    /// Adds a client driven by the returned [`ScriptedClient`] instead of a transport and its async tasks, see [`crate::testing`].
+    ///
+    /// Meant for deterministic tests of the systems handling the clients: the client is accepted by the next update of the server, like a client added with [`Endpoint::add_transport_connection`], and the events and messages scripted with the [`ScriptedClient`] are all raised by the following update.
+    #[cfg(feature = "testing")]
+    pub fn add_scripted_client(&self) -> ScriptedClient {
+        let (client_close_send, client_close_recv) =
+            broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
+        let (bytes_from_client_send, bytes_from_client_recv) =
+            incoming_channel(DEFAULT_MESSAGE_QUEUE_SIZE);
+        let (to_connection_send, from_sync_server_recv) =
+            mpsc::channel::<ServerSyncMessage>(DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE);
+        let (from_channels_send, from_channels_recv) =
+            mpsc::channel::<ChannelAsyncMessage>(DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE);
+        let (to_channels_send, to_channels_recv) =
+            mpsc::channel::<ChannelSyncMessage>(DEFAULT_QCHANNEL_MESSAGES_CHANNEL_SIZE);
+
+        if !self.is_accepting() {
+            debug!("Refused a scripted client: endpoint is not accepting new connections");
+            let _ = client_close_send.send(CloseReason::LocalOrder(CloseCode::NotAccepting));
+        } else {
+            let connection = ServerSideConnection::new(
+                Arc::new(ScriptedTransport),
+                Arc::new(RwLock::new(HashMap::new())),
+                bytes_from_client_recv,
+                client_close_send,
+                to_connection_send,
+                from_channels_recv,
+                to_channels_send,
+            );
+            if let Err(err) = self
+                .to_sync_endpoint_send
+                .try_send(ServerAsyncMessage::ClientConnected(Box::new(connection)))
+            {
+                error!("Failed to add a scripted client: {}", err);
+            }
+        }
+        ScriptedClient::new(
+            self.to_sync_endpoint_send.clone(),
+            from_sync_server_recv,
+            bytes_from_client_send,
+            from_channels_send,
+            to_channels_recv,
+            client_close_recv,
+        )
+    }
+
     fn close_incoming_connections_handler(&mut self) -> Result<(), AsyncChannelError> {
         match self.close_sender.send(()) {
             Ok(_) => Ok(()),
@@ -980,19 +3338,58 @@ impl Endpoint {
         }
     }
 
-    fn handle_connection(
-        &mut self,
-        mut connection: ServerSideConnection,
-    ) -> Result<ClientId, AsyncChannelError> {
-        for (channel_id, channel_type) in self.opened_channels.iter() {
-            if let Err(err) = connection.create_connection_channel(*channel_id, *channel_type) {
+    /// Returns the id of the new client, or `None` if its connection was refused
+    fn handle_connection(&mut self, mut connection: ServerSideConnection) -> Option<ClientId> {
+        let virtual_host = connection
+            .server_name()
+            .and_then(|server_name| self.virtual_hosts.get(server_name));
+        connection.virtual_host = virtual_host.is_some();
+        connection.network_tick = self.network_tick.clone();
+        let channels: Vec<(ChannelId, ChannelConfig)> = match virtual_host {
+            Some(channels_config) => channels_config
+                .configs()
+                .iter()
+                .enumerate()
+                .map(|(channel_id, config)| (channel_id as ChannelId, config.clone()))
+                .collect(),
+            None => self
+                .opened_channels
+                .iter()
+                .map(|(channel_id, config)| (*channel_id, config.clone()))
+                .collect(),
+        };
+        for (channel_id, channel_config) in channels {
+            if let Err(err) = connection.create_connection_channel(
+                channel_id,
+                channel_config,
+                self.buffer_pool.sibling(),
+            ) {
+                error!("Failed to handle connection of a client: {}", err);
                 connection.try_close();
-                return Err(err);
+                return None;
             };
         }
 
-        self.client_id_gen += 1;
-        let client_id = self.client_id_gen;
+        connection.deferred_flush = self.deferred_flush;
+        connection
+            .bytes_from_client_recv
+            .set_frozen(self.frame_coherent_receive);
+        connection.stats_history = self.stats_history.map(StatsHistory::new);
+        connection.congestion = self.congestion_events.then(CongestionMonitor::default);
+        connection.memory.set_budget(self.memory_budget);
+        let clients = &self.clients;
+        let Some(client_id) = self
+            .client_ids
+            .allocate(|client_id| clients.contains_key(&client_id))
+        else {
+            warn!(
+                "No client id available for the connection from {:?}, refusing it",
+                connection.remote_address()
+            );
+            connection.try_close();
+            self.stats.handshakes.refuse(RefusalReason::NoClientId);
+            return None;
+        };
 
         match connection
             .to_connection_send
@@ -1000,11 +3397,13 @@ impl Endpoint {
         {
             Ok(_) => {
                 self.clients.insert(client_id, connection);
-                Ok(client_id)
+                Some(client_id)
             }
             Err(_) => {
+                error!("Failed to handle connection of a client, already disconnected");
+                self.client_ids.release(client_id);
                 connection.try_close();
-                Err(AsyncChannelError::InternalChannelClosed)
+                None
             }
         }
     }
@@ -1017,6 +3416,23 @@ impl Endpoint {
 pub struct QuinnetServer {
     runtime: runtime::Handle,
     endpoint: Option<Endpoint>,
+    last_start: Option<EndpointStartSettings>,
+    lifecycle_events: Vec<EndpointLifecycleEvent>,
+    deferred_flush: bool,
+}
+
+/// Settings of the last started endpoint, re-used on restart
+#[derive(Clone)]
+struct EndpointStartSettings {
+    server_cert: Arc<ServerCertificate>,
+    channels_config: ChannelsConfiguration,
+}
+
+/// Endpoint start/stop, raised as bevy events during the next sync update
+enum EndpointLifecycleEvent {
+    Started(SocketAddr),
+    ClientDisconnected(ClientId, DisconnectReason),
+    Stopped,
 }
 
 impl FromWorld for QuinnetServer {
@@ -1026,7 +3442,7 @@ impl FromWorld for QuinnetServer {
                 .enable_all()
                 .build()
                 .unwrap();
-            world.insert_resource(AsyncRuntime(async_runtime));
+            world.insert_resource(AsyncRuntime::new(async_runtime));
         };
 
         let runtime = world.resource::<AsyncRuntime>();
@@ -1039,6 +3455,17 @@ impl QuinnetServer {
         Self {
             endpoint: None,
             runtime,
+            last_start: None,
+            lifecycle_events: Vec::new(),
+            deferred_flush: false,
+        }
+    }
+
+    /// Sets the flush mode of the current endpoint and of the endpoints started later, see [`Endpoint::set_deferred_flush`]
+    pub fn set_deferred_flush(&mut self, deferred: bool) {
+        self.deferred_flush = deferred;
+        if let Some(endpoint) = self.endpoint.as_mut() {
+            endpoint.set_deferred_flush(deferred);
         }
     }
 
@@ -1075,60 +3502,259 @@ impl QuinnetServer {
         cert_mode: CertificateRetrievalMode,
         channels_config: ChannelsConfiguration,
     ) -> Result<ServerCertificate, EndpointStartError> {
-        // Endpoint configuration
         let server_cert = retrieve_certificate(cert_mode)?;
-        let mut endpoint_config = ServerConfig::with_single_cert(
-            server_cert.cert_chain.clone(),
-            server_cert.priv_key.clone_key(),
-        )?;
-        Arc::get_mut(&mut endpoint_config.transport)
-            .ok_or(EndpointStartError::LockAcquisitionFailure)?
-            .keep_alive_interval(Some(DEFAULT_KEEP_ALIVE_INTERVAL_S));
+        self.internal_start_endpoint(config, Arc::new(server_cert.clone()), channels_config)?;
+
+        Ok(server_cert)
+    }
+
+    /// Stops the endpoint if it is opened, and starts a new one on the address given by `config`.
+    ///
+    /// The new endpoint re-uses the certificate and the [ChannelsConfiguration] of the last started endpoint. All the clients are disconnected.
+    ///
+    /// Returns [`EndpointStartError::NeverStarted`] if no endpoint was ever started on this server.
+    pub fn restart_endpoint(
+        &mut self,
+        config: ServerEndpointConfiguration,
+    ) -> Result<(), EndpointStartError> {
+        let Some(last_start) = self.last_start.clone() else {
+            return Err(EndpointStartError::NeverStarted);
+        };
+        if self.is_listening() {
+            self.try_stop_endpoint();
+        }
+        self.internal_start_endpoint(config, last_start.server_cert, last_start.channels_config)
+    }
+
+    /// Starts a new endpoint on a [`quinn::Endpoint`] created by the app, with the given [ExternalEndpointConfiguration] and [ChannelsConfiguration]
+    ///
+    /// The app keeps its own handles on the endpoint, to share its socket with other QUIC services: see [`ExternalEndpointConfiguration::with_protocol_routing`] to share its incoming connections. Stopping the Quinnet endpoint disconnects its clients and stops accepting incoming connections, but does not close the quinn endpoint.
+    ///
+    /// The certificate is part of the server configuration of the endpoint, [`QuinnetServer::restart_endpoint`] cannot restart an external endpoint.
+    pub fn start_external_endpoint(
+        &mut self,
+        config: ExternalEndpointConfiguration,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<(), EndpointStartError> {
+        let (to_sync_endpoint_send, from_async_endpoint_recv) =
+            mpsc::channel::<ServerAsyncMessage>(DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE);
+        let (endpoint_close_send, endpoint_close_recv) =
+            broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
+
+        let accepting = Arc::new(AtomicBool::new(true));
+        let status = config
+            .status
+            .map(|status| Arc::new(StatusState::new(status, accepting.clone())));
+        let local_addr = config.endpoint.local_addr()?;
+
+        info!("Starting endpoint on external endpoint: {} ...", local_addr);
+        let endpoint_accepting = accepting.clone();
+        let endpoint_to_sync_send = to_sync_endpoint_send.clone();
+        let hardening = config.hardening.clone();
+        let endpoint_status = status.clone();
+        let handshakes = Arc::<HandshakeCounters>::default();
+        let endpoint_handshakes = handshakes.clone();
+        self.runtime.spawn(async move {
+            accept_task(
+                config.endpoint,
+                config.routing,
+                endpoint_status,
+                ConnectionHandling {
+                    to_sync_endpoint_send: endpoint_to_sync_send,
+                    hardening: config.hardening,
+                    shard: 0,
+                    handshakes: endpoint_handshakes,
+                    address_validation: false,
+                },
+                endpoint_close_recv,
+                endpoint_accepting,
+            )
+            .await;
+        });
+
+        let mut endpoint = Endpoint::new(
+            local_addr,
+            hardening,
+            endpoint_close_send,
+            accepting,
+            self.runtime.clone(),
+            to_sync_endpoint_send,
+            from_async_endpoint_recv,
+        );
+        endpoint.stats.handshakes = handshakes;
+        self.install_endpoint(endpoint, status, channels_config)
+    }
+
+    fn internal_start_endpoint(
+        &mut self,
+        config: ServerEndpointConfiguration,
+        server_cert: Arc<ServerCertificate>,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<(), EndpointStartError> {
+        if let Some(size) = config.socket.invalid_max_udp_payload_size() {
+            return Err(EndpointStartError::InvalidMaxUdpPayloadSize(size));
+        }
+        let alpn_protocols = match &config.status {
+            Some(status) => vec![QUINNET_ALPN.to_vec(), status.alpn().to_vec()],
+            None => Vec::new(),
+        };
+        let endpoint_config = server_config(&server_cert, alpn_protocols, &config)?;
 
         let (to_sync_endpoint_send, from_async_endpoint_recv) =
             mpsc::channel::<ServerAsyncMessage>(DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE);
-        let (endpoint_close_send, endpoint_close_recv) =
-            broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
+        let (endpoint_close_send, _) = broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
+
+        let accepting = Arc::new(AtomicBool::new(true));
+        let status = config
+            .status
+            .clone()
+            .map(|status| Arc::new(StatusState::new(status, accepting.clone())));
+
+        let sockets = bind_shard_sockets(config.local_bind_addr, config.shards)?;
+        for socket in &sockets {
+            config.qos.apply(socket)?;
+            config.socket.apply(socket)?;
+        }
+        let local_addr = sockets[0].local_addr()?;
 
-        let socket = std::net::UdpSocket::bind(config.local_bind_addr)?;
+        self.last_start = Some(EndpointStartSettings {
+            server_cert,
+            channels_config: channels_config.clone(),
+        });
 
-        info!("Starting endpoint on: {} ...", config.local_bind_addr);
-        self.runtime.spawn(async move {
-            endpoint_task(
-                socket,
-                endpoint_config,
+        #[cfg(feature = "port-mapping")]
+        if let Some(port_mapping) = config.port_mapping.clone() {
+            self.runtime.spawn(port_mapping_task(
+                port_mapping,
+                local_addr.port(),
                 to_sync_endpoint_send.clone(),
-                endpoint_close_recv,
-            )
-            .await;
-        });
+                endpoint_close_send.subscribe(),
+            ));
+        }
+
+        match sockets.len() {
+            1 => info!("Starting endpoint on: {} ...", local_addr),
+            shards => info!(
+                "Starting endpoint on: {} with {} shards ...",
+                local_addr, shards
+            ),
+        }
+        let shards = sockets.len();
+        let releases: Vec<SocketRelease> = (0..shards).map(|_| SocketRelease::default()).collect();
+        let handshakes = Arc::<HandshakeCounters>::default();
+        for (shard, (socket, release)) in sockets.into_iter().zip(releases.clone()).enumerate() {
+            let endpoint_accepting = accepting.clone();
+            let endpoint_close_recv = endpoint_close_send.subscribe();
+            let endpoint_status = status.clone();
+            let endpoint_config = endpoint_config.clone();
+            let handling = ConnectionHandling {
+                to_sync_endpoint_send: to_sync_endpoint_send.clone(),
+                hardening: config.hardening.clone(),
+                shard,
+                handshakes: handshakes.clone(),
+                address_validation: config.address_validation,
+            };
+            // The external address is the same for all the shards
+            let stun_server = config.stun_server.filter(|_| shard == 0);
+            let (qos, socket_config) = (config.qos, config.socket);
+            self.runtime.spawn(async move {
+                let endpoint = create_quinn_endpoint(
+                    socket,
+                    release,
+                    stun_server,
+                    qos,
+                    socket_config,
+                    endpoint_config,
+                    handling.to_sync_endpoint_send.clone(),
+                )
+                .await;
+                accept_task(
+                    endpoint,
+                    None,
+                    endpoint_status,
+                    handling,
+                    endpoint_close_recv,
+                    endpoint_accepting,
+                )
+                .await;
+            });
+        }
+
+        let mut endpoint = Endpoint::new(
+            local_addr,
+            config.hardening,
+            endpoint_close_send,
+            accepting,
+            self.runtime.clone(),
+            to_sync_endpoint_send,
+            from_async_endpoint_recv,
+        );
+        endpoint.shards = shards;
+        endpoint.sockets = releases;
+        endpoint.stats.handshakes = handshakes;
+        self.install_endpoint(endpoint, status, channels_config)
+    }
 
-        let mut endpoint = Endpoint::new(endpoint_close_send, from_async_endpoint_recv);
-        for channel_type in channels_config.configs() {
-            endpoint.unchecked_open_channel(*channel_type)?;
+    fn install_endpoint(
+        &mut self,
+        mut endpoint: Endpoint,
+        status: Option<Arc<StatusState>>,
+        channels_config: ChannelsConfiguration,
+    ) -> Result<(), EndpointStartError> {
+        endpoint.status = status;
+        endpoint.set_deferred_flush(self.deferred_flush);
+        endpoint.protocol_hash = channels_config.protocol_hash();
+        for channel_config in channels_config.configs() {
+            endpoint.unchecked_open_channel(channel_config.clone())?;
         }
 
+        self.lifecycle_events
+            .push(EndpointLifecycleEvent::Started(endpoint.local_addr()));
         self.endpoint = Some(endpoint);
 
-        Ok(server_cert)
+        Ok(())
     }
 
-    /// Closes the endpoint and all the connections associated with it
+    /// Closes the endpoint and all the connections associated with it, the clients receive [`CloseCode::ServerShutdown`]
+    ///
+    /// Blocks until the sockets of the endpoint are closed, for up to [`ENDPOINT_STOP_TIMEOUT`] while the connections finish closing: a new endpoint can be started on the same port right away.
     ///
     /// Returns [`EndpointAlreadyClosed`] if the endpoint is already closed
     pub fn stop_endpoint(&mut self) -> Result<(), EndpointAlreadyClosed> {
         match self.endpoint.take() {
             Some(mut endpoint) => {
-                endpoint.disconnect_all_clients();
-                match endpoint.close_incoming_connections_handler() {
+                endpoint.disconnect_all_clients_with(
+                    CloseCode::ServerShutdown,
+                    DisconnectReason::EndpointStopped,
+                );
+                // The endpoint is dropped, raise the events of its clients with the stop event
+                for (id, reason) in std::mem::take(&mut endpoint.disconnected_clients) {
+                    endpoint.run_disconnect_hook(id, &reason);
+                    self.lifecycle_events
+                        .push(EndpointLifecycleEvent::ClientDisconnected(id, reason));
+                }
+                self.lifecycle_events.push(EndpointLifecycleEvent::Stopped);
+                let result = match endpoint.close_incoming_connections_handler() {
                     Ok(_) => Ok(()),
                     Err(_) => Err(EndpointAlreadyClosed),
+                };
+                let deadline = Instant::now() + ENDPOINT_STOP_TIMEOUT;
+                for socket in &endpoint.sockets {
+                    socket.release(deadline);
                 }
+                result
             }
             None => Err(EndpointAlreadyClosed),
         }
     }
 
+    /// Same as [QuinnetServer::stop_endpoint] but will log the error instead of returning it
+    pub fn try_stop_endpoint(&mut self) {
+        if let Err(err) = self.stop_endpoint() {
+            error!("Failed to stop endpoint: {}", err);
+        }
+    }
+
     /// Returns true if the server is currently listening for messages and connections.
     pub fn is_listening(&self) -> bool {
         match &self.endpoint {
@@ -1136,83 +3762,924 @@ impl QuinnetServer {
             None => false,
         }
     }
+
+    /// Receives the messages from the async server tasks and updates the endpoint, like [`update_sync_server`] does, without any Bevy system.
+    ///
+    /// Meant for custom runners and schedules, or tools which don't add the [`QuinnetServerPlugin`]. Returns the events which would have been raised, in order.
+    pub fn pump(&mut self) -> Vec<QuinnetServerEvent> {
+        let mut events = Vec::new();
+        for event in self.lifecycle_events.drain(..) {
+            match event {
+                EndpointLifecycleEvent::Started(local_addr) => {
+                    events.push(QuinnetServerEvent::EndpointStarted(EndpointStartedEvent {
+                        local_addr,
+                    }));
+                }
+                EndpointLifecycleEvent::ClientDisconnected(id, reason) => {
+                    events.push(QuinnetServerEvent::ConnectionLost(ConnectionLostEvent {
+                        id,
+                        reason,
+                    }));
+                }
+                EndpointLifecycleEvent::Stopped => {
+                    events.push(QuinnetServerEvent::EndpointStopped(EndpointStoppedEvent));
+                }
+            }
+        }
+
+        if let Some(endpoint) = self.endpoint.as_mut() {
+            while let Ok(message) = endpoint.from_async_endpoint_recv.try_recv() {
+                match message {
+                    ServerAsyncMessage::ClientConnected(connection) => {
+                        if let Some(client_id) = endpoint.handle_connection(*connection) {
+                            endpoint.stats.connect_count += 1;
+                            let server_name = endpoint.clients[&client_id]
+                                .server_name()
+                                .map(str::to_owned);
+                            events.push(QuinnetServerEvent::Connection(ConnectionEvent {
+                                id: client_id,
+                                server_name,
+                            }));
+                        }
+                    }
+                    ServerAsyncMessage::ConnectionAttempt(remote_addr) => {
+                        events.push(QuinnetServerEvent::ConnectionAttempt(
+                            ConnectionAttemptEvent { remote_addr },
+                        ));
+                    }
+                    ServerAsyncMessage::HandshakeFailed(remote_addr, error) => {
+                        events.push(QuinnetServerEvent::HandshakeFailed(HandshakeFailedEvent {
+                            remote_addr,
+                            error,
+                        }));
+                    }
+                    ServerAsyncMessage::ExternalAddressDiscovered(external_addr) => {
+                        endpoint.external_addr = Some(external_addr);
+                        events.push(QuinnetServerEvent::ExternalAddressDiscovered(
+                            ExternalAddressDiscoveredEvent { external_addr },
+                        ));
+                    }
+                    #[cfg(feature = "port-mapping")]
+                    ServerAsyncMessage::PortMapping(result) => match result {
+                        Ok(mapping) => {
+                            endpoint.port_mapping = Some(mapping);
+                            events.push(QuinnetServerEvent::PortMappingSucceeded(
+                                PortMappingSucceededEvent { mapping },
+                            ));
+                        }
+                        Err(error) => {
+                            endpoint.port_mapping = None;
+                            events.push(QuinnetServerEvent::PortMappingFailed(
+                                PortMappingFailedEvent { error },
+                            ));
+                        }
+                    },
+                    ServerAsyncMessage::ClientConnectionClosed(client_id, reason) => {
+                        if let Some(connection) = endpoint.clients.get(&client_id) {
+                            let reason = match (
+                                connection.transfer_deadline,
+                                connection.mismatch_deadline,
+                            ) {
+                                (Some(_), _) => DisconnectReason::Transferred,
+                                (_, Some(_)) => DisconnectReason::ProtocolMismatch,
+                                _ => reason,
+                            };
+                            endpoint.stats.disconnect_count += 1;
+                            endpoint.try_disconnect_closed_client(client_id, reason);
+                        }
+                    }
+                    ServerAsyncMessage::ClientCloseStage(client_id, stage, elapsed) => {
+                        events.push(QuinnetServerEvent::ClientCloseStage(
+                            ClientCloseStageEvent {
+                                id: client_id,
+                                stage,
+                                elapsed,
+                            },
+                        ));
+                    }
+                    ServerAsyncMessage::DisconnectHookCompleted(client_id, reason) => {
+                        events.push(QuinnetServerEvent::DisconnectHookCompleted(
+                            DisconnectHookCompletedEvent {
+                                id: client_id,
+                                reason,
+                            },
+                        ));
+                    }
+                }
+            }
+
+            let channels_messages =
+                par_map_connections(endpoint.clients.iter_mut(), |client_id, connection| {
+                    let mut lost = false;
+                    let mut violations = Vec::new();
+                    let mut channel_events = Vec::new();
+                    while let Ok(message) = connection.from_channels_recv.try_recv() {
+                        match message {
+                            ChannelAsyncMessage::LostConnection => lost = true,
+                            ChannelAsyncMessage::ProtocolViolation(violation) => {
+                                connection.protocol_violations += 1;
+                                violations.push(ProtocolViolationEvent {
+                                    id: client_id,
+                                    violation,
+                                    count: connection.protocol_violations,
+                                });
+                            }
+                            ChannelAsyncMessage::ChannelResumed(channel_id) => {
+                                channel_events.push(QuinnetServerEvent::ChannelResumed(
+                                    ChannelResumedEvent {
+                                        id: client_id,
+                                        channel_id,
+                                    },
+                                ));
+                            }
+                            ChannelAsyncMessage::ChannelError(channel_id, error) => {
+                                channel_events.push(QuinnetServerEvent::ChannelError(
+                                    ChannelErrorEvent {
+                                        id: client_id,
+                                        channel_id,
+                                        error,
+                                    },
+                                ));
+                            }
+                        }
+                    }
+                    (client_id, lost, violations, channel_events)
+                });
+            let mut lost_clients = Vec::new();
+            let mut violating_clients = Vec::new();
+            for (client_id, lost, violations, channel_events) in channels_messages {
+                if lost {
+                    lost_clients.push(client_id);
+                }
+                let over_budget = endpoint
+                    .hardening
+                    .decode_error_budget()
+                    .zip(violations.last())
+                    .is_some_and(|(budget, last)| last.count > budget);
+                if over_budget && !lost {
+                    violating_clients.push(client_id);
+                }
+                events.extend(
+                    violations
+                        .into_iter()
+                        .map(QuinnetServerEvent::ProtocolViolation),
+                );
+                events.extend(channel_events);
+            }
+            for client_id in violating_clients {
+                if let Err(err) = endpoint.internal_disconnect_client(
+                    client_id,
+                    CloseReason::LocalOrder(CloseCode::ProtocolViolation),
+                    DisconnectReason::ProtocolViolation,
+                ) {
+                    error!(
+                        "Failed to properly disconnect client {}: {}",
+                        client_id, err
+                    );
+                }
+            }
+            let now = Instant::now();
+            let mut over_memory_clients = Vec::new();
+            for (client_id, connection) in endpoint.clients.iter_mut() {
+                if let Some(history) = &mut connection.stats_history {
+                    history.sample(now, connection.connection_handle.stats());
+                }
+                let max_datagram_size = connection.max_datagram_size();
+                if max_datagram_size != connection.last_max_datagram_size {
+                    events.push(QuinnetServerEvent::MaxDatagramSizeChanged(
+                        MaxDatagramSizeChangedEvent {
+                            id: *client_id,
+                            previous: connection.last_max_datagram_size,
+                            max_datagram_size,
+                        },
+                    ));
+                    connection.last_max_datagram_size = max_datagram_size;
+                }
+                if let Some(monitor) = &mut connection.congestion {
+                    let path_stats = connection.connection_handle.stats().path;
+                    events.extend(monitor.update(path_stats).into_iter().map(|kind| {
+                        QuinnetServerEvent::Congestion(CongestionEvent {
+                            id: *client_id,
+                            kind,
+                            path_stats,
+                        })
+                    }));
+                }
+                for (limit, bytes) in connection.bandwidth.check(&endpoint.bandwidth_limits, now) {
+                    events.push(QuinnetServerEvent::ClientBandwidthExceeded(
+                        ClientBandwidthExceededEvent {
+                            id: *client_id,
+                            limit,
+                            bytes,
+                        },
+                    ));
+                }
+                match connection.memory.check() {
+                    Some((budget, used)) if !connection.memory_exceeded => {
+                        connection.memory_exceeded = true;
+                        events.push(QuinnetServerEvent::MemoryBudgetExceeded(
+                            MemoryBudgetExceededEvent {
+                                id: *client_id,
+                                budget,
+                                used,
+                            },
+                        ));
+                        if budget.policy() == MemoryBudgetPolicy::Disconnect
+                            && !lost_clients.contains(client_id)
+                        {
+                            over_memory_clients.push(*client_id);
+                        }
+                    }
+                    Some(_) => (),
+                    None => connection.memory_exceeded = false,
+                }
+            }
+            for client_id in over_memory_clients {
+                if let Err(err) = endpoint.internal_disconnect_client(
+                    client_id,
+                    CloseReason::LocalOrder(CloseCode::MemoryBudgetExceeded),
+                    DisconnectReason::MemoryBudgetExceeded,
+                ) {
+                    error!(
+                        "Failed to properly disconnect client {}: {}",
+                        client_id, err
+                    );
+                }
+            }
+            if let Some(detection) = &endpoint.slow_client_detection {
+                let mut slow_clients = Vec::new();
+                for (client_id, connection) in endpoint.clients.iter_mut() {
+                    let pending_bytes = connection.pending_reliable_bytes();
+                    match connection.backlog.check(detection, pending_bytes, now) {
+                        Some(BacklogChange::Slow) => {
+                            events.push(QuinnetServerEvent::SlowClient(SlowClientEvent {
+                                id: *client_id,
+                                pending_bytes,
+                            }))
+                        }
+                        Some(BacklogChange::Recovered(slow_for)) => events.push(
+                            QuinnetServerEvent::SlowClientRecovered(SlowClientRecoveredEvent {
+                                id: *client_id,
+                                slow_for,
+                            }),
+                        ),
+                        Some(BacklogChange::Expired) if !lost_clients.contains(client_id) => {
+                            slow_clients.push(*client_id)
+                        }
+                        Some(BacklogChange::Expired) | None => (),
+                    }
+                }
+                for client_id in slow_clients {
+                    if let Err(err) = endpoint.internal_disconnect_client(
+                        client_id,
+                        CloseReason::LocalOrder(CloseCode::SlowClient),
+                        DisconnectReason::SlowClient,
+                    ) {
+                        error!(
+                            "Failed to properly disconnect client {}: {}",
+                            client_id, err
+                        );
+                    }
+                }
+            }
+            if let Some(idle_detection) = &endpoint.idle_detection {
+                let mut idle_clients = Vec::new();
+                for (client_id, connection) in endpoint.clients.iter_mut() {
+                    if let Some(idle_for) = connection.activity.check(idle_detection, now) {
+                        events.push(QuinnetServerEvent::ClientIdle(ClientIdleEvent {
+                            id: *client_id,
+                            idle_for,
+                        }));
+                        if idle_detection.kicks() && !lost_clients.contains(client_id) {
+                            idle_clients.push(*client_id);
+                        }
+                    }
+                }
+                for client_id in idle_clients {
+                    if let Err(err) = endpoint.internal_disconnect_client(
+                        client_id,
+                        CloseReason::LocalOrder(CloseCode::Idle),
+                        DisconnectReason::Idle,
+                    ) {
+                        error!(
+                            "Failed to properly disconnect client {}: {}",
+                            client_id, err
+                        );
+                    }
+                }
+            }
+            let mut transferred_clients = Vec::new();
+            let mut mismatched_clients = Vec::new();
+            let mut protocol_hashes = Vec::new();
+            for (client_id, connection) in endpoint.clients.iter_mut() {
+                for (channel_id, error) in connection.apply_conditions(now) {
+                    endpoint.send_failures.push(ClientSendFailedEvent {
+                        client_id: *client_id,
+                        channel_id,
+                        error,
+                    });
+                }
+                // Messages of the frame, see [`Endpoint::set_frame_coherent_receive`]. After the conditions, which hold the messages instead.
+                connection.bytes_from_client_recv.capture();
+                let acks = connection.bytes_from_client_recv.take_acks();
+                for ids in acks.chunks(MAX_ACKS_PER_CONTROL_MESSAGE) {
+                    if let Err(err) = connection.send_control(
+                        ControlMessage::Acks(ids.to_vec()),
+                        endpoint.buffer_pool.sibling(),
+                    ) {
+                        error!(
+                            "Failed to acknowledge the messages of client {}: {}",
+                            client_id, err
+                        );
+                    }
+                }
+                for payload in connection.bytes_from_client_recv.take_control() {
+                    let event = match ControlMessage::decode(&payload) {
+                        Some(ControlMessage::Acks(ids)) => {
+                            for (channel_id, message_id) in connection.acks.acknowledge(&ids) {
+                                events.push(QuinnetServerEvent::MessageAcked(MessageAckedEvent {
+                                    id: *client_id,
+                                    channel_id,
+                                    message_id,
+                                }));
+                            }
+                            continue;
+                        }
+                        Some(ControlMessage::ProtocolHash(client_hash)) => {
+                            protocol_hashes.push((*client_id, client_hash));
+                            continue;
+                        }
+                        Some(ControlMessage::ChannelProbe {
+                            channel_id,
+                            sequence,
+                        }) => {
+                            if let Err(err) = connection.send_control(
+                                ControlMessage::ChannelProbeAck {
+                                    channel_id,
+                                    sequence,
+                                },
+                                endpoint.buffer_pool.sibling(),
+                            ) {
+                                error!(
+                                    "Failed to answer the liveness probe of client {} on channel {}: {}",
+                                    client_id, channel_id, err
+                                );
+                            }
+                            continue;
+                        }
+                        Some(ControlMessage::ChannelProbeAck {
+                            channel_id,
+                            sequence,
+                        }) => {
+                            if let Some(Some(channel)) =
+                                connection.channels.get_mut(channel_id as usize)
+                            {
+                                channel.answer_probe(sequence, now);
+                            }
+                            continue;
+                        }
+                        Some(ControlMessage::ChannelOpened {
+                            channel_id,
+                            dictionary_id,
+                        }) => {
+                            connection.dictionaries.announced(channel_id, dictionary_id);
+                            continue;
+                        }
+                        Some(ControlMessage::ForwardedClient(header)) => {
+                            match connection.forward(endpoint.forwarding_key.as_ref(), &header) {
+                                Ok(client) => {
+                                    QuinnetServerEvent::ClientForwarded(ClientForwardedEvent {
+                                        id: *client_id,
+                                        client,
+                                    })
+                                }
+                                Err(err) => QuinnetServerEvent::ClientForwardingRejected(
+                                    ClientForwardingRejectedEvent {
+                                        id: *client_id,
+                                        err,
+                                    },
+                                ),
+                            }
+                        }
+                        message => {
+                            let result = match message {
+                                Some(ControlMessage::PresentToken(token)) => {
+                                    endpoint.transfer.validate(&token)
+                                }
+                                _ => Err(TransferTokenError::Malformed),
+                            };
+                            match result {
+                                Ok(ticket) => {
+                                    QuinnetServerEvent::ClientTransfer(ClientTransferEvent {
+                                        id: *client_id,
+                                        ticket,
+                                    })
+                                }
+                                Err(err) => QuinnetServerEvent::ClientTransferRejected(
+                                    ClientTransferRejectedEvent {
+                                        id: *client_id,
+                                        err,
+                                    },
+                                ),
+                            }
+                        }
+                    };
+                    events.push(event);
+                }
+                for announcement in connection.dictionaries.take_announcements() {
+                    if let Err(err) =
+                        connection.send_control(announcement, endpoint.buffer_pool.sibling())
+                    {
+                        error!(
+                            "Failed to announce a compressed channel to client {}: {}",
+                            client_id, err
+                        );
+                    }
+                }
+                for mismatch in connection.dictionaries.take_mismatches() {
+                    warn!(
+                        "Dictionary {:?} of client {} on channel {} does not match the dictionary {:?} of the server, closing the channel for this client",
+                        mismatch.peer_dictionary_id,
+                        client_id,
+                        mismatch.channel_id,
+                        mismatch.local_dictionary_id
+                    );
+                    if let Err(err) = connection.close_channel(mismatch.channel_id) {
+                        trace!(
+                            "Failed to close channel {} of client {}: {}",
+                            mismatch.channel_id,
+                            client_id,
+                            err
+                        );
+                    }
+                    events.push(QuinnetServerEvent::ChannelRejected(ChannelRejectedEvent {
+                        id: *client_id,
+                        channel_id: mismatch.channel_id,
+                        client_dictionary_id: mismatch.peer_dictionary_id,
+                        server_dictionary_id: mismatch.local_dictionary_id,
+                    }));
+                }
+                for channel in connection.channels.iter_mut().flatten() {
+                    if let Some(waited) = channel.poll_liveness(now) {
+                        events.push(QuinnetServerEvent::ChannelUnresponsive(
+                            ChannelUnresponsiveEvent {
+                                id: *client_id,
+                                channel_id: channel.id(),
+                                waited,
+                            },
+                        ));
+                    }
+                }
+                for (channel_id, message_id) in connection.acks.expire(now) {
+                    events.push(QuinnetServerEvent::MessageLost(MessageLostEvent {
+                        id: *client_id,
+                        channel_id,
+                        message_id,
+                    }));
+                }
+                if connection
+                    .transfer_deadline
+                    .is_some_and(|deadline| deadline <= now)
+                    && !lost_clients.contains(client_id)
+                {
+                    transferred_clients.push(*client_id);
+                }
+                if connection
+                    .mismatch_deadline
+                    .is_some_and(|deadline| deadline <= now)
+                    && !lost_clients.contains(client_id)
+                {
+                    mismatched_clients.push(*client_id);
+                }
+            }
+            endpoint.route_payloads();
+            for (client_id, client_hash) in protocol_hashes {
+                if !endpoint.protocol_check {
+                    continue;
+                }
+                let Some(server_hash) = endpoint
+                    .clients
+                    .get(&client_id)
+                    .and_then(|connection| endpoint.expected_protocol_hash(connection))
+                else {
+                    continue;
+                };
+                if client_hash == server_hash {
+                    continue;
+                }
+                warn!(
+                    "Protocol hash {:x} of client {} does not match the hash {:x} of the server, disconnecting it",
+                    client_hash, client_id, server_hash
+                );
+                events.push(QuinnetServerEvent::ProtocolMismatch(
+                    ProtocolMismatchEvent {
+                        id: client_id,
+                        client_hash,
+                        server_hash,
+                    },
+                ));
+                let buffer_pool = endpoint.buffer_pool.sibling();
+                if let Some(connection) = endpoint.clients.get_mut(&client_id) {
+                    // Closing right away could lose the notification, the client closes the connection once notified
+                    match connection.send_control(
+                        ControlMessage::ProtocolMismatch { server_hash },
+                        buffer_pool,
+                    ) {
+                        Ok(()) => {
+                            connection.mismatch_deadline =
+                                Some(Instant::now() + PROTOCOL_MISMATCH_CLOSE_DELAY)
+                        }
+                        Err(err) => {
+                            error!(
+                                "Failed to notify client {} of its protocol mismatch: {}",
+                                client_id, err
+                            );
+                            mismatched_clients.push(client_id);
+                        }
+                    }
+                }
+            }
+            for client_id in mismatched_clients {
+                if let Err(err) = endpoint.internal_disconnect_client(
+                    client_id,
+                    CloseReason::LocalOrder(CloseCode::ProtocolMismatch),
+                    DisconnectReason::ProtocolMismatch,
+                ) {
+                    error!(
+                        "Failed to properly disconnect client {}: {}",
+                        client_id, err
+                    );
+                }
+            }
+            for client_id in transferred_clients {
+                if let Err(err) = endpoint.internal_disconnect_client(
+                    client_id,
+                    CloseReason::LocalOrder(CloseCode::Transferred),
+                    DisconnectReason::Transferred,
+                ) {
+                    error!(
+                        "Failed to properly disconnect client {}: {}",
+                        client_id, err
+                    );
+                }
+            }
+            for client_id in lost_clients {
+                if let Err(err) = endpoint.internal_disconnect_client(
+                    client_id,
+                    CloseReason::LocalOrder(CloseCode::Closed),
+                    DisconnectReason::Error("Connection lost by a channel task".to_string()),
+                ) {
+                    error!(
+                        "Failed to properly disconnect client {}: {}",
+                        client_id, err
+                    );
+                }
+            }
+
+            for (id, reason) in std::mem::take(&mut endpoint.disconnected_clients) {
+                endpoint.run_disconnect_hook(id, &reason);
+                events.push(QuinnetServerEvent::ConnectionLost(ConnectionLostEvent {
+                    id,
+                    reason,
+                }));
+            }
+            events.extend(
+                endpoint
+                    .send_failures
+                    .drain(..)
+                    .map(QuinnetServerEvent::ClientSendFailed),
+            );
+            if let Some(status) = &endpoint.status {
+                status.set_players(endpoint.clients.len());
+            }
+        }
+        events
+    }
+}
+
+/// Returns true if `channel_id` is an unreliable channel, whose messages can be dropped by the [`ClientConditions`] of a client
+fn is_lossy(channels_configs: &SharedChannelConfigs, channel_id: ChannelId) -> bool {
+    channels_configs
+        .read()
+        .ok()
+        .and_then(|configs| {
+            configs
+                .get(&channel_id)
+                .map(|config| matches!(config.kind(), ChannelKind::Unreliable))
+        })
+        .unwrap_or(false)
+}
+
+/// Binds the sockets of the shards of an endpoint on `local_bind_addr`, sharing their port with `SO_REUSEPORT`
+fn bind_shard_sockets(
+    local_bind_addr: SocketAddr,
+    shards: NonZeroUsize,
+) -> Result<Vec<UdpSocket>, EndpointStartError> {
+    if shards == NonZeroUsize::MIN {
+        return Ok(vec![UdpSocket::bind(local_bind_addr)?]);
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut sockets = Vec::with_capacity(shards.get());
+        let mut bind_addr = local_bind_addr;
+        for _ in 0..shards.get() {
+            let socket = socket2::Socket::new(
+                socket2::Domain::for_address(bind_addr),
+                socket2::Type::DGRAM,
+                Some(socket2::Protocol::UDP),
+            )?;
+            socket.set_reuse_port(true)?;
+            socket.bind(&bind_addr.into())?;
+            let socket: UdpSocket = socket.into();
+            // The next shards bind on the port assigned to the first one
+            bind_addr = socket.local_addr()?;
+            sockets.push(socket);
+        }
+        Ok(sockets)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    Err(EndpointStartError::ShardingUnsupported)
+}
+
+/// QUIC configuration of the endpoints started with a [`ServerCertificate`]
+fn server_config(
+    server_cert: &ServerCertificate,
+    alpn_protocols: Vec<Vec<u8>>,
+    config: &ServerEndpointConfiguration,
+) -> Result<ServerConfig, EndpointStartError> {
+    let crypto = match &config.tls_config {
+        Some(tls_config) => {
+            let mut crypto = rustls::ServerConfig::clone(tls_config);
+            if crypto.alpn_protocols.is_empty() {
+                crypto.alpn_protocols = alpn_protocols;
+            }
+            QuicServerConfig::try_from(crypto)?
+        }
+        None => {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+                .with_protocol_versions(&[&rustls::version::TLS13])?;
+            let builder = match &config.client_authentication {
+                Some(client_authentication) => {
+                    builder.with_client_cert_verifier(client_authentication.verifier(provider)?)
+                }
+                None => builder.with_no_client_auth(),
+            };
+            let mut crypto = builder.with_single_cert(
+                server_cert.cert_chain.clone(),
+                server_cert.priv_key.clone_key(),
+            )?;
+            // Same as quinn's defaults, 0-RTT data accepted
+            crypto.max_early_data_size = u32::MAX;
+            crypto.alpn_protocols = alpn_protocols;
+            if config.key_log {
+                crypto.key_log = Arc::new(rustls::KeyLogFile::new());
+            }
+            QuicServerConfig::try_from(crypto).expect(
+                "TLS 1.3 with the ring provider should provide the QUIC initial cipher suite",
+            )
+        }
+    };
+
+    let mut endpoint_config = ServerConfig::with_crypto(Arc::new(crypto));
+    let transport = Arc::get_mut(&mut endpoint_config.transport)
+        .ok_or(EndpointStartError::LockAcquisitionFailure)?;
+    transport.keep_alive_interval(Some(DEFAULT_KEEP_ALIVE_INTERVAL_S));
+    config.socket.configure_transport(transport);
+    Ok(endpoint_config)
 }
 
-async fn endpoint_task(
+/// Creates the quinn endpoint on `socket`, once its external address is queried if a STUN server is configured
+async fn create_quinn_endpoint(
     socket: UdpSocket,
+    release: SocketRelease,
+    stun_server: Option<SocketAddr>,
+    qos: QosConfiguration,
+    socket_config: SocketConfiguration,
     endpoint_config: ServerConfig,
     to_sync_endpoint_send: mpsc::Sender<ServerAsyncMessage>,
-    mut endpoint_close_recv: broadcast::Receiver<()>,
-) {
-    let endpoint = QuinnEndpoint::new(
-        EndpointConfig::default(),
+) -> QuinnEndpoint {
+    let socket = match stun_server {
+        Some(stun_server) => {
+            let (socket, result) = tokio::task::spawn_blocking(move || {
+                let result = query_external_address(
+                    &socket,
+                    stun_server,
+                    DEFAULT_STUN_TIMEOUT,
+                    DEFAULT_STUN_ATTEMPTS,
+                );
+                (socket, result)
+            })
+            .await
+            .expect("STUN query task should not panic");
+            match result {
+                Ok(external_addr) => {
+                    info!("Endpoint external address: {}", external_addr);
+                    let _ = to_sync_endpoint_send
+                        .send(ServerAsyncMessage::ExternalAddressDiscovered(external_addr))
+                        .await;
+                }
+                Err(err) => warn!(
+                    "Failed to discover the external address with STUN server {}: {}",
+                    stun_server, err
+                ),
+            }
+            socket
+        }
+        None => socket,
+    };
+
+    let runtime = default_runtime().expect("async runtime should be valid");
+    QuinnEndpoint::new_with_abstract_socket(
+        socket_config.endpoint_config(),
         Some(endpoint_config),
-        socket,
-        default_runtime().expect("async runtime should be valid"),
+        socket_config
+            .wrap_socket(socket, &qos, &runtime)
+            .and_then(|socket| release.wrap(socket))
+            .expect("should wrap the endpoint socket"),
+        runtime,
     )
-    .expect("should create quinn endpoint");
+    .expect("should create quinn endpoint")
+}
 
+/// Accepts the incoming connections of `endpoint` until the endpoint is closed or stopped
+///
+/// Each handshake runs in its own task, a slow or stalled handshake does not hold back the following incoming connections.
+async fn accept_task(
+    endpoint: QuinnEndpoint,
+    routing: Option<ProtocolRouting>,
+    status: Option<Arc<StatusState>>,
+    handling: ConnectionHandling,
+    mut endpoint_close_recv: broadcast::Receiver<()>,
+    accepting: Arc<AtomicBool>,
+) {
     // Handle incoming connections/clients.
     tokio::select! {
         _ = endpoint_close_recv.recv() => {
             trace!("Endpoint incoming connection handler received a request to close")
         }
         _ = async {
-            while let Some(connecting) = endpoint.accept().await {
-                match connecting.await {
-                    Err(err) => error!("An incoming connection failed: {}", err),
-                    Ok(connection) => {
-                        let to_sync_endpoint_send = to_sync_endpoint_send.clone();
-                        tokio::spawn(async move {
-                            client_connection_task(
-                                connection,
-                                to_sync_endpoint_send
-                            )
-                            .await
-                        });
-                    },
+            while let Some(incoming) = endpoint.accept().await {
+                // With a status responder or a protocol routing, the connections of the other services are accepted anyway
+                if status.is_none() && routing.is_none() && !accepting.load(Ordering::Relaxed) {
+                    debug!(
+                        "Refused an incoming connection from {}: endpoint is not accepting new connections",
+                        incoming.remote_address()
+                    );
+                    handling.attempt(incoming.remote_address());
+                    handling.handshakes.refuse(RefusalReason::NotAccepting);
+                    // The handshake completes so that the client receives the close code
+                    tokio::spawn(async move {
+                        if let Ok(connection) = incoming.await {
+                            TransportConnection::close(&connection, CloseCode::NotAccepting);
+                        }
+                    });
+                    continue;
                 }
+                if handling.address_validation && !incoming.remote_address_validated() && incoming.may_retry() {
+                    trace!("Validating the address of an incoming connection from {}", incoming.remote_address());
+                    if incoming.retry().is_ok() {
+                        handling.handshakes.retries_sent.fetch_add(1, Ordering::Relaxed);
+                    }
+                    continue;
+                }
+                handling.attempt(incoming.remote_address());
+                tokio::spawn(handshake_task(
+                    incoming,
+                    routing.clone(),
+                    status.clone(),
+                    handling.clone(),
+                    accepting.clone(),
+                ));
             }
         } => {}
     }
 }
 
-async fn client_connection_task(
-    connection_handle: quinn::Connection,
+/// Completes the handshake of an incoming connection, then hands it to its service
+async fn handshake_task(
+    incoming: quinn::Incoming,
+    routing: Option<ProtocolRouting>,
+    status: Option<Arc<StatusState>>,
+    handling: ConnectionHandling,
+    accepting: Arc<AtomicBool>,
+) {
+    let remote_addr = incoming.remote_address();
+    let pending = PendingHandshake::new(handling.handshakes.clone());
+    let connection = match incoming.await {
+        Err(err) => {
+            error!("An incoming connection failed: {}", err);
+            handling.handshakes.failed.fetch_add(1, Ordering::Relaxed);
+            // Not reported while the sync side lags behind
+            let _ = handling
+                .to_sync_endpoint_send
+                .try_send(ServerAsyncMessage::HandshakeFailed(remote_addr, err));
+            return;
+        }
+        Ok(connection) => connection,
+    };
+    drop(pending);
+    let protocol = connection
+        .handshake_data()
+        .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+        .and_then(|data| data.protocol);
+    if let Some(status) = &status {
+        if protocol.as_deref() == Some(status.alpn()) {
+            status_connection_task(connection, status.clone()).await;
+            return;
+        }
+    }
+    if let Some(routing) = &routing {
+        if !routing.routes_to_quinnet(protocol.as_ref()) {
+            if !routing.forward(connection) {
+                handling.handshakes.refuse(RefusalReason::NoService);
+            }
+            return;
+        }
+    }
+    if !accepting.load(Ordering::Relaxed) {
+        debug!(
+            "Refused a connection from {}: endpoint is not accepting new connections",
+            connection.remote_address()
+        );
+        TransportConnection::close(&connection, CloseCode::NotAccepting);
+        handling.handshakes.refuse(RefusalReason::NotAccepting);
+        return;
+    }
+    client_connection_task(connection, handling).await;
+}
+
+/// Handling of the connections accepted by an endpoint
+#[derive(Debug, Clone)]
+struct ConnectionHandling {
     to_sync_endpoint_send: mpsc::Sender<ServerAsyncMessage>,
+    hardening: HardeningConfiguration,
+    /// Shard of the endpoint accepting the connections, see [`ServerEndpointConfiguration::with_shards`]
+    shard: usize,
+    handshakes: Arc<HandshakeCounters>,
+    /// See [`ServerEndpointConfiguration::with_address_validation`]
+    address_validation: bool,
+}
+
+impl ConnectionHandling {
+    /// Signals an incoming connection to the sync side, see [`ConnectionAttemptEvent`]
+    fn attempt(&self, remote_addr: SocketAddr) {
+        // Not reported while the sync side lags behind, the accept loop must not wait
+        let _ = self
+            .to_sync_endpoint_send
+            .try_send(ServerAsyncMessage::ConnectionAttempt(remote_addr));
+    }
+}
+
+async fn client_connection_task<C: TransportConnection>(
+    connection_handle: C,
+    handling: ConnectionHandling,
 ) {
+    let ConnectionHandling {
+        to_sync_endpoint_send,
+        hardening,
+        shard,
+        ..
+    } = handling;
     let (client_close_send, client_close_recv) =
         broadcast::channel(DEFAULT_KILL_MESSAGE_QUEUE_SIZE);
     let (bytes_from_client_send, bytes_from_client_recv) =
-        mpsc::channel::<(ChannelId, Bytes)>(DEFAULT_MESSAGE_QUEUE_SIZE);
+        incoming_channel(DEFAULT_MESSAGE_QUEUE_SIZE);
     let (to_connection_send, mut from_sync_server_recv) =
         mpsc::channel::<ServerSyncMessage>(DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE);
     let (from_channels_send, from_channels_recv) =
         mpsc::channel::<ChannelAsyncMessage>(DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE);
     let (to_channels_send, to_channels_recv) =
         mpsc::channel::<ChannelSyncMessage>(DEFAULT_QCHANNEL_MESSAGES_CHANNEL_SIZE);
+    let channels_configs: SharedChannelConfigs = Arc::new(RwLock::new(HashMap::new()));
 
     // Signal the sync server of this new connection
-    to_sync_endpoint_send
-        .send(ServerAsyncMessage::ClientConnected(
+    let signaled = to_sync_endpoint_send
+        .send(ServerAsyncMessage::ClientConnected(Box::new(
             ServerSideConnection::new(
-                connection_handle.clone(),
+                Arc::new(connection_handle.clone()),
+                channels_configs.clone(),
                 bytes_from_client_recv,
                 client_close_send.clone(),
                 to_connection_send,
                 from_channels_recv,
                 to_channels_send,
-            ),
-        ))
-        .await
-        .expect("Failed to signal connection to sync client");
+            )
+            .on_shard(shard),
+        )))
+        .await;
+    if signaled.is_err() {
+        // The endpoint was dropped
+        TransportConnection::close(&connection_handle, CloseCode::ServerShutdown);
+        return;
+    }
 
     // Wait for the sync server response before spawning connection tasks.
     match from_sync_server_recv.recv().await {
         Some(ServerSyncMessage::ClientConnectedAck(client_id)) => {
             info!(
                 "New connection from {}, client_id: {}",
-                connection_handle.remote_address(),
+                display_remote(&connection_handle),
                 client_id
             );
 
@@ -1228,15 +4695,15 @@ async fn client_connection_task(
                 let conn = connection_handle.clone();
                 let to_sync_server = to_sync_endpoint_send.clone();
                 tokio::spawn(async move {
-                    let _conn_err = conn.closed().await;
-                    info!("Connection {} closed: {}", client_id, _conn_err);
-                    // If we requested the connection to close, channel may have been closed already.
-                    if !to_sync_server.is_closed() {
-                        to_sync_server
-                            .send(ServerAsyncMessage::ClientConnectionClosed(client_id))
-                            .await
-                            .expect("Failed to signal connection lost in async connection");
-                    }
+                    let conn_err = conn.closed().await;
+                    info!("Connection {} closed: {}", client_id, conn_err);
+                    // If we requested the connection to close, or the endpoint was dropped, the channel is closed already.
+                    let _ = to_sync_server
+                        .send(ServerAsyncMessage::ClientConnectionClosed(
+                            client_id,
+                            DisconnectReason::from_transport_error(&conn_err),
+                        ))
+                        .await;
                 });
             };
 
@@ -1245,76 +4712,284 @@ async fn client_connection_task(
                 client_id,
                 client_close_recv.resubscribe(),
                 bytes_from_client_send,
+                channels_configs,
+                ReceiveHardening::new(&hardening, from_channels_send.clone()),
+                // The clients do not stamp their payloads with a tick
+                SharedNetworkTick::default(),
             );
 
             spawn_send_channels_tasks_spawner(
                 connection_handle,
+                client_id,
                 client_close_recv,
                 to_channels_recv,
                 from_channels_send,
+                Box::new(move |stage, elapsed| {
+                    let _ = to_sync_endpoint_send.try_send(ServerAsyncMessage::ClientCloseStage(
+                        client_id, stage, elapsed,
+                    ));
+                }),
             );
         }
         _ => info!(
             "Connection from {} refused",
-            connection_handle.remote_address()
+            display_remote(&connection_handle)
         ),
     }
 }
 
+/// Writers of the events of the clients connections, see [`update_sync_server`]
+#[derive(SystemParam)]
+pub struct ConnectionEventWriters<'w> {
+    connection_attempt: EventWriter<'w, ConnectionAttemptEvent>,
+    handshake_failed: EventWriter<'w, HandshakeFailedEvent>,
+    connection: EventWriter<'w, ConnectionEvent>,
+    connection_lost: EventWriter<'w, ConnectionLostEvent>,
+    close_stage: EventWriter<'w, ClientCloseStageEvent>,
+    disconnect_hook_completed: EventWriter<'w, DisconnectHookCompletedEvent>,
+}
+
+/// Writers of the events of the endpoint lifecycle, see [`update_sync_server`]
+#[derive(SystemParam)]
+pub struct EndpointEventWriters<'w> {
+    started: EventWriter<'w, EndpointStartedEvent>,
+    stopped: EventWriter<'w, EndpointStoppedEvent>,
+    external_address: EventWriter<'w, ExternalAddressDiscoveredEvent>,
+    #[cfg(feature = "port-mapping")]
+    port_mapping_succeeded: EventWriter<'w, PortMappingSucceededEvent>,
+    #[cfg(feature = "port-mapping")]
+    port_mapping_failed: EventWriter<'w, PortMappingFailedEvent>,
+}
+
+/// Writers of the events of the delivery of the messages sent to the clients, see [`update_sync_server`]
+#[derive(SystemParam)]
+pub struct DeliveryEventWriters<'w> {
+    send_failed: EventWriter<'w, ClientSendFailedEvent>,
+    message_acked: EventWriter<'w, MessageAckedEvent>,
+    message_lost: EventWriter<'w, MessageLostEvent>,
+    channel_resumed: EventWriter<'w, ChannelResumedEvent>,
+    channel_error: EventWriter<'w, ChannelErrorEvent>,
+    channel_unresponsive: EventWriter<'w, ChannelUnresponsiveEvent>,
+    channel_rejected: EventWriter<'w, ChannelRejectedEvent>,
+}
+
+/// Writers of the events raised by the checks of the clients traffic, see [`update_sync_server`]
+#[derive(SystemParam)]
+pub struct ClientChecksEventWriters<'w> {
+    protocol_violation: EventWriter<'w, ProtocolViolationEvent>,
+    protocol_mismatch: EventWriter<'w, ProtocolMismatchEvent>,
+    idle: EventWriter<'w, ClientIdleEvent>,
+    bandwidth_exceeded: EventWriter<'w, ClientBandwidthExceededEvent>,
+    congestion: EventWriter<'w, CongestionEvent>,
+    max_datagram_size_changed: EventWriter<'w, MaxDatagramSizeChangedEvent>,
+    memory_budget_exceeded: EventWriter<'w, MemoryBudgetExceededEvent>,
+    slow_client: EventWriter<'w, SlowClientEvent>,
+    slow_client_recovered: EventWriter<'w, SlowClientRecoveredEvent>,
+}
+
+/// Writers of the events of the clients transferred from other servers or forwarded by a gateway, see [`update_sync_server`]
+#[derive(SystemParam)]
+pub struct ClientHandoverEventWriters<'w> {
+    transfer: EventWriter<'w, ClientTransferEvent>,
+    transfer_rejected: EventWriter<'w, ClientTransferRejectedEvent>,
+    forwarded: EventWriter<'w, ClientForwardedEvent>,
+    forwarding_rejected: EventWriter<'w, ClientForwardingRejectedEvent>,
+}
+
 /// Receive messages from the async server tasks and update the sync server.
 ///
 /// This system generates the server's bevy events
 pub fn update_sync_server(
     mut server: ResMut<QuinnetServer>,
-    mut connection_events: EventWriter<ConnectionEvent>,
-    mut connection_lost_events: EventWriter<ConnectionLostEvent>,
+    mut connection_events: ConnectionEventWriters,
+    mut delivery_events: DeliveryEventWriters,
+    mut client_checks_events: ClientChecksEventWriters,
+    mut client_handover_events: ClientHandoverEventWriters,
+    mut endpoint_events: EndpointEventWriters,
 ) {
-    if let Some(endpoint) = server.get_endpoint_mut() {
-        while let Ok(message) = endpoint.from_async_endpoint_recv.try_recv() {
-            match message {
-                ServerAsyncMessage::ClientConnected(connection) => {
-                    match endpoint.handle_connection(connection) {
-                        Ok(client_id) => {
-                            endpoint.stats.connect_count += 1;
-                            connection_events.write(ConnectionEvent { id: client_id });
-                        }
-                        Err(_) => {
-                            error!("Failed to handle connection of a client, already disconnected");
-                        }
-                    };
-                }
-                ServerAsyncMessage::ClientConnectionClosed(client_id) => {
-                    match endpoint.clients.contains_key(&client_id) {
-                        true => {
-                            endpoint.stats.disconnect_count += 1;
-                            endpoint.try_disconnect_closed_client(client_id);
-                            connection_lost_events.write(ConnectionLostEvent { id: client_id });
-                        }
-                        false => (),
-                    }
-                }
+    for event in server.pump() {
+        match event {
+            QuinnetServerEvent::ConnectionAttempt(event) => {
+                connection_events.connection_attempt.write(event);
             }
-        }
-
-        let mut lost_clients = HashSet::new();
-        for (client_id, connection) in endpoint.clients.iter_mut() {
-            while let Ok(message) = connection.from_channels_recv.try_recv() {
-                match message {
-                    ChannelAsyncMessage::LostConnection => {
-                        if !lost_clients.contains(client_id) {
-                            lost_clients.insert(*client_id);
-                            connection_lost_events.write(ConnectionLostEvent { id: *client_id });
-                        }
-                    }
-                }
+            QuinnetServerEvent::HandshakeFailed(event) => {
+                connection_events.handshake_failed.write(event);
+            }
+            QuinnetServerEvent::Connection(event) => {
+                connection_events.connection.write(event);
+            }
+            QuinnetServerEvent::ConnectionLost(event) => {
+                connection_events.connection_lost.write(event);
+            }
+            QuinnetServerEvent::ClientCloseStage(event) => {
+                connection_events.close_stage.write(event);
+            }
+            QuinnetServerEvent::DisconnectHookCompleted(event) => {
+                connection_events.disconnect_hook_completed.write(event);
+            }
+            QuinnetServerEvent::ClientSendFailed(event) => {
+                delivery_events.send_failed.write(event);
+            }
+            QuinnetServerEvent::MessageAcked(event) => {
+                delivery_events.message_acked.write(event);
+            }
+            QuinnetServerEvent::MessageLost(event) => {
+                delivery_events.message_lost.write(event);
+            }
+            QuinnetServerEvent::ChannelResumed(event) => {
+                delivery_events.channel_resumed.write(event);
+            }
+            QuinnetServerEvent::ChannelUnresponsive(event) => {
+                delivery_events.channel_unresponsive.write(event);
+            }
+            QuinnetServerEvent::ChannelRejected(event) => {
+                delivery_events.channel_rejected.write(event);
+            }
+            QuinnetServerEvent::ChannelError(event) => {
+                delivery_events.channel_error.write(event);
+            }
+            QuinnetServerEvent::ProtocolMismatch(event) => {
+                client_checks_events.protocol_mismatch.write(event);
+            }
+            QuinnetServerEvent::ProtocolViolation(event) => {
+                client_checks_events.protocol_violation.write(event);
+            }
+            QuinnetServerEvent::ClientIdle(event) => {
+                client_checks_events.idle.write(event);
+            }
+            QuinnetServerEvent::ClientBandwidthExceeded(event) => {
+                client_checks_events.bandwidth_exceeded.write(event);
+            }
+            QuinnetServerEvent::Congestion(event) => {
+                client_checks_events.congestion.write(event);
+            }
+            QuinnetServerEvent::MaxDatagramSizeChanged(event) => {
+                client_checks_events.max_datagram_size_changed.write(event);
+            }
+            QuinnetServerEvent::MemoryBudgetExceeded(event) => {
+                client_checks_events.memory_budget_exceeded.write(event);
+            }
+            QuinnetServerEvent::SlowClient(event) => {
+                client_checks_events.slow_client.write(event);
+            }
+            QuinnetServerEvent::SlowClientRecovered(event) => {
+                client_checks_events.slow_client_recovered.write(event);
+            }
+            QuinnetServerEvent::ClientTransfer(event) => {
+                client_handover_events.transfer.write(event);
+            }
+            QuinnetServerEvent::ClientTransferRejected(event) => {
+                client_handover_events.transfer_rejected.write(event);
+            }
+            QuinnetServerEvent::ClientForwarded(event) => {
+                client_handover_events.forwarded.write(event);
+            }
+            QuinnetServerEvent::ClientForwardingRejected(event) => {
+                client_handover_events.forwarding_rejected.write(event);
+            }
+            QuinnetServerEvent::EndpointStarted(event) => {
+                endpoint_events.started.write(event);
+            }
+            QuinnetServerEvent::EndpointStopped(event) => {
+                endpoint_events.stopped.write(event);
+            }
+            QuinnetServerEvent::ExternalAddressDiscovered(event) => {
+                endpoint_events.external_address.write(event);
+            }
+            #[cfg(feature = "port-mapping")]
+            QuinnetServerEvent::PortMappingSucceeded(event) => {
+                endpoint_events.port_mapping_succeeded.write(event);
+            }
+            #[cfg(feature = "port-mapping")]
+            QuinnetServerEvent::PortMappingFailed(event) => {
+                endpoint_events.port_mapping_failed.write(event);
             }
-        }
-        for client_id in lost_clients {
-            endpoint.try_disconnect_client(client_id);
         }
     }
 }
 
+/// Event produced by [`QuinnetServer::pump`]. [`update_sync_server`] raises each of them as the bevy event it wraps.
+pub enum QuinnetServerEvent {
+    /// See [`ConnectionAttemptEvent`]
+    ConnectionAttempt(ConnectionAttemptEvent),
+    /// See [`HandshakeFailedEvent`]
+    HandshakeFailed(HandshakeFailedEvent),
+    /// See [`ConnectionEvent`]
+    Connection(ConnectionEvent),
+    /// See [`ConnectionLostEvent`]
+    ConnectionLost(ConnectionLostEvent),
+    /// See [`ClientCloseStageEvent`]
+    ClientCloseStage(ClientCloseStageEvent),
+    /// See [`DisconnectHookCompletedEvent`]
+    DisconnectHookCompleted(DisconnectHookCompletedEvent),
+    /// See [`ClientSendFailedEvent`]
+    ClientSendFailed(ClientSendFailedEvent),
+    /// See [`MessageAckedEvent`]
+    MessageAcked(MessageAckedEvent),
+    /// See [`MessageLostEvent`]
+    MessageLost(MessageLostEvent),
+    /// See [`ChannelResumedEvent`]
+    ChannelResumed(ChannelResumedEvent),
+    /// See [`ChannelErrorEvent`]
+    ChannelError(ChannelErrorEvent),
+    /// See [`ChannelUnresponsiveEvent`]
+    ChannelUnresponsive(ChannelUnresponsiveEvent),
+    /// See [`ChannelRejectedEvent`]
+    ChannelRejected(ChannelRejectedEvent),
+    /// See [`ProtocolViolationEvent`]
+    ProtocolViolation(ProtocolViolationEvent),
+    /// See [`ProtocolMismatchEvent`]
+    ProtocolMismatch(ProtocolMismatchEvent),
+    /// See [`ClientIdleEvent`]
+    ClientIdle(ClientIdleEvent),
+    /// See [`ClientBandwidthExceededEvent`]
+    ClientBandwidthExceeded(ClientBandwidthExceededEvent),
+    /// See [`CongestionEvent`]
+    Congestion(CongestionEvent),
+    /// See [`MaxDatagramSizeChangedEvent`]
+    MaxDatagramSizeChanged(MaxDatagramSizeChangedEvent),
+    /// See [`MemoryBudgetExceededEvent`]
+    MemoryBudgetExceeded(MemoryBudgetExceededEvent),
+    /// See [`SlowClientEvent`]
+    SlowClient(SlowClientEvent),
+    /// See [`SlowClientRecoveredEvent`]
+    SlowClientRecovered(SlowClientRecoveredEvent),
+    /// See [`ClientTransferEvent`]
+    ClientTransfer(ClientTransferEvent),
+    /// See [`ClientTransferRejectedEvent`]
+    ClientTransferRejected(ClientTransferRejectedEvent),
+    /// See [`ClientForwardedEvent`]
+    ClientForwarded(ClientForwardedEvent),
+    /// See [`ClientForwardingRejectedEvent`]
+    ClientForwardingRejected(ClientForwardingRejectedEvent),
+    /// See [`EndpointStartedEvent`]
+    EndpointStarted(EndpointStartedEvent),
+    /// See [`EndpointStoppedEvent`]
+    EndpointStopped(EndpointStoppedEvent),
+    /// See [`ExternalAddressDiscoveredEvent`]
+    ExternalAddressDiscovered(ExternalAddressDiscoveredEvent),
+    /// See [`PortMappingSucceededEvent`]
+    #[cfg(feature = "port-mapping")]
+    PortMappingSucceeded(PortMappingSucceededEvent),
+    /// See [`PortMappingFailedEvent`]
+    #[cfg(feature = "port-mapping")]
+    PortMappingFailed(PortMappingFailedEvent),
+}
+
+/// Sends the messages held by the server endpoint, see [`Endpoint::set_deferred_flush`]
+pub fn flush_sync_server(server: Res<QuinnetServer>) {
+    if let Some(endpoint) = server.get_endpoint() {
+        endpoint.flush();
+    }
+}
+
+/// Stops the endpoint of the server when the app exits, so that the clients are notified before the [`AsyncRuntime`] is dropped. Runs in the [`QuinnetShutdown`] set.
+pub fn shutdown_sync_server(mut server: ResMut<QuinnetServer>) {
+    if server.is_listening() {
+        server.try_stop_endpoint();
+    }
+}
+
 /// Quinnet Server's plugin
 ///
 /// It is possbile to add both this plugin and the [`crate::client::QuinnetClientPlugin`]
@@ -1323,30 +4998,104 @@ pub struct QuinnetServerPlugin {
     /// Server systems are scheduled to only run if the `Server` resource exists.
     /// A Bevy command to create the resource `commands.init_resource::<Server>();` can be done later on, when needed.
     pub initialize_later: bool,
+    /// Schedule of the [`QuinnetSyncUpdate`] set, where the server receives from its async back-end and raises its events. `PreUpdate` by default, `FixedPreUpdate` aligns message intake with the fixed tick.
+    pub sync_schedule: InternedScheduleLabel,
+    /// Schedule of the [`QuinnetFlush`] set, where the deferred messages are sent. `PostUpdate` by default.
+    pub flush_schedule: InternedScheduleLabel,
+    /// Enables the deferred flush of the server initialized by the plugin, see [`Endpoint::set_deferred_flush`]
+    pub deferred_flush: bool,
 }
 
 impl Default for QuinnetServerPlugin {
     fn default() -> Self {
         Self {
             initialize_later: false,
+            sync_schedule: PreUpdate.intern(),
+            flush_schedule: PostUpdate.intern(),
+            deferred_flush: false,
         }
     }
 }
 
+impl QuinnetServerPlugin {
+    /// Runs the [`QuinnetSyncUpdate`] set in `schedule`
+    pub fn with_sync_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.sync_schedule = schedule.intern();
+        self
+    }
+
+    /// Runs the [`QuinnetFlush`] set in `schedule`
+    pub fn with_flush_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.flush_schedule = schedule.intern();
+        self
+    }
+
+    /// Holds the messages sent by the server until the [`QuinnetFlush`] set runs
+    pub fn with_deferred_flush(mut self) -> Self {
+        self.deferred_flush = true;
+        self
+    }
+}
+
 impl Plugin for QuinnetServerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<ConnectionEvent>()
-            .add_event::<ConnectionLostEvent>();
+        app.add_event::<ConnectionAttemptEvent>()
+            .add_event::<HandshakeFailedEvent>()
+            .add_event::<ConnectionEvent>()
+            .add_event::<ConnectionLostEvent>()
+            .add_event::<ClientCloseStageEvent>()
+            .add_event::<DisconnectHookCompletedEvent>()
+            .add_event::<ClientSendFailedEvent>()
+            .add_event::<MessageAckedEvent>()
+            .add_event::<MessageLostEvent>()
+            .add_event::<ChannelResumedEvent>()
+            .add_event::<ChannelErrorEvent>()
+            .add_event::<ChannelUnresponsiveEvent>()
+            .add_event::<ChannelRejectedEvent>()
+            .add_event::<ProtocolViolationEvent>()
+            .add_event::<ProtocolMismatchEvent>()
+            .add_event::<ClientIdleEvent>()
+            .add_event::<ClientBandwidthExceededEvent>()
+            .add_event::<CongestionEvent>()
+            .add_event::<MaxDatagramSizeChangedEvent>()
+            .add_event::<MemoryBudgetExceededEvent>()
+            .add_event::<SlowClientEvent>()
+            .add_event::<SlowClientRecoveredEvent>()
+            .add_event::<ClientTransferEvent>()
+            .add_event::<ClientTransferRejectedEvent>()
+            .add_event::<ClientForwardedEvent>()
+            .add_event::<ClientForwardingRejectedEvent>()
+            .add_event::<EndpointStartedEvent>()
+            .add_event::<EndpointStoppedEvent>()
+            .add_event::<ExternalAddressDiscoveredEvent>();
+        #[cfg(feature = "port-mapping")]
+        app.add_event::<PortMappingSucceededEvent>()
+            .add_event::<PortMappingFailedEvent>();
 
         if !self.initialize_later {
             app.init_resource::<QuinnetServer>();
+            app.world_mut()
+                .resource_mut::<QuinnetServer>()
+                .set_deferred_flush(self.deferred_flush);
         }
 
         app.add_systems(
-            PreUpdate,
+            self.sync_schedule,
             update_sync_server
                 .in_set(QuinnetSyncUpdate)
                 .run_if(resource_exists::<QuinnetServer>),
+        )
+        .add_systems(
+            self.flush_schedule,
+            flush_sync_server
+                .in_set(QuinnetFlush)
+                .run_if(resource_exists::<QuinnetServer>),
+        )
+        .add_systems(
+            Last,
+            shutdown_sync_server
+                .in_set(QuinnetShutdown)
+                .run_if(resource_exists::<QuinnetServer>.and(on_event::<AppExit>)),
         );
     }
 }
@@ -0,0 +1,177 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+
+use crate::shared::ClientId;
+
+/// Default duration of the bandwidth history kept for each client, extended to the longest window of the [`BandwidthLimit`]s
+pub const DEFAULT_BANDWIDTH_HISTORY: Duration = Duration::from_secs(10);
+
+/// Resolution of the bandwidth history: bytes are accounted in buckets of this duration, bounding the memory use of the history
+pub const BANDWIDTH_BUCKET_DURATION: Duration = Duration::from_millis(100);
+
+/// Direction of the traffic of a client, as seen from the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BandwidthDirection {
+    /// Bytes received from the client
+    Inbound,
+    /// Bytes sent to the client
+    Outbound,
+}
+
+/// Maximum number of bytes exchanged with a client over a rolling time window, see [`crate::server::Endpoint::set_bandwidth_limits`].
+///
+/// Crossing a limit only raises a [`ClientBandwidthExceededEvent`], throttling or disconnecting the client is up to the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BandwidthLimit {
+    /// Direction of the limited traffic
+    pub direction: BandwidthDirection,
+    /// Maximum number of bytes over `window`
+    pub max_bytes: u64,
+    /// Duration of the rolling window
+    pub window: Duration,
+}
+
+impl BandwidthLimit {
+    /// Limits the bytes received from a client to `max_bytes` per `window`
+    pub fn inbound(max_bytes: u64, window: Duration) -> Self {
+        Self {
+            direction: BandwidthDirection::Inbound,
+            max_bytes,
+            window,
+        }
+    }
+
+    /// Limits the bytes sent to a client to `max_bytes` per `window`
+    pub fn outbound(max_bytes: u64, window: Duration) -> Self {
+        Self {
+            direction: BandwidthDirection::Outbound,
+            max_bytes,
+            window,
+        }
+    }
+}
+
+/// Bytes exchanged with a client over a time window
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BandwidthUsage {
+    /// Bytes received from the client
+    pub inbound_bytes: u64,
+    /// Bytes sent to the client
+    pub outbound_bytes: u64,
+}
+
+impl BandwidthUsage {
+    /// Bytes exchanged in `direction`
+    pub fn bytes(&self, direction: BandwidthDirection) -> u64 {
+        match direction {
+            BandwidthDirection::Inbound => self.inbound_bytes,
+            BandwidthDirection::Outbound => self.outbound_bytes,
+        }
+    }
+}
+
+/// Raised when the traffic of a client crosses one of the [`BandwidthLimit`]s of the endpoint. Raised again once the traffic went back under the limit and crosses it again. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ClientBandwidthExceededEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// Limit crossed by the client
+    pub limit: BandwidthLimit,
+    /// Bytes exchanged with the client over the window of the limit
+    pub bytes: u64,
+}
+
+/// Bytes exchanged during [`BANDWIDTH_BUCKET_DURATION`]
+#[derive(Debug)]
+struct Bucket {
+    start: Instant,
+    inbound: u64,
+    outbound: u64,
+}
+
+/// Rolling history of the bytes exchanged with a client
+#[derive(Debug, Default)]
+pub(crate) struct BandwidthTracker {
+    /// Oldest first
+    buckets: VecDeque<Bucket>,
+    /// Whether each limit of the endpoint is currently exceeded
+    exceeded: Vec<bool>,
+}
+
+impl BandwidthTracker {
+    pub(crate) fn record_inbound(&mut self, bytes: usize) {
+        self.current_bucket().inbound += bytes as u64;
+    }
+
+    pub(crate) fn record_outbound(&mut self, bytes: usize) {
+        self.current_bucket().outbound += bytes as u64;
+    }
+
+    fn current_bucket(&mut self) -> &mut Bucket {
+        let now = Instant::now();
+        match self.buckets.back() {
+            Some(last) if now.saturating_duration_since(last.start) < BANDWIDTH_BUCKET_DURATION => {
+            }
+            _ => self.buckets.push_back(Bucket {
+                start: now,
+                inbound: 0,
+                outbound: 0,
+            }),
+        }
+        self.buckets.back_mut().unwrap()
+    }
+
+    /// Bytes of the buckets started during the last `window`
+    pub(crate) fn usage(&self, window: Duration, now: Instant) -> BandwidthUsage {
+        self.buckets
+            .iter()
+            .rev()
+            .take_while(|bucket| now.saturating_duration_since(bucket.start) <= window)
+            .fold(BandwidthUsage::default(), |usage, bucket| BandwidthUsage {
+                inbound_bytes: usage.inbound_bytes + bucket.inbound,
+                outbound_bytes: usage.outbound_bytes + bucket.outbound,
+            })
+    }
+
+    /// Returns the limits crossed since the last check, and forgets the buckets older than the longest window
+    pub(crate) fn check(
+        &mut self,
+        limits: &[BandwidthLimit],
+        now: Instant,
+    ) -> Vec<(BandwidthLimit, u64)> {
+        let history = limits
+            .iter()
+            .map(|limit| limit.window)
+            .fold(DEFAULT_BANDWIDTH_HISTORY, Duration::max);
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| now.saturating_duration_since(bucket.start) > history)
+        {
+            self.buckets.pop_front();
+        }
+        self.exceeded.resize(limits.len(), false);
+        let usages: Vec<u64> = limits
+            .iter()
+            .map(|limit| self.usage(limit.window, now).bytes(limit.direction))
+            .collect();
+        let mut crossed = Vec::new();
+        for ((limit, bytes), exceeded) in limits.iter().zip(usages).zip(self.exceeded.iter_mut()) {
+            let over = bytes > limit.max_bytes;
+            if over && !*exceeded {
+                crossed.push((*limit, bytes));
+            }
+            *exceeded = over;
+        }
+        crossed
+    }
+
+    /// Forgets which limits were exceeded, when the limits change
+    pub(crate) fn reset_limits(&mut self) {
+        self.exceeded.clear();
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+use bytes::Bytes;
+use serde::Serialize;
+
+use crate::shared::{baseline::BaselineMessage, channels::ChannelId, ClientId, QuinnetSyncUpdate};
+
+use super::{
+    Endpoint, QuinnetServer, ServerGroupMessageSendError, ServerGroupSendError,
+    ServerMessageSendError, ServerSendError,
+};
+
+/// Default size of the chunks of a baseline, in bytes
+pub const DEFAULT_BASELINE_CHUNK_SIZE: usize = 16 * 1024;
+/// Default number of chunks of a baseline sent to a client at each update
+pub const DEFAULT_BASELINE_CHUNKS_PER_UPDATE: usize = 8;
+
+/// Step of a client in its [`BaselineSync`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaselineState {
+    /// The chunks of the baseline are being sent
+    Sending,
+    /// All the chunks were sent, waiting for the client to be ready
+    AwaitingReady,
+}
+
+/// Raised on the server for each update of a [`BaselineSync`] sending chunks to a client. Raised in the CoreStage::PreUpdate stage by the [`QuinnetBaselinePlugin`].
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaselineProgressEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// Number of bytes of the baseline sent
+    pub sent: usize,
+    /// Size of the baseline, in bytes
+    pub total: usize,
+}
+
+/// Raised on the server when a client received its baseline and receives the live updates. Raised in the CoreStage::PreUpdate stage by the [`QuinnetBaselinePlugin`].
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaselineReadyEvent {
+    /// Id of the client
+    pub id: ClientId,
+}
+
+#[derive(Debug)]
+struct ClientBaseline {
+    state: BaselineState,
+    baseline: Bytes,
+    sent: usize,
+    /// Live updates held until the client is ready, in their order
+    pending: Vec<(ChannelId, Bytes)>,
+}
+
+/// Synchronization of a large initial state, the baseline, to the newly connected clients: the baseline is sent in chunks of bounded size on a dedicated channel, a few chunks at each update, rather than as a multi-megabyte first message.
+///
+/// The live updates sent to a client with [`BaselineSync::send_payload_on`] or [`BaselineSync::broadcast_payload_on`] are held until the client received its whole baseline and answered that it is ready, then sent in their order. The client's side is [`crate::client::baseline::BaselineReceiver`].
+///
+/// The channel must be reliable and ordered, with a max message size fitting the chunks, and dedicated to the baselines: all the messages received on it are consumed.
+#[derive(Resource, Debug)]
+pub struct BaselineSync {
+    channel_id: ChannelId,
+    chunk_size: usize,
+    chunks_per_update: usize,
+    clients: HashMap<ClientId, ClientBaseline>,
+}
+
+impl BaselineSync {
+    /// Synchronization sending the baselines on `channel_id`, in chunks of [`DEFAULT_BASELINE_CHUNK_SIZE`] bytes, [`DEFAULT_BASELINE_CHUNKS_PER_UPDATE`] at a time
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            chunk_size: DEFAULT_BASELINE_CHUNK_SIZE,
+            chunks_per_update: DEFAULT_BASELINE_CHUNKS_PER_UPDATE,
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Sends the baselines in chunks of `chunk_size` bytes, at least 1
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Sends at most `chunks_per_update` chunks to each client at each update, at least 1
+    pub fn with_chunks_per_update(mut self, chunks_per_update: usize) -> Self {
+        self.chunks_per_update = chunks_per_update.max(1);
+        self
+    }
+
+    /// Channel the baselines are sent on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Size of the chunks, in bytes
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Starts sending `baseline` to a client, from the next update. The live updates sent to the client are held until it is ready.
+    ///
+    /// Starting again for a client restarts its synchronization, its held updates are dropped.
+    pub fn start<T: Into<Bytes>>(&mut self, client_id: ClientId, baseline: T) {
+        self.clients.insert(
+            client_id,
+            ClientBaseline {
+                state: BaselineState::Sending,
+                baseline: baseline.into(),
+                sent: 0,
+                pending: Vec::new(),
+            },
+        );
+    }
+
+    /// Same as [`BaselineSync::start`] with a baseline serialized with bincode
+    pub fn start_with_message<T: Serialize>(
+        &mut self,
+        client_id: ClientId,
+        baseline: &T,
+    ) -> Result<(), ServerMessageSendError> {
+        let payload =
+            bincode::serialize(baseline).map_err(|_| ServerMessageSendError::Serialization)?;
+        self.start(client_id, payload);
+        Ok(())
+    }
+
+    /// Stops the synchronization of a client, its held updates are dropped. Returns false if it was not synchronizing
+    pub fn cancel(&mut self, client_id: ClientId) -> bool {
+        self.clients.remove(&client_id).is_some()
+    }
+
+    /// Returns the state of a client, `None` if it is not synchronizing
+    pub fn state(&self, client_id: ClientId) -> Option<BaselineState> {
+        self.clients.get(&client_id).map(|client| client.state)
+    }
+
+    /// Returns the number of bytes of the baseline sent to a client and the size of the baseline, `None` if it is not synchronizing
+    pub fn progress(&self, client_id: ClientId) -> Option<(usize, usize)> {
+        self.clients
+            .get(&client_id)
+            .map(|client| (client.sent, client.baseline.len()))
+    }
+
+    /// Returns the number of live updates held for a client, 0 if it is not synchronizing
+    pub fn pending_len(&self, client_id: ClientId) -> usize {
+        self.clients
+            .get(&client_id)
+            .map_or(0, |client| client.pending.len())
+    }
+
+    /// Sends a live update to a client, or holds it if the client is synchronizing
+    pub fn send_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &mut self,
+        endpoint: &mut Endpoint,
+        client_id: ClientId,
+        channel_id: C,
+        payload: T,
+    ) -> Result<(), ServerSendError> {
+        let channel_id = channel_id.into();
+        match self.clients.get_mut(&client_id) {
+            Some(client) => {
+                client.pending.push((channel_id, payload.into()));
+                Ok(())
+            }
+            None => endpoint.send_payload_on(client_id, channel_id, payload),
+        }
+    }
+
+    /// Same as [`BaselineSync::send_payload_on`] with a message serialized with bincode
+    pub fn send_message_on<T: Serialize, C: Into<ChannelId>>(
+        &mut self,
+        endpoint: &mut Endpoint,
+        client_id: ClientId,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), ServerMessageSendError> {
+        let payload =
+            bincode::serialize(&message).map_err(|_| ServerMessageSendError::Serialization)?;
+        Ok(self.send_payload_on(endpoint, client_id, channel_id, payload)?)
+    }
+
+    /// Sends a live update to all the clients of the endpoint, held for the clients synchronizing
+    pub fn broadcast_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &mut self,
+        endpoint: &mut Endpoint,
+        channel_id: C,
+        payload: T,
+    ) -> Result<(), ServerGroupSendError> {
+        let channel_id = channel_id.into();
+        let payload: Bytes = payload.into();
+        let mut errs = Vec::new();
+        for client_id in endpoint.clients() {
+            if let Err(err) = self.send_payload_on(endpoint, client_id, channel_id, payload.clone())
+            {
+                errs.push((client_id, err));
+            }
+        }
+        match errs.is_empty() {
+            true => Ok(()),
+            false => Err(ServerGroupSendError(errs)),
+        }
+    }
+
+    /// Same as [`BaselineSync::broadcast_payload_on`] with a message serialized with bincode
+    pub fn broadcast_message_on<T: Serialize, C: Into<ChannelId>>(
+        &mut self,
+        endpoint: &mut Endpoint,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), ServerGroupMessageSendError> {
+        let payload =
+            bincode::serialize(&message).map_err(|_| ServerGroupMessageSendError::Serialization)?;
+        Ok(self.broadcast_payload_on(endpoint, channel_id, payload)?)
+    }
+
+    /// Sends the next chunks of the baselines, and returns the progress of each client which was sent chunks.
+    ///
+    /// The clients no longer connected to the endpoint are forgotten.
+    pub fn send_chunks(&mut self, endpoint: &mut Endpoint) -> Vec<BaselineProgressEvent> {
+        let client_ids = endpoint.clients();
+        self.clients
+            .retain(|client_id, _| client_ids.contains(client_id));
+
+        let mut progress = Vec::new();
+        for (&client_id, client) in self.clients.iter_mut() {
+            if client.state != BaselineState::Sending {
+                continue;
+            }
+            let total = client.baseline.len();
+            for _ in 0..self.chunks_per_update {
+                let end = (client.sent + self.chunk_size).min(total);
+                let chunk = BaselineMessage::Chunk {
+                    total: total as u64,
+                    data: client.baseline[client.sent..end].to_vec(),
+                };
+                let Ok(payload) = bincode::serialize(&chunk) else {
+                    break;
+                };
+                if let Err(err) = endpoint.send_payload_on(client_id, self.channel_id, payload) {
+                    error!(
+                        "Failed to send a baseline chunk to client {}: {}",
+                        client_id, err
+                    );
+                    break;
+                }
+                client.sent = end;
+                if client.sent == total {
+                    client.state = BaselineState::AwaitingReady;
+                    break;
+                }
+            }
+            progress.push(BaselineProgressEvent {
+                id: client_id,
+                sent: client.sent,
+                total,
+            });
+        }
+        progress
+    }
+
+    /// Receives the ready answers of the clients, and sends their held updates. Returns the clients which became ready.
+    pub fn receive_ready(&mut self, endpoint: &mut Endpoint) -> Vec<ClientId> {
+        let mut ready = Vec::new();
+        for client_id in endpoint.clients() {
+            let Ok(payloads) = endpoint.receive_all_from_on(client_id, self.channel_id) else {
+                continue;
+            };
+            let answered = payloads.into_iter().any(|payload| {
+                matches!(bincode::deserialize(&payload), Ok(BaselineMessage::Ready))
+            });
+            if !answered || self.state(client_id) != Some(BaselineState::AwaitingReady) {
+                continue;
+            }
+            if let Some(client) = self.clients.remove(&client_id) {
+                for (channel_id, payload) in client.pending {
+                    endpoint.try_send_payload_on(client_id, channel_id, payload);
+                }
+                ready.push(client_id);
+            }
+        }
+        ready
+    }
+}
+
+/// Updates a [`BaselineSync`] resource, and raises a [`BaselineProgressEvent`] for each client sent chunks and a [`BaselineReadyEvent`] for each client which became ready.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetBaselinePlugin {
+    channel_id: ChannelId,
+    chunk_size: usize,
+    chunks_per_update: usize,
+    schedule: InternedScheduleLabel,
+}
+
+impl QuinnetBaselinePlugin {
+    /// Plugin sending the baselines on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            chunk_size: DEFAULT_BASELINE_CHUNK_SIZE,
+            chunks_per_update: DEFAULT_BASELINE_CHUNKS_PER_UPDATE,
+            schedule: PreUpdate.intern(),
+        }
+    }
+
+    /// See [`BaselineSync::with_chunk_size`]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// See [`BaselineSync::with_chunks_per_update`]
+    pub fn with_chunks_per_update(mut self, chunks_per_update: usize) -> Self {
+        self.chunks_per_update = chunks_per_update;
+        self
+    }
+
+    /// Updates the synchronizations in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetBaselinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BaselineProgressEvent>()
+            .add_event::<BaselineReadyEvent>()
+            .insert_resource(
+                BaselineSync::new(self.channel_id)
+                    .with_chunk_size(self.chunk_size)
+                    .with_chunks_per_update(self.chunks_per_update),
+            )
+            .add_systems(
+                self.schedule,
+                update_baselines
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetServer>),
+            );
+    }
+}
+
+fn update_baselines(
+    mut server: ResMut<QuinnetServer>,
+    mut baselines: ResMut<BaselineSync>,
+    mut progress_events: EventWriter<BaselineProgressEvent>,
+    mut ready_events: EventWriter<BaselineReadyEvent>,
+) {
+    let Some(endpoint) = server.get_endpoint_mut() else {
+        return;
+    };
+    ready_events.write_batch(
+        baselines
+            .receive_ready(endpoint)
+            .into_iter()
+            .map(|id| BaselineReadyEvent { id }),
+    );
+    progress_events.write_batch(baselines.send_chunks(endpoint));
+}
@@ -2,11 +2,17 @@ use std::{
     fs::{self, File},
     io::BufReader,
     path::Path,
+    sync::Arc,
 };
 
 use bevy::log::{trace, warn};
+use rustls::{
+    crypto::CryptoProvider,
+    server::{danger::ClientCertVerifier, WebPkiClientVerifier},
+    RootCertStore,
+};
 
-use super::EndpointCertificateError;
+use super::{EndpointCertificateError, EndpointStartError};
 use crate::shared::certificate::CertificateFingerprint;
 
 /// Represents the origin of a certificate.
@@ -60,6 +66,70 @@ pub struct ServerCertificate {
     pub fingerprint: CertificateFingerprint,
 }
 
+impl Clone for ServerCertificate {
+    fn clone(&self) -> Self {
+        Self {
+            cert_chain: self.cert_chain.clone(),
+            priv_key: self.priv_key.clone_key(),
+            fingerprint: self.fingerprint.clone(),
+        }
+    }
+}
+
+/// Authentication of the clients by their certificate (mutual TLS), see [`crate::server::ServerEndpointConfiguration::with_client_authentication`].
+///
+/// Meant for the connections between servers of a backend (a gateway and its zone servers): each server connects to the others as a client presenting a certificate signed by a private authority, see [`crate::client::connection::ClientEndpointConfigurationBuilder::with_client_certificate`].
+#[derive(Debug, Clone)]
+pub struct ClientAuthentication {
+    roots: Vec<rustls::pki_types::CertificateDer<'static>>,
+    optional: bool,
+}
+
+impl ClientAuthentication {
+    /// Only accepts the clients presenting a certificate signed by one of the `roots` authorities
+    pub fn new(roots: Vec<rustls::pki_types::CertificateDer<'static>>) -> Self {
+        Self {
+            roots,
+            optional: false,
+        }
+    }
+
+    /// Also accepts the clients without certificate, such as the players connecting to a gateway which also accepts other servers. Certificates presented are still verified.
+    ///
+    /// See [`crate::server::ServerSideConnection::peer_certificates`] to tell the authenticated clients apart.
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// Authorities signing the certificates of the clients
+    pub fn roots(&self) -> &[rustls::pki_types::CertificateDer<'static>] {
+        &self.roots
+    }
+
+    /// Returns true if the clients without certificate are accepted
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
+    pub(crate) fn verifier(
+        &self,
+        provider: Arc<CryptoProvider>,
+    ) -> Result<Arc<dyn ClientCertVerifier>, EndpointStartError> {
+        let mut roots = RootCertStore::empty();
+        for root in &self.roots {
+            roots.add(root.clone())?;
+        }
+        let builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+        let builder = if self.optional {
+            builder.allow_unauthenticated()
+        } else {
+            builder
+        };
+        Ok(builder.build()?)
+    }
+}
+
 fn read_cert_from_files(
     cert_file: &String,
     key_file: &String,
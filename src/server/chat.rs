@@ -0,0 +1,366 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use crate::shared::{
+    channels::ChannelId,
+    chat::{
+        ChatEvent, ChatMessage, ChatRejection, ChatRequest, ChatTarget, DEFAULT_CHAT_MAX_LENGTH,
+    },
+    ClientId, QuinnetSyncUpdate,
+};
+
+use super::{Endpoint, QuinnetServer, ServerGroupMessageSendError};
+
+/// Default number of messages a client can send per [`DEFAULT_CHAT_RATE_PERIOD`]
+pub const DEFAULT_CHAT_RATE_LIMIT: usize = 5;
+/// Default period of the rate limit of the chat
+pub const DEFAULT_CHAT_RATE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Filter of the chat messages, such as a profanity filter: receives the id of the sender, the target and the text of a message. Returns the text to deliver, possibly rewritten, or `None` to reject the message.
+pub type ChatFilter =
+    Box<dyn Fn(ClientId, &ChatTarget, &str) -> Option<String> + Send + Sync + 'static>;
+
+/// Raised on the server when a chat message of a client is rejected. Raised in the CoreStage::PreUpdate stage by the [`QuinnetServerChatPlugin`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ChatRejectedEvent {
+    /// Id of the sender
+    pub client_id: ClientId,
+    /// Reason of the rejection
+    pub reason: ChatRejection,
+}
+
+/// Server of the text chat: relays the messages of the clients to the members of their chat channels or to the recipients of their whispers, on a channel of the connections.
+///
+/// The messages of a client are rejected when it exceeds the rate limit, when they exceed the max length, when it is not a member of their chat channel, or when the [`ChatFilter`] refuses them. The rejections are sent back to the client. The clients' side is [`crate::client::chat::ChatClient`].
+///
+/// The channel must be reliable and dedicated to the chat: all the messages received on it are consumed.
+#[derive(Resource)]
+pub struct ChatServer {
+    channel_id: ChannelId,
+    rate_limit: usize,
+    rate_period: Duration,
+    max_length: usize,
+    filter: Option<ChatFilter>,
+    channels: HashMap<String, HashSet<ClientId>>,
+    /// Send times of the recent messages of each client, oldest first
+    recent_sends: HashMap<ClientId, VecDeque<Instant>>,
+}
+
+impl ChatServer {
+    /// Chat on `channel_id`, limited to [`DEFAULT_CHAT_RATE_LIMIT`] messages per [`DEFAULT_CHAT_RATE_PERIOD`] of [`DEFAULT_CHAT_MAX_LENGTH`] chars, without filter
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            rate_limit: DEFAULT_CHAT_RATE_LIMIT,
+            rate_period: DEFAULT_CHAT_RATE_PERIOD,
+            max_length: DEFAULT_CHAT_MAX_LENGTH,
+            filter: None,
+            channels: HashMap::new(),
+            recent_sends: HashMap::new(),
+        }
+    }
+
+    /// Limits each client to `count` messages per `period`
+    pub fn with_rate_limit(mut self, count: usize, period: Duration) -> Self {
+        self.rate_limit = count;
+        self.rate_period = period;
+        self
+    }
+
+    /// Rejects the messages longer than `max_length` chars
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Sets the [`ChatFilter`] run on each message of a client before relaying it
+    pub fn with_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(ClientId, &ChatTarget, &str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.set_filter(filter);
+        self
+    }
+
+    /// Same as [`ChatServer::with_filter`], on an existing server
+    pub fn set_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(ClientId, &ChatTarget, &str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+    }
+
+    /// Channel the chat messages are sent on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Returns the members of a chat channel, if it has any
+    pub fn members(&self, channel: &str) -> Option<&HashSet<ClientId>> {
+        self.channels.get(channel)
+    }
+
+    /// Adds a client to a chat channel, and notifies it
+    pub fn join(&mut self, endpoint: &mut Endpoint, client_id: ClientId, channel: &str) {
+        if self
+            .channels
+            .entry(channel.to_string())
+            .or_default()
+            .insert(client_id)
+        {
+            endpoint.try_send_message_on(
+                client_id,
+                self.channel_id,
+                ChatEvent::Joined(channel.to_string()),
+            );
+        }
+    }
+
+    /// Removes a client from a chat channel, and notifies it
+    pub fn leave(&mut self, endpoint: &mut Endpoint, client_id: ClientId, channel: &str) {
+        let Some(members) = self.channels.get_mut(channel) else {
+            return;
+        };
+        if members.remove(&client_id) {
+            if members.is_empty() {
+                self.channels.remove(channel);
+            }
+            endpoint.try_send_message_on(
+                client_id,
+                self.channel_id,
+                ChatEvent::Left(channel.to_string()),
+            );
+        }
+    }
+
+    /// Sends a message of the server to a target, without rate limit nor filter
+    pub fn send(
+        &mut self,
+        endpoint: &mut Endpoint,
+        target: ChatTarget,
+        text: String,
+    ) -> Result<(), ServerGroupMessageSendError> {
+        self.deliver(
+            endpoint,
+            ChatMessage {
+                from: None,
+                target,
+                text,
+            },
+        )
+    }
+
+    fn deliver(
+        &self,
+        endpoint: &mut Endpoint,
+        message: ChatMessage,
+    ) -> Result<(), ServerGroupMessageSendError> {
+        let recipients: Vec<ClientId> = match &message.target {
+            ChatTarget::Channel(channel) => self
+                .channels
+                .get(channel)
+                .map(|members| members.iter().copied().collect())
+                .unwrap_or_default(),
+            ChatTarget::Whisper(recipient) => {
+                let mut recipients = vec![*recipient];
+                recipients.extend(message.from.filter(|from| from != recipient));
+                recipients
+            }
+        };
+        endpoint.send_group_message_on(
+            recipients.iter(),
+            self.channel_id,
+            ChatEvent::Message(message),
+        )
+    }
+
+    /// Receives the requests of the clients of the endpoint: joins and leaves the chat channels, relays the accepted messages and notifies the rejected ones. Returns the relayed messages and the rejections.
+    ///
+    /// Requests that can't be deserialized are dropped. The clients no longer connected are removed from the chat channels.
+    pub fn receive(
+        &mut self,
+        endpoint: &mut Endpoint,
+    ) -> (Vec<ChatMessage>, Vec<ChatRejectedEvent>) {
+        let client_ids = endpoint.clients();
+        self.recent_sends
+            .retain(|client_id, _| client_ids.contains(client_id));
+        self.channels.retain(|_, members| {
+            members.retain(|client_id| client_ids.contains(client_id));
+            !members.is_empty()
+        });
+
+        let (mut messages, mut rejections) = (Vec::new(), Vec::new());
+        for client_id in client_ids {
+            let Ok(payloads) = endpoint.receive_all_from_on(client_id, self.channel_id) else {
+                continue;
+            };
+            for payload in payloads {
+                let Ok(request) = bincode::deserialize::<ChatRequest>(&payload) else {
+                    continue;
+                };
+                let (target, text) = match request {
+                    ChatRequest::Join(channel) => {
+                        self.join(endpoint, client_id, &channel);
+                        continue;
+                    }
+                    ChatRequest::Leave(channel) => {
+                        self.leave(endpoint, client_id, &channel);
+                        continue;
+                    }
+                    ChatRequest::Send { target, text } => (target, text),
+                };
+                match self.accept(endpoint, client_id, target, text) {
+                    Ok(message) => {
+                        if let Err(err) = self.deliver(endpoint, message.clone()) {
+                            error!(
+                                "Failed to relay a chat message of client {}: {}",
+                                client_id, err
+                            );
+                        }
+                        messages.push(message);
+                    }
+                    Err(reason) => {
+                        endpoint.try_send_message_on(
+                            client_id,
+                            self.channel_id,
+                            ChatEvent::Rejected(reason),
+                        );
+                        rejections.push(ChatRejectedEvent { client_id, reason });
+                    }
+                }
+            }
+        }
+        (messages, rejections)
+    }
+
+    fn accept(
+        &mut self,
+        endpoint: &Endpoint,
+        client_id: ClientId,
+        target: ChatTarget,
+        text: String,
+    ) -> Result<ChatMessage, ChatRejection> {
+        let now = Instant::now();
+        let recent_sends = self.recent_sends.entry(client_id).or_default();
+        while recent_sends
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= self.rate_period)
+        {
+            recent_sends.pop_front();
+        }
+        if recent_sends.len() >= self.rate_limit {
+            return Err(ChatRejection::RateLimited);
+        }
+        recent_sends.push_back(now);
+
+        if text.chars().count() > self.max_length {
+            return Err(ChatRejection::TooLong);
+        }
+        match &target {
+            ChatTarget::Channel(channel) => {
+                if !self
+                    .channels
+                    .get(channel)
+                    .is_some_and(|members| members.contains(&client_id))
+                {
+                    return Err(ChatRejection::NotMember);
+                }
+            }
+            ChatTarget::Whisper(recipient) => {
+                if endpoint.get_connection(*recipient).is_none() {
+                    return Err(ChatRejection::UnknownRecipient);
+                }
+            }
+        }
+        let text = match &self.filter {
+            Some(filter) => filter(client_id, &target, &text).ok_or(ChatRejection::Filtered)?,
+            None => text,
+        };
+        Ok(ChatMessage {
+            from: Some(client_id),
+            target,
+            text,
+        })
+    }
+}
+
+/// Runs the text chat with a [`ChatServer`] resource, and raises a [`ChatMessage`] event for each relayed message and a [`ChatRejectedEvent`] for each rejected one. The filter can be set on the resource, see [`ChatServer::set_filter`].
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetServerChatPlugin {
+    channel_id: ChannelId,
+    rate_limit: usize,
+    rate_period: Duration,
+    max_length: usize,
+    schedule: InternedScheduleLabel,
+}
+
+impl QuinnetServerChatPlugin {
+    /// Plugin running the chat on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            rate_limit: DEFAULT_CHAT_RATE_LIMIT,
+            rate_period: DEFAULT_CHAT_RATE_PERIOD,
+            max_length: DEFAULT_CHAT_MAX_LENGTH,
+            schedule: PreUpdate.intern(),
+        }
+    }
+
+    /// See [`ChatServer::with_rate_limit`]
+    pub fn with_rate_limit(mut self, count: usize, period: Duration) -> Self {
+        self.rate_limit = count;
+        self.rate_period = period;
+        self
+    }
+
+    /// See [`ChatServer::with_max_length`]
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Receives the chat requests in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetServerChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ChatMessage>()
+            .add_event::<ChatRejectedEvent>()
+            .insert_resource(
+                ChatServer::new(self.channel_id)
+                    .with_rate_limit(self.rate_limit, self.rate_period)
+                    .with_max_length(self.max_length),
+            )
+            .add_systems(
+                self.schedule,
+                receive_chat_requests
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetServer>),
+            );
+    }
+}
+
+fn receive_chat_requests(
+    mut server: ResMut<QuinnetServer>,
+    mut chat: ResMut<ChatServer>,
+    mut message_events: EventWriter<ChatMessage>,
+    mut rejected_events: EventWriter<ChatRejectedEvent>,
+) {
+    if let Some(endpoint) = server.get_endpoint_mut() {
+        let (messages, rejections) = chat.receive(endpoint);
+        message_events.write_batch(messages);
+        rejected_events.write_batch(rejections);
+    }
+}
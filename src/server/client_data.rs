@@ -0,0 +1,39 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+/// User data attached to a client, at most one value per type. Dropped with the connection of the client.
+#[derive(Debug, Default)]
+pub(crate) struct ClientData {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl ClientData {
+    /// Sets the value of type `T`, returns the previous one
+    pub(crate) fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    pub(crate) fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub(crate) fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    pub(crate) fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+}
@@ -4,15 +4,17 @@ use futures::SinkExt;
 use tokio::sync::mpsc::{self};
 use tokio_util::codec::{FramedWrite, LengthDelimitedCodec};
 
-use crate::shared::{channels::ChannelAsyncMessage, ClientId, CLIENT_ID_LEN};
+use crate::shared::{
+    channels::ChannelAsyncMessage, transport::TransportConnection, ClientId, CLIENT_ID_LEN,
+};
 
-pub(crate) fn spawn_client_id_sender(
-    connection_handle: quinn::Connection,
+pub(crate) fn spawn_client_id_sender<C: TransportConnection>(
+    connection_handle: C,
     client_id: ClientId,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
 ) {
     tokio::spawn(async move {
-        let (stream_send, _) = connection_handle
+        let stream_send = connection_handle
             .open_bi()
             .await
             .expect("Failed to open send stream");
@@ -0,0 +1,174 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+
+use crate::shared::channels::{ChannelId, MessagePriority, TrackedMessageId};
+
+/// Artificial network conditions applied by the server to the messages exchanged with one client, see [`crate::server::ServerSideConnection::set_conditions`].
+///
+/// Meant for playtests, to check the lag compensation of a game against a single worst-case player while the other clients keep a normal link. The conditions are applied in both directions on top of the real network: the messages sent to the client are held before being queued on their channel, and the messages received from the client are held before being made available to the `receive_*` methods of the [`crate::server::Endpoint`]. The control messages of Quinnet are not affected.
+///
+/// Messages keep their order in each direction, the jitter only bunches them. The draws come from a seed: the same seed and the same messages always give the same delays and losses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientConditions {
+    latency: Duration,
+    jitter: Duration,
+    loss: f64,
+    seed: u64,
+}
+
+impl ClientConditions {
+    /// Adds `latency` to each message, in each direction: the round-trip time of the client grows by twice `latency`
+    pub fn new(latency: Duration) -> Self {
+        Self {
+            latency,
+            jitter: Duration::ZERO,
+            loss: 0.,
+            seed: 0,
+        }
+    }
+
+    /// Adds up to `jitter` of random latency to each message
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Drops each message of the [`crate::shared::channels::ChannelKind::Unreliable`] channels with a probability of `loss`, in each direction
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    /// Draws the jitter and the losses from `seed` instead of 0
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Latency added to each message, in each direction
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Maximum random latency added on top of [`ClientConditions::latency`]
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Probability of dropping a message of an unreliable channel
+    pub fn loss(&self) -> f64 {
+        self.loss
+    }
+}
+
+/// Message to a client held by its [`Conditioner`]
+#[derive(Debug)]
+pub(crate) struct HeldPayload {
+    pub(crate) channel_id: ChannelId,
+    pub(crate) payload: Bytes,
+    pub(crate) priority: Option<MessagePriority>,
+    pub(crate) tracked: Option<TrackedMessageId>,
+}
+
+/// Applies the [`ClientConditions`] of a client
+#[derive(Debug)]
+pub(crate) struct Conditioner {
+    conditions: ClientConditions,
+    state: u64,
+    last_inbound_due: Option<Instant>,
+    last_outbound_due: Option<Instant>,
+    outbound: VecDeque<(Instant, HeldPayload)>,
+}
+
+impl Conditioner {
+    pub(crate) fn new(conditions: ClientConditions) -> Self {
+        Self {
+            state: conditions.seed,
+            conditions,
+            last_inbound_due: None,
+            last_outbound_due: None,
+            outbound: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn conditions(&self) -> &ClientConditions {
+        &self.conditions
+    }
+
+    /// Replaces the conditions, the messages already held keep their delay
+    pub(crate) fn set_conditions(&mut self, conditions: ClientConditions) {
+        self.state = conditions.seed;
+        self.conditions = conditions;
+    }
+
+    /// SplitMix64
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Instant a message handled now is delivered at, `None` if dropped
+    fn draw_due(&mut self, lossy: bool, now: Instant, inbound: bool) -> Option<Instant> {
+        if lossy && self.conditions.loss > 0. && self.next_f64() < self.conditions.loss {
+            return None;
+        }
+        let mut delay = self.conditions.latency;
+        if !self.conditions.jitter.is_zero() {
+            delay += self.conditions.jitter.mul_f64(self.next_f64());
+        }
+        let last_due = match inbound {
+            true => &mut self.last_inbound_due,
+            false => &mut self.last_outbound_due,
+        };
+        // Never deliver before the previous message, to keep the order
+        let due = last_due.map_or(now + delay, |last_due| last_due.max(now + delay));
+        *last_due = Some(due);
+        Some(due)
+    }
+
+    /// Instant a message received now from the client is made available at, `None` if dropped
+    pub(crate) fn inbound_due(&mut self, lossy: bool, now: Instant) -> Option<Instant> {
+        self.draw_due(lossy, now, true)
+    }
+
+    /// Holds a message to the client. Returns false if dropped.
+    pub(crate) fn hold_outbound(&mut self, lossy: bool, now: Instant, held: HeldPayload) -> bool {
+        match self.draw_due(lossy, now, false) {
+            Some(due) => {
+                self.outbound.push_back((due, held));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the messages to the client due at `now`, in their sending order
+    pub(crate) fn release_outbound(&mut self, now: Instant) -> Vec<HeldPayload> {
+        let due_count = self
+            .outbound
+            .iter()
+            .take_while(|(due, _)| *due <= now)
+            .count();
+        self.outbound
+            .drain(..due_count)
+            .map(|(_, held)| held)
+            .collect()
+    }
+
+    /// Removes all the messages held for the client
+    pub(crate) fn release_all_outbound(&mut self) -> Vec<HeldPayload> {
+        self.outbound.drain(..).map(|(_, held)| held).collect()
+    }
+}
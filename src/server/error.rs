@@ -12,6 +12,17 @@ pub enum ServerSendError {
     /// A channel is closed
     #[error("Channel is closed")]
     ChannelClosed,
+    /// A payload exceeds the maximum message size of its channel
+    #[error("Payload of {size} bytes exceeds the maximum message size of the channel ({max_message_size} bytes)")]
+    PayloadTooLarge {
+        /// Size of the payload
+        size: usize,
+        /// Maximum message size of the channel
+        max_message_size: usize,
+    },
+    /// A channel does not acknowledge its messages, see [`crate::shared::channels::ChannelConfig::acknowledged`]
+    #[error("Channel with id `{0}` does not acknowledge its messages")]
+    ChannelNotAcknowledged(ChannelId),
     /// Quinnet async channel error
     #[error("Quinnet async channel error")]
     ChannelSendError(#[from] AsyncChannelError),
@@ -105,6 +116,85 @@ pub enum ServerDisconnectError {
     ClientAlreadyDisconnected(ClientId),
 }
 
+/// Error while attaching data to a client on the server
+#[derive(thiserror::Error, Debug)]
+pub enum ServerClientDataError {
+    /// A client id is unknown
+    #[error("Client with id `{0}` is unknown")]
+    UnknownClient(ClientId),
+}
+
+/// Error while assigning a client to an instance, see [`crate::server::Endpoint::assign_instance`]
+#[derive(thiserror::Error, Debug)]
+pub enum ServerInstanceError {
+    /// A client id is unknown
+    #[error("Client with id `{0}` is unknown")]
+    UnknownClient(ClientId),
+    /// The instance is not open
+    #[error("Instance `{0}` is not open")]
+    UnknownInstance(String),
+}
+
+/// Error while transferring a client to another server
+#[derive(thiserror::Error, Debug)]
+pub enum ServerTransferError {
+    /// A client id is unknown
+    #[error("Client with id `{0}` is unknown")]
+    UnknownClient(ClientId),
+    /// The client is already being transferred
+    #[error("Client with id `{0}` is already being transferred")]
+    AlreadyTransferring(ClientId),
+    /// No transfer key was set on the endpoint
+    #[error("No transfer key was set on the endpoint")]
+    NoTransferKey,
+    /// The payload exceeds [`crate::server::transfer::MAX_TRANSFER_PAYLOAD_LEN`]
+    #[error("Transfer payload of {0} bytes is too large")]
+    PayloadTooLarge(usize),
+    /// The nonce of the token could not be generated
+    #[error("Failed to generate the nonce of the transfer token")]
+    RandomGenerationFailed,
+    /// Quinnet async channel error
+    #[error("Quinnet async channel error")]
+    ChannelSendError(#[from] AsyncChannelError),
+}
+
+/// Error while streaming to a spectator, see [`crate::server::spectator::SpectatorStream`]
+#[derive(thiserror::Error, Debug)]
+pub enum ServerSpectatorError {
+    /// A client is not a spectator of the stream
+    #[error("Client with id `{0}` is not a spectator")]
+    UnknownSpectator(ClientId),
+    /// The snapshot of the spectator was already ended
+    #[error("The snapshot of the spectator with id `{0}` was already ended")]
+    SnapshotEnded(ClientId),
+    /// Failed serialization
+    #[error("Failed serialization")]
+    Serialization,
+    /// Error when sending data
+    #[error("Error when sending data")]
+    SendError(#[from] ServerSendError),
+}
+
+/// Reason of the rejection of a transfer token, see [`crate::server::transfer::ClientTransferRejectedEvent`]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum TransferTokenError {
+    /// No transfer key was set on the endpoint
+    #[error("No transfer key was set on the endpoint")]
+    NoTransferKey,
+    /// The token or the control message carrying it could not be decoded
+    #[error("Malformed transfer token")]
+    Malformed,
+    /// The token was not signed with the transfer key of the endpoint
+    #[error("Invalid transfer token signature")]
+    InvalidSignature,
+    /// The token expired
+    #[error("Expired transfer token")]
+    Expired,
+    /// The token was already presented
+    #[error("Transfer token already used")]
+    Replayed,
+}
+
 /// Endpoint is already closed
 #[derive(thiserror::Error, Debug)]
 #[error("Endpoint is already closed")]
@@ -125,9 +215,24 @@ pub enum EndpointStartError {
     ///Rustls protocol error
     #[error("Rustls protocol error")]
     RustlsError(#[from] rustls::Error),
+    /// The verifier of the client certificates could not be built, see [`crate::server::certificate::ClientAuthentication`]
+    #[error("Client certificate verifier error")]
+    ClientVerifierError(#[from] rustls::server::VerifierBuilderError),
     /// Quinnet async channel error
     #[error("Quinnet async channel error")]
     AsyncChannelError(#[from] AsyncChannelError),
+    /// No endpoint was ever started, there is no configuration to restart from
+    #[error("No endpoint was ever started")]
+    NeverStarted,
+    /// The maximum UDP payload size of the socket is out of bounds, see [`crate::shared::socket::SocketConfiguration::with_max_udp_payload_size`]
+    #[error("Max UDP payload size of {0} bytes is out of bounds")]
+    InvalidMaxUdpPayloadSize(u16),
+    /// The endpoint is sharded on a platform without `SO_REUSEPORT` balancing, see [`crate::server::ServerEndpointConfiguration::with_shards`]
+    #[error("Sharded endpoints are not supported on this platform")]
+    ShardingUnsupported,
+    /// The custom TLS configuration does not support QUIC, see [`crate::server::ServerEndpointConfiguration::with_tls_config`]
+    #[error("The TLS configuration does not support QUIC")]
+    UnsupportedTlsConfig(#[from] quinn::crypto::rustls::NoInitialCipherSuite),
 }
 
 /// Error while retrieving a certificate on the server
@@ -141,6 +246,30 @@ pub enum EndpointCertificateError {
     IoError(#[from] std::io::Error),
 }
 
+/// Error while mapping a port on the gateway with NAT-PMP
+#[cfg(feature = "port-mapping")]
+#[derive(thiserror::Error, Debug)]
+pub enum PortMappingError {
+    /// No gateway was configured and the default gateway could not be found
+    #[error("No gateway was configured and the default gateway could not be found")]
+    NoGateway,
+    /// I/O Error
+    #[error("I/O error")]
+    IoError(#[from] std::io::Error),
+    /// The gateway did not respond in time
+    #[error("The gateway did not respond in time")]
+    Timeout,
+    /// The gateway response is invalid
+    #[error("Invalid NAT-PMP response")]
+    InvalidResponse,
+    /// The gateway refused the request
+    #[error("The gateway refused the request with result code `{0}`")]
+    Refused(u16),
+    /// The gateway granted the mapping with a lifetime of 0, deleting it
+    #[error("The gateway granted the mapping with a lifetime of 0")]
+    NoLifetime,
+}
+
 /// Endpoint connection is already closed
 #[derive(thiserror::Error, Debug)]
 #[error("Endpoint connection is already closed")]
@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+
+use crate::shared::ClientId;
+
+/// Number of low bits of a [`ClientId`] holding its index, the high bits hold its generation. See [`client_id_index`] and [`client_id_generation`].
+pub const CLIENT_ID_INDEX_BITS: u32 = 32;
+
+/// Index of `client_id`: with [`ClientIdPolicy::Generational`], the clients given the same slot share the same index
+pub fn client_id_index(client_id: ClientId) -> u32 {
+    client_id as u32
+}
+
+/// Generation of `client_id`: with [`ClientIdPolicy::Generational`], incremented each time the index of a disconnected client is given to a new client. Always 0 with [`ClientIdPolicy::Sequential`].
+pub fn client_id_generation(client_id: ClientId) -> u32 {
+    (client_id >> CLIENT_ID_INDEX_BITS) as u32
+}
+
+/// Client id made of `index` and `generation`, see [`client_id_index`] and [`client_id_generation`]
+pub fn client_id_from_parts(index: u32, generation: u32) -> ClientId {
+    ((generation as ClientId) << CLIENT_ID_INDEX_BITS) | index as ClientId
+}
+
+/// Allocator of the client ids driven by the user, see [`ClientIdPolicy::Custom`]
+pub trait ClientIdAllocator: Send + Sync + 'static {
+    /// Returns the id of a new client, or `None` to refuse its connection.
+    ///
+    /// A connection given the id of a client still connected is refused.
+    fn allocate(&mut self) -> Option<ClientId>;
+
+    /// Called when the client with `client_id` is disconnected, its id is free again
+    fn release(&mut self, _client_id: ClientId) {}
+}
+
+/// How an [`crate::server::Endpoint`] allocates the ids of its new clients, see [`crate::server::Endpoint::set_client_id_policy`]
+#[derive(Default)]
+pub enum ClientIdPolicy {
+    /// Ids are never reused: 1, 2, 3, ... The default.
+    #[default]
+    Sequential,
+    /// The index of a disconnected client is given to a later client, with its generation incremented, like a Bevy `Entity`.
+    ///
+    /// Ids stay small and dense, for example to index a `Vec`, while a stale id of a disconnected client never designates a new client. Indexes start at 1 and are reused in the order they were freed.
+    Generational,
+    /// Ids are allocated by the user
+    Custom(Box<dyn ClientIdAllocator>),
+}
+
+impl std::fmt::Debug for ClientIdPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientIdPolicy::Sequential => write!(f, "Sequential"),
+            ClientIdPolicy::Generational => write!(f, "Generational"),
+            ClientIdPolicy::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// State of the allocation of the client ids of an endpoint
+#[derive(Debug, Default)]
+pub(crate) struct ClientIds {
+    policy: ClientIdPolicy,
+    /// Last id given by [`ClientIdPolicy::Sequential`]
+    last_sequential: ClientId,
+    /// Current generation of each index of [`ClientIdPolicy::Generational`], by index - 1
+    generations: Vec<u32>,
+    /// Indexes freed by [`ClientIdPolicy::Generational`], in the order they were freed
+    free_indexes: VecDeque<u32>,
+}
+
+impl ClientIds {
+    pub(crate) fn set_policy(&mut self, policy: ClientIdPolicy) {
+        self.policy = policy;
+    }
+
+    pub(crate) fn policy(&self) -> &ClientIdPolicy {
+        &self.policy
+    }
+
+    /// Returns the id of a new client, never one for which `in_use` is true
+    pub(crate) fn allocate(&mut self, in_use: impl Fn(ClientId) -> bool) -> Option<ClientId> {
+        match &mut self.policy {
+            ClientIdPolicy::Sequential => loop {
+                self.last_sequential = self.last_sequential.checked_add(1)?;
+                if !in_use(self.last_sequential) {
+                    return Some(self.last_sequential);
+                }
+            },
+            ClientIdPolicy::Generational => loop {
+                let index = match self.free_indexes.pop_front() {
+                    Some(index) => index,
+                    None => {
+                        let index = u32::try_from(self.generations.len() + 1).ok()?;
+                        self.generations.push(0);
+                        index
+                    }
+                };
+                let client_id = client_id_from_parts(index, self.generations[index as usize - 1]);
+                if !in_use(client_id) {
+                    return Some(client_id);
+                }
+            },
+            ClientIdPolicy::Custom(allocator) => {
+                allocator.allocate().filter(|client_id| !in_use(*client_id))
+            }
+        }
+    }
+
+    /// Frees the id of a disconnected client
+    pub(crate) fn release(&mut self, client_id: ClientId) {
+        match &mut self.policy {
+            ClientIdPolicy::Sequential => (),
+            ClientIdPolicy::Generational => {
+                let index = client_id_index(client_id);
+                let Some(generation) = index
+                    .checked_sub(1)
+                    .and_then(|slot| self.generations.get_mut(slot as usize))
+                else {
+                    return;
+                };
+                // Ids given before a change of policy are not in the slots
+                if *generation == client_id_generation(client_id) {
+                    *generation = generation.wrapping_add(1);
+                    self.free_indexes.push_back(index);
+                }
+            }
+            ClientIdPolicy::Custom(allocator) => allocator.release(client_id),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+
+use crate::shared::{channels::ChannelId, ClientId};
+
+/// Application-level idle detection of the clients of an endpoint, see [`crate::server::Endpoint::set_idle_detection`].
+///
+/// QUIC keep-alives keep the connection of an inactive player alive, this detects the clients which did not send any message (on the selected channels) for a while.
+///
+/// A client is considered active when the server receives its messages with the `receive_*` methods of the [`crate::server::Endpoint`], and since its connection.
+#[derive(Debug, Clone)]
+pub struct IdleDetection {
+    timeout: Duration,
+    channels: Option<Vec<ChannelId>>,
+    kick: bool,
+}
+
+impl IdleDetection {
+    /// A client is idle once it did not send any message, on any channel, for `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            channels: None,
+            kick: false,
+        }
+    }
+
+    /// Only the messages received on `channels` count as activity, for example to ignore a channel of automatic pings
+    pub fn on_channels(mut self, channels: impl IntoIterator<Item = ChannelId>) -> Self {
+        self.channels = Some(channels.into_iter().collect());
+        self
+    }
+
+    /// Disconnects the idle clients with [`crate::shared::close::CloseCode::Idle`], after raising their [`ClientIdleEvent`]
+    pub fn kick(mut self) -> Self {
+        self.kick = true;
+        self
+    }
+
+    /// Period of inactivity after which a client is idle
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Channels counting as activity, `None` for all the channels
+    pub fn channels(&self) -> Option<&[ChannelId]> {
+        self.channels.as_deref()
+    }
+
+    /// Returns true if the idle clients are disconnected
+    pub fn kicks(&self) -> bool {
+        self.kick
+    }
+
+    fn tracks(&self, channel_id: ChannelId) -> bool {
+        match &self.channels {
+            Some(channels) => channels.contains(&channel_id),
+            None => true,
+        }
+    }
+}
+
+/// Raised once when a client becomes idle, see [`IdleDetection`]. Raised again if the client becomes idle again after some activity. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct ClientIdleEvent {
+    /// Id of the idle client
+    pub id: ClientId,
+    /// Time since the last activity of the client
+    pub idle_for: Duration,
+}
+
+/// Last activity of a client, per channel
+#[derive(Debug)]
+pub(crate) struct ClientActivity {
+    connected_at: Instant,
+    last_received: HashMap<ChannelId, Instant>,
+    /// Last activity of the idle period already reported
+    reported: Option<Instant>,
+}
+
+impl ClientActivity {
+    pub(crate) fn new() -> Self {
+        Self {
+            connected_at: Instant::now(),
+            last_received: HashMap::new(),
+            reported: None,
+        }
+    }
+
+    pub(crate) fn record(&mut self, channel_id: ChannelId) {
+        self.last_received.insert(channel_id, Instant::now());
+    }
+
+    fn last_activity(&self, detection: &IdleDetection) -> Instant {
+        self.last_received
+            .iter()
+            .filter(|(channel_id, _)| detection.tracks(**channel_id))
+            .map(|(_, received)| *received)
+            .fold(self.connected_at, Instant::max)
+    }
+
+    /// Returns how long the client has been idle, only once per idle period
+    pub(crate) fn check(&mut self, detection: &IdleDetection, now: Instant) -> Option<Duration> {
+        let last_activity = self.last_activity(detection);
+        let idle_for = now.saturating_duration_since(last_activity);
+        if idle_for < detection.timeout || self.reported == Some(last_activity) {
+            return None;
+        }
+        self.reported = Some(last_activity);
+        Some(idle_for)
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+use serde::de::DeserializeOwned;
+
+use crate::shared::{
+    channels::ChannelId,
+    input::{packet_ticks, InputPacket, InputTick},
+    ClientId, QuinnetSyncUpdate,
+};
+
+use super::{
+    timestamp::ReceiveTimestamp, ConnectionLostEvent, Endpoint, QuinnetServer, ServerReceiveError,
+};
+
+/// Input of a client for a tick, received from its [`crate::client::input::InputStream`]. Raised once per tick, in increasing tick order for each client. Raised in the CoreStage::PreUpdate stage by the [`QuinnetInputPlugin`].
+#[derive(Event, Debug, Clone)]
+pub struct ClientInputEvent<T> {
+    /// Id of the client
+    pub id: ClientId,
+    /// Tick of the input, as counted by the client
+    pub tick: InputTick,
+    /// Input of the client for this tick
+    pub input: T,
+    /// Arrival of the message which delivered the input, the first one carrying this tick
+    pub timestamp: ReceiveTimestamp,
+}
+
+/// Receiving end of the input streams of the clients, on a channel. Dedupes the inputs repeated in the messages of the streams.
+///
+/// Inputs older than the last tick received from a client are dropped, including the inputs of a lost tick that arrive late.
+#[derive(Resource, Debug)]
+pub struct InputReceiver<T> {
+    channel_id: ChannelId,
+    last_ticks: HashMap<ClientId, InputTick>,
+    _input: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> InputReceiver<T> {
+    /// Receiver of the input streams sent on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            last_ticks: HashMap::new(),
+            _input: PhantomData,
+        }
+    }
+
+    /// Channel the inputs are received on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Tick of the last input received from `client_id`, if any
+    pub fn last_tick(&self, client_id: ClientId) -> Option<InputTick> {
+        self.last_ticks.get(&client_id).copied()
+    }
+
+    /// Receives the input messages of `client_id` and returns the inputs of the ticks not received yet, in increasing tick order.
+    ///
+    /// Messages that can't be deserialized are dropped.
+    ///
+    /// Can return an [`Err`] if:
+    /// - the connection is closed
+    /// - the client id is not valid
+    pub fn receive_from(
+        &mut self,
+        endpoint: &mut Endpoint,
+        client_id: ClientId,
+    ) -> Result<Vec<ClientInputEvent<T>>, ServerReceiveError> {
+        let mut received = Vec::new();
+        for (payload, timestamp) in
+            endpoint.receive_all_timestamped_from_on(client_id, self.channel_id)?
+        {
+            let Ok((last_tick, inputs)) = bincode::deserialize::<InputPacket<T>>(&payload) else {
+                continue;
+            };
+            let Some(ticks) = packet_ticks(last_tick, inputs.len()) else {
+                continue;
+            };
+            let previous_tick = self.last_ticks.get(&client_id).copied();
+            received.extend(
+                ticks
+                    .zip(inputs)
+                    .filter(|(tick, _)| previous_tick.is_none_or(|previous| *tick > previous))
+                    .map(|(tick, input)| ClientInputEvent {
+                        id: client_id,
+                        tick,
+                        input,
+                        timestamp,
+                    }),
+            );
+            if previous_tick.is_none_or(|previous| last_tick > previous) {
+                self.last_ticks.insert(client_id, last_tick);
+            }
+        }
+        Ok(received)
+    }
+
+    /// Same as [`InputReceiver::receive_from`] for all the clients of the endpoint. Clients whose connection is closed are skipped.
+    pub fn receive(&mut self, endpoint: &mut Endpoint) -> Vec<ClientInputEvent<T>> {
+        endpoint
+            .clients()
+            .into_iter()
+            .filter_map(|client_id| self.receive_from(endpoint, client_id).ok())
+            .flatten()
+            .collect()
+    }
+
+    /// Forgets the last tick received from `client_id`, the next input of its stream is accepted whatever its tick
+    pub fn reset(&mut self, client_id: ClientId) {
+        self.last_ticks.remove(&client_id);
+    }
+}
+
+/// Receives the input streams of the clients on a channel, and raises a [`ClientInputEvent`] for each tick of input received.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetInputPlugin<T> {
+    channel_id: ChannelId,
+    schedule: InternedScheduleLabel,
+    _input: PhantomData<fn() -> T>,
+}
+
+impl<T> QuinnetInputPlugin<T> {
+    /// Plugin receiving the input streams sent on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            schedule: PreUpdate.intern(),
+            _input: PhantomData,
+        }
+    }
+
+    /// Receives the inputs in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl<T: DeserializeOwned + Send + Sync + 'static> Plugin for QuinnetInputPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClientInputEvent<T>>()
+            .insert_resource(InputReceiver::<T>::new(self.channel_id))
+            .add_systems(
+                self.schedule,
+                receive_client_inputs::<T>
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetServer>),
+            );
+    }
+}
+
+fn receive_client_inputs<T: DeserializeOwned + Send + Sync + 'static>(
+    mut server: ResMut<QuinnetServer>,
+    mut receiver: ResMut<InputReceiver<T>>,
+    mut connection_lost_events: EventReader<ConnectionLostEvent>,
+    mut input_events: EventWriter<ClientInputEvent<T>>,
+) {
+    for event in connection_lost_events.read() {
+        receiver.reset(event.id);
+    }
+    if let Some(endpoint) = server.get_endpoint_mut() {
+        input_events.write_batch(receiver.receive(endpoint));
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::shared::{
+    channels::ChannelId,
+    lockstep::{LockstepBundle, LockstepInputMessage, LockstepTick, DEFAULT_LOCKSTEP_TIMEOUT},
+    ClientId, QuinnetSyncUpdate,
+};
+
+use super::{Endpoint, QuinnetServer};
+
+/// Raised on the server when a lockstep tick is released after its timeout, without the inputs of some clients. Raised in the CoreStage::PreUpdate stage by the [`QuinnetServerLockstepPlugin`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct LockstepStallEvent {
+    /// Tick released without all the inputs
+    pub tick: LockstepTick,
+    /// Connected clients whose input was missing, ordered by client id
+    pub missing: Vec<ClientId>,
+}
+
+/// Server side of a lockstep simulation, on a channel: collects the inputs sent by the clients with their [`crate::client::lockstep::LockstepClient`] and broadcasts a [`LockstepBundle`] for each tick.
+///
+/// A tick is released once all the connected clients sent their input for it, or once it waited longer than the timeout, since its first input was received or since the previous tick was released. Ticks are released in order, starting at tick 0: a tick is never released before the previous one, and the inputs of the ticks already released are dropped.
+///
+/// The channel must be reliable and ordered, such as [`crate::shared::channels::ChannelKind::OrderedReliable`], and dedicated to the lockstep simulation: all the messages received on it are consumed.
+#[derive(Resource, Debug)]
+pub struct LockstepServer<T> {
+    channel_id: ChannelId,
+    timeout: Duration,
+    next_tick: LockstepTick,
+    /// Since when the next tick is waited for, once one of its inputs was received
+    waiting_since: Option<Instant>,
+    pending: BTreeMap<LockstepTick, BTreeMap<ClientId, T>>,
+    _input: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> LockstepServer<T> {
+    /// Lockstep simulation on `channel_id`, waiting [`DEFAULT_LOCKSTEP_TIMEOUT`] for the missing inputs of a tick
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            timeout: DEFAULT_LOCKSTEP_TIMEOUT,
+            next_tick: 0,
+            waiting_since: None,
+            pending: BTreeMap::new(),
+            _input: PhantomData,
+        }
+    }
+
+    /// Waits `timeout` for the missing inputs of a tick before releasing it without them
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Channel the inputs are received and the bundles are sent on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Time waited for the missing inputs of a tick
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Tick of the next bundle to release
+    pub fn next_tick(&self) -> LockstepTick {
+        self.next_tick
+    }
+
+    /// Receives the inputs of the clients of the endpoint. A client's first input for a tick is kept, the next ones are dropped.
+    ///
+    /// Messages that can't be deserialized are dropped. Clients whose connection is closed are skipped.
+    pub fn receive(&mut self, endpoint: &mut Endpoint) {
+        for client_id in endpoint.clients() {
+            let Ok(payloads) = endpoint.receive_all_from_on(client_id, self.channel_id) else {
+                continue;
+            };
+            for payload in payloads {
+                let Ok((tick, input)) = bincode::deserialize::<LockstepInputMessage<T>>(&payload)
+                else {
+                    continue;
+                };
+                if tick < self.next_tick {
+                    continue;
+                }
+                if tick == self.next_tick && self.waiting_since.is_none() {
+                    self.waiting_since = Some(Instant::now());
+                }
+                self.pending
+                    .entry(tick)
+                    .or_default()
+                    .entry(client_id)
+                    .or_insert(input);
+            }
+        }
+    }
+
+    /// Releases the ticks whose inputs are complete or which timed out, in order, and broadcasts their bundles to the clients of the endpoint. Returns the released bundles.
+    pub fn release(&mut self, endpoint: &mut Endpoint) -> Vec<LockstepBundle<T>> {
+        let client_ids = endpoint.clients();
+        let mut bundles = Vec::new();
+        while let Some(inputs) = self.pending.get(&self.next_tick) {
+            let mut missing: Vec<ClientId> = client_ids
+                .iter()
+                .filter(|client_id| !inputs.contains_key(client_id))
+                .copied()
+                .collect();
+            let waiting_since = *self.waiting_since.get_or_insert_with(Instant::now);
+            if !missing.is_empty() && waiting_since.elapsed() < self.timeout {
+                break;
+            }
+            missing.sort_unstable();
+            let bundle = LockstepBundle {
+                tick: self.next_tick,
+                inputs: self
+                    .pending
+                    .remove(&self.next_tick)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect(),
+                missing,
+            };
+            endpoint.try_broadcast_message_on(self.channel_id, &bundle);
+            self.next_tick = self.next_tick.wrapping_add(1);
+            self.waiting_since = None;
+            bundles.push(bundle);
+        }
+        bundles
+    }
+
+    /// Restarts the simulation at `next_tick`, dropping the pending inputs
+    pub fn reset(&mut self, next_tick: LockstepTick) {
+        self.next_tick = next_tick;
+        self.waiting_since = None;
+        self.pending.clear();
+    }
+}
+
+/// Runs a lockstep simulation on a channel with a [`LockstepServer`] resource, and raises a [`LockstepBundle`] event for each released tick and a [`LockstepStallEvent`] for each tick released after its timeout.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetServerLockstepPlugin<T> {
+    channel_id: ChannelId,
+    timeout: Duration,
+    schedule: InternedScheduleLabel,
+    _input: PhantomData<fn() -> T>,
+}
+
+impl<T> QuinnetServerLockstepPlugin<T> {
+    /// Plugin running the lockstep simulation on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            timeout: DEFAULT_LOCKSTEP_TIMEOUT,
+            schedule: PreUpdate.intern(),
+            _input: PhantomData,
+        }
+    }
+
+    /// See [`LockstepServer::with_timeout`]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Releases the ticks in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + 'static> Plugin
+    for QuinnetServerLockstepPlugin<T>
+{
+    fn build(&self, app: &mut App) {
+        app.add_event::<LockstepBundle<T>>()
+            .add_event::<LockstepStallEvent>()
+            .insert_resource(LockstepServer::<T>::new(self.channel_id).with_timeout(self.timeout))
+            .add_systems(
+                self.schedule,
+                release_lockstep_ticks::<T>
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetServer>),
+            );
+    }
+}
+
+fn release_lockstep_ticks<T: Serialize + DeserializeOwned + Send + Sync + 'static>(
+    mut server: ResMut<QuinnetServer>,
+    mut lockstep: ResMut<LockstepServer<T>>,
+    mut bundle_events: EventWriter<LockstepBundle<T>>,
+    mut stall_events: EventWriter<LockstepStallEvent>,
+) {
+    let Some(endpoint) = server.get_endpoint_mut() else {
+        return;
+    };
+    lockstep.receive(endpoint);
+    for bundle in lockstep.release(endpoint) {
+        if bundle.stalled() {
+            stall_events.write(LockstepStallEvent {
+                tick: bundle.tick,
+                missing: bundle.missing.clone(),
+            });
+        }
+        bundle_events.write(bundle);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use crate::shared::{
+    channels::ChannelId,
+    master::{
+        MasterRequest, MasterResponse, ServerInfo, ServerListing, DEFAULT_MASTER_LISTING_TIMEOUT,
+    },
+    ClientId, QuinnetSyncUpdate,
+};
+
+use super::{Endpoint, QuinnetServer};
+
+/// Raised on a master server when a server registers. Raised in the CoreStage::PreUpdate stage by the [`QuinnetMasterServerPlugin`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ServerRegisteredEvent {
+    /// Id of the connection of the server to the master server
+    pub id: ClientId,
+    /// Listing of the server
+    pub listing: ServerListing,
+}
+
+/// Raised on a master server when a server unregisters, disconnects or times out. Raised in the CoreStage::PreUpdate stage by the [`QuinnetMasterServerPlugin`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ServerUnregisteredEvent {
+    /// Id of the connection of the server to the master server
+    pub id: ClientId,
+    /// Address the server was listed at
+    pub addr: SocketAddr,
+}
+
+#[derive(Debug)]
+struct Registration {
+    listing: ServerListing,
+    last_heartbeat: Instant,
+}
+
+/// Master server run by a Quinnet server: lists the game servers registered with their [`crate::client::master::MasterRegistration`], and answers the queries of the [`crate::client::master::ServerBrowser`]s.
+///
+/// The game servers and the players connect to the master server as clients. A registered server is dropped from the list when it unregisters, disconnects, or stops sending heartbeats for longer than the listing timeout.
+///
+/// The channel must be reliable and dedicated to the master server protocol: all the messages received on it are consumed.
+#[derive(Resource, Debug)]
+pub struct MasterServer {
+    channel_id: ChannelId,
+    listing_timeout: Duration,
+    registrations: HashMap<ClientId, Registration>,
+}
+
+impl MasterServer {
+    /// Master server on `channel_id`, dropping the servers silent for [`DEFAULT_MASTER_LISTING_TIMEOUT`]
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            listing_timeout: DEFAULT_MASTER_LISTING_TIMEOUT,
+            registrations: HashMap::new(),
+        }
+    }
+
+    /// Drops the servers which sent no heartbeat for `listing_timeout`
+    pub fn with_listing_timeout(mut self, listing_timeout: Duration) -> Self {
+        self.listing_timeout = listing_timeout;
+        self
+    }
+
+    /// Channel of the master server protocol
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Returns the listings of the registered servers
+    pub fn listings(&self) -> Vec<ServerListing> {
+        self.registrations
+            .values()
+            .map(|registration| registration.listing.clone())
+            .collect()
+    }
+
+    /// Receives the requests of the clients of the endpoint: registers and unregisters the servers, answers the queries, and drops the servers which timed out. Returns the servers registered and unregistered.
+    ///
+    /// Requests that can't be deserialized are dropped.
+    pub fn update(
+        &mut self,
+        endpoint: &mut Endpoint,
+    ) -> (Vec<ServerRegisteredEvent>, Vec<ServerUnregisteredEvent>) {
+        let now = Instant::now();
+        let client_ids = endpoint.clients();
+        let (mut registered, mut unregistered) = (Vec::new(), Vec::new());
+        self.registrations.retain(|client_id, registration| {
+            let listed = client_ids.contains(client_id)
+                && now.duration_since(registration.last_heartbeat) < self.listing_timeout;
+            if !listed {
+                unregistered.push(ServerUnregisteredEvent {
+                    id: *client_id,
+                    addr: registration.listing.addr,
+                });
+            }
+            listed
+        });
+
+        for client_id in client_ids {
+            let Ok(payloads) = endpoint.receive_all_from_on(client_id, self.channel_id) else {
+                continue;
+            };
+            for payload in payloads {
+                match bincode::deserialize::<MasterRequest>(&payload) {
+                    Ok(MasterRequest::Register(info)) => {
+                        let Some(addr) = listing_addr(endpoint, client_id, &info) else {
+                            warn!(
+                                "Server {} has no address to be listed at, its registration is ignored",
+                                client_id
+                            );
+                            continue;
+                        };
+                        let listing = ServerListing { addr, info };
+                        let previous = self.registrations.insert(
+                            client_id,
+                            Registration {
+                                listing: listing.clone(),
+                                last_heartbeat: now,
+                            },
+                        );
+                        if previous.is_none() {
+                            registered.push(ServerRegisteredEvent {
+                                id: client_id,
+                                listing,
+                            });
+                        }
+                    }
+                    Ok(MasterRequest::Unregister) => {
+                        if let Some(registration) = self.registrations.remove(&client_id) {
+                            unregistered.push(ServerUnregisteredEvent {
+                                id: client_id,
+                                addr: registration.listing.addr,
+                            });
+                        }
+                    }
+                    Ok(MasterRequest::Query) => endpoint.try_send_message_on(
+                        client_id,
+                        self.channel_id,
+                        MasterResponse::List(self.listings()),
+                    ),
+                    Err(_) => (),
+                }
+            }
+        }
+        (registered, unregistered)
+    }
+}
+
+fn listing_addr(endpoint: &Endpoint, client_id: ClientId, info: &ServerInfo) -> Option<SocketAddr> {
+    info.public_addr.or_else(|| {
+        endpoint
+            .get_connection(client_id)
+            .and_then(|connection| connection.remote_address())
+            .map(|remote| SocketAddr::new(remote.ip(), info.port))
+    })
+}
+
+/// Runs a master server with a [`MasterServer`] resource, and raises a [`ServerRegisteredEvent`] for each server registered and a [`ServerUnregisteredEvent`] for each server unregistered.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetMasterServerPlugin {
+    channel_id: ChannelId,
+    listing_timeout: Duration,
+    schedule: InternedScheduleLabel,
+}
+
+impl QuinnetMasterServerPlugin {
+    /// Plugin running the master server on `channel_id`
+    pub fn new<C: Into<ChannelId>>(channel_id: C) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            listing_timeout: DEFAULT_MASTER_LISTING_TIMEOUT,
+            schedule: PreUpdate.intern(),
+        }
+    }
+
+    /// See [`MasterServer::with_listing_timeout`]
+    pub fn with_listing_timeout(mut self, listing_timeout: Duration) -> Self {
+        self.listing_timeout = listing_timeout;
+        self
+    }
+
+    /// Updates the master server in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetMasterServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ServerRegisteredEvent>()
+            .add_event::<ServerUnregisteredEvent>()
+            .insert_resource(
+                MasterServer::new(self.channel_id).with_listing_timeout(self.listing_timeout),
+            )
+            .add_systems(
+                self.schedule,
+                update_master_server
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetServer>),
+            );
+    }
+}
+
+fn update_master_server(
+    mut server: ResMut<QuinnetServer>,
+    mut master: ResMut<MasterServer>,
+    mut registered_events: EventWriter<ServerRegisteredEvent>,
+    mut unregistered_events: EventWriter<ServerUnregisteredEvent>,
+) {
+    if let Some(endpoint) = server.get_endpoint_mut() {
+        let (registered, unregistered) = master.update(endpoint);
+        registered_events.write_batch(registered);
+        unregistered_events.write_batch(unregistered);
+    }
+}
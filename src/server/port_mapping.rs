@@ -0,0 +1,284 @@
+use std::{
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use tokio::sync::{broadcast, mpsc};
+
+use super::{PortMappingError, ServerAsyncMessage};
+
+/// Default lifetime requested for a port mapping. The mapping is renewed at half its lifetime while the endpoint is running.
+pub const DEFAULT_PORT_MAPPING_LIFETIME: Duration = Duration::from_secs(7200);
+/// Minimum lifetime requested for a port mapping, shorter lifetimes are raised to it. NAT-PMP counts lifetimes in seconds, and a lifetime of 0 deletes the mapping.
+pub const MIN_PORT_MAPPING_LIFETIME: Duration = Duration::from_secs(2);
+
+const NAT_PMP_PORT: u16 = 5351;
+const NAT_PMP_VERSION: u8 = 0;
+const OPCODE_EXTERNAL_ADDRESS: u8 = 0;
+const OPCODE_MAP_UDP: u8 = 1;
+const RESPONSE_OPCODE_OFFSET: u8 = 128;
+const RESULT_SUCCESS: u16 = 0;
+/// Initial retransmission delay, doubled on each attempt (RFC 6886 section 3.1)
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_ATTEMPTS: usize = 4;
+/// Minimum delay between two renewals of a mapping, whatever the lifetime granted by the gateway
+const MIN_RENEW_DELAY: Duration = Duration::from_secs(1);
+
+/// Configuration of the NAT-PMP (RFC 6886) port mapping requested by a server endpoint, see [`crate::server::ServerEndpointConfiguration::with_port_mapping`].
+///
+/// Only NAT-PMP (and PCP gateways compatible with it) is supported, UPnP IGD gateways are not.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PortMappingConfiguration {
+    gateway: Option<Ipv4Addr>,
+    external_port: Option<u16>,
+    lifetime: Duration,
+}
+
+impl Default for PortMappingConfiguration {
+    fn default() -> Self {
+        Self {
+            gateway: None,
+            external_port: None,
+            lifetime: DEFAULT_PORT_MAPPING_LIFETIME,
+        }
+    }
+}
+
+impl PortMappingConfiguration {
+    /// Sets the address of the gateway. By default, the default gateway of the system is used (only detected on Linux).
+    pub fn with_gateway(mut self, gateway: Ipv4Addr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Sets the external port suggested to the gateway. By default, the local port of the endpoint is suggested. The gateway may assign a different port.
+    pub fn with_external_port(mut self, external_port: u16) -> Self {
+        self.external_port = Some(external_port);
+        self
+    }
+
+    /// Sets the lifetime requested for the mapping, see [`DEFAULT_PORT_MAPPING_LIFETIME`]. Raised to [`MIN_PORT_MAPPING_LIFETIME`] if shorter.
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime.max(MIN_PORT_MAPPING_LIFETIME);
+        self
+    }
+}
+
+/// Raised when a port mapping was established or renewed on the gateway. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct PortMappingSucceededEvent {
+    /// The established mapping
+    pub mapping: PortMapping,
+}
+
+/// Raised when a port mapping could not be established or renewed on the gateway. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug)]
+pub struct PortMappingFailedEvent {
+    /// Error raised while mapping the port
+    pub error: PortMappingError,
+}
+
+/// A port mapping established on the gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortMapping {
+    /// Address of the gateway
+    pub gateway: Ipv4Addr,
+    /// Local port of the endpoint
+    pub internal_port: u16,
+    /// Address on which the endpoint can be reached from outside the local network
+    pub external_addr: SocketAddrV4,
+    /// Lifetime granted by the gateway
+    pub lifetime: Duration,
+}
+
+/// Returns the default IPv4 gateway of the system, read from `/proc/net/route`.
+///
+/// Always returns `None` on systems other than Linux.
+pub fn default_gateway() -> Option<Ipv4Addr> {
+    let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match (fields.get(1), fields.get(2)) {
+            (Some(&"00000000"), Some(gateway)) => u32::from_str_radix(gateway, 16)
+                .ok()
+                .map(|gateway| Ipv4Addr::from(gateway.to_le_bytes())),
+            _ => None,
+        }
+    })
+}
+
+/// Sends `request` to the gateway and returns the payload of the matching response (after the version, opcode and result code)
+fn nat_pmp_request(
+    gateway: Ipv4Addr,
+    request: &[u8],
+    response_len: usize,
+) -> Result<Vec<u8>, PortMappingError> {
+    let gateway_addr = SocketAddr::from((gateway, NAT_PMP_PORT));
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect(gateway_addr)?;
+
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut buf = [0; 16];
+    for _ in 0..MAX_ATTEMPTS {
+        socket.send(request)?;
+        socket.set_read_timeout(Some(delay))?;
+        match socket.recv(&mut buf) {
+            Ok(len) => {
+                let response = &buf[..len];
+                if len < response_len
+                    || response[0] != NAT_PMP_VERSION
+                    || response[1] != request[1] + RESPONSE_OPCODE_OFFSET
+                {
+                    return Err(PortMappingError::InvalidResponse);
+                }
+                let result_code = u16::from_be_bytes([response[2], response[3]]);
+                if result_code != RESULT_SUCCESS {
+                    return Err(PortMappingError::Refused(result_code));
+                }
+                return Ok(response[4..response_len].to_vec());
+            }
+            Err(err)
+                if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut =>
+            {
+                delay *= 2;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Err(PortMappingError::Timeout)
+}
+
+/// Blocking. A `lifetime` of 0 deletes the mapping.
+fn map_udp_port(
+    gateway: Ipv4Addr,
+    internal_port: u16,
+    external_port: u16,
+    lifetime: Duration,
+) -> Result<(u16, Duration), PortMappingError> {
+    let mut request = vec![NAT_PMP_VERSION, OPCODE_MAP_UDP, 0, 0];
+    request.extend_from_slice(&internal_port.to_be_bytes());
+    request.extend_from_slice(&external_port.to_be_bytes());
+    request.extend_from_slice(&(lifetime.as_secs().min(u32::MAX as u64) as u32).to_be_bytes());
+
+    // EPOCH (4) | INTERNAL PORT (2) | EXTERNAL PORT (2) | LIFETIME (4)
+    let response = nat_pmp_request(gateway, &request, 16)?;
+    let mapped_port = u16::from_be_bytes([response[6], response[7]]);
+    let granted_lifetime =
+        u32::from_be_bytes([response[8], response[9], response[10], response[11]]);
+    Ok((mapped_port, Duration::from_secs(granted_lifetime as u64)))
+}
+
+/// Blocking
+fn request_external_ip(gateway: Ipv4Addr) -> Result<Ipv4Addr, PortMappingError> {
+    // EPOCH (4) | EXTERNAL IP (4)
+    let response = nat_pmp_request(gateway, &[NAT_PMP_VERSION, OPCODE_EXTERNAL_ADDRESS], 12)?;
+    Ok(Ipv4Addr::new(
+        response[4],
+        response[5],
+        response[6],
+        response[7],
+    ))
+}
+
+fn create_mapping(
+    gateway: Ipv4Addr,
+    internal_port: u16,
+    config: &PortMappingConfiguration,
+) -> Result<PortMapping, PortMappingError> {
+    let external_ip = request_external_ip(gateway)?;
+    let (external_port, lifetime) = map_udp_port(
+        gateway,
+        internal_port,
+        config.external_port.unwrap_or(internal_port),
+        // Also bounds the lifetimes of deserialized configurations
+        config.lifetime.max(MIN_PORT_MAPPING_LIFETIME),
+    )?;
+    // A mapping granted for 0 seconds is a deleted mapping
+    if lifetime.is_zero() {
+        return Err(PortMappingError::NoLifetime);
+    }
+    Ok(PortMapping {
+        gateway,
+        internal_port,
+        external_addr: SocketAddrV4::new(external_ip, external_port),
+        lifetime,
+    })
+}
+
+/// Creates the port mapping, renews it until the endpoint closes and then deletes it.
+pub(crate) async fn port_mapping_task(
+    config: PortMappingConfiguration,
+    internal_port: u16,
+    to_sync_endpoint_send: mpsc::Sender<ServerAsyncMessage>,
+    mut endpoint_close_recv: broadcast::Receiver<()>,
+) {
+    let Some(gateway) = config.gateway.or_else(default_gateway) else {
+        let _ = to_sync_endpoint_send
+            .send(ServerAsyncMessage::PortMapping(Err(
+                PortMappingError::NoGateway,
+            )))
+            .await;
+        return;
+    };
+
+    let mut mapped = false;
+    loop {
+        let task_config = config.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            create_mapping(gateway, internal_port, &task_config)
+        })
+        .await
+        .expect("Port mapping task should not panic");
+
+        let renew_delay = match &result {
+            Ok(mapping) => {
+                info!(
+                    "Mapped port {} to {} on gateway {}",
+                    internal_port, mapping.external_addr, gateway
+                );
+                mapped = true;
+                Some((mapping.lifetime / 2).max(MIN_RENEW_DELAY))
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to map port {} on gateway {}: {}",
+                    internal_port, gateway, err
+                );
+                None
+            }
+        };
+        if to_sync_endpoint_send
+            .send(ServerAsyncMessage::PortMapping(result))
+            .await
+            .is_err()
+        {
+            break;
+        }
+        let Some(renew_delay) = renew_delay else {
+            break;
+        };
+        tokio::select! {
+            _ = endpoint_close_recv.recv() => break,
+            _ = tokio::time::sleep(renew_delay) => {}
+        }
+    }
+
+    if mapped {
+        let result = tokio::task::spawn_blocking(move || {
+            map_udp_port(gateway, internal_port, 0, Duration::ZERO)
+        })
+        .await;
+        match result {
+            Ok(Ok(_)) => info!("Removed port mapping of port {}", internal_port),
+            Ok(Err(err)) => warn!(
+                "Failed to remove port mapping of port {}: {}",
+                internal_port, err
+            ),
+            Err(_) => (),
+        }
+    }
+}
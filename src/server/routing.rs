@@ -0,0 +1,69 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use bevy::prelude::Resource;
+use bytes::Bytes;
+
+use crate::shared::{channels::ChannelId, ClientId};
+
+/// Payload of a client received on a routed channel or from a client of an instance, see [`crate::server::Endpoint::route_channels`] and [`crate::server::Endpoint::open_instance`]
+#[derive(Debug, Clone)]
+pub struct RoutedPayload {
+    /// Id of the client who sent the payload
+    pub client_id: ClientId,
+    /// Channel the payload was received on
+    pub channel_id: ChannelId,
+    /// Received payload
+    pub payload: Bytes,
+    /// When the payload arrived from the network, on the server's monotonic clock
+    pub received_at: Instant,
+}
+
+/// Receiving end of the payloads of routed channels or of the clients of an instance, see [`crate::server::Endpoint::route_channels`] and [`crate::server::Endpoint::open_instance`].
+///
+/// Can be inserted as a resource in another world, such as a sub-app running the simulation, or moved to another thread: the payloads never go through the main world. Dropping it ends the route, the payloads of its channels or clients are then delivered by the [`crate::server::Endpoint`] again.
+#[derive(Debug, Resource)]
+pub struct PayloadRoute {
+    recv: Mutex<Receiver<RoutedPayload>>,
+}
+
+impl PayloadRoute {
+    pub(crate) fn new() -> (Sender<RoutedPayload>, Self) {
+        let (send, recv) = mpsc::channel();
+        (
+            send,
+            Self {
+                recv: Mutex::new(recv),
+            },
+        )
+    }
+
+    /// Returns the next routed payload, if any, in the order they were routed
+    pub fn try_recv(&self) -> Option<RoutedPayload> {
+        match self.recv.lock() {
+            Ok(recv) => recv.try_recv().ok(),
+            Err(_) => None,
+        }
+    }
+
+    /// Returns all the payloads routed so far, in the order they were routed
+    pub fn drain(&self) -> Vec<RoutedPayload> {
+        match self.recv.lock() {
+            Ok(recv) => recv.try_iter().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Blocks until a payload is routed, for a dedicated thread. Returns `None` once the endpoint is stopped or its routes to this [`PayloadRoute`] are removed.
+    pub fn recv(&self) -> Option<RoutedPayload> {
+        match self.recv.lock() {
+            Ok(recv) => recv.recv().ok(),
+            Err(_) => None,
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use crate::shared::{channels::ChannelId, ClientId, QuinnetSyncUpdate};
+
+use super::{slow_clients::SlowClientDetection, Endpoint, QuinnetServer};
+
+/// Default round-trip time above which the send rate of a client is decreased
+pub const DEFAULT_SEND_RATE_TARGET_RTT: Duration = Duration::from_millis(200);
+/// Default ratio of lost packets above which the send rate of a client is decreased
+pub const DEFAULT_SEND_RATE_LOSS_THRESHOLD: f32 = 0.02;
+/// Default factor applied to the send rate of a degraded client
+pub const DEFAULT_SEND_RATE_DECREASE_FACTOR: f32 = 0.75;
+/// Default increase of the send rate of a healthy client at each sample, in Hz
+pub const DEFAULT_SEND_RATE_INCREASE_STEP: f32 = 1.;
+/// Default period between two samples of the stats of a client
+pub const DEFAULT_SEND_RATE_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Raised when the send rate of a channel changes for a client, by the [`QuinnetSendRatePlugin`]
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct SendRateChangedEvent {
+    /// Id of the client
+    pub client_id: ClientId,
+    /// Channel the rate applies to
+    pub channel_id: ChannelId,
+    /// New send rate, in Hz
+    pub rate: f32,
+}
+
+#[derive(Debug, Clone)]
+struct ClientSendRate {
+    rate: f32,
+    sampled_at: Instant,
+    sent_packets: u64,
+    lost_packets: u64,
+    congestion_events: u64,
+    last_send: Option<Instant>,
+}
+
+/// Send rate of a channel adjusted for each client to its connection, such as the frequency of the snapshots.
+///
+/// Sampling the stats of the connections, the rate of a client is multiplied by a decrease factor when its round-trip time exceeds a target, when its ratio of lost packets exceeds a threshold, when its congestion controller reports congestion or when it is slow with [`crate::server::slow_clients::SlowClientDetection::throttle_send_rates`], and increased by a step otherwise (additive increase, multiplicative decrease), within bounds. The clients start at the max rate.
+#[derive(Debug, Clone)]
+pub struct AdaptiveSendRate {
+    min_rate: f32,
+    max_rate: f32,
+    target_rtt: Duration,
+    loss_threshold: f32,
+    decrease_factor: f32,
+    increase_step: f32,
+    sample_interval: Duration,
+    clients: HashMap<ClientId, ClientSendRate>,
+}
+
+impl AdaptiveSendRate {
+    /// Send rate between `min_rate` and `max_rate`, in Hz
+    pub fn new(min_rate: f32, max_rate: f32) -> Self {
+        let min_rate = min_rate.max(f32::MIN_POSITIVE);
+        Self {
+            min_rate,
+            max_rate: max_rate.max(min_rate),
+            target_rtt: DEFAULT_SEND_RATE_TARGET_RTT,
+            loss_threshold: DEFAULT_SEND_RATE_LOSS_THRESHOLD,
+            decrease_factor: DEFAULT_SEND_RATE_DECREASE_FACTOR,
+            increase_step: DEFAULT_SEND_RATE_INCREASE_STEP,
+            sample_interval: DEFAULT_SEND_RATE_SAMPLE_INTERVAL,
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Decreases the rate of the clients whose round-trip time exceeds `target_rtt`, including the latency of their [`crate::server::conditions::ClientConditions`]
+    pub fn with_target_rtt(mut self, target_rtt: Duration) -> Self {
+        self.target_rtt = target_rtt;
+        self
+    }
+
+    /// Decreases the rate of the clients losing more than this ratio of their packets
+    pub fn with_loss_threshold(mut self, loss_threshold: f32) -> Self {
+        self.loss_threshold = loss_threshold;
+        self
+    }
+
+    /// Multiplies the rate of the degraded clients by `decrease_factor`, between 0 and 1
+    pub fn with_decrease_factor(mut self, decrease_factor: f32) -> Self {
+        self.decrease_factor = decrease_factor.clamp(0., 1.);
+        self
+    }
+
+    /// Increases the rate of the healthy clients by `increase_step` Hz at each sample
+    pub fn with_increase_step(mut self, increase_step: f32) -> Self {
+        self.increase_step = increase_step.max(0.);
+        self
+    }
+
+    /// Samples the stats of each client at most once every `sample_interval`
+    pub fn with_sample_interval(mut self, sample_interval: Duration) -> Self {
+        self.sample_interval = sample_interval;
+        self
+    }
+
+    /// Current send rate of a client, in Hz. The max rate for a client not sampled yet
+    pub fn rate(&self, client_id: ClientId) -> f32 {
+        self.clients
+            .get(&client_id)
+            .map_or(self.max_rate, |client| client.rate)
+    }
+
+    /// Current period between two sends to a client
+    pub fn interval(&self, client_id: ClientId) -> Duration {
+        Duration::from_secs_f32(1. / self.rate(client_id))
+    }
+
+    /// Returns true if a period of the current rate elapsed since the last send to the client, see [`AdaptiveSendRate::mark_sent`]
+    pub fn is_due(&self, client_id: ClientId) -> bool {
+        match self
+            .clients
+            .get(&client_id)
+            .and_then(|client| client.last_send)
+        {
+            Some(last_send) => last_send.elapsed() >= self.interval(client_id),
+            None => true,
+        }
+    }
+
+    /// Records a send to the client, see [`AdaptiveSendRate::is_due`]
+    pub fn mark_sent(&mut self, client_id: ClientId) {
+        let now = Instant::now();
+        self.clients
+            .entry(client_id)
+            .or_insert_with(|| ClientSendRate {
+                rate: self.max_rate,
+                sampled_at: now,
+                sent_packets: 0,
+                lost_packets: 0,
+                congestion_events: 0,
+                last_send: None,
+            })
+            .last_send = Some(now);
+    }
+
+    /// Samples the stats of the clients of the endpoint and adjusts their rates. Returns the clients whose rate changed, with their new rate.
+    ///
+    /// The disconnected clients are forgotten.
+    pub fn update(&mut self, endpoint: &Endpoint) -> Vec<(ClientId, f32)> {
+        let now = Instant::now();
+        let client_ids = endpoint.clients();
+        self.clients
+            .retain(|client_id, _| client_ids.contains(client_id));
+
+        let mut changes = Vec::new();
+        for client_id in client_ids {
+            let Some(connection) = endpoint.get_connection(client_id) else {
+                continue;
+            };
+            let stats = connection.connection_stats();
+            let client = self
+                .clients
+                .entry(client_id)
+                .or_insert_with(|| ClientSendRate {
+                    rate: self.max_rate,
+                    sampled_at: now,
+                    sent_packets: stats.path.sent_packets,
+                    lost_packets: stats.path.lost_packets,
+                    congestion_events: stats.path.congestion_events,
+                    last_send: None,
+                });
+            if now.duration_since(client.sampled_at) < self.sample_interval {
+                continue;
+            }
+            let sent = stats.path.sent_packets.saturating_sub(client.sent_packets);
+            let lost = stats.path.lost_packets.saturating_sub(client.lost_packets);
+            let congested = stats.path.congestion_events > client.congestion_events;
+            client.sampled_at = now;
+            client.sent_packets = stats.path.sent_packets;
+            client.lost_packets = stats.path.lost_packets;
+            client.congestion_events = stats.path.congestion_events;
+
+            let lossy = sent > 0 && lost as f32 / sent as f32 > self.loss_threshold;
+            let throttled = connection.is_slow()
+                && endpoint
+                    .slow_client_detection()
+                    .is_some_and(SlowClientDetection::throttles_send_rates);
+            let rate = match congested
+                || lossy
+                || throttled
+                || connection.round_trip_time() > self.target_rtt
+            {
+                true => (client.rate * self.decrease_factor).max(self.min_rate),
+                false => (client.rate + self.increase_step).min(self.max_rate),
+            };
+            if rate != client.rate {
+                client.rate = rate;
+                changes.push((client_id, rate));
+            }
+        }
+        changes
+    }
+}
+
+/// Send rates of the channels of the server, see [`AdaptiveSendRate`]
+#[derive(Resource, Debug, Clone, Default)]
+pub struct AdaptiveSendRates {
+    channels: HashMap<ChannelId, AdaptiveSendRate>,
+}
+
+impl AdaptiveSendRates {
+    /// Adapts the send rate of a channel, replacing its previous [`AdaptiveSendRate`] if any
+    pub fn insert<C: Into<ChannelId>>(&mut self, channel_id: C, send_rate: AdaptiveSendRate) {
+        self.channels.insert(channel_id.into(), send_rate);
+    }
+
+    /// Stops adapting the send rate of a channel
+    pub fn remove<C: Into<ChannelId>>(&mut self, channel_id: C) -> Option<AdaptiveSendRate> {
+        self.channels.remove(&channel_id.into())
+    }
+
+    /// Returns the send rate of a channel, if adapted
+    pub fn get<C: Into<ChannelId>>(&self, channel_id: C) -> Option<&AdaptiveSendRate> {
+        self.channels.get(&channel_id.into())
+    }
+
+    /// Returns the send rate of a channel as mut, if adapted
+    pub fn get_mut<C: Into<ChannelId>>(&mut self, channel_id: C) -> Option<&mut AdaptiveSendRate> {
+        self.channels.get_mut(&channel_id.into())
+    }
+
+    /// Updates the send rates of all the channels, see [`AdaptiveSendRate::update`]
+    pub fn update(&mut self, endpoint: &Endpoint) -> Vec<SendRateChangedEvent> {
+        let mut events = Vec::new();
+        for (channel_id, send_rate) in self.channels.iter_mut() {
+            events.extend(
+                send_rate
+                    .update(endpoint)
+                    .into_iter()
+                    .map(|(client_id, rate)| SendRateChangedEvent {
+                        client_id,
+                        channel_id: *channel_id,
+                        rate,
+                    }),
+            );
+        }
+        events
+    }
+}
+
+/// Updates the [`AdaptiveSendRates`] resource and raises a [`SendRateChangedEvent`] for each change of rate.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetSendRatePlugin {
+    schedule: InternedScheduleLabel,
+}
+
+impl Default for QuinnetSendRatePlugin {
+    fn default() -> Self {
+        Self {
+            schedule: PreUpdate.intern(),
+        }
+    }
+}
+
+impl QuinnetSendRatePlugin {
+    /// Updates the send rates in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetSendRatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SendRateChangedEvent>()
+            .init_resource::<AdaptiveSendRates>()
+            .add_systems(
+                self.schedule,
+                update_send_rates
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetServer>),
+            );
+    }
+}
+
+fn update_send_rates(
+    server: Res<QuinnetServer>,
+    mut send_rates: ResMut<AdaptiveSendRates>,
+    mut events: EventWriter<SendRateChangedEvent>,
+) {
+    if let Some(endpoint) = server.get_endpoint() {
+        events.write_batch(send_rates.update(endpoint));
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::shared::ClientId;
+
+/// Detection of the clients reading their messages slower than the server sends them, see [`crate::server::Endpoint::set_slow_client_detection`].
+///
+/// The messages of the reliable channels of a slow reader pile up in the outgoing queues of its connection, once QUIC's flow control stops the server from sending more. A client is slow once the messages waiting in the queues of its reliable channels exceed a threshold, see [`crate::server::ServerSideConnection::pending_reliable_bytes`]. It recovers once they fall back under half the threshold.
+///
+/// A [`SlowClientEvent`] is raised when a client becomes slow, for example to mark the player as lagging, and a [`SlowClientRecoveredEvent`] when it recovers. The detection can also act on its own, see [`SlowClientDetection::skip_unreliable`], [`SlowClientDetection::throttle_send_rates`] and [`SlowClientDetection::disconnect_after`].
+#[derive(Debug, Clone)]
+pub struct SlowClientDetection {
+    threshold: usize,
+    skip_unreliable: bool,
+    throttle_send_rates: bool,
+    disconnect_after: Option<Duration>,
+}
+
+impl SlowClientDetection {
+    /// A client is slow once more than `threshold` bytes wait in the queues of its reliable channels
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            skip_unreliable: false,
+            throttle_send_rates: false,
+            disconnect_after: None,
+        }
+    }
+
+    /// Drops the messages sent on the unreliable channels to the slow clients, leaving their bandwidth to the reliable channels. Their tracked messages are reported as lost.
+    pub fn skip_unreliable(mut self) -> Self {
+        self.skip_unreliable = true;
+        self
+    }
+
+    /// Decreases the rates of the [`crate::server::send_rate::AdaptiveSendRate`] of the slow clients, as for a degraded connection, such as the frequency of their snapshots
+    pub fn throttle_send_rates(mut self) -> Self {
+        self.throttle_send_rates = true;
+        self
+    }
+
+    /// Disconnects the clients which stay slow for `delay` with [`crate::shared::close::CloseCode::SlowClient`]
+    pub fn disconnect_after(mut self, delay: Duration) -> Self {
+        self.disconnect_after = Some(delay);
+        self
+    }
+
+    /// Bytes waiting in the queues of the reliable channels above which a client is slow
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns true if the messages of the unreliable channels are dropped for the slow clients
+    pub fn skips_unreliable(&self) -> bool {
+        self.skip_unreliable
+    }
+
+    /// Returns true if the send rates of the slow clients are decreased
+    pub fn throttles_send_rates(&self) -> bool {
+        self.throttle_send_rates
+    }
+
+    /// Time after which the slow clients are disconnected, if they are
+    pub fn disconnect_delay(&self) -> Option<Duration> {
+        self.disconnect_after
+    }
+}
+
+/// Raised once when a client becomes slow, see [`SlowClientDetection`]. Raised again if the client becomes slow again after recovering. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SlowClientEvent {
+    /// Id of the slow client
+    pub id: ClientId,
+    /// Bytes waiting in the queues of the reliable channels of the client
+    pub pending_bytes: usize,
+}
+
+/// Raised when a slow client recovered, see [`SlowClientDetection`]. Raised in the CoreStage::PreUpdate stage.
+#[derive(Event, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SlowClientRecoveredEvent {
+    /// Id of the client
+    pub id: ClientId,
+    /// Time during which the client was slow
+    pub slow_for: Duration,
+}
+
+/// Change of the state of a client, see [`ClientBacklog::check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BacklogChange {
+    Slow,
+    Recovered(Duration),
+    /// The client stayed slow for longer than [`SlowClientDetection::disconnect_after`]
+    Expired,
+}
+
+/// Slowness of a client
+#[derive(Debug, Default)]
+pub(crate) struct ClientBacklog {
+    slow_since: Option<Instant>,
+    skip_unreliable: bool,
+}
+
+impl ClientBacklog {
+    pub(crate) fn is_slow(&self) -> bool {
+        self.slow_since.is_some()
+    }
+
+    /// Returns true if the messages of the unreliable channels are dropped for this client
+    pub(crate) fn skips_unreliable(&self) -> bool {
+        self.skip_unreliable
+    }
+
+    pub(crate) fn check(
+        &mut self,
+        detection: &SlowClientDetection,
+        pending_bytes: usize,
+        now: Instant,
+    ) -> Option<BacklogChange> {
+        let change = match self.slow_since {
+            None if pending_bytes > detection.threshold => {
+                self.slow_since = Some(now);
+                Some(BacklogChange::Slow)
+            }
+            None => None,
+            Some(slow_since) if pending_bytes <= detection.threshold / 2 => {
+                self.slow_since = None;
+                Some(BacklogChange::Recovered(
+                    now.saturating_duration_since(slow_since),
+                ))
+            }
+            Some(slow_since) => detection
+                .disconnect_after
+                .filter(|delay| now.saturating_duration_since(slow_since) >= *delay)
+                .map(|_| BacklogChange::Expired),
+        };
+        self.skip_unreliable = self.is_slow() && detection.skip_unreliable;
+        change
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+use bytes::Bytes;
+use serde::Serialize;
+
+use crate::shared::{
+    channels::ChannelId, spectator::SpectatorMessage, ClientId, QuinnetSyncUpdate,
+};
+
+use super::{
+    Endpoint, QuinnetServer, ServerGroupMessageSendError, ServerGroupSendError,
+    ServerSpectatorError,
+};
+
+/// Step of a spectator in the join synchronization of a [`SpectatorStream`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectatorState {
+    /// The snapshot is being sent to the spectator
+    Snapshot,
+    /// The snapshot was sent, waiting for the spectator to acknowledge it
+    Synchronizing,
+    /// The spectator receives the live updates
+    Live,
+}
+
+/// Raised on the server when a spectator acknowledged its snapshot and starts receiving the live updates. Raised in the CoreStage::PreUpdate stage by the [`QuinnetServerSpectatorPlugin`].
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpectatorLiveEvent {
+    /// Id of the spectator
+    pub id: ClientId,
+}
+
+#[derive(Debug)]
+struct Spectator {
+    state: SpectatorState,
+    /// Live updates held until the spectator is live, in their order
+    pending: Vec<(ChannelId, Bytes)>,
+}
+
+/// One-way stream of the server to its spectators, such as the viewers of a match or the recorders of a replay.
+///
+/// A spectator joining late first receives a snapshot of the state, sent in parts on the snapshot channel, then the live updates. The stream holds the join synchronization barrier: the live updates broadcast with [`SpectatorStream::broadcast_payload_on`] are held for each spectator until it acknowledged its snapshot, then sent in their order. The live updates sent before the snapshot was complete are in the snapshot's past, the snapshot should therefore be taken when it starts being sent.
+///
+/// The snapshot channel must be reliable and ordered, and dedicated to the stream: all the messages received on it are consumed. The spectators' side is [`crate::client::spectator::SpectatorClient`].
+#[derive(Resource, Debug)]
+pub struct SpectatorStream {
+    snapshot_channel_id: ChannelId,
+    spectators: HashMap<ClientId, Spectator>,
+}
+
+impl SpectatorStream {
+    /// Stream sending the snapshots on `snapshot_channel_id`
+    pub fn new<C: Into<ChannelId>>(snapshot_channel_id: C) -> Self {
+        Self {
+            snapshot_channel_id: snapshot_channel_id.into(),
+            spectators: HashMap::new(),
+        }
+    }
+
+    /// Channel the snapshots are sent on
+    pub fn snapshot_channel_id(&self) -> ChannelId {
+        self.snapshot_channel_id
+    }
+
+    /// Adds a spectator to the stream, in the [`SpectatorState::Snapshot`] state: the live updates are held until its snapshot is sent and acknowledged.
+    ///
+    /// Adding a spectator again restarts its synchronization, its held updates are dropped.
+    pub fn add_spectator(&mut self, client_id: ClientId) {
+        self.spectators.insert(
+            client_id,
+            Spectator {
+                state: SpectatorState::Snapshot,
+                pending: Vec::new(),
+            },
+        );
+    }
+
+    /// Removes a spectator from the stream, its held updates are dropped. Returns false if it was not a spectator
+    pub fn remove_spectator(&mut self, client_id: ClientId) -> bool {
+        self.spectators.remove(&client_id).is_some()
+    }
+
+    /// Returns the state of a spectator, `None` if it is not a spectator of the stream
+    pub fn state(&self, client_id: ClientId) -> Option<SpectatorState> {
+        self.spectators
+            .get(&client_id)
+            .map(|spectator| spectator.state)
+    }
+
+    /// Returns the ids of the spectators
+    pub fn spectators(&self) -> Vec<ClientId> {
+        self.spectators.keys().cloned().collect()
+    }
+
+    /// Returns the number of live updates held for a spectator, 0 if it is not a spectator
+    pub fn pending_len(&self, client_id: ClientId) -> usize {
+        self.spectators
+            .get(&client_id)
+            .map_or(0, |spectator| spectator.pending.len())
+    }
+
+    /// Sends the next part of the snapshot of a spectator. Large snapshots can be sent in several parts, which the spectator receives together.
+    ///
+    /// Will return an [`Err`] if the client is not a spectator, if its snapshot was already ended, or if the payload can't be sent.
+    pub fn send_snapshot_payload<T: Into<Bytes>>(
+        &mut self,
+        endpoint: &mut Endpoint,
+        client_id: ClientId,
+        payload: T,
+    ) -> Result<(), ServerSpectatorError> {
+        let payload: Bytes = payload.into();
+        self.send_snapshot(
+            endpoint,
+            client_id,
+            SpectatorMessage::Part(payload.to_vec()),
+        )
+    }
+
+    /// Same as [`SpectatorStream::send_snapshot_payload`] with a message serialized with bincode
+    pub fn send_snapshot_message<T: Serialize>(
+        &mut self,
+        endpoint: &mut Endpoint,
+        client_id: ClientId,
+        message: T,
+    ) -> Result<(), ServerSpectatorError> {
+        let payload =
+            bincode::serialize(&message).map_err(|_| ServerSpectatorError::Serialization)?;
+        self.send_snapshot(endpoint, client_id, SpectatorMessage::Part(payload))
+    }
+
+    /// Ends the snapshot of a spectator, which becomes [`SpectatorState::Synchronizing`]: its held updates are sent once it acknowledges the snapshot.
+    ///
+    /// Will return an [`Err`] if the client is not a spectator, if its snapshot was already ended, or if the end can't be sent.
+    pub fn end_snapshot(
+        &mut self,
+        endpoint: &mut Endpoint,
+        client_id: ClientId,
+    ) -> Result<(), ServerSpectatorError> {
+        self.send_snapshot(endpoint, client_id, SpectatorMessage::End)?;
+        if let Some(spectator) = self.spectators.get_mut(&client_id) {
+            spectator.state = SpectatorState::Synchronizing;
+        }
+        Ok(())
+    }
+
+    fn send_snapshot(
+        &mut self,
+        endpoint: &mut Endpoint,
+        client_id: ClientId,
+        message: SpectatorMessage,
+    ) -> Result<(), ServerSpectatorError> {
+        match self.state(client_id) {
+            None => Err(ServerSpectatorError::UnknownSpectator(client_id)),
+            Some(SpectatorState::Snapshot) => {
+                let payload = bincode::serialize(&message)
+                    .map_err(|_| ServerSpectatorError::Serialization)?;
+                Ok(endpoint.send_payload_on(client_id, self.snapshot_channel_id, payload)?)
+            }
+            Some(_) => Err(ServerSpectatorError::SnapshotEnded(client_id)),
+        }
+    }
+
+    /// Sends a live update to the live spectators, and holds it for the spectators still synchronizing.
+    ///
+    /// Will return an [`Err`] with the errors of the live spectators the payload could not be sent to.
+    pub fn broadcast_payload_on<T: Into<Bytes>, C: Into<ChannelId>>(
+        &mut self,
+        endpoint: &mut Endpoint,
+        channel_id: C,
+        payload: T,
+    ) -> Result<(), ServerGroupSendError> {
+        let channel_id = channel_id.into();
+        let payload: Bytes = payload.into();
+        let mut errs = Vec::new();
+        for (&client_id, spectator) in self.spectators.iter_mut() {
+            match spectator.state {
+                SpectatorState::Live => {
+                    if let Err(err) =
+                        endpoint.send_payload_on(client_id, channel_id, payload.clone())
+                    {
+                        errs.push((client_id, err));
+                    }
+                }
+                _ => spectator.pending.push((channel_id, payload.clone())),
+            }
+        }
+        match errs.is_empty() {
+            true => Ok(()),
+            false => Err(ServerGroupSendError(errs)),
+        }
+    }
+
+    /// Same as [`SpectatorStream::broadcast_payload_on`] with a message serialized with bincode
+    pub fn broadcast_message_on<T: Serialize, C: Into<ChannelId>>(
+        &mut self,
+        endpoint: &mut Endpoint,
+        channel_id: C,
+        message: T,
+    ) -> Result<(), ServerGroupMessageSendError> {
+        let payload =
+            bincode::serialize(&message).map_err(|_| ServerGroupMessageSendError::Serialization)?;
+        Ok(self.broadcast_payload_on(endpoint, channel_id, payload)?)
+    }
+
+    /// Receives the acknowledgements of the spectators' snapshots and sends their held updates. Returns the spectators which became live.
+    ///
+    /// The spectators no longer connected to the endpoint are removed.
+    pub fn update(&mut self, endpoint: &mut Endpoint) -> Vec<ClientId> {
+        let client_ids = endpoint.clients();
+        self.spectators
+            .retain(|client_id, _| client_ids.contains(client_id));
+
+        let mut live = Vec::new();
+        for (&client_id, spectator) in self.spectators.iter_mut() {
+            let Ok(payloads) = endpoint.receive_all_from_on(client_id, self.snapshot_channel_id)
+            else {
+                continue;
+            };
+            let acknowledged = payloads.into_iter().any(|payload| {
+                matches!(
+                    bincode::deserialize(&payload),
+                    Ok(SpectatorMessage::Received)
+                )
+            });
+            if !acknowledged || spectator.state != SpectatorState::Synchronizing {
+                continue;
+            }
+            for (channel_id, payload) in spectator.pending.drain(..) {
+                endpoint.try_send_payload_on(client_id, channel_id, payload);
+            }
+            spectator.state = SpectatorState::Live;
+            live.push(client_id);
+        }
+        live
+    }
+}
+
+/// Updates a [`SpectatorStream`] resource, and raises a [`SpectatorLiveEvent`] for each spectator which acknowledged its snapshot.
+///
+/// Runs after the [`QuinnetSyncUpdate`] set, in the `PreUpdate` schedule by default.
+pub struct QuinnetServerSpectatorPlugin {
+    snapshot_channel_id: ChannelId,
+    schedule: InternedScheduleLabel,
+}
+
+impl QuinnetServerSpectatorPlugin {
+    /// Plugin streaming the snapshots on `snapshot_channel_id`
+    pub fn new<C: Into<ChannelId>>(snapshot_channel_id: C) -> Self {
+        Self {
+            snapshot_channel_id: snapshot_channel_id.into(),
+            schedule: PreUpdate.intern(),
+        }
+    }
+
+    /// Updates the stream in `schedule`, which should be the schedule of the [`QuinnetSyncUpdate`] set
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Plugin for QuinnetServerSpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpectatorLiveEvent>()
+            .insert_resource(SpectatorStream::new(self.snapshot_channel_id))
+            .add_systems(
+                self.schedule,
+                update_spectator_stream
+                    .after(QuinnetSyncUpdate)
+                    .run_if(resource_exists::<QuinnetServer>),
+            );
+    }
+}
+
+fn update_spectator_stream(
+    mut server: ResMut<QuinnetServer>,
+    mut stream: ResMut<SpectatorStream>,
+    mut live_events: EventWriter<SpectatorLiveEvent>,
+) {
+    if let Some(endpoint) = server.get_endpoint_mut() {
+        live_events.write_batch(
+            stream
+                .update(endpoint)
+                .into_iter()
+                .map(|id| SpectatorLiveEvent { id }),
+        );
+    }
+}
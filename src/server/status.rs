@@ -0,0 +1,219 @@
+use std::{
+    convert::Infallible,
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use bevy::log::{debug, trace};
+use quinn::VarInt;
+use serde::Deserialize;
+
+use crate::shared::{close::CloseCode, transport::TransportConnection};
+
+/// Default ALPN protocol of the status responder: HTTP/3
+pub const DEFAULT_STATUS_ALPN: &[u8] = b"h3";
+
+/// Max size of a status request, the requests are not interpreted
+const MAX_REQUEST_LEN: usize = 4_096;
+/// A status connection is closed after this delay, answered or not
+const STATUS_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// HTTP/3 frame types, RFC 9114
+const FRAME_DATA: u64 = 0x00;
+const FRAME_HEADERS: u64 = 0x01;
+const FRAME_SETTINGS: u64 = 0x04;
+/// HTTP/3 control stream type
+const STREAM_CONTROL: u64 = 0x00;
+/// HTTP/3 error code of the requests not processed
+const H3_REQUEST_REJECTED: u32 = 0x10b;
+/// Entries of the QPACK static table, RFC 9204 Appendix A
+const QPACK_STATUS_200: u8 = 25;
+const QPACK_STATUS_503: u8 = 28;
+const QPACK_CONTENT_TYPE_JSON: u8 = 46;
+
+/// Health/status responder of a server endpoint, served on the game port to the connections negotiating a distinct ALPN protocol, see [`crate::server::ServerEndpointConfiguration::with_status`].
+///
+/// The responder answers any HTTP/3 request, whatever its method or path, with a JSON document:
+/// ```json
+/// {"status":"ok","players":3,"uptime_secs":120,"version":"1.2.0"}
+/// ```
+/// The HTTP status is `200` while the endpoint accepts new connections, and `503` with a `"not_accepting"` status otherwise, so that orchestrators and load balancers can health-check a game server with an HTTP/3 client (`curl --http3`) without a second listener.
+///
+/// Once the server advertises ALPN protocols, QUIC requires every client to offer one of them: the Quinnet clients must offer [`crate::shared::QUINNET_ALPN`], see [`crate::client::connection::ClientEndpointConfigurationBuilder::with_alpn_protocols`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatusConfiguration {
+    version: String,
+    #[serde(default = "default_status_alpn")]
+    alpn: Vec<u8>,
+}
+
+fn default_status_alpn() -> Vec<u8> {
+    DEFAULT_STATUS_ALPN.to_vec()
+}
+
+impl StatusConfiguration {
+    /// Status responder reporting `version`, the version of the game server, on the [`DEFAULT_STATUS_ALPN`] protocol
+    pub fn new(version: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            alpn: default_status_alpn(),
+        }
+    }
+
+    /// Serves the status on the `alpn` protocol instead of [`DEFAULT_STATUS_ALPN`]
+    pub fn with_alpn(mut self, alpn: Vec<u8>) -> Self {
+        self.alpn = alpn;
+        self
+    }
+
+    /// Version of the game server reported by the responder
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// ALPN protocol of the responder
+    pub fn alpn(&self) -> &[u8] {
+        &self.alpn
+    }
+}
+
+/// Status shared between the sync endpoint and its status responder
+#[derive(Debug)]
+pub(crate) struct StatusState {
+    config: StatusConfiguration,
+    started_at: Instant,
+    players: AtomicUsize,
+    accepting: Arc<AtomicBool>,
+}
+
+impl StatusState {
+    pub(crate) fn new(config: StatusConfiguration, accepting: Arc<AtomicBool>) -> Self {
+        Self {
+            config,
+            started_at: Instant::now(),
+            players: AtomicUsize::new(0),
+            accepting,
+        }
+    }
+
+    pub(crate) fn alpn(&self) -> &[u8] {
+        &self.config.alpn
+    }
+
+    pub(crate) fn set_players(&self, players: usize) {
+        self.players.store(players, Ordering::Relaxed);
+    }
+
+    /// HTTP status code, and the JSON body of the response
+    fn response(&self) -> (u16, String) {
+        let accepting = self.accepting.load(Ordering::Relaxed);
+        let body = format!(
+            "{{\"status\":\"{}\",\"players\":{},\"uptime_secs\":{},\"version\":\"{}\"}}",
+            if accepting { "ok" } else { "not_accepting" },
+            self.players.load(Ordering::Relaxed),
+            self.started_at.elapsed().as_secs(),
+            escape_json(&self.config.version),
+        );
+        (if accepting { 200 } else { 503 }, body)
+    }
+}
+
+fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// QUIC variable-length integer, RFC 9000 section 16
+fn put_varint(buf: &mut Vec<u8>, value: u64) {
+    if value < 1 << 6 {
+        buf.push(value as u8);
+    } else if value < 1 << 14 {
+        buf.extend_from_slice(&(0x4000 | value as u16).to_be_bytes());
+    } else if value < 1 << 30 {
+        buf.extend_from_slice(&(0x8000_0000 | value as u32).to_be_bytes());
+    } else {
+        buf.extend_from_slice(&(0xC000_0000_0000_0000 | value).to_be_bytes());
+    }
+}
+
+fn put_frame(buf: &mut Vec<u8>, frame_type: u64, payload: &[u8]) {
+    put_varint(buf, frame_type);
+    put_varint(buf, payload.len() as u64);
+    buf.extend_from_slice(payload);
+}
+
+/// HEADERS and DATA frames of a response, the header fields only use the QPACK static table
+fn encode_response(status: u16, body: &str) -> Vec<u8> {
+    let status_index = match status {
+        200 => QPACK_STATUS_200,
+        _ => QPACK_STATUS_503,
+    };
+    // Required insert count and base of 0, then indexed field lines from the static table
+    let field_section = [
+        0x00,
+        0x00,
+        0xC0 | status_index,
+        0xC0 | QPACK_CONTENT_TYPE_JSON,
+    ];
+    let mut response = Vec::with_capacity(body.len() + 16);
+    put_frame(&mut response, FRAME_HEADERS, &field_section);
+    put_frame(&mut response, FRAME_DATA, body.as_bytes());
+    response
+}
+
+/// Answers the requests of a status connection, until the peer closes it or [`STATUS_CONNECTION_TIMEOUT`]
+pub(crate) async fn status_connection_task(connection: quinn::Connection, state: Arc<StatusState>) {
+    let remote = connection.remote_address();
+    match tokio::time::timeout(STATUS_CONNECTION_TIMEOUT, serve_status(&connection, state)).await {
+        Ok(Ok(never)) => match never {},
+        Ok(Err(err)) => trace!("Status connection from {} ended: {}", remote, err),
+        Err(_) => {
+            debug!("Status connection from {} timed out", remote);
+            TransportConnection::close(&connection, CloseCode::Closed);
+        }
+    }
+}
+
+async fn serve_status(
+    connection: &quinn::Connection,
+    state: Arc<StatusState>,
+) -> Result<Infallible, Box<dyn Error + Send + Sync>> {
+    // The peer expects our control stream and its SETTINGS, even empty. The stream must stay open.
+    let mut control = connection.open_uni().await?;
+    let mut settings = Vec::with_capacity(3);
+    put_varint(&mut settings, STREAM_CONTROL);
+    put_frame(&mut settings, FRAME_SETTINGS, &[]);
+    control.write_all(&settings).await?;
+
+    loop {
+        let (mut send, mut recv) = connection.accept_bi().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            // The request itself is not interpreted, any request gets the status
+            if recv.read_to_end(MAX_REQUEST_LEN).await.is_err() {
+                let _ = send.reset(VarInt::from_u32(H3_REQUEST_REJECTED));
+                return;
+            }
+            let (status, body) = state.response();
+            if send
+                .write_all(&encode_response(status, &body))
+                .await
+                .is_ok()
+            {
+                let _ = send.finish();
+            }
+        });
+    }
+}
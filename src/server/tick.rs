@@ -0,0 +1,55 @@
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
+
+use crate::shared::tick::NetworkTick;
+
+use super::QuinnetServer;
+
+/// Fixed tick driver of the server: increments the [`NetworkTick`] resource once per run of its schedule, `FixedPreUpdate` by default, and sets it as the network tick of the endpoint, which stamps it on the payloads of the ticked channels. See [`super::Endpoint::set_network_tick`].
+///
+/// The tick is advanced in the [`QuinnetTickUpdate`] set. The clients expose the tick as their own [`NetworkTick`] resource, updated by the [`crate::client::QuinnetClientPlugin`].
+pub struct QuinnetServerTickPlugin {
+    schedule: InternedScheduleLabel,
+}
+
+impl Default for QuinnetServerTickPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: FixedPreUpdate.intern(),
+        }
+    }
+}
+
+impl QuinnetServerTickPlugin {
+    /// Increments the tick in `schedule`
+    pub fn with_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+/// System set used by the [`QuinnetServerTickPlugin`] to advance the [`NetworkTick`], in the schedule of the plugin.
+///
+/// The systems of this schedule using the tick of the current step, or sending messages stamped with it, run after this set.
+#[derive(Debug, SystemSet, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuinnetTickUpdate;
+
+impl Plugin for QuinnetServerTickPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetworkTick>().add_systems(
+            self.schedule,
+            advance_network_tick
+                .in_set(QuinnetTickUpdate)
+                .run_if(resource_exists::<QuinnetServer>),
+        );
+    }
+}
+
+fn advance_network_tick(mut server: ResMut<QuinnetServer>, mut tick: ResMut<NetworkTick>) {
+    *tick = tick.next();
+    if let Some(endpoint) = server.get_endpoint_mut() {
+        endpoint.set_network_tick(*tick);
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+/// When a message of a client arrived on the server, for lag compensation: when an input actually arrived rather than when the system handling it ran. See [`crate::server::Endpoint::receive_timestamped_payload_from`].
+///
+/// Unlike a [`crate::shared::channels::MessageTrace`], the send time of the message is estimated on the server's clock, from the round-trip time of the connection: the clocks of the client and the server are never compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiveTimestamp {
+    received_at: Instant,
+    one_way_latency: Duration,
+}
+
+impl ReceiveTimestamp {
+    pub(crate) fn new(received_at: Instant, round_trip_time: Duration) -> Self {
+        Self {
+            received_at,
+            one_way_latency: round_trip_time / 2,
+        }
+    }
+
+    /// When the message arrived from the network, on the server's monotonic clock. Messages held by [`crate::server::conditions::ClientConditions`] arrive when released.
+    pub fn received_at(&self) -> Instant {
+        self.received_at
+    }
+
+    /// Estimated time spent by the message on the network: half the round-trip time of the connection (including the latency of its [`crate::server::conditions::ClientConditions`]) when the message was read
+    pub fn one_way_latency(&self) -> Duration {
+        self.one_way_latency
+    }
+
+    /// Estimated time the client sent the message, on the server's clock: [`ReceiveTimestamp::received_at`] minus [`ReceiveTimestamp::one_way_latency`]
+    pub fn estimated_sent_at(&self) -> Instant {
+        self.received_at
+            .checked_sub(self.one_way_latency)
+            .unwrap_or(self.received_at)
+    }
+
+    /// Time elapsed since the message arrived from the network
+    pub fn since_received(&self) -> Duration {
+        self.received_at.elapsed()
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::shared::ClientId;
+
+use super::{ServerTransferError, TransferTokenError};
+
+/// Default validity of the transfer tokens, see [`TransferKey::with_token_lifetime`]
+pub const DEFAULT_TRANSFER_TOKEN_LIFETIME: Duration = Duration::from_secs(30);
+/// Maximum size of the application payload carried by a transfer token
+pub const MAX_TRANSFER_PAYLOAD_LEN: usize = 1_024;
+/// A transferred client still connected after this delay is disconnected by the server
+pub(crate) const TRANSFER_CLOSE_DELAY: Duration = Duration::from_secs(5);
+
+const NONCE_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+
+/// Secret shared by the servers transferring clients to each other, see [`crate::server::Endpoint::transfer_client`].
+///
+/// The transfer tokens are signed with HMAC-SHA256: a server only accepts the tokens issued by a server using the same secret.
+#[derive(Debug, Clone)]
+pub struct TransferKey {
+    key: hmac::Key,
+    token_lifetime: Duration,
+}
+
+impl TransferKey {
+    /// Transfer key derived from `secret`, which should be at least 32 random bytes
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            token_lifetime: DEFAULT_TRANSFER_TOKEN_LIFETIME,
+        }
+    }
+
+    /// Tokens issued with this key expire after `token_lifetime` instead of [`DEFAULT_TRANSFER_TOKEN_LIFETIME`]
+    pub fn with_token_lifetime(mut self, token_lifetime: Duration) -> Self {
+        self.token_lifetime = token_lifetime;
+        self
+    }
+
+    /// Validity of the tokens issued with this key
+    pub fn token_lifetime(&self) -> Duration {
+        self.token_lifetime
+    }
+
+    fn sign(&self, ticket: &TransferTicket) -> Vec<u8> {
+        let mut token =
+            bincode::serialize(ticket).expect("Transfer tickets should be serializable");
+        token.extend_from_slice(hmac::sign(&self.key, &token).as_ref());
+        token
+    }
+
+    fn verify(&self, token: &[u8]) -> Result<TransferTicket, TransferTokenError> {
+        let Some(signed_len) = token.len().checked_sub(TAG_LEN) else {
+            return Err(TransferTokenError::Malformed);
+        };
+        let (signed, tag) = token.split_at(signed_len);
+        hmac::verify(&self.key, signed, tag).map_err(|_| TransferTokenError::InvalidSignature)?;
+        bincode::deserialize(signed).map_err(|_| TransferTokenError::Malformed)
+    }
+}
+
+/// Server a client is transferred to, see [`crate::server::Endpoint::transfer_client`]
+#[derive(Debug, Clone)]
+pub struct TransferTarget {
+    addr: SocketAddr,
+    server_hostname: String,
+}
+
+impl TransferTarget {
+    /// Server listening on `addr`, its certificate is verified against the IP of the address
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            server_hostname: addr.ip().to_string(),
+        }
+    }
+
+    /// Verifies the certificate of the server against `server_hostname` instead of the IP of its address
+    pub fn with_server_hostname(mut self, server_hostname: impl Into<String>) -> Self {
+        self.server_hostname = server_hostname.into();
+        self
+    }
+
+    /// Address of the server
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Hostname used to verify the certificate of the server
+    pub fn server_hostname(&self) -> &str {
+        &self.server_hostname
+    }
+}
+
+/// Content of a transfer token, issued by the server transferring a client and validated by the server receiving it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferTicket {
+    origin_client_id: ClientId,
+    target_addr: SocketAddr,
+    issued_at: u64,
+    expires_at: u64,
+    nonce: [u8; NONCE_LEN],
+    payload: Vec<u8>,
+}
+
+impl TransferTicket {
+    /// Id of the client on the server which issued the ticket
+    pub fn origin_client_id(&self) -> ClientId {
+        self.origin_client_id
+    }
+
+    /// Address of the server the client was transferred to
+    pub fn target_addr(&self) -> SocketAddr {
+        self.target_addr
+    }
+
+    /// When the ticket was issued, with a precision of one second
+    pub fn issued_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.issued_at)
+    }
+
+    /// When the ticket expires, with a precision of one second
+    pub fn expires_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.expires_at)
+    }
+
+    /// Application payload given to [`crate::server::Endpoint::transfer_client`], such as the session of the player
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// Event raised when a client transferred from another server presented a valid transfer token. Raised in the CoreStage::PreUpdate stage.
+///
+/// The client presents its token right after connecting: this event follows the [`crate::server::ConnectionEvent`] of the client.
+#[derive(Event, Debug, Clone)]
+pub struct ClientTransferEvent {
+    /// Id of the client on this server
+    pub id: ClientId,
+    /// Content of the validated token
+    pub ticket: TransferTicket,
+}
+
+/// Event raised when a client presented a transfer token which was rejected. Raised in the CoreStage::PreUpdate stage.
+///
+/// The client stays connected, the app decides whether to disconnect it.
+#[derive(Event, Debug, Clone)]
+pub struct ClientTransferRejectedEvent {
+    /// Id of the client on this server
+    pub id: ClientId,
+    /// Why the token was rejected
+    pub err: TransferTokenError,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+/// Issues the tokens of an endpoint, and validates the tokens presented to it
+#[derive(Debug, Default)]
+pub(crate) struct TransferState {
+    key: Option<TransferKey>,
+    /// Nonces of the accepted tokens, kept until their expiration to refuse replays
+    used_nonces: HashMap<[u8; NONCE_LEN], u64>,
+}
+
+impl TransferState {
+    pub(crate) fn key(&self) -> Option<&TransferKey> {
+        self.key.as_ref()
+    }
+
+    pub(crate) fn set_key(&mut self, key: Option<TransferKey>) {
+        self.key = key;
+    }
+
+    /// Returns the ticket and its signed token
+    pub(crate) fn issue(
+        &self,
+        client_id: ClientId,
+        target: &TransferTarget,
+        payload: Vec<u8>,
+    ) -> Result<(TransferTicket, Vec<u8>), ServerTransferError> {
+        let key = self
+            .key
+            .as_ref()
+            .ok_or(ServerTransferError::NoTransferKey)?;
+        if payload.len() > MAX_TRANSFER_PAYLOAD_LEN {
+            return Err(ServerTransferError::PayloadTooLarge(payload.len()));
+        }
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| ServerTransferError::RandomGenerationFailed)?;
+        let issued_at = unix_now();
+        let ticket = TransferTicket {
+            origin_client_id: client_id,
+            target_addr: target.addr,
+            issued_at,
+            expires_at: issued_at.saturating_add(key.token_lifetime.as_secs()),
+            nonce,
+            payload,
+        };
+        let token = key.sign(&ticket);
+        Ok((ticket, token))
+    }
+
+    /// Each token is only accepted once
+    pub(crate) fn validate(&mut self, token: &[u8]) -> Result<TransferTicket, TransferTokenError> {
+        let key = self.key.as_ref().ok_or(TransferTokenError::NoTransferKey)?;
+        let ticket = key.verify(token)?;
+        let now = unix_now();
+        self.used_nonces.retain(|_, expires_at| *expires_at >= now);
+        if ticket.expires_at < now {
+            return Err(TransferTokenError::Expired);
+        }
+        if self
+            .used_nonces
+            .insert(ticket.nonce, ticket.expires_at)
+            .is_some()
+        {
+            return Err(TransferTokenError::Replayed);
+        }
+        Ok(ticket)
+    }
+}
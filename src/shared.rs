@@ -1,18 +1,69 @@
+#[cfg(any(feature = "client", feature = "server"))]
+use std::sync::Arc;
 use std::{mem::size_of, time::Duration};
 
+#[cfg(any(feature = "client", feature = "server"))]
 use bevy::{
     ecs::schedule::SystemSet,
-    prelude::{Deref, DerefMut, Resource},
+    prelude::Resource,
+    tasks::{ComputeTaskPool, ParallelSliceMut, TaskPool},
 };
 use channels::MAX_CHANNEL_COUNT;
-use tokio::runtime::Runtime;
+#[cfg(any(feature = "client", feature = "server"))]
+use tokio::runtime::{Handle, Runtime};
 
+/// Chunked synchronization of a large initial state
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) mod baseline;
+/// Reuse of the buffers used to serialize and frame messages
+pub mod buffer_pool;
 /// Certificate features shared by client & server
 pub mod certificate;
 /// Channel features shared by client & server
 pub mod channels;
+/// Text chat between the clients, relayed by the server
+pub mod chat;
+/// Application close codes shared by client & server
+pub mod close;
+/// Slow start and loss episodes of the congestion controller of a connection
+pub mod congestion;
 /// Shared error types
 pub mod error;
+/// Forwarding of the clients of a gateway to internal servers
+pub mod forwarding;
+/// Defenses against malformed traffic
+pub mod hardening;
+/// Tick-based input streams, from clients to the server
+pub mod input;
+/// Invite codes bundling the address and the certificate fingerprint of a server
+pub mod invite;
+/// Lockstep simulations: inputs of all the clients released together for each tick
+pub mod lockstep;
+/// Registration of the servers to a master server, and listing of the servers
+pub mod master;
+/// Memory budget of the buffers of the connections
+pub mod memory;
+/// Spans around the sending steps of the messages, recorded with the `profiling` feature
+pub(crate) mod profiling;
+/// Compile-time declaration of the channels of a protocol and of their messages
+pub mod protocol;
+/// Traffic class of the packets: DSCP and ECN marking
+pub mod qos;
+/// Performance settings of the UDP sockets
+pub mod socket;
+/// One-way streams to spectators: a state snapshot, then the live updates
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) mod spectator;
+/// Ring buffer of the sampled stats of a connection
+pub mod stats_history;
+/// Minimal STUN client, used to discover the external address of a socket
+pub mod stun;
+/// Tick of the server's fixed simulation, shared with the clients
+pub mod tick;
+/// Transport abstraction used by the channels
+pub mod transport;
+/// Wire format of the connections, and its conformance test vectors
+pub mod wire;
 
 /// Default max size of async channels used to hold network messages. 1 async channel per connection.
 pub const DEFAULT_MESSAGE_QUEUE_SIZE: usize = 150;
@@ -29,24 +80,117 @@ pub const DEFAULT_INTERNAL_MESSAGES_CHANNEL_SIZE: usize = 100;
 /// At least MAX_CHANNEL_COUNT capacity if all available channel slots are requested to open
 pub const DEFAULT_QCHANNEL_MESSAGES_CHANNEL_SIZE: usize = 2 * MAX_CHANNEL_COUNT;
 
+/// ALPN protocol of the Quinnet connections.
+///
+/// Only needed by the clients of servers advertising ALPN protocols, see [`crate::server::status::StatusConfiguration`] and [`crate::server::ExternalEndpointConfiguration::with_protocol_routing`].
+pub const QUINNET_ALPN: &[u8] = b"quinnet";
+
 /// Default max size of the queues used to transmit close messages for async tasks
 pub(crate) const DEFAULT_KILL_MESSAGE_QUEUE_SIZE: usize = 10;
+/// Time given to the tasks of an [`AsyncRuntime`] to stop when it is dropped
+#[cfg(any(feature = "client", feature = "server"))]
+pub const ASYNC_RUNTIME_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// Represents the id of a client on the server.
 pub type ClientId = u64;
 pub(crate) const CLIENT_ID_LEN: usize = size_of::<ClientId>();
 
 /// Async runtime newtype wrapping the tokio runtime handle. used by both quinnet client and server's async back-ends.
-#[derive(Resource, Deref, DerefMut)]
-pub struct AsyncRuntime(pub(crate) Runtime);
-pub(crate) type InternalConnectionRef = quinn::Connection;
+///
+/// Dropping it shuts the runtime down, waiting up to [`ASYNC_RUNTIME_SHUTDOWN_TIMEOUT`] for its tasks: the world can be dropped with its resources in any order. From an async context, where waiting is not allowed, the runtime is shut down in the background instead. The tasks still running are dropped at their next yield point, see [`QuinnetShutdown`] to close the connections before.
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Resource)]
+pub struct AsyncRuntime(Option<Runtime>);
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl AsyncRuntime {
+    pub(crate) fn new(runtime: Runtime) -> Self {
+        Self(Some(runtime))
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl std::ops::Deref for AsyncRuntime {
+    type Target = Runtime;
+
+    fn deref(&self) -> &Self::Target {
+        // Only taken on drop
+        self.0.as_ref().expect("the runtime should be running")
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl std::ops::DerefMut for AsyncRuntime {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut().expect("the runtime should be running")
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl Drop for AsyncRuntime {
+    fn drop(&mut self) {
+        // Waiting for the tasks of a runtime panics in an async context
+        if let Some(runtime) = self.0.take() {
+            match Handle::try_current() {
+                Ok(_) => runtime.shutdown_background(),
+                Err(_) => runtime.shutdown_timeout(ASYNC_RUNTIME_SHUTDOWN_TIMEOUT),
+            }
+        }
+    }
+}
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) type InternalConnectionRef = Arc<dyn transport::TransportInfo>;
+
+/// Calls `f` on each connection, spreading the connections over the threads of the [`ComputeTaskPool`].
+///
+/// Runs on the calling thread when Bevy's `multi_threaded` feature is disabled.
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) fn par_map_connections<'a, K, V, R, F>(
+    connections: impl Iterator<Item = (&'a K, &'a mut V)>,
+    f: F,
+) -> Vec<R>
+where
+    K: Copy + Sync + 'a,
+    V: Send + 'a,
+    R: Send + 'static,
+    F: Fn(K, &mut V) -> R + Send + Sync,
+{
+    let mut connections: Vec<(&K, &mut V)> = connections.collect();
+    connections
+        .par_splat_map_mut(
+            ComputeTaskPool::get_or_init(TaskPool::default),
+            None,
+            |_, chunk| {
+                chunk
+                    .iter_mut()
+                    .map(|(id, connection)| f(**id, connection))
+                    .collect::<Vec<R>>()
+            },
+        )
+        .into_iter()
+        .flatten()
+        .collect()
+}
 
 /// System set used to update the sync client & server from updates coming from the async quinnet back-end.
 ///
 /// This is where client & server events are raised.
 ///
-/// This system set runs in PreUpdate.
+/// This system set runs in PreUpdate, unless another schedule is given to the client & server plugins.
+#[cfg(any(feature = "client", feature = "server"))]
 #[derive(Debug, SystemSet, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct QuinnetSyncUpdate;
 
-// May add a `QuinnetFlush` SystemSet to buffer and flush messages.
+/// System set used to send the messages held by the client & server when their deferred flush is enabled.
+///
+/// This system set runs in PostUpdate, unless another schedule is given to the client & server plugins.
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug, SystemSet, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuinnetFlush;
+
+/// System set used to close the client connections and stop the server endpoint when the app exits, on an [`bevy::app::AppExit`] event, so that the peers are notified before the [`AsyncRuntime`] is dropped.
+///
+/// This system set runs in Last.
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug, SystemSet, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuinnetShutdown;
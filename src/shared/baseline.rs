@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// Message of the channel of a baseline synchronization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum BaselineMessage {
+    /// Server to client: next chunk of the baseline, of `total` bytes
+    Chunk { total: u64, data: Vec<u8> },
+    /// Client to server: the baseline was received, the live updates can follow
+    Ready,
+}
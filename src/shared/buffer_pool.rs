@@ -0,0 +1,124 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// Default size of the chunks allocated by a buffer pool, in bytes
+pub const DEFAULT_BUFFER_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Statistics of the buffers used to serialize and frame messages, to tune [`DEFAULT_BUFFER_CHUNK_SIZE`] and the message sizes.
+///
+/// Messages are written in large chunks and split off them, a chunk is reused once every message written in it has been sent and dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Number of messages written in an already allocated chunk
+    pub reused: u64,
+    /// Number of chunks allocated. Without pooling, each message is allocated as a chunk of its own.
+    pub allocated_chunks: u64,
+    /// Total size of the allocated chunks, in bytes
+    pub allocated_bytes: u64,
+}
+
+/// Counters shared by all the pools of an endpoint or connection
+#[derive(Debug, Default)]
+struct BufferPoolCounters {
+    reused: AtomicU64,
+    allocated_chunks: AtomicU64,
+    allocated_bytes: AtomicU64,
+}
+
+/// Hands out buffers split off a reused chunk, to avoid one allocation per message on hot paths.
+///
+/// A chunk is only freed once all the messages split off it are dropped: a message kept alive long-term keeps its whole chunk alive, see [`BufferPool::set_pooling`].
+#[derive(Debug)]
+pub(crate) struct BufferPool {
+    chunk: BytesMut,
+    chunk_size: usize,
+    pooling: bool,
+    counters: Arc<BufferPoolCounters>,
+}
+
+impl BufferPool {
+    pub(crate) fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk: BytesMut::new(),
+            chunk_size,
+            pooling: true,
+            counters: Arc::new(BufferPoolCounters::default()),
+        }
+    }
+
+    /// New pool with its own chunk, always pooling, sharing the statistics of this pool
+    pub(crate) fn sibling(&self) -> Self {
+        Self {
+            chunk: BytesMut::new(),
+            chunk_size: self.chunk_size,
+            // Frames are dropped as soon as they are written
+            pooling: true,
+            counters: self.counters.clone(),
+        }
+    }
+
+    /// Without pooling, each buffer is allocated with the exact size of its message and is freed as soon as the message is dropped. Enabled by default.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn set_pooling(&mut self, pooling: bool) {
+        self.pooling = pooling;
+        // The current chunk is freed once its last message is dropped
+        self.chunk = BytesMut::new();
+    }
+
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn pooling(&self) -> bool {
+        self.pooling
+    }
+
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            reused: self.counters.reused.load(Ordering::Relaxed),
+            allocated_chunks: self.counters.allocated_chunks.load(Ordering::Relaxed),
+            allocated_bytes: self.counters.allocated_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns an empty buffer with a capacity of at least `len` bytes. Once written, the message is retrieved with [`BufferPool::split`].
+    pub(crate) fn buffer(&mut self, len: usize) -> &mut BytesMut {
+        self.chunk.clear();
+        if !self.pooling {
+            self.chunk = BytesMut::with_capacity(len);
+            self.counters
+                .allocated_chunks
+                .fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .allocated_bytes
+                .fetch_add(len as u64, Ordering::Relaxed);
+        } else if self.chunk.capacity() >= len || self.chunk.try_reclaim(len) {
+            self.counters.reused.fetch_add(1, Ordering::Relaxed);
+        } else {
+            // Previous chunk is freed once all the messages split off it are dropped
+            let chunk_size = self.chunk_size.max(len);
+            self.chunk = BytesMut::with_capacity(chunk_size);
+            self.counters
+                .allocated_chunks
+                .fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .allocated_bytes
+                .fetch_add(chunk_size as u64, Ordering::Relaxed);
+        }
+        &mut self.chunk
+    }
+
+    /// Splits the message written in the buffer off the chunk
+    pub(crate) fn split(&mut self) -> Bytes {
+        self.chunk.split().freeze()
+    }
+
+    /// Serializes the message in a pooled buffer
+    pub(crate) fn serialize<T: serde::Serialize>(&mut self, message: &T) -> Option<Bytes> {
+        let len = bincode::serialized_size(message).ok()? as usize;
+        bincode::serialize_into(self.buffer(len).writer(), message).ok()?;
+        Some(self.split())
+    }
+}
@@ -1,4 +1,9 @@
 use std::fmt;
+#[cfg(any(feature = "client", feature = "no-bevy"))]
+use std::sync::Arc;
+
+#[cfg(any(feature = "client", feature = "no-bevy"))]
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
 
 /// SHA-256 hash of the certificate data in DER form
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -10,15 +15,20 @@ impl CertificateFingerprint {
         CertificateFingerprint(buf)
     }
 
+    /// Returns the wrapped buffer
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
     /// Encodes the wrapped buffer content to base64
     pub fn to_base64(&self) -> String {
-        base64::encode(&self.0)
+        base64::encode(self.0)
     }
 }
 
 impl From<&rustls::pki_types::CertificateDer<'_>> for CertificateFingerprint {
     fn from(cert: &rustls::pki_types::CertificateDer<'_>) -> CertificateFingerprint {
-        let hash = ring::digest::digest(&ring::digest::SHA256, &cert);
+        let hash = ring::digest::digest(&ring::digest::SHA256, cert);
         let fingerprint_bytes = hash.as_ref().try_into().unwrap();
         CertificateFingerprint(fingerprint_bytes)
     }
@@ -30,3 +40,61 @@ impl fmt::Display for CertificateFingerprint {
         fmt::Display::fmt(&self.to_base64(), f)
     }
 }
+
+/// Implementation of `ServerCertVerifier` that verifies everything as trustworthy.
+#[cfg(any(feature = "client", feature = "no-bevy"))]
+#[derive(Debug)]
+pub(crate) struct SkipServerVerification(Arc<rustls::crypto::CryptoProvider>);
+
+#[cfg(any(feature = "client", feature = "no-bevy"))]
+impl SkipServerVerification {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self(Arc::new(rustls::crypto::ring::default_provider())))
+    }
+}
+
+#[cfg(any(feature = "client", feature = "no-bevy"))]
+impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp: &[u8],
+        _now: UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
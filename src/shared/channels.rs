@@ -1,11 +1,14 @@
-use bevy::log::trace;
 use bytes::Bytes;
-use quinn::VarInt;
-use std::fmt::Debug;
-use tokio::sync::{
-    broadcast,
-    mpsc::{self, error::TrySendError},
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
 };
+use tokio::sync::{broadcast, mpsc};
+#[cfg(any(feature = "client", feature = "server"))]
+use tracing::warn;
+use tracing::{error, info_span, trace};
 
 use crate::shared::channels::{
     reliable::send::{ordered_reliable_channel_task, unordered_reliable_channel_task},
@@ -13,16 +16,56 @@ use crate::shared::channels::{
 };
 
 use self::{
-    reliable::recv::reliable_channels_receiver_task,
+    ack::write_ack_header, encryption::ChannelCipher, incoming::IncomingSend,
+    payload::PayloadEncoder, queue::OutgoingQueue, redundancy::RedundantCopies,
+    reliable::recv::reliable_channels_receiver_task, tick::write_tick_header,
     unreliable::recv::unreliable_channel_receiver_task,
 };
-
-mod reliable;
+#[cfg(any(feature = "client", feature = "server"))]
+use self::{control::ControlMessage, liveness::ChannelLiveness};
+
+pub(crate) mod ack;
+pub(crate) mod compression;
+pub(crate) mod control;
+pub(crate) mod encryption;
+#[cfg(feature = "fec")]
+pub(crate) mod fec;
+pub(crate) mod incoming;
+pub(crate) mod liveness;
+pub(crate) mod payload;
+pub(crate) mod queue;
+pub(crate) mod redundancy;
+pub(crate) mod reliable;
+pub(crate) mod replay;
+pub(crate) mod tick;
+pub(crate) mod trace;
 mod unreliable;
 
+pub use ack::{TrackedMessageId, ACK_HEADER_LEN, MESSAGE_ACK_TIMEOUT};
+pub use compression::{CompressionDictionary, MAX_DICTIONARY_LEN};
+pub use control::CONTROL_CHANNEL_ID;
+pub use encryption::{ChannelEncryption, ENCRYPTED_PAYLOAD_OVERHEAD};
+#[cfg(feature = "fec")]
+pub use fec::{FEC_FLUSH_DELAY, FEC_HEADER_LEN, FEC_RECEIVE_WINDOW};
+pub use liveness::LivenessProbe;
+pub use redundancy::{MAX_REDUNDANT_PAYLOADS, REDUNDANCY_HEADER_LEN};
 pub use reliable::DEFAULT_MAX_RELIABLE_FRAME_LEN;
-
-use super::error::{AsyncChannelError, ChannelCloseError, ChannelConfigError};
+pub use replay::{REPLAY_HEADER_LEN, REPLAY_WINDOW_LEN};
+pub use tick::TICK_HEADER_LEN;
+pub use trace::{MessageTrace, MAX_BUFFERED_TRACES, TRACE_HEADER_LEN};
+
+#[cfg(any(feature = "client", feature = "server"))]
+use super::error::ChannelCloseError;
+use super::{
+    buffer_pool::BufferPool,
+    close::{CloseCode, CloseStage, CloseStageReporter},
+    error::{AsyncChannelError, ChannelConfigError, ChannelError},
+    hardening::{ProtocolViolation, ReceiveHardening},
+    profiling::profiled,
+    protocol::protocol_hash,
+    tick::SharedNetworkTick,
+    transport::TransportConnection,
+};
 
 /// Id of an opened channel
 pub type ChannelId = u8;
@@ -31,12 +74,14 @@ pub const MAX_CHANNEL_COUNT: usize = u8::MAX as usize + 1;
 
 pub(crate) const CHANNEL_ID_LEN: usize = 1;
 pub(crate) const PROTOCOL_HEADER_LEN: usize = CHANNEL_ID_LEN;
+#[cfg(feature = "client")]
 pub(crate) type CloseSend = broadcast::Sender<CloseReason>;
 pub(crate) type CloseRecv = broadcast::Receiver<CloseReason>;
 
 #[derive(PartialEq, Clone, Copy)]
 pub(crate) enum CloseReason {
-    LocalOrder,
+    /// Closed by this side, the code is sent to the peer
+    LocalOrder(CloseCode),
     PeerClosed,
 }
 
@@ -59,6 +104,15 @@ pub enum ChannelKind {
     Unreliable,
 }
 
+/// Priority of a message in the outgoing queue of a reliable channel.
+///
+/// Messages waiting to be sent on a reliable channel are sent by decreasing priority, messages with the same priority keep their sending order. This lets urgent messages overtake bulk messages already queued on the same channel.
+///
+/// Unreliable channels send their datagrams as soon as possible, so priorities rarely have an effect on them.
+pub type MessagePriority = u8;
+/// Default priority of a channel, see [`ChannelConfig::priority`]
+pub const DEFAULT_MESSAGE_PRIORITY: MessagePriority = 0;
+
 impl Default for ChannelKind {
     fn default() -> Self {
         ChannelKind::OrderedReliable {
@@ -67,17 +121,313 @@ impl Default for ChannelKind {
     }
 }
 
+/// Configuration of a channel: its [`ChannelKind`] and the options applied to the payloads sent on it.
+///
+/// Both peers must use the same configuration (except for the priority and the liveness probe, which only affect the sending side) on the same [`ChannelId`].
+///
+/// ### Example
+///
+/// ```
+/// use bevy_quinnet::shared::channels::ChannelConfig;
+///
+/// let config = ChannelConfig::reliable_ordered()
+///     .priority(3)
+///     .compressed()
+///     .max_message_size(64 * 1024);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChannelConfig {
+    kind: ChannelKind,
+    priority: MessagePriority,
+    compressed: bool,
+    compression_dictionary: Option<CompressionDictionary>,
+    encryption: Option<ChannelEncryption>,
+    max_message_size: Option<usize>,
+    traced: bool,
+    replay_protected: bool,
+    acknowledged: bool,
+    redundancy: u8,
+    ticked: bool,
+    #[cfg(feature = "fec")]
+    fec_group_size: Option<u8>,
+    liveness_probe: Option<LivenessProbe>,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self::new(ChannelKind::default())
+    }
+}
+
+impl From<ChannelKind> for ChannelConfig {
+    fn from(kind: ChannelKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl ChannelConfig {
+    /// New configuration of a channel of the given kind, without any option
+    pub fn new(kind: ChannelKind) -> Self {
+        Self {
+            kind,
+            priority: DEFAULT_MESSAGE_PRIORITY,
+            compressed: false,
+            compression_dictionary: None,
+            encryption: None,
+            max_message_size: None,
+            traced: false,
+            replay_protected: false,
+            acknowledged: false,
+            redundancy: 1,
+            ticked: false,
+            #[cfg(feature = "fec")]
+            fec_group_size: None,
+            liveness_probe: None,
+        }
+    }
+
+    /// [`ChannelKind::OrderedReliable`] channel with a maximum frame size of [`DEFAULT_MAX_RELIABLE_FRAME_LEN`]
+    pub fn reliable_ordered() -> Self {
+        Self::new(ChannelKind::OrderedReliable {
+            max_frame_size: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+        })
+    }
+
+    /// [`ChannelKind::UnorderedReliable`] channel with a maximum frame size of [`DEFAULT_MAX_RELIABLE_FRAME_LEN`]
+    pub fn reliable_unordered() -> Self {
+        Self::new(ChannelKind::UnorderedReliable {
+            max_frame_size: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+        })
+    }
+
+    /// [`ChannelKind::Unreliable`] channel
+    pub fn unreliable() -> Self {
+        Self::new(ChannelKind::Unreliable)
+    }
+
+    /// Sets the priority of the messages sent on this channel without an explicit [`MessagePriority`]
+    pub fn priority(mut self, priority: MessagePriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Compresses the payloads sent on this channel (LZ4)
+    pub fn compressed(mut self) -> Self {
+        self.compressed = true;
+        self
+    }
+
+    /// Compresses the payloads sent on this channel with zstd and a dictionary shared by both peers, instead of LZ4, see [`CompressionDictionary`].
+    ///
+    /// Small payloads, such as the messages of a game, compress much better with a dictionary of typical payloads. Both peers must use the dictionary with the same id on the same [`ChannelId`]: the id is announced to the peer when the channel opens, and a channel whose dictionary differs from the dictionary of the peer is closed with a `ChannelRejectedEvent`.
+    pub fn compressed_with_dictionary(mut self, dictionary: CompressionDictionary) -> Self {
+        self.compressed = true;
+        self.compression_dictionary = Some(dictionary);
+        self
+    }
+
+    /// Encrypts the payloads sent on this channel, see [`ChannelEncryption`]
+    pub fn encrypted(mut self, encryption: ChannelEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    /// Sets the maximum size of the payloads sent on this channel, before compression and encryption.
+    ///
+    /// Sending a larger payload fails, and larger incoming payloads are dropped. Reliable frames stay limited by the `max_frame_size` of the [`ChannelKind`].
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
+    /// Stamps the payloads sent on this channel with a trace id and their send time, adding [`TRACE_HEADER_LEN`] bytes to each payload.
+    ///
+    /// The receiving peer keeps a [`MessageTrace`] of each payload, to tell apart the network latency from the processing delay. Both peers must enable tracing on the same [`ChannelId`], like compression.
+    pub fn traced(mut self) -> Self {
+        self.traced = true;
+        self
+    }
+
+    /// Stamps the payloads sent on this [`ChannelKind::Unreliable`] channel with a monotonically increasing nonce, adding [`REPLAY_HEADER_LEN`] bytes to each payload. Ignored on reliable channels.
+    ///
+    /// The receiving peer drops the payloads whose nonce was already received, and those older than the last [`REPLAY_WINDOW_LEN`] nonces, reporting them as [`ProtocolViolation::ReplayedPayload`](crate::shared::hardening::ProtocolViolation::ReplayedPayload). Combined with [`ChannelConfig::encrypted`], captured datagrams can't be replayed nor forged. Both peers must enable replay protection on the same [`ChannelId`], like compression.
+    pub fn replay_protected(mut self) -> Self {
+        self.replay_protected = true;
+        self
+    }
+
+    /// Allows tracking the delivery of the payloads sent on this [`ChannelKind::Unreliable`] channel with `send_unreliable_tracked`, adding [`ACK_HEADER_LEN`] bytes to each payload. Ignored on reliable channels.
+    ///
+    /// The receiving peer acknowledges the tracked payloads on the reliable control channel once they reach it, raising a `MessageAckedEvent` on the sending peer, or a `MessageLostEvent` after [`MESSAGE_ACK_TIMEOUT`]. Both peers must enable acknowledgements on the same [`ChannelId`], like compression.
+    pub fn acknowledged(mut self) -> Self {
+        self.acknowledged = true;
+        self
+    }
+
+    /// Sends each payload of this [`ChannelKind::Unreliable`] channel `copies` times: once as usual, then once more on each of the next `copies - 1` flushes, adding [`REDUNDANCY_HEADER_LEN`] bytes to each payload. Ignored on reliable channels, and when `copies` is lower than 2.
+    ///
+    /// Trades bandwidth for resilience to losses, for critical messages such as a player death, without the head-of-line blocking of a reliable channel. The copies are spaced by the flushes of the [`QuinnetFlush`](crate::shared::QuinnetFlush) set, run once per frame by the plugins: a burst of losses is less likely to drop all of them. The receiving peer delivers the first copy to reach it and silently drops the others, as well as the copies older than the last [`REPLAY_WINDOW_LEN`] payloads of the channel. Both peers must enable redundancy on the same [`ChannelId`], like compression.
+    pub fn redundant(mut self, copies: u8) -> Self {
+        self.redundancy = copies;
+        self
+    }
+
+    /// Stamps the payloads sent by the server on this channel with its network tick, adding [`TICK_HEADER_LEN`] bytes to each of them. The payloads sent by the client are not stamped.
+    ///
+    /// The client keeps the most recent tick read from the payloads of its ticked channels, see [`NetworkTick`](crate::shared::tick::NetworkTick): the tick reaches the clients along with the messages of the server, without any message of its own. Both peers must enable it on the same [`ChannelId`], like compression.
+    pub fn ticked(mut self) -> Self {
+        self.ticked = true;
+        self
+    }
+
+    /// Adds a parity datagram after each group of `group_size` datagrams sent on this [`ChannelKind::Unreliable`] channel, the XOR of their payloads, and adds [`FEC_HEADER_LEN`] bytes to each datagram. Ignored on reliable channels, and when `group_size` is 0.
+    ///
+    /// The receiving peer rebuilds a datagram lost in a group from the other datagrams of the group and its parity, without waiting for a retransmission. Recovers one loss per group, within the last [`FEC_RECEIVE_WINDOW`] groups, for `1 / group_size` more datagrams. The parity datagram is as long as the longest datagram of its group. A group left partial for [`FEC_FLUSH_DELAY`], or when the channel closes, gets the parity of the datagrams sent so far. `group_size` is capped at 254. Both peers must enable forward error correction on the same [`ChannelId`], with the same `group_size`.
+    #[cfg(feature = "fec")]
+    pub fn fec(mut self, group_size: u8) -> Self {
+        self.fec_group_size = Some(group_size);
+        self
+    }
+
+    /// Periodically probes the stream of this reliable channel, to detect a stuck stream while the connection itself is alive, see [`LivenessProbe`]. Ignored on unreliable channels.
+    ///
+    /// A probe left unanswered raises a `ChannelUnresponsiveEvent` on the sending peer. Only the sending peer needs the probe in its configuration, the peer always answers the probes.
+    pub fn liveness_probe(mut self, probe: LivenessProbe) -> Self {
+        self.liveness_probe = Some(probe);
+        self
+    }
+
+    /// Kind of the channel
+    pub fn kind(&self) -> ChannelKind {
+        self.kind
+    }
+
+    /// Priority of the messages sent without an explicit [`MessagePriority`]
+    pub fn default_priority(&self) -> MessagePriority {
+        self.priority
+    }
+
+    /// Whether payloads are compressed
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Dictionary of the compression, if any
+    pub fn compression_dictionary(&self) -> Option<&CompressionDictionary> {
+        self.compression_dictionary.as_ref()
+    }
+
+    /// Encryption of the payloads, if any
+    pub fn encryption(&self) -> Option<&ChannelEncryption> {
+        self.encryption.as_ref()
+    }
+
+    /// Maximum size of the payloads, if any
+    pub fn message_size_limit(&self) -> Option<usize> {
+        self.max_message_size
+    }
+
+    /// Whether payloads are stamped with a trace
+    pub fn is_traced(&self) -> bool {
+        self.traced
+    }
+
+    /// Whether payloads are stamped with a nonce checked against replays, only for unreliable channels
+    pub fn is_replay_protected(&self) -> bool {
+        self.replay_protected && matches!(self.kind, ChannelKind::Unreliable)
+    }
+
+    /// Whether the delivery of the payloads can be tracked, only for unreliable channels
+    pub fn is_acknowledged(&self) -> bool {
+        self.acknowledged && matches!(self.kind, ChannelKind::Unreliable)
+    }
+
+    /// Number of times each payload is sent, 1 unless the channel is a redundant unreliable channel
+    pub fn redundancy(&self) -> u8 {
+        match self.kind {
+            ChannelKind::Unreliable => self.redundancy.max(1),
+            _ => 1,
+        }
+    }
+
+    /// Whether payloads are sent more than once, see [`ChannelConfig::redundant`]
+    pub fn is_redundant(&self) -> bool {
+        self.redundancy() > 1
+    }
+
+    /// Whether the payloads sent by the server are stamped with its network tick, see [`ChannelConfig::ticked`]
+    pub fn is_ticked(&self) -> bool {
+        self.ticked
+    }
+
+    /// Liveness probe of the channel, only for reliable channels, see [`ChannelConfig::liveness_probe`]
+    pub fn liveness(&self) -> Option<LivenessProbe> {
+        match self.kind {
+            ChannelKind::Unreliable => None,
+            _ => self.liveness_probe,
+        }
+    }
+
+    /// Bytes added by the options of this channel to each payload sent as a datagram, compression aside. Only meaningful for [`ChannelKind::Unreliable`] channels.
+    pub fn datagram_overhead(&self) -> usize {
+        let mut overhead = 0;
+        if self.is_acknowledged() {
+            overhead += ACK_HEADER_LEN;
+        }
+        if self.is_redundant() {
+            overhead += REDUNDANCY_HEADER_LEN;
+        }
+        if self.is_ticked() {
+            overhead += TICK_HEADER_LEN;
+        }
+        if self.is_traced() {
+            overhead += TRACE_HEADER_LEN;
+        }
+        if self.is_replay_protected() {
+            overhead += REPLAY_HEADER_LEN;
+        }
+        if self.encryption.is_some() {
+            overhead += ENCRYPTED_PAYLOAD_OVERHEAD;
+        }
+        #[cfg(feature = "fec")]
+        if self.fec_group_size().is_some() {
+            overhead += FEC_HEADER_LEN;
+        }
+        overhead
+    }
+
+    /// Number of datagrams protected by each parity datagram, only for unreliable channels, see [`ChannelConfig::fec`]
+    #[cfg(feature = "fec")]
+    pub fn fec_group_size(&self) -> Option<u8> {
+        match self.kind {
+            ChannelKind::Unreliable => self.fec_group_size.filter(|size| *size > 0),
+            _ => None,
+        }
+    }
+}
+
+/// Shared by the sync side (which registers the opened channels) and the async receiving tasks (which decode payloads).
+pub(crate) type SharedChannelConfigs = Arc<RwLock<HashMap<ChannelId, ChannelConfig>>>;
+
 #[derive(Debug)]
 pub(crate) enum ChannelAsyncMessage {
     LostConnection,
+    /// Only read by the server, the client connections only log the violations
+    #[cfg_attr(not(any(feature = "server", feature = "no-bevy")), allow(dead_code))]
+    ProtocolViolation(ProtocolViolation),
+    /// The stream of a reliable channel was reset by the peer and replaced by a new one
+    ChannelResumed(ChannelId),
+    /// A message could not be sent on a channel, which stays open
+    ChannelError(ChannelId, ChannelError),
 }
 
 #[derive(Debug)]
 pub(crate) enum ChannelSyncMessage {
     CreateChannel {
         id: ChannelId,
-        kind: ChannelKind,
-        bytes_to_channel_recv: mpsc::Receiver<Bytes>,
+        config: ChannelConfig,
+        queue: Arc<OutgoingQueue>,
+        buffers: BufferPool,
         channel_close_recv: mpsc::Receiver<()>,
     },
 }
@@ -85,37 +435,193 @@ pub(crate) enum ChannelSyncMessage {
 #[derive(Debug)]
 pub(crate) struct Channel {
     id: ChannelId,
-    sender: mpsc::Sender<Bytes>,
+    default_priority: MessagePriority,
+    max_message_size: Option<usize>,
+    acknowledged: bool,
+    unreliable: bool,
+    redundancy: Option<Mutex<RedundantCopies>>,
+    #[cfg(any(feature = "client", feature = "server"))]
+    liveness: Option<Mutex<ChannelLiveness>>,
+    /// Network tick stamped on the payloads of a ticked channel of the server
+    network_tick: Option<SharedNetworkTick>,
+    queue: Arc<OutgoingQueue>,
+    #[cfg_attr(not(any(feature = "client", feature = "server")), allow(dead_code))]
     close_sender: mpsc::Sender<()>,
 }
 
 impl Channel {
     pub(crate) fn new(
         id: ChannelId,
-        sender: mpsc::Sender<Bytes>,
+        config: &ChannelConfig,
+        queue: Arc<OutgoingQueue>,
         close_sender: mpsc::Sender<()>,
     ) -> Self {
         Self {
             id,
-            sender,
+            default_priority: config.priority,
+            max_message_size: config.max_message_size,
+            acknowledged: config.is_acknowledged(),
+            unreliable: matches!(config.kind(), ChannelKind::Unreliable),
+            redundancy: config
+                .is_redundant()
+                .then(|| Mutex::new(RedundantCopies::new(config.redundancy()))),
+            #[cfg(any(feature = "client", feature = "server"))]
+            liveness: config
+                .liveness()
+                .map(|probe| Mutex::new(ChannelLiveness::new(probe))),
+            network_tick: None,
+            queue,
             close_sender,
         }
     }
 
+    /// Stamps `network_tick` on the payloads sent on this channel, by the server on a ticked channel, see [`ChannelConfig::ticked`]
+    #[cfg(feature = "server")]
+    pub(crate) fn with_network_tick(mut self, network_tick: SharedNetworkTick) -> Self {
+        self.network_tick = Some(network_tick);
+        self
+    }
+
+    #[cfg(any(feature = "client", feature = "server"))]
     pub fn id(&self) -> ChannelId {
         self.id
     }
 
-    pub(crate) fn send_payload(&self, payload: Bytes) -> Result<(), AsyncChannelError> {
-        match self.sender.try_send(payload) {
-            Ok(_) => Ok(()),
-            Err(err) => match err {
-                TrySendError::Full(_) => Err(AsyncChannelError::FullQueue),
-                TrySendError::Closed(_) => Err(AsyncChannelError::InternalChannelClosed),
+    /// Maximum size of the payloads accepted by this channel, if any
+    pub(crate) fn max_message_size(&self) -> Option<usize> {
+        self.max_message_size
+    }
+
+    /// Uses the default priority of the channel if `priority` is `None`. If `deferred`, the payload is sent on the next [`Channel::flush`].
+    pub(crate) fn send_payload(
+        &self,
+        payload: Bytes,
+        priority: Option<MessagePriority>,
+        deferred: bool,
+    ) -> Result<(), AsyncChannelError> {
+        let payload = match self.acknowledged {
+            true => write_ack_header(None, payload),
+            false => payload,
+        };
+        self.push(payload, priority.unwrap_or(self.default_priority), deferred)
+    }
+
+    fn push(
+        &self,
+        payload: Bytes,
+        priority: MessagePriority,
+        deferred: bool,
+    ) -> Result<(), AsyncChannelError> {
+        // Quinnet's own messages are counted, but never refused
+        if self.id != CONTROL_CHANNEL_ID {
+            self.queue.memory().admit(payload.len(), self.unreliable)?;
+        }
+        let payload = match &self.network_tick {
+            Some(network_tick) => write_tick_header(network_tick.get(), payload),
+            None => payload,
+        };
+        let payload = match &self.redundancy {
+            Some(redundancy) => match redundancy.lock() {
+                Ok(mut redundancy) => redundancy.stamp(payload),
+                Err(_) => return Err(AsyncChannelError::InternalChannelClosed),
             },
+            None => payload,
+        };
+        self.queue.push(payload, priority, deferred)
+    }
+
+    /// Returns true if this is a [`ChannelKind::Unreliable`] channel
+    #[cfg(feature = "server")]
+    pub(crate) fn is_unreliable(&self) -> bool {
+        self.unreliable
+    }
+
+    /// Returns true if the delivery of the payloads of this channel can be tracked, see [`ChannelConfig::acknowledged`]
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn is_acknowledged(&self) -> bool {
+        self.acknowledged
+    }
+
+    /// Sends a payload of an acknowledged channel, to be acknowledged by the peer with `id`
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn send_tracked_payload(
+        &self,
+        id: TrackedMessageId,
+        payload: Bytes,
+        deferred: bool,
+    ) -> Result<(), AsyncChannelError> {
+        self.push(
+            write_ack_header(Some(id), payload),
+            self.default_priority,
+            deferred,
+        )
+    }
+
+    /// Sends the deferred payloads, and the copies due of the payloads of a redundant channel
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn flush(&self) {
+        if let Some(Ok(mut redundancy)) = self.redundancy.as_ref().map(Mutex::lock) {
+            for copy in redundancy.take_due() {
+                if let Err(err) = self.queue.push(copy, self.default_priority, true) {
+                    warn!(
+                        "Failed to send a copy of a payload on channel {}: {}",
+                        self.id, err
+                    );
+                }
+            }
         }
+        self.queue.flush();
+    }
+
+    /// Sends the liveness probe of the channel if due. Returns how long the pending probe waited for its answer once it exceeds its timeout, only once per probe.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn poll_liveness(&self, now: Instant) -> Option<Duration> {
+        let mut liveness = self.liveness.as_ref()?.lock().ok()?;
+        if let Some(sequence) = liveness.due_probe(now) {
+            self.queue.probe(
+                ControlMessage::ChannelProbe {
+                    channel_id: self.id,
+                    sequence,
+                }
+                .encode(),
+            );
+        }
+        liveness.check(now)
+    }
+
+    /// Records the answer of the peer to the liveness probe `sequence` of the channel
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn answer_probe(&self, sequence: u64, now: Instant) {
+        if let Some(Ok(mut liveness)) = self.liveness.as_ref().map(Mutex::lock) {
+            liveness.answer(sequence, now);
+        }
+    }
+
+    /// Waits until the outgoing queue of the channel has room for a payload
+    #[cfg(feature = "no-bevy")]
+    pub(crate) async fn reserve(&self) {
+        self.queue.reserve().await
+    }
+
+    /// Number of messages waiting in the outgoing queue of the channel
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn pending_messages_count(&self) -> usize {
+        self.queue.len()
     }
 
+    /// Size of the messages waiting in the outgoing queue of the channel, in bytes
+    #[cfg(feature = "server")]
+    pub(crate) fn pending_bytes(&self) -> usize {
+        self.queue.bytes()
+    }
+
+    /// Discards the messages waiting in the outgoing queue of the channel and returns how many were discarded
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn clear_pending_messages(&self) -> usize {
+        self.queue.clear()
+    }
+
+    #[cfg(any(feature = "client", feature = "server"))]
     pub(crate) fn close(&self) -> Result<(), ChannelCloseError> {
         match self.close_sender.blocking_send(()) {
             Ok(_) => Ok(()),
@@ -127,6 +633,12 @@ impl Channel {
     }
 }
 
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
 /// Stores a configuration that represents multiple channels to be opened by a [`crate::client::connection::ClientSideConnection`] or [`crate::server::Endpoint`]
 ///
 /// Each channel in a [ChannelsConfiguration] is assigned a [ChannelId], starting from 0 and incrementing sequentially by 1.
@@ -135,7 +647,7 @@ impl Channel {
 ///
 /// Declare 3 configured channels with their respective ids `0`, `1` and `2`:
 /// ```
-/// use bevy_quinnet::shared::channels::{ChannelKind, ChannelsConfiguration};
+/// use bevy_quinnet::shared::channels::{ChannelConfig, ChannelKind, ChannelsConfiguration};
 ///
 /// let configs = ChannelsConfiguration::from_types(vec![
 ///     ChannelKind::OrderedReliable {
@@ -148,18 +660,25 @@ impl Channel {
 ///         max_frame_size: 10 * 1_024,
 ///     },
 /// ]).unwrap();
+///
+/// // Or with channel options
+/// let configs = ChannelsConfiguration::from_configs(vec![
+///     ChannelConfig::reliable_ordered().compressed(),
+///     ChannelConfig::unreliable().max_message_size(1_024),
+/// ]).unwrap();
 /// ```
 #[derive(Debug, Clone)]
 pub struct ChannelsConfiguration {
-    channels: Vec<ChannelKind>,
+    channels: Vec<ChannelConfig>,
+    /// Names of the types of the messages of each channel, part of the protocol hash
+    message_types: Vec<String>,
 }
 
 impl Default for ChannelsConfiguration {
     fn default() -> Self {
         Self {
-            channels: vec![ChannelKind::OrderedReliable {
-                max_frame_size: DEFAULT_MAX_RELIABLE_FRAME_LEN,
-            }],
+            channels: vec![ChannelConfig::default()],
+            message_types: Vec::new(),
         }
     }
 }
@@ -169,6 +688,7 @@ impl ChannelsConfiguration {
     pub fn new() -> Self {
         Self {
             channels: Vec::new(),
+            message_types: Vec::new(),
         }
     }
 
@@ -178,137 +698,295 @@ impl ChannelsConfiguration {
     pub fn from_types(
         channel_types: Vec<ChannelKind>,
     ) -> Result<ChannelsConfiguration, ChannelConfigError> {
-        if channel_types.len() > MAX_CHANNEL_COUNT {
+        Self::from_configs(channel_types.into_iter().map(ChannelConfig::from).collect())
+    }
+
+    /// New configuration from a list of [`ChannelConfig`].
+    ///
+    /// Opened channels (and their [`ChannelId`]) will have the same order as in this collection
+    pub fn from_configs(
+        channel_configs: Vec<ChannelConfig>,
+    ) -> Result<ChannelsConfiguration, ChannelConfigError> {
+        if channel_configs.len() > MAX_CHANNEL_COUNT {
             Err(ChannelConfigError::MaxChannelsCountReached)
         } else {
             Ok(Self {
-                channels: channel_types,
+                channels: channel_configs,
+                message_types: Vec::new(),
             })
         }
     }
 
-    /// Adds one element to the configuration from a [`ChannelKind`].
+    /// Adds one element to the configuration from a [`ChannelKind`] or a [`ChannelConfig`].
     ///
     /// Opened channels (and their [`ChannelId`]) will have the same order as their insertion order.
-    pub fn add(&mut self, channel_type: ChannelKind) -> Option<ChannelId> {
+    pub fn add<T: Into<ChannelConfig>>(&mut self, channel_config: T) -> Option<ChannelId> {
         if self.channels.len() < MAX_CHANNEL_COUNT {
-            self.channels.push(channel_type);
+            self.channels.push(channel_config.into());
             Some((self.channels.len() - 1) as u8)
         } else {
             None
         }
     }
 
-    pub(crate) fn configs(&self) -> &Vec<ChannelKind> {
+    /// Same as [`ChannelsConfiguration::add`], but payloads sent on this channel will be encrypted with the given [`ChannelEncryption`].
+    ///
+    /// The peer must enable the same [`ChannelEncryption`] on the same [`ChannelId`] to be able to read them.
+    pub fn add_encrypted<T: Into<ChannelConfig>>(
+        &mut self,
+        channel_config: T,
+        encryption: ChannelEncryption,
+    ) -> Option<ChannelId> {
+        self.add(channel_config.into().encrypted(encryption))
+    }
+
+    /// Names the types of the messages of the channels, in the order of the channels. Both peers must name the same types for their [`ChannelsConfiguration::protocol_hash`] to match.
+    ///
+    /// Set by the configurations generated by [`crate::protocol!`].
+    pub fn with_message_types<S: Into<String>>(
+        mut self,
+        message_types: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.message_types = message_types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Names of the types of the messages of the channels, see [`ChannelsConfiguration::with_message_types`]
+    pub fn message_types(&self) -> &[String] {
+        &self.message_types
+    }
+
+    /// Hash of the configuration: the number of channels, their kinds, their options and the names of the types of their messages, see [`crate::shared::protocol::protocol_hash`].
+    ///
+    /// Exchanged when a client connects, to detect the clients and servers built with different protocols, see [`crate::server::Endpoint::set_protocol_check`].
+    pub fn protocol_hash(&self) -> u64 {
+        let message_types: Vec<&str> = self.message_types.iter().map(String::as_str).collect();
+        protocol_hash(self, &message_types)
+    }
+
+    pub(crate) fn configs(&self) -> &Vec<ChannelConfig> {
         &self.channels
     }
 }
 
 /// Spawn a task to handle send channels creation for this connection
-pub(crate) fn spawn_send_channels_tasks_spawner(
-    connection_handle: quinn::Connection,
+pub(crate) fn spawn_send_channels_tasks_spawner<C: TransportConnection>(
+    connection_handle: C,
+    connection_id: u64,
     close_recv: broadcast::Receiver<CloseReason>,
     to_channels_recv: mpsc::Receiver<ChannelSyncMessage>,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    close_stages: CloseStageReporter,
 ) {
-    tokio::spawn(async move {
+    tokio::spawn(profiled(
         send_channels_tasks_spawner(
             connection_handle,
             close_recv,
             to_channels_recv,
             from_channels_send,
-        )
-        .await
-    });
+            close_stages,
+        ),
+        move || info_span!("quinnet_connection", connection_id),
+    ));
 }
 
-struct SendChannelTask {
-    connection: quinn::Connection,
+/// Messages left unsent by the send channels drained by the close of their connection, see [`CloseStage::BuffersFlushed`]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct DrainRemainders(Arc<Mutex<BTreeMap<ChannelId, usize>>>);
+
+impl DrainRemainders {
+    /// Registers `channel` as drained by the close of the connection
+    pub(crate) fn register(&self, channel: ChannelId) {
+        self.add(channel, 0);
+    }
+
+    /// Counts `count` messages of `channel` as unsent
+    pub(crate) fn add(&self, channel: ChannelId, count: usize) {
+        if let Ok(mut remainders) = self.0.lock() {
+            *remainders.entry(channel).or_default() += count;
+        }
+    }
+
+    fn collect(&self) -> Vec<(ChannelId, usize)> {
+        match self.0.lock() {
+            // The control channel is internal to Quinnet
+            Ok(remainders) => remainders
+                .iter()
+                .filter(|(channel, _)| **channel != CONTROL_CHANNEL_ID)
+                .map(|(channel, count)| (*channel, *count))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+pub(crate) struct SendChannelTask<C: TransportConnection> {
+    connection: C,
     id: ChannelId,
     channels_keepalive: mpsc::Sender<()>,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
     close_recv: CloseRecv,
     channel_close_recv: mpsc::Receiver<()>,
-    bytes_recv: mpsc::Receiver<Bytes>,
+    queue: Arc<OutgoingQueue>,
+    encoder: PayloadEncoder,
+    buffers: BufferPool,
+    remainders: DrainRemainders,
 }
 
-pub(crate) async fn send_channels_tasks_spawner(
-    connection: quinn::Connection,
+pub(crate) async fn send_channels_tasks_spawner<C: TransportConnection>(
+    connection: C,
     mut close_recv: broadcast::Receiver<CloseReason>,
     mut to_channels_recv: mpsc::Receiver<ChannelSyncMessage>,
     from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    close_stages: CloseStageReporter,
 ) {
     // Use an mpsc channel where, instead of sending messages, we wait for the channel to be closed, which happens when every sender has been dropped. We can't use a JoinSet as simply here since we would also need to drain closed channels from it.
     let (channel_tasks_keepalive, mut channel_tasks_waiter) = mpsc::channel::<()>(1);
 
-    let close_receiver_clone = close_recv.resubscribe();
-    tokio::select! {
-        _ = close_recv.recv() => {
-            trace!("Connection Channels listener received a close signal")
+    let mut close_receiver_clone = close_recv.resubscribe();
+    let mut closing = None;
+    let remainders = DrainRemainders::default();
+    let mut queues = Vec::new();
+    let close_reason = tokio::select! {
+        close_reason = close_recv.recv() => {
+            trace!("Connection Channels listener received a close signal");
+            close_reason.ok()
         }
         _ = async {
             while let Some(ChannelSyncMessage::CreateChannel {
                 id,
-                kind,
-                bytes_to_channel_recv: bytes_recv,
+                config,
+                queue,
+                buffers,
                 channel_close_recv,
             }) = to_channels_recv.recv().await {
+                let cipher = match config.encryption() {
+                    Some(encryption) => match ChannelCipher::derive(&connection, encryption, id, connection.side()) {
+                        Ok(cipher) => Some(cipher),
+                        Err(err) => {
+                            error!("Failed to create encrypted channel {}: {}", id, err);
+                            // Without a task, the queue would accept messages which are never sent
+                            queue.clear();
+                            queue.close();
+                            let _ = from_channels_send
+                                .send(ChannelAsyncMessage::ChannelError(
+                                    id,
+                                    ChannelError::EncryptionUnavailable,
+                                ))
+                                .await;
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
 
+                queues.push(queue.clone());
+                let mut channel_close_signal = close_receiver_clone.resubscribe();
+                // A close sent before the resubscription is only received by the clone, the task must still see it
+                if closing.is_none() {
+                    closing = close_receiver_clone.try_recv().ok();
+                }
+                if let Some(reason) = &closing {
+                    let (close_send, close_recv) = broadcast::channel(1);
+                    let _ = close_send.send(*reason);
+                    channel_close_signal = close_recv;
+                }
                 let channel_task_data = SendChannelTask {
                     connection: connection.clone(),
                     id,
                     channels_keepalive: channel_tasks_keepalive.clone(),
                     from_channels_send: from_channels_send.clone(),
-                    close_recv: close_receiver_clone.resubscribe(),
+                    close_recv: channel_close_signal,
                     channel_close_recv,
-                    bytes_recv,
+                    queue,
+                    encoder: PayloadEncoder::new(&config, cipher),
+                    buffers,
+                    remainders: remainders.clone(),
                 };
 
-                match kind {
+                let channel_span = move || info_span!("quinnet_send_channel", channel_id = id);
+                match config.kind() {
                     ChannelKind::OrderedReliable { max_frame_size } => {
-                        tokio::spawn(async move { ordered_reliable_channel_task(channel_task_data, max_frame_size).await });
+                        tokio::spawn(profiled(ordered_reliable_channel_task(channel_task_data, max_frame_size), channel_span));
                     }
                     ChannelKind::UnorderedReliable { max_frame_size } => {
-                        tokio::spawn(
-                            async move { unordered_reliable_channel_task(channel_task_data, max_frame_size).await },
-                        );
+                        tokio::spawn(profiled(unordered_reliable_channel_task(channel_task_data, max_frame_size), channel_span));
                     }
                     ChannelKind::Unreliable => {
-                        tokio::spawn(async move { unreliable_channel_task(channel_task_data).await });
+                        tokio::spawn(profiled(unreliable_channel_task(channel_task_data), channel_span));
                     }
                 }
             }
         } => {
-            trace!("Connection Channels listener ended")
+            trace!("Connection Channels listener ended");
+            // The sync side may have ordered the close just before dropping the channels sender
+            close_recv.try_recv().ok()
+        }
+    };
+    let close_code = match close_reason {
+        Some(CloseReason::LocalOrder(code)) => code,
+        _ => CloseCode::Closed,
+    };
+    // A connection closed by its peer is already lost, its close is not reported
+    let close_stages = |stage: CloseStage, elapsed: Duration| {
+        if !matches!(close_reason, Some(CloseReason::PeerClosed)) {
+            close_stages(stage, elapsed);
         }
     };
 
+    let drain_start = Instant::now();
+    close_stages(
+        CloseStage::DrainStarted {
+            pending_messages: queues.iter().map(|queue| queue.len()).sum(),
+        },
+        drain_start.elapsed(),
+    );
+
     // Wait for all the channels to have flushed/finished:
     // We drop our sender first because the recv() call otherwise sleeps forever.
     // When every sender has gone out of scope, the recv call will return with an error. We ignore the error.
     drop(channel_tasks_keepalive);
     let _ = channel_tasks_waiter.recv().await;
-
-    connection.close(VarInt::from_u32(0), "closed".as_bytes());
+    close_stages(
+        CloseStage::BuffersFlushed {
+            remainders: remainders.collect(),
+        },
+        drain_start.elapsed(),
+    );
+
+    connection.close(close_code);
+    close_stages(
+        CloseStage::Closed { code: close_code },
+        drain_start.elapsed(),
+    );
 }
 
-pub(crate) fn spawn_recv_channels_tasks(
-    connection_handle: quinn::Connection,
+pub(crate) fn spawn_recv_channels_tasks<C: TransportConnection>(
+    connection_handle: C,
     connection_id: u64,
     close_recv: broadcast::Receiver<CloseReason>,
-    bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    bytes_incoming_send: IncomingSend,
+    channels_configs: SharedChannelConfigs,
+    hardening: ReceiveHardening,
+    server_tick: SharedNetworkTick,
 ) {
     // Spawn a task to listen for reliable messages
     {
         let connection_handle = connection_handle.clone();
         let close_recv = close_recv.resubscribe();
         let bytes_incoming_send = bytes_incoming_send.clone();
+        let channels_configs = channels_configs.clone();
+        let hardening = hardening.clone();
+        let server_tick = server_tick.clone();
         tokio::spawn(async move {
             reliable_channels_receiver_task(
                 connection_id,
                 connection_handle,
                 close_recv,
                 bytes_incoming_send,
+                channels_configs,
+                hardening,
+                server_tick,
             )
             .await
         });
@@ -325,6 +1003,9 @@ pub(crate) fn spawn_recv_channels_tasks(
                 connection_handle,
                 close_recv,
                 bytes_incoming_send,
+                channels_configs,
+                hardening,
+                server_tick,
             )
             .await
         });
@@ -0,0 +1,102 @@
+#[cfg(any(feature = "client", feature = "server"))]
+use std::{collections::BTreeMap, time::Instant};
+use std::{fmt, time::Duration};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+#[cfg(any(feature = "client", feature = "server"))]
+use super::ChannelId;
+
+/// Size overhead added to each payload sent on an acknowledged channel, in bytes
+pub const ACK_HEADER_LEN: usize = 8;
+/// A tracked message not acknowledged by the peer after this delay is considered lost
+pub const MESSAGE_ACK_TIMEOUT: Duration = Duration::from_secs(1);
+/// Maximum number of acknowledgements sent in one control message
+pub(crate) const MAX_ACKS_PER_CONTROL_MESSAGE: usize = 256;
+
+/// Id of a message sent with `send_unreliable_tracked`, unique on its connection. Reported by the acknowledgement or the loss event of the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TrackedMessageId(u64);
+
+impl fmt::Display for TrackedMessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// TRACKED MESSAGE ID (0 if not tracked) | PAYLOAD
+pub(crate) fn write_ack_header(id: Option<TrackedMessageId>, payload: Bytes) -> Bytes {
+    let mut stamped = BytesMut::with_capacity(ACK_HEADER_LEN + payload.len());
+    stamped.put_u64(id.map_or(0, |id| id.0));
+    stamped.extend_from_slice(&payload);
+    stamped.into()
+}
+
+/// Removes the header of a payload received on an acknowledged channel, returns the id to acknowledge if the message is tracked. `None` if the payload is too short to carry a header.
+pub(crate) fn read_ack_header(mut payload: Bytes) -> Option<(Bytes, Option<u64>)> {
+    if payload.len() < ACK_HEADER_LEN {
+        return None;
+    }
+    let header = payload.split_to(ACK_HEADER_LEN);
+    let id = u64::from_be_bytes(header[..].try_into().ok()?);
+    Some((payload, (id != 0).then_some(id)))
+}
+
+/// Tracked messages sent on a connection and waiting for their acknowledgement
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug)]
+pub(crate) struct AckTracker {
+    next_id: u64,
+    /// Ordered by id, hence by sending time
+    in_flight: BTreeMap<u64, (ChannelId, Instant)>,
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl Default for AckTracker {
+    fn default() -> Self {
+        Self {
+            next_id: 1,
+            in_flight: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl AckTracker {
+    pub(crate) fn track(&mut self, channel_id: ChannelId, now: Instant) -> TrackedMessageId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.in_flight.insert(id, (channel_id, now));
+        TrackedMessageId(id)
+    }
+
+    /// Stops tracking a message which could not be sent
+    pub(crate) fn forget(&mut self, id: TrackedMessageId) {
+        self.in_flight.remove(&id.0);
+    }
+
+    /// Returns the messages acknowledged by `ids`. Acknowledgements of messages already acknowledged or considered lost are ignored.
+    pub(crate) fn acknowledge(&mut self, ids: &[u64]) -> Vec<(ChannelId, TrackedMessageId)> {
+        ids.iter()
+            .filter_map(|id| {
+                self.in_flight
+                    .remove(id)
+                    .map(|(channel_id, _)| (channel_id, TrackedMessageId(*id)))
+            })
+            .collect()
+    }
+
+    /// Removes the messages sent more than [`MESSAGE_ACK_TIMEOUT`] before `now`, in their sending order
+    pub(crate) fn expire(&mut self, now: Instant) -> Vec<(ChannelId, TrackedMessageId)> {
+        let mut lost = Vec::new();
+        while let Some(entry) = self.in_flight.first_entry() {
+            let (channel_id, sent_at) = *entry.get();
+            if now.saturating_duration_since(sent_at) < MESSAGE_ACK_TIMEOUT {
+                break;
+            }
+            lost.push((channel_id, TrackedMessageId(*entry.key())));
+            entry.remove();
+        }
+        lost
+    }
+}
@@ -0,0 +1,220 @@
+use std::{collections::HashMap, fmt};
+
+use bytes::Bytes;
+use zstd::bulk::{Compressor, Decompressor};
+
+use super::{control::ControlMessage, ChannelConfig, ChannelId};
+use crate::shared::error::CompressionDictionaryError;
+
+/// Size of the uncompressed length prepended to compressed payloads
+pub(crate) const COMPRESSED_SIZE_PREFIX_LEN: usize = 4;
+
+/// Maximum size of a dictionary, larger dictionaries are refused by [`CompressionDictionary::new`]
+pub const MAX_DICTIONARY_LEN: usize = 1024 * 1024;
+
+/// Dictionary of a zstd compressed channel, see [`super::ChannelConfig::compressed_with_dictionary`].
+///
+/// Small payloads have little to reference on their own, and compress poorly. With a dictionary, the compressor also references the content of the dictionary, known by both peers. A good dictionary is trained with `zstd --train` on samples of the typical payloads of the channel, such as serialized messages, and shipped with the game. Any other content is used as a raw dictionary.
+///
+/// Both peers must use the same dictionary, identified by its id. The peers announce the id of the dictionary of each compressed channel when opening it: a channel whose dictionary does not match the dictionary announced by the peer is closed, and a `ChannelRejectedEvent` is raised. The id is also part of the [`super::ChannelsConfiguration::protocol_hash`].
+#[derive(Clone)]
+pub struct CompressionDictionary {
+    id: u32,
+    data: Bytes,
+}
+
+impl CompressionDictionary {
+    /// Creates a dictionary identified by `id`, the application's own numbering of its dictionaries.
+    ///
+    /// Fails if the dictionary exceeds [`MAX_DICTIONARY_LEN`], or if zstd refuses it.
+    pub fn new(id: u32, data: impl Into<Bytes>) -> Result<Self, CompressionDictionaryError> {
+        let data = data.into();
+        if data.len() > MAX_DICTIONARY_LEN {
+            return Err(CompressionDictionaryError::TooLarge {
+                len: data.len(),
+                max_len: MAX_DICTIONARY_LEN,
+            });
+        }
+        Compressor::with_dictionary(zstd::DEFAULT_COMPRESSION_LEVEL, &data)?;
+        Decompressor::with_dictionary(&data)?;
+        Ok(Self { id, data })
+    }
+
+    /// Id of the dictionary
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Content of the dictionary
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+}
+
+impl fmt::Debug for CompressionDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressionDictionary")
+            .field("id", &self.id)
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+/// UNCOMPRESSED LENGTH (u32, little endian) | LZ4 BLOCK
+pub(crate) fn compress(payload: &[u8]) -> Bytes {
+    lz4_flex::compress_prepend_size(payload).into()
+}
+
+/// Refuses to allocate more than `max_size` bytes, whatever the size announced by the payload
+pub(crate) fn decompress(payload: &[u8], max_size: usize) -> Option<Bytes> {
+    let (size, block) = read_size_prefix(payload, max_size)?;
+    lz4_flex::decompress(block, size).ok().map(Bytes::from)
+}
+
+fn read_size_prefix(payload: &[u8], max_size: usize) -> Option<(usize, &[u8])> {
+    let size_prefix: [u8; COMPRESSED_SIZE_PREFIX_LEN] =
+        payload.get(..COMPRESSED_SIZE_PREFIX_LEN)?.try_into().ok()?;
+    let size = u32::from_le_bytes(size_prefix) as usize;
+    if size > max_size {
+        return None;
+    }
+    Some((size, &payload[COMPRESSED_SIZE_PREFIX_LEN..]))
+}
+
+/// Compresses the payloads of a channel with its [`CompressionDictionary`], reusing its zstd context
+pub(crate) struct DictionaryCompressor {
+    compressor: Compressor<'static>,
+}
+
+impl DictionaryCompressor {
+    pub(crate) fn new(dictionary: &CompressionDictionary) -> Self {
+        let mut compressor =
+            Compressor::with_dictionary(zstd::DEFAULT_COMPRESSION_LEVEL, &dictionary.data)
+                .expect("Dictionaries should be checked on creation");
+        // Both already known from the size prefix and the channel
+        let _ = compressor.include_contentsize(false);
+        let _ = compressor.include_dictid(false);
+        Self { compressor }
+    }
+
+    /// UNCOMPRESSED LENGTH (u32, little endian) | ZSTD FRAME
+    pub(crate) fn compress(&mut self, payload: &[u8]) -> Bytes {
+        let frame = self
+            .compressor
+            .compress(payload)
+            .expect("Compressing in memory should not fail");
+        let mut compressed = Vec::with_capacity(COMPRESSED_SIZE_PREFIX_LEN + frame.len());
+        compressed.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        compressed.extend_from_slice(&frame);
+        compressed.into()
+    }
+}
+
+/// Decompresses the payloads of a channel compressed with a [`CompressionDictionary`]
+pub(crate) struct DictionaryDecompressor {
+    dictionary_id: u32,
+    decompressor: Decompressor<'static>,
+}
+
+impl DictionaryDecompressor {
+    pub(crate) fn new(dictionary: &CompressionDictionary) -> Self {
+        Self {
+            dictionary_id: dictionary.id,
+            decompressor: Decompressor::with_dictionary(&dictionary.data)
+                .expect("Dictionaries should be checked on creation"),
+        }
+    }
+
+    /// Id of the dictionary the decompressor was created with
+    pub(crate) fn dictionary_id(&self) -> u32 {
+        self.dictionary_id
+    }
+
+    /// Refuses to allocate more than `max_size` bytes, whatever the size announced by the payload
+    pub(crate) fn decompress(&mut self, payload: &[u8], max_size: usize) -> Option<Bytes> {
+        let (size, frame) = read_size_prefix(payload, max_size)?;
+        match self.decompressor.decompress(frame, size) {
+            Ok(decompressed) if decompressed.len() == size => Some(decompressed.into()),
+            _ => None,
+        }
+    }
+}
+
+/// Compressed channel whose dictionary does not match the dictionary announced by the peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DictionaryMismatch {
+    pub(crate) channel_id: ChannelId,
+    /// Id of the dictionary of the local channel, `None` without a dictionary
+    pub(crate) local_dictionary_id: Option<u32>,
+    /// Id of the dictionary announced by the peer, `None` without a dictionary
+    pub(crate) peer_dictionary_id: Option<u32>,
+}
+
+/// Announces the dictionaries of the compressed channels opened by a connection, and checks them against the dictionaries announced by the peer, see [`ControlMessage::ChannelOpened`].
+///
+/// A channel is checked once both peers opened it, whichever opened it first.
+#[derive(Debug, Default)]
+pub(crate) struct DictionaryNegotiation {
+    /// Dictionaries of the compressed channels opened locally, and not rejected
+    local: HashMap<ChannelId, Option<u32>>,
+    /// Dictionaries announced by the peer for its compressed channels
+    peer: HashMap<ChannelId, Option<u32>>,
+    /// Announcements of the channels opened since the last [`DictionaryNegotiation::take_announcements`]
+    unannounced: Vec<ControlMessage>,
+    mismatches: Vec<DictionaryMismatch>,
+}
+
+impl DictionaryNegotiation {
+    pub(crate) fn opened(&mut self, channel_id: ChannelId, config: &ChannelConfig) {
+        if !config.is_compressed() {
+            self.local.remove(&channel_id);
+            return;
+        }
+        let dictionary_id = config
+            .compression_dictionary()
+            .map(CompressionDictionary::id);
+        self.local.insert(channel_id, dictionary_id);
+        self.unannounced.push(ControlMessage::ChannelOpened {
+            channel_id,
+            dictionary_id,
+        });
+        self.check(channel_id);
+    }
+
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn closed(&mut self, channel_id: ChannelId) {
+        self.local.remove(&channel_id);
+    }
+
+    /// The peer opened a compressed channel
+    pub(crate) fn announced(&mut self, channel_id: ChannelId, dictionary_id: Option<u32>) {
+        self.peer.insert(channel_id, dictionary_id);
+        self.check(channel_id);
+    }
+
+    fn check(&mut self, channel_id: ChannelId) {
+        let (Some(&local_dictionary_id), Some(&peer_dictionary_id)) =
+            (self.local.get(&channel_id), self.peer.get(&channel_id))
+        else {
+            return;
+        };
+        if local_dictionary_id != peer_dictionary_id {
+            self.local.remove(&channel_id);
+            self.mismatches.push(DictionaryMismatch {
+                channel_id,
+                local_dictionary_id,
+                peer_dictionary_id,
+            });
+        }
+    }
+
+    /// Control messages announcing the compressed channels opened since the last call, to send to the peer
+    pub(crate) fn take_announcements(&mut self) -> Vec<ControlMessage> {
+        std::mem::take(&mut self.unannounced)
+    }
+
+    /// Channels found mismatched since the last call, to close
+    pub(crate) fn take_mismatches(&mut self) -> Vec<DictionaryMismatch> {
+        std::mem::take(&mut self.mismatches)
+    }
+}
@@ -0,0 +1,69 @@
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use super::{ChannelConfig, ChannelId};
+
+/// Channel reserved by Quinnet for its control messages, never opened by the endpoints and connections for the application.
+///
+/// Payloads received on this channel are consumed by Quinnet and never returned by the `receive_*` methods.
+pub const CONTROL_CHANNEL_ID: ChannelId = u8::MAX;
+
+/// Maximum size of a control message
+const MAX_CONTROL_MESSAGE_SIZE: usize = 4 * 1_024;
+
+/// Configuration of the control channel, identical on both peers
+pub(crate) fn control_channel_config() -> ChannelConfig {
+    ChannelConfig::reliable_ordered().max_message_size(MAX_CONTROL_MESSAGE_SIZE)
+}
+
+/// Messages exchanged by the client and the server on [`CONTROL_CHANNEL_ID`]
+///
+/// Part of the wire format, see [`crate::shared::wire`]: the variants are encoded by their index, new variants are only appended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum ControlMessage {
+    /// Server to client: the client is transferred to another server, and presents `token` to it
+    Redirect {
+        target_addr: SocketAddr,
+        server_hostname: String,
+        token: Vec<u8>,
+    },
+    /// Client to server: token received from the server which transferred the client
+    PresentToken(Vec<u8>),
+    /// Client to server: signed header of the client forwarded by a gateway
+    ForwardedClient(Vec<u8>),
+    /// Both directions: ids of the tracked payloads received on the acknowledged channels
+    Acks(Vec<u64>),
+    /// Client to server: hash of the channels configuration the client connected with
+    ProtocolHash(u64),
+    /// Server to client: the protocol hash of the client does not match the hash of the server, the client closes the connection
+    ProtocolMismatch { server_hash: u64 },
+    /// Both directions: liveness probe of a reliable channel, sent on the stream of the channel instead of the control channel
+    ChannelProbe {
+        channel_id: ChannelId,
+        sequence: u64,
+    },
+    /// Both directions: answer to a [`ControlMessage::ChannelProbe`]
+    ChannelProbeAck {
+        channel_id: ChannelId,
+        sequence: u64,
+    },
+    /// Both directions: a compressed channel was opened, with the id of its dictionary if any. The peer closes its own channel if its dictionary differs, see [`super::CompressionDictionary`].
+    ChannelOpened {
+        channel_id: ChannelId,
+        dictionary_id: Option<u32>,
+    },
+}
+
+impl ControlMessage {
+    pub(crate) fn encode(&self) -> Bytes {
+        bincode::serialize(self)
+            .expect("Control messages should be serializable")
+            .into()
+    }
+
+    pub(crate) fn decode(payload: &[u8]) -> Option<Self> {
+        bincode::deserialize(payload).ok()
+    }
+}
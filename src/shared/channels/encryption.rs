@@ -0,0 +1,235 @@
+use std::fmt;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use quinn_proto::Side;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, MAX_TAG_LEN, NONCE_LEN},
+    hkdf,
+    rand::{SecureRandom, SystemRandom},
+};
+
+use super::ChannelId;
+use crate::shared::transport::TransportConnection;
+
+/// Label used to derive channel keys from the TLS session, see [TransportConnection::export_keying_material]
+const CHANNEL_KEY_EXPORTER_LABEL: &[u8] = b"bevy_quinnet channel key";
+const CHANNEL_KEY_LEN: usize = 32;
+const NONCE_COUNTER_LEN: usize = 8;
+/// Size of the random salt drawn at each opening of an encrypted channel
+pub(crate) const SALT_LEN: usize = 16;
+
+/// Size overhead added to each payload sent on an encrypted channel, in bytes
+pub const ENCRYPTED_PAYLOAD_OVERHEAD: usize = SALT_LEN + NONCE_COUNTER_LEN + MAX_TAG_LEN;
+
+/// Application-layer encryption applied to the payloads of a channel, on top of the QUIC/TLS encryption.
+///
+/// Payloads are sealed with ChaCha20-Poly1305, using one key per channel, per direction and per opening of the channel. Both peers must enable the same [`ChannelEncryption`] on the same [`ChannelId`], payloads which can't be decrypted are dropped.
+#[derive(Clone)]
+pub enum ChannelEncryption {
+    /// Keys are derived from the TLS session of the connection (RFC 5705 exporter).
+    ///
+    /// This does not add confidentiality between the two endpoints of the QUIC connection, but keeps payloads opaque to anything which would only have access to decrypted QUIC frames.
+    TlsExporter,
+    /// Keys are derived from a secret shared by the application on both peers.
+    ///
+    /// Payloads stay confidential end-to-end between the original peers, even when the QUIC connection is terminated by an untrusted relay.
+    PreSharedKey([u8; CHANNEL_KEY_LEN]),
+}
+
+impl fmt::Debug for ChannelEncryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TlsExporter => f.write_str("TlsExporter"),
+            Self::PreSharedKey(_) => f.write_str("PreSharedKey(..)"),
+        }
+    }
+}
+
+/// CHANNEL ID | 1 if the sender is the server, 0 otherwise
+fn key_context(channel_id: ChannelId, sender_side: Side) -> [u8; 2] {
+    [channel_id, sender_side.is_server() as u8]
+}
+
+/// Error while deriving a channel key
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to derive a channel encryption key")]
+pub(crate) struct ChannelKeyDerivationError;
+
+/// Keying material of one direction of a channel, from which each opening of the channel derives its own key with a random salt
+fn channel_secret<C: TransportConnection>(
+    connection: &C,
+    encryption: &ChannelEncryption,
+    channel_id: ChannelId,
+    sender_side: Side,
+) -> Result<[u8; CHANNEL_KEY_LEN], ChannelKeyDerivationError> {
+    match encryption {
+        ChannelEncryption::TlsExporter => {
+            let mut secret = [0; CHANNEL_KEY_LEN];
+            connection
+                .export_keying_material(
+                    &mut secret,
+                    CHANNEL_KEY_EXPORTER_LABEL,
+                    &key_context(channel_id, sender_side),
+                )
+                .map_err(|_| ChannelKeyDerivationError)?;
+            Ok(secret)
+        }
+        ChannelEncryption::PreSharedKey(secret) => Ok(*secret),
+    }
+}
+
+/// Key of the opening of the channel identified by `salt`
+fn salted_key(
+    secret: &[u8; CHANNEL_KEY_LEN],
+    salt: &[u8; SALT_LEN],
+    channel_id: ChannelId,
+    sender_side: Side,
+) -> Result<LessSafeKey, ChannelKeyDerivationError> {
+    let mut key_bytes = [0; CHANNEL_KEY_LEN];
+    hkdf::Salt::new(hkdf::HKDF_SHA256, salt)
+        .extract(secret)
+        .expand(
+            &[
+                CHANNEL_KEY_EXPORTER_LABEL,
+                &key_context(channel_id, sender_side),
+            ],
+            &CHACHA20_POLY1305,
+        )
+        .and_then(|okm| okm.fill(&mut key_bytes))
+        .map_err(|_| ChannelKeyDerivationError)?;
+    let key =
+        UnboundKey::new(&CHACHA20_POLY1305, &key_bytes).map_err(|_| ChannelKeyDerivationError)?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[NONCE_LEN - NONCE_COUNTER_LEN..].copy_from_slice(&counter.to_be_bytes());
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/// Sealing key of one direction of a channel, for one opening of the channel.
+///
+/// The key is derived from a salt drawn at random when the channel opens: two connections sharing a [`ChannelEncryption::PreSharedKey`], or two openings of the same channel id on a connection, never seal with the same key, and their nonce counters can safely restart at 0.
+pub(crate) struct ChannelCipher {
+    key: LessSafeKey,
+    salt: [u8; SALT_LEN],
+    channel_id: ChannelId,
+    counter: u64,
+}
+
+impl ChannelCipher {
+    /// Derives a new key used by `sender_side` to send on `channel_id`.
+    pub(crate) fn derive<C: TransportConnection>(
+        connection: &C,
+        encryption: &ChannelEncryption,
+        channel_id: ChannelId,
+        sender_side: Side,
+    ) -> Result<Self, ChannelKeyDerivationError> {
+        let secret = channel_secret(connection, encryption, channel_id, sender_side)?;
+        let mut salt = [0; SALT_LEN];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| ChannelKeyDerivationError)?;
+        Self::salted(&secret, salt, channel_id, sender_side)
+    }
+
+    /// Derives the key used by `sender_side` to send on `channel_id` from a [`ChannelEncryption::PreSharedKey`] secret and a given salt, for the reference encodings
+    pub(crate) fn pre_shared(
+        secret: &[u8; CHANNEL_KEY_LEN],
+        salt: [u8; SALT_LEN],
+        channel_id: ChannelId,
+        sender_side: Side,
+    ) -> Result<Self, ChannelKeyDerivationError> {
+        Self::salted(secret, salt, channel_id, sender_side)
+    }
+
+    fn salted(
+        secret: &[u8; CHANNEL_KEY_LEN],
+        salt: [u8; SALT_LEN],
+        channel_id: ChannelId,
+        sender_side: Side,
+    ) -> Result<Self, ChannelKeyDerivationError> {
+        Ok(Self {
+            key: salted_key(secret, &salt, channel_id, sender_side)?,
+            salt,
+            channel_id,
+            counter: 0,
+        })
+    }
+
+    /// SALT | NONCE COUNTER | CIPHERTEXT | TAG
+    pub(crate) fn seal(&mut self, payload: Bytes) -> Bytes {
+        let counter = self.counter;
+        self.counter += 1;
+
+        let mut sealed = BytesMut::with_capacity(ENCRYPTED_PAYLOAD_OVERHEAD + payload.len());
+        sealed.put_slice(&self.salt);
+        sealed.put_u64(counter);
+        let mut in_out = payload.to_vec();
+        self.key
+            .seal_in_place_append_tag(nonce(counter), Aad::from([self.channel_id]), &mut in_out)
+            .expect("payload should not exceed the maximum size of a ChaCha20-Poly1305 message");
+        sealed.extend_from_slice(&in_out);
+        sealed.into()
+    }
+}
+
+/// Opening keys of one direction of a channel, derived from the salt of each payload
+pub(crate) struct ChannelDecipher {
+    secret: [u8; CHANNEL_KEY_LEN],
+    channel_id: ChannelId,
+    sender_side: Side,
+    /// Key of the last salt which opened a payload
+    current: Option<([u8; SALT_LEN], LessSafeKey)>,
+}
+
+impl ChannelDecipher {
+    /// Derives the keying material used by `sender_side` to send on `channel_id`.
+    pub(crate) fn derive<C: TransportConnection>(
+        connection: &C,
+        encryption: &ChannelEncryption,
+        channel_id: ChannelId,
+        sender_side: Side,
+    ) -> Result<Self, ChannelKeyDerivationError> {
+        Ok(Self {
+            secret: channel_secret(connection, encryption, channel_id, sender_side)?,
+            channel_id,
+            sender_side,
+            current: None,
+        })
+    }
+
+    pub(crate) fn open(&mut self, sealed: Bytes) -> Option<Bytes> {
+        if sealed.len() < ENCRYPTED_PAYLOAD_OVERHEAD {
+            return None;
+        }
+        let salt: [u8; SALT_LEN] = sealed[..SALT_LEN].try_into().ok()?;
+        let counter = u64::from_be_bytes(
+            sealed[SALT_LEN..SALT_LEN + NONCE_COUNTER_LEN]
+                .try_into()
+                .ok()?,
+        );
+        let mut in_out = sealed[SALT_LEN + NONCE_COUNTER_LEN..].to_vec();
+        let aad = Aad::from([self.channel_id]);
+        let plaintext_len = match &self.current {
+            Some((current_salt, key)) if *current_salt == salt => key
+                .open_in_place(nonce(counter), aad, &mut in_out)
+                .ok()?
+                .len(),
+            _ => {
+                let key =
+                    salted_key(&self.secret, &salt, self.channel_id, self.sender_side).ok()?;
+                let plaintext_len = key
+                    .open_in_place(nonce(counter), aad, &mut in_out)
+                    .ok()?
+                    .len();
+                // Only the salts of authentic payloads replace the key of the last opening
+                self.current = Some((salt, key));
+                plaintext_len
+            }
+        };
+        in_out.truncate(plaintext_len);
+        Some(in_out.into())
+    }
+}
@@ -0,0 +1,235 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+/// Size overhead added to each datagram sent on a channel with forward error correction, in bytes
+pub const FEC_HEADER_LEN: usize = 9;
+/// Number of groups kept by the receiver of a channel with forward error correction, waiting for their parity. Losses in older groups can't be recovered.
+pub const FEC_RECEIVE_WINDOW: usize = 16;
+/// Delay without any new datagram on a channel with forward error correction after which the parity of its partial group is sent
+pub const FEC_FLUSH_DELAY: Duration = Duration::from_millis(20);
+
+/// Index of the parity datagram of a group
+const PARITY_INDEX: u8 = u8::MAX;
+/// Size of the group number on the wire, the groups wrap around after `2^40` groups
+const GROUP_LEN: usize = 5;
+const GROUP_MASK: u64 = (1 << (8 * GROUP_LEN)) - 1;
+
+/// GROUP | INDEX | COUNT | LEN | PAYLOAD
+///
+/// `COUNT` is the number of payloads of the group, only set in the parity datagram. `LEN` is the length of the payload, the XOR of the lengths of the payloads of the group in the parity datagram.
+fn write_fec_header(group: u64, index: u8, count: u8, len: u16, payload: &[u8]) -> Bytes {
+    let mut datagram = BytesMut::with_capacity(FEC_HEADER_LEN + payload.len());
+    datagram.put_uint(group, GROUP_LEN);
+    datagram.put_u8(index);
+    datagram.put_u8(count);
+    datagram.put_u16(len);
+    datagram.extend_from_slice(payload);
+    datagram.into()
+}
+
+/// XORs `payload` into `parity`, padded with zeroes to the longest of them
+fn xor_into(parity: &mut Vec<u8>, payload: &[u8]) {
+    if parity.len() < payload.len() {
+        parity.resize(payload.len(), 0);
+    }
+    for (parity, byte) in parity.iter_mut().zip(payload) {
+        *parity ^= byte;
+    }
+}
+
+/// Groups the encoded payloads of a channel and adds a parity datagram after each group, the XOR of its payloads.
+///
+/// The parity datagram is never longer than the longest datagram of its group.
+#[derive(Debug)]
+pub(crate) struct FecEncoder {
+    group_size: u8,
+    group: u64,
+    index: u8,
+    parity: Vec<u8>,
+    parity_len: u16,
+}
+
+impl FecEncoder {
+    pub(crate) fn new(group_size: u8) -> Self {
+        Self {
+            group_size: group_size.min(PARITY_INDEX - 1),
+            group: 0,
+            index: 0,
+            parity: Vec::new(),
+            parity_len: 0,
+        }
+    }
+
+    /// Returns the datagram payload carrying `payload` in the current group. The group only counts it once it is [`FecEncoder::sent`].
+    pub(crate) fn encode(&self, payload: &[u8]) -> Bytes {
+        write_fec_header(self.group, self.index, 0, payload.len() as u16, payload)
+    }
+
+    /// Adds a datagram returned by [`FecEncoder::encode`] to its group once sent. Returns the parity datagram payload if it completes its group.
+    pub(crate) fn sent(&mut self, datagram: &[u8]) -> Option<Bytes> {
+        let payload = datagram.get(FEC_HEADER_LEN..)?;
+        xor_into(&mut self.parity, payload);
+        self.parity_len ^= payload.len() as u16;
+        self.index += 1;
+        match self.index < self.group_size {
+            true => None,
+            false => self.parity(),
+        }
+    }
+
+    /// Whether datagrams were sent in the current group, waiting for its parity
+    pub(crate) fn has_partial_group(&self) -> bool {
+        self.index > 0
+    }
+
+    /// Returns the parity datagram payload of the current group and starts the next one, `None` if no datagram was sent in the group.
+    ///
+    /// Sends the parity of a partial group, so that the last datagrams sent before a pause can be recovered.
+    pub(crate) fn parity(&mut self) -> Option<Bytes> {
+        if self.index == 0 {
+            return None;
+        }
+        let parity = write_fec_header(
+            self.group,
+            PARITY_INDEX,
+            self.index,
+            self.parity_len,
+            &self.parity,
+        );
+        self.parity.clear();
+        self.parity_len = 0;
+        self.group = (self.group + 1) & GROUP_MASK;
+        self.index = 0;
+        Some(parity)
+    }
+}
+
+#[derive(Debug)]
+struct ReceivedGroup {
+    group: u64,
+    payloads: Vec<Option<Bytes>>,
+    /// XOR of the lengths and of the payloads of the group
+    parity: Option<(u16, Bytes)>,
+}
+
+impl ReceivedGroup {
+    /// The missing payload if it is the only one missing and the parity was received
+    fn recover(&mut self) -> Option<Bytes> {
+        let (len, parity) = self.parity.as_ref()?;
+        let mut missing = self
+            .payloads
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.is_none());
+        let (missing_index, _) = missing.next()?;
+        if missing.next().is_some() {
+            return None;
+        }
+        let mut len = *len;
+        let mut recovered = parity.to_vec();
+        for payload in self.payloads.iter().flatten() {
+            if payload.len() > recovered.len() {
+                return None;
+            }
+            xor_into(&mut recovered, payload);
+            len ^= payload.len() as u16;
+        }
+        let payload = Bytes::copy_from_slice(recovered.get(..len as usize)?);
+        self.payloads[missing_index] = Some(payload.clone());
+        Some(payload)
+    }
+}
+
+/// Recovers the single losses of each group of a channel with forward error correction
+#[derive(Debug)]
+pub(crate) struct FecDecoder {
+    group_size: u8,
+    /// By increasing group
+    groups: VecDeque<ReceivedGroup>,
+}
+
+impl FecDecoder {
+    pub(crate) fn new(group_size: u8) -> Self {
+        Self {
+            group_size: group_size.min(PARITY_INDEX - 1),
+            groups: VecDeque::new(),
+        }
+    }
+
+    /// Returns the payloads carried by a datagram: its own payload, and the payload of its group recovered thanks to it, if any. `None` if the datagram is malformed.
+    ///
+    /// Payloads already received or recovered are not returned again.
+    pub(crate) fn receive(&mut self, mut datagram: Bytes) -> Option<[Option<Bytes>; 2]> {
+        if datagram.len() < FEC_HEADER_LEN {
+            return None;
+        }
+        let mut header = datagram.split_to(FEC_HEADER_LEN);
+        let group = header.get_uint(GROUP_LEN);
+        let index = header.get_u8();
+        let count = header.get_u8();
+        let len = header.get_u16();
+        if index != PARITY_INDEX && index >= self.group_size {
+            return None;
+        }
+        match index {
+            PARITY_INDEX if count == 0 || count > self.group_size => return None,
+            PARITY_INDEX => (),
+            _ if len as usize != datagram.len() => return None,
+            _ => (),
+        }
+        let Some(received) = self.group(group) else {
+            // Too old to be recovered, still delivered
+            return Some([(index != PARITY_INDEX).then_some(datagram), None]);
+        };
+        let payload = match index {
+            PARITY_INDEX => {
+                if received.parity.is_none() {
+                    // The datagrams of a partial group past its count are not expected
+                    received.payloads.truncate(count as usize);
+                    received.parity = Some((len, datagram));
+                }
+                None
+            }
+            _ if index as usize >= received.payloads.len() => Some(datagram),
+            _ => match &received.payloads[index as usize] {
+                Some(_) => return Some([None, None]),
+                None => {
+                    received.payloads[index as usize] = Some(datagram.clone());
+                    Some(datagram)
+                }
+            },
+        };
+        Some([payload, received.recover()])
+    }
+
+    /// Group `group`, created if needed. `None` if older than the window.
+    fn group(&mut self, group: u64) -> Option<&mut ReceivedGroup> {
+        if let Some(position) = self.groups.iter().position(|g| g.group >= group) {
+            if self.groups[position].group != group {
+                if self.groups.len() == FEC_RECEIVE_WINDOW && position == 0 {
+                    return None;
+                }
+                self.groups.insert(position, self.new_group(group));
+            }
+            if self.groups.len() > FEC_RECEIVE_WINDOW {
+                self.groups.pop_front();
+                return self.groups.get_mut(position - 1);
+            }
+            return self.groups.get_mut(position);
+        }
+        self.groups.push_back(self.new_group(group));
+        if self.groups.len() > FEC_RECEIVE_WINDOW {
+            self.groups.pop_front();
+        }
+        self.groups.back_mut()
+    }
+
+    fn new_group(&self, group: u64) -> ReceivedGroup {
+        ReceivedGroup {
+            group,
+            payloads: vec![None; self.group_size as usize],
+            parity: None,
+        }
+    }
+}
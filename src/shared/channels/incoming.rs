@@ -0,0 +1,514 @@
+#[cfg(any(feature = "client", feature = "server"))]
+use std::collections::HashMap;
+use std::{collections::VecDeque, time::Instant};
+#[cfg(feature = "no-bevy")]
+use std::{future::poll_fn, task::Poll};
+
+use bytes::Bytes;
+use tokio::sync::mpsc::{self, error::TryRecvError};
+
+use crate::shared::memory::SharedConnectionMemory;
+
+use super::{
+    trace::{MessageTrace, MAX_BUFFERED_TRACES},
+    ChannelId, CONTROL_CHANNEL_ID,
+};
+
+/// Payload sent by the receiving tasks to the sync client or server, with its trace if the channel is traced, the id to acknowledge if the payload is tracked, and the instant it arrived from the network
+pub(crate) type ReceivedPayload = (ChannelId, Bytes, Option<MessageTrace>, Option<u64>, Instant);
+
+/// The async channel was closed and all the received payloads were consumed
+#[derive(Debug)]
+pub(crate) struct IncomingPayloadsClosed;
+
+/// Opens the async channel of the payloads received on a connection, made of one lane per receiving task, each lane holding up to `capacity` payloads
+pub(crate) fn incoming_channel(capacity: usize) -> (IncomingSend, IncomingRecv) {
+    let (lanes_send, lanes_recv) = mpsc::unbounded_channel();
+    (
+        IncomingSend {
+            lanes: lanes_send,
+            capacity,
+        },
+        IncomingRecv {
+            new_lanes: lanes_recv,
+            lanes: Vec::new(),
+            merged: VecDeque::new(),
+            opening: true,
+        },
+    )
+}
+
+/// Sending end of the payloads received on a connection.
+///
+/// Each receiving task (the task of each reliable stream, the task of the datagrams) sends on a lane of its own, a single producer queue: the tasks of a connection receiving at the same time never contend on a shared queue.
+#[derive(Debug, Clone)]
+pub(crate) struct IncomingSend {
+    lanes: mpsc::UnboundedSender<mpsc::Receiver<ReceivedPayload>>,
+    capacity: usize,
+}
+
+impl IncomingSend {
+    /// Opens a lane for a single receiving task. The payloads of a lane are received in their sending order.
+    pub(crate) fn lane(&self) -> mpsc::Sender<ReceivedPayload> {
+        let (send, recv) = mpsc::channel(self.capacity);
+        // If the receiving end is dropped, so is the lane and the sends on it fail
+        let _ = self.lanes.send(recv);
+        send
+    }
+}
+
+/// Receiving end of the payloads received on a connection, merging its lanes
+#[derive(Debug)]
+pub(crate) struct IncomingRecv {
+    new_lanes: mpsc::UnboundedReceiver<mpsc::Receiver<ReceivedPayload>>,
+    lanes: Vec<Lane>,
+    /// Payloads read from the lanes, in their arrival order
+    merged: VecDeque<ReceivedPayload>,
+    /// New lanes can still be opened
+    opening: bool,
+}
+
+/// Lane of a receiving task, see [`IncomingSend::lane`]
+#[derive(Debug)]
+struct Lane {
+    recv: mpsc::Receiver<ReceivedPayload>,
+    /// Payloads read from the lane and not merged yet
+    pending: VecDeque<ReceivedPayload>,
+    closed: bool,
+}
+
+impl Lane {
+    fn new(recv: mpsc::Receiver<ReceivedPayload>) -> Self {
+        Self {
+            recv,
+            pending: VecDeque::new(),
+            closed: false,
+        }
+    }
+
+    /// Reads everything available in the lane, returns true if some payloads are pending
+    fn read(&mut self) -> bool {
+        while !self.closed {
+            match self.recv.try_recv() {
+                Ok(payload) => self.pending.push_back(payload),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.closed = true,
+            }
+        }
+        !self.pending.is_empty()
+    }
+}
+
+impl IncomingRecv {
+    fn accept_lanes(&mut self) {
+        while self.opening {
+            match self.new_lanes.try_recv() {
+                Ok(lane) => self.lanes.push(Lane::new(lane)),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => self.opening = false,
+            }
+        }
+    }
+
+    /// Moves everything available in the lanes to the merged payloads, interleaving the lanes by arrival instant. Returns false once all the lanes are closed and empty.
+    fn merge(&mut self) -> bool {
+        self.accept_lanes();
+        let mut filled = 0;
+        for lane in self.lanes.iter_mut() {
+            filled += lane.read() as usize;
+        }
+        match filled {
+            0 => (),
+            1 => {
+                for lane in self.lanes.iter_mut() {
+                    self.merged.extend(lane.pending.drain(..));
+                }
+            }
+            // Keeps the order of each lane, the payloads of a channel all come from the same lane
+            _ => {
+                while let Some(lane) = self
+                    .lanes
+                    .iter_mut()
+                    .filter(|lane| !lane.pending.is_empty())
+                    .min_by_key(|lane| lane.pending.front().map(|payload| payload.4))
+                {
+                    self.merged.extend(lane.pending.pop_front());
+                }
+            }
+        }
+        self.lanes
+            .retain(|lane| !lane.closed || !lane.pending.is_empty());
+        self.opening || !self.lanes.is_empty()
+    }
+
+    fn try_recv(&mut self) -> Result<ReceivedPayload, TryRecvError> {
+        if self.merged.is_empty() && !self.merge() && self.merged.is_empty() {
+            return Err(TryRecvError::Disconnected);
+        }
+        self.merged.pop_front().ok_or(TryRecvError::Empty)
+    }
+
+    /// Waits for the next payload of any lane. Cancel safe.
+    #[cfg(feature = "no-bevy")]
+    async fn recv(&mut self) -> Option<ReceivedPayload> {
+        poll_fn(|cx| {
+            if let Some(payload) = self.merged.pop_front() {
+                return Poll::Ready(Some(payload));
+            }
+            while self.opening {
+                match self.new_lanes.poll_recv(cx) {
+                    Poll::Ready(Some(lane)) => self.lanes.push(Lane::new(lane)),
+                    Poll::Ready(None) => self.opening = false,
+                    Poll::Pending => break,
+                }
+            }
+            let mut received = None;
+            for lane in self.lanes.iter_mut() {
+                if let Some(payload) = lane.pending.pop_front() {
+                    received = Some(payload);
+                    break;
+                }
+                match lane.recv.poll_recv(cx) {
+                    Poll::Ready(Some(payload)) => {
+                        received = Some(payload);
+                        break;
+                    }
+                    Poll::Ready(None) => lane.closed = true,
+                    Poll::Pending => (),
+                }
+            }
+            self.lanes
+                .retain(|lane| !lane.closed || !lane.pending.is_empty());
+            match received {
+                Some(payload) => Poll::Ready(Some(payload)),
+                None if !self.opening && self.lanes.is_empty() => Poll::Ready(None),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+/// Payloads received on a connection, waiting to be read by the sync client or server.
+///
+/// Payloads can be read one by one, all at once, or channel by channel. Payloads of the other channels stay buffered in their receiving order.
+///
+/// Payloads of the [`CONTROL_CHANNEL_ID`] are set aside for Quinnet, see [`IncomingPayloads::take_control`]. Traces of the payloads of traced channels are kept apart, see [`IncomingPayloads::drain_traces`], as are the ids of the tracked payloads to acknowledge, see [`IncomingPayloads::take_acks`].
+#[derive(Debug)]
+pub(crate) struct IncomingPayloads {
+    recv: IncomingRecv,
+    /// With the instant each payload arrived
+    buffered: VecDeque<(ChannelId, Bytes, Instant)>,
+    control: Vec<Bytes>,
+    traces: VecDeque<MessageTrace>,
+    acks: Vec<u64>,
+    /// Payloads held until their instant, see [`IncomingPayloads::hold`]
+    #[cfg(feature = "server")]
+    held: VecDeque<(Instant, ChannelId, Bytes)>,
+    #[cfg(feature = "server")]
+    holding: bool,
+    #[cfg(feature = "server")]
+    disconnected: bool,
+    /// Payloads are only read from the async channel by [`IncomingPayloads::capture`], see [`IncomingPayloads::set_frozen`]
+    frozen: bool,
+    /// The async channel was found closed by the last capture
+    closed: bool,
+    /// Size of the payloads buffered, held and set aside, counted in the memory of the connection
+    bytes: usize,
+    memory: SharedConnectionMemory,
+}
+
+impl IncomingPayloads {
+    pub(crate) fn new(recv: IncomingRecv, memory: SharedConnectionMemory) -> Self {
+        Self {
+            recv,
+            buffered: VecDeque::new(),
+            control: Vec::new(),
+            traces: VecDeque::new(),
+            acks: Vec::new(),
+            #[cfg(feature = "server")]
+            held: VecDeque::new(),
+            #[cfg(feature = "server")]
+            holding: false,
+            #[cfg(feature = "server")]
+            disconnected: false,
+            frozen: false,
+            closed: false,
+            bytes: 0,
+            memory,
+        }
+    }
+
+    fn store(&mut self, len: usize) {
+        self.bytes += len;
+        self.memory.add(len);
+    }
+
+    fn release(&mut self, len: usize) {
+        self.bytes -= len;
+        self.memory.remove(len);
+    }
+
+    fn set_aside_control(&mut self, payload: Bytes) {
+        self.store(payload.len());
+        self.control.push(payload);
+    }
+
+    /// Moves everything available in the async channel to the held payloads, each until the instant given by `due_at` for its channel (`None` drops it), then makes the payloads due at `now` available. Until [`IncomingPayloads::stop_holding`], payloads are only read from the async channel by this method.
+    #[cfg(feature = "server")]
+    pub(crate) fn hold<F>(&mut self, now: Instant, mut due_at: F)
+    where
+        F: FnMut(ChannelId) -> Option<Instant>,
+    {
+        self.holding = true;
+        loop {
+            match self.recv.try_recv() {
+                Ok((CONTROL_CHANNEL_ID, payload, _, _, _)) => self.set_aside_control(payload),
+                Ok((channel_id, payload, trace, ack_id, _)) => {
+                    self.keep_trace(trace);
+                    // Dropped payloads are not acknowledged, as if lost on the network
+                    if let Some(due) = due_at(channel_id) {
+                        self.acks.extend(ack_id);
+                        self.store(payload.len());
+                        self.held.push_back((due, channel_id, payload));
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.disconnected = true;
+                    break;
+                }
+            }
+        }
+        while let Some((due, _, _)) = self.held.front() {
+            if *due > now {
+                break;
+            }
+            // Held payloads arrive when released
+            if let Some((due, channel_id, payload)) = self.held.pop_front() {
+                self.buffered.push_back((channel_id, payload, due));
+            }
+        }
+    }
+
+    /// Makes all the held payloads available and reads the async channel again
+    #[cfg(feature = "server")]
+    pub(crate) fn stop_holding(&mut self) {
+        self.holding = false;
+        let now = Instant::now();
+        self.buffered.extend(
+            self.held
+                .drain(..)
+                .map(|(_, channel_id, payload)| (channel_id, payload, now)),
+        );
+    }
+
+    /// While frozen, the readers only see the payloads moved to the buffer by the last [`IncomingPayloads::capture`], instead of the payloads arriving as they are read.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Moves everything available in the async channel to the buffer if frozen. Payloads held by [`IncomingPayloads::hold`] are captured by it instead.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn capture(&mut self) {
+        if !self.frozen {
+            return;
+        }
+        #[cfg(feature = "server")]
+        if self.holding {
+            return;
+        }
+        self.closed = !self.read_channel();
+    }
+
+    /// Puts a payload taken from the buffer back in front of it, such as a payload which could not be routed
+    #[cfg(feature = "server")]
+    pub(crate) fn requeue(&mut self, channel_id: ChannelId, payload: Bytes, received_at: Instant) {
+        self.store(payload.len());
+        self.buffered.push_front((channel_id, payload, received_at));
+    }
+
+    fn keep_trace(&mut self, trace: Option<MessageTrace>) {
+        if let Some(trace) = trace {
+            if self.traces.len() == MAX_BUFFERED_TRACES {
+                self.traces.pop_front();
+            }
+            self.traces.push_back(trace);
+        }
+    }
+
+    pub(crate) fn try_recv(
+        &mut self,
+    ) -> Result<Option<(ChannelId, Bytes)>, IncomingPayloadsClosed> {
+        Ok(self
+            .try_recv_timestamped()?
+            .map(|(channel_id, payload, _)| (channel_id, payload)))
+    }
+
+    /// Same as [`IncomingPayloads::try_recv`], with the instant the payload arrived
+    pub(crate) fn try_recv_timestamped(
+        &mut self,
+    ) -> Result<Option<(ChannelId, Bytes, Instant)>, IncomingPayloadsClosed> {
+        if let Some(payload) = self.buffered.pop_front() {
+            self.release(payload.1.len());
+            return Ok(Some(payload));
+        }
+        #[cfg(feature = "server")]
+        if self.holding {
+            return match self.disconnected && self.held.is_empty() {
+                true => Err(IncomingPayloadsClosed),
+                false => Ok(None),
+            };
+        }
+        if self.frozen {
+            return match self.closed {
+                true => Err(IncomingPayloadsClosed),
+                false => Ok(None),
+            };
+        }
+        loop {
+            match self.recv.try_recv() {
+                Ok((CONTROL_CHANNEL_ID, payload, _, _, _)) => self.set_aside_control(payload),
+                Ok((channel_id, payload, trace, ack_id, received_at)) => {
+                    self.keep_trace(trace);
+                    self.acks.extend(ack_id);
+                    return Ok(Some((channel_id, payload, received_at)));
+                }
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(IncomingPayloadsClosed),
+            }
+        }
+    }
+
+    /// Waits for the next payload, same as [`IncomingPayloads::try_recv`] otherwise. Cancel safe. Payloads held by [`IncomingPayloads::hold`] are not waited for.
+    #[cfg(feature = "no-bevy")]
+    pub(crate) async fn recv(&mut self) -> Result<(ChannelId, Bytes), IncomingPayloadsClosed> {
+        if let Some((channel_id, payload, _)) = self.buffered.pop_front() {
+            self.release(payload.len());
+            return Ok((channel_id, payload));
+        }
+        loop {
+            match self.recv.recv().await {
+                Some((CONTROL_CHANNEL_ID, payload, _, _, _)) => self.set_aside_control(payload),
+                Some((channel_id, payload, trace, ack_id, _)) => {
+                    self.keep_trace(trace);
+                    self.acks.extend(ack_id);
+                    return Ok((channel_id, payload));
+                }
+                None => return Err(IncomingPayloadsClosed),
+            }
+        }
+    }
+
+    /// Removes the traces of the payloads received on traced channels, in their receiving order
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn drain_traces(&mut self) -> Vec<MessageTrace> {
+        self.fill_buffer();
+        self.traces.drain(..).collect()
+    }
+
+    /// Removes the ids of the tracked payloads received since the last call, to acknowledge to the peer
+    pub(crate) fn take_acks(&mut self) -> Vec<u64> {
+        self.fill_buffer();
+        std::mem::take(&mut self.acks)
+    }
+
+    /// Removes the received control payloads, in their receiving order
+    pub(crate) fn take_control(&mut self) -> Vec<Bytes> {
+        self.fill_buffer();
+        let control = std::mem::take(&mut self.control);
+        self.release(control.iter().map(Bytes::len).sum());
+        control
+    }
+
+    /// Makes the received payloads available to the readers, unless frozen or holding. Returns false if the async channel is closed.
+    fn fill_buffer(&mut self) -> bool {
+        #[cfg(feature = "server")]
+        if self.holding {
+            return !self.disconnected || !self.held.is_empty();
+        }
+        if self.frozen {
+            return !self.closed;
+        }
+        self.read_channel()
+    }
+
+    /// Moves everything available in the async channel to the buffer. Returns false if the async channel is closed.
+    fn read_channel(&mut self) -> bool {
+        let open = self.recv.merge();
+        while let Some((channel_id, payload, trace, ack_id, received_at)) =
+            self.recv.merged.pop_front()
+        {
+            match channel_id {
+                CONTROL_CHANNEL_ID => self.set_aside_control(payload),
+                _ => {
+                    self.keep_trace(trace);
+                    self.acks.extend(ack_id);
+                    self.store(payload.len());
+                    self.buffered.push_back((channel_id, payload, received_at));
+                }
+            }
+        }
+        open
+    }
+
+    /// Removes all the received payloads of `channel_id`
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn drain_channel(
+        &mut self,
+        channel_id: ChannelId,
+    ) -> Result<Vec<Bytes>, IncomingPayloadsClosed> {
+        Ok(self
+            .drain_channel_timestamped(channel_id)?
+            .into_iter()
+            .map(|(payload, _)| payload)
+            .collect())
+    }
+
+    /// Same as [`IncomingPayloads::drain_channel`], with the instant each payload arrived
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn drain_channel_timestamped(
+        &mut self,
+        channel_id: ChannelId,
+    ) -> Result<Vec<(Bytes, Instant)>, IncomingPayloadsClosed> {
+        let open = self.fill_buffer();
+        let mut payloads = Vec::new();
+        self.buffered
+            .retain(|(id, payload, received_at)| match *id == channel_id {
+                true => {
+                    payloads.push((payload.clone(), *received_at));
+                    false
+                }
+                false => true,
+            });
+        self.release(payloads.iter().map(|(payload, _)| payload.len()).sum());
+        match payloads.is_empty() && !open && self.buffered.is_empty() {
+            true => Err(IncomingPayloadsClosed),
+            false => Ok(payloads),
+        }
+    }
+
+    /// Removes all the received payloads, grouped by channel
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn drain(
+        &mut self,
+    ) -> Result<HashMap<ChannelId, Vec<Bytes>>, IncomingPayloadsClosed> {
+        let open = self.fill_buffer();
+        if !open && self.buffered.is_empty() {
+            return Err(IncomingPayloadsClosed);
+        }
+        let mut payloads: HashMap<ChannelId, Vec<Bytes>> = HashMap::new();
+        for (channel_id, payload, _) in std::mem::take(&mut self.buffered) {
+            self.release(payload.len());
+            payloads.entry(channel_id).or_default().push(payload);
+        }
+        Ok(payloads)
+    }
+}
+
+impl Drop for IncomingPayloads {
+    fn drop(&mut self) {
+        self.memory.remove(self.bytes);
+    }
+}
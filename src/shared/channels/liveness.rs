@@ -0,0 +1,101 @@
+use std::time::Duration;
+#[cfg(any(feature = "client", feature = "server"))]
+use std::time::Instant;
+
+/// Liveness probe of a reliable channel, see [`super::ChannelConfig::liveness_probe`].
+///
+/// QUIC keep-alives only prove that the connection is alive: the stream of a channel can still be stuck, such as when the peer stopped reading it and the flow control blocks it. The probe is a tiny frame sent periodically on the stream of the channel itself, behind the frames already written on it, and answered by the peer on the control channel. A probe left unanswered for a timeout marks the channel as unresponsive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LivenessProbe {
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl LivenessProbe {
+    /// Probes the channel `interval` after the answer to the previous probe, the channel is unresponsive once a probe is left unanswered for `timeout`
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self { interval, timeout }
+    }
+
+    /// Period between the answer to a probe and the next probe
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Time after which an unanswered probe marks the channel as unresponsive
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+/// Probe sent and not answered yet
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug)]
+struct PendingProbe {
+    sequence: u64,
+    sent_at: Instant,
+    reported: bool,
+}
+
+/// State of the [`LivenessProbe`] of a channel, on the sync side
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug)]
+pub(crate) struct ChannelLiveness {
+    probe: LivenessProbe,
+    next_sequence: u64,
+    /// Answer to the last probe, or opening of the channel
+    last_answer: Instant,
+    pending: Option<PendingProbe>,
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl ChannelLiveness {
+    pub(crate) fn new(probe: LivenessProbe) -> Self {
+        Self {
+            probe,
+            next_sequence: 0,
+            last_answer: Instant::now(),
+            pending: None,
+        }
+    }
+
+    /// Returns the sequence of the probe to send, if one is due. Only one probe is pending at a time.
+    pub(crate) fn due_probe(&mut self, now: Instant) -> Option<u64> {
+        if self.pending.is_some()
+            || now.saturating_duration_since(self.last_answer) < self.probe.interval
+        {
+            return None;
+        }
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.pending = Some(PendingProbe {
+            sequence,
+            sent_at: now,
+            reported: false,
+        });
+        Some(sequence)
+    }
+
+    /// Returns how long the pending probe waited for its answer once it exceeds the timeout, only once per probe
+    pub(crate) fn check(&mut self, now: Instant) -> Option<Duration> {
+        let pending = self.pending.as_mut()?;
+        let waited = now.saturating_duration_since(pending.sent_at);
+        if pending.reported || waited < self.probe.timeout {
+            return None;
+        }
+        pending.reported = true;
+        Some(waited)
+    }
+
+    /// Records the answer of the peer to the probe `sequence`
+    pub(crate) fn answer(&mut self, sequence: u64, now: Instant) {
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.sequence == sequence)
+        {
+            self.pending = None;
+            self.last_answer = now;
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use std::{collections::HashMap, time::Duration};
+
+use bytes::Bytes;
+use quinn_proto::Side;
+use tracing::warn;
+
+#[cfg(feature = "fec")]
+use super::fec::{FecDecoder, FecEncoder, FEC_FLUSH_DELAY};
+use super::{
+    ack::{read_ack_header, ACK_HEADER_LEN},
+    compression::{compress, decompress, DictionaryCompressor, DictionaryDecompressor},
+    control::{control_channel_config, CONTROL_CHANNEL_ID},
+    encryption::{ChannelCipher, ChannelDecipher},
+    redundancy::REDUNDANCY_HEADER_LEN,
+    replay::{NonceStamper, ReplayWindow},
+    tick::{read_tick_header, TICK_HEADER_LEN},
+    trace::{read_trace, MessageTrace, TraceStamper, TRACE_HEADER_LEN},
+    ChannelConfig, ChannelId, SharedChannelConfigs, DEFAULT_MAX_RELIABLE_FRAME_LEN,
+};
+use crate::shared::{
+    hardening::{ProtocolViolation, ReceiveHardening},
+    tick::SharedNetworkTick,
+    transport::TransportConnection,
+};
+
+/// Transforms the payloads sent on a channel according to its [`ChannelConfig`], on top of the redundancy, tick and acknowledgement headers written by the sync side: trace stamp first, then compression, then replay nonce, then encryption.
+pub(crate) struct PayloadEncoder {
+    stamper: Option<TraceStamper>,
+    compressed: bool,
+    dictionary: Option<DictionaryCompressor>,
+    nonces: Option<NonceStamper>,
+    cipher: Option<ChannelCipher>,
+    #[cfg(feature = "fec")]
+    fec: Option<FecEncoder>,
+}
+
+impl PayloadEncoder {
+    pub(crate) fn new(config: &ChannelConfig, cipher: Option<ChannelCipher>) -> Self {
+        Self {
+            stamper: config.is_traced().then(TraceStamper::default),
+            compressed: config.is_compressed(),
+            dictionary: config
+                .compression_dictionary()
+                .map(DictionaryCompressor::new),
+            nonces: config.is_replay_protected().then(NonceStamper::default),
+            cipher,
+            #[cfg(feature = "fec")]
+            fec: config.fec_group_size().map(FecEncoder::new),
+        }
+    }
+
+    pub(crate) fn encode(&mut self, payload: Bytes) -> Bytes {
+        let payload = match &mut self.stamper {
+            Some(stamper) => stamper.stamp(payload),
+            None => payload,
+        };
+        let payload = match (&mut self.dictionary, self.compressed) {
+            (Some(dictionary), _) => dictionary.compress(&payload),
+            (None, true) => compress(&payload),
+            (None, false) => payload,
+        };
+        let payload = match &mut self.nonces {
+            Some(nonces) => nonces.stamp(payload),
+            None => payload,
+        };
+        match &mut self.cipher {
+            Some(cipher) => cipher.seal(payload),
+            None => payload,
+        }
+    }
+
+    /// Encodes a payload sent as a datagram, see [`ChannelConfig::fec`]. Once sent, the datagram must be passed to [`PayloadEncoder::datagram_sent`].
+    pub(crate) fn encode_datagram(&mut self, payload: Bytes) -> Bytes {
+        let payload = self.encode(payload);
+        #[cfg(feature = "fec")]
+        if let Some(fec) = &self.fec {
+            return fec.encode(&payload);
+        }
+        payload
+    }
+
+    /// Adds a sent datagram to its group. Returns the parity datagram of its group if it completes it.
+    #[cfg_attr(not(feature = "fec"), allow(unused_variables))]
+    pub(crate) fn datagram_sent(&mut self, datagram: &[u8]) -> Option<Bytes> {
+        #[cfg(feature = "fec")]
+        if let Some(fec) = &mut self.fec {
+            return fec.sent(datagram);
+        }
+        None
+    }
+
+    /// Delay after which the parity datagram of the partial group is due if no other datagram is sent, `None` without a partial group
+    pub(crate) fn partial_parity_delay(&self) -> Option<Duration> {
+        #[cfg(feature = "fec")]
+        if let Some(fec) = &self.fec {
+            return fec.has_partial_group().then_some(FEC_FLUSH_DELAY);
+        }
+        None
+    }
+
+    /// Returns the parity datagram of the partial group, if any, see [`ChannelConfig::fec`]
+    pub(crate) fn partial_parity(&mut self) -> Option<Bytes> {
+        #[cfg(feature = "fec")]
+        if let Some(fec) = &mut self.fec {
+            return fec.parity();
+        }
+        None
+    }
+}
+
+/// Used by the receiving tasks of a connection to reverse the transformations applied by the [`PayloadEncoder`] of the peer.
+pub(crate) struct PayloadDecoder<C: TransportConnection> {
+    connection: C,
+    channels_configs: SharedChannelConfigs,
+    deciphers: HashMap<ChannelId, ChannelDecipher>,
+    decompressors: HashMap<ChannelId, DictionaryDecompressor>,
+    replay_windows: HashMap<ChannelId, ReplayWindow>,
+    /// Sequences of the payloads received on the redundant channels, to drop their copies
+    redundancy_windows: HashMap<ChannelId, ReplayWindow>,
+    #[cfg(feature = "fec")]
+    fec_decoders: HashMap<ChannelId, FecDecoder>,
+    hardening: ReceiveHardening,
+    /// Updated with the ticks read from the payloads of the server, see [`ChannelConfig::ticked`]
+    server_tick: SharedNetworkTick,
+}
+
+impl<C: TransportConnection> PayloadDecoder<C> {
+    pub(crate) fn new(
+        connection: C,
+        channels_configs: SharedChannelConfigs,
+        hardening: ReceiveHardening,
+        server_tick: SharedNetworkTick,
+    ) -> Self {
+        Self {
+            connection,
+            channels_configs,
+            deciphers: HashMap::new(),
+            decompressors: HashMap::new(),
+            replay_windows: HashMap::new(),
+            redundancy_windows: HashMap::new(),
+            #[cfg(feature = "fec")]
+            fec_decoders: HashMap::new(),
+            hardening,
+            server_tick,
+        }
+    }
+
+    pub(crate) fn hardening(&self) -> &ReceiveHardening {
+        &self.hardening
+    }
+
+    pub(crate) fn channels_configs(&self) -> SharedChannelConfigs {
+        self.channels_configs.clone()
+    }
+
+    /// Returns the payload, its trace if the channel is traced, and the id to acknowledge if the payload is tracked. Returns `None` if the payload could not be decrypted, was replayed, could not be decompressed, lacks its trace or its tick, is a copy of a payload already received on a redundant channel, or exceeds the maximum message size of the channel. In strict mode, also returns `None` for the payloads of unknown channels.
+    ///
+    /// Rejected payloads are reported as [`ProtocolViolation`].
+    pub(crate) fn decode(
+        &mut self,
+        channel_id: ChannelId,
+        payload: Bytes,
+    ) -> Option<(Bytes, Option<MessageTrace>, Option<u64>)> {
+        let config = match self.channels_configs.read() {
+            Ok(configs) => configs.get(&channel_id).cloned(),
+            Err(_) => None,
+        }
+        .or_else(|| (channel_id == CONTROL_CHANNEL_ID).then(control_channel_config));
+        let Some(config) = config else {
+            self.deciphers.remove(&channel_id);
+            self.decompressors.remove(&channel_id);
+            self.replay_windows.remove(&channel_id);
+            self.redundancy_windows.remove(&channel_id);
+            if self.hardening.is_strict() {
+                self.hardening
+                    .report(ProtocolViolation::UnknownChannel(channel_id));
+                return None;
+            }
+            return Some((payload, None, None));
+        };
+        let payload = self.decrypt(channel_id, &config, payload)?;
+        let payload = self.check_replay(channel_id, &config, payload)?;
+        let max_message_size = config
+            .message_size_limit()
+            .unwrap_or(DEFAULT_MAX_RELIABLE_FRAME_LEN);
+        let trace_header_len = match config.is_traced() {
+            true => TRACE_HEADER_LEN,
+            false => 0,
+        };
+        let ack_header_len = match config.is_acknowledged() {
+            true => ACK_HEADER_LEN,
+            false => 0,
+        };
+        let redundancy_header_len = match config.is_redundant() {
+            true => REDUNDANCY_HEADER_LEN,
+            false => 0,
+        };
+        // Only the server stamps its payloads
+        let ticked = config.is_ticked() && self.connection.side() == Side::Client;
+        let tick_header_len = match ticked {
+            true => TICK_HEADER_LEN,
+            false => 0,
+        };
+        let max_size = max_message_size
+            + trace_header_len
+            + ack_header_len
+            + redundancy_header_len
+            + tick_header_len;
+        let payload = match config.is_compressed() {
+            true => self.decompress(channel_id, &config, &payload, max_size),
+            false => Some(payload),
+        };
+        let payload = match payload {
+            Some(payload) if config.is_traced() => {
+                read_trace(channel_id, payload).map(|(payload, trace)| (payload, Some(trace)))
+            }
+            payload => payload.map(|payload| (payload, None)),
+        };
+        if !config.is_redundant() {
+            self.redundancy_windows.remove(&channel_id);
+        }
+        let payload = match payload {
+            Some((payload, trace))
+                if config.is_redundant() && payload.len() >= REDUNDANCY_HEADER_LEN =>
+            {
+                // Copies of a payload already received are expected, they are not violations
+                let payload = self
+                    .redundancy_windows
+                    .entry(channel_id)
+                    .or_default()
+                    .check(payload)?;
+                Some((payload, trace))
+            }
+            Some(_) if config.is_redundant() => None,
+            payload => payload,
+        };
+        let payload = match payload {
+            Some((payload, trace)) if ticked => read_tick_header(payload).map(|(payload, tick)| {
+                if let Some(tick) = tick {
+                    self.server_tick.update(tick);
+                }
+                (payload, trace)
+            }),
+            payload => payload,
+        };
+        let payload = match payload {
+            Some((payload, trace)) if config.is_acknowledged() => {
+                read_ack_header(payload).map(|(payload, ack_id)| (payload, trace, ack_id))
+            }
+            payload => payload.map(|(payload, trace)| (payload, trace, None)),
+        };
+        match payload {
+            Some((payload, trace, ack_id)) if payload.len() <= max_message_size => {
+                Some((payload, trace, ack_id))
+            }
+            _ => {
+                self.hardening
+                    .report(ProtocolViolation::InvalidPayload(channel_id));
+                None
+            }
+        }
+    }
+
+    /// Returns the payloads carried by a datagram of `channel_id`, to [`PayloadDecoder::decode`]: the datagram itself, or with forward error correction its payload and the payload recovered thanks to it
+    pub(crate) fn unwrap_datagram(
+        &mut self,
+        channel_id: ChannelId,
+        datagram: Bytes,
+    ) -> [Option<Bytes>; 2] {
+        #[cfg(feature = "fec")]
+        {
+            let group_size = match self.channels_configs.read() {
+                Ok(configs) => configs
+                    .get(&channel_id)
+                    .and_then(ChannelConfig::fec_group_size),
+                Err(_) => None,
+            };
+            let Some(group_size) = group_size else {
+                self.fec_decoders.remove(&channel_id);
+                return [Some(datagram), None];
+            };
+            let received = self
+                .fec_decoders
+                .entry(channel_id)
+                .or_insert_with(|| FecDecoder::new(group_size))
+                .receive(datagram);
+            match received {
+                Some(payloads) => payloads,
+                None => {
+                    self.hardening
+                        .report(ProtocolViolation::InvalidPayload(channel_id));
+                    [None, None]
+                }
+            }
+        }
+        #[cfg(not(feature = "fec"))]
+        {
+            let _ = channel_id;
+            [Some(datagram), None]
+        }
+    }
+
+    fn decompress(
+        &mut self,
+        channel_id: ChannelId,
+        config: &ChannelConfig,
+        payload: &[u8],
+        max_size: usize,
+    ) -> Option<Bytes> {
+        let Some(dictionary) = config.compression_dictionary() else {
+            self.decompressors.remove(&channel_id);
+            return decompress(payload, max_size);
+        };
+        // The channel may have been reopened with another dictionary
+        let decompressor = match self.decompressors.get_mut(&channel_id) {
+            Some(decompressor) if decompressor.dictionary_id() == dictionary.id() => decompressor,
+            _ => self
+                .decompressors
+                .entry(channel_id)
+                .insert_entry(DictionaryDecompressor::new(dictionary))
+                .into_mut(),
+        };
+        decompressor.decompress(payload, max_size)
+    }
+
+    fn check_replay(
+        &mut self,
+        channel_id: ChannelId,
+        config: &ChannelConfig,
+        payload: Bytes,
+    ) -> Option<Bytes> {
+        if !config.is_replay_protected() {
+            self.replay_windows.remove(&channel_id);
+            return Some(payload);
+        }
+        let checked = self
+            .replay_windows
+            .entry(channel_id)
+            .or_default()
+            .check(payload);
+        if checked.is_none() {
+            self.hardening
+                .report(ProtocolViolation::ReplayedPayload(channel_id));
+        }
+        checked
+    }
+
+    fn decrypt(
+        &mut self,
+        channel_id: ChannelId,
+        config: &ChannelConfig,
+        payload: Bytes,
+    ) -> Option<Bytes> {
+        let Some(encryption) = config.encryption() else {
+            self.deciphers.remove(&channel_id);
+            return Some(payload);
+        };
+        if !self.deciphers.contains_key(&channel_id) {
+            match ChannelDecipher::derive(
+                &self.connection,
+                encryption,
+                channel_id,
+                !self.connection.side(),
+            ) {
+                Ok(decipher) => {
+                    self.deciphers.insert(channel_id, decipher);
+                }
+                Err(err) => {
+                    warn!("Channel {}: {}", channel_id, err);
+                    return None;
+                }
+            }
+        }
+        let opened = self
+            .deciphers
+            .get_mut(&channel_id)
+            .and_then(|decipher| decipher.open(payload));
+        if opened.is_none() {
+            self.hardening
+                .report(ProtocolViolation::UndecryptablePayload(channel_id));
+        }
+        opened
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{Mutex, MutexGuard},
+};
+
+use bytes::Bytes;
+use tokio::sync::Notify;
+
+use crate::shared::{
+    error::AsyncChannelError,
+    memory::{ConnectionMemory, SharedConnectionMemory},
+};
+
+use super::MessagePriority;
+
+#[derive(Debug)]
+struct QueuedMessage {
+    priority: MessagePriority,
+    sequence: u64,
+    payload: Bytes,
+}
+
+impl PartialEq for QueuedMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedMessage {}
+
+impl PartialOrd for QueuedMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedMessage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Highest priority first, then oldest first
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    messages: BinaryHeap<QueuedMessage>,
+    /// Size of the queued payloads
+    bytes: usize,
+    next_sequence: u64,
+    closed: bool,
+    /// The channel task waits for a message, only then does a push need to wake it up
+    parked: bool,
+    /// Liveness probe waiting to be sent, ahead of the messages
+    probe: Option<Bytes>,
+}
+
+/// Frame popped from an [`OutgoingQueue`] by a reliable channel task
+#[derive(Debug)]
+pub(crate) enum OutgoingFrame {
+    Payload(Bytes),
+    /// Control message of a liveness probe, framed on the control channel but written on the stream of the channel
+    Probe(Bytes),
+}
+
+/// Outgoing messages of a channel, shared between the sync side which pushes them and the channel task which sends them.
+///
+/// Messages are popped by decreasing priority and then in sending order.
+///
+/// The channel task is only woken up when it waits for messages: the messages pushed while it is sending are picked up without crossing threads again, a burst of messages costs a single wakeup.
+#[derive(Debug)]
+pub(crate) struct OutgoingQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+    /// Notified when messages leave the queue, see [`OutgoingQueue::reserve`]
+    space: Notify,
+    capacity: usize,
+    /// Counts the queued payloads in the memory of the connection
+    memory: SharedConnectionMemory,
+}
+
+impl OutgoingQueue {
+    pub(crate) fn new(capacity: usize, memory: SharedConnectionMemory) -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+            notify: Notify::new(),
+            space: Notify::new(),
+            capacity,
+            memory,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .expect("Outgoing queue lock should not be poisoned")
+    }
+
+    /// If `deferred`, the channel task is only woken up by the next [`OutgoingQueue::flush`], it may still send the message earlier if it was already awake.
+    pub(crate) fn push(
+        &self,
+        payload: Bytes,
+        priority: MessagePriority,
+        deferred: bool,
+    ) -> Result<(), AsyncChannelError> {
+        let mut state = self.state();
+        if state.closed {
+            return Err(AsyncChannelError::InternalChannelClosed);
+        }
+        if state.messages.len() >= self.capacity {
+            return Err(AsyncChannelError::FullQueue);
+        }
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.bytes += payload.len();
+        self.memory.add(payload.len());
+        state.messages.push(QueuedMessage {
+            priority,
+            sequence,
+            payload,
+        });
+        if !deferred {
+            self.wake(state);
+        }
+        Ok(())
+    }
+
+    /// Sends a liveness probe ahead of the queued messages, replacing the probe not sent yet if any. Not counted in the length of the queue nor in the memory of the connection.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn probe(&self, probe: Bytes) {
+        let mut state = self.state();
+        if state.closed {
+            return;
+        }
+        state.probe = Some(probe);
+        self.wake(state);
+    }
+
+    /// Wakes up the channel task if messages are waiting to be sent
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn flush(&self) {
+        let state = self.state();
+        if !state.messages.is_empty() {
+            self.wake(state);
+        }
+    }
+
+    /// Wakes up the channel task if it is parked
+    fn wake(&self, mut state: MutexGuard<'_, QueueState>) {
+        if state.parked {
+            state.parked = false;
+            drop(state);
+            self.notify.notify_one();
+        }
+    }
+
+    pub(crate) fn pop(&self) -> Option<Bytes> {
+        let payload = self.take(&mut self.state());
+        self.space.notify_waiters();
+        payload
+    }
+
+    fn take(&self, state: &mut QueueState) -> Option<Bytes> {
+        let payload = state.messages.pop()?.payload;
+        state.bytes -= payload.len();
+        self.memory.remove(payload.len());
+        Some(payload)
+    }
+
+    /// Waits for the next message to send, ignoring the liveness probes. Returns `None` once the queue is closed and empty.
+    pub(crate) async fn next(&self) -> Option<Bytes> {
+        loop {
+            if let OutgoingFrame::Payload(payload) = self.next_frame().await? {
+                return Some(payload);
+            }
+        }
+    }
+
+    /// Waits for the next frame to send, the liveness probe first. Returns `None` once the queue is closed and empty.
+    pub(crate) async fn next_frame(&self) -> Option<OutgoingFrame> {
+        loop {
+            {
+                let mut state = self.state();
+                if let Some(probe) = state.probe.take() {
+                    return Some(OutgoingFrame::Probe(probe));
+                }
+                if let Some(payload) = self.take(&mut state) {
+                    drop(state);
+                    self.space.notify_waiters();
+                    return Some(OutgoingFrame::Payload(payload));
+                }
+                if state.closed {
+                    return None;
+                }
+                // A push between the release of the lock and the wait stores a permit for it
+                state.parked = true;
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Memory of the connection of the queue
+    pub(crate) fn memory(&self) -> &ConnectionMemory {
+        &self.memory
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.state().messages.len()
+    }
+
+    /// Size of the queued payloads, in bytes
+    #[cfg(feature = "server")]
+    pub(crate) fn bytes(&self) -> usize {
+        self.state().bytes
+    }
+
+    /// Discards all the pending messages and returns how many were discarded
+    pub(crate) fn clear(&self) -> usize {
+        let mut state = self.state();
+        let count = state.messages.len();
+        state.messages.clear();
+        self.memory.remove(std::mem::take(&mut state.bytes));
+        drop(state);
+        self.space.notify_waiters();
+        count
+    }
+
+    /// Waits until a message can be pushed without exceeding the capacity of the queue, or until the queue is closed
+    #[cfg(feature = "no-bevy")]
+    pub(crate) async fn reserve(&self) {
+        loop {
+            let space = self.space.notified();
+            {
+                let state = self.state();
+                if state.closed || state.messages.len() < self.capacity {
+                    return;
+                }
+            }
+            space.await;
+        }
+    }
+
+    /// No more messages can be pushed, the channel task ends once the remaining messages are sent
+    pub(crate) fn close(&self) {
+        self.state().closed = true;
+        self.notify.notify_one();
+        self.space.notify_waiters();
+    }
+}
+
+impl Drop for OutgoingQueue {
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.get_mut() {
+            self.memory.remove(state.bytes);
+        }
+    }
+}
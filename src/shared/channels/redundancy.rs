@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// Size overhead added to each payload sent on a redundant channel, in bytes
+pub const REDUNDANCY_HEADER_LEN: usize = 8;
+/// Maximum number of payloads of a redundant channel whose copies are waiting for a flush. The copies of the oldest payloads are dropped beyond.
+pub const MAX_REDUNDANT_PAYLOADS: usize = 1_024;
+
+/// Copies of the payloads sent on a redundant channel, sent again on the next flushes
+#[derive(Debug)]
+pub(crate) struct RedundantCopies {
+    #[cfg_attr(not(any(feature = "client", feature = "server")), allow(dead_code))]
+    copies: u8,
+    next_sequence: u64,
+    /// Payloads sent since the last flush, their first copy is sent on the next one
+    fresh: VecDeque<Bytes>,
+    /// Payloads with the number of copies left to send
+    pending: VecDeque<(Bytes, u8)>,
+}
+
+impl RedundantCopies {
+    pub(crate) fn new(copies: u8) -> Self {
+        Self {
+            copies,
+            next_sequence: 0,
+            fresh: VecDeque::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// SEQUENCE | PAYLOAD. The copies carry the sequence of the original payload.
+    pub(crate) fn stamp(&mut self, payload: Bytes) -> Bytes {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let mut stamped = BytesMut::with_capacity(REDUNDANCY_HEADER_LEN + payload.len());
+        stamped.put_u64(sequence);
+        stamped.extend_from_slice(&payload);
+        let stamped: Bytes = stamped.into();
+        if self.fresh.len() + self.pending.len() == MAX_REDUNDANT_PAYLOADS {
+            match self.pending.is_empty() {
+                true => self.fresh.pop_front(),
+                false => self.pending.pop_front().map(|(payload, _)| payload),
+            };
+        }
+        self.fresh.push_back(stamped.clone());
+        stamped
+    }
+
+    /// Returns the copies to send on this flush, one for each payload sent before the previous flush and not yet copied enough
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn take_due(&mut self) -> Vec<Bytes> {
+        let due = self
+            .pending
+            .iter()
+            .map(|(payload, _)| payload.clone())
+            .collect();
+        for (_, left) in self.pending.iter_mut() {
+            *left -= 1;
+        }
+        self.pending.retain(|(_, left)| *left > 0);
+        let copies_left = self.copies - 1;
+        self.pending
+            .extend(self.fresh.drain(..).map(|payload| (payload, copies_left)));
+        due
+    }
+}
@@ -6,7 +6,10 @@ use std::{
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
-use crate::shared::channels::PROTOCOL_HEADER_LEN;
+use crate::shared::{
+    channels::{ChannelKind, SharedChannelConfigs, CONTROL_CHANNEL_ID, PROTOCOL_HEADER_LEN},
+    hardening::ProtocolViolation,
+};
 
 use super::{RELIABLE_FRAME_LENGTH_FIELD_LEN, RELIABLE_FRAME_TOTAL_HEADER_LEN};
 
@@ -50,10 +53,13 @@ impl QuinnetProtocolCodecEncoder {
     }
 }
 
-impl Encoder<Bytes> for QuinnetProtocolCodecEncoder {
-    type Error = io::Error;
-
-    fn encode(&mut self, frame: Bytes, dst: &mut BytesMut) -> Result<(), io::Error> {
+impl QuinnetProtocolCodecEncoder {
+    fn encode_frame(
+        &self,
+        raw_channel_id: u8,
+        frame: &[u8],
+        dst: &mut BytesMut,
+    ) -> Result<(), io::Error> {
         if frame.len() > self.max_frame_len {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
@@ -68,21 +74,44 @@ impl Encoder<Bytes> for QuinnetProtocolCodecEncoder {
             PROTOCOL_HEADER_LEN as u64 + frame.len() as u64,
             RELIABLE_FRAME_LENGTH_FIELD_LEN,
         );
-        dst.put_u8(self.raw_channel_id);
+        dst.put_u8(raw_channel_id);
 
         // Write the frame to the buffer
-        dst.extend_from_slice(&frame[..]);
+        dst.extend_from_slice(frame);
 
         Ok(())
     }
 }
 
+impl Encoder<Bytes> for QuinnetProtocolCodecEncoder {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: Bytes, dst: &mut BytesMut) -> Result<(), io::Error> {
+        self.encode_frame(self.raw_channel_id, &frame, dst)
+    }
+}
+
+/// Control message written on the stream of another channel, such as a liveness probe
+pub(crate) struct ControlFrame(pub(crate) Bytes);
+
+impl Encoder<ControlFrame> for QuinnetProtocolCodecEncoder {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: ControlFrame, dst: &mut BytesMut) -> Result<(), io::Error> {
+        self.encode_frame(CONTROL_CHANNEL_ID, &frame.0, dst)
+    }
+}
+
 #[derive(Debug)]
 pub struct QuinnetProtocolCodecDecoder {
     // Read state
     state: DecodeState,
     // Maximum frame length
     max_frame_len: usize,
+    // Also bounds the frames by the max frame size of their channel, in strict mode
+    channels_configs: Option<SharedChannelConfigs>,
+    // Violation which made the last decode fail
+    violation: Option<ProtocolViolation>,
 }
 
 impl QuinnetProtocolCodecDecoder {
@@ -90,9 +119,45 @@ impl QuinnetProtocolCodecDecoder {
         Self {
             max_frame_len,
             state: DecodeState::Head,
+            channels_configs: None,
+            violation: None,
         }
     }
 
+    /// Frames of a known reliable channel are also bounded by the `max_frame_size` of the channel
+    pub(crate) fn with_channel_bounds(mut self, channels_configs: SharedChannelConfigs) -> Self {
+        self.channels_configs = Some(channels_configs);
+        self
+    }
+
+    /// Returns the violation which made the last decode fail, if any
+    pub(crate) fn take_violation(&mut self) -> Option<ProtocolViolation> {
+        self.violation.take()
+    }
+
+    /// Maximum length of a frame of `channel_id`, channel id included
+    fn channel_max_frame_len(&self, channel_id: u8) -> usize {
+        let channel_kind = self
+            .channels_configs
+            .as_ref()
+            .and_then(|configs| configs.read().ok()?.get(&channel_id).map(|c| c.kind()));
+        match channel_kind {
+            Some(ChannelKind::OrderedReliable { max_frame_size })
+            | Some(ChannelKind::UnorderedReliable { max_frame_size }) => self
+                .max_frame_len
+                .min(PROTOCOL_HEADER_LEN.saturating_add(max_frame_size)),
+            _ => self.max_frame_len,
+        }
+    }
+
+    fn violation_error(&mut self, violation: ProtocolViolation) -> io::Error {
+        self.violation = Some(violation);
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            QuinnetProtocolCodecError { _priv: () },
+        )
+    }
+
     fn decode_head(&mut self, src: &mut BytesMut) -> io::Result<Option<usize>> {
         if src.len() < RELIABLE_FRAME_TOTAL_HEADER_LEN {
             // Not enough data
@@ -103,12 +168,18 @@ impl QuinnetProtocolCodecDecoder {
             let mut src = Cursor::new(&mut *src);
 
             let payload_length = src.get_uint(RELIABLE_FRAME_LENGTH_FIELD_LEN);
-
-            if payload_length > self.max_frame_len as u64 {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    QuinnetProtocolCodecError { _priv: () },
-                ));
+            let channel_id = src.get_u8();
+
+            let max_len = self.channel_max_frame_len(channel_id);
+            if payload_length > max_len as u64 {
+                return Err(self.violation_error(ProtocolViolation::FrameTooLarge {
+                    len: payload_length as usize,
+                    max_len,
+                }));
+            }
+            // The channel id is part of the payload
+            if payload_length < PROTOCOL_HEADER_LEN as u64 {
+                return Err(self.violation_error(ProtocolViolation::EmptyFrame));
             }
 
             // The check above ensures there is no overflow
@@ -1,21 +1,28 @@
-use bevy::log::trace;
 use bytes::{Buf, Bytes, BytesMut};
 use futures::StreamExt;
-use quinn::RecvStream;
-use std::{fmt::Display, io::Cursor};
-use tokio::sync::mpsc::{self};
+use std::{fmt::Display, io::Cursor, time::Instant};
+use tokio::sync::mpsc;
 use tokio_util::codec::FramedRead;
+use tracing::trace;
 
 use crate::shared::channels::{
-    reliable::{codec::QuinnetProtocolCodecDecoder, DEFAULT_MAX_RELIABLE_FRAME_LEN},
-    ChannelId, CloseRecv, CHANNEL_ID_LEN,
+    incoming::{IncomingSend, ReceivedPayload},
+    payload::PayloadDecoder,
+    reliable::codec::QuinnetProtocolCodecDecoder,
+    ChannelId, CloseRecv, SharedChannelConfigs, CHANNEL_ID_LEN,
 };
+use crate::shared::hardening::ReceiveHardening;
+use crate::shared::tick::SharedNetworkTick;
+use crate::shared::transport::TransportConnection;
 
-pub(crate) async fn reliable_channels_receiver_task<T: Display>(
+pub(crate) async fn reliable_channels_receiver_task<T: Display, C: TransportConnection>(
     task_id: T,
-    connection: quinn::Connection,
+    connection: C,
     mut close_recv: CloseRecv,
-    bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    bytes_incoming_send: IncomingSend,
+    channels_configs: SharedChannelConfigs,
+    hardening: ReceiveHardening,
+    server_tick: SharedNetworkTick,
 ) {
     let close_recv_clone = close_recv.resubscribe();
     tokio::select! {
@@ -24,13 +31,15 @@ pub(crate) async fn reliable_channels_receiver_task<T: Display>(
         }
         _ = async {
             while let Ok(recv) = connection.accept_uni().await {
-                let bytes_incoming_send_clone = bytes_incoming_send.clone();
+                let bytes_incoming_send_lane = bytes_incoming_send.lane();
                 let close_recv_clone = close_recv_clone.resubscribe();
+                let decoder = PayloadDecoder::new(connection.clone(), channels_configs.clone(), hardening.clone(), server_tick.clone());
                 tokio::spawn(async move {
                     reliable_stream_receiver_task(
                         recv,
                         close_recv_clone,
-                        bytes_incoming_send_clone
+                        bytes_incoming_send_lane,
+                        decoder,
                     ).await;
                 });
             }
@@ -40,21 +49,44 @@ pub(crate) async fn reliable_channels_receiver_task<T: Display>(
     };
 }
 
-async fn reliable_stream_receiver_task(
-    recv: RecvStream,
+async fn reliable_stream_receiver_task<C: TransportConnection>(
+    recv: C::RecvStream,
     mut close_recv: CloseRecv,
-    bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    bytes_incoming_send: mpsc::Sender<ReceivedPayload>,
+    mut decoder: PayloadDecoder<C>,
 ) {
+    let mut frame_decoder = QuinnetProtocolCodecDecoder::new(decoder.hardening().max_frame_len());
+    if decoder.hardening().is_strict() {
+        frame_decoder = frame_decoder.with_channel_bounds(decoder.channels_configs());
+    }
     tokio::select! {
         _ = close_recv.recv() => {}
         _ = async {
-            let mut frame_recv = FramedRead::new(recv, QuinnetProtocolCodecDecoder::new(DEFAULT_MAX_RELIABLE_FRAME_LEN));
-            while let Some(Ok(msg_bytes)) = frame_recv.next().await {
-                // TODO Clean: error handling
-                bytes_incoming_send
-                    .send(decode_incoming_reliable_message(msg_bytes))
+            let mut frame_recv = FramedRead::new(recv, frame_decoder);
+            while let Some(frame) = frame_recv.next().await {
+                let msg_bytes = match frame {
+                    Ok(msg_bytes) => msg_bytes,
+                    Err(_) => {
+                        // The rest of the stream can not be framed anymore
+                        if let Some(violation) = frame_recv.decoder_mut().take_violation() {
+                            decoder.hardening().report(violation);
+                        }
+                        break;
+                    }
+                };
+                let received_at = Instant::now();
+                let (channel_id, payload) = decode_incoming_reliable_message(msg_bytes);
+                let Some((payload, trace, ack_id)) = decoder.decode(channel_id, payload) else {
+                    continue;
+                };
+                // The receiving end is dropped with its connection
+                if bytes_incoming_send
+                    .send((channel_id, payload, trace, ack_id, received_at))
                     .await
-                    .unwrap();
+                    .is_err()
+                {
+                    break;
+                }
             }
         } => {}
     };
@@ -1,79 +1,239 @@
-use bevy::log::{error, trace, warn};
+use std::io;
+
+use bytes::Bytes;
 use futures::sink::SinkExt;
-use quinn::SendStream;
+use tokio::sync::mpsc;
 use tokio_util::codec::FramedWrite;
+use tracing::{error, info_span, trace, warn};
+
+use crate::shared::{
+    channels::{
+        payload::PayloadEncoder, queue::OutgoingFrame, ChannelAsyncMessage, ChannelId, CloseReason,
+        SendChannelTask,
+    },
+    close::CloseCode,
+    error::ChannelError,
+    profiling::{self, profiled},
+    transport::{TransportConnection, TransportError},
+};
 
-use crate::shared::channels::{ChannelAsyncMessage, ChannelId, CloseReason, SendChannelTask};
+use super::codec::{ControlFrame, QuinnetProtocolCodecEncoder, QuinnetProtocolCodecError};
 
-use super::codec::QuinnetProtocolCodecEncoder;
+type FrameSender<C> =
+    FramedWrite<<C as TransportConnection>::SendStream, QuinnetProtocolCodecEncoder>;
 
-async fn new_uni_frame_sender(
-    connection: &quinn::Connection,
+async fn new_uni_frame_sender<C: TransportConnection>(
+    connection: &C,
     raw_channel_id: ChannelId,
     max_frame_len: usize,
-) -> FramedWrite<SendStream, QuinnetProtocolCodecEncoder> {
-    let uni_sender = connection
-        .open_uni()
-        .await
-        .expect("Failed to open send stream");
-    FramedWrite::new(
+) -> Result<FrameSender<C>, TransportError> {
+    let uni_sender = connection.open_uni().await?;
+    Ok(FramedWrite::new(
         uni_sender,
         QuinnetProtocolCodecEncoder::new(raw_channel_id, max_frame_len),
+    ))
+}
+
+/// Returns true if `err` was raised because the peer reset the stream, or stopped reading it
+fn is_stream_reset(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionReset | io::ErrorKind::BrokenPipe
     )
 }
 
-pub(crate) async fn ordered_reliable_channel_task(
-    mut channel_task: SendChannelTask,
+/// Failure of a send on a reliable channel
+enum SendFailure {
+    /// The connection is lost
+    ConnectionLost(io::Error),
+    /// Only the message was dropped, the channel can still be used
+    Channel(ChannelError),
+}
+
+impl SendFailure {
+    fn new(err: io::Error, len: usize, max_len: usize) -> Self {
+        if err.kind() == io::ErrorKind::NotConnected {
+            return SendFailure::ConnectionLost(err);
+        }
+        match err
+            .get_ref()
+            .is_some_and(|inner| inner.is::<QuinnetProtocolCodecError>())
+        {
+            true => SendFailure::Channel(ChannelError::FrameTooLarge { len, max_len }),
+            false => SendFailure::Channel(ChannelError::StreamFailed(err.to_string())),
+        }
+    }
+
+    /// Signals the failure to the sync side, `channel_kind` names the channel in the logs
+    async fn report(
+        self,
+        from_channels_send: &mpsc::Sender<ChannelAsyncMessage>,
+        channel_id: ChannelId,
+        channel_kind: &str,
+    ) {
+        match self {
+            SendFailure::ConnectionLost(err) => {
+                error!("Error while sending on {} Channel, {}", channel_kind, err);
+                // The sync side may already be gone
+                let _ = from_channels_send
+                    .send(ChannelAsyncMessage::LostConnection)
+                    .await;
+            }
+            SendFailure::Channel(err) => {
+                warn!(
+                    "Failed to send a message on {} Channel {}, {}",
+                    channel_kind, channel_id, err
+                );
+                // Not reported while the sync side lags behind
+                let _ =
+                    from_channels_send.try_send(ChannelAsyncMessage::ChannelError(channel_id, err));
+            }
+        }
+    }
+}
+
+/// Encodes `msg_bytes` with the options of the channel
+fn encode(encoder: &mut PayloadEncoder, msg_bytes: Bytes) -> Bytes {
+    let _span = profiling::enter(|| info_span!("quinnet_encode"));
+    encoder.encode(msg_bytes)
+}
+
+/// Sends `msg_bytes` on `frame_sender`, framing it.
+///
+/// If the peer reset the stream while the connection is still alive, the stream is replaced by a new one on which the message is sent again, and the resume of the channel is signaled: the messages sent earlier on the reset stream may have been lost.
+async fn send_or_resume<C: TransportConnection>(
+    connection: &C,
+    frame_sender: &mut FrameSender<C>,
+    channel_id: ChannelId,
     max_frame_len: usize,
+    from_channels_send: &mpsc::Sender<ChannelAsyncMessage>,
+    msg_bytes: Bytes,
+) -> Result<(), SendFailure> {
+    let len = msg_bytes.len();
+    let err = match frame_sender.send(msg_bytes.clone()).await {
+        Ok(()) => return Ok(()),
+        Err(err) if is_stream_reset(&err) => err,
+        Err(err) => return Err(SendFailure::new(err, len, max_frame_len)),
+    };
+    // Fails if the connection itself is lost
+    let Ok(new_frame_sender) = new_uni_frame_sender(connection, channel_id, max_frame_len).await
+    else {
+        return Err(SendFailure::ConnectionLost(err));
+    };
+    warn!(
+        "Stream of Reliable Channel {} was reset by the peer, resuming on a new stream: {}",
+        channel_id, err
+    );
+    *frame_sender = new_frame_sender;
+    let _ = from_channels_send
+        .send(ChannelAsyncMessage::ChannelResumed(channel_id))
+        .await;
+    frame_sender
+        .send(msg_bytes)
+        .await
+        .map_err(|err| SendFailure::new(err, len, max_frame_len))
+}
+
+/// Writes a liveness probe on the stream of a channel. A failed probe is left unanswered, the channel is then reported as unresponsive.
+async fn send_probe<C: TransportConnection>(
+    frame_sender: &mut FrameSender<C>,
+    channel_id: ChannelId,
+    probe: Bytes,
 ) {
-    let mut frame_sender =
-        new_uni_frame_sender(&channel_task.connection, channel_task.id, max_frame_len).await;
+    if let Err(err) = frame_sender.send(ControlFrame(probe)).await {
+        trace!(
+            "Failed to send a liveness probe on Reliable Channel {}, {}",
+            channel_id,
+            err
+        );
+    }
+}
+
+pub(crate) async fn ordered_reliable_channel_task<C: TransportConnection>(
+    mut channel_task: SendChannelTask<C>,
+    max_frame_len: usize,
+) {
+    let mut frame_sender = match new_uni_frame_sender(
+        &channel_task.connection,
+        channel_task.id,
+        max_frame_len,
+    )
+    .await
+    {
+        Ok(frame_sender) => frame_sender,
+        // The connection was closed before the channel opened
+        Err(err) => {
+            warn!("Failed to open Ordered Reliable Channel stream, {}", err);
+            return;
+        }
+    };
 
     let close_reason = tokio::select! {
+        // The close of the connection also closes the queue, it must be seen first to report the drain
+        biased;
         close_reason = channel_task.close_recv.recv() => {
             trace!("Ordered Reliable Channel task received a close signal");
+            channel_task.remainders.register(channel_task.id);
             match close_reason {
                 Ok(reason) => reason,
-                Err(_) => CloseReason::LocalOrder,
+                Err(_) => CloseReason::LocalOrder(CloseCode::Closed),
             }
         }
         _ = channel_task.channel_close_recv.recv() => {
             trace!("Ordered Reliable Channel task received a channel close signal");
-            CloseReason::LocalOrder
+            CloseReason::LocalOrder(CloseCode::Closed)
         }
         _ = async {
             // Send channel messages
-            while let Some(msg_bytes) = channel_task.bytes_recv.recv().await {
-                if let Err(err) = frame_sender.send(msg_bytes).await {
-                    error!("Error while sending on Ordered Reliable Channel, {}", err);
-                    channel_task.from_channels_send.send(
-                        ChannelAsyncMessage::LostConnection)
-                        .await
-                        .expect("Failed to signal connection lost on Ordered Reliable Channel");
+            while let Some(frame) = channel_task.queue.next_frame().await {
+                let msg_bytes = match frame {
+                    OutgoingFrame::Payload(msg_bytes) => encode(&mut channel_task.encoder, msg_bytes),
+                    OutgoingFrame::Probe(probe) => {
+                        send_probe::<C>(&mut frame_sender, channel_task.id, probe).await;
+                        continue;
+                    }
+                };
+                if let Err(failure) = profiled(send_or_resume(
+                    &channel_task.connection,
+                    &mut frame_sender,
+                    channel_task.id,
+                    max_frame_len,
+                    &channel_task.from_channels_send,
+                    msg_bytes,
+                ), || info_span!("quinnet_write")).await {
+                    failure.report(&channel_task.from_channels_send, channel_task.id, "Ordered Reliable").await;
                 }
             }
         } => {
             trace!("Ordered Reliable Channel task ended");
-            CloseReason::LocalOrder
+            CloseReason::LocalOrder(CloseCode::Closed)
         }
     };
     // No need to try to flush if we know that the peer is already closed
-    if close_reason != CloseReason::PeerClosed {
-        while let Ok(msg_bytes) = channel_task.bytes_recv.try_recv() {
-            if let Err(err) = frame_sender.send(msg_bytes).await {
+    if close_reason == CloseReason::PeerClosed {
+        channel_task
+            .remainders
+            .add(channel_task.id, channel_task.queue.len());
+    } else {
+        while let Some(msg_bytes) = channel_task.queue.pop() {
+            let msg_bytes = encode(&mut channel_task.encoder, msg_bytes);
+            if let Err(err) =
+                profiled(frame_sender.send(msg_bytes), || info_span!("quinnet_write")).await
+            {
                 warn!(
                     "Failed to send a remaining message on Ordered Reliable Channel, {}",
                     err
                 );
+                channel_task.remainders.add(channel_task.id, 1);
             }
         }
-        if let Err(err) = frame_sender.flush().await {
+        if let Err(err) = SinkExt::<Bytes>::flush(&mut frame_sender).await {
             warn!(
                 "Error while flushing Ordered Reliable Channel stream: {}",
                 err
             );
         }
-        if let Err(err) = frame_sender.into_inner().finish() {
+        if let Err(err) = SinkExt::<Bytes>::close(&mut frame_sender).await {
             warn!(
                 "Failed to shutdown Ordered Reliable Channel stream gracefully: {}",
                 err
@@ -82,69 +242,117 @@ pub(crate) async fn ordered_reliable_channel_task(
     }
 }
 
-pub(crate) async fn unordered_reliable_channel_task(
-    mut channel_task: SendChannelTask,
+pub(crate) async fn unordered_reliable_channel_task<C: TransportConnection>(
+    mut channel_task: SendChannelTask<C>,
     max_frame_len: usize,
 ) {
     let close_reason = tokio::select! {
+        // The close of the connection also closes the queue, it must be seen first to report the drain
+        biased;
         close_reason = channel_task.close_recv.recv() => {
             trace!("Unordered Reliable Channel task received a close signal");
+            channel_task.remainders.register(channel_task.id);
             match close_reason {
                 Ok(reason) => reason,
-                Err(_) => CloseReason::LocalOrder,
+                Err(_) => CloseReason::LocalOrder(CloseCode::Closed),
             }
         }
         _ = channel_task.channel_close_recv.recv() => {
             trace!("Unordered Reliable Channel task received a channel close signal");
-            CloseReason::LocalOrder
+            CloseReason::LocalOrder(CloseCode::Closed)
         }
         _ = async {
-            while let Some(msg_bytes) = channel_task.bytes_recv.recv().await {
+            while let Some(frame) = channel_task.queue.next_frame().await {
+                let frame = match frame {
+                    OutgoingFrame::Payload(msg_bytes) => OutgoingFrame::Payload(encode(&mut channel_task.encoder, msg_bytes)),
+                    probe => probe,
+                };
                 let conn = channel_task.connection.clone();
                 let from_channels_send_clone = channel_task.from_channels_send.clone();
                 let channels_keepalive_clone = channel_task.channels_keepalive.clone();
-                tokio::spawn(async move {
-                    let mut frame_sender = new_uni_frame_sender(&conn,channel_task.id, max_frame_len).await;
-                    if let Err(err) = frame_sender.send(msg_bytes).await {
-                        error!("Error while sending on Unordered Reliable Channel, {}", err);
-                        from_channels_send_clone.send(
-                            ChannelAsyncMessage::LostConnection)
-                            .await
-                            .expect("Failed to signal connection lost on Unordered Reliable Channel");
+                tokio::spawn(profiled(async move {
+                    let mut frame_sender = match new_uni_frame_sender(&conn, channel_task.id, max_frame_len).await {
+                        Ok(frame_sender) => frame_sender,
+                        Err(err) => {
+                            // The connection was closed while the message was queued
+                            warn!("Failed to open Unordered Reliable Channel stream, {}", err);
+                            return;
+                        }
+                    };
+                    match frame {
+                        OutgoingFrame::Payload(msg_bytes) => {
+                            if let Err(failure) = send_or_resume(
+                                &conn,
+                                &mut frame_sender,
+                                channel_task.id,
+                                max_frame_len,
+                                &from_channels_send_clone,
+                                msg_bytes,
+                            ).await {
+                                failure.report(&from_channels_send_clone, channel_task.id, "Unordered Reliable").await;
+                            }
+                        }
+                        // Probes the opening of a new stream, as for a message
+                        OutgoingFrame::Probe(probe) => send_probe::<C>(&mut frame_sender, channel_task.id, probe).await,
                     }
-                    if let Err(err) = frame_sender.into_inner().finish() {
+                    if let Err(err) = SinkExt::<Bytes>::close(&mut frame_sender).await {
                         warn!("Failed to shutdown Unordered Reliable Channel stream gracefully: {}", err);
                     }
                     drop(channels_keepalive_clone)
-                });
+                }, || info_span!("quinnet_write")));
             }
         } => {
             trace!("Unordered Reliable Channel task ended");
-            CloseReason::LocalOrder
+            CloseReason::LocalOrder(CloseCode::Closed)
         }
     };
     // No need to try to flush if we know that the peer is already closed
-    if close_reason != CloseReason::PeerClosed {
-        while let Ok(msg_bytes) = channel_task.bytes_recv.try_recv() {
+    if close_reason == CloseReason::PeerClosed {
+        channel_task
+            .remainders
+            .add(channel_task.id, channel_task.queue.len());
+    } else {
+        while let Some(msg_bytes) = channel_task.queue.pop() {
+            let msg_bytes = encode(&mut channel_task.encoder, msg_bytes);
             let conn = channel_task.connection.clone();
             let channels_keepalive_clone = channel_task.channels_keepalive.clone();
-            tokio::spawn(async move {
-                let mut frame_sender =
-                    new_uni_frame_sender(&conn, channel_task.id, max_frame_len).await;
-                if let Err(err) = frame_sender.send(msg_bytes).await {
-                    warn!(
-                        "Failed to send a remaining message on Unordered Reliable Channel, {}",
-                        err
-                    );
-                }
-                if let Err(err) = frame_sender.into_inner().finish() {
-                    warn!(
-                        "Failed to shutdown Unordered Reliable Channel stream gracefully: {}",
-                        err
-                    );
-                }
-                drop(channels_keepalive_clone)
-            });
+            let remainders = channel_task.remainders.clone();
+            tokio::spawn(profiled(
+                async move {
+                    let mut frame_sender = match new_uni_frame_sender(
+                        &conn,
+                        channel_task.id,
+                        max_frame_len,
+                    )
+                    .await
+                    {
+                        Ok(frame_sender) => frame_sender,
+                        Err(err) => {
+                            warn!(
+                                    "Failed to open a stream for a remaining message on Unordered Reliable Channel, {}",
+                                    err
+                                );
+                            remainders.add(channel_task.id, 1);
+                            return;
+                        }
+                    };
+                    if let Err(err) = frame_sender.send(msg_bytes).await {
+                        warn!(
+                            "Failed to send a remaining message on Unordered Reliable Channel, {}",
+                            err
+                        );
+                        remainders.add(channel_task.id, 1);
+                    }
+                    if let Err(err) = SinkExt::<Bytes>::close(&mut frame_sender).await {
+                        warn!(
+                            "Failed to shutdown Unordered Reliable Channel stream gracefully: {}",
+                            err
+                        );
+                    }
+                    drop(channels_keepalive_clone)
+                },
+                || info_span!("quinnet_write"),
+            ));
         }
     }
 }
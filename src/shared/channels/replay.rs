@@ -0,0 +1,67 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// Size overhead added to each payload sent on a replay protected channel, in bytes
+pub const REPLAY_HEADER_LEN: usize = 8;
+/// Number of nonces tracked below the highest nonce received on a replay protected channel. Older payloads are rejected, as if replayed.
+pub const REPLAY_WINDOW_LEN: u64 = 64;
+
+/// Stamps the payloads sent on a replay protected channel with a monotonically increasing nonce
+#[derive(Debug, Default)]
+pub(crate) struct NonceStamper {
+    next_nonce: u64,
+}
+
+impl NonceStamper {
+    /// NONCE | PAYLOAD
+    pub(crate) fn stamp(&mut self, payload: Bytes) -> Bytes {
+        let nonce = self.next_nonce;
+        self.next_nonce += 1;
+
+        let mut stamped = BytesMut::with_capacity(REPLAY_HEADER_LEN + payload.len());
+        stamped.put_u64(nonce);
+        stamped.extend_from_slice(&payload);
+        stamped.into()
+    }
+}
+
+/// Sliding window of the nonces received on a replay protected channel (RFC 4303, section 3.4.3)
+#[derive(Debug, Default)]
+pub(crate) struct ReplayWindow {
+    /// Highest nonce received, `None` before the first payload
+    highest: Option<u64>,
+    /// Bit `n` is set if the nonce `highest - n` was received
+    received: u64,
+}
+
+impl ReplayWindow {
+    /// Removes the nonce of a payload. `None` if the payload is too short to carry one, if its nonce was already received, or if it is older than the window.
+    pub(crate) fn check(&mut self, mut payload: Bytes) -> Option<Bytes> {
+        if payload.len() < REPLAY_HEADER_LEN {
+            return None;
+        }
+        let header = payload.split_to(REPLAY_HEADER_LEN);
+        let nonce = u64::from_be_bytes(header[..].try_into().ok()?);
+        match self.highest {
+            None => {
+                self.highest = Some(nonce);
+                self.received = 1;
+            }
+            Some(highest) if nonce > highest => {
+                let shift = nonce - highest;
+                self.received = match shift < REPLAY_WINDOW_LEN {
+                    true => (self.received << shift) | 1,
+                    false => 1,
+                };
+                self.highest = Some(nonce);
+            }
+            Some(highest) => {
+                let age = highest - nonce;
+                if age >= REPLAY_WINDOW_LEN || self.received & (1 << age) != 0 {
+                    return None;
+                }
+                self.received |= 1 << age;
+            }
+        }
+        Some(payload)
+    }
+}
@@ -0,0 +1,27 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::shared::tick::NetworkTick;
+
+/// Size overhead added to each payload sent by the server on a ticked channel, in bytes
+pub const TICK_HEADER_LEN: usize = 4;
+
+/// NETWORK TICK + 1 (0 if the server has no tick) | PAYLOAD
+pub(crate) fn write_tick_header(tick: Option<NetworkTick>, payload: Bytes) -> Bytes {
+    let mut stamped = BytesMut::with_capacity(TICK_HEADER_LEN + payload.len());
+    stamped.put_u32(tick.map_or(0, |tick| tick.0.wrapping_add(1)));
+    stamped.extend_from_slice(&payload);
+    stamped.into()
+}
+
+/// Removes the header of a payload received from the server on a ticked channel, returns the tick it carries if any. `None` if the payload is too short to carry a header.
+pub(crate) fn read_tick_header(mut payload: Bytes) -> Option<(Bytes, Option<NetworkTick>)> {
+    if payload.len() < TICK_HEADER_LEN {
+        return None;
+    }
+    let header = payload.split_to(TICK_HEADER_LEN);
+    let tick = u32::from_be_bytes(header[..].try_into().ok()?);
+    Some((
+        payload,
+        (tick != 0).then(|| NetworkTick(tick.wrapping_sub(1))),
+    ))
+}
@@ -0,0 +1,103 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use super::ChannelId;
+
+/// Size overhead added to each payload sent on a traced channel, in bytes
+pub const TRACE_HEADER_LEN: usize = 16;
+/// Maximum number of traces kept by a connection until they are drained, the oldest are dropped first
+pub const MAX_BUFFERED_TRACES: usize = 1_024;
+
+/// Trace of a message received on a traced channel, see [`super::ChannelConfig::traced`].
+///
+/// The sending peer stamps each message with a trace id and its send time, the receiving peer adds the time the message arrived from the network. The gap between the arrival and the moment the application handles the message is the processing delay, see [`MessageTrace::since_received`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageTrace {
+    channel_id: ChannelId,
+    trace_id: u64,
+    sent_at: SystemTime,
+    received_at: SystemTime,
+}
+
+impl MessageTrace {
+    /// Channel the message was received on
+    pub fn channel_id(&self) -> ChannelId {
+        self.channel_id
+    }
+
+    /// Id of the message, increasing by one with each message sent on the channel since the connection was established
+    pub fn trace_id(&self) -> u64 {
+        self.trace_id
+    }
+
+    /// When the sending peer handed the message to the network, according to its clock
+    pub fn sent_at(&self) -> SystemTime {
+        self.sent_at
+    }
+
+    /// When the message arrived from the network, according to the local clock
+    pub fn received_at(&self) -> SystemTime {
+        self.received_at
+    }
+
+    /// One-way network latency of the message, from [`MessageTrace::sent_at`] to [`MessageTrace::received_at`].
+    ///
+    /// Only an estimate: the two timestamps come from the clocks of two machines, this is only accurate if both are synchronized (NTP, PTP). `None` if the clock of the sender is ahead by more than the latency.
+    pub fn one_way_latency(&self) -> Option<Duration> {
+        self.received_at.duration_since(self.sent_at).ok()
+    }
+
+    /// Time elapsed since the message arrived from the network, on the local clock
+    pub fn since_received(&self) -> Duration {
+        self.received_at.elapsed().unwrap_or_default()
+    }
+}
+
+fn unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+/// Stamps the payloads sent on a traced channel
+#[derive(Debug, Default)]
+pub(crate) struct TraceStamper {
+    next_trace_id: u64,
+}
+
+impl TraceStamper {
+    /// TRACE ID | SENT AT (µs since the UNIX epoch) | PAYLOAD
+    pub(crate) fn stamp(&mut self, payload: Bytes) -> Bytes {
+        let trace_id = self.next_trace_id;
+        self.next_trace_id = self.next_trace_id.wrapping_add(1);
+
+        let mut stamped = BytesMut::with_capacity(TRACE_HEADER_LEN + payload.len());
+        stamped.put_u64(trace_id);
+        stamped.put_u64(unix_micros(SystemTime::now()));
+        stamped.extend_from_slice(&payload);
+        stamped.into()
+    }
+}
+
+/// Removes the trace header of a payload received now on `channel_id`. `None` if the payload is too short to carry one.
+pub(crate) fn read_trace(
+    channel_id: ChannelId,
+    mut payload: Bytes,
+) -> Option<(Bytes, MessageTrace)> {
+    if payload.len() < TRACE_HEADER_LEN {
+        return None;
+    }
+    let header = payload.split_to(TRACE_HEADER_LEN);
+    let trace_id = u64::from_be_bytes(header[..8].try_into().ok()?);
+    let sent_at = u64::from_be_bytes(header[8..].try_into().ok()?);
+    Some((
+        payload,
+        MessageTrace {
+            channel_id,
+            trace_id,
+            sent_at: UNIX_EPOCH + Duration::from_micros(sent_at),
+            received_at: SystemTime::now(),
+        },
+    ))
+}
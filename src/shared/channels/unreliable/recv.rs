@@ -1,16 +1,26 @@
-use bevy::log::trace;
-use bytes::Bytes;
-use std::fmt::Display;
-use tokio::sync::mpsc::{self};
+use std::{fmt::Display, time::Instant};
+use tracing::trace;
 
-use crate::shared::channels::{ChannelId, CloseRecv, CHANNEL_ID_LEN};
+use crate::shared::channels::{
+    incoming::IncomingSend, payload::PayloadDecoder, CloseRecv, SharedChannelConfigs,
+    CHANNEL_ID_LEN,
+};
+use crate::shared::hardening::{ProtocolViolation, ReceiveHardening};
+use crate::shared::tick::SharedNetworkTick;
+use crate::shared::transport::TransportConnection;
 
-pub(crate) async fn unreliable_channel_receiver_task<T: Display>(
+pub(crate) async fn unreliable_channel_receiver_task<T: Display, C: TransportConnection>(
     task_id: T,
-    connection: quinn::Connection,
+    connection: C,
     mut close_recv: CloseRecv,
-    bytes_incoming_send: mpsc::Sender<(ChannelId, Bytes)>,
+    bytes_incoming_send: IncomingSend,
+    channels_configs: SharedChannelConfigs,
+    hardening: ReceiveHardening,
+    server_tick: SharedNetworkTick,
 ) {
+    let mut decoder =
+        PayloadDecoder::new(connection.clone(), channels_configs, hardening, server_tick);
+    let bytes_incoming_send = bytes_incoming_send.lane();
     tokio::select! {
         _ = close_recv.recv() => {
             trace!("Listener for unreliable datagrams with id {} received a close signal", task_id)
@@ -18,12 +28,21 @@ pub(crate) async fn unreliable_channel_receiver_task<T: Display>(
         _ = async {
             while let Ok(mut msg_bytes) = connection.read_datagram().await {
                 if msg_bytes.len() <= CHANNEL_ID_LEN {
+                    decoder.hardening().report(ProtocolViolation::MalformedDatagram);
                     continue;
                 }
-                let payload = msg_bytes.split_off(1).into();
+                let received_at = Instant::now();
+                let payload = msg_bytes.split_off(1);
                 let channel_id = msg_bytes[0];
-                // TODO Clean: error handling
-                bytes_incoming_send.send((channel_id, payload)).await.unwrap();
+                for payload in decoder.unwrap_datagram(channel_id, payload).into_iter().flatten() {
+                    let Some((payload, trace, ack_id)) = decoder.decode(channel_id, payload) else {
+                        continue;
+                    };
+                    // The receiving end is dropped with its connection
+                    if bytes_incoming_send.send((channel_id, payload, trace, ack_id, received_at)).await.is_err() {
+                        return;
+                    }
+                }
             }
         } => {
             trace!("Listener for unreliable datagrams with id {} ended", task_id)
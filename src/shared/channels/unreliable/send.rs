@@ -1,66 +1,161 @@
-use bevy::log::{error, trace, warn};
-use bytes::{BufMut, Bytes, BytesMut};
-use quinn::SendDatagramError;
-
-use crate::shared::channels::{
-    ChannelAsyncMessage, ChannelId, CloseReason, SendChannelTask, PROTOCOL_HEADER_LEN,
+use crate::shared::{
+    buffer_pool::BufferPool,
+    channels::{
+        payload::PayloadEncoder, ChannelAsyncMessage, ChannelId, CloseReason, SendChannelTask,
+        PROTOCOL_HEADER_LEN,
+    },
+    close::CloseCode,
+    error::ChannelError,
+    profiling,
+    transport::{TransportConnection, TransportError},
 };
+use bytes::{BufMut, Bytes};
+use tokio::sync::mpsc;
+use tracing::{error, info_span, trace, warn};
 
-pub(crate) async fn unreliable_channel_task(mut task: SendChannelTask) {
+pub(crate) async fn unreliable_channel_task<C: TransportConnection>(mut task: SendChannelTask<C>) {
     let close_reason = tokio::select! {
+        // The close of the connection also closes the queue, it must be seen first to report the drain
+        biased;
         close_reason = task.close_recv.recv() => {
             trace!("Unreliable Channel task received a close signal");
+            task.remainders.register(task.id);
             match close_reason {
                 Ok(reason) => reason,
-                Err(_) => CloseReason::LocalOrder,
+                Err(_) => CloseReason::LocalOrder(CloseCode::Closed),
             }
         }
         _ = task.channel_close_recv.recv() => {
             trace!("Unreliable Channel task received a channel close signal");
-            CloseReason::LocalOrder
+            CloseReason::LocalOrder(CloseCode::Closed)
         }
         _ = async {
-            while let Some(msg_bytes) = task.bytes_recv.recv().await {
-                if let Err(err) = send_unreliable_message(&task.connection, msg_bytes, task.id) {
-                    error!("Error while sending message on Unreliable Channel, {}", err);
-                    match err {
-                        SendDatagramError::UnsupportedByPeer => (),
-                        SendDatagramError::Disabled => (),
-                        SendDatagramError::TooLarge => (),
-                        SendDatagramError::ConnectionLost(_) => {
-                            task.from_channels_send.send(
-                                ChannelAsyncMessage::LostConnection)
-                                .await
-                                .expect("Failed to signal connection lost from channels");
-                        },
-                    }
+            loop {
+                let msg_bytes = match task.encoder.partial_parity_delay() {
+                    Some(delay) => tokio::select! {
+                        msg_bytes = task.queue.next() => msg_bytes,
+                        _ = tokio::time::sleep(delay) => {
+                            if let Err(err) = send_partial_parity(&task.connection, &mut task.encoder, &mut task.buffers, task.id) {
+                                report_send_error(&task.from_channels_send, task.id, err).await;
+                            }
+                            continue;
+                        }
+                    },
+                    None => task.queue.next().await,
+                };
+                let Some(msg_bytes) = msg_bytes else {
+                    break;
+                };
+                if let Err(err) = send_encoded_message(&task.connection, &mut task.encoder, &mut task.buffers, msg_bytes, task.id) {
+                    report_send_error(&task.from_channels_send, task.id, err).await;
                 }
             }
         } => {
             trace!("Unreliable Channel task ended");
-            CloseReason::LocalOrder
+            CloseReason::LocalOrder(CloseCode::Closed)
         }
     };
     // No need to try to flush if we know that the peer is already closed
-    if close_reason != CloseReason::PeerClosed {
-        while let Ok(msg_bytes) = task.bytes_recv.try_recv() {
-            if let Err(err) = send_unreliable_message(&task.connection, msg_bytes, task.id) {
+    if close_reason == CloseReason::PeerClosed {
+        task.remainders.add(task.id, task.queue.len());
+    } else {
+        while let Some(msg_bytes) = task.queue.pop() {
+            if let Err(err) = send_encoded_message(
+                &task.connection,
+                &mut task.encoder,
+                &mut task.buffers,
+                msg_bytes,
+                task.id,
+            ) {
                 warn!(
                     "Failed to send a remaining message on Unreliable Channel, {}",
                     err
                 );
+                task.remainders.add(task.id, 1);
             }
         }
+        if let Err(err) = send_partial_parity(
+            &task.connection,
+            &mut task.encoder,
+            &mut task.buffers,
+            task.id,
+        ) {
+            warn!(
+                "Failed to send the last parity datagram on Unreliable Channel, {}",
+                err
+            );
+        }
     }
 }
 
-fn send_unreliable_message(
-    connection: &quinn::Connection,
+async fn report_send_error(
+    from_channels_send: &mpsc::Sender<ChannelAsyncMessage>,
+    channel_id: ChannelId,
+    err: TransportError,
+) {
+    error!("Error while sending message on Unreliable Channel, {}", err);
+    match err {
+        TransportError::ConnectionLost(_) => {
+            // The sync side may already be gone
+            let _ = from_channels_send
+                .send(ChannelAsyncMessage::LostConnection)
+                .await;
+        }
+        // Not reported while the sync side lags behind
+        err => {
+            let _ = from_channels_send.try_send(ChannelAsyncMessage::ChannelError(
+                channel_id,
+                ChannelError::DatagramNotSent(err.to_string()),
+            ));
+        }
+    }
+}
+
+/// Encodes the message and sends it, followed by the parity datagram of its group if it completes it. A datagram which could not be sent is left out of its group.
+fn send_encoded_message<C: TransportConnection>(
+    connection: &C,
+    encoder: &mut PayloadEncoder,
+    buffers: &mut BufferPool,
     msg_bytes: Bytes,
     channel_id: ChannelId,
-) -> Result<(), SendDatagramError> {
-    let mut datagram = BytesMut::with_capacity(PROTOCOL_HEADER_LEN + msg_bytes.len());
-    datagram.put_u8(channel_id);
-    datagram.extend_from_slice(&msg_bytes[..]);
-    connection.send_datagram(datagram.into())
+) -> Result<(), TransportError> {
+    let msg_bytes = {
+        let _span = profiling::enter(|| info_span!("quinnet_encode"));
+        encoder.encode_datagram(msg_bytes)
+    };
+    send_unreliable_message(connection, buffers, &msg_bytes, channel_id)?;
+    match encoder.datagram_sent(&msg_bytes) {
+        Some(parity) => send_unreliable_message(connection, buffers, &parity, channel_id),
+        None => Ok(()),
+    }
+}
+
+/// Sends the parity datagram of the partial group of the channel, if any
+fn send_partial_parity<C: TransportConnection>(
+    connection: &C,
+    encoder: &mut PayloadEncoder,
+    buffers: &mut BufferPool,
+    channel_id: ChannelId,
+) -> Result<(), TransportError> {
+    match encoder.partial_parity() {
+        Some(parity) => send_unreliable_message(connection, buffers, &parity, channel_id),
+        None => Ok(()),
+    }
+}
+
+fn send_unreliable_message<C: TransportConnection>(
+    connection: &C,
+    buffers: &mut BufferPool,
+    msg_bytes: &[u8],
+    channel_id: ChannelId,
+) -> Result<(), TransportError> {
+    let datagram = {
+        let _span = profiling::enter(|| info_span!("quinnet_frame"));
+        let datagram = buffers.buffer(PROTOCOL_HEADER_LEN + msg_bytes.len());
+        datagram.put_u8(channel_id);
+        datagram.extend_from_slice(msg_bytes);
+        buffers.split()
+    };
+    let _span = profiling::enter(|| info_span!("quinnet_write"));
+    connection.send_datagram(datagram)
 }
@@ -0,0 +1,67 @@
+#[cfg(any(feature = "client", feature = "server"))]
+use bevy::prelude::Event;
+use serde::{Deserialize, Serialize};
+
+use super::ClientId;
+
+/// Default max length of a chat message, in chars
+pub const DEFAULT_CHAT_MAX_LENGTH: usize = 256;
+
+/// Recipients of a chat message
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChatTarget {
+    /// Members of a chat channel, a named group of clients. Not to be confused with the channels of the connections
+    Channel(String),
+    /// A single client, the sender also receives its whisper
+    Whisper(ClientId),
+}
+
+/// Chat message delivered by the server
+#[cfg_attr(any(feature = "client", feature = "server"), derive(Event))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// Sender of the message, `None` for the messages of the server
+    pub from: Option<ClientId>,
+    /// Recipients of the message
+    pub target: ChatTarget,
+    /// Text of the message, as rewritten by the filter of the server if any
+    pub text: String,
+}
+
+/// Reason why the server rejected a chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatRejection {
+    /// The sender sent too many messages recently
+    RateLimited,
+    /// The message exceeds the max length of the server
+    TooLong,
+    /// The sender is not a member of the chat channel
+    NotMember,
+    /// The recipient of the whisper is not connected
+    UnknownRecipient,
+    /// The filter of the server refused the message
+    Filtered,
+}
+
+/// Chat event of a client, sent by the server
+#[cfg_attr(any(feature = "client", feature = "server"), derive(Event))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatEvent {
+    /// A message was received
+    Message(ChatMessage),
+    /// A message sent by the client was rejected
+    Rejected(ChatRejection),
+    /// The client joined a chat channel
+    Joined(String),
+    /// The client left a chat channel
+    Left(String),
+}
+
+/// Chat request of a client
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum ChatRequest {
+    Join(String),
+    Leave(String),
+    Send { target: ChatTarget, text: String },
+}
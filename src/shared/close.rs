@@ -0,0 +1,168 @@
+use std::{fmt, time::Duration};
+
+use bytes::Bytes;
+
+use super::channels::ChannelId;
+#[cfg(feature = "client")]
+use super::transport::{memory::MemoryTransportError, TransportError};
+
+/// First application close code available to [`CloseCode::User`] codes. Codes below are reserved by Quinnet.
+pub const USER_CLOSE_CODE_START: u64 = 0x1000;
+
+const CLOSED: u64 = 0;
+const SERVER_SHUTDOWN: u64 = 1;
+const KICKED: u64 = 2;
+const PROTOCOL_MISMATCH: u64 = 3;
+const IDLE: u64 = 4;
+const PROTOCOL_VIOLATION: u64 = 5;
+const TRANSFERRED: u64 = 6;
+const MEMORY_BUDGET_EXCEEDED: u64 = 7;
+const SLOW_CLIENT: u64 = 8;
+const NOT_ACCEPTING: u64 = 9;
+
+/// Application close code sent to the peer when a connection is closed.
+///
+/// Codes used by Quinnet are encoded below [`USER_CLOSE_CODE_START`], user defined codes are encoded above it, so that both sides decode the same [`CloseCode`] from the same value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CloseCode {
+    /// Regular close, without any particular reason
+    Closed,
+    /// The server endpoint was stopped
+    ServerShutdown,
+    /// The client was disconnected by the server
+    Kicked,
+    /// The peer does not use a compatible protocol
+    ProtocolMismatch,
+    /// The connection was closed after a period of inactivity
+    Idle,
+    /// The peer sent too much malformed traffic, see [`crate::shared::hardening::HardeningConfiguration::with_decode_error_budget`]
+    ProtocolViolation,
+    /// The client was transferred to another server, see [`crate::server::Endpoint::transfer_client`]
+    Transferred,
+    /// The connection exceeded its memory budget, see [`crate::shared::memory::MemoryBudgetPolicy::Disconnect`]
+    MemoryBudgetExceeded,
+    /// The client did not read its messages for too long, see [`crate::server::slow_clients::SlowClientDetection::disconnect_after`]
+    SlowClient,
+    /// The server was not accepting new connections, see [`crate::server::Endpoint::set_accepting`]
+    NotAccepting,
+    /// User defined code, encoded as `USER_CLOSE_CODE_START + code`
+    User(u32),
+    /// Code in the reserved range unknown to this version, or above the user range
+    Unknown(u64),
+}
+
+impl CloseCode {
+    /// Value of the code sent on the wire
+    pub fn code(&self) -> u64 {
+        match self {
+            CloseCode::Closed => CLOSED,
+            CloseCode::ServerShutdown => SERVER_SHUTDOWN,
+            CloseCode::Kicked => KICKED,
+            CloseCode::ProtocolMismatch => PROTOCOL_MISMATCH,
+            CloseCode::Idle => IDLE,
+            CloseCode::ProtocolViolation => PROTOCOL_VIOLATION,
+            CloseCode::Transferred => TRANSFERRED,
+            CloseCode::MemoryBudgetExceeded => MEMORY_BUDGET_EXCEEDED,
+            CloseCode::SlowClient => SLOW_CLIENT,
+            CloseCode::NotAccepting => NOT_ACCEPTING,
+            CloseCode::User(code) => USER_CLOSE_CODE_START + *code as u64,
+            CloseCode::Unknown(code) => *code,
+        }
+    }
+
+    /// Short reason sent along with the code, for the logs of the peer
+    pub(crate) fn reason(&self) -> Bytes {
+        Bytes::from(self.to_string())
+    }
+}
+
+/// Application close code of a connection closed by the peer, if any
+#[cfg(feature = "client")]
+pub(crate) fn peer_close_code(err: &TransportError) -> Option<CloseCode> {
+    let TransportError::ConnectionLost(err) = err else {
+        return None;
+    };
+    if let Some(quinn::ConnectionError::ApplicationClosed(close)) =
+        err.downcast_ref::<quinn::ConnectionError>()
+    {
+        return Some(close.error_code.into_inner().into());
+    }
+    match err.downcast_ref::<MemoryTransportError>() {
+        Some(MemoryTransportError::ApplicationClosed(code)) => Some(*code),
+        _ => None,
+    }
+}
+
+impl From<u64> for CloseCode {
+    fn from(code: u64) -> Self {
+        match code {
+            CLOSED => CloseCode::Closed,
+            SERVER_SHUTDOWN => CloseCode::ServerShutdown,
+            KICKED => CloseCode::Kicked,
+            PROTOCOL_MISMATCH => CloseCode::ProtocolMismatch,
+            IDLE => CloseCode::Idle,
+            PROTOCOL_VIOLATION => CloseCode::ProtocolViolation,
+            TRANSFERRED => CloseCode::Transferred,
+            MEMORY_BUDGET_EXCEEDED => CloseCode::MemoryBudgetExceeded,
+            SLOW_CLIENT => CloseCode::SlowClient,
+            NOT_ACCEPTING => CloseCode::NotAccepting,
+            code => match code
+                .checked_sub(USER_CLOSE_CODE_START)
+                .and_then(|code| u32::try_from(code).ok())
+            {
+                Some(user_code) => CloseCode::User(user_code),
+                None => CloseCode::Unknown(code),
+            },
+        }
+    }
+}
+
+impl From<CloseCode> for u64 {
+    fn from(code: CloseCode) -> Self {
+        code.code()
+    }
+}
+
+impl fmt::Display for CloseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloseCode::Closed => write!(f, "closed"),
+            CloseCode::ServerShutdown => write!(f, "server shutdown"),
+            CloseCode::Kicked => write!(f, "kicked"),
+            CloseCode::ProtocolMismatch => write!(f, "protocol mismatch"),
+            CloseCode::Idle => write!(f, "idle"),
+            CloseCode::ProtocolViolation => write!(f, "protocol violation"),
+            CloseCode::Transferred => write!(f, "transferred"),
+            CloseCode::MemoryBudgetExceeded => write!(f, "memory budget exceeded"),
+            CloseCode::SlowClient => write!(f, "slow client"),
+            CloseCode::NotAccepting => write!(f, "not accepting"),
+            CloseCode::User(code) => write!(f, "user code {}", code),
+            CloseCode::Unknown(code) => write!(f, "unknown code {}", code),
+        }
+    }
+}
+
+/// Stage reached by a connection while it closes, reported with the time elapsed since the start of its drain.
+///
+/// A closing connection first sends the messages remaining in its channels, then closes the transport connection: a slow close is usually explained by a large number of pending messages at [`CloseStage::DrainStarted`], or by the time taken to reach [`CloseStage::BuffersFlushed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloseStage {
+    /// The channels stopped accepting messages and started to send the ones remaining in their queues
+    DrainStarted {
+        /// Number of messages waiting in the queues of the channels
+        pending_messages: usize,
+    },
+    /// The channels sent their remaining messages, or gave up on them
+    BuffersFlushed {
+        /// Number of messages each drained channel failed to send, or discarded because the peer had already closed the connection, by increasing channel id
+        remainders: Vec<(ChannelId, usize)>,
+    },
+    /// The transport connection was closed
+    Closed {
+        /// Application close code sent to the peer
+        code: CloseCode,
+    },
+}
+
+/// Reports the [`CloseStage`]s of a connection to the sync side, with the time elapsed since the start of the drain
+pub(crate) type CloseStageReporter = Box<dyn Fn(CloseStage, Duration) + Send + Sync>;
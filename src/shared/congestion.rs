@@ -0,0 +1,68 @@
+use quinn_proto::PathStats;
+
+/// Change of the state of the congestion controller of a connection, see [`CongestionMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CongestionEventKind {
+    /// A congestion event ended the slow start of the congestion controller, the initial one or one re-entered after a persistent congestion
+    SlowStartExited,
+    /// A persistent congestion collapsed the congestion window to its minimum, the congestion controller is back in slow start
+    SlowStartEntered,
+    /// The congestion controller reduced its congestion window, following lost packets or ECN marks
+    LossEpisode {
+        /// Packets lost since the previous update of the monitor
+        lost_packets: u64,
+        /// Bytes lost since the previous update of the monitor
+        lost_bytes: u64,
+    },
+}
+
+/// Detects the changes of the state of the congestion controller of a connection from the deltas of its [`PathStats`].
+///
+/// Connections start in slow start. Stats are polled: several congestion events between two updates are reported as a single loss episode, and the state of the controller is inferred from its congestion window.
+#[derive(Debug, Clone)]
+pub struct CongestionMonitor {
+    slow_start: bool,
+    last: Option<PathStats>,
+}
+
+impl Default for CongestionMonitor {
+    fn default() -> Self {
+        Self {
+            slow_start: true,
+            last: None,
+        }
+    }
+}
+
+impl CongestionMonitor {
+    /// Returns whether the congestion controller is assumed to be in slow start
+    pub fn in_slow_start(&self) -> bool {
+        self.slow_start
+    }
+
+    /// Compares `path` to the stats of the previous update and returns the changes, in the order they happened
+    pub fn update(&mut self, path: PathStats) -> Vec<CongestionEventKind> {
+        let mut events = Vec::new();
+        let Some(last) = self.last.replace(path) else {
+            return events;
+        };
+        if path.congestion_events == last.congestion_events {
+            return events;
+        }
+        // A persistent congestion collapses the window to its minimum of 2 datagrams
+        let collapsed = path.cwnd <= 2 * u64::from(path.current_mtu);
+        if self.slow_start && !collapsed {
+            self.slow_start = false;
+            events.push(CongestionEventKind::SlowStartExited);
+        }
+        events.push(CongestionEventKind::LossEpisode {
+            lost_packets: path.lost_packets.saturating_sub(last.lost_packets),
+            lost_bytes: path.lost_bytes.saturating_sub(last.lost_bytes),
+        });
+        if !self.slow_start && collapsed {
+            self.slow_start = true;
+            events.push(CongestionEventKind::SlowStartEntered);
+        }
+        events
+    }
+}
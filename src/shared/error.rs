@@ -11,6 +11,9 @@ pub enum AsyncChannelError {
         "The receiving half of the internal channel was explicitly closed or has been dropped"
     )]
     InternalChannelClosed,
+    /// The data was refused because the connection exceeds its memory budget, see [`crate::shared::memory::MemoryBudget`]
+    #[error("The data was refused because the connection exceeds its memory budget")]
+    MemoryBudgetExceeded,
 }
 
 /// Error while closing a channel
@@ -35,6 +38,28 @@ pub enum ChannelCreationError {
     AsyncChannelError(#[from] AsyncChannelError),
 }
 
+/// Failure of a channel, reported by the `ChannelErrorEvent` of the client and the server. Unless stated otherwise, only the message being sent is dropped, the channel and the connection stay open.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChannelError {
+    /// A payload exceeded the max frame size of its reliable channel, see [`crate::shared::channels::ChannelKind`]
+    #[error("Payload of {len} bytes exceeds the max frame size of {max_len} bytes")]
+    FrameTooLarge {
+        /// Size of the payload, once encoded
+        len: usize,
+        /// Max frame size of the channel
+        max_len: usize,
+    },
+    /// The stream of a reliable channel failed, even after resuming on a new stream
+    #[error("Stream failed: {0}")]
+    StreamFailed(String),
+    /// A datagram of an unreliable channel could not be sent
+    #[error("Datagram not sent: {0}")]
+    DatagramNotSent(String),
+    /// The encryption key of an encrypted channel could not be derived, see [`crate::shared::channels::ChannelEncryption`]. The channel sends nothing: its queued messages are dropped and the next sends fail.
+    #[error("Failed to derive the encryption key of the channel")]
+    EncryptionUnavailable,
+}
+
 /// Error while configuring channels
 #[derive(thiserror::Error, Debug)]
 pub enum ChannelConfigError {
@@ -42,3 +67,123 @@ pub enum ChannelConfigError {
     #[error("The maximum number of configured channels has been reached")]
     MaxChannelsCountReached,
 }
+
+/// Error while creating a [`crate::shared::channels::CompressionDictionary`]
+#[derive(thiserror::Error, Debug)]
+pub enum CompressionDictionaryError {
+    /// The dictionary exceeds [`crate::shared::channels::MAX_DICTIONARY_LEN`]
+    #[error("Dictionary of {len} bytes exceeds the max size of {max_len} bytes")]
+    TooLarge {
+        /// Size of the dictionary
+        len: usize,
+        /// Max size of a dictionary
+        max_len: usize,
+    },
+    /// zstd refused the dictionary, such as a dictionary starting with the magic number of the zstd dictionaries but malformed
+    #[error("Invalid zstd dictionary: {0}")]
+    Invalid(#[from] std::io::Error),
+}
+
+/// Error while querying a STUN server
+#[derive(thiserror::Error, Debug)]
+pub enum StunError {
+    /// I/O Error
+    #[error("I/O error")]
+    IoError(#[from] std::io::Error),
+    /// The STUN server did not respond in time
+    #[error("The STUN server did not respond in time")]
+    Timeout,
+    /// The STUN server response is invalid or is an error response
+    #[error("Invalid STUN response")]
+    InvalidResponse,
+    /// Failed to generate a transaction id
+    #[error("Failed to generate a STUN transaction id")]
+    RandomGenerationFailed,
+}
+
+/// Reason of the rejection of a forwarding header, see [`crate::shared::forwarding::ForwardingKey`]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ForwardingError {
+    /// No forwarding key was set on the endpoint
+    #[error("No forwarding key was set on the endpoint")]
+    NoForwardingKey,
+    /// The header or the control message carrying it could not be decoded
+    #[error("Malformed forwarding header")]
+    Malformed,
+    /// The header was not signed with the forwarding key of the endpoint
+    #[error("Invalid forwarding header signature")]
+    InvalidSignature,
+    /// The header is older than the max age of the forwarding key
+    #[error("Expired forwarding header")]
+    Expired,
+    /// The connection already presented a valid header
+    #[error("Connection already forwarded")]
+    AlreadyForwarded,
+}
+
+/// Error while creating or parsing an [`crate::shared::invite::InviteCode`]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum InviteCodeError {
+    /// The host is not a valid DNS name or IP address
+    #[error("Host `{0}` is not a valid DNS name or IP address")]
+    InvalidHost(String),
+    /// The join token is longer than [`crate::shared::invite::MAX_INVITE_TOKEN_LEN`]
+    #[error("Join token of {0} bytes is too long")]
+    TokenTooLong(usize),
+    /// The code is not valid base64
+    #[error("Invite code is not valid base64")]
+    InvalidEncoding,
+    /// The code was created by an unsupported version of the format
+    #[error("Unsupported invite code version {0}")]
+    UnsupportedVersion(u8),
+    /// The code is truncated or its content is invalid
+    #[error("Malformed invite code")]
+    Malformed,
+    /// The code could not be decrypted with the key: a typo, a code encrypted with another key, or a forged code
+    #[error("Invite code could not be decrypted")]
+    Undecryptable,
+}
+
+/// Error of a headless bot connection, see [`crate::bot::BotConnection`]
+#[cfg(feature = "no-bevy")]
+#[derive(thiserror::Error, Debug)]
+pub enum BotError {
+    /// The local endpoint could not be bound
+    #[error("Failed to bind the local endpoint")]
+    Bind(#[from] std::io::Error),
+    /// The TLS configuration has no cipher suite usable by QUIC
+    #[error("Unsupported TLS configuration")]
+    UnsupportedTlsConfig(#[from] quinn::crypto::rustls::NoInitialCipherSuite),
+    /// The connection could not be started
+    #[error("Failed to connect: {0}")]
+    Connect(#[from] quinn::ConnectError),
+    /// The connection failed or was closed
+    #[error("Connection error: {0}")]
+    Connection(#[from] quinn::ConnectionError),
+    /// The server did not send a valid client id
+    #[error("The client id was not received from the server")]
+    ClientIdNotReceived,
+    /// A channel id is invalid
+    #[error("Channel with id `{0}` is invalid")]
+    InvalidChannelId(ChannelId),
+    /// The payload is larger than the max message size of its channel
+    #[error("Payload of {size} bytes exceeds the max message size of {max_message_size} bytes")]
+    PayloadTooLarge {
+        /// Size of the payload
+        size: usize,
+        /// Max message size of the channel
+        max_message_size: usize,
+    },
+    /// Failed to serialize a message
+    #[error("Failed to serialize the message")]
+    Serialization,
+    /// Failed to deserialize a message
+    #[error("Failed to deserialize the message")]
+    Deserialization,
+    /// The connection is closed
+    #[error("The connection is closed")]
+    ConnectionClosed,
+    /// Quinnet async channel error
+    #[error("Quinnet async channel error")]
+    AsyncChannelError(#[from] AsyncChannelError),
+}
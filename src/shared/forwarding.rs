@@ -0,0 +1,128 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "server")]
+use super::error::ForwardingError;
+
+/// Default max age of the forwarding headers, see [`ForwardingKey::with_max_age`]
+pub const DEFAULT_FORWARDING_HEADER_MAX_AGE: Duration = Duration::from_secs(30);
+/// Maximum size of the identity carried by a forwarding header
+pub const MAX_FORWARDED_IDENTITY_LEN: usize = 1_024;
+
+#[cfg(feature = "server")]
+const TAG_LEN: usize = 32;
+
+/// Secret shared by a gateway and the internal servers it forwards its clients to.
+///
+/// The gateway signs the forwarding headers with HMAC-SHA256, see [`crate::client::connection::ClientEndpointConfigurationBuilder::with_forwarded_client`], and the internal servers only accept the headers signed with the same secret, see [`crate::server::Endpoint::set_forwarding_key`].
+#[derive(Debug, Clone)]
+pub struct ForwardingKey {
+    #[cfg_attr(not(any(feature = "client", feature = "server")), allow(dead_code))]
+    key: hmac::Key,
+    max_age: Duration,
+}
+
+impl ForwardingKey {
+    /// Forwarding key derived from `secret`, which should be at least 32 random bytes
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            max_age: DEFAULT_FORWARDING_HEADER_MAX_AGE,
+        }
+    }
+
+    /// Headers signed more than `max_age` ago are refused instead of after [`DEFAULT_FORWARDING_HEADER_MAX_AGE`]
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Max age of the headers accepted with this key
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Signs `client` as of now
+    #[cfg(feature = "client")]
+    pub(crate) fn sign(&self, client: &ForwardedClient) -> Vec<u8> {
+        let client = ForwardedClient {
+            signed_at: unix_now(),
+            ..client.clone()
+        };
+        let mut header =
+            bincode::serialize(&client).expect("Forwarded clients should be serializable");
+        header.extend_from_slice(hmac::sign(&self.key, &header).as_ref());
+        header
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn verify(&self, header: &[u8]) -> Result<ForwardedClient, ForwardingError> {
+        let Some(signed_len) = header.len().checked_sub(TAG_LEN) else {
+            return Err(ForwardingError::Malformed);
+        };
+        let (signed, tag) = header.split_at(signed_len);
+        hmac::verify(&self.key, signed, tag).map_err(|_| ForwardingError::InvalidSignature)?;
+        let client: ForwardedClient =
+            bincode::deserialize(signed).map_err(|_| ForwardingError::Malformed)?;
+        if client.identity.len() > MAX_FORWARDED_IDENTITY_LEN {
+            return Err(ForwardingError::Malformed);
+        }
+        if client.signed_at.saturating_add(self.max_age.as_secs()) < unix_now() {
+            return Err(ForwardingError::Expired);
+        }
+        Ok(client)
+    }
+}
+
+/// Original client of a connection forwarded by a gateway: its address and the identity the gateway established
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardedClient {
+    client_addr: SocketAddr,
+    identity: Vec<u8>,
+    signed_at: u64,
+}
+
+impl ForwardedClient {
+    /// Client connected to the gateway from `client_addr`, without identity
+    pub fn new(client_addr: SocketAddr) -> Self {
+        Self {
+            client_addr,
+            identity: Vec::new(),
+            signed_at: 0,
+        }
+    }
+
+    /// Identity of the client established by the gateway, such as its account or its client id on the gateway, up to [`MAX_FORWARDED_IDENTITY_LEN`] bytes
+    pub fn with_identity(mut self, identity: Vec<u8>) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Address of the client, as seen by the gateway
+    pub fn client_addr(&self) -> SocketAddr {
+        self.client_addr
+    }
+
+    /// Identity of the client established by the gateway
+    pub fn identity(&self) -> &[u8] {
+        &self.identity
+    }
+
+    /// When the gateway signed the header, with a precision of one second. `UNIX_EPOCH` if not signed yet.
+    pub fn signed_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.signed_at)
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::channels::{ChannelAsyncMessage, ChannelId, DEFAULT_MAX_RELIABLE_FRAME_LEN};
+
+/// Protocol violation committed by a peer, detected while decoding its incoming traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolViolation {
+    /// A reliable frame announced a length above the allowed bound
+    FrameTooLarge {
+        /// Length announced by the frame header, channel id included
+        len: usize,
+        /// Maximum length allowed for this frame
+        max_len: usize,
+    },
+    /// A reliable frame announced a length too short to hold a channel id
+    EmptyFrame,
+    /// A datagram too short to hold a channel id and a payload
+    MalformedDatagram,
+    /// A payload was received on a channel which is not opened, only detected in strict mode, see [`HardeningConfiguration::strict`]
+    UnknownChannel(ChannelId),
+    /// A payload of this channel could not be decrypted
+    UndecryptablePayload(ChannelId),
+    /// A payload of this channel could not be decompressed, or exceeds the maximum message size of the channel
+    InvalidPayload(ChannelId),
+    /// A payload of this replay protected channel was already received, or is older than the replay window, see [`crate::shared::channels::ChannelConfig::replay_protected`]
+    ReplayedPayload(ChannelId),
+}
+
+impl fmt::Display for ProtocolViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolViolation::FrameTooLarge { len, max_len } => {
+                write!(f, "frame of {} bytes exceeds {} bytes", len, max_len)
+            }
+            ProtocolViolation::EmptyFrame => write!(f, "empty frame"),
+            ProtocolViolation::MalformedDatagram => write!(f, "malformed datagram"),
+            ProtocolViolation::UnknownChannel(channel_id) => {
+                write!(f, "payload on unknown channel {}", channel_id)
+            }
+            ProtocolViolation::UndecryptablePayload(channel_id) => {
+                write!(f, "undecryptable payload on channel {}", channel_id)
+            }
+            ProtocolViolation::InvalidPayload(channel_id) => {
+                write!(f, "invalid payload on channel {}", channel_id)
+            }
+            ProtocolViolation::ReplayedPayload(channel_id) => {
+                write!(f, "replayed payload on channel {}", channel_id)
+            }
+        }
+    }
+}
+
+/// Defenses of an endpoint against malformed traffic, see [`crate::server::ServerEndpointConfiguration::with_hardening`].
+///
+/// Whatever the configuration, malformed frames and payloads are dropped without disrupting the other connections. By default, the lenient configuration only bounds reliable frames with [`DEFAULT_MAX_RELIABLE_FRAME_LEN`] and never disconnects a client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HardeningConfiguration {
+    #[serde(default)]
+    strict: bool,
+    #[serde(default = "default_max_frame_len")]
+    max_frame_len: usize,
+    #[serde(default)]
+    decode_error_budget: Option<u32>,
+}
+
+fn default_max_frame_len() -> usize {
+    DEFAULT_MAX_RELIABLE_FRAME_LEN
+}
+
+impl Default for HardeningConfiguration {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            max_frame_len: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+            decode_error_budget: None,
+        }
+    }
+}
+
+impl HardeningConfiguration {
+    /// Strict configuration: reliable frames are also bounded by the `max_frame_size` of their channel, and payloads received on channels which are not opened are rejected instead of being delivered.
+    ///
+    /// Intended for internet-facing servers, where both sides agree on the opened channels.
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the maximum length of the reliable frames, whatever their channel. Defaults to [`DEFAULT_MAX_RELIABLE_FRAME_LEN`].
+    ///
+    /// A reliable stream announcing a larger frame is not read any further.
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Disconnects a client with [`crate::shared::close::CloseCode::ProtocolViolation`] once it committed more than `budget` protocol violations. Unlimited by default.
+    pub fn with_decode_error_budget(mut self, budget: u32) -> Self {
+        self.decode_error_budget = Some(budget);
+        self
+    }
+
+    /// Returns true if the strict checks are enabled, see [`HardeningConfiguration::strict`]
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Maximum length of the reliable frames
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Number of protocol violations tolerated per connection, if any
+    pub fn decode_error_budget(&self) -> Option<u32> {
+        self.decode_error_budget
+    }
+}
+
+/// Checks applied by the receiving tasks of a connection, which report the violations to the sync side
+#[derive(Debug, Clone)]
+pub(crate) struct ReceiveHardening {
+    strict: bool,
+    max_frame_len: usize,
+    violations_send: Option<mpsc::Sender<ChannelAsyncMessage>>,
+}
+
+impl ReceiveHardening {
+    #[cfg(feature = "server")]
+    pub(crate) fn new(
+        config: &HardeningConfiguration,
+        violations_send: mpsc::Sender<ChannelAsyncMessage>,
+    ) -> Self {
+        Self {
+            strict: config.strict,
+            max_frame_len: config.max_frame_len,
+            violations_send: Some(violations_send),
+        }
+    }
+
+    /// Lenient checks, violations are only logged
+    #[cfg(any(feature = "client", feature = "no-bevy"))]
+    pub(crate) fn lenient() -> Self {
+        Self {
+            strict: false,
+            max_frame_len: DEFAULT_MAX_RELIABLE_FRAME_LEN,
+            violations_send: None,
+        }
+    }
+
+    pub(crate) fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub(crate) fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Never waits: the violation is not reported if the sync side is already flooded with them
+    pub(crate) fn report(&self, violation: ProtocolViolation) {
+        warn!("Protocol violation: {}", violation);
+        if let Some(violations_send) = &self.violations_send {
+            let _ = violations_send.try_send(ChannelAsyncMessage::ProtocolViolation(violation));
+        }
+    }
+}
@@ -0,0 +1,21 @@
+/// Tick of an input, as counted by the client sending it. Consecutive inputs of a stream have consecutive ticks.
+pub type InputTick = u32;
+
+/// Default number of ticks of inputs carried by each message of an input stream
+pub const DEFAULT_INPUT_HISTORY: usize = 8;
+
+/// Message of an input stream: LAST TICK | INPUTS, the inputs of the ticks ending at LAST TICK, oldest first.
+///
+/// Serialized by the client from a borrowed history and deserialized by the server as `(InputTick, Vec<T>)`, which share the same encoding.
+#[cfg(feature = "server")]
+pub(crate) type InputPacket<T> = (InputTick, Vec<T>);
+
+/// Returns the tick of each input of a packet, oldest first. `None` if the packet starts before the first tick.
+#[cfg(feature = "server")]
+pub(crate) fn packet_ticks(
+    last_tick: InputTick,
+    len: usize,
+) -> Option<impl Iterator<Item = InputTick>> {
+    let first_tick = last_tick.checked_sub(u32::try_from(len).ok()?.checked_sub(1)?)?;
+    Some(first_tick..=last_tick)
+}
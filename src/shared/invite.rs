@@ -0,0 +1,238 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, MAX_TAG_LEN, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use super::{certificate::CertificateFingerprint, error::InviteCodeError};
+
+/// Version of the binary format of the invite codes
+pub const INVITE_CODE_VERSION: u8 = 1;
+/// Max length of the join token of an invite code, in bytes
+pub const MAX_INVITE_TOKEN_LEN: usize = u8::MAX as usize;
+/// Length of the secret of an [`InviteKey`], in bytes
+pub const INVITE_KEY_LEN: usize = 32;
+
+const HOST_KIND_DNS: u8 = 0;
+const HOST_KIND_IPV4: u8 = 4;
+const HOST_KIND_IPV6: u8 = 6;
+
+/// Secret key encrypting the invite codes, shared by the servers creating the codes and the clients joining with them, such as a key built into the game.
+///
+/// Without the key, a code can neither be read nor forged.
+#[derive(Clone)]
+pub struct InviteKey([u8; INVITE_KEY_LEN]);
+
+impl InviteKey {
+    /// Key made of a secret of [`INVITE_KEY_LEN`] bytes
+    pub fn new(secret: [u8; INVITE_KEY_LEN]) -> Self {
+        Self(secret)
+    }
+
+    fn aead_key(&self) -> LessSafeKey {
+        LessSafeKey::new(
+            UnboundKey::new(&CHACHA20_POLY1305, &self.0)
+                .expect("an invite key should have the length of a ChaCha20-Poly1305 key"),
+        )
+    }
+}
+
+impl fmt::Debug for InviteKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InviteKey(..)")
+    }
+}
+
+/// Compact code to join a self-hosted server, such as a code sent by a player to a friend: the host and the port of the server, the fingerprint of its certificate and an optional join token.
+///
+/// Encrypted with an [`InviteKey`] (ChaCha20-Poly1305) and encoded as URL-safe base64: only the holders of the key can read the code, and a typo or a forged code fails to decrypt. A client connecting with the code only trusts the certificate whose fingerprint is in the code, see [`crate::client::QuinnetClient::open_connection_from_invite`].
+///
+/// # Example
+///
+/// ```
+/// use bevy_quinnet::shared::{
+///     certificate::CertificateFingerprint,
+///     invite::{InviteCode, InviteKey},
+/// };
+/// let key = InviteKey::new([3; 32]);
+/// let invite = InviteCode::new("127.0.0.1", 6000, CertificateFingerprint::new([7; 32]))
+///     .unwrap()
+///     .with_token(b"secret".to_vec())
+///     .unwrap();
+/// let code = invite.encode(&key);
+/// assert_eq!(InviteCode::decode(&code, &key).unwrap(), invite);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InviteCode {
+    host: String,
+    port: u16,
+    fingerprint: CertificateFingerprint,
+    token: Option<Vec<u8>>,
+}
+
+impl InviteCode {
+    /// Invite to the server at `host`, a DNS name or an IP address, on `port`, whose certificate has `fingerprint`, such as the fingerprint of the [`crate::server::certificate::ServerCertificate`] returned when starting the endpoint
+    pub fn new(
+        host: impl Into<String>,
+        port: u16,
+        fingerprint: CertificateFingerprint,
+    ) -> Result<Self, InviteCodeError> {
+        let host = host.into();
+        if host.len() > u8::MAX as usize
+            || rustls::pki_types::ServerName::try_from(host.as_str()).is_err()
+        {
+            return Err(InviteCodeError::InvalidHost(host));
+        }
+        Ok(Self {
+            host,
+            port,
+            fingerprint,
+            token: None,
+        })
+    }
+
+    /// Bundles a join token with the invite, such as a token checked by the server to let the client in. At most [`MAX_INVITE_TOKEN_LEN`] bytes
+    pub fn with_token(mut self, token: Vec<u8>) -> Result<Self, InviteCodeError> {
+        if token.len() > MAX_INVITE_TOKEN_LEN {
+            return Err(InviteCodeError::TokenTooLong(token.len()));
+        }
+        self.token = Some(token);
+        Ok(self)
+    }
+
+    /// Host of the server, a DNS name or an IP address
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Port of the server
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Fingerprint of the certificate of the server
+    pub fn fingerprint(&self) -> &CertificateFingerprint {
+        &self.fingerprint
+    }
+
+    /// Join token of the invite, if any
+    pub fn token(&self) -> Option<&[u8]> {
+        self.token.as_deref()
+    }
+
+    /// Encrypts the invite with `key`: version | nonce | sealed content and its tag, in URL-safe base64. The content is host kind | host | port | fingerprint | token length | token.
+    pub fn encode(&self, key: &InviteKey) -> String {
+        let mut buf = Vec::new();
+        match self.host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                buf.push(HOST_KIND_IPV4);
+                buf.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                buf.push(HOST_KIND_IPV6);
+                buf.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                buf.push(HOST_KIND_DNS);
+                buf.push(self.host.len() as u8);
+                buf.extend_from_slice(self.host.as_bytes());
+            }
+        }
+        buf.extend_from_slice(&self.port.to_be_bytes());
+        buf.extend_from_slice(self.fingerprint.as_bytes());
+        if let Some(token) = &self.token {
+            buf.push(token.len() as u8);
+            buf.extend_from_slice(token);
+        }
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .expect("the system random number generator should be available");
+        key.aead_key()
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from([INVITE_CODE_VERSION]),
+                &mut buf,
+            )
+            .expect("an invite should not exceed the maximum size of a ChaCha20-Poly1305 message");
+        let mut code = vec![INVITE_CODE_VERSION];
+        code.extend_from_slice(&nonce);
+        code.extend_from_slice(&buf);
+        base64::encode_config(code, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Decrypts an invite encoded by [`InviteCode::encode`] with the same `key`. Surrounding whitespace is ignored
+    pub fn decode(code: &str, key: &InviteKey) -> Result<Self, InviteCodeError> {
+        let buf = base64::decode_config(code.trim(), base64::URL_SAFE_NO_PAD)
+            .map_err(|_| InviteCodeError::InvalidEncoding)?;
+        let version = *buf.first().ok_or(InviteCodeError::Malformed)?;
+        if version != INVITE_CODE_VERSION {
+            return Err(InviteCodeError::UnsupportedVersion(version));
+        }
+        if buf.len() < 1 + NONCE_LEN + MAX_TAG_LEN {
+            return Err(InviteCodeError::Malformed);
+        }
+        let (nonce, sealed) = buf[1..].split_at(NONCE_LEN);
+        let mut sealed = sealed.to_vec();
+        let content = key
+            .aead_key()
+            .open_in_place(
+                Nonce::try_assume_unique_for_key(nonce).map_err(|_| InviteCodeError::Malformed)?,
+                Aad::from([version]),
+                &mut sealed,
+            )
+            .map_err(|_| InviteCodeError::Undecryptable)?;
+
+        let mut reader = Reader(content);
+        let host = match reader.byte()? {
+            HOST_KIND_IPV4 => Ipv4Addr::from(reader.array::<4>()?).to_string(),
+            HOST_KIND_IPV6 => Ipv6Addr::from(reader.array::<16>()?).to_string(),
+            HOST_KIND_DNS => {
+                let len = reader.byte()? as usize;
+                String::from_utf8(reader.take(len)?.to_vec())
+                    .map_err(|_| InviteCodeError::Malformed)?
+            }
+            _ => return Err(InviteCodeError::Malformed),
+        };
+        let port = u16::from_be_bytes(reader.array()?);
+        let fingerprint = CertificateFingerprint::new(reader.array()?);
+        let token = match reader.0.is_empty() {
+            true => None,
+            false => {
+                let len = reader.byte()? as usize;
+                Some(reader.take(len)?.to_vec())
+            }
+        };
+        if !reader.0.is_empty() {
+            return Err(InviteCodeError::Malformed);
+        }
+
+        let invite = Self::new(host, port, fingerprint).map_err(|_| InviteCodeError::Malformed)?;
+        Ok(Self { token, ..invite })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], InviteCodeError> {
+        if self.0.len() < len {
+            return Err(InviteCodeError::Malformed);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, InviteCodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], InviteCodeError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
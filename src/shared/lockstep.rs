@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+#[cfg(any(feature = "client", feature = "server"))]
+use bevy::prelude::Event;
+use serde::{Deserialize, Serialize};
+
+use super::ClientId;
+
+/// Tick of a lockstep simulation, as counted by all the peers
+pub type LockstepTick = u32;
+
+/// Default time the server waits for the missing inputs of a tick, since the first input of the tick was received
+pub const DEFAULT_LOCKSTEP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Inputs of all the clients for a tick of a lockstep simulation, released by the server once all the connected clients sent their input for the tick, or once the tick timed out.
+///
+/// The bundles are released in increasing tick order, without gaps, and every peer receives the same bundles: a deterministic simulation stepped with them stays in sync on every peer. Raised as an event on the server and on the clients by their lockstep plugins.
+#[cfg_attr(any(feature = "client", feature = "server"), derive(Event))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockstepBundle<T> {
+    /// Tick of the inputs
+    pub tick: LockstepTick,
+    /// Input of each client for the tick, ordered by client id
+    pub inputs: Vec<(ClientId, T)>,
+    /// Connected clients whose input was not received before the timeout, ordered by client id. Empty unless the tick stalled
+    pub missing: Vec<ClientId>,
+}
+
+impl<T> LockstepBundle<T> {
+    /// Returns true if the tick was released after a timeout, without the inputs of some clients
+    pub fn stalled(&self) -> bool {
+        !self.missing.is_empty()
+    }
+}
+
+/// Message of a client: TICK | INPUT
+///
+/// Serialized by the client from a borrowed input and deserialized by the server, which share the same encoding.
+#[cfg(feature = "server")]
+pub(crate) type LockstepInputMessage<T> = (LockstepTick, T);
@@ -0,0 +1,71 @@
+use std::{net::SocketAddr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// Default period between two heartbeats of a server registered to a master server
+pub const DEFAULT_MASTER_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// Default time after which a master server drops a server which stopped sending heartbeats
+pub const DEFAULT_MASTER_LISTING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Description of a game server, as registered to a master server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// Name of the server
+    pub name: String,
+    /// Port of the game endpoint of the server
+    pub port: u16,
+    /// Public address of the game endpoint of the server. If `None`, the master server lists the address the server registered from, with [`ServerInfo::port`]
+    pub public_addr: Option<SocketAddr>,
+    /// Number of players connected
+    pub players: u32,
+    /// Max number of players
+    pub max_players: u32,
+    /// Version of the game server
+    pub version: String,
+    /// Free-form tags, such as the game mode or the region
+    pub tags: Vec<String>,
+}
+
+impl ServerInfo {
+    /// Server `name` with a game endpoint on `port`, without players, version nor tags
+    pub fn new(name: impl Into<String>, port: u16) -> Self {
+        Self {
+            name: name.into(),
+            port,
+            public_addr: None,
+            players: 0,
+            max_players: 0,
+            version: String::new(),
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// Server listed by a master server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerListing {
+    /// Address of the game endpoint of the server
+    pub addr: SocketAddr,
+    /// Description of the server
+    pub info: ServerInfo,
+}
+
+/// Request to a master server
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum MasterRequest {
+    /// Registers the server or updates its info, also acting as a heartbeat
+    Register(ServerInfo),
+    /// Removes the server from the list
+    Unregister,
+    /// Asks for the list of the servers
+    Query,
+}
+
+/// Response of a master server
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum MasterResponse {
+    /// List of the servers
+    List(Vec<ServerListing>),
+}
@@ -0,0 +1,105 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, RwLock,
+};
+
+use super::error::AsyncChannelError;
+
+/// What a connection does with the messages sent while it exceeds its [`MemoryBudget`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryBudgetPolicy {
+    /// Refuses the messages of the unreliable channels, the messages of the reliable channels are still queued
+    DropUnreliable,
+    /// Refuses all the messages until the queues are drained, the sender can retry them later
+    Block,
+    /// Refuses all the messages and closes the connection with [`crate::shared::close::CloseCode::MemoryBudgetExceeded`]
+    Disconnect,
+}
+
+/// Maximum memory held by the buffers of a connection, and what happens past it.
+///
+/// Counts the payloads waiting in the outgoing queues of the channels of the connection, and the received payloads waiting to be read by the application. A client which stops reading its messages, such as a stalled or malicious one, can't make the server buffer them without bound.
+///
+/// The messages refused because of the budget fail with [`AsyncChannelError::MemoryBudgetExceeded`]. The messages of Quinnet's own control channel are never refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    max_bytes: usize,
+    policy: MemoryBudgetPolicy,
+}
+
+impl MemoryBudget {
+    /// Budget of `max_bytes` bytes, applying `policy` past it
+    pub fn new(max_bytes: usize, policy: MemoryBudgetPolicy) -> Self {
+        Self { max_bytes, policy }
+    }
+
+    /// Maximum number of bytes buffered by the connection
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Policy applied past the budget
+    pub fn policy(&self) -> MemoryBudgetPolicy {
+        self.policy
+    }
+}
+
+/// Bytes buffered by a connection, shared by its outgoing queues and its incoming payloads
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionMemory {
+    used: AtomicUsize,
+    budget: RwLock<Option<MemoryBudget>>,
+    /// A message was refused since the last [`ConnectionMemory::check`]
+    refused: AtomicBool,
+}
+
+pub(crate) type SharedConnectionMemory = Arc<ConnectionMemory>;
+
+impl ConnectionMemory {
+    pub(crate) fn add(&self, len: usize) {
+        self.used.fetch_add(len, Ordering::Relaxed);
+    }
+
+    pub(crate) fn remove(&self, len: usize) {
+        self.used.fetch_sub(len, Ordering::Relaxed);
+    }
+
+    pub(crate) fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn budget(&self) -> Option<MemoryBudget> {
+        self.budget.read().ok().and_then(|budget| *budget)
+    }
+
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn set_budget(&self, budget: Option<MemoryBudget>) {
+        if let Ok(mut current) = self.budget.write() {
+            *current = budget;
+        }
+    }
+
+    /// Checks whether a payload of `len` bytes can be queued on a channel, according to the budget
+    pub(crate) fn admit(&self, len: usize, unreliable: bool) -> Result<(), AsyncChannelError> {
+        let Some(budget) = self.budget() else {
+            return Ok(());
+        };
+        if self.used() + len <= budget.max_bytes {
+            return Ok(());
+        }
+        if budget.policy == MemoryBudgetPolicy::DropUnreliable && !unreliable {
+            return Ok(());
+        }
+        self.refused.store(true, Ordering::Relaxed);
+        Err(AsyncChannelError::MemoryBudgetExceeded)
+    }
+
+    /// Returns the budget and the bytes used if the connection exceeds its budget, or refused a message since the last check
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn check(&self) -> Option<(MemoryBudget, usize)> {
+        let refused = self.refused.swap(false, Ordering::Relaxed);
+        let budget = self.budget()?;
+        let used = self.used();
+        (refused || used > budget.max_bytes).then_some((budget, used))
+    }
+}
@@ -0,0 +1,35 @@
+use std::future::Future;
+
+use tracing::Span;
+
+/// Enters the span built by `span` until the returned guard is dropped, with the `profiling` feature. Does nothing without it.
+///
+/// The guard must not be held across an `.await`, see [`profiled`] for the async steps.
+#[cfg(feature = "profiling")]
+pub(crate) fn enter(span: impl FnOnce() -> Span) -> Option<tracing::span::EnteredSpan> {
+    Some(span().entered())
+}
+
+/// Enters the span built by `span` until the returned guard is dropped, with the `profiling` feature. Does nothing without it.
+///
+/// The guard must not be held across an `.await`, see [`profiled`] for the async steps.
+#[cfg(not(feature = "profiling"))]
+pub(crate) fn enter(_span: impl FnOnce() -> Span) -> Option<tracing::span::EnteredSpan> {
+    None
+}
+
+/// Runs `future` in the span built by `span`, entered each time the future is polled, with the `profiling` feature. Returns it as is without it.
+///
+/// The span is built right away, as a child of the current span.
+pub(crate) fn profiled<F: Future>(
+    future: F,
+    span: impl FnOnce() -> Span,
+) -> impl Future<Output = F::Output> {
+    #[cfg(feature = "profiling")]
+    return tracing::Instrument::instrument(future, span());
+    #[cfg(not(feature = "profiling"))]
+    {
+        let _ = span;
+        future
+    }
+}
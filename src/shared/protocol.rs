@@ -0,0 +1,198 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::shared::channels::{ChannelConfig, ChannelId, ChannelKind, ChannelsConfiguration};
+
+/// Channel of a protocol declared with [`crate::protocol!`], with the type of the messages allowed on it.
+///
+/// Used by the typed send & receive methods, such as [`crate::client::connection::ClientSideConnection::send_on`] and [`crate::server::Endpoint::receive_from_on`], to check at compile time that a message is sent on the channel it was declared on.
+pub trait ProtocolChannel: Into<ChannelId> + Copy {
+    /// Type of the messages sent on the channel
+    type Message: Serialize + DeserializeOwned;
+
+    /// Id of the channel, its position in the declaration of the protocol
+    const ID: ChannelId;
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a, stable across builds and platforms unlike the std hasher
+struct ProtocolHasher(u64);
+
+impl ProtocolHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    fn write_channel(&mut self, config: &ChannelConfig) {
+        match config.kind() {
+            ChannelKind::OrderedReliable { max_frame_size } => {
+                self.write(&[0]);
+                self.write_u64(max_frame_size as u64);
+            }
+            ChannelKind::UnorderedReliable { max_frame_size } => {
+                self.write(&[1]);
+                self.write_u64(max_frame_size as u64);
+            }
+            ChannelKind::Unreliable => self.write(&[2]),
+        }
+        // The priority only affects the sending side, and the keys of the encryption are not part of the protocol
+        self.write(&[
+            config.is_compressed() as u8,
+            config.encryption().is_some() as u8,
+            config.is_traced() as u8,
+            config.is_replay_protected() as u8,
+            config.is_acknowledged() as u8,
+            config.redundancy(),
+        ]);
+        self.write_u64(
+            config
+                .message_size_limit()
+                .map_or(0, |size| size as u64 + 1),
+        );
+        #[cfg(feature = "fec")]
+        self.write(&[config.fec_group_size().unwrap_or(0)]);
+        // Only written when present, to keep the hashes of the channels without these options
+        if let Some(dictionary) = config.compression_dictionary() {
+            self.write_u64(dictionary.id() as u64);
+        }
+        if config.is_ticked() {
+            self.write(b"ticked");
+        }
+    }
+}
+
+/// Hash of a protocol: the number of channels, their kinds, their options and the names of the types of their messages.
+///
+/// Stable across builds and platforms, two peers with different hashes can not understand each other.
+pub fn protocol_hash(channels: &ChannelsConfiguration, message_types: &[&str]) -> u64 {
+    let mut hasher = ProtocolHasher(FNV_OFFSET_BASIS);
+    hasher.write_u64(channels.configs().len() as u64);
+    for config in channels.configs() {
+        hasher.write_channel(config);
+    }
+    hasher.write_u64(message_types.len() as u64);
+    for message_type in message_types {
+        hasher.write_str(message_type);
+    }
+    hasher.0
+}
+
+/// Declares a protocol: its channels, their configurations and the type of the messages allowed on each of them.
+///
+/// Generates a module containing:
+/// - a unit struct per channel implementing [`ProtocolChannel`], usable wherever a [`ChannelId`] is expected, with the typed send & receive methods
+/// - `channels_configuration()`, the [`ChannelsConfiguration`] of the protocol, the channels having the ids of their declaration order, naming their message types
+/// - `hash()`, the [`ChannelsConfiguration::protocol_hash`] of the protocol
+/// - `MESSAGE_TYPES`, the names of the message types, as written in the declaration
+///
+/// The message types and the configurations are resolved where the macro is invoked, at module scope or inside a function. The macro also emits the implementations of the channels next to the generated module.
+///
+/// ### Example
+///
+/// ```
+/// use bevy_quinnet::shared::{
+///     channels::{ChannelConfig, ChannelKind},
+///     protocol::ProtocolChannel,
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// pub struct ChatMessage(String);
+/// #[derive(Serialize, Deserialize)]
+/// pub struct Position(f32, f32);
+///
+/// bevy_quinnet::protocol! {
+///     /// Protocol of the game
+///     pub mod game {
+///         Chat: ChatMessage = ChannelConfig::reliable_ordered(),
+///         Positions: Position = ChannelConfig::new(ChannelKind::Unreliable).compressed(),
+///     }
+/// }
+///
+/// fn main() {
+///     let channels = game::channels_configuration();
+///     assert_eq!(<game::Positions as ProtocolChannel>::ID, 1);
+///     assert_eq!(game::MESSAGE_TYPES, ["ChatMessage", "Position"]);
+/// }
+/// ```
+#[macro_export]
+macro_rules! protocol {
+    (
+        $(#[$meta:meta])*
+        $vis:vis mod $protocol:ident {
+            $(
+                $(#[$channel_meta:meta])*
+                $channel:ident : $message:ty = $config:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis mod $protocol {
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            #[repr(u8)]
+            pub enum ChannelIds {
+                $($channel),*
+            }
+
+            /// Configurations of the channels, implemented where the protocol is declared
+            #[doc(hidden)]
+            pub struct Configs;
+
+            $(
+                $(#[$channel_meta])*
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                pub struct $channel;
+
+                impl From<$channel> for $crate::shared::channels::ChannelId {
+                    fn from(_: $channel) -> Self {
+                        <$channel as $crate::shared::protocol::ProtocolChannel>::ID
+                    }
+                }
+            )*
+
+            /// Names of the types of the messages of each channel, as written in the declaration of the protocol
+            pub const MESSAGE_TYPES: &[&str] = &[$(stringify!($message)),*];
+
+            /// Configuration of the channels of the protocol, in their declaration order
+            pub fn channels_configuration() -> $crate::shared::channels::ChannelsConfiguration {
+                $crate::shared::channels::ChannelsConfiguration::from_configs(Configs::configs())
+                    .expect("Too many channels in the protocol")
+                    .with_message_types(MESSAGE_TYPES.iter().copied())
+            }
+
+            /// Hash of the protocol, exchanged when a client connects, see `bevy_quinnet::shared::channels::ChannelsConfiguration::protocol_hash`
+            pub fn hash() -> u64 {
+                channels_configuration().protocol_hash()
+            }
+        }
+
+        // Outside of the module, to resolve the message types and the configurations in the scope of the declaration
+        $(
+            impl $crate::shared::protocol::ProtocolChannel for $protocol::$channel {
+                type Message = $message;
+                const ID: $crate::shared::channels::ChannelId =
+                    $protocol::ChannelIds::$channel as $crate::shared::channels::ChannelId;
+            }
+        )*
+
+        impl $protocol::Configs {
+            fn configs() -> Vec<$crate::shared::channels::ChannelConfig> {
+                vec![$($crate::shared::channels::ChannelConfig::from($config)),*]
+            }
+        }
+    };
+}
@@ -0,0 +1,235 @@
+#[cfg(any(feature = "client", feature = "server"))]
+use std::{
+    io::{self, IoSliceMut},
+    net::{SocketAddr, UdpSocket},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+#[cfg(any(feature = "client", feature = "server"))]
+use quinn::{
+    udp::{RecvMeta, Transmit},
+    AsyncUdpSocket, Runtime, UdpPoller,
+};
+use serde::Deserialize;
+#[cfg(any(feature = "client", feature = "server"))]
+use socket2::SockRef;
+
+/// Differentiated services code point (DSCP), the 6 high bits of the traffic class of a packet, used by the routers to prioritize it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "u8")]
+pub struct Dscp(u8);
+
+impl Dscp {
+    /// Best effort (0), the class of the unmarked packets
+    pub const BEST_EFFORT: Dscp = Dscp(0);
+    /// Expedited forwarding (46), low loss and low latency, for the real-time traffic of a game
+    pub const EXPEDITED_FORWARDING: Dscp = Dscp(46);
+    /// Assured forwarding 41 (34), for interactive audio and video
+    pub const ASSURED_FORWARDING_41: Dscp = Dscp(34);
+
+    /// Returns the code point `value`, or None if it does not fit in 6 bits
+    pub const fn new(value: u8) -> Option<Dscp> {
+        match value {
+            0..=0x3F => Some(Dscp(value)),
+            _ => None,
+        }
+    }
+
+    /// Value of the code point
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for Dscp {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Dscp::new(value).ok_or("a DSCP fits in 6 bits")
+    }
+}
+
+/// Traffic class of the packets of an endpoint: the DSCP they are marked with and their use of Explicit Congestion Notification (ECN). See [`crate::server::ServerEndpointConfiguration::with_qos`] and [`crate::client::connection::ClientEndpointConfigurationBuilder::with_qos`].
+///
+/// By default, the packets are left unmarked and ECN is enabled, as done by quinn: the packets are marked as ECN capable, and the congestion marks set by the routers are reported to the congestion controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct QosConfiguration {
+    #[serde(default)]
+    dscp: Option<Dscp>,
+    #[serde(default = "default_ecn")]
+    ecn: bool,
+}
+
+fn default_ecn() -> bool {
+    true
+}
+
+impl Default for QosConfiguration {
+    fn default() -> Self {
+        Self {
+            dscp: None,
+            ecn: true,
+        }
+    }
+}
+
+impl QosConfiguration {
+    /// Marks the outgoing packets with `dscp`.
+    ///
+    /// The packets are then sent one by one, without segmentation offload nor source address selection: on a multihomed host, bind the endpoint to a specific address rather than to a wildcard address. Marking IPv6 packets is not supported on Windows.
+    pub fn with_dscp(mut self, dscp: Dscp) -> Self {
+        self.dscp = Some(dscp);
+        self
+    }
+
+    /// Enables or disables ECN, enabled by default.
+    ///
+    /// When disabled, the outgoing packets are not marked as ECN capable and the congestion marks of the incoming packets are ignored: some middleboxes drop or bleach ECN capable packets.
+    pub fn with_ecn(mut self, ecn: bool) -> Self {
+        self.ecn = ecn;
+        self
+    }
+
+    /// DSCP of the outgoing packets, if any
+    pub fn dscp(&self) -> Option<Dscp> {
+        self.dscp
+    }
+
+    /// Returns true if ECN is enabled
+    pub fn is_ecn_enabled(&self) -> bool {
+        self.ecn
+    }
+
+    /// Sets the DSCP on `socket`, before it is wrapped by [`QosConfiguration::wrap_socket`]. Fails early on the platforms which can't mark the packets of the socket.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn apply(&self, socket: &UdpSocket) -> io::Result<()> {
+        match self.dscp {
+            Some(dscp) => set_traffic_class(socket, traffic_class(dscp, None)),
+            None => Ok(()),
+        }
+    }
+
+    /// Wraps `socket`, on which the configuration was applied, into the socket of a quinn endpoint
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn wrap_socket(
+        &self,
+        socket: UdpSocket,
+        runtime: &Arc<dyn Runtime>,
+    ) -> io::Result<Arc<dyn AsyncUdpSocket>> {
+        if *self == QosConfiguration::default() {
+            return runtime.wrap_udp_socket(socket);
+        }
+        let marked = socket.try_clone()?;
+        Ok(Arc::new(QosSocket {
+            inner: runtime.wrap_udp_socket(socket)?,
+            traffic_class: AtomicU8::new(self.dscp.map_or(0, |dscp| traffic_class(dscp, None))),
+            socket: marked,
+            config: *self,
+        }))
+    }
+}
+
+/// Traffic class of the packets marked with `dscp` and `ecn`
+#[cfg(any(feature = "client", feature = "server"))]
+fn traffic_class(dscp: Dscp, ecn: Option<quinn::udp::EcnCodepoint>) -> u8 {
+    (dscp.value() << 2) | ecn.map_or(0, |ecn| ecn as u8)
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+fn set_traffic_class(socket: &UdpSocket, traffic_class: u8) -> io::Result<()> {
+    let sock_ref = SockRef::from(socket);
+    if socket.local_addr()?.is_ipv4() {
+        return sock_ref.set_tos_v4(traffic_class as u32);
+    }
+    // Dual-stack sockets may use either option for the IPv4 packets
+    let _ = sock_ref.set_tos_v4(traffic_class as u32);
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd"
+    ))]
+    return sock_ref.set_tclass_v6(traffic_class as u32);
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd"
+    )))]
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Socket of an endpoint marking its packets with a [`QosConfiguration`].
+///
+/// quinn sets the traffic class of each packet to its ECN codepoint, clearing any DSCP set on the socket: when a DSCP is set, the packets are sent through a clone of the socket instead, with the traffic class set on the socket.
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug)]
+struct QosSocket {
+    inner: Arc<dyn AsyncUdpSocket>,
+    socket: UdpSocket,
+    /// Traffic class currently set on `socket`
+    traffic_class: AtomicU8,
+    config: QosConfiguration,
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl AsyncUdpSocket for QosSocket {
+    fn create_io_poller(self: Arc<Self>) -> Pin<Box<dyn UdpPoller>> {
+        self.inner.clone().create_io_poller()
+    }
+
+    fn try_send(&self, transmit: &Transmit) -> io::Result<()> {
+        let ecn = transmit.ecn.filter(|_| self.config.ecn);
+        let Some(dscp) = self.config.dscp else {
+            return self.inner.try_send(&Transmit { ecn, ..*transmit });
+        };
+        let traffic_class = traffic_class(dscp, ecn);
+        if self.traffic_class.load(Ordering::Relaxed) != traffic_class {
+            set_traffic_class(&self.socket, traffic_class)?;
+            self.traffic_class.store(traffic_class, Ordering::Relaxed);
+        }
+        self.socket
+            .send_to(transmit.contents, transmit.destination)
+            .map(|_| ())
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        let poll = self.inner.poll_recv(cx, bufs, meta);
+        if let Poll::Ready(Ok(count)) = poll {
+            if !self.config.ecn {
+                meta[..count].iter_mut().for_each(|meta| meta.ecn = None);
+            }
+        }
+        poll
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn max_transmit_segments(&self) -> usize {
+        match self.config.dscp {
+            // Sent one by one
+            Some(_) => 1,
+            None => self.inner.max_transmit_segments(),
+        }
+    }
+
+    fn max_receive_segments(&self) -> usize {
+        self.inner.max_receive_segments()
+    }
+
+    fn may_fragment(&self) -> bool {
+        self.inner.may_fragment()
+    }
+}
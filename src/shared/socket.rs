@@ -0,0 +1,377 @@
+#[cfg(any(feature = "client", feature = "server"))]
+use std::{io, net::UdpSocket, sync::Arc};
+#[cfg(feature = "server")]
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, Weak},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+#[cfg(feature = "server")]
+use quinn::{
+    udp::{RecvMeta, Transmit},
+    UdpPoller,
+};
+#[cfg(any(feature = "client", feature = "server"))]
+use quinn::{AsyncUdpSocket, EndpointConfig, Runtime, TransportConfig};
+use serde::Deserialize;
+#[cfg(any(feature = "client", feature = "server"))]
+use socket2::SockRef;
+#[cfg(any(feature = "client", feature = "server"))]
+use tracing::info;
+
+#[cfg(any(feature = "client", feature = "server"))]
+use super::qos::QosConfiguration;
+
+/// Smallest maximum UDP payload size, the minimum MTU of QUIC
+pub const MIN_MAX_UDP_PAYLOAD_SIZE: u16 = 1200;
+/// Largest maximum UDP payload size
+pub const MAX_MAX_UDP_PAYLOAD_SIZE: u16 = 65_527;
+
+/// Performance settings of the UDP socket of an endpoint, the OS and quinn's defaults when unset. See [`crate::server::ServerEndpointConfiguration::with_socket`] and [`crate::client::connection::ClientEndpointConfigurationBuilder::with_socket`].
+///
+/// The settings in use are logged when the socket is bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct SocketConfiguration {
+    #[serde(default)]
+    receive_buffer_size: Option<usize>,
+    #[serde(default)]
+    send_buffer_size: Option<usize>,
+    #[serde(default)]
+    max_udp_payload_size: Option<u16>,
+    #[serde(default = "default_segmentation_offload")]
+    segmentation_offload: bool,
+}
+
+fn default_segmentation_offload() -> bool {
+    true
+}
+
+impl Default for SocketConfiguration {
+    fn default() -> Self {
+        Self {
+            receive_buffer_size: None,
+            send_buffer_size: None,
+            max_udp_payload_size: None,
+            segmentation_offload: true,
+        }
+    }
+}
+
+impl SocketConfiguration {
+    /// Requests a receive buffer (`SO_RCVBUF`) of `size` bytes. The OS may grant a different size, bounded on Linux by `net.core.rmem_max`.
+    ///
+    /// A larger buffer absorbs the bursts of packets of many clients, instead of dropping them while the endpoint is busy.
+    pub fn with_receive_buffer_size(mut self, size: usize) -> Self {
+        self.receive_buffer_size = Some(size);
+        self
+    }
+
+    /// Requests a send buffer (`SO_SNDBUF`) of `size` bytes. The OS may grant a different size, bounded on Linux by `net.core.wmem_max`.
+    pub fn with_send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Accepts UDP payloads of up to `size` bytes from the peers, from [`MIN_MAX_UDP_PAYLOAD_SIZE`] to [`MAX_MAX_UDP_PAYLOAD_SIZE`]. Defaults to 1472 bytes, the largest payload of a 1500 bytes Ethernet MTU.
+    ///
+    /// Raising it only helps on links with larger MTUs, such as jumbo frames or loopback, at the cost of larger receive buffers.
+    pub fn with_max_udp_payload_size(mut self, size: u16) -> Self {
+        self.max_udp_payload_size = Some(size);
+        self
+    }
+
+    /// Enables or disables the segmentation offload (GSO), enabled by default where supported: several packets to the same peer are handed to the OS in a single call, and split by the OS or the network card.
+    ///
+    /// Disable it on the network drivers which mishandle it.
+    pub fn with_segmentation_offload(mut self, enabled: bool) -> Self {
+        self.segmentation_offload = enabled;
+        self
+    }
+
+    /// Requested receive buffer size, if any
+    pub fn receive_buffer_size(&self) -> Option<usize> {
+        self.receive_buffer_size
+    }
+
+    /// Requested send buffer size, if any
+    pub fn send_buffer_size(&self) -> Option<usize> {
+        self.send_buffer_size
+    }
+
+    /// Maximum UDP payload size, if set
+    pub fn max_udp_payload_size(&self) -> Option<u16> {
+        self.max_udp_payload_size
+    }
+
+    /// Returns true if the segmentation offload is enabled
+    pub fn is_segmentation_offload_enabled(&self) -> bool {
+        self.segmentation_offload
+    }
+
+    /// Returns the maximum UDP payload size if it is out of bounds
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn invalid_max_udp_payload_size(&self) -> Option<u16> {
+        self.max_udp_payload_size
+            .filter(|size| !(MIN_MAX_UDP_PAYLOAD_SIZE..=MAX_MAX_UDP_PAYLOAD_SIZE).contains(size))
+    }
+
+    /// Endpoint configuration of the socket, once validated
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn endpoint_config(&self) -> EndpointConfig {
+        let mut endpoint_config = EndpointConfig::default();
+        if let Some(size) = self.max_udp_payload_size {
+            endpoint_config
+                .max_udp_payload_size(size)
+                .expect("max UDP payload size should be validated");
+        }
+        endpoint_config
+    }
+
+    /// Applies the transport settings of the socket to the connections using it
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn configure_transport(&self, transport: &mut TransportConfig) {
+        transport.enable_segmentation_offload(self.segmentation_offload);
+    }
+
+    /// Sets the buffer sizes of `socket`
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn apply(&self, socket: &UdpSocket) -> io::Result<()> {
+        let sock_ref = SockRef::from(socket);
+        if let Some(size) = self.receive_buffer_size {
+            sock_ref.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            sock_ref.set_send_buffer_size(size)?;
+        }
+        Ok(())
+    }
+
+    /// Wraps `socket`, on which the configuration and `qos` were applied, into the socket of a quinn endpoint, and logs the settings in use
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn wrap_socket(
+        &self,
+        socket: UdpSocket,
+        qos: &QosConfiguration,
+        runtime: &Arc<dyn Runtime>,
+    ) -> io::Result<Arc<dyn AsyncUdpSocket>> {
+        let local_addr = socket.local_addr()?;
+        let sock_ref = SockRef::from(&socket);
+        let receive_buffer_size = sock_ref.recv_buffer_size()?;
+        let send_buffer_size = sock_ref.send_buffer_size()?;
+        let socket = qos.wrap_socket(socket, runtime)?;
+        let gso_segments = match self.segmentation_offload {
+            true => socket.max_transmit_segments(),
+            false => 1,
+        };
+        info!(
+            "Socket {}: GSO {} segments, GRO {} segments, receive buffer {} bytes, send buffer {} bytes, max UDP payload {} bytes",
+            local_addr,
+            gso_segments,
+            socket.max_receive_segments(),
+            receive_buffer_size,
+            send_buffer_size,
+            self.endpoint_config().get_max_udp_payload_size()
+        );
+        Ok(socket)
+    }
+}
+
+/// Handle closing the socket of a quinn endpoint on demand, see [`SocketRelease::release`]
+///
+/// quinn only drops the socket of an endpoint once all its connections are drained and all its handles are dropped, which may outlive the stop of the endpoint.
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SocketRelease(Arc<ReleaseState>);
+
+/// Poller of a [`ReleasableSocket`], emptied by the release of the socket
+#[cfg(feature = "server")]
+type PollerSlot = Mutex<Option<Pin<Box<dyn UdpPoller>>>>;
+
+#[cfg(feature = "server")]
+#[derive(Debug, Default)]
+struct ReleaseState {
+    socket: RwLock<Option<Arc<dyn AsyncUdpSocket>>>,
+    /// Pollers created on the socket, each keeping it open
+    pollers: Mutex<Vec<Weak<PollerSlot>>>,
+    status: Mutex<SocketStatus>,
+    dropped: Condvar,
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum SocketStatus {
+    /// Bound, not handed to quinn yet
+    #[default]
+    Pending,
+    /// Held by quinn
+    InUse,
+    /// Dropped by quinn or released
+    Released,
+}
+
+#[cfg(feature = "server")]
+impl ReleaseState {
+    fn socket(&self) -> RwLockReadGuard<'_, Option<Arc<dyn AsyncUdpSocket>>> {
+        self.socket.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn status(&self) -> MutexGuard<'_, SocketStatus> {
+        self.status.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn pollers(&self) -> MutexGuard<'_, Vec<Weak<PollerSlot>>> {
+        self.pollers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Drops the socket and the pollers created on it, closing the socket
+    fn close(&self) {
+        self.socket
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        for poller in self.pollers().drain(..) {
+            if let Some(poller) = poller.upgrade() {
+                poller.lock().unwrap_or_else(PoisonError::into_inner).take();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl SocketRelease {
+    /// Wraps `socket` into a socket closed by [`SocketRelease::release`]. The socket is closed right away if it was already released.
+    pub(crate) fn wrap(
+        &self,
+        socket: Arc<dyn AsyncUdpSocket>,
+    ) -> io::Result<Arc<dyn AsyncUdpSocket>> {
+        let releasable = ReleasableSocket {
+            release: self.0.clone(),
+            local_addr: socket.local_addr()?,
+            max_transmit_segments: socket.max_transmit_segments(),
+            max_receive_segments: socket.max_receive_segments(),
+            may_fragment: socket.may_fragment(),
+        };
+        let mut status = self.0.status();
+        if *status == SocketStatus::Pending {
+            *self
+                .0
+                .socket
+                .write()
+                .unwrap_or_else(PoisonError::into_inner) = Some(socket);
+            *status = SocketStatus::InUse;
+        }
+        Ok(Arc::new(releasable))
+    }
+
+    /// Waits until `deadline` for quinn to drop the socket, letting the closing connections reach their peers, then closes it anyway.
+    ///
+    /// Once released, the socket drops the packets sent and receives nothing.
+    pub(crate) fn release(&self, deadline: Instant) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let (mut status, _) = self
+            .0
+            .dropped
+            .wait_timeout_while(self.0.status(), timeout, |status| {
+                *status != SocketStatus::Released
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+        *status = SocketStatus::Released;
+        drop(status);
+        self.0.close();
+    }
+}
+
+/// Socket handed to quinn, closed by its [`SocketRelease`]
+#[cfg(feature = "server")]
+#[derive(Debug)]
+struct ReleasableSocket {
+    release: Arc<ReleaseState>,
+    local_addr: SocketAddr,
+    max_transmit_segments: usize,
+    max_receive_segments: usize,
+    may_fragment: bool,
+}
+
+#[cfg(feature = "server")]
+impl Drop for ReleasableSocket {
+    fn drop(&mut self) {
+        *self.release.status() = SocketStatus::Released;
+        self.release.close();
+        self.release.dropped.notify_all();
+    }
+}
+
+#[cfg(feature = "server")]
+impl AsyncUdpSocket for ReleasableSocket {
+    fn create_io_poller(self: Arc<Self>) -> Pin<Box<dyn UdpPoller>> {
+        // Locked first: a release either empties this poller or has already dropped the socket
+        let mut pollers = self.release.pollers();
+        let inner = self
+            .release
+            .socket()
+            .clone()
+            .map(|socket| socket.create_io_poller());
+        let poller = Arc::new(Mutex::new(inner));
+        pollers.retain(|poller| poller.strong_count() > 0);
+        pollers.push(Arc::downgrade(&poller));
+        Box::pin(ReleasablePoller(poller))
+    }
+
+    fn try_send(&self, transmit: &Transmit) -> io::Result<()> {
+        match &*self.release.socket() {
+            Some(socket) => socket.try_send(transmit),
+            None => Ok(()),
+        }
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [io::IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        match &*self.release.socket() {
+            Some(socket) => socket.poll_recv(cx, bufs, meta),
+            None => Poll::Pending,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn max_transmit_segments(&self) -> usize {
+        self.max_transmit_segments
+    }
+
+    fn max_receive_segments(&self) -> usize {
+        self.max_receive_segments
+    }
+
+    fn may_fragment(&self) -> bool {
+        self.may_fragment
+    }
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug)]
+struct ReleasablePoller(Arc<PollerSlot>);
+
+#[cfg(feature = "server")]
+impl UdpPoller for ReleasablePoller {
+    fn poll_writable(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        match self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            Some(poller) => poller.as_mut().poll_writable(cx),
+            // The packets sent on a released socket are dropped
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// Message of the snapshot channel of a spectator stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum SpectatorMessage {
+    /// Server to spectator: next part of the state snapshot
+    Part(Vec<u8>),
+    /// Server to spectator: the snapshot is complete, the live updates follow once acknowledged
+    End,
+    /// Spectator to server: the snapshot was received
+    Received,
+}
@@ -0,0 +1,132 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use quinn_proto::ConnectionStats;
+
+/// Default period between two samples of a [`StatsHistory`]
+pub const DEFAULT_STATS_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+/// Default number of samples kept by a [`StatsHistory`], 30 seconds with the [`DEFAULT_STATS_SAMPLE_INTERVAL`]
+pub const DEFAULT_STATS_HISTORY_CAPACITY: usize = 120;
+
+/// Sampling of a [`StatsHistory`]: the history covers `sample_interval * capacity`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatsHistoryConfig {
+    /// Minimum period between two samples. Samples are taken during the sync updates of the connections, at most once per frame.
+    pub sample_interval: Duration,
+    /// Number of samples kept, the oldest sample is dropped when a new one is taken
+    pub capacity: usize,
+}
+
+impl Default for StatsHistoryConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval: DEFAULT_STATS_SAMPLE_INTERVAL,
+            capacity: DEFAULT_STATS_HISTORY_CAPACITY,
+        }
+    }
+}
+
+/// Stats of a connection over one sample interval of a [`StatsHistory`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsSample {
+    /// Instant the sample was taken
+    pub at: Instant,
+    /// Round-trip time estimated by the connection
+    pub rtt: Duration,
+    /// UDP traffic sent since the previous sample, in kilobits per second
+    pub sent_kbps: f64,
+    /// UDP traffic received since the previous sample, in kilobits per second
+    pub received_kbps: f64,
+    /// Ratio of the packets sent since the previous sample that were lost, between 0 and 1
+    pub loss: f64,
+}
+
+/// Ring buffer of the sampled stats of a connection, to render graphs of the last seconds, see [`StatsHistoryConfig`].
+///
+/// Samples are computed from the [`ConnectionStats`] of the connection: custom transports without statistics only record zeroed samples.
+#[derive(Debug, Clone)]
+pub struct StatsHistory {
+    config: StatsHistoryConfig,
+    /// Oldest first
+    samples: VecDeque<StatsSample>,
+    /// Instant and totals of the previous sample, the first sample of the history only sets them
+    last: Option<(Instant, ConnectionStats)>,
+}
+
+impl StatsHistory {
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn new(config: StatsHistoryConfig) -> Self {
+        Self {
+            config,
+            samples: VecDeque::with_capacity(config.capacity),
+            last: None,
+        }
+    }
+
+    /// Configuration of the history
+    pub fn config(&self) -> StatsHistoryConfig {
+        self.config
+    }
+
+    /// Samples of the history, oldest first
+    pub fn samples(&self) -> impl DoubleEndedIterator<Item = &StatsSample> + ExactSizeIterator {
+        self.samples.iter()
+    }
+
+    /// Most recent sample, if any
+    pub fn latest(&self) -> Option<&StatsSample> {
+        self.samples.back()
+    }
+
+    /// Samples taken during the last `window` before `now`, oldest first
+    pub fn window(&self, window: Duration, now: Instant) -> impl Iterator<Item = &StatsSample> {
+        self.samples
+            .iter()
+            .filter(move |sample| now.saturating_duration_since(sample.at) <= window)
+    }
+
+    /// Removes all the samples. The next sample only sets the totals the following samples are computed from.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.last = None;
+    }
+
+    /// Takes a sample if the sample interval elapsed since the previous one
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn sample(&mut self, now: Instant, stats: ConnectionStats) {
+        let Some((last_at, last_stats)) = &self.last else {
+            self.last = Some((now, stats));
+            return;
+        };
+        let elapsed = now.saturating_duration_since(*last_at);
+        if elapsed < self.config.sample_interval || elapsed.is_zero() || self.config.capacity == 0 {
+            return;
+        }
+        let kbps = |bytes: u64| (bytes * 8) as f64 / 1_000. / elapsed.as_secs_f64();
+        let sent_packets = stats
+            .path
+            .sent_packets
+            .saturating_sub(last_stats.path.sent_packets);
+        let lost_packets = stats
+            .path
+            .lost_packets
+            .saturating_sub(last_stats.path.lost_packets);
+        let sample = StatsSample {
+            at: now,
+            rtt: stats.path.rtt,
+            sent_kbps: kbps(stats.udp_tx.bytes.saturating_sub(last_stats.udp_tx.bytes)),
+            received_kbps: kbps(stats.udp_rx.bytes.saturating_sub(last_stats.udp_rx.bytes)),
+            loss: match sent_packets {
+                0 => 0.,
+                _ => (lost_packets as f64 / sent_packets as f64).min(1.),
+            },
+        };
+        if self.samples.len() >= self.config.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        self.last = Some((now, stats));
+    }
+}
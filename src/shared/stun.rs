@@ -0,0 +1,157 @@
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use ring::rand::{SecureRandom, SystemRandom};
+
+use super::error::StunError;
+
+/// Default time to wait for each response of a STUN server
+pub const DEFAULT_STUN_TIMEOUT: Duration = Duration::from_secs(1);
+/// Default number of requests sent to a STUN server before giving up
+pub const DEFAULT_STUN_ATTEMPTS: usize = 3;
+
+const MAGIC_COOKIE: u32 = 0x2112A442;
+const HEADER_LEN: usize = 20;
+const TRANSACTION_ID_LEN: usize = 12;
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS_RESPONSE: u16 = 0x0101;
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const FAMILY_IPV4: u8 = 0x01;
+const FAMILY_IPV6: u8 = 0x02;
+const MAX_RESPONSE_LEN: usize = 512;
+
+/// Queries a STUN server (RFC 5389 Binding request) from `socket` and returns the address of the socket as seen by the STUN server.
+///
+/// When the socket is behind a NAT, this is the external (public) address that peers can use to reach it. The mapping is specific to the socket, the query must be done on the socket that will later be used for the QUIC traffic.
+///
+/// This call is blocking: up to `attempts` requests are sent, each waiting up to `timeout` for a response. The read timeout of the socket is restored before returning.
+pub fn query_external_address(
+    socket: &UdpSocket,
+    stun_server: SocketAddr,
+    timeout: Duration,
+    attempts: usize,
+) -> Result<SocketAddr, StunError> {
+    let mut transaction_id = [0; TRANSACTION_ID_LEN];
+    SystemRandom::new()
+        .fill(&mut transaction_id)
+        .map_err(|_| StunError::RandomGenerationFailed)?;
+    let request = binding_request(&transaction_id);
+
+    let previous_timeout = socket.read_timeout()?;
+    socket.set_read_timeout(Some(timeout))?;
+    let result = (|| {
+        let mut buf = [0; MAX_RESPONSE_LEN];
+        for _ in 0..attempts {
+            socket.send_to(&request, stun_server)?;
+            loop {
+                let (len, from) = match socket.recv_from(&mut buf) {
+                    Ok(received) => received,
+                    Err(err)
+                        if err.kind() == ErrorKind::WouldBlock
+                            || err.kind() == ErrorKind::TimedOut =>
+                    {
+                        break
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                if from != stun_server {
+                    continue;
+                }
+                if let Some(addr) = parse_binding_response(&buf[..len], &transaction_id)? {
+                    return Ok(addr);
+                }
+            }
+        }
+        Err(StunError::Timeout)
+    })();
+    socket.set_read_timeout(previous_timeout)?;
+    result
+}
+
+fn binding_request(transaction_id: &[u8; TRANSACTION_ID_LEN]) -> [u8; HEADER_LEN] {
+    let mut request = [0; HEADER_LEN];
+    request[0..2].copy_from_slice(&BINDING_REQUEST.to_be_bytes());
+    // Message length is 0: no attributes
+    request[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request[8..HEADER_LEN].copy_from_slice(transaction_id);
+    request
+}
+
+/// Returns `Ok(None)` if the message is not a response to our transaction
+fn parse_binding_response(
+    message: &[u8],
+    transaction_id: &[u8; TRANSACTION_ID_LEN],
+) -> Result<Option<SocketAddr>, StunError> {
+    if message.len() < HEADER_LEN
+        || message[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || message[8..HEADER_LEN] != transaction_id[..]
+    {
+        return Ok(None);
+    }
+    if u16::from_be_bytes([message[0], message[1]]) != BINDING_SUCCESS_RESPONSE {
+        return Err(StunError::InvalidResponse);
+    }
+    let attributes_len = u16::from_be_bytes([message[2], message[3]]) as usize;
+    let attributes = message
+        .get(HEADER_LEN..HEADER_LEN + attributes_len)
+        .ok_or(StunError::InvalidResponse)?;
+
+    let mut mapped_address = None;
+    let mut offset = 0;
+    while offset + 4 <= attributes.len() {
+        let attr_type = u16::from_be_bytes([attributes[offset], attributes[offset + 1]]);
+        let attr_len =
+            u16::from_be_bytes([attributes[offset + 2], attributes[offset + 3]]) as usize;
+        let value = attributes
+            .get(offset + 4..offset + 4 + attr_len)
+            .ok_or(StunError::InvalidResponse)?;
+        match attr_type {
+            ATTR_XOR_MAPPED_ADDRESS => {
+                return parse_address(value, Some(transaction_id)).map(Some);
+            }
+            ATTR_MAPPED_ADDRESS => mapped_address = Some(parse_address(value, None)?),
+            _ => (),
+        }
+        // Attributes are padded to a multiple of 4 bytes
+        offset += 4 + attr_len.div_ceil(4) * 4;
+    }
+    mapped_address.map(Some).ok_or(StunError::InvalidResponse)
+}
+
+fn parse_address(
+    value: &[u8],
+    xor_transaction_id: Option<&[u8; TRANSACTION_ID_LEN]>,
+) -> Result<SocketAddr, StunError> {
+    if value.len() < 4 {
+        return Err(StunError::InvalidResponse);
+    }
+    let mut port = u16::from_be_bytes([value[2], value[3]]);
+    let mut xor_mask = [0; 16];
+    if let Some(transaction_id) = xor_transaction_id {
+        port ^= (MAGIC_COOKIE >> 16) as u16;
+        xor_mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        xor_mask[4..].copy_from_slice(transaction_id);
+    }
+    let ip = match (value[1], value.len()) {
+        (FAMILY_IPV4, 8) => {
+            let mut octets = [0; 4];
+            for (i, octet) in octets.iter_mut().enumerate() {
+                *octet = value[4 + i] ^ xor_mask[i];
+            }
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        (FAMILY_IPV6, 20) => {
+            let mut octets = [0; 16];
+            for (i, octet) in octets.iter_mut().enumerate() {
+                *octet = value[4 + i] ^ xor_mask[i];
+            }
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return Err(StunError::InvalidResponse),
+    };
+    Ok(SocketAddr::new(ip, port))
+}
@@ -0,0 +1,67 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+#[cfg(any(feature = "client", feature = "server"))]
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+/// Tick of the server's fixed simulation, shared with its clients.
+///
+/// On the server, driven by the [`crate::server::tick::QuinnetServerTickPlugin`] or set manually with [`crate::server::Endpoint::set_network_tick`]. On the client, the most recent tick read from the payloads of the ticked channels of the default connection, updated by the [`crate::client::QuinnetClientPlugin`], see [`crate::client::connection::ClientSideConnection::server_tick`].
+#[cfg_attr(any(feature = "client", feature = "server"), derive(Resource))]
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct NetworkTick(pub u32);
+
+impl NetworkTick {
+    /// The tick following this one
+    pub fn next(self) -> Self {
+        Self(self.0.wrapping_add(1))
+    }
+
+    /// Number of ticks elapsed since `earlier`
+    pub fn since(self, earlier: NetworkTick) -> u32 {
+        self.0.wrapping_sub(earlier.0)
+    }
+}
+
+/// Stored by a [`SharedNetworkTick`] without a tick
+const NO_TICK: u64 = u64::MAX;
+
+/// Network tick shared by the sync side of a connection and its async tasks: the tick of the server stamped on the payloads of its ticked channels, or the last tick read from them by a client, see [`crate::shared::channels::ChannelConfig::ticked`]
+#[derive(Debug, Clone)]
+pub(crate) struct SharedNetworkTick(Arc<AtomicU64>);
+
+impl Default for SharedNetworkTick {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU64::new(NO_TICK)))
+    }
+}
+
+impl SharedNetworkTick {
+    pub(crate) fn get(&self) -> Option<NetworkTick> {
+        match self.0.load(Ordering::Relaxed) {
+            NO_TICK => None,
+            tick => Some(NetworkTick(tick as u32)),
+        }
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn set(&self, tick: NetworkTick) {
+        self.0.store(tick.0 as u64, Ordering::Relaxed);
+    }
+
+    /// Keeps the most recent of the current tick and `tick`: the payloads of different channels, or of an unreliable channel, may be received out of order
+    pub(crate) fn update(&self, tick: NetworkTick) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                let newer = current == NO_TICK
+                    || tick.since(NetworkTick(current as u32)).wrapping_sub(1) < u32::MAX / 2;
+                newer.then_some(tick.0 as u64)
+            });
+    }
+}
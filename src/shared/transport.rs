@@ -0,0 +1,252 @@
+use std::{error::Error, fmt::Debug, future::Future, net::SocketAddr};
+
+use bytes::Bytes;
+use quinn::{SendDatagramError, VarInt};
+use quinn_proto::{ConnectionStats, Side};
+use rustls::pki_types::CertificateDer;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::close::CloseCode;
+
+/// In-memory transport, to connect a client and a server running in the same process without any socket
+pub mod memory;
+
+/// Error raised by a [`TransportConnection`]
+#[derive(thiserror::Error, Debug)]
+pub enum TransportError {
+    /// The connection was lost or closed
+    #[error("Connection lost: {0}")]
+    ConnectionLost(Box<dyn Error + Send + Sync>),
+    /// A datagram could not be sent, the connection itself is still usable
+    #[error("Datagram not sent: {0}")]
+    DatagramNotSent(Box<dyn Error + Send + Sync>),
+    /// The operation is not supported by the transport
+    #[error("Operation not supported by the transport")]
+    Unsupported,
+}
+
+/// A connection of the underlying transport, used by the channels tasks to carry the channels messages.
+///
+/// Quinnet channels need two primitives from a transport:
+/// - reliable & ordered unidirectional streams, for the reliable channels. A transport without native streams (Steam networking sockets, EOS P2P, ...) can emulate them with a reliable message lane per stream.
+/// - unreliable datagrams, for the unreliable channels.
+///
+/// Implemented for [`quinn::Connection`] and [`memory::MemoryConnection`].
+pub trait TransportConnection: Clone + Debug + Send + Sync + 'static {
+    /// Sending half of a unidirectional stream. Shutting it down finishes the stream.
+    type SendStream: AsyncWrite + Unpin + Send + 'static;
+    /// Receiving half of a unidirectional stream
+    type RecvStream: AsyncRead + Unpin + Send + 'static;
+
+    /// Which side of the connection we are on
+    fn side(&self) -> Side;
+
+    /// Opens a new unidirectional stream
+    fn open_uni(&self) -> impl Future<Output = Result<Self::SendStream, TransportError>> + Send;
+
+    /// Accepts the next unidirectional stream opened by the peer
+    fn accept_uni(&self) -> impl Future<Output = Result<Self::RecvStream, TransportError>> + Send;
+
+    /// Opens a new bidirectional stream. Only its sending half is used.
+    fn open_bi(&self) -> impl Future<Output = Result<Self::SendStream, TransportError>> + Send;
+
+    /// Accepts the next bidirectional stream opened by the peer. Only its receiving half is used.
+    fn accept_bi(&self) -> impl Future<Output = Result<Self::RecvStream, TransportError>> + Send;
+
+    /// Sends an unreliable datagram
+    fn send_datagram(&self, datagram: Bytes) -> Result<(), TransportError>;
+
+    /// Receives the next unreliable datagram sent by the peer
+    fn read_datagram(&self) -> impl Future<Output = Result<Bytes, TransportError>> + Send;
+
+    /// Derives keying material from the security context of the connection (RFC 5705), used by [`crate::shared::channels::ChannelEncryption::TlsExporter`].
+    ///
+    /// Transports without such a context should return [`TransportError::Unsupported`].
+    fn export_keying_material(
+        &self,
+        output: &mut [u8],
+        label: &[u8],
+        context: &[u8],
+    ) -> Result<(), TransportError>;
+
+    /// Closes the connection immediately, sending `code` to the peer
+    fn close(&self, code: CloseCode);
+
+    /// Waits for the connection to be closed, by either side, and returns the reason
+    fn closed(&self) -> impl Future<Output = TransportError> + Send;
+
+    /// Address of the peer, if the transport has one
+    fn remote_address(&self) -> Option<SocketAddr>;
+
+    /// Maximum size of the datagrams that can be sent, `None` if datagrams are unsupported
+    fn max_datagram_size(&self) -> Option<usize>;
+
+    /// Server name (SNI) requested by the client during the handshake, if the transport has one
+    fn server_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Statistics about the connection. Transports without such statistics return zeroed stats.
+    fn stats(&self) -> ConnectionStats {
+        ConnectionStats::default()
+    }
+
+    /// Certificate chain presented by the peer during the handshake, if the transport has one
+    fn peer_certificates(&self) -> Option<Vec<CertificateDer<'static>>> {
+        None
+    }
+}
+
+/// Object safe view of a [`TransportConnection`], kept by the sync client & server to query the connection
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) trait TransportInfo: Debug + Send + Sync {
+    #[cfg(feature = "server")]
+    fn remote_address(&self) -> Option<SocketAddr>;
+    fn max_datagram_size(&self) -> Option<usize>;
+    #[cfg(feature = "server")]
+    fn server_name(&self) -> Option<String>;
+    fn stats(&self) -> ConnectionStats;
+    #[cfg(feature = "server")]
+    fn peer_certificates(&self) -> Option<Vec<CertificateDer<'static>>>;
+    /// The underlying QUIC connection, `None` for custom transports
+    #[cfg(feature = "raw")]
+    fn quic_connection(&self) -> Option<quinn::Connection>;
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl<C: TransportConnection> TransportInfo for C {
+    #[cfg(feature = "server")]
+    fn remote_address(&self) -> Option<SocketAddr> {
+        TransportConnection::remote_address(self)
+    }
+
+    fn max_datagram_size(&self) -> Option<usize> {
+        TransportConnection::max_datagram_size(self)
+    }
+
+    #[cfg(feature = "server")]
+    fn server_name(&self) -> Option<String> {
+        TransportConnection::server_name(self)
+    }
+
+    fn stats(&self) -> ConnectionStats {
+        TransportConnection::stats(self)
+    }
+
+    #[cfg(feature = "server")]
+    fn peer_certificates(&self) -> Option<Vec<CertificateDer<'static>>> {
+        TransportConnection::peer_certificates(self)
+    }
+
+    #[cfg(feature = "raw")]
+    fn quic_connection(&self) -> Option<quinn::Connection> {
+        (self as &dyn std::any::Any)
+            .downcast_ref::<quinn::Connection>()
+            .cloned()
+    }
+}
+
+/// Formats the remote address of a connection for logs
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) fn display_remote<C: TransportConnection>(connection: &C) -> String {
+    match connection.remote_address() {
+        Some(addr) => addr.to_string(),
+        None => "custom transport".to_string(),
+    }
+}
+
+impl TransportConnection for quinn::Connection {
+    type SendStream = quinn::SendStream;
+    type RecvStream = quinn::RecvStream;
+
+    fn side(&self) -> Side {
+        quinn::Connection::side(self)
+    }
+
+    async fn open_uni(&self) -> Result<Self::SendStream, TransportError> {
+        quinn::Connection::open_uni(self)
+            .await
+            .map_err(|err| TransportError::ConnectionLost(err.into()))
+    }
+
+    async fn accept_uni(&self) -> Result<Self::RecvStream, TransportError> {
+        quinn::Connection::accept_uni(self)
+            .await
+            .map_err(|err| TransportError::ConnectionLost(err.into()))
+    }
+
+    async fn open_bi(&self) -> Result<Self::SendStream, TransportError> {
+        quinn::Connection::open_bi(self)
+            .await
+            .map(|(send, _)| send)
+            .map_err(|err| TransportError::ConnectionLost(err.into()))
+    }
+
+    async fn accept_bi(&self) -> Result<Self::RecvStream, TransportError> {
+        quinn::Connection::accept_bi(self)
+            .await
+            .map(|(_, recv)| recv)
+            .map_err(|err| TransportError::ConnectionLost(err.into()))
+    }
+
+    fn send_datagram(&self, datagram: Bytes) -> Result<(), TransportError> {
+        quinn::Connection::send_datagram(self, datagram).map_err(|err| match err {
+            SendDatagramError::ConnectionLost(err) => TransportError::ConnectionLost(err.into()),
+            err => TransportError::DatagramNotSent(err.into()),
+        })
+    }
+
+    async fn read_datagram(&self) -> Result<Bytes, TransportError> {
+        quinn::Connection::read_datagram(self)
+            .await
+            .map_err(|err| TransportError::ConnectionLost(err.into()))
+    }
+
+    fn export_keying_material(
+        &self,
+        output: &mut [u8],
+        label: &[u8],
+        context: &[u8],
+    ) -> Result<(), TransportError> {
+        quinn::Connection::export_keying_material(self, output, label, context)
+            .map_err(|_| TransportError::Unsupported)
+    }
+
+    fn close(&self, code: CloseCode) {
+        quinn::Connection::close(
+            self,
+            VarInt::from_u64(code.code()).unwrap_or(VarInt::MAX),
+            &code.reason(),
+        );
+    }
+
+    async fn closed(&self) -> TransportError {
+        TransportError::ConnectionLost(quinn::Connection::closed(self).await.into())
+    }
+
+    fn remote_address(&self) -> Option<SocketAddr> {
+        Some(quinn::Connection::remote_address(self))
+    }
+
+    fn max_datagram_size(&self) -> Option<usize> {
+        quinn::Connection::max_datagram_size(self)
+    }
+
+    fn server_name(&self) -> Option<String> {
+        quinn::Connection::handshake_data(self)?
+            .downcast::<quinn::crypto::rustls::HandshakeData>()
+            .ok()?
+            .server_name
+    }
+
+    fn stats(&self) -> ConnectionStats {
+        quinn::Connection::stats(self)
+    }
+
+    fn peer_certificates(&self) -> Option<Vec<CertificateDer<'static>>> {
+        quinn::Connection::peer_identity(self)?
+            .downcast::<Vec<CertificateDer<'static>>>()
+            .ok()
+            .map(|certificates| *certificates)
+    }
+}
@@ -0,0 +1,220 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use bytes::Bytes;
+use quinn_proto::Side;
+use tokio::{
+    io::{duplex, DuplexStream},
+    sync::{mpsc, watch, Mutex},
+};
+
+use super::{TransportConnection, TransportError};
+use crate::shared::close::CloseCode;
+
+/// Size of the buffer of each in-memory stream
+pub const MEMORY_STREAM_BUFFER_SIZE: usize = 64 * 1024;
+/// Maximum size of the datagrams sent on a [`MemoryConnection`]
+pub const MEMORY_MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+const STREAMS_QUEUE_SIZE: usize = 256;
+const DATAGRAMS_QUEUE_SIZE: usize = 1024;
+
+/// Error raised by a [`MemoryConnection`]
+#[derive(thiserror::Error, Debug)]
+pub enum MemoryTransportError {
+    /// The connection was closed by either side
+    #[error("The in-memory connection is closed")]
+    Closed,
+    /// The connection was closed by either side with an application close code
+    #[error("The in-memory connection was closed: {0}")]
+    ApplicationClosed(CloseCode),
+    /// The datagram is larger than [`MEMORY_MAX_DATAGRAM_SIZE`]
+    #[error("The datagram is larger than the maximum datagram size")]
+    DatagramTooLarge,
+    /// The peer did not read its datagrams fast enough, the datagram was dropped
+    #[error("The datagrams queue of the peer is full")]
+    DatagramsQueueFull,
+}
+
+#[derive(Debug)]
+struct OutgoingLanes {
+    uni_streams: mpsc::Sender<DuplexStream>,
+    bi_streams: mpsc::Sender<DuplexStream>,
+    datagrams: mpsc::Sender<Bytes>,
+}
+
+#[derive(Debug)]
+struct IncomingLanes {
+    uni_streams: Mutex<mpsc::Receiver<DuplexStream>>,
+    bi_streams: Mutex<mpsc::Receiver<DuplexStream>>,
+    datagrams: Mutex<mpsc::Receiver<Bytes>>,
+}
+
+fn lanes() -> (OutgoingLanes, IncomingLanes) {
+    let (uni_send, uni_recv) = mpsc::channel(STREAMS_QUEUE_SIZE);
+    let (bi_send, bi_recv) = mpsc::channel(STREAMS_QUEUE_SIZE);
+    let (datagrams_send, datagrams_recv) = mpsc::channel(DATAGRAMS_QUEUE_SIZE);
+    (
+        OutgoingLanes {
+            uni_streams: uni_send,
+            bi_streams: bi_send,
+            datagrams: datagrams_send,
+        },
+        IncomingLanes {
+            uni_streams: Mutex::new(uni_recv),
+            bi_streams: Mutex::new(bi_recv),
+            datagrams: Mutex::new(datagrams_recv),
+        },
+    )
+}
+
+fn closed_error() -> TransportError {
+    TransportError::ConnectionLost(MemoryTransportError::Closed.into())
+}
+
+/// One end of an in-memory connection, created with [`MemoryConnection::pair`].
+///
+/// Lets a client run in the same process as the server (e.g. the host of a listen server) without going through a socket. The client end is opened with [`crate::client::QuinnetClient::open_transport_connection`] and the server end is added with [`crate::server::Endpoint::add_transport_connection`].
+#[derive(Debug, Clone)]
+pub struct MemoryConnection {
+    side: Side,
+    outgoing: Arc<OutgoingLanes>,
+    incoming: Arc<IncomingLanes>,
+    closed: Arc<watch::Sender<Option<CloseCode>>>,
+}
+
+impl MemoryConnection {
+    /// Creates the two ends of an in-memory connection, as `(client, server)`
+    pub fn pair() -> (MemoryConnection, MemoryConnection) {
+        let (to_server, from_client) = lanes();
+        let (to_client, from_server) = lanes();
+        let closed = Arc::new(watch::Sender::new(None));
+        (
+            MemoryConnection {
+                side: Side::Client,
+                outgoing: Arc::new(to_server),
+                incoming: Arc::new(from_server),
+                closed: closed.clone(),
+            },
+            MemoryConnection {
+                side: Side::Server,
+                outgoing: Arc::new(to_client),
+                incoming: Arc::new(from_client),
+                closed,
+            },
+        )
+    }
+
+    /// Returns whether the connection was closed by either side
+    pub fn is_closed(&self) -> bool {
+        self.closed.borrow().is_some()
+    }
+
+    async fn open_stream(
+        &self,
+        lane: &mpsc::Sender<DuplexStream>,
+    ) -> Result<DuplexStream, TransportError> {
+        if self.is_closed() {
+            return Err(closed_error());
+        }
+        let (local, remote) = duplex(MEMORY_STREAM_BUFFER_SIZE);
+        lane.send(remote).await.map_err(|_| closed_error())?;
+        Ok(local)
+    }
+
+    async fn receive<T>(&self, lane: &Mutex<mpsc::Receiver<T>>) -> Result<T, TransportError> {
+        let mut closed = self.closed.subscribe();
+        let mut lane = lane.lock().await;
+        tokio::select! {
+            biased;
+            _ = closed.wait_for(Option::is_some) => Err(closed_error()),
+            item = lane.recv() => item.ok_or_else(closed_error),
+        }
+    }
+}
+
+impl TransportConnection for MemoryConnection {
+    type SendStream = DuplexStream;
+    type RecvStream = DuplexStream;
+
+    fn side(&self) -> Side {
+        self.side
+    }
+
+    async fn open_uni(&self) -> Result<Self::SendStream, TransportError> {
+        self.open_stream(&self.outgoing.uni_streams).await
+    }
+
+    async fn accept_uni(&self) -> Result<Self::RecvStream, TransportError> {
+        self.receive(&self.incoming.uni_streams).await
+    }
+
+    async fn open_bi(&self) -> Result<Self::SendStream, TransportError> {
+        self.open_stream(&self.outgoing.bi_streams).await
+    }
+
+    async fn accept_bi(&self) -> Result<Self::RecvStream, TransportError> {
+        self.receive(&self.incoming.bi_streams).await
+    }
+
+    fn send_datagram(&self, datagram: Bytes) -> Result<(), TransportError> {
+        if self.is_closed() {
+            return Err(closed_error());
+        }
+        if datagram.len() > MEMORY_MAX_DATAGRAM_SIZE {
+            return Err(TransportError::DatagramNotSent(
+                MemoryTransportError::DatagramTooLarge.into(),
+            ));
+        }
+        self.outgoing
+            .datagrams
+            .try_send(datagram)
+            .map_err(|err| match err {
+                mpsc::error::TrySendError::Full(_) => {
+                    TransportError::DatagramNotSent(MemoryTransportError::DatagramsQueueFull.into())
+                }
+                mpsc::error::TrySendError::Closed(_) => closed_error(),
+            })
+    }
+
+    async fn read_datagram(&self) -> Result<Bytes, TransportError> {
+        self.receive(&self.incoming.datagrams).await
+    }
+
+    fn export_keying_material(
+        &self,
+        _output: &mut [u8],
+        _label: &[u8],
+        _context: &[u8],
+    ) -> Result<(), TransportError> {
+        Err(TransportError::Unsupported)
+    }
+
+    fn close(&self, code: CloseCode) {
+        // Only the first close code is kept, as with a QUIC connection
+        self.closed.send_if_modified(|closed| match closed {
+            Some(_) => false,
+            None => {
+                *closed = Some(code);
+                true
+            }
+        });
+    }
+
+    async fn closed(&self) -> TransportError {
+        let mut closed = self.closed.subscribe();
+        match closed.wait_for(Option::is_some).await.map(|code| *code) {
+            Ok(Some(code)) => {
+                TransportError::ConnectionLost(MemoryTransportError::ApplicationClosed(code).into())
+            }
+            _ => closed_error(),
+        }
+    }
+
+    fn remote_address(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn max_datagram_size(&self) -> Option<usize> {
+        Some(MEMORY_MAX_DATAGRAM_SIZE)
+    }
+}
@@ -0,0 +1,316 @@
+use std::fmt;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use quinn_proto::Side;
+use tokio_util::codec::{Encoder, LengthDelimitedCodec};
+
+use super::{
+    channels::{
+        ack::write_ack_header,
+        compression::{CompressionDictionary, DictionaryCompressor},
+        control::{ControlMessage, CONTROL_CHANNEL_ID},
+        encryption::{ChannelCipher, SALT_LEN},
+        payload::PayloadEncoder,
+        redundancy::RedundantCopies,
+        reliable::{codec::QuinnetProtocolCodecEncoder, RELIABLE_FRAME_LENGTH_FIELD_LEN},
+        replay::NonceStamper,
+        tick::write_tick_header,
+        ChannelConfig, ChannelId, CHANNEL_ID_LEN,
+    },
+    tick::NetworkTick,
+    ClientId, CLIENT_ID_LEN,
+};
+
+/// Version of the wire format of the Quinnet connections, described in `docs/WireFormat.md`.
+///
+/// Bumped on any change of the framing, of the payload headers or of the control messages which would break a peer implementing the previous version. The [`wire_test_vectors`] are the reference encodings of this version.
+pub const WIRE_FORMAT_VERSION: u16 = 1;
+
+/// Frames a payload sent on a reliable channel: PAYLOAD LENGTH + 1 (u32, big endian) | CHANNEL ID | PAYLOAD
+pub fn encode_reliable_frame(channel_id: ChannelId, payload: &[u8]) -> Bytes {
+    let mut frame = BytesMut::new();
+    QuinnetProtocolCodecEncoder::new(channel_id, usize::MAX)
+        .encode(Bytes::copy_from_slice(payload), &mut frame)
+        .expect("frames without a max length should always be encoded");
+    frame.into()
+}
+
+/// Parses the first frame of the bytes read from the stream of a reliable channel. Returns its channel, its payload and the bytes following it, `None` if the frame is incomplete or invalid.
+pub fn decode_reliable_frame(bytes: &[u8]) -> Option<(ChannelId, &[u8], &[u8])> {
+    let (length, rest) = bytes.split_at_checked(RELIABLE_FRAME_LENGTH_FIELD_LEN)?;
+    let length = u32::from_be_bytes(length.try_into().ok()?) as usize;
+    if length < CHANNEL_ID_LEN {
+        return None;
+    }
+    let (frame, rest) = rest.split_at_checked(length)?;
+    Some((frame[0], &frame[CHANNEL_ID_LEN..], rest))
+}
+
+/// Frames a payload sent on an unreliable channel, as a datagram: CHANNEL ID | PAYLOAD
+pub fn encode_datagram(channel_id: ChannelId, payload: &[u8]) -> Bytes {
+    let mut datagram = BytesMut::with_capacity(CHANNEL_ID_LEN + payload.len());
+    datagram.put_u8(channel_id);
+    datagram.extend_from_slice(payload);
+    datagram.into()
+}
+
+/// Parses a datagram. Returns its channel and its payload, `None` if it has no payload
+pub fn decode_datagram(datagram: &[u8]) -> Option<(ChannelId, &[u8])> {
+    match datagram {
+        [channel_id, payload @ ..] if !payload.is_empty() => Some((*channel_id, payload)),
+        _ => None,
+    }
+}
+
+/// Frames the client id sent by the server on the first bidirectional stream of the connection: 8 (u32, big endian) | CLIENT ID (u64, big endian)
+pub fn encode_client_id(client_id: ClientId) -> Bytes {
+    let mut frame = BytesMut::new();
+    LengthDelimitedCodec::new()
+        .encode(Bytes::copy_from_slice(&client_id.to_be_bytes()), &mut frame)
+        .expect("a client id should always be encoded");
+    frame.into()
+}
+
+/// Parses the frame of the client id. `None` if the frame is incomplete or invalid
+pub fn decode_client_id(bytes: &[u8]) -> Option<ClientId> {
+    let (length, rest) = bytes.split_at_checked(4)?;
+    if u32::from_be_bytes(length.try_into().ok()?) as usize != CLIENT_ID_LEN {
+        return None;
+    }
+    Some(ClientId::from_be_bytes(
+        rest.get(..CLIENT_ID_LEN)?.try_into().ok()?,
+    ))
+}
+
+/// Reference encoding of the wire format, see [`wire_test_vectors`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireTestVector {
+    /// Name of the vector, dot-separated
+    pub name: &'static str,
+    /// Inputs of the encoding, as space-separated `key=value` pairs. Byte strings are in lowercase hexadecimal
+    pub input: String,
+    /// Encoded bytes
+    pub encoded: Bytes,
+}
+
+impl fmt::Display for WireTestVector {
+    /// One line: NAME, INPUT and ENCODED in lowercase hexadecimal, separated by tabs
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", self.name, self.input, hex(&self.encoded))
+    }
+}
+
+/// Reference encodings of the [`WIRE_FORMAT_VERSION`], produced by the encoders of the connections, for alternative implementations to check their own encoders and decoders against.
+///
+/// The vectors of the current version are also published in `docs/wire_format_vectors.tsv`, see [`format_wire_test_vectors`].
+pub fn wire_test_vectors() -> Vec<WireTestVector> {
+    const PAYLOAD: &[u8] = b"hello";
+    const PRE_SHARED_KEY: [u8; 32] = [0x42; 32];
+    const SALT: [u8; SALT_LEN] = [0x24; SALT_LEN];
+    let payload = Bytes::from_static(PAYLOAD);
+    let vector = |name, input: String, encoded: Bytes| WireTestVector {
+        name,
+        input,
+        encoded,
+    };
+    let control = |name, input: &str, message: ControlMessage| {
+        vector(name, input.to_string(), message.encode())
+    };
+    let sealed = |side: Side| {
+        ChannelCipher::pre_shared(&PRE_SHARED_KEY, SALT, 2, side)
+            .expect("the pre-shared key of the vectors should derive a key")
+            .seal(payload.clone())
+    };
+    let compressed = Bytes::from_static(b"hello hello hello hello hello hello");
+
+    let mut stacked_encoder = PayloadEncoder::new(
+        &ChannelConfig::unreliable()
+            .replay_protected()
+            .acknowledged()
+            .redundant(2),
+        ChannelCipher::pre_shared(&PRE_SHARED_KEY, SALT, 1, Side::Client).ok(),
+    );
+    let stacked = stacked_encoder
+        .encode(RedundantCopies::new(2).stamp(write_ack_header(None, payload.clone())));
+
+    vec![
+        vector(
+            "client_id",
+            "client_id=7".to_string(),
+            encode_client_id(7),
+        ),
+        vector(
+            "reliable_frame",
+            format!("channel_id=0 payload={}", hex(PAYLOAD)),
+            encode_reliable_frame(0, PAYLOAD),
+        ),
+        vector(
+            "reliable_frame.empty_payload",
+            "channel_id=4 payload=".to_string(),
+            encode_reliable_frame(4, &[]),
+        ),
+        vector(
+            "reliable_frame.control",
+            "channel_id=255 message=Acks([42])".to_string(),
+            encode_reliable_frame(CONTROL_CHANNEL_ID, &ControlMessage::Acks(vec![42]).encode()),
+        ),
+        vector(
+            "datagram",
+            format!("channel_id=3 payload={}", hex(PAYLOAD)),
+            encode_datagram(3, PAYLOAD),
+        ),
+        control(
+            "control.redirect",
+            "target_addr=127.0.0.1:6000 server_hostname=zone token=0102",
+            ControlMessage::Redirect {
+                target_addr: ([127, 0, 0, 1], 6000).into(),
+                server_hostname: "zone".to_string(),
+                token: vec![1, 2],
+            },
+        ),
+        control(
+            "control.present_token",
+            "token=0102",
+            ControlMessage::PresentToken(vec![1, 2]),
+        ),
+        control(
+            "control.forwarded_client",
+            "header=0102",
+            ControlMessage::ForwardedClient(vec![1, 2]),
+        ),
+        control("control.acks", "ids=1,2", ControlMessage::Acks(vec![1, 2])),
+        control(
+            "control.protocol_hash",
+            "hash=0x0123456789abcdef",
+            ControlMessage::ProtocolHash(0x0123456789abcdef),
+        ),
+        control(
+            "control.protocol_mismatch",
+            "server_hash=0x0123456789abcdef",
+            ControlMessage::ProtocolMismatch {
+                server_hash: 0x0123456789abcdef,
+            },
+        ),
+        control(
+            "control.channel_probe",
+            "channel_id=2 sequence=7",
+            ControlMessage::ChannelProbe {
+                channel_id: 2,
+                sequence: 7,
+            },
+        ),
+        control(
+            "control.channel_probe_ack",
+            "channel_id=2 sequence=7",
+            ControlMessage::ChannelProbeAck {
+                channel_id: 2,
+                sequence: 7,
+            },
+        ),
+        control(
+            "control.channel_opened",
+            "channel_id=2 dictionary_id=7",
+            ControlMessage::ChannelOpened {
+                channel_id: 2,
+                dictionary_id: Some(7),
+            },
+        ),
+        control(
+            "control.channel_opened.no_dictionary",
+            "channel_id=2",
+            ControlMessage::ChannelOpened {
+                channel_id: 2,
+                dictionary_id: None,
+            },
+        ),
+        vector(
+            "payload.ack.untracked",
+            format!("payload={}", hex(PAYLOAD)),
+            write_ack_header(None, payload.clone()),
+        ),
+        vector(
+            "payload.tick",
+            format!("tick=42 payload={}", hex(PAYLOAD)),
+            write_tick_header(Some(NetworkTick(42)), payload.clone()),
+        ),
+        vector(
+            "payload.tick.none",
+            format!("payload={}", hex(PAYLOAD)),
+            write_tick_header(None, payload.clone()),
+        ),
+        vector(
+            "payload.redundancy",
+            format!("sequence=0 payload={}", hex(PAYLOAD)),
+            RedundantCopies::new(2).stamp(payload.clone()),
+        ),
+        vector(
+            "payload.replay",
+            format!("nonce=0 payload={}", hex(PAYLOAD)),
+            NonceStamper::default().stamp(payload.clone()),
+        ),
+        vector(
+            "payload.compression",
+            format!("payload={}", hex(&compressed)),
+            lz4_flex::compress_prepend_size(&compressed).into(),
+        ),
+        vector(
+            "payload.compression.dictionary",
+            format!(
+                "dictionary={} payload={}",
+                hex(b"hello hello hello "),
+                hex(&compressed)
+            ),
+            DictionaryCompressor::new(
+                &CompressionDictionary::new(1, &b"hello hello hello "[..])
+                    .expect("A raw dictionary should be valid"),
+            )
+            .compress(&compressed),
+        ),
+        vector(
+            "payload.encryption.client",
+            format!(
+                "pre_shared_key={} salt={} channel_id=2 sender=client counter=0 payload={}",
+                hex(&PRE_SHARED_KEY),
+                hex(&SALT),
+                hex(PAYLOAD)
+            ),
+            sealed(Side::Client),
+        ),
+        vector(
+            "payload.encryption.server",
+            format!(
+                "pre_shared_key={} salt={} channel_id=2 sender=server counter=0 payload={}",
+                hex(&PRE_SHARED_KEY),
+                hex(&SALT),
+                hex(PAYLOAD)
+            ),
+            sealed(Side::Server),
+        ),
+        vector(
+            "datagram.stacked",
+            format!(
+                "channel_id=1 untracked sequence=0 nonce=0 pre_shared_key={} salt={} sender=client payload={}",
+                hex(&PRE_SHARED_KEY),
+                hex(&SALT),
+                hex(PAYLOAD)
+            ),
+            encode_datagram(1, &stacked),
+        ),
+    ]
+}
+
+/// Formats the vectors as the lines of `docs/wire_format_vectors.tsv`, after a header naming the [`WIRE_FORMAT_VERSION`]
+pub fn format_wire_test_vectors(vectors: &[WireTestVector]) -> String {
+    let mut formatted = format!(
+        "# Quinnet wire format version {}\n# name\tinput\tencoded\n",
+        WIRE_FORMAT_VERSION
+    );
+    for vector in vectors {
+        formatted.push_str(&format!("{}\n", vector));
+    }
+    formatted
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
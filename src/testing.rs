@@ -0,0 +1,283 @@
+//! Deterministic test utilities, driving the client connections and the server endpoints with scripted peers instead of sockets and async tasks.
+//!
+//! A [`ScriptedServer`] plays the server of a client connection opened with [`crate::client::QuinnetClient::open_scripted_connection`], a [`ScriptedClient`] plays a client of an endpoint, added with [`crate::server::Endpoint::add_scripted_client`]. Everything scripted on them (connections, losses, certificate interactions, messages) is raised by the next update of the client or of the server, and the messages sent by the app are read back synchronously. Tests of the netcode systems of a game then run the same way every time.
+//!
+//! [`relay`] links both sides of a connection, with network conditions drawn from a seed by [`LinkConditions`].
+//!
+//! ### Example
+//!
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_quinnet::{
+//!     client::{QuinnetClient, QuinnetClientEvent},
+//!     shared::channels::ChannelsConfiguration,
+//! };
+//!
+//! let mut world = World::new();
+//! let mut client = QuinnetClient::from_world(&mut world);
+//! let (connection_id, mut server) = client
+//!     .open_scripted_connection(ChannelsConfiguration::default())
+//!     .unwrap();
+//!
+//! server.connect(Some(7)).unwrap();
+//! server.send_payload(0, "hello").unwrap();
+//! assert!(matches!(
+//!     client.pump()[..],
+//!     [QuinnetClientEvent::Connection(_)]
+//! ));
+//! let connection = client.get_connection_mut_by_id(connection_id).unwrap();
+//! assert_eq!(connection.client_id(), Some(7));
+//! assert_eq!(connection.receive_payload().unwrap().unwrap().1, "hello");
+//!
+//! connection.send_payload("hi").unwrap();
+//! assert_eq!(server.sent_payloads(), vec![(0, "hi".into())]);
+//! ```
+
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
+
+use bytes::Bytes;
+use quinn_proto::ConnectionStats;
+use rustls::pki_types::CertificateDer;
+use tokio::sync::{
+    broadcast::{self, error::TryRecvError},
+    mpsc::{self, error::TrySendError},
+};
+
+use crate::shared::{
+    channels::{queue::OutgoingQueue, ChannelId, ChannelKind, ChannelSyncMessage, CloseReason},
+    close::CloseCode,
+    error::AsyncChannelError,
+    transport::TransportInfo,
+};
+
+#[cfg(feature = "client")]
+mod client;
+#[cfg(feature = "client")]
+pub use client::*;
+
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "server")]
+pub use server::*;
+
+/// Error while scripting a peer
+#[derive(thiserror::Error, Debug)]
+pub enum ScriptError {
+    /// The scripted client was not accepted by the endpoint yet, see [`ScriptedClient::client_id`]
+    #[error("The scripted client was not accepted by the endpoint yet")]
+    NotAccepted,
+    /// Quinnet async channel error
+    #[error("Quinnet async channel error")]
+    AsyncChannelError(#[from] AsyncChannelError),
+}
+
+/// Transport of the scripted connections: no address, no datagram size limit, no statistics
+#[derive(Debug)]
+pub(crate) struct ScriptedTransport;
+
+impl TransportInfo for ScriptedTransport {
+    fn remote_address(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn max_datagram_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn server_name(&self) -> Option<String> {
+        None
+    }
+
+    fn stats(&self) -> ConnectionStats {
+        ConnectionStats::default()
+    }
+
+    fn peer_certificates(&self) -> Option<Vec<CertificateDer<'static>>> {
+        None
+    }
+
+    #[cfg(feature = "raw")]
+    fn quic_connection(&self) -> Option<quinn::Connection> {
+        None
+    }
+}
+
+fn try_send<T>(sender: &mpsc::Sender<T>, message: T) -> Result<(), AsyncChannelError> {
+    sender.try_send(message).map_err(|err| match err {
+        TrySendError::Full(_) => AsyncChannelError::FullQueue,
+        TrySendError::Closed(_) => AsyncChannelError::InternalChannelClosed,
+    })
+}
+
+/// Outgoing channels of a scripted connection, read synchronously instead of by the send tasks
+#[derive(Debug)]
+struct ScriptedChannels {
+    to_channels_recv: mpsc::Receiver<ChannelSyncMessage>,
+    queues: BTreeMap<ChannelId, (ChannelKind, Arc<OutgoingQueue>)>,
+    close_recv: broadcast::Receiver<CloseReason>,
+    close_code: Option<CloseCode>,
+}
+
+impl ScriptedChannels {
+    fn new(
+        to_channels_recv: mpsc::Receiver<ChannelSyncMessage>,
+        close_recv: broadcast::Receiver<CloseReason>,
+    ) -> Self {
+        Self {
+            to_channels_recv,
+            queues: BTreeMap::new(),
+            close_recv,
+            close_code: None,
+        }
+    }
+
+    /// Payloads sent by the app, by channel id and then in sending order
+    fn take_sent(&mut self) -> Vec<(ChannelId, ChannelKind, Bytes)> {
+        let mut sent = Vec::new();
+        while let Ok(ChannelSyncMessage::CreateChannel {
+            id, config, queue, ..
+        }) = self.to_channels_recv.try_recv()
+        {
+            // A channel id re-used after a close: what was sent on the closed channel comes first
+            if let Some((kind, closed_queue)) = self.queues.insert(id, (config.kind(), queue)) {
+                while let Some(payload) = closed_queue.pop() {
+                    sent.push((id, kind, payload));
+                }
+            }
+        }
+        for (id, (kind, queue)) in &self.queues {
+            while let Some(payload) = queue.pop() {
+                sent.push((*id, *kind, payload));
+            }
+        }
+        sent
+    }
+
+    fn close_code(&mut self) -> Option<CloseCode> {
+        loop {
+            match self.close_recv.try_recv() {
+                Ok(CloseReason::LocalOrder(code)) => self.close_code = Some(code),
+                Ok(CloseReason::PeerClosed) | Err(TryRecvError::Lagged(_)) => (),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+            }
+        }
+        self.close_code
+    }
+}
+
+/// Shared with a scripted client connection, which hands the async ends of its reconnections to its [`ScriptedServer`]
+#[cfg(feature = "client")]
+pub(crate) type ScriptSlot =
+    Arc<std::sync::Mutex<Option<crate::client::connection::AsyncConnectionEnds>>>;
+
+/// Network conditions applied by [`relay`], drawn from a seed: the same seed and the same calls always give the same deliveries.
+///
+/// By default, the link delivers everything in the sending order.
+#[cfg(all(feature = "client", feature = "server"))]
+#[derive(Debug, Clone)]
+pub struct LinkConditions {
+    state: u64,
+    loss: f64,
+    reordering: bool,
+}
+
+#[cfg(all(feature = "client", feature = "server"))]
+impl LinkConditions {
+    /// Perfect link, `seed` is used once losses or reordering are enabled
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+            loss: 0.,
+            reordering: false,
+        }
+    }
+
+    /// Drops each payload of the [`ChannelKind::Unreliable`] channels with a probability of `loss`
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    /// Shuffles the payloads relayed together, the payloads of each [`ChannelKind::OrderedReliable`] channel staying in order
+    pub fn with_reordering(mut self) -> Self {
+        self.reordering = true;
+        self
+    }
+
+    /// SplitMix64
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn apply(&mut self, sent: Vec<(ChannelId, ChannelKind, Bytes)>) -> Vec<(ChannelId, Bytes)> {
+        let mut delivered: Vec<(ChannelId, ChannelKind, Bytes)> = Vec::with_capacity(sent.len());
+        for (channel_id, kind, payload) in sent {
+            if matches!(kind, ChannelKind::Unreliable)
+                && self.loss > 0.
+                && self.next_f64() < self.loss
+            {
+                continue;
+            }
+            delivered.push((channel_id, kind, payload));
+        }
+        if self.reordering {
+            let mut ordered: BTreeMap<ChannelId, Vec<Bytes>> = BTreeMap::new();
+            for (channel_id, kind, payload) in &delivered {
+                if matches!(kind, ChannelKind::OrderedReliable { .. }) {
+                    ordered
+                        .entry(*channel_id)
+                        .or_default()
+                        .push(payload.clone());
+                }
+            }
+            // Fisher-Yates
+            for i in (1..delivered.len()).rev() {
+                let j = (self.next_u64() % (i as u64 + 1)) as usize;
+                delivered.swap(i, j);
+            }
+            // Put back the ordered payloads in order, in the slots of their channel
+            let mut ordered = ordered
+                .into_iter()
+                .map(|(channel_id, payloads)| (channel_id, payloads.into_iter()))
+                .collect::<BTreeMap<_, _>>();
+            for (channel_id, kind, payload) in &mut delivered {
+                if matches!(kind, ChannelKind::OrderedReliable { .. }) {
+                    if let Some(next) = ordered.get_mut(channel_id).and_then(Iterator::next) {
+                        *payload = next;
+                    }
+                }
+            }
+        }
+        delivered
+            .into_iter()
+            .map(|(channel_id, _, payload)| (channel_id, payload))
+            .collect()
+    }
+}
+
+/// Relays the payloads sent by the app on each side of a scripted connection to the other side, through `conditions`.
+///
+/// `server` plays the server of a client connection and `client` plays a client of an endpoint: the payloads sent by the client connection are received by the endpoint from `client`, and the payloads sent by the endpoint to `client` are received by the client connection. The lifecycle of the connection stays scripted on each side.
+#[cfg(all(feature = "client", feature = "server"))]
+pub fn relay(
+    server: &mut ScriptedServer,
+    client: &mut ScriptedClient,
+    conditions: &mut LinkConditions,
+) -> Result<(), ScriptError> {
+    for (channel_id, payload) in conditions.apply(server.take_sent()) {
+        client.send_payload(channel_id, payload)?;
+    }
+    for (channel_id, payload) in conditions.apply(client.take_sent()) {
+        server.send_payload(channel_id, payload)?;
+    }
+    Ok(())
+}
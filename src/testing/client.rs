@@ -0,0 +1,202 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use bytes::Bytes;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    client::{
+        certificate::{CertVerificationInfo, CertVerificationStatus, CertVerifierAction},
+        connection::AsyncConnectionEnds,
+        ClientAsyncMessage, QuinnetConnectionError,
+    },
+    shared::{
+        channels::{
+            incoming::ReceivedPayload, ChannelAsyncMessage, ChannelId, ChannelKind,
+            CONTROL_CHANNEL_ID,
+        },
+        close::CloseCode,
+        error::AsyncChannelError,
+        ClientId,
+    },
+};
+
+use super::{try_send, ScriptSlot, ScriptedChannels, ScriptedTransport};
+
+/// Scripted server of a client connection, see [`crate::client::QuinnetClient::open_scripted_connection`].
+///
+/// When the app reconnects the connection with [`crate::client::connection::ClientSideConnection::reconnect`], the script drives the new connection attempt: the messages sent before are dropped, and the connection waits for [`ScriptedServer::connect`] again.
+#[derive(Debug)]
+pub struct ScriptedServer {
+    slot: ScriptSlot,
+    bytes_from_server_send: mpsc::Sender<ReceivedPayload>,
+    to_sync_client_send: mpsc::Sender<ClientAsyncMessage>,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    channels: ScriptedChannels,
+}
+
+impl ScriptedServer {
+    pub(crate) fn new(ends: AsyncConnectionEnds) -> Self {
+        Self {
+            slot: Arc::new(Mutex::new(None)),
+            bytes_from_server_send: ends.bytes_from_server_send.lane(),
+            to_sync_client_send: ends.to_sync_client_send,
+            from_channels_send: ends.from_channels_send,
+            channels: ScriptedChannels::new(ends.to_channels_recv, ends.close_recv),
+        }
+    }
+
+    pub(crate) fn slot(&self) -> ScriptSlot {
+        self.slot.clone()
+    }
+
+    /// Switches to the ends of the last reconnection, if any
+    fn refresh(&mut self) {
+        let ends = self
+            .slot
+            .lock()
+            .expect("Script lock should not be poisoned")
+            .take();
+        if let Some(ends) = ends {
+            *self = Self {
+                slot: self.slot.clone(),
+                ..Self::new(ends)
+            };
+        }
+    }
+
+    /// Connects the connection, with `client_id` as the [`ClientId`] sent by the server
+    pub fn connect(&mut self, client_id: Option<ClientId>) -> Result<(), AsyncChannelError> {
+        self.refresh();
+        try_send(
+            &self.to_sync_client_send,
+            ClientAsyncMessage::Connected(Arc::new(ScriptedTransport), client_id, None),
+        )
+    }
+
+    /// Fails the connection attempt with `err`
+    pub fn fail(&mut self, err: QuinnetConnectionError) -> Result<(), AsyncChannelError> {
+        self.refresh();
+        try_send(
+            &self.to_sync_client_send,
+            ClientAsyncMessage::ConnectionFailed(err),
+        )
+    }
+
+    /// Loses the connection, with the application close code sent by the server if any
+    pub fn lose(&mut self, close_code: Option<CloseCode>) -> Result<(), AsyncChannelError> {
+        self.refresh();
+        try_send(
+            &self.to_sync_client_send,
+            ClientAsyncMessage::ConnectionClosed(close_code),
+        )
+    }
+
+    /// Loses the connection as the channels tasks would, without any close code
+    pub fn lose_channels(&mut self) -> Result<(), AsyncChannelError> {
+        self.refresh();
+        try_send(
+            &self.from_channels_send,
+            ChannelAsyncMessage::LostConnection,
+        )
+    }
+
+    /// Raises a [`crate::client::certificate::CertInteractionEvent`], as the certificate verifier of a connection attempt does. The action applied by the app can then be read from the returned [`ScriptedCertInteraction`].
+    pub fn request_cert_interaction(
+        &mut self,
+        status: CertVerificationStatus,
+        info: CertVerificationInfo,
+    ) -> Result<ScriptedCertInteraction, AsyncChannelError> {
+        self.refresh();
+        let (action_sender, action_recv) = oneshot::channel();
+        try_send(
+            &self.to_sync_client_send,
+            ClientAsyncMessage::CertificateInteractionRequest {
+                status,
+                info,
+                action_sender,
+            },
+        )?;
+        Ok(ScriptedCertInteraction {
+            action_recv,
+            action: None,
+        })
+    }
+
+    /// Raises a [`crate::client::certificate::CertTrustUpdateEvent`]
+    pub fn update_cert_trust(
+        &mut self,
+        info: CertVerificationInfo,
+    ) -> Result<(), AsyncChannelError> {
+        self.refresh();
+        try_send(
+            &self.to_sync_client_send,
+            ClientAsyncMessage::CertificateTrustUpdate(info),
+        )
+    }
+
+    /// Raises a [`crate::client::certificate::CertConnectionAbortEvent`]
+    pub fn abort_on_cert(
+        &mut self,
+        status: CertVerificationStatus,
+        cert_info: CertVerificationInfo,
+    ) -> Result<(), AsyncChannelError> {
+        self.refresh();
+        try_send(
+            &self.to_sync_client_send,
+            ClientAsyncMessage::CertificateConnectionAbort { status, cert_info },
+        )
+    }
+
+    /// Sends a payload to the connection, on the channel `channel_id`
+    pub fn send_payload<T: Into<Bytes>>(
+        &mut self,
+        channel_id: ChannelId,
+        payload: T,
+    ) -> Result<(), AsyncChannelError> {
+        self.refresh();
+        try_send(
+            &self.bytes_from_server_send,
+            (channel_id, payload.into(), None, None, Instant::now()),
+        )
+    }
+
+    /// Takes the payloads sent by the connection since the last call, by channel id and then in sending order. The control payloads of Quinnet are left out.
+    pub fn sent_payloads(&mut self) -> Vec<(ChannelId, Bytes)> {
+        self.take_sent()
+            .into_iter()
+            .filter(|(channel_id, _, _)| *channel_id != CONTROL_CHANNEL_ID)
+            .map(|(channel_id, _, payload)| (channel_id, payload))
+            .collect()
+    }
+
+    pub(crate) fn take_sent(&mut self) -> Vec<(ChannelId, ChannelKind, Bytes)> {
+        self.refresh();
+        self.channels.take_sent()
+    }
+
+    /// Application close code of the connection, once the app closed it
+    pub fn close_code(&mut self) -> Option<CloseCode> {
+        self.refresh();
+        self.channels.close_code()
+    }
+}
+
+/// Certificate interaction requested by a [`ScriptedServer`]
+#[derive(Debug)]
+pub struct ScriptedCertInteraction {
+    action_recv: oneshot::Receiver<CertVerifierAction>,
+    action: Option<CertVerifierAction>,
+}
+
+impl ScriptedCertInteraction {
+    /// Action applied by the app on the [`crate::client::certificate::CertInteractionEvent`], if any yet
+    pub fn action(&mut self) -> Option<CertVerifierAction> {
+        if self.action.is_none() {
+            self.action = self.action_recv.try_recv().ok();
+        }
+        self.action.clone()
+    }
+}
@@ -0,0 +1,118 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{
+    server::{DisconnectReason, ServerAsyncMessage, ServerSyncMessage},
+    shared::{
+        channels::{
+            incoming::{IncomingSend, ReceivedPayload},
+            ChannelAsyncMessage, ChannelId, ChannelKind, ChannelSyncMessage, CloseReason,
+            CONTROL_CHANNEL_ID,
+        },
+        close::CloseCode,
+        hardening::ProtocolViolation,
+        ClientId,
+    },
+};
+
+use super::{try_send, ScriptError, ScriptedChannels};
+
+/// Scripted client of an endpoint, see [`crate::server::Endpoint::add_scripted_client`]
+#[derive(Debug)]
+pub struct ScriptedClient {
+    to_sync_endpoint_send: mpsc::Sender<ServerAsyncMessage>,
+    from_sync_server_recv: mpsc::Receiver<ServerSyncMessage>,
+    client_id: Option<ClientId>,
+    bytes_from_client_send: mpsc::Sender<ReceivedPayload>,
+    from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+    channels: ScriptedChannels,
+}
+
+impl ScriptedClient {
+    pub(crate) fn new(
+        to_sync_endpoint_send: mpsc::Sender<ServerAsyncMessage>,
+        from_sync_server_recv: mpsc::Receiver<ServerSyncMessage>,
+        bytes_from_client_send: IncomingSend,
+        from_channels_send: mpsc::Sender<ChannelAsyncMessage>,
+        to_channels_recv: mpsc::Receiver<ChannelSyncMessage>,
+        close_recv: broadcast::Receiver<CloseReason>,
+    ) -> Self {
+        Self {
+            to_sync_endpoint_send,
+            from_sync_server_recv,
+            client_id: None,
+            bytes_from_client_send: bytes_from_client_send.lane(),
+            from_channels_send,
+            channels: ScriptedChannels::new(to_channels_recv, close_recv),
+        }
+    }
+
+    /// [`ClientId`] given by the endpoint, once it accepted the client
+    pub fn client_id(&mut self) -> Option<ClientId> {
+        if self.client_id.is_none() {
+            if let Ok(ServerSyncMessage::ClientConnectedAck(client_id)) =
+                self.from_sync_server_recv.try_recv()
+            {
+                self.client_id = Some(client_id);
+            }
+        }
+        self.client_id
+    }
+
+    /// Loses the connection of the client for `reason`. The client must have been accepted.
+    pub fn lose(&mut self, reason: DisconnectReason) -> Result<(), ScriptError> {
+        let client_id = self.client_id().ok_or(ScriptError::NotAccepted)?;
+        Ok(try_send(
+            &self.to_sync_endpoint_send,
+            ServerAsyncMessage::ClientConnectionClosed(client_id, reason),
+        )?)
+    }
+
+    /// Loses the connection as the channels tasks would, reported as [`DisconnectReason::Error`]
+    pub fn lose_channels(&mut self) -> Result<(), ScriptError> {
+        Ok(try_send(
+            &self.from_channels_send,
+            ChannelAsyncMessage::LostConnection,
+        )?)
+    }
+
+    /// Reports a protocol violation of the client, as the receiving tasks would
+    pub fn violate(&mut self, violation: ProtocolViolation) -> Result<(), ScriptError> {
+        Ok(try_send(
+            &self.from_channels_send,
+            ChannelAsyncMessage::ProtocolViolation(violation),
+        )?)
+    }
+
+    /// Sends a payload to the endpoint, on the channel `channel_id`
+    pub fn send_payload<T: Into<Bytes>>(
+        &mut self,
+        channel_id: ChannelId,
+        payload: T,
+    ) -> Result<(), ScriptError> {
+        Ok(try_send(
+            &self.bytes_from_client_send,
+            (channel_id, payload.into(), None, None, Instant::now()),
+        )?)
+    }
+
+    /// Takes the payloads sent by the endpoint to this client since the last call, by channel id and then in sending order. The control payloads of Quinnet are left out.
+    pub fn sent_payloads(&mut self) -> Vec<(ChannelId, Bytes)> {
+        self.take_sent()
+            .into_iter()
+            .filter(|(channel_id, _, _)| *channel_id != CONTROL_CHANNEL_ID)
+            .map(|(channel_id, _, payload)| (channel_id, payload))
+            .collect()
+    }
+
+    pub(crate) fn take_sent(&mut self) -> Vec<(ChannelId, ChannelKind, Bytes)> {
+        self.channels.take_sent()
+    }
+
+    /// Application close code of the connection, once the endpoint closed it
+    pub fn close_code(&mut self) -> Option<CloseCode> {
+        self.channels.close_code()
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    net::SocketAddr,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::{FromWorld, World};
+use bevy_quinnet::{
+    bot::{BotConfiguration, BotConnection},
+    server::{certificate::CertificateRetrievalMode, QuinnetServer, ServerEndpointConfiguration},
+    shared::{channels::ChannelsConfiguration, close::CloseCode},
+};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn headless_bot_connection() {
+    let port = 6080; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let connecting = runtime.spawn(BotConnection::connect(
+        BotConfiguration::new(SocketAddr::new(SERVER_IP.into(), port)),
+        ChannelsConfiguration::default(),
+    ));
+    // The server accepts the bot and sends its client id when pumped
+    let start = Instant::now();
+    while !connecting.is_finished() {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        server.pump();
+    }
+    let mut bot = runtime.block_on(connecting).unwrap().unwrap();
+    let client_id = bot.client_id().unwrap();
+    assert!(server.endpoint().clients().contains(&client_id));
+
+    let bot_message = SharedMessage::TestMessage("from the bot".to_string());
+    bot.send_message_on(0, bot_message.clone()).unwrap();
+    let start = Instant::now();
+    let received = loop {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        if let Some((_, message)) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            break message;
+        }
+    };
+    assert_eq!(received, bot_message);
+
+    let server_message = SharedMessage::TestMessage("from the server".to_string());
+    server
+        .endpoint_mut()
+        .send_message_on(client_id, 0, server_message.clone())
+        .unwrap();
+    let start = Instant::now();
+    let received = loop {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        if let Some((channel_id, message)) = bot.receive_message::<SharedMessage>().unwrap() {
+            assert_eq!(channel_id, 0);
+            break message;
+        }
+    };
+    assert_eq!(received, server_message);
+
+    runtime.block_on(bot.disconnect(CloseCode::Closed));
+    let start = Instant::now();
+    while server.endpoint().clients().contains(&client_id) {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        server.pump();
+    }
+}
+
+#[test]
+fn async_bot_send_and_recv() {
+    let port = 6081; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let connecting = runtime.spawn(BotConnection::connect(
+        BotConfiguration::new(SocketAddr::new(SERVER_IP.into(), port)),
+        ChannelsConfiguration::default(),
+    ));
+    let start = Instant::now();
+    while !connecting.is_finished() {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        server.pump();
+    }
+    let mut bot = runtime.block_on(connecting).unwrap().unwrap();
+    let client_id = bot.client_id().unwrap();
+
+    // Echoes back the first message from the server
+    let echo = runtime.spawn(async move {
+        let (channel_id, message) = bot.recv_message::<SharedMessage>().await.unwrap();
+        bot.send_message(channel_id, message).await.unwrap();
+        bot
+    });
+
+    let server_message = SharedMessage::TestMessage("echo".to_string());
+    server
+        .endpoint_mut()
+        .send_message_on(client_id, 0, server_message.clone())
+        .unwrap();
+    let start = Instant::now();
+    let received = loop {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        if let Some((_, message)) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            break message;
+        }
+    };
+    assert_eq!(received, server_message);
+
+    let bot = runtime.block_on(echo).unwrap();
+    runtime.block_on(bot.disconnect(CloseCode::Closed));
+}
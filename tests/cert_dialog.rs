@@ -0,0 +1,111 @@
+use std::{thread::sleep, time::Duration};
+
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    prelude::{App, Button, With},
+};
+use bevy_quinnet::{
+    client::{
+        cert_dialog::{CertDialogs, QuinnetCertDialogPlugin},
+        certificate::{
+            CertVerificationStatus, CertVerifierAction, CertificateVerificationMode,
+            FingerprintMismatchPolicy, FirstUsePolicy, KnownHosts, TofuPolicy,
+            TrustOnFirstUseConfig,
+        },
+        QuinnetClient, QuinnetClientPlugin,
+    },
+    shared::channels::ChannelsConfiguration,
+};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn cert_dialog_answers_interaction() {
+    let port = 6041; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app = start_simple_server_app(port);
+
+    let mut client_app = App::new();
+    client_app.add_plugins((
+        ScheduleRunnerPlugin::default(),
+        QuinnetClientPlugin::default(),
+        QuinnetCertDialogPlugin,
+    ));
+    client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::TrustOnFirstUse(
+                TrustOnFirstUseConfig {
+                    known_hosts: KnownHosts::Store(Default::default()),
+                    ..Default::default()
+                }
+                .with_policy(TofuPolicy {
+                    first_use: FirstUsePolicy::Ask,
+                    on_mismatch: FingerprintMismatchPolicy::Ask,
+                }),
+            ),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    while client_app
+        .world()
+        .resource::<CertDialogs>()
+        .pending()
+        .next()
+        .is_none()
+    {
+        sleep(Duration::from_millis(5));
+        client_app.update();
+        server_app.update();
+    }
+    // Let the dialog spawn
+    client_app.update();
+    {
+        let dialogs = client_app.world().resource::<CertDialogs>();
+        let interaction = dialogs.pending().next().unwrap();
+        assert_eq!(
+            interaction.status,
+            CertVerificationStatus::UnknownCertificate
+        );
+        assert_eq!(interaction.info.port, port);
+        assert_eq!(interaction.info.known_fingerprint, None);
+    }
+    let buttons = client_app
+        .world_mut()
+        .query_filtered::<(), With<Button>>()
+        .iter(client_app.world())
+        .count();
+    assert_eq!(buttons, 3, "The dialog should offer every action");
+
+    client_app
+        .world_mut()
+        .resource_mut::<CertDialogs>()
+        .answer(CertVerifierAction::TrustOnce);
+    wait_for_client_connected(&mut client_app, &mut server_app);
+
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<CertDialogs>()
+            .pending()
+            .count(),
+        0
+    );
+    let buttons = client_app
+        .world_mut()
+        .query_filtered::<(), With<Button>>()
+        .iter(client_app.world())
+        .count();
+    assert_eq!(buttons, 0, "The dialog should be closed once answered");
+}
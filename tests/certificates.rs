@@ -1,20 +1,33 @@
-use std::{fs, path::Path, thread::sleep, time::Duration};
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::sleep,
+    time::Duration,
+};
 
 use bevy::{
     app::ScheduleRunnerPlugin,
-    prelude::{App, Update},
+    prelude::{App, FromWorld, Update, World},
 };
 use bevy_quinnet::{
     client::{
         self,
-        certificate::{CertVerificationStatus, CertificateVerificationMode},
-        QuinnetClient, QuinnetClientPlugin, DEFAULT_KNOWN_HOSTS_FILE,
+        certificate::{
+            CertVerificationStatus, CertVerifierAction, CertificateVerificationMode,
+            FingerprintMismatchPolicy, FirstUsePolicy, KnownHost, KnownHosts, KnownHostsKey,
+            KnownHostsStore, ServerName, TofuPolicy, TrustOnFirstUseConfig,
+        },
+        KnownHostsError, QuinnetClient, QuinnetClientEvent, QuinnetClientPlugin,
     },
     server::{
         certificate::CertificateRetrievalMode, QuinnetServer, QuinnetServerPlugin,
         ServerEndpointConfiguration,
     },
-    shared::channels::ChannelsConfiguration,
+    shared::{certificate::CertificateFingerprint, channels::ChannelsConfiguration},
 };
 
 // https://github.com/rust-lang/rust/issues/46379
@@ -49,9 +62,12 @@ fn trust_on_first_use() {
 
     let port = 6004; // TODO Use port 0 and retrieve the port used by the server.
 
-    if Path::new(DEFAULT_KNOWN_HOSTS_FILE).exists() {
-        fs::remove_file(DEFAULT_KNOWN_HOSTS_FILE)
-            .expect("Failed to remove default known hosts file");
+    let hosts_file = std::env::temp_dir()
+        .join("quinnet_trust_on_first_use")
+        .to_string_lossy()
+        .to_string();
+    if Path::new(&hosts_file).exists() {
+        fs::remove_file(&hosts_file).expect("Failed to remove the known hosts file");
     }
 
     let mut client_app = App::new();
@@ -104,6 +120,7 @@ fn trust_on_first_use() {
                 default_client_configuration(port),
                 CertificateVerificationMode::TrustOnFirstUse(
                     client::certificate::TrustOnFirstUseConfig {
+                        known_hosts: KnownHosts::HostsFile(hosts_file.clone()),
                         ..Default::default()
                     },
                 ),
@@ -156,6 +173,7 @@ fn trust_on_first_use() {
                 default_client_configuration(port),
                 CertificateVerificationMode::TrustOnFirstUse(
                     client::certificate::TrustOnFirstUseConfig {
+                        known_hosts: KnownHosts::HostsFile(hosts_file.clone()),
                         ..Default::default()
                     },
                 ),
@@ -216,6 +234,7 @@ fn trust_on_first_use() {
                 default_client_configuration(port),
                 CertificateVerificationMode::TrustOnFirstUse(
                     client::certificate::TrustOnFirstUseConfig {
+                        known_hosts: KnownHosts::HostsFile(hosts_file.clone()),
                         ..Default::default()
                     },
                 ),
@@ -302,6 +321,361 @@ fn trust_on_first_use() {
         );
     }
 
-    // Leave the workspace clean
-    fs::remove_file(DEFAULT_KNOWN_HOSTS_FILE).expect("Failed to remove default known hosts file");
+    fs::remove_file(&hosts_file).expect("Failed to remove the known hosts file");
+}
+
+#[test]
+fn trust_on_first_use_policies() {
+    // The client knows another fingerprint for the server and aborts on mismatch, except when a server policy replaces the fingerprint
+    let port = 6024; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    let server_cert = server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let server_name = ServerName::try_from(SERVER_IP.to_string().as_str()).unwrap();
+    let stale_fingerprint = CertificateFingerprint::new([0; 32]);
+    let tofu_config = TrustOnFirstUseConfig {
+        known_hosts: KnownHosts::Store(
+            [(
+                KnownHost::any_port(server_name.clone()),
+                stale_fingerprint.clone(),
+            )]
+            .into(),
+        ),
+        ..Default::default()
+    }
+    .with_policy(TofuPolicy {
+        first_use: FirstUsePolicy::Ask,
+        on_mismatch: FingerprintMismatchPolicy::Abort,
+    });
+    let mut connect = |client: &mut QuinnetClient, config: TrustOnFirstUseConfig| {
+        client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::TrustOnFirstUse(config),
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+        let mut events = Vec::new();
+        for _ in 0..200 {
+            sleep(Duration::from_millis(5));
+            server.pump();
+            events.extend(client.pump());
+            if events.iter().any(|event| {
+                matches!(
+                    event,
+                    QuinnetClientEvent::Connection(_) | QuinnetClientEvent::CertConnectionAbort(_)
+                )
+            }) {
+                break;
+            }
+        }
+        events
+    };
+
+    // Server override: the stored fingerprint is replaced
+    let events = connect(
+        &mut client,
+        tofu_config.clone().with_server_policy(
+            server_name.clone(),
+            TofuPolicy {
+                first_use: FirstUsePolicy::Ask,
+                on_mismatch: FingerprintMismatchPolicy::Replace,
+            },
+        ),
+    );
+    let trust_update = events
+        .iter()
+        .find_map(|event| match event {
+            QuinnetClientEvent::CertTrustUpdate(update) => Some(update),
+            _ => None,
+        })
+        .expect("The new certificate should have been trusted");
+    assert_eq!(trust_update.cert_info.fingerprint, server_cert.fingerprint);
+    assert_eq!(
+        trust_update.cert_info.known_fingerprint,
+        Some(stale_fingerprint)
+    );
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, QuinnetClientEvent::Connection(_))));
+
+    // Global policy: the connection is aborted
+    let events = connect(&mut client, tofu_config);
+    let abort = events
+        .iter()
+        .find_map(|event| match event {
+            QuinnetClientEvent::CertConnectionAbort(abort) => Some(abort),
+            _ => None,
+        })
+        .expect("The connection should have been aborted");
+    assert_eq!(abort.status, CertVerificationStatus::UntrustedCertificate);
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, QuinnetClientEvent::CertInteraction(_))));
+}
+
+#[test]
+fn headless_cert_interaction_resolver() {
+    // The client asks on first use, a resolver answers instead of a CertInteractionEvent
+    let port = 6025; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let resolved = Arc::new(AtomicUsize::new(0));
+    let resolver_calls = resolved.clone();
+    let tofu_config = TrustOnFirstUseConfig {
+        known_hosts: KnownHosts::Store(Default::default()),
+        ..Default::default()
+    }
+    .with_policy(TofuPolicy {
+        first_use: FirstUsePolicy::Ask,
+        on_mismatch: FingerprintMismatchPolicy::Ask,
+    })
+    .with_interaction_resolver(move |status, _info| {
+        resolver_calls.fetch_add(1, Ordering::Relaxed);
+        match status {
+            CertVerificationStatus::UnknownCertificate => CertVerifierAction::TrustAndStore,
+            _ => CertVerifierAction::AbortConnection,
+        }
+    });
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::TrustOnFirstUse(tofu_config),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let mut events = Vec::new();
+    while !events
+        .iter()
+        .any(|event| matches!(event, QuinnetClientEvent::Connection(_)))
+    {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        events.extend(client.pump());
+    }
+    assert_eq!(resolved.load(Ordering::Relaxed), 1);
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, QuinnetClientEvent::CertTrustUpdate(_))));
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, QuinnetClientEvent::CertInteraction(_))));
+}
+
+#[test]
+fn known_hosts_store_per_port() {
+    // Fingerprints are trusted per server port, entries are listed, removed and exported from a known hosts file
+    let port = 6026; // TODO Use port 0 and retrieve the port used by the server.
+
+    let hosts_file = std::env::temp_dir()
+        .join("quinnet_known_hosts_store_per_port")
+        .to_string_lossy()
+        .to_string();
+    if Path::new(&hosts_file).exists() {
+        fs::remove_file(&hosts_file).expect("Failed to remove the known hosts file");
+    }
+
+    let server_name = ServerName::try_from(SERVER_IP.to_string().as_str()).unwrap();
+    let other_port_fingerprint = CertificateFingerprint::new([1; 32]);
+    let other_server = ServerName::try_from("other.server.com").unwrap();
+    let mut store = KnownHostsStore::open(hosts_file.clone()).unwrap();
+    assert!(store.entries().is_empty());
+    store.insert(
+        KnownHost::new(server_name.clone(), port + 1),
+        other_port_fingerprint.clone(),
+    );
+    store.insert(
+        KnownHost::any_port(other_server.clone()),
+        CertificateFingerprint::new([2; 32]),
+    );
+    store.save().unwrap();
+
+    let mut store = KnownHostsStore::open(hosts_file.clone()).unwrap();
+    assert_eq!(store.entries().len(), 2);
+    assert_eq!(
+        store.get(&server_name, port + 1),
+        Some(&other_port_fingerprint)
+    );
+    assert_eq!(store.get(&server_name, port), None);
+    assert_eq!(
+        store.get(&other_server, port),
+        Some(&CertificateFingerprint::new([2; 32]))
+    );
+
+    let mut exported = Vec::new();
+    store.export(&mut exported).unwrap();
+    let exported = String::from_utf8(exported).unwrap();
+    assert!(exported.contains(&format!("[{}]:{} ", SERVER_IP, port + 1)));
+    assert!(exported.contains("other.server.com "));
+
+    store.remove_server(&other_server);
+    assert_eq!(store.entries().len(), 1);
+
+    // The fingerprint of the other port is not used for this port
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    let server_cert = server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::TrustOnFirstUse(
+                TrustOnFirstUseConfig {
+                    known_hosts: store.clone().into(),
+                    ..Default::default()
+                }
+                .with_policy(TofuPolicy {
+                    first_use: FirstUsePolicy::AutoTrust,
+                    on_mismatch: FingerprintMismatchPolicy::Abort,
+                }),
+            ),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut events = Vec::new();
+    while !events
+        .iter()
+        .any(|event| matches!(event, QuinnetClientEvent::Connection(_)))
+    {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        events.extend(client.pump());
+    }
+    let trust_update = events
+        .iter()
+        .find_map(|event| match event {
+            QuinnetClientEvent::CertTrustUpdate(update) => Some(update),
+            _ => None,
+        })
+        .expect("The certificate should have been trusted");
+    assert_eq!(trust_update.cert_info.known_fingerprint, None);
+    assert_eq!(
+        trust_update.cert_info.known_host(),
+        KnownHost::new(server_name.clone(), port)
+    );
+
+    store.insert(
+        trust_update.cert_info.known_host(),
+        server_cert.fingerprint.clone(),
+    );
+    assert_eq!(
+        store.get(&server_name, port),
+        Some(&server_cert.fingerprint)
+    );
+    assert_eq!(
+        store.remove(&KnownHost::new(server_name, port + 1)),
+        Some(other_port_fingerprint)
+    );
+    fs::remove_file(&hosts_file).unwrap();
+}
+
+#[test]
+fn encrypted_known_hosts_file() {
+    // A certificate trusted on first use is stored in an encrypted known hosts file, which can only be read with its key
+    let port = 6027; // TODO Use port 0 and retrieve the port used by the server.
+
+    let hosts_file = std::env::temp_dir()
+        .join("quinnet_encrypted_known_hosts_file")
+        .to_string_lossy()
+        .to_string();
+    if Path::new(&hosts_file).exists() {
+        fs::remove_file(&hosts_file).expect("Failed to remove the known hosts file");
+    }
+    let key = Arc::new(KnownHostsKey::new([7; 32]));
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    let server_cert = server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::TrustOnFirstUse(TrustOnFirstUseConfig {
+                known_hosts: KnownHosts::EncryptedHostsFile {
+                    file: hosts_file.clone(),
+                    cipher: key.clone(),
+                },
+                ..Default::default()
+            }),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    while !client
+        .pump()
+        .iter()
+        .any(|event| matches!(event, QuinnetClientEvent::Connection(_)))
+    {
+        sleep(Duration::from_millis(5));
+        server.pump();
+    }
+
+    let content = fs::read(&hosts_file).unwrap();
+    let fingerprint_b64 = server_cert.fingerprint.to_base64();
+    assert!(!content
+        .windows(fingerprint_b64.len())
+        .any(|window| window == fingerprint_b64.as_bytes()));
+    assert!(KnownHostsStore::open(hosts_file.clone()).is_err());
+    assert!(matches!(
+        KnownHostsStore::open_encrypted(hosts_file.clone(), Arc::new(KnownHostsKey::new([8; 32]))),
+        Err(KnownHostsError::Encryption)
+    ));
+
+    let mut store = KnownHostsStore::open_encrypted(hosts_file.clone(), key).unwrap();
+    let server_name = ServerName::try_from(SERVER_IP.to_string().as_str()).unwrap();
+    assert_eq!(
+        store.get(&server_name, port),
+        Some(&server_cert.fingerprint)
+    );
+
+    // Decrypted to plaintext
+    store.set_cipher(None);
+    store.save().unwrap();
+    let store = KnownHostsStore::open(hosts_file.clone()).unwrap();
+    assert_eq!(
+        store.get(&server_name, port),
+        Some(&server_cert.fingerprint)
+    );
+    fs::remove_file(&hosts_file).unwrap();
 }
@@ -1,10 +1,39 @@
-use bevy::prelude::App;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::{App, Events, FromWorld, World};
 
 use bevy_quinnet::{
-    client::QuinnetClient,
-    server::{QuinnetServer, ServerGroupMessageSendError},
-    shared::channels::{ChannelKind, DEFAULT_MAX_RELIABLE_FRAME_LEN},
+    client::{
+        certificate::CertificateVerificationMode,
+        split::{ConnectionReceiver, ConnectionSender},
+        ClientPayloadSendError, ClientSendError, QuinnetClient, QuinnetClientEvent,
+    },
+    server::{
+        certificate::CertificateRetrievalMode, conditions::ClientConditions, ClientSendFailedEvent,
+        QuinnetServer, QuinnetServerEvent, ServerEndpointConfiguration,
+        ServerGroupMessageSendError, ServerSendError,
+    },
+    shared::{
+        buffer_pool::DEFAULT_BUFFER_CHUNK_SIZE,
+        channels::{
+            ChannelConfig, ChannelEncryption, ChannelId, ChannelKind, ChannelsConfiguration,
+            CompressionDictionary, LivenessProbe, DEFAULT_MAX_RELIABLE_FRAME_LEN,
+            MAX_DICTIONARY_LEN, MESSAGE_ACK_TIMEOUT, REPLAY_HEADER_LEN, REPLAY_WINDOW_LEN,
+        },
+        error::{ChannelError, CompressionDictionaryError},
+        hardening::ProtocolViolation,
+        protocol::protocol_hash,
+        transport::{memory::MemoryConnection, TransportConnection},
+        ClientId,
+    },
 };
+use bytes::Bytes;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 // https://github.com/rust-lang/rust/issues/46379
 pub use utils::*;
@@ -165,3 +194,1598 @@ fn multi_instance_channels() {
         }
     }
 }
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn encrypted_channels() {
+    let port = 6006; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let mut msg_counter = 0;
+    for (channel_type, encryption) in [
+        (ChannelKind::default(), ChannelEncryption::TlsExporter),
+        (
+            ChannelKind::Unreliable,
+            ChannelEncryption::PreSharedKey([7; 32]),
+        ),
+    ] {
+        let client_channel = client_app
+            .world_mut()
+            .resource_mut::<QuinnetClient>()
+            .connection_mut()
+            .open_encrypted_channel(channel_type, encryption.clone())
+            .expect("Failed to open channel");
+        let server_channel = server_app
+            .world_mut()
+            .resource_mut::<QuinnetServer>()
+            .endpoint_mut()
+            .open_encrypted_channel(channel_type, encryption)
+            .expect("Failed to open channel");
+        assert_eq!(client_channel, server_channel);
+
+        send_and_test_client_message(
+            client_id,
+            client_channel,
+            &mut client_app,
+            &mut server_app,
+            &mut msg_counter,
+        );
+        send_and_test_server_message(
+            client_id,
+            server_channel,
+            &mut server_app,
+            &mut client_app,
+            &mut msg_counter,
+        );
+    }
+
+    // A reopened channel derives a new key, its messages are still opened by the peer
+    for channel_id in [0, 1] {
+        client_app
+            .world_mut()
+            .resource_mut::<QuinnetClient>()
+            .connection_mut()
+            .close_channel(channel_id)
+            .unwrap();
+        server_app
+            .world_mut()
+            .resource_mut::<QuinnetServer>()
+            .endpoint_mut()
+            .close_channel(channel_id)
+            .unwrap();
+    }
+    let encryption = ChannelEncryption::PreSharedKey([7; 32]);
+    let client_channel = client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .connection_mut()
+        .open_encrypted_channel(ChannelKind::default(), encryption.clone())
+        .expect("Failed to open channel");
+    let server_channel = server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .open_encrypted_channel(ChannelKind::default(), encryption)
+        .expect("Failed to open channel");
+    assert_eq!(client_channel, 0);
+    assert_eq!(client_channel, server_channel);
+    send_and_test_client_message(
+        client_id,
+        client_channel,
+        &mut client_app,
+        &mut server_app,
+        &mut msg_counter,
+    );
+    send_and_test_server_message(
+        client_id,
+        server_channel,
+        &mut server_app,
+        &mut client_app,
+        &mut msg_counter,
+    );
+}
+
+#[test]
+fn encrypted_channel_without_key() {
+    let port = 6098; // TODO Use port 0 and retrieve the port used by the server.
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    // Without TLS, the connection has no keying material to export
+    let (_client_end, server_end) = MemoryConnection::pair();
+    server.endpoint().add_transport_connection(server_end);
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(client_id) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::Connection(event) => Some(event.id),
+            _ => None,
+        }) {
+            break client_id;
+        }
+    };
+
+    let channel_id = server
+        .endpoint_mut()
+        .open_encrypted_channel(ChannelKind::default(), ChannelEncryption::TlsExporter)
+        .unwrap();
+    let start = Instant::now();
+    let error = loop {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        if let Some(event) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::ChannelError(event) => Some(event),
+            _ => None,
+        }) {
+            break event;
+        }
+    };
+    assert_eq!(error.id, client_id);
+    assert_eq!(error.channel_id, channel_id);
+    assert_eq!(error.error, ChannelError::EncryptionUnavailable);
+    // The channel refuses the messages it would never send
+    assert!(server
+        .endpoint_mut()
+        .send_payload_on(client_id, channel_id, Bytes::from_static(b"lost"))
+        .is_err());
+}
+
+#[test]
+fn prioritized_messages() {
+    let port = 6011; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_client_channel(&client_app);
+
+    // Fill the outgoing queue of the channel with bulk messages, then send an urgent one
+    const BULK_MESSAGES_COUNT: usize = 100;
+    let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+    for _ in 0..BULK_MESSAGES_COUNT {
+        client
+            .connection_mut()
+            .send_payload_on(channel, vec![0; 256 * 1024])
+            .unwrap();
+    }
+    client
+        .connection_mut()
+        .send_prioritized_payload_on(channel, vec![1], 10)
+        .unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < BULK_MESSAGES_COUNT + 1 {
+        sleep(Duration::from_millis(5));
+        let mut server = server_app.world_mut().resource_mut::<QuinnetServer>();
+        while let Some((_, payload)) = server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap()
+        {
+            received.push(payload);
+        }
+    }
+    let urgent_position = received
+        .iter()
+        .position(|payload| payload[..] == [1])
+        .expect("The urgent message should have been received");
+    assert!(
+        urgent_position < BULK_MESSAGES_COUNT / 2,
+        "The urgent message was received at position {}",
+        urgent_position
+    );
+}
+
+#[test]
+fn clear_pending_messages() {
+    let port = 6012; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_client_channel(&client_app);
+
+    const BULK_MESSAGES_COUNT: usize = 100;
+    let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+    let connection = client.connection_mut();
+    for _ in 0..BULK_MESSAGES_COUNT {
+        connection
+            .send_payload_on(channel, vec![0; 256 * 1024])
+            .unwrap();
+    }
+    assert!(connection.pending_messages_count(channel).unwrap() > 0);
+    let discarded = connection.clear_pending_messages(channel).unwrap();
+    assert!(discarded > 0);
+    assert_eq!(connection.pending_messages_count(channel), Some(0));
+    assert_eq!(connection.pending_messages_count(channel + 1), None);
+    connection.send_payload_on(channel, vec![1]).unwrap();
+
+    let mut bulk_received = 0;
+    loop {
+        sleep(Duration::from_millis(5));
+        let mut server = server_app.world_mut().resource_mut::<QuinnetServer>();
+        match server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap()
+        {
+            Some((_, payload)) if payload[..] == [1] => break,
+            Some(_) => bulk_received += 1,
+            None => (),
+        }
+    }
+    assert_eq!(bulk_received, BULK_MESSAGES_COUNT - discarded);
+}
+
+#[test]
+fn configured_channels() {
+    let port = 6013; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+    let config = ChannelConfig::reliable_ordered()
+        .priority(3)
+        .compressed()
+        .encrypted(ChannelEncryption::TlsExporter)
+        .max_message_size(MAX_MESSAGE_SIZE);
+    let client_channel = open_client_channel(config.clone(), &mut client_app);
+    let server_channel = open_server_channel(config, &mut server_app);
+    assert_eq!(client_channel, server_channel);
+
+    let mut msg_counter = 0;
+    send_and_test_client_message(
+        client_id,
+        client_channel,
+        &mut client_app,
+        &mut server_app,
+        &mut msg_counter,
+    );
+    send_and_test_server_message(
+        client_id,
+        server_channel,
+        &mut server_app,
+        &mut client_app,
+        &mut msg_counter,
+    );
+
+    // Compressible payload at the size limit goes through, larger payloads are refused
+    let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+    let connection = client.connection_mut();
+    connection
+        .send_payload_on(client_channel, vec![42; MAX_MESSAGE_SIZE])
+        .unwrap();
+    assert!(matches!(
+        connection.send_payload_on(client_channel, vec![42; MAX_MESSAGE_SIZE + 1]),
+        Err(ClientSendError::PayloadTooLarge {
+            size,
+            max_message_size: MAX_MESSAGE_SIZE
+        }) if size == MAX_MESSAGE_SIZE + 1
+    ));
+
+    let payload = loop {
+        sleep(Duration::from_millis(5));
+        let mut server = server_app.world_mut().resource_mut::<QuinnetServer>();
+        if let Some((channel, payload)) = server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap()
+        {
+            assert_eq!(channel, server_channel);
+            break payload;
+        }
+    };
+    assert_eq!(payload, vec![42; MAX_MESSAGE_SIZE]);
+}
+
+#[test]
+fn dictionary_compressed_channels() {
+    let port = 6093; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let dictionary =
+        CompressionDictionary::new(7, b"TestMessage dictionary compressed".to_vec()).unwrap();
+    let config = ChannelConfig::unreliable().compressed_with_dictionary(dictionary.clone());
+    assert!(config.is_compressed());
+    assert_eq!(config.compression_dictionary().unwrap().id(), 7);
+    let client_channel = open_client_channel(config.clone(), &mut client_app);
+    let server_channel = open_server_channel(config, &mut server_app);
+    assert_eq!(client_channel, server_channel);
+
+    let mut msg_counter = 0;
+    send_and_test_client_message(
+        client_id,
+        client_channel,
+        &mut client_app,
+        &mut server_app,
+        &mut msg_counter,
+    );
+    send_and_test_server_message(
+        client_id,
+        server_channel,
+        &mut server_app,
+        &mut client_app,
+        &mut msg_counter,
+    );
+
+    // The id of the dictionary is part of the protocol, not its content
+    let hash = |config: ChannelConfig| {
+        let mut channels = ChannelsConfiguration::default();
+        channels.add(config).unwrap();
+        channels.protocol_hash()
+    };
+    let with_dictionary = hash(ChannelConfig::unreliable().compressed_with_dictionary(dictionary));
+    assert_ne!(
+        with_dictionary,
+        hash(ChannelConfig::unreliable().compressed())
+    );
+    assert_ne!(
+        with_dictionary,
+        hash(ChannelConfig::unreliable().compressed_with_dictionary(
+            CompressionDictionary::new(8, b"dictionary".to_vec()).unwrap()
+        ))
+    );
+    assert_eq!(
+        with_dictionary,
+        hash(ChannelConfig::unreliable().compressed_with_dictionary(
+            CompressionDictionary::new(7, b"dictionary".to_vec()).unwrap()
+        ))
+    );
+
+    assert!(matches!(
+        CompressionDictionary::new(1, vec![0; MAX_DICTIONARY_LEN + 1]),
+        Err(CompressionDictionaryError::TooLarge { len, max_len })
+            if len == MAX_DICTIONARY_LEN + 1 && max_len == MAX_DICTIONARY_LEN
+    ));
+    // Starts with the magic number of the zstd dictionaries, without their header
+    let mut malformed = vec![0x37, 0xa4, 0x30, 0xec];
+    malformed.extend_from_slice(&[0; 12]);
+    assert!(matches!(
+        CompressionDictionary::new(1, malformed),
+        Err(CompressionDictionaryError::Invalid(_))
+    ));
+}
+
+#[test]
+fn mismatched_dictionaries_reject_the_channel() {
+    let port = 6100; // TODO Use port 0 and retrieve the port used by the server.
+    let dictionary =
+        |id| CompressionDictionary::new(id, b"TestMessage dictionary compressed".to_vec()).unwrap();
+    let server_channels = ChannelsConfiguration::from_configs(vec![
+        ChannelConfig::unreliable().compressed_with_dictionary(dictionary(7)),
+        ChannelConfig::reliable_ordered().compressed_with_dictionary(dictionary(3)),
+    ])
+    .unwrap();
+    let client_channels = ChannelsConfiguration::from_configs(vec![
+        ChannelConfig::unreliable().compressed_with_dictionary(dictionary(8)),
+        ChannelConfig::reliable_ordered().compressed_with_dictionary(dictionary(3)),
+    ])
+    .unwrap();
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            server_channels,
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            client_channels,
+        )
+        .unwrap();
+
+    // Both peers announce their dictionaries and reject the mismatched channel
+    let mut client_id = None;
+    let mut server_rejections = Vec::new();
+    let mut client_rejections = Vec::new();
+    let start = Instant::now();
+    while (server_rejections.is_empty() || client_rejections.is_empty())
+        && start.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            match event {
+                QuinnetServerEvent::Connection(event) => client_id = Some(event.id),
+                QuinnetServerEvent::ChannelRejected(event) => server_rejections.push(event),
+                _ => (),
+            }
+        }
+        client_rejections.extend(client.pump().into_iter().filter_map(|event| match event {
+            QuinnetClientEvent::ChannelRejected(event) => Some(event),
+            _ => None,
+        }));
+    }
+    let client_id = client_id.expect("A client should have connected");
+    assert_eq!(server_rejections.len(), 1);
+    assert_eq!(server_rejections[0].id, client_id);
+    assert_eq!(server_rejections[0].channel_id, 0);
+    assert_eq!(server_rejections[0].client_dictionary_id, Some(8));
+    assert_eq!(server_rejections[0].server_dictionary_id, Some(7));
+    assert_eq!(client_rejections.len(), 1);
+    assert_eq!(client_rejections[0].channel_id, 0);
+    assert_eq!(client_rejections[0].local_dictionary_id, Some(8));
+    assert_eq!(client_rejections[0].server_dictionary_id, Some(7));
+
+    // The rejected channel is closed on both sides, only for this client on the server
+    assert_eq!(client.connection().channel_ids(), vec![1]);
+    assert_eq!(
+        server
+            .endpoint()
+            .get_connection(client_id)
+            .unwrap()
+            .channel_ids(),
+        vec![1]
+    );
+    assert!(client
+        .connection_mut()
+        .send_message_on(0, SharedMessage::TestMessage("rejected".to_string()))
+        .is_err());
+    assert!(server
+        .endpoint_mut()
+        .send_message_on(
+            client_id,
+            0,
+            SharedMessage::TestMessage("rejected".to_string())
+        )
+        .is_err());
+
+    // The channel with the same dictionary stays open
+    client
+        .connection_mut()
+        .send_message_on(1, SharedMessage::TestMessage("accepted".to_string()))
+        .unwrap();
+    let start = Instant::now();
+    let received = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        if let Some(received) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            break received;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    };
+    assert_eq!(
+        received,
+        (1, SharedMessage::TestMessage("accepted".to_string()))
+    );
+    assert!(!server
+        .pump()
+        .iter()
+        .any(|event| matches!(event, QuinnetServerEvent::ChannelRejected(_))));
+}
+
+#[test]
+fn channel_liveness_probes() {
+    let port = 6096; // TODO Use port 0 and retrieve the port used by the server.
+    let probe = LivenessProbe::new(Duration::from_millis(50), Duration::from_millis(200));
+    let config = ChannelConfig::reliable_ordered().liveness_probe(probe);
+    assert_eq!(config.liveness(), Some(probe));
+    assert_eq!(
+        ChannelConfig::unreliable().liveness_probe(probe).liveness(),
+        None
+    );
+    // Only the sending peer needs the probe
+    let hash = |config: ChannelConfig| {
+        ChannelsConfiguration::from_configs(vec![config])
+            .unwrap()
+            .protocol_hash()
+    };
+    assert_eq!(
+        hash(config.clone()),
+        hash(ChannelConfig::reliable_ordered())
+    );
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    let channels = ChannelsConfiguration::from_configs(vec![config]).unwrap();
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+
+    // Both peers probe their channel and answer the probes of the other
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels,
+        )
+        .unwrap();
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(600) {
+        sleep(Duration::from_millis(5));
+        assert!(!server
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetServerEvent::ChannelUnresponsive(_))));
+        assert!(!client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::ChannelUnresponsive(_))));
+    }
+    assert_eq!(server.endpoint().clients().len(), 1);
+
+    // A raw peer, reading the probes of the server
+    let (client_end, server_end) = MemoryConnection::pair();
+    server.endpoint().add_transport_connection(server_end);
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(client_id) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::Connection(event) => Some(event.id),
+            _ => None,
+        }) {
+            break client_id;
+        }
+    };
+    let mut stream = futures::executor::block_on(client_end.accept_uni()).unwrap();
+    let pump_for = |server: &mut QuinnetServer, duration: Duration| {
+        let start = Instant::now();
+        let mut unresponsive = Vec::new();
+        while start.elapsed() < duration {
+            sleep(Duration::from_millis(5));
+            unresponsive.extend(server.pump().into_iter().filter_map(|event| match event {
+                QuinnetServerEvent::ChannelUnresponsive(event) if event.id == client_id => {
+                    Some(event)
+                }
+                _ => None,
+            }));
+        }
+        unresponsive
+    };
+    let mut read_probe = || {
+        let mut frame = [0; 18];
+        futures::executor::block_on(stream.read_exact(&mut frame)).unwrap();
+        frame
+    };
+    // A `ChannelProbe` control message, on the stream of channel 0
+    let probe_frame = |sequence: u64| {
+        let mut frame = vec![0, 0, 0, 14, 255, 6, 0, 0, 0, 0];
+        frame.extend_from_slice(&sequence.to_le_bytes());
+        frame
+    };
+
+    // Unresponsive once a probe is left unanswered, reported once
+    assert!(pump_for(&mut server, Duration::from_millis(80)).is_empty());
+    assert_eq!(read_probe().to_vec(), probe_frame(0));
+    let unresponsive = pump_for(&mut server, Duration::from_millis(500));
+    assert_eq!(unresponsive.len(), 1);
+    assert_eq!(unresponsive[0].channel_id, 0);
+    assert!(unresponsive[0].waited >= probe.timeout());
+
+    // Probed again once answered
+    futures::executor::block_on(async {
+        let mut control = client_end.open_uni().await.unwrap();
+        let mut frame = vec![0, 0, 0, 14, 255, 7, 0, 0, 0, 0];
+        frame.extend_from_slice(&0u64.to_le_bytes());
+        control.write_all(&frame).await.unwrap();
+    });
+    assert!(pump_for(&mut server, Duration::from_millis(120)).is_empty());
+    assert_eq!(read_probe().to_vec(), probe_frame(1));
+}
+
+#[test]
+fn group_message_with_per_client_payload() {
+    let port = 6014; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app_1: App = start_simple_client_app(port);
+    let client_id_1 = wait_for_client_connected(&mut client_app_1, &mut server_app);
+    let mut client_app_2: App = start_simple_client_app(port);
+    let client_id_2 = wait_for_client_connected(&mut client_app_2, &mut server_app);
+
+    let channel = get_default_server_channel(&server_app);
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .send_group_message_with([client_id_1, client_id_2].iter(), channel, |client_id| {
+            SharedMessage::TestMessage(format!("Hello client {}", client_id))
+        })
+        .unwrap();
+
+    for (client_id, client_app) in [
+        (client_id_1, &mut client_app_1),
+        (client_id_2, &mut client_app_2),
+    ] {
+        assert_eq!(
+            wait_for_server_message(client_app),
+            (
+                channel,
+                SharedMessage::TestMessage(format!("Hello client {}", client_id))
+            )
+        );
+    }
+}
+
+#[test]
+fn pooled_message_buffers() {
+    let port = 6015; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_client_channel(&client_app);
+
+    const MESSAGES_COUNT: u64 = 10;
+    let mut msg_counter = 0;
+    for _ in 0..MESSAGES_COUNT {
+        send_and_test_client_message(
+            client_id,
+            channel,
+            &mut client_app,
+            &mut server_app,
+            &mut msg_counter,
+        );
+    }
+
+    // All the messages fit in the first chunk
+    let stats = client_app
+        .world()
+        .resource::<QuinnetClient>()
+        .connection()
+        .buffer_pool_stats();
+    assert_eq!(stats.allocated_chunks, 1);
+    assert_eq!(stats.reused, MESSAGES_COUNT - 1);
+    assert_eq!(stats.allocated_bytes, DEFAULT_BUFFER_CHUNK_SIZE as u64);
+}
+
+#[test]
+fn unpooled_message_buffers() {
+    let port = 6086; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_client_channel(&client_app);
+    {
+        let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+        let connection = client.connection_mut();
+        assert!(connection.buffer_pooling());
+        connection.set_buffer_pooling(false);
+        assert!(!connection.buffer_pooling());
+    }
+
+    const MESSAGES_COUNT: u64 = 10;
+    let mut msg_counter = 0;
+    for _ in 0..MESSAGES_COUNT {
+        send_and_test_client_message(
+            client_id,
+            channel,
+            &mut client_app,
+            &mut server_app,
+            &mut msg_counter,
+        );
+    }
+
+    // Each message has a buffer of its own, of its exact size
+    let stats = client_app
+        .world()
+        .resource::<QuinnetClient>()
+        .connection()
+        .buffer_pool_stats();
+    assert_eq!(stats.allocated_chunks, MESSAGES_COUNT);
+    assert_eq!(stats.reused, 0);
+    assert!(stats.allocated_bytes < DEFAULT_BUFFER_CHUNK_SIZE as u64);
+}
+
+#[test]
+fn frame_coherent_receive() {
+    let port = 6087; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .set_frame_coherent_receive(true);
+    client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .connection_mut()
+        .set_frame_coherent_receive(true);
+
+    let client_message = SharedMessage::TestMessage("from client".to_string());
+    client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .connection_mut()
+        .send_message(client_message.clone())
+        .unwrap();
+    let server_message = SharedMessage::TestMessage("from server".to_string());
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .send_message(client_id, server_message.clone())
+        .unwrap();
+    sleep(Duration::from_millis(100));
+
+    // The messages arrived, but are not captured until the next update
+    assert!(server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .receive_message_from::<SharedMessage>(client_id)
+        .unwrap()
+        .is_none());
+    assert!(client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .connection_mut()
+        .receive_message::<SharedMessage>()
+        .unwrap()
+        .is_none());
+
+    let start = Instant::now();
+    let mut server_received = None;
+    let mut client_received = None;
+    while server_received.is_none() || client_received.is_none() {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        server_app.update();
+        client_app.update();
+        server_received = server_received.or(server_app
+            .world_mut()
+            .resource_mut::<QuinnetServer>()
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap());
+        client_received = client_received.or(client_app
+            .world_mut()
+            .resource_mut::<QuinnetClient>()
+            .connection_mut()
+            .receive_message::<SharedMessage>()
+            .unwrap());
+    }
+    assert_eq!(server_received.unwrap().1, client_message);
+    assert_eq!(client_received.unwrap().1, server_message);
+}
+
+#[test]
+fn split_connection() {
+    let port = 6097; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let (sender, receiver) = client_app
+        .world()
+        .resource::<QuinnetClient>()
+        .connection()
+        .split();
+    client_app.insert_resource(sender);
+    client_app.insert_resource(receiver);
+
+    let server_message = SharedMessage::TestMessage("to receiver".to_string());
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .send_message(client_id, server_message.clone())
+        .unwrap();
+    let client_message = SharedMessage::TestMessage("from sender".to_string());
+    client_app
+        .world_mut()
+        .resource_mut::<ConnectionSender>()
+        .send_message(client_message.clone())
+        .unwrap();
+
+    let start = Instant::now();
+    let mut server_received = None;
+    let mut client_received = None;
+    while server_received.is_none() || client_received.is_none() {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        server_app.update();
+        client_app.update();
+        server_received = server_received.or(server_app
+            .world_mut()
+            .resource_mut::<QuinnetServer>()
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap());
+        client_received = client_received.or(client_app
+            .world_mut()
+            .resource_mut::<ConnectionReceiver>()
+            .receive_message::<SharedMessage>()
+            .unwrap());
+    }
+    assert_eq!(server_received.unwrap().1, client_message);
+    assert_eq!(client_received.unwrap().1, server_message);
+
+    // The halves share the stats of the connection
+    let client = client_app.world().resource::<QuinnetClient>();
+    assert_eq!(client.connection().received_messages_count(), 1);
+    assert!(client.connection().sent_bytes_count() > 0);
+
+    client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .connection_mut()
+        .disconnect()
+        .unwrap();
+    assert!(matches!(
+        client_app
+            .world()
+            .resource::<ConnectionSender>()
+            .send_payload(Bytes::from_static(b"closed")),
+        Err(ClientPayloadSendError::SendError(
+            ClientSendError::ConnectionClosed
+        ))
+    ));
+    assert!(client_app
+        .world_mut()
+        .resource_mut::<ConnectionReceiver>()
+        .receive_payload()
+        .is_err());
+}
+
+#[test]
+fn batch_receive_per_channel() {
+    let port = 6016; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let default_channel = get_default_client_channel(&client_app);
+    let other_channel = open_client_channel(ChannelKind::default(), &mut client_app);
+    open_server_channel(ChannelKind::default(), &mut server_app);
+
+    let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+    let connection = client.connection_mut();
+    for i in 0..3u8 {
+        connection
+            .send_payload_on(default_channel, vec![i])
+            .unwrap();
+        connection
+            .send_payload_on(other_channel, vec![10 + i])
+            .unwrap();
+    }
+
+    let mut other_payloads = Vec::new();
+    let mut default_payloads = Vec::new();
+    while other_payloads.len() < 3 || default_payloads.len() < 3 {
+        sleep(Duration::from_millis(5));
+        let mut server = server_app.world_mut().resource_mut::<QuinnetServer>();
+        let endpoint = server.endpoint_mut();
+        other_payloads.extend(
+            endpoint
+                .receive_all_from_on(client_id, other_channel)
+                .unwrap(),
+        );
+        let mut drained = endpoint.drain_payloads_from(client_id).unwrap();
+        other_payloads.extend(drained.remove(&other_channel).unwrap_or_default());
+        default_payloads.extend(drained.remove(&default_channel).unwrap_or_default());
+        assert!(drained.is_empty());
+    }
+
+    assert_eq!(other_payloads, vec![vec![10], vec![11], vec![12]]);
+    assert_eq!(default_payloads, vec![vec![0], vec![1], vec![2]]);
+    let server = server_app.world().resource::<QuinnetServer>();
+    assert_eq!(
+        server.endpoint().endpoint_stats().received_messages_count(),
+        6
+    );
+}
+
+#[test]
+fn parallel_receive_from_clients() {
+    let port = 6017; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app_1: App = start_simple_client_app(port);
+    let client_id_1 = wait_for_client_connected(&mut client_app_1, &mut server_app);
+    let mut client_app_2: App = start_simple_client_app(port);
+    let client_id_2 = wait_for_client_connected(&mut client_app_2, &mut server_app);
+
+    const MESSAGES_COUNT: usize = 5;
+    for client_app in [&mut client_app_1, &mut client_app_2] {
+        let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+        for i in 0..MESSAGES_COUNT {
+            client
+                .connection_mut()
+                .send_message(SharedMessage::TestMessage(i.to_string()))
+                .unwrap();
+        }
+    }
+
+    let received = Mutex::new(HashMap::<ClientId, Vec<SharedMessage>>::new());
+    while received
+        .lock()
+        .unwrap()
+        .values()
+        .map(Vec::len)
+        .sum::<usize>()
+        < 2 * MESSAGES_COUNT
+    {
+        sleep(Duration::from_millis(5));
+        server_app
+            .world_mut()
+            .resource_mut::<QuinnetServer>()
+            .endpoint_mut()
+            .par_receive_messages(|client_id, _, message: SharedMessage| {
+                received
+                    .lock()
+                    .unwrap()
+                    .entry(client_id)
+                    .or_default()
+                    .push(message);
+            });
+    }
+
+    let expected: Vec<SharedMessage> = (0..MESSAGES_COUNT)
+        .map(|i| SharedMessage::TestMessage(i.to_string()))
+        .collect();
+    let received = received.into_inner().unwrap();
+    assert_eq!(received[&client_id_1], expected);
+    assert_eq!(received[&client_id_2], expected);
+    let server = server_app.world().resource::<QuinnetServer>();
+    assert_eq!(
+        server.endpoint().endpoint_stats().received_messages_count(),
+        2 * MESSAGES_COUNT as u64
+    );
+}
+
+#[test]
+fn deferred_flush() {
+    let port = 6018; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_client_channel(&client_app);
+
+    let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+    client.set_deferred_flush(true);
+    let connection = client.connection_mut();
+    assert!(connection.deferred_flush());
+    connection
+        .send_message_on(channel, SharedMessage::TestMessage("deferred".to_string()))
+        .unwrap();
+
+    // Held until the flush system runs
+    sleep(Duration::from_millis(50));
+    assert_eq!(connection.pending_messages_count(channel), Some(1));
+    assert!(server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .receive_payload_from(client_id)
+        .unwrap()
+        .is_none());
+
+    client_app.update();
+    assert_eq!(
+        wait_for_client_message(client_id, &mut server_app),
+        (channel, SharedMessage::TestMessage("deferred".to_string()))
+    );
+}
+
+#[test]
+fn group_send_failures_as_events() {
+    let port = 6022; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_server_channel(&server_app);
+    let unknown_client_id = client_id + 1;
+
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .try_send_group_message_on(
+            [client_id, unknown_client_id].iter(),
+            channel,
+            SharedMessage::TestMessage("group".to_string()),
+        );
+
+    // Still sent to the other clients
+    assert_eq!(
+        wait_for_server_message(&mut client_app),
+        (channel, SharedMessage::TestMessage("group".to_string()))
+    );
+
+    server_app.update();
+    let events = server_app
+        .world()
+        .resource::<Events<ClientSendFailedEvent>>();
+    let failures: Vec<&ClientSendFailedEvent> = events.iter_current_update_events().collect();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].client_id, unknown_client_id);
+    assert_eq!(failures[0].channel_id, channel);
+    assert!(matches!(
+        failures[0].error,
+        ServerSendError::UnknownClient(id) if id == unknown_client_id
+    ));
+}
+
+#[test]
+fn traced_channels() {
+    let port = 6039; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    const MAX_MESSAGE_SIZE: usize = 1024;
+    let config = ChannelConfig::reliable_ordered()
+        .traced()
+        .compressed()
+        .max_message_size(MAX_MESSAGE_SIZE);
+    let client_channel = open_client_channel(config.clone(), &mut client_app);
+    let server_channel = open_server_channel(config, &mut server_app);
+    assert_eq!(client_channel, server_channel);
+    let default_channel = get_default_client_channel(&client_app);
+
+    // The trace header does not count in the size limit, nor shows in the payloads
+    let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+    let connection = client.connection_mut();
+    for byte in 0..3 {
+        connection
+            .send_payload_on(client_channel, vec![byte; MAX_MESSAGE_SIZE])
+            .unwrap();
+    }
+    connection
+        .send_payload_on(default_channel, vec![42; 8])
+        .unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 4 {
+        sleep(Duration::from_millis(5));
+        let mut server = server_app.world_mut().resource_mut::<QuinnetServer>();
+        if let Some(payload) = server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap()
+        {
+            received.push(payload);
+        }
+    }
+    for byte in 0..3 {
+        assert!(received.contains(&(server_channel, vec![byte; MAX_MESSAGE_SIZE].into())));
+    }
+
+    let mut server = server_app.world_mut().resource_mut::<QuinnetServer>();
+    let traces = server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .drain_message_traces();
+    assert_eq!(traces.len(), 3, "Only the traced channel is traced");
+    for (trace_id, trace) in traces.iter().enumerate() {
+        assert_eq!(trace.channel_id(), server_channel);
+        assert_eq!(trace.trace_id(), trace_id as u64);
+        // Both peers share the same clock
+        let latency = trace
+            .one_way_latency()
+            .expect("The message should not be received before being sent");
+        assert!(latency < Duration::from_secs(5));
+        assert!(trace.sent_at() <= trace.received_at());
+    }
+    assert!(server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .drain_message_traces()
+        .is_empty());
+
+    // Traced in the other direction too
+    server
+        .endpoint_mut()
+        .send_payload_on(client_id, server_channel, vec![7; 16])
+        .unwrap();
+    let payload = loop {
+        sleep(Duration::from_millis(5));
+        let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+        if let Some(payload) = client.connection_mut().receive_payload().unwrap() {
+            break payload;
+        }
+    };
+    assert_eq!(payload, (client_channel, vec![7; 16].into()));
+    let traces = client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .connection_mut()
+        .drain_message_traces();
+    assert_eq!(traces.len(), 1);
+    assert_eq!(traces[0].trace_id(), 0);
+}
+
+#[test]
+fn replay_protected_channels() {
+    let port = 6043; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::from_configs(vec![
+                ChannelConfig::unreliable().replay_protected()
+            ])
+            .unwrap(),
+        )
+        .unwrap();
+
+    // A raw peer, free to replay its datagrams
+    let (client_end, server_end) = MemoryConnection::pair();
+    server.endpoint().add_transport_connection(server_end);
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(client_id) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::Connection(event) => Some(event.id),
+            _ => None,
+        }) {
+            break client_id;
+        }
+    };
+
+    // Nonces are stamped by the sender
+    for _ in 0..2 {
+        server
+            .endpoint_mut()
+            .send_payload(client_id, Bytes::from_static(b"fire"))
+            .unwrap();
+    }
+    for nonce in 0..2u64 {
+        let datagram = futures::executor::block_on(client_end.read_datagram()).unwrap();
+        assert_eq!(datagram[0], 0);
+        assert_eq!(datagram[1..1 + REPLAY_HEADER_LEN], nonce.to_be_bytes());
+        assert_eq!(&datagram[1 + REPLAY_HEADER_LEN..], b"fire");
+    }
+
+    // And checked by the receiver
+    let send_datagram = |nonce: u64| {
+        let mut datagram = vec![0];
+        datagram.extend_from_slice(&nonce.to_be_bytes());
+        datagram.extend_from_slice(b"fire");
+        client_end.send_datagram(Bytes::from(datagram)).unwrap();
+    };
+    let mut wait_received = || loop {
+        sleep(Duration::from_millis(5));
+        let violations = server
+            .pump()
+            .into_iter()
+            .filter_map(|event| match event {
+                QuinnetServerEvent::ProtocolViolation(event) => Some(event.violation),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let received = server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap();
+        if received.is_some() || !violations.is_empty() {
+            break (received.map(|(_, payload)| payload), violations);
+        }
+    };
+    send_datagram(5);
+    assert_eq!(wait_received(), (Some(Bytes::from_static(b"fire")), vec![]));
+    send_datagram(5);
+    assert_eq!(
+        wait_received(),
+        (None, vec![ProtocolViolation::ReplayedPayload(0)])
+    );
+    // Late but inside the window
+    send_datagram(3);
+    assert_eq!(wait_received(), (Some(Bytes::from_static(b"fire")), vec![]));
+    send_datagram(5 + REPLAY_WINDOW_LEN);
+    assert_eq!(wait_received(), (Some(Bytes::from_static(b"fire")), vec![]));
+    // Out of the window
+    send_datagram(5);
+    assert_eq!(
+        wait_received(),
+        (None, vec![ProtocolViolation::ReplayedPayload(0)])
+    );
+}
+
+#[test]
+fn tracked_unreliable_messages() {
+    let port = 6044; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    // Channel 0 is acknowledged, channel 1 is not
+    let channels = ChannelsConfiguration::from_configs(vec![
+        ChannelConfig::unreliable().acknowledged(),
+        ChannelConfig::unreliable(),
+    ])
+    .unwrap();
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels,
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+
+    // Client to server
+    let message_id = client
+        .connection_mut()
+        .send_unreliable_tracked(0, Bytes::from_static(b"input"))
+        .unwrap();
+    let mut received = None;
+    let acked = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        received = received.or(server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap());
+        if let Some(acked) = client.pump().into_iter().find_map(|event| match event {
+            QuinnetClientEvent::MessageAcked(event) => Some(event),
+            _ => None,
+        }) {
+            break acked;
+        }
+    };
+    assert_eq!(received, Some((0, Bytes::from_static(b"input"))));
+    assert_eq!((acked.channel_id, acked.message_id), (0, message_id));
+
+    // Server to client
+    let message_id = server
+        .endpoint_mut()
+        .send_unreliable_tracked(client_id, 0, Bytes::from_static(b"hit"))
+        .unwrap();
+    let mut received = None;
+    let acked = loop {
+        sleep(Duration::from_millis(5));
+        client.pump();
+        received = received.or(client.connection_mut().receive_payload().unwrap());
+        if let Some(acked) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::MessageAcked(event) => Some(event),
+            _ => None,
+        }) {
+            break acked;
+        }
+    };
+    assert_eq!(received, Some((0, Bytes::from_static(b"hit"))));
+    assert_eq!(
+        (acked.id, acked.channel_id, acked.message_id),
+        (client_id, 0, message_id)
+    );
+
+    // Only on acknowledged channels
+    assert!(matches!(
+        client
+            .connection_mut()
+            .send_unreliable_tracked(1, Bytes::from_static(b"input")),
+        Err(ClientSendError::ChannelNotAcknowledged(1))
+    ));
+    assert!(matches!(
+        server
+            .endpoint_mut()
+            .send_unreliable_tracked(client_id, 1, Bytes::from_static(b"hit")),
+        Err(ServerSendError::ChannelNotAcknowledged(1))
+    ));
+
+    // Never acknowledged
+    server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .set_conditions(Some(ClientConditions::new(Duration::ZERO).with_loss(1.)));
+    let sent_at = Instant::now();
+    let message_id = server
+        .endpoint_mut()
+        .send_unreliable_tracked(client_id, 0, Bytes::from_static(b"hit"))
+        .unwrap();
+    let lost = loop {
+        sleep(Duration::from_millis(5));
+        client.pump();
+        let events = server.pump();
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, QuinnetServerEvent::MessageAcked(_))));
+        if let Some(lost) = events.into_iter().find_map(|event| match event {
+            QuinnetServerEvent::MessageLost(event) => Some(event),
+            _ => None,
+        }) {
+            break lost;
+        }
+    };
+    assert_eq!(
+        (lost.id, lost.channel_id, lost.message_id),
+        (client_id, 0, message_id)
+    );
+    assert!(sent_at.elapsed() >= MESSAGE_ACK_TIMEOUT);
+    assert_eq!(client.connection_mut().receive_payload().unwrap(), None);
+}
+
+#[test]
+fn redundant_channels() {
+    let port = 6045; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::from_configs(vec![ChannelConfig::unreliable().redundant(3)])
+                .unwrap(),
+        )
+        .unwrap();
+
+    // A raw peer, to see each copy
+    let (client_end, server_end) = MemoryConnection::pair();
+    server.endpoint().add_transport_connection(server_end);
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(client_id) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::Connection(event) => Some(event.id),
+            _ => None,
+        }) {
+            break client_id;
+        }
+    };
+    let datagram = |sequence: u64, payload: &[u8]| {
+        let mut datagram = vec![0];
+        datagram.extend_from_slice(&sequence.to_be_bytes());
+        datagram.extend_from_slice(payload);
+        Bytes::from(datagram)
+    };
+    let read_datagram = || futures::executor::block_on(client_end.read_datagram()).unwrap();
+
+    // Sent once, then copied by the next 2 flushes
+    server
+        .endpoint_mut()
+        .send_payload(client_id, Bytes::from_static(b"dead"))
+        .unwrap();
+    assert_eq!(read_datagram(), datagram(0, b"dead"));
+    for _ in 0..3 {
+        server.endpoint().flush();
+    }
+    assert_eq!(read_datagram(), datagram(0, b"dead"));
+    assert_eq!(read_datagram(), datagram(0, b"dead"));
+    server
+        .endpoint_mut()
+        .send_payload(client_id, Bytes::from_static(b"respawn"))
+        .unwrap();
+    assert_eq!(read_datagram(), datagram(1, b"respawn"));
+
+    // The copies received are dropped silently
+    for _ in 0..3 {
+        client_end.send_datagram(datagram(7, b"dead")).unwrap();
+    }
+    client_end.send_datagram(datagram(8, b"respawn")).unwrap();
+    let mut received = Vec::new();
+    while received.last() != Some(&Bytes::from_static(b"respawn")) {
+        sleep(Duration::from_millis(5));
+        assert!(!server
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetServerEvent::ProtocolViolation(_))));
+        while let Some((_, payload)) = server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap()
+        {
+            received.push(payload);
+        }
+    }
+    assert_eq!(
+        received,
+        vec![Bytes::from_static(b"dead"), Bytes::from_static(b"respawn")]
+    );
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChatLine(String);
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Position(i32, i32);
+
+bevy_quinnet::protocol! {
+    /// Protocol of the typed channels test
+    mod game {
+        Chat: ChatLine = ChannelKind::default(),
+        Positions: Position = ChannelConfig::unreliable(),
+    }
+}
+
+#[test]
+fn protocol_typed_channels() {
+    let port = 6064; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            game::channels_configuration(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            game::channels_configuration(),
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+
+    client
+        .connection_mut()
+        .send_on(game::Chat, &ChatLine("hello".to_string()))
+        .unwrap();
+    let chat = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        let lines = server
+            .endpoint_mut()
+            .receive_from_on(client_id, game::Chat)
+            .unwrap();
+        if !lines.is_empty() {
+            break lines;
+        }
+    };
+    assert_eq!(chat, vec![ChatLine("hello".to_string())]);
+
+    server
+        .endpoint_mut()
+        .send_on(client_id, game::Chat, &ChatLine("welcome".to_string()))
+        .unwrap();
+    let chat = loop {
+        sleep(Duration::from_millis(5));
+        client.pump();
+        let lines = client.connection_mut().receive_on(game::Chat).unwrap();
+        if !lines.is_empty() {
+            break lines;
+        }
+    };
+    assert_eq!(chat, vec![ChatLine("welcome".to_string())]);
+
+    // The channels have the ids of their declaration order, the hash covers the message types
+    assert_eq!(ChannelId::from(game::Positions), 1);
+    assert_eq!(game::MESSAGE_TYPES, ["ChatLine", "Position"]);
+    assert_ne!(
+        game::hash(),
+        protocol_hash(&game::channels_configuration(), &["ChatLine", "Other"])
+    );
+}
+
+#[test]
+fn protocol_declared_in_a_function() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    pub struct Score(u32);
+
+    bevy_quinnet::protocol! {
+        mod local {
+            Scores: Score = ChannelConfig::reliable_ordered(),
+            // Named like the type of its messages
+            Position: Position = ChannelConfig::unreliable(),
+        }
+    }
+
+    assert_eq!(ChannelId::from(local::Scores), 0);
+    assert_eq!(ChannelId::from(local::Position), 1);
+    assert_eq!(local::MESSAGE_TYPES, ["Score", "Position"]);
+    assert_eq!(
+        local::hash(),
+        protocol_hash(&local::channels_configuration(), &["Score", "Position"])
+    );
+}
+
+#[test]
+fn message_bursts() {
+    let port = 6085; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    let channel = get_default_client_channel(&client_app);
+
+    // The channel task waits for messages between the bursts, each burst must wake it up
+    const BURSTS_COUNT: u32 = 20;
+    const BURST_LEN: u32 = 100;
+    let mut received = Vec::new();
+    for burst in 0..BURSTS_COUNT {
+        let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+        for index in 0..BURST_LEN {
+            client
+                .connection_mut()
+                .send_payload_on(channel, (burst * BURST_LEN + index).to_be_bytes().to_vec())
+                .unwrap();
+        }
+        let start = Instant::now();
+        while received.len() < ((burst + 1) * BURST_LEN) as usize {
+            assert!(start.elapsed() < Duration::from_secs(2));
+            sleep(Duration::from_millis(5));
+            let mut server = server_app.world_mut().resource_mut::<QuinnetServer>();
+            while let Some((_, payload)) = server
+                .endpoint_mut()
+                .receive_payload_from(client_id)
+                .unwrap()
+            {
+                received.push(u32::from_be_bytes(payload[..].try_into().unwrap()));
+            }
+        }
+    }
+    assert!(received.iter().copied().eq(0..BURSTS_COUNT * BURST_LEN));
+}
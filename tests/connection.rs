@@ -1,6 +1,75 @@
-use std::{thread::sleep, time::Duration};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    sync::Arc,
+    thread::{self, sleep},
+    time::{Duration, Instant},
+};
 
-use bevy_quinnet::{client::QuinnetClient, server::QuinnetServer};
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    prelude::{App, Events, FromWorld, Update, World},
+};
+use bevy_quinnet::{
+    client::{
+        baseline::BaselineReceiver,
+        certificate::{CertificateVerificationMode, ClientCertificate},
+        chat::ChatClient,
+        connection::{ClientEndpointConfiguration, ConnectionState, RaceOutcome},
+        lockstep::LockstepClient,
+        master::{MasterRegistration, ServerBrowser},
+        spectator::SpectatorClient,
+        ClientConfigurationError, ClientPayloadSendError, ClientSendError, QuinnetClient,
+        QuinnetClientEvent, QuinnetClientPlugin, QuinnetConnectionError,
+    },
+    server::{
+        bandwidth::BandwidthLimit,
+        baseline::{BaselineState, BaselineSync},
+        certificate::{CertificateRetrievalMode, ClientAuthentication},
+        chat::{ChatRejectedEvent, ChatServer},
+        conditions::ClientConditions,
+        id_allocation::{client_id_generation, client_id_index, ClientIdPolicy},
+        idle::IdleDetection,
+        lockstep::LockstepServer,
+        master::MasterServer,
+        send_rate::AdaptiveSendRate,
+        slow_clients::SlowClientDetection,
+        spectator::{SpectatorState, SpectatorStream},
+        status::{StatusConfiguration, DEFAULT_STATUS_ALPN},
+        transfer::{TransferKey, TransferTarget},
+        ConnectionAttemptEvent, DisconnectReason, EndpointStartError, EndpointStartedEvent,
+        EndpointStoppedEvent, ExternalEndpointConfiguration, QuinnetServer, QuinnetServerEvent,
+        QuinnetServerPlugin, RefusalReason, ServerClientDataError, ServerEndpointConfiguration,
+        ServerInstanceError, ServerSendError, ServerSpectatorError, ServerTransferError,
+        TransferTokenError,
+    },
+    shared::{
+        certificate::CertificateFingerprint,
+        channels::{
+            ChannelConfig, ChannelKind, ChannelsConfiguration, ACK_HEADER_LEN, REPLAY_HEADER_LEN,
+        },
+        chat::{ChatEvent, ChatMessage, ChatRejection, ChatTarget},
+        close::{CloseCode, CloseStage, USER_CLOSE_CODE_START},
+        congestion::{CongestionEventKind, CongestionMonitor},
+        error::{AsyncChannelError, ChannelError, ForwardingError, InviteCodeError},
+        forwarding::{ForwardedClient, ForwardingKey},
+        hardening::{HardeningConfiguration, ProtocolViolation},
+        invite::{InviteCode, InviteKey},
+        lockstep::LockstepBundle,
+        master::ServerInfo,
+        memory::{MemoryBudget, MemoryBudgetPolicy},
+        qos::{Dscp, QosConfiguration},
+        socket::{SocketConfiguration, MIN_MAX_UDP_PAYLOAD_SIZE},
+        stats_history::StatsHistoryConfig,
+        tick::NetworkTick,
+        transport::{memory::MemoryConnection, TransportConnection},
+        QUINNET_ALPN,
+    },
+};
+use bytes::Bytes;
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn_proto::PathStats;
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+use tokio::{io::AsyncWriteExt, sync::mpsc};
 
 // https://github.com/rust-lang/rust/issues/46379
 pub use utils::*;
@@ -171,3 +240,5211 @@ fn reconnection() {
         2
     );
 }
+
+#[test]
+fn reused_local_socket() {
+    let port = 6050; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let config = default_client_configuration_builder(port)
+        .with_reused_local_socket()
+        .build()
+        .unwrap();
+    assert!(config.reuses_local_socket());
+    client
+        .open_connection(
+            config,
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let wait_connected = |server: &mut QuinnetServer, client: &mut QuinnetClient| loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        if client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)))
+        {
+            break client.connection().local_addr().unwrap();
+        }
+    };
+    let local_addr = wait_connected(&mut server, &mut client);
+    assert_ne!(local_addr.port(), 0);
+
+    // The port assigned by the OS is kept by the reconnection
+    client.connection_mut().disconnect().unwrap();
+    client.connection_mut().reconnect().unwrap();
+    assert_eq!(wait_connected(&mut server, &mut client), local_addr);
+}
+
+#[test]
+fn qos_marking() {
+    let port = 6053; // TODO Use port 0 and retrieve the port used by the server.
+
+    assert_eq!(Dscp::new(46), Some(Dscp::EXPEDITED_FORWARDING));
+    assert_eq!(Dscp::new(64), None);
+    let qos = QosConfiguration::default()
+        .with_dscp(Dscp::EXPEDITED_FORWARDING)
+        .with_ecn(false);
+    assert!(QosConfiguration::default().is_ecn_enabled());
+    assert!(!qos.is_ecn_enabled());
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port).with_qos(qos),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let config = default_client_configuration_builder(port)
+        .with_qos(qos)
+        .build()
+        .unwrap();
+    assert_eq!(config.qos().dscp(), Some(Dscp::EXPEDITED_FORWARDING));
+    client
+        .open_connection(
+            config,
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    // Marked packets go through in both directions
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client.connection().client_id() {
+            break client_id;
+        }
+    };
+    let message = SharedMessage::TestMessage("marked".to_string());
+    client
+        .connection_mut()
+        .send_message(message.clone())
+        .unwrap();
+    server
+        .endpoint_mut()
+        .send_message(client_id, message.clone())
+        .unwrap();
+    let (mut server_received, mut client_received) = (None, None);
+    while server_received.is_none() || client_received.is_none() {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some((_, message)) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            server_received = Some(message);
+        }
+        if let Some((_, message)) = client
+            .connection_mut()
+            .receive_message::<SharedMessage>()
+            .unwrap()
+        {
+            client_received = Some(message);
+        }
+    }
+    assert_eq!(server_received, Some(message.clone()));
+    assert_eq!(client_received, Some(message));
+}
+
+#[test]
+fn socket_tuning() {
+    let port = 6054; // TODO Use port 0 and retrieve the port used by the server.
+
+    // Out of bounds payload sizes are refused
+    let too_small = SocketConfiguration::default().with_max_udp_payload_size(1000);
+    assert!(matches!(
+        default_client_configuration_builder(port)
+            .with_socket(too_small)
+            .build(),
+        Err(ClientConfigurationError::InvalidMaxUdpPayloadSize(1000))
+    ));
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    assert!(matches!(
+        server.start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port).with_socket(too_small),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        ),
+        Err(EndpointStartError::InvalidMaxUdpPayloadSize(1000))
+    ));
+
+    let socket = SocketConfiguration::default()
+        .with_receive_buffer_size(1 << 20)
+        .with_send_buffer_size(1 << 20)
+        .with_max_udp_payload_size(MIN_MAX_UDP_PAYLOAD_SIZE)
+        .with_segmentation_offload(false);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port).with_socket(socket),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let config = default_client_configuration_builder(port)
+        .with_socket(socket)
+        .build()
+        .unwrap();
+    assert!(!config.socket().is_segmentation_offload_enabled());
+    client
+        .open_connection(
+            config,
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client.connection().client_id() {
+            break client_id;
+        }
+    };
+
+    // A message larger than the max UDP payload size is split into several packets
+    let message = SharedMessage::TestMessage("tuned".repeat(1000));
+    client
+        .connection_mut()
+        .send_message(message.clone())
+        .unwrap();
+    let received = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some((_, message)) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            break message;
+        }
+    };
+    assert_eq!(received, message);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn sharded_endpoint() {
+    let port = 6055; // TODO Use port 0 and retrieve the port used by the server.
+    let shards = 4;
+    let client_count = 16;
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port)
+                .with_shards(std::num::NonZeroUsize::new(shards).unwrap()),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    assert_eq!(server.endpoint().shard_count(), shards);
+    for _ in 0..client_count {
+        client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+    }
+    while server.endpoint().clients().len() < client_count {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+    }
+
+    // One logical endpoint: a single id space, the clients spread over the shards
+    let counts = server.endpoint().shard_client_counts();
+    assert_eq!(counts.len(), shards);
+    assert_eq!(counts.iter().sum::<usize>(), client_count);
+    let mut client_ids = server.endpoint().clients();
+    client_ids.sort();
+    client_ids.dedup();
+    assert_eq!(client_ids.len(), client_count);
+    for client_id in client_ids {
+        let connection = server.endpoint().get_connection(client_id).unwrap();
+        assert!(connection.shard() < shards);
+        server
+            .endpoint_mut()
+            .send_message(client_id, SharedMessage::TestMessage("sharded".to_string()))
+            .unwrap();
+    }
+    let mut received = 0;
+    while received < client_count {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        for (_, connection) in client.connections_mut() {
+            while let Some((_, message)) = connection.receive_message::<SharedMessage>().unwrap() {
+                assert_eq!(message, SharedMessage::TestMessage("sharded".to_string()));
+                received += 1;
+            }
+        }
+    }
+}
+
+#[test]
+fn endpoint_not_accepting() {
+    let port = 6007; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut server_app = start_simple_server_app(port);
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .set_accepting(false);
+
+    let mut client_app = start_simple_client_app(port);
+    loop {
+        client_app.update();
+        server_app.update();
+        let state = client_app
+            .world()
+            .resource::<QuinnetClient>()
+            .connection()
+            .state();
+        assert_ne!(state, ConnectionState::Connected);
+        if state == ConnectionState::Disconnected {
+            break;
+        }
+    }
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<ServerTestData>()
+            .connection_events_received,
+        0
+    );
+
+    let mut server = server_app.world_mut().resource_mut::<QuinnetServer>();
+    assert!(!server.endpoint().is_accepting());
+    server.endpoint_mut().set_accepting(true);
+
+    client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .connection_mut()
+        .reconnect()
+        .unwrap();
+    wait_for_client_connected(&mut client_app, &mut server_app);
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<ServerTestData>()
+            .connection_events_received,
+        1
+    );
+}
+
+#[test]
+fn endpoint_not_accepting_close_code() {
+    let port = 6099; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    server.endpoint_mut().set_accepting(false);
+
+    let connection_id = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let start = Instant::now();
+    let err = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        let failed = client.pump().into_iter().find_map(|event| match event {
+            QuinnetClientEvent::ConnectionFailed(event) => Some(event),
+            QuinnetClientEvent::Connection(_) => panic!("The refused client connected"),
+            QuinnetClientEvent::ConnectionLost(_) => {
+                panic!("The refused client lost its connection")
+            }
+            _ => None,
+        });
+        if let Some(event) = failed {
+            assert_eq!(event.id, connection_id);
+            break event.err;
+        }
+    };
+    assert!(matches!(
+        err,
+        QuinnetConnectionError::ConnectionClosed(CloseCode::NotAccepting)
+    ));
+
+    // A single failure is raised for the refused connection
+    sleep(Duration::from_millis(50));
+    assert!(!client.pump().iter().any(|event| matches!(
+        event,
+        QuinnetClientEvent::ConnectionFailed(_) | QuinnetClientEvent::ConnectionLost(_)
+    )));
+    assert_eq!(
+        client.get_connection_by_id(connection_id).unwrap().state(),
+        ConnectionState::Disconnected
+    );
+}
+
+#[test]
+fn endpoint_restart_on_new_port() {
+    let port = 6008; // TODO Use port 0 and retrieve the port used by the server.
+    let new_port = 6009;
+
+    let mut client_app = start_simple_client_app(port);
+    let mut server_app = start_simple_server_app(port);
+    wait_for_client_connected(&mut client_app, &mut server_app);
+
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .restart_endpoint(ServerEndpointConfiguration::from_ip(
+            LOCAL_BIND_IP,
+            new_port,
+        ))
+        .unwrap();
+    server_app.update();
+
+    let stopped_events = server_app
+        .world()
+        .resource::<Events<EndpointStoppedEvent>>();
+    assert_eq!(stopped_events.len(), 1);
+    let started_events = server_app
+        .world()
+        .resource::<Events<EndpointStartedEvent>>();
+    assert_eq!(
+        started_events
+            .iter_current_update_events()
+            .last()
+            .expect("A start event should have been raised")
+            .local_addr
+            .port(),
+        new_port
+    );
+
+    // Wait for the client to notice the disconnection
+    loop {
+        client_app.update();
+        if client_app
+            .world()
+            .resource::<QuinnetClient>()
+            .connection()
+            .state()
+            == ConnectionState::Disconnected
+        {
+            break;
+        }
+    }
+
+    let mut client = client_app.world_mut().resource_mut::<QuinnetClient>();
+    client.close_all_connections();
+    client
+        .open_connection(
+            default_client_configuration(new_port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    wait_for_client_connected(&mut client_app, &mut server_app);
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<ServerTestData>()
+            .connection_events_received,
+        2
+    );
+}
+
+/// Answers a single STUN Binding request with the address it was sent from
+fn spawn_fake_stun_server() -> SocketAddr {
+    let socket = UdpSocket::bind((SERVER_IP, 0)).unwrap();
+    let stun_addr = socket.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut request = [0; 512];
+        let (_, from) = socket.recv_from(&mut request).unwrap();
+        let SocketAddr::V6(from_v6) = from else {
+            panic!("Expected an IPv6 request");
+        };
+        let mut response = vec![0x01, 0x01, 0, 24];
+        // Magic cookie & transaction id
+        response.extend_from_slice(&request[4..20]);
+        // XOR-MAPPED-ADDRESS
+        response.extend_from_slice(&[0x00, 0x20, 0, 20, 0, 0x02]);
+        response.extend_from_slice(&(from.port() ^ 0x2112).to_be_bytes());
+        for (i, octet) in from_v6.ip().octets().iter().enumerate() {
+            response.push(octet ^ request[4 + i]);
+        }
+        socket.send_to(&response, from).unwrap();
+    });
+    stun_addr
+}
+
+#[test]
+fn local_and_external_addresses() {
+    let stun_addr = spawn_fake_stun_server();
+
+    let mut server_app = App::new();
+    server_app
+        .add_plugins((
+            ScheduleRunnerPlugin::default(),
+            QuinnetServerPlugin::default(),
+        ))
+        .insert_resource(ServerTestData::default())
+        .add_systems(Update, handle_server_events);
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, 0).with_stun_server(stun_addr),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let local_addr = server_app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .local_addr();
+    assert_ne!(local_addr.port(), 0);
+
+    let external_addr = loop {
+        server_app.update();
+        if let Some(addr) = server_app
+            .world()
+            .resource::<QuinnetServer>()
+            .endpoint()
+            .external_addr()
+        {
+            break addr;
+        }
+    };
+    assert_eq!(
+        external_addr,
+        SocketAddr::new(SERVER_IP.into(), local_addr.port())
+    );
+
+    let mut client_app = start_simple_client_app(local_addr.port());
+    wait_for_client_connected(&mut client_app, &mut server_app);
+    let client_local_addr = client_app
+        .world()
+        .resource::<QuinnetClient>()
+        .connection()
+        .local_addr()
+        .expect("A connected client should have a local address");
+    assert_ne!(client_local_addr.port(), 0);
+}
+
+#[test]
+fn memory_transport_alongside_quic() {
+    let port = 6010; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut server_app = start_simple_server_app(port);
+    let mut quic_client_app = start_simple_client_app(port);
+    let quic_client_id = wait_for_client_connected(&mut quic_client_app, &mut server_app);
+
+    let mut memory_client_app = App::new();
+    memory_client_app.add_plugins((
+        ScheduleRunnerPlugin::default(),
+        QuinnetClientPlugin::default(),
+    ));
+    let (client_end, server_end) = MemoryConnection::pair();
+    server_app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .add_transport_connection(server_end);
+    memory_client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .open_transport_connection(client_end.clone(), ChannelsConfiguration::default())
+        .unwrap();
+    let memory_client_id = wait_for_client_connected(&mut memory_client_app, &mut server_app);
+
+    assert_ne!(memory_client_id, quic_client_id);
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<ServerTestData>()
+            .connection_events_received,
+        2
+    );
+    let memory_connection = memory_client_app.world().resource::<QuinnetClient>();
+    assert_eq!(memory_connection.connection().local_addr(), None);
+    assert!(memory_connection
+        .connection()
+        .endpoint_configuration()
+        .is_none());
+    assert_eq!(
+        server_app
+            .world()
+            .resource::<QuinnetServer>()
+            .endpoint()
+            .get_connection(memory_client_id)
+            .unwrap()
+            .remote_address(),
+        None
+    );
+
+    // Both clients are handled the same way by the server
+    let mut msg_counter = 0;
+    for (client_id, client_app) in [
+        (quic_client_id, &mut quic_client_app),
+        (memory_client_id, &mut memory_client_app),
+    ] {
+        let client_channel = get_default_client_channel(client_app);
+        send_and_test_client_message(
+            client_id,
+            client_channel,
+            client_app,
+            &mut server_app,
+            &mut msg_counter,
+        );
+        let server_channel = get_default_server_channel(&server_app);
+        send_and_test_server_message(
+            client_id,
+            server_channel,
+            &mut server_app,
+            client_app,
+            &mut msg_counter,
+        );
+    }
+
+    memory_client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .close_all_connections();
+    loop {
+        server_app.update();
+        if server_app
+            .world()
+            .resource::<ServerTestData>()
+            .connection_lost_events_received
+            == 1
+        {
+            break;
+        }
+    }
+    assert!(client_end.is_closed());
+    let server = server_app.world().resource::<QuinnetServer>();
+    assert_eq!(server.endpoint().clients(), vec![quic_client_id]);
+}
+
+#[test]
+fn manual_pump_without_plugins() {
+    let port = 6019; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    assert!(matches!(
+        server.pump()[..],
+        [QuinnetServerEvent::EndpointStarted(_)]
+    ));
+
+    let connection_id = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let mut server_client_id = None;
+    let mut client_connected = false;
+    while server_client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                server_client_id = Some(event.id);
+            }
+        }
+        for event in client.pump() {
+            if let QuinnetClientEvent::Connection(event) = event {
+                assert_eq!(event.id, connection_id);
+                client_connected = true;
+            }
+        }
+    }
+    let client_id = server_client_id.unwrap();
+    assert_eq!(client.connection().client_id(), Some(client_id));
+
+    client
+        .connection_mut()
+        .send_message(SharedMessage::TestMessage("pumped".to_string()))
+        .unwrap();
+    let message = loop {
+        sleep(Duration::from_millis(5));
+        assert!(server.pump().is_empty());
+        if let Some((_, message)) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            break message;
+        }
+    };
+    assert_eq!(message, SharedMessage::TestMessage("pumped".to_string()));
+
+    client.close_all_connections();
+    loop {
+        sleep(Duration::from_millis(5));
+        if let [QuinnetServerEvent::ConnectionLost(event)] = &server.pump()[..] {
+            assert_eq!(event.id, client_id);
+            assert!(matches!(
+                event.reason,
+                DisconnectReason::ClosedByClient { .. }
+            ));
+            break;
+        }
+    }
+}
+
+#[test]
+fn no_message_before_connection_event() {
+    let port = 6020; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    // The server greets the client as soon as it connects, the client is not updated yet
+    // The endpoint start may be raised in the same pump as the connection
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(client_id) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::Connection(event) => Some(event.id),
+            _ => None,
+        }) {
+            break client_id;
+        }
+    };
+    server
+        .endpoint_mut()
+        .send_message(client_id, SharedMessage::TestMessage("hello".to_string()))
+        .unwrap();
+    sleep(Duration::from_millis(100));
+    assert!(client.connection_mut().receive_payload().unwrap().is_none());
+    assert!(client.connection_mut().drain_payloads().unwrap().is_empty());
+
+    // Delivered once the connection event is raised, along with the initial max datagram size
+    assert!(matches!(
+        client.pump()[..],
+        [
+            QuinnetClientEvent::Connection(_),
+            QuinnetClientEvent::MaxDatagramSizeChanged(_)
+        ]
+    ));
+    assert_eq!(
+        client
+            .connection_mut()
+            .receive_message::<SharedMessage>()
+            .unwrap(),
+        Some((0, SharedMessage::TestMessage("hello".to_string())))
+    );
+}
+
+#[test]
+fn connection_lost_reasons() {
+    let port = 6021; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    for _ in 0..2 {
+        client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+    }
+    let mut client_ids = Vec::new();
+    while client_ids.len() < 2 {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_ids.push(event.id);
+            }
+        }
+    }
+
+    server
+        .endpoint_mut()
+        .disconnect_client(client_ids[0])
+        .unwrap();
+    match &server.pump()[..] {
+        [QuinnetServerEvent::ConnectionLost(event)] => {
+            assert_eq!(event.id, client_ids[0]);
+            assert_eq!(event.reason, DisconnectReason::DisconnectedByServer);
+        }
+        _ => panic!("Expected a single connection lost event"),
+    }
+
+    // The remaining client is reported before the endpoint stop, and only once
+    server.stop_endpoint().unwrap();
+    match &server.pump()[..] {
+        [QuinnetServerEvent::ConnectionLost(event), QuinnetServerEvent::EndpointStopped(_)] => {
+            assert_eq!(event.id, client_ids[1]);
+            assert_eq!(event.reason, DisconnectReason::EndpointStopped);
+        }
+        _ => panic!("Expected a connection lost event and the endpoint stop"),
+    }
+    sleep(Duration::from_millis(50));
+    assert!(server.pump().is_empty());
+}
+
+#[test]
+fn application_close_codes() {
+    let port = 6023; // TODO Use port 0 and retrieve the port used by the server.
+
+    for code in [
+        CloseCode::Closed,
+        CloseCode::ServerShutdown,
+        CloseCode::Kicked,
+        CloseCode::ProtocolMismatch,
+        CloseCode::Idle,
+        CloseCode::ProtocolViolation,
+        CloseCode::Transferred,
+        CloseCode::MemoryBudgetExceeded,
+        CloseCode::SlowClient,
+        CloseCode::NotAccepting,
+        CloseCode::User(0),
+        CloseCode::User(u32::MAX),
+    ] {
+        assert_eq!(CloseCode::from(code.code()), code);
+    }
+    assert_eq!(CloseCode::User(3).code(), USER_CLOSE_CODE_START + 3);
+    assert_eq!(CloseCode::from(42), CloseCode::Unknown(42));
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let connect = |server: &mut QuinnetServer, client: &mut QuinnetClient| {
+        let connection_id = client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+        let mut client_id = None;
+        let mut client_connected = false;
+        while client_id.is_none() || !client_connected {
+            sleep(Duration::from_millis(5));
+            for event in server.pump() {
+                if let QuinnetServerEvent::Connection(event) = event {
+                    client_id = Some(event.id);
+                }
+            }
+            client_connected |= client
+                .pump()
+                .iter()
+                .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+        }
+        (connection_id, client_id.unwrap())
+    };
+    let wait_client_close_code = |client: &mut QuinnetClient| loop {
+        sleep(Duration::from_millis(5));
+        if let [QuinnetClientEvent::ConnectionLost(event)] = &client.pump()[..] {
+            break event.close_code;
+        }
+    };
+
+    // Kicked by the server with a user code
+    let (_, client_id) = connect(&mut server, &mut client);
+    server
+        .endpoint_mut()
+        .disconnect_client_with_code(client_id, CloseCode::User(7))
+        .unwrap();
+    assert_eq!(
+        wait_client_close_code(&mut client),
+        Some(CloseCode::User(7))
+    );
+
+    // Closed by the client with a user code
+    let (connection_id, client_id) = connect(&mut server, &mut client);
+    client
+        .get_connection_mut_by_id(connection_id)
+        .unwrap()
+        .disconnect_with_code(CloseCode::User(3))
+        .unwrap();
+    loop {
+        sleep(Duration::from_millis(5));
+        if let [QuinnetServerEvent::ConnectionLost(event)] = &server.pump()[..] {
+            assert_eq!(event.id, client_id);
+            assert!(matches!(
+                event.reason,
+                DisconnectReason::ClosedByClient {
+                    code: CloseCode::User(3),
+                    ..
+                }
+            ));
+            break;
+        }
+    }
+
+    // Endpoint stopped
+    connect(&mut server, &mut client);
+    server.stop_endpoint().unwrap();
+    assert_eq!(
+        wait_client_close_code(&mut client),
+        Some(CloseCode::ServerShutdown)
+    );
+}
+
+#[test]
+fn virtual_hosts() {
+    let port = 6028; // TODO Use port 0 and retrieve the port used by the server.
+    let shard_name = "shard-a.localhost";
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let shard_channels = ChannelsConfiguration::from_configs(vec![
+        ChannelConfig::reliable_ordered(),
+        ChannelConfig::reliable_unordered(),
+    ])
+    .unwrap();
+    server
+        .endpoint_mut()
+        .set_virtual_host(shard_name, shard_channels.clone());
+
+    let mut connect = |config: ClientEndpointConfiguration, channels: ChannelsConfiguration| {
+        let connection_id = client
+            .open_connection(
+                config,
+                CertificateVerificationMode::SkipVerification,
+                channels,
+            )
+            .unwrap();
+        let mut connection_event = None;
+        let mut client_connected = false;
+        while connection_event.is_none() || !client_connected {
+            sleep(Duration::from_millis(5));
+            for event in server.pump() {
+                if let QuinnetServerEvent::Connection(event) = event {
+                    connection_event = Some(event);
+                }
+            }
+            client_connected |= client
+                .pump()
+                .iter()
+                .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+        }
+        (connection_id, connection_event.unwrap())
+    };
+
+    // The SNI is only sent when connecting with a hostname
+    let (shard_connection_id, shard_event) = connect(
+        default_client_configuration_builder(port)
+            .with_server_name(shard_name)
+            .build()
+            .unwrap(),
+        shard_channels,
+    );
+    assert_eq!(shard_event.server_name.as_deref(), Some(shard_name));
+    let (default_connection_id, default_event) = connect(
+        default_client_configuration(port),
+        ChannelsConfiguration::default(),
+    );
+    assert_eq!(default_event.server_name, None);
+
+    let endpoint = server.endpoint_mut();
+    assert_eq!(endpoint.host_clients(shard_name), vec![shard_event.id]);
+    assert_eq!(
+        endpoint
+            .get_connection(shard_event.id)
+            .unwrap()
+            .server_name(),
+        Some(shard_name)
+    );
+
+    // Channels of the virtual host are only opened for its clients
+    let message = SharedMessage::TestMessage("shard".to_string());
+    endpoint
+        .send_message_on(shard_event.id, 1, message.clone())
+        .unwrap();
+    assert!(endpoint
+        .send_message_on(default_event.id, 1, message.clone())
+        .is_err());
+
+    // Endpoint channels do not apply to the clients of the virtual host
+    endpoint.close_channel(0).unwrap();
+    endpoint
+        .send_message_on(shard_event.id, 0, message.clone())
+        .unwrap();
+    assert!(endpoint
+        .send_message_on(default_event.id, 0, message.clone())
+        .is_err());
+
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        sleep(Duration::from_millis(5));
+        let connection = client
+            .get_connection_mut_by_id(shard_connection_id)
+            .unwrap();
+        while let Some(channel_message) = connection.receive_message::<SharedMessage>().unwrap() {
+            received.push(channel_message);
+        }
+    }
+    received.sort_by_key(|(channel_id, _)| *channel_id);
+    assert_eq!(received, vec![(0, message.clone()), (1, message)]);
+    assert!(client
+        .get_connection_mut_by_id(default_connection_id)
+        .unwrap()
+        .try_receive_payload()
+        .is_none());
+}
+
+#[test]
+fn protocol_hardening() {
+    let port = 6029; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port).with_hardening(
+                HardeningConfiguration::strict()
+                    .with_max_frame_len(1_024)
+                    .with_decode_error_budget(3),
+            ),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    // A raw peer, free to send malformed traffic
+    let (client_end, server_end) = MemoryConnection::pair();
+    server.endpoint().add_transport_connection(server_end);
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(client_id) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::Connection(event) => Some(event.id),
+            _ => None,
+        }) {
+            break client_id;
+        }
+    };
+    let send_frame = |frame: &[u8]| {
+        futures::executor::block_on(async {
+            let mut stream = client_end.open_uni().await.unwrap();
+            stream.write_all(frame).await.unwrap();
+        })
+    };
+    let mut wait_violation = || loop {
+        sleep(Duration::from_millis(5));
+        if let Some(event) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::ProtocolViolation(event) => Some(event),
+            _ => None,
+        }) {
+            assert_eq!(event.id, client_id);
+            break (event.violation, event.count);
+        }
+    };
+
+    // Length of 4096 bytes on channel 0
+    send_frame(&[0, 0, 0x10, 0, 0]);
+    assert_eq!(
+        wait_violation(),
+        (
+            ProtocolViolation::FrameTooLarge {
+                len: 4_096,
+                max_len: 1_024
+            },
+            1
+        )
+    );
+    send_frame(&[0, 0, 0, 0, 0]);
+    assert_eq!(wait_violation(), (ProtocolViolation::EmptyFrame, 2));
+    client_end.send_datagram(Bytes::from_static(&[0])).unwrap();
+    assert_eq!(wait_violation(), (ProtocolViolation::MalformedDatagram, 3));
+    assert!(!client_end.is_closed());
+
+    // Over budget
+    client_end
+        .send_datagram(Bytes::from_static(&[9, 1, 2, 3]))
+        .unwrap();
+    let reason = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(reason) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::ConnectionLost(event) => Some(event.reason),
+            _ => None,
+        }) {
+            break reason;
+        }
+    };
+    assert_eq!(reason, DisconnectReason::ProtocolViolation);
+    assert!(server.endpoint().clients().is_empty());
+    while !client_end.is_closed() {
+        sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn idle_clients_detection() {
+    let port = 6030; // TODO Use port 0 and retrieve the port used by the server.
+    let idle_timeout = Duration::from_millis(300);
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    server
+        .endpoint_mut()
+        .set_idle_detection(Some(IdleDetection::new(idle_timeout).on_channels([0])));
+
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+    let wait_idle = |server: &mut QuinnetServer| loop {
+        sleep(Duration::from_millis(5));
+        if let Some(event) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::ClientIdle(event) => Some(event),
+            _ => None,
+        }) {
+            break event;
+        }
+    };
+
+    let event = wait_idle(&mut server);
+    assert_eq!(event.id, client_id);
+    assert!(event.idle_for >= idle_timeout);
+    // Raised once per idle period
+    for _ in 0..20 {
+        sleep(Duration::from_millis(5));
+        assert!(!server
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetServerEvent::ClientIdle(_))));
+    }
+
+    // Active again, then idle and kicked
+    client
+        .connection_mut()
+        .send_message_on(0, SharedMessage::TestMessage("active".to_string()))
+        .unwrap();
+    while server
+        .endpoint_mut()
+        .receive_message_from::<SharedMessage>(client_id)
+        .unwrap()
+        .is_none()
+    {
+        sleep(Duration::from_millis(5));
+    }
+    server
+        .endpoint_mut()
+        .set_idle_detection(Some(IdleDetection::new(idle_timeout).kick()));
+    let mut idle_event = None;
+    let mut lost_event = None;
+    while lost_event.is_none() {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            match event {
+                QuinnetServerEvent::ClientIdle(event) => idle_event = Some(event),
+                QuinnetServerEvent::ConnectionLost(event) => lost_event = Some(event),
+                _ => (),
+            }
+        }
+    }
+    assert_eq!(idle_event.unwrap().id, client_id);
+    assert_eq!(lost_event.unwrap().reason, DisconnectReason::Idle);
+    assert!(server.endpoint().clients().is_empty());
+    let close_code = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(close_code) = client.pump().into_iter().find_map(|event| match event {
+            QuinnetClientEvent::ConnectionLost(event) => Some(event.close_code),
+            _ => None,
+        }) {
+            break close_code;
+        }
+    };
+    assert_eq!(close_code, Some(CloseCode::Idle));
+}
+
+#[test]
+fn memory_budget() {
+    let port = 6094; // TODO Use port 0 and retrieve the port used by the server.
+    const MAX_BYTES: usize = 4_000;
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    let mut channels = ChannelsConfiguration::default();
+    let unreliable = channels.add(ChannelKind::Unreliable).unwrap();
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+    // The received payloads stay buffered until read
+    server.endpoint_mut().set_frame_coherent_receive(true);
+    server
+        .endpoint_mut()
+        .set_memory_budget(Some(MemoryBudget::new(
+            MAX_BYTES,
+            MemoryBudgetPolicy::DropUnreliable,
+        )));
+
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels,
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+    let flood = |client: &mut QuinnetClient| {
+        for _ in 0..50 {
+            client.connection_mut().send_payload(vec![0; 200]).unwrap();
+        }
+    };
+    let wait_exceeded = |server: &mut QuinnetServer| {
+        let start = Instant::now();
+        loop {
+            assert!(start.elapsed() < Duration::from_secs(2));
+            sleep(Duration::from_millis(5));
+            let events = server.pump();
+            if let Some(event) = events.iter().find_map(|event| match event {
+                QuinnetServerEvent::MemoryBudgetExceeded(event) => Some(*event),
+                _ => None,
+            }) {
+                break (event, events);
+            }
+        }
+    };
+    let refused = |result| {
+        matches!(
+            result,
+            Err(ServerSendError::ChannelSendError(
+                AsyncChannelError::MemoryBudgetExceeded
+            ))
+        )
+    };
+
+    // The unread payloads of a client exceed its budget, its unreliable messages are refused
+    flood(&mut client);
+    let (event, _) = wait_exceeded(&mut server);
+    assert_eq!(event.id, client_id);
+    assert_eq!(event.budget.max_bytes(), MAX_BYTES);
+    assert!(event.used > MAX_BYTES);
+    assert!(refused(server.endpoint_mut().send_payload_on(
+        client_id,
+        unreliable,
+        vec![0; 10]
+    )));
+    server
+        .endpoint_mut()
+        .send_payload_on(client_id, 0, vec![0; 10])
+        .unwrap();
+
+    // All its messages are refused
+    server
+        .endpoint_mut()
+        .set_memory_budget(Some(MemoryBudget::new(
+            MAX_BYTES,
+            MemoryBudgetPolicy::Block,
+        )));
+    assert!(refused(server.endpoint_mut().send_payload_on(
+        client_id,
+        0,
+        vec![0; 10]
+    )));
+
+    // Back under the budget once read
+    let mut received = 0;
+    while received < 50 {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        while server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap()
+            .is_some()
+        {
+            received += 1;
+        }
+    }
+    let start = Instant::now();
+    while server
+        .endpoint()
+        .get_connection(client_id)
+        .unwrap()
+        .memory_usage()
+        > MAX_BYTES
+    {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+    }
+    server
+        .endpoint_mut()
+        .send_payload_on(client_id, 0, vec![0; 10])
+        .unwrap();
+    assert!(!server
+        .pump()
+        .iter()
+        .any(|event| matches!(event, QuinnetServerEvent::MemoryBudgetExceeded(_))));
+
+    // The client budget refuses a payload larger than the budget
+    client
+        .connection_mut()
+        .set_memory_budget(Some(MemoryBudget::new(100, MemoryBudgetPolicy::Block)));
+    assert!(matches!(
+        client.connection_mut().send_payload(vec![0; 200]),
+        Err(ClientPayloadSendError::SendError(
+            ClientSendError::ChannelSendError(AsyncChannelError::MemoryBudgetExceeded)
+        ))
+    ));
+    assert!(client
+        .pump()
+        .iter()
+        .any(|event| matches!(event, QuinnetClientEvent::MemoryBudgetExceeded(event) if event.budget.max_bytes() == 100)));
+    client.connection_mut().set_memory_budget(None);
+
+    // Disconnected once exceeding its budget
+    server
+        .endpoint_mut()
+        .set_memory_budget(Some(MemoryBudget::new(
+            MAX_BYTES,
+            MemoryBudgetPolicy::Disconnect,
+        )));
+    flood(&mut client);
+    let (event, mut events) = wait_exceeded(&mut server);
+    assert_eq!(event.id, client_id);
+    let lost_event = loop {
+        if let Some(event) = events.into_iter().find_map(|event| match event {
+            QuinnetServerEvent::ConnectionLost(event) => Some(event),
+            _ => None,
+        }) {
+            break event;
+        }
+        sleep(Duration::from_millis(5));
+        events = server.pump();
+    };
+    assert_eq!(lost_event.reason, DisconnectReason::MemoryBudgetExceeded);
+    assert!(server.endpoint().clients().is_empty());
+    let close_code = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(close_code) = client.pump().into_iter().find_map(|event| match event {
+            QuinnetClientEvent::ConnectionLost(event) => Some(event.close_code),
+            _ => None,
+        }) {
+            break close_code;
+        }
+    };
+    assert_eq!(close_code, Some(CloseCode::MemoryBudgetExceeded));
+}
+
+#[test]
+fn slow_clients() {
+    let port = 6095; // TODO Use port 0 and retrieve the port used by the server.
+    const THRESHOLD: usize = 1_000_000;
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    let mut channels = ChannelsConfiguration::default();
+    let unreliable = channels.add(ChannelKind::Unreliable).unwrap();
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+    server.endpoint_mut().set_slow_client_detection(Some(
+        SlowClientDetection::new(THRESHOLD)
+            .skip_unreliable()
+            .throttle_send_rates(),
+    ));
+
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels,
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+
+    // The client does not read its messages while the server floods it, until the queues of the server fill up
+    let flood_until_slow = |server: &mut QuinnetServer| {
+        let start = Instant::now();
+        loop {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            for _ in 0..10 {
+                // Refused once the queue is full
+                let _ = server
+                    .endpoint_mut()
+                    .send_payload(client_id, vec![0; 64_000]);
+            }
+            sleep(Duration::from_millis(5));
+            let events = server.pump();
+            if let Some(event) = events.iter().find_map(|event| match event {
+                QuinnetServerEvent::SlowClient(event) => Some(*event),
+                _ => None,
+            }) {
+                break (event, events);
+            }
+        }
+    };
+    let (event, _) = flood_until_slow(&mut server);
+    assert_eq!(event.id, client_id);
+    assert!(event.pending_bytes > THRESHOLD);
+    let connection = server.endpoint().get_connection(client_id).unwrap();
+    assert!(connection.is_slow());
+    assert!(connection.pending_reliable_bytes() > THRESHOLD);
+
+    // Its unreliable messages are skipped, and its send rates throttled
+    let sent_bytes = connection.sent_bytes_count();
+    server
+        .endpoint_mut()
+        .send_payload_on(client_id, unreliable, vec![0; 10])
+        .unwrap();
+    let connection = server.endpoint().get_connection(client_id).unwrap();
+    assert_eq!(connection.sent_bytes_count(), sent_bytes);
+    let mut send_rate = AdaptiveSendRate::new(1., 30.).with_sample_interval(Duration::ZERO);
+    send_rate.update(server.endpoint());
+    assert!(send_rate.rate(client_id) < 30.);
+
+    // Recovered once the client reads its messages
+    let start = Instant::now();
+    let recovered = loop {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        sleep(Duration::from_millis(5));
+        client.pump();
+        while client.connection_mut().receive_payload().unwrap().is_some() {}
+        if let Some(event) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::SlowClientRecovered(event) => Some(event),
+            _ => None,
+        }) {
+            break event;
+        }
+    };
+    assert_eq!(recovered.id, client_id);
+    assert!(!server
+        .endpoint()
+        .get_connection(client_id)
+        .unwrap()
+        .is_slow());
+
+    // Disconnected once slow for too long, while the server keeps flooding it
+    server.endpoint_mut().set_slow_client_detection(Some(
+        SlowClientDetection::new(THRESHOLD).disconnect_after(Duration::from_millis(100)),
+    ));
+    let (_, mut events) = flood_until_slow(&mut server);
+    let start = Instant::now();
+    let lost_event = loop {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        if let Some(event) = events.into_iter().find_map(|event| match event {
+            QuinnetServerEvent::ConnectionLost(event) => Some(event),
+            _ => None,
+        }) {
+            break event;
+        }
+        for _ in 0..10 {
+            let _ = server
+                .endpoint_mut()
+                .send_payload(client_id, vec![0; 64_000]);
+        }
+        sleep(Duration::from_millis(5));
+        events = server.pump();
+    };
+    assert_eq!(lost_event.reason, DisconnectReason::SlowClient);
+    assert!(server.endpoint().clients().is_empty());
+}
+
+#[test]
+fn bandwidth_limits() {
+    let port = 6031; // TODO Use port 0 and retrieve the port used by the server.
+    let outbound_window = Duration::from_millis(500);
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let inbound_limit = BandwidthLimit::inbound(1_000, Duration::from_secs(5));
+    let outbound_limit = BandwidthLimit::outbound(1_000, outbound_window);
+    server
+        .endpoint_mut()
+        .set_bandwidth_limits([inbound_limit, outbound_limit]);
+
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+    let message = SharedMessage::TestMessage("x".repeat(800));
+    let bandwidth_events = |server: &mut QuinnetServer| {
+        server
+            .pump()
+            .into_iter()
+            .filter_map(|event| match event {
+                QuinnetServerEvent::ClientBandwidthExceeded(event) => Some(event),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Outbound, raised once while over the limit
+    server
+        .endpoint_mut()
+        .send_message(client_id, message.clone())
+        .unwrap();
+    assert!(bandwidth_events(&mut server).is_empty());
+    server
+        .endpoint_mut()
+        .send_message(client_id, message.clone())
+        .unwrap();
+    let events = bandwidth_events(&mut server);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].id, client_id);
+    assert_eq!(events[0].limit, outbound_limit);
+    assert!(events[0].bytes > 1_600);
+    assert!(bandwidth_events(&mut server).is_empty());
+
+    // Raised again after going back under the limit
+    sleep(outbound_window + Duration::from_millis(100));
+    assert!(bandwidth_events(&mut server).is_empty());
+    let connection = server.endpoint().get_connection(client_id).unwrap();
+    assert_eq!(
+        connection.bandwidth_usage(outbound_window).outbound_bytes,
+        0
+    );
+    assert!(
+        connection
+            .bandwidth_usage(Duration::from_secs(5))
+            .outbound_bytes
+            > 1_600
+    );
+    for _ in 0..2 {
+        server
+            .endpoint_mut()
+            .send_message(client_id, message.clone())
+            .unwrap();
+    }
+    let events = bandwidth_events(&mut server);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].limit, outbound_limit);
+
+    // Inbound, accounted once received by the server
+    for _ in 0..2 {
+        client
+            .connection_mut()
+            .send_message(message.clone())
+            .unwrap();
+    }
+    let mut received = 0;
+    while received < 2 {
+        sleep(Duration::from_millis(5));
+        while server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+            .is_some()
+        {
+            received += 1;
+        }
+    }
+    let events = bandwidth_events(&mut server);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].limit, inbound_limit);
+    assert!(
+        server
+            .endpoint()
+            .get_connection(client_id)
+            .unwrap()
+            .bandwidth_usage(Duration::from_secs(5))
+            .inbound_bytes
+            > 1_600
+    );
+}
+
+#[test]
+fn stats_history() {
+    let port = 6082; // TODO Use port 0 and retrieve the port used by the server.
+    let config = StatsHistoryConfig {
+        sample_interval: Duration::from_millis(20),
+        capacity: 4,
+    };
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    server.endpoint_mut().set_stats_history(Some(config));
+
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client.connection_mut().set_stats_history(Some(config));
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+
+    // Samples are taken during the updates, the oldest are dropped past the capacity
+    let message = SharedMessage::TestMessage("x".repeat(800));
+    for _ in 0..10 {
+        server
+            .endpoint_mut()
+            .send_message(client_id, message.clone())
+            .unwrap();
+        sleep(Duration::from_millis(25));
+        server.pump();
+        client.pump();
+    }
+    let history = server
+        .endpoint()
+        .get_connection(client_id)
+        .unwrap()
+        .stats_history()
+        .unwrap();
+    assert_eq!(history.samples().len(), 4);
+    assert!(history.samples().any(|sample| sample.sent_kbps > 0.));
+    assert!(history
+        .samples()
+        .zip(history.samples().skip(1))
+        .all(|(older, newer)| older.at < newer.at));
+    let latest = history.latest().unwrap();
+    assert!(latest.rtt > Duration::ZERO);
+    assert_eq!(
+        history
+            .window(Duration::ZERO, latest.at)
+            .collect::<Vec<_>>(),
+        vec![latest]
+    );
+    let client_history = client.connection().stats_history().unwrap();
+    assert_eq!(client_history.samples().len(), 4);
+    assert!(client_history
+        .samples()
+        .any(|sample| sample.received_kbps > 0.));
+
+    // Disabled, the histories are dropped
+    server.endpoint_mut().set_stats_history(None);
+    assert!(server
+        .endpoint()
+        .get_connection(client_id)
+        .unwrap()
+        .stats_history()
+        .is_none());
+}
+
+#[test]
+fn external_endpoints_with_protocol_routing() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _guard = runtime.enter();
+
+    let certified_key = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let cert = CertificateDer::from(certified_key.cert);
+    let key = PrivatePkcs8KeyDer::from(certified_key.key_pair.serialize_der());
+    let mut server_crypto = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(vec![cert.clone()], key.into())
+    .unwrap();
+    server_crypto.alpn_protocols = vec![b"quinnet".to_vec(), b"status".to_vec()];
+    let server_endpoint = quinn::Endpoint::server(
+        quinn::ServerConfig::with_crypto(Arc::new(
+            QuicServerConfig::try_from(server_crypto).unwrap(),
+        )),
+        SocketAddr::new(LOCAL_BIND_IP.into(), 0),
+    )
+    .unwrap();
+    let port = server_endpoint.local_addr().unwrap().port();
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    let (other_connections_send, mut other_connections_recv) = mpsc::unbounded_channel();
+    server
+        .start_external_endpoint(
+            ExternalEndpointConfiguration::new(server_endpoint.clone())
+                .with_protocol_routing(vec![b"quinnet".to_vec()], other_connections_send),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    assert!(matches!(
+        server.pump()[..],
+        [QuinnetServerEvent::EndpointStarted(event)] if event.local_addr.port() == port
+    ));
+
+    // A Quinnet client and another service share the client endpoint
+    let client_endpoint =
+        quinn::Endpoint::client(SocketAddr::new(LOCAL_BIND_IP.into(), 0)).unwrap();
+    client
+        .open_connection(
+            ClientEndpointConfiguration::builder()
+                .with_server_ip(SERVER_IP, port)
+                .with_alpn_protocols(vec![b"quinnet".to_vec()])
+                .with_endpoint(client_endpoint.clone())
+                .build()
+                .unwrap(),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut server_client_id = None;
+    let mut client_connected = false;
+    while server_client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                server_client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    assert_eq!(
+        client.connection().local_addr(),
+        Some(client_endpoint.local_addr().unwrap())
+    );
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert).unwrap();
+    let mut status_crypto = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    status_crypto.alpn_protocols = vec![b"status".to_vec()];
+    let status_config =
+        quinn::ClientConfig::new(Arc::new(QuicClientConfig::try_from(status_crypto).unwrap()));
+    let status_connection = runtime
+        .block_on(
+            client_endpoint
+                .connect_with(
+                    status_config,
+                    SocketAddr::new(SERVER_IP.into(), port),
+                    "localhost",
+                )
+                .unwrap(),
+        )
+        .unwrap();
+    let routed = runtime.block_on(other_connections_recv.recv()).unwrap();
+    assert_eq!(
+        routed.remote_address().port(),
+        client_endpoint.local_addr().unwrap().port()
+    );
+    assert_eq!(
+        routed
+            .handshake_data()
+            .unwrap()
+            .downcast::<quinn::crypto::rustls::HandshakeData>()
+            .unwrap()
+            .protocol,
+        Some(b"status".to_vec())
+    );
+    sleep(Duration::from_millis(20));
+    // Only the attempt is seen by Quinnet, the protocol is known once the handshake completes: the routed connection is never a client
+    assert!(!server
+        .pump()
+        .iter()
+        .any(|event| matches!(event, QuinnetServerEvent::Connection(_))));
+    assert_eq!(server.endpoint().clients(), vec![server_client_id.unwrap()]);
+
+    // Stopping Quinnet leaves the shared endpoint and the other connections open
+    server.stop_endpoint().unwrap();
+    sleep(Duration::from_millis(20));
+    assert!(status_connection.close_reason().is_none());
+    assert!(routed.close_reason().is_none());
+}
+
+/// Minimal HTTP/3 GET on a new connection, returns the QPACK field section and the body of the response
+fn http3_get(
+    runtime: &tokio::runtime::Runtime,
+    client_endpoint: &quinn::Endpoint,
+    client_config: quinn::ClientConfig,
+    port: u16,
+) -> (Vec<u8>, String) {
+    fn read_varint(buf: &[u8], pos: &mut usize) -> u64 {
+        let len = 1 << (buf[*pos] >> 6);
+        let mut value = (buf[*pos] & 0x3f) as u64;
+        for byte in &buf[*pos + 1..*pos + len] {
+            value = (value << 8) | *byte as u64;
+        }
+        *pos += len;
+        value
+    }
+    runtime.block_on(async {
+        let connection = client_endpoint
+            .connect_with(
+                client_config,
+                SocketAddr::new(SERVER_IP.into(), port),
+                "localhost",
+            )
+            .unwrap()
+            .await
+            .unwrap();
+        let (mut send, mut recv) = connection.open_bi().await.unwrap();
+        // HEADERS frame: :method GET, :scheme https, :path /, from the QPACK static table
+        send.write_all(&[0x01, 0x05, 0x00, 0x00, 0xD1, 0xD7, 0xC1])
+            .await
+            .unwrap();
+        send.finish().unwrap();
+        let response = recv.read_to_end(4_096).await.unwrap();
+        connection.close(0u32.into(), b"done");
+
+        let mut pos = 0;
+        let mut headers = Vec::new();
+        let mut body = String::new();
+        while pos < response.len() {
+            let frame_type = read_varint(&response, &mut pos);
+            let len = read_varint(&response, &mut pos) as usize;
+            let payload = &response[pos..pos + len];
+            match frame_type {
+                0x01 => headers = payload.to_vec(),
+                0x00 => body.push_str(std::str::from_utf8(payload).unwrap()),
+                _ => (),
+            }
+            pos += len;
+        }
+        (headers, body)
+    })
+}
+
+#[test]
+fn status_endpoint() {
+    let port = 6033; // TODO Use port 0 and retrieve the port used by the server.
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _guard = runtime.enter();
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    let server_cert = server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port)
+                .with_status(StatusConfiguration::new("1.2.3")),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: "localhost".to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    server.pump();
+
+    client
+        .open_connection(
+            default_client_configuration_builder(port)
+                .with_alpn_protocols(vec![QUINNET_ALPN.to_vec()])
+                .build()
+                .unwrap(),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut client_connected = false;
+    let mut server_connected = false;
+    while !client_connected || !server_connected {
+        sleep(Duration::from_millis(5));
+        server_connected |= server
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetServerEvent::Connection(_)));
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(server_cert.cert_chain[0].clone()).unwrap();
+    let mut crypto = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    crypto.alpn_protocols = vec![DEFAULT_STATUS_ALPN.to_vec()];
+    let status_config =
+        quinn::ClientConfig::new(Arc::new(QuicClientConfig::try_from(crypto).unwrap()));
+    let status_endpoint =
+        quinn::Endpoint::client(SocketAddr::new(LOCAL_BIND_IP.into(), 0)).unwrap();
+
+    // Indexed :status 200 and content-type application/json
+    let (headers, body) = http3_get(&runtime, &status_endpoint, status_config.clone(), port);
+    assert_eq!(headers, vec![0x00, 0x00, 0xD9, 0xEE]);
+    assert!(body.starts_with("{\"status\":\"ok\",\"players\":1,\"uptime_secs\":"));
+    assert!(body.ends_with(",\"version\":\"1.2.3\"}"));
+    sleep(Duration::from_millis(20));
+    // The status requests are only seen as connection attempts
+    assert!(server
+        .pump()
+        .iter()
+        .all(|event| matches!(event, QuinnetServerEvent::ConnectionAttempt(_))));
+    assert_eq!(server.endpoint().clients().len(), 1);
+
+    // Indexed :status 503
+    server.endpoint_mut().set_accepting(false);
+    let (headers, body) = http3_get(&runtime, &status_endpoint, status_config, port);
+    assert_eq!(headers, vec![0x00, 0x00, 0xDC, 0xEE]);
+    assert!(body.starts_with("{\"status\":\"not_accepting\",\"players\":1,"));
+}
+
+#[test]
+fn server_transfer() {
+    let old_port = 6034; // TODO Use port 0 and retrieve the port used by the server.
+    let new_port = 6035;
+    let target_addr = SocketAddr::new(SERVER_IP.into(), new_port);
+
+    let mut world = World::new();
+    let mut old_server = QuinnetServer::from_world(&mut world);
+    let mut new_server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    for (server, port) in [(&mut old_server, old_port), (&mut new_server, new_port)] {
+        server
+            .start_endpoint(
+                ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+                CertificateRetrievalMode::GenerateSelfSigned {
+                    server_hostname: SERVER_IP.to_string(),
+                },
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+    }
+    client
+        .open_connection(
+            default_client_configuration(old_port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut old_client_id = None;
+    let mut client_connected = false;
+    while old_client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in old_server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                old_client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let old_client_id = old_client_id.unwrap();
+
+    let target = TransferTarget::new(target_addr);
+    assert!(matches!(
+        old_server.endpoint_mut().transfer_client(
+            old_client_id,
+            target.clone(),
+            b"session".to_vec()
+        ),
+        Err(ServerTransferError::NoTransferKey)
+    ));
+    let key = TransferKey::new(b"a secret shared by the zone servers");
+    old_server
+        .endpoint_mut()
+        .set_transfer_key(Some(key.clone()));
+    new_server.endpoint_mut().set_transfer_key(Some(key));
+    assert!(matches!(
+        old_server
+            .endpoint_mut()
+            .transfer_client(42, target.clone(), Vec::new()),
+        Err(ServerTransferError::UnknownClient(42))
+    ));
+    let ticket = old_server
+        .endpoint_mut()
+        .transfer_client(old_client_id, target.clone(), b"session".to_vec())
+        .unwrap();
+    assert_eq!(ticket.origin_client_id(), old_client_id);
+    assert_eq!(ticket.target_addr(), target_addr);
+    assert!(matches!(
+        old_server
+            .endpoint_mut()
+            .transfer_client(old_client_id, target, Vec::new()),
+        Err(ServerTransferError::AlreadyTransferring(_))
+    ));
+
+    // Client: transferred then connected, old server: client lost, new server: client transferred
+    let mut transfer_event = None;
+    let mut reconnected = false;
+    let mut lost_event = None;
+    let mut new_client_id = None;
+    let mut new_transfer_event = None;
+    while !reconnected || lost_event.is_none() || new_transfer_event.is_none() {
+        sleep(Duration::from_millis(5));
+        for event in client.pump() {
+            match event {
+                QuinnetClientEvent::ConnectionTransfer(event) => transfer_event = Some(event),
+                QuinnetClientEvent::Connection(_) => {
+                    assert!(transfer_event.is_some());
+                    reconnected = true;
+                }
+                QuinnetClientEvent::ConnectionLost(_) => panic!("Unexpected connection loss"),
+                _ => (),
+            }
+        }
+        for event in old_server.pump() {
+            if let QuinnetServerEvent::ConnectionLost(event) = event {
+                lost_event = Some(event);
+            }
+        }
+        for event in new_server.pump() {
+            match event {
+                QuinnetServerEvent::Connection(event) => new_client_id = Some(event.id),
+                QuinnetServerEvent::ClientTransfer(event) => new_transfer_event = Some(event),
+                QuinnetServerEvent::ClientTransferRejected(event) => {
+                    panic!("Unexpected transfer rejection: {}", event.err)
+                }
+                _ => (),
+            }
+        }
+    }
+    let transfer_event = transfer_event.unwrap();
+    assert_eq!(transfer_event.target_addr, target_addr);
+    assert_eq!(transfer_event.server_hostname, SERVER_IP.to_string());
+    let lost_event = lost_event.unwrap();
+    assert_eq!(lost_event.id, old_client_id);
+    assert_eq!(lost_event.reason, DisconnectReason::Transferred);
+    assert!(old_server.endpoint().clients().is_empty());
+    let new_transfer_event = new_transfer_event.unwrap();
+    assert_eq!(Some(new_transfer_event.id), new_client_id);
+    assert_eq!(new_transfer_event.ticket, ticket);
+    assert_eq!(new_transfer_event.ticket.payload(), b"session");
+
+    // Still connected to the new server after the transfer
+    let new_client_id = new_client_id.unwrap();
+    client
+        .connection_mut()
+        .send_message_on(0, SharedMessage::TestMessage("transferred".to_string()))
+        .unwrap();
+    let message = loop {
+        sleep(Duration::from_millis(5));
+        if let Some((_, message)) = new_server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(new_client_id)
+            .unwrap()
+        {
+            break message;
+        }
+    };
+    assert_eq!(
+        message,
+        SharedMessage::TestMessage("transferred".to_string())
+    );
+
+    // Token signed with another key
+    new_server
+        .endpoint_mut()
+        .set_transfer_key(Some(TransferKey::new(b"another secret")));
+    new_server
+        .endpoint_mut()
+        .transfer_client(
+            new_client_id,
+            TransferTarget::new(SocketAddr::new(SERVER_IP.into(), old_port)),
+            Vec::new(),
+        )
+        .unwrap();
+    let rejected_event = loop {
+        sleep(Duration::from_millis(5));
+        client.pump();
+        new_server.pump();
+        if let Some(event) = old_server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::ClientTransferRejected(event) => Some(event),
+            QuinnetServerEvent::ClientTransfer(_) => panic!("Unexpected transfer"),
+            _ => None,
+        }) {
+            break event;
+        }
+    };
+    assert_eq!(rejected_event.err, TransferTokenError::InvalidSignature);
+}
+
+/// Certificate of a private authority, and a certificate it signed for each of `names`
+fn private_authority(
+    names: &[&str],
+) -> (
+    CertificateDer<'static>,
+    Vec<(rcgen::Certificate, rcgen::KeyPair)>,
+) {
+    let ca_key = rcgen::KeyPair::generate().unwrap();
+    let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+    let signed = names
+        .iter()
+        .map(|name| {
+            let key = rcgen::KeyPair::generate().unwrap();
+            let cert = rcgen::CertificateParams::new(vec![name.to_string()])
+                .unwrap()
+                .signed_by(&key, &ca_cert, &ca_key)
+                .unwrap();
+            (cert, key)
+        })
+        .collect();
+    (ca_cert.der().clone(), signed)
+}
+
+#[test]
+fn server_to_server_mutual_authentication() {
+    let port = 6036; // TODO Use port 0 and retrieve the port used by the server.
+
+    let (authority, mut signed) = private_authority(&[&SERVER_IP.to_string(), "gateway"]);
+    let (gateway_cert, gateway_key) = signed.pop().unwrap();
+    let (zone_cert, zone_key) = signed.pop().unwrap();
+    let dir = std::env::temp_dir().join(format!("quinnet_mtls_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cert_file = dir.join("zone.pem");
+    let key_file = dir.join("zone.key");
+    std::fs::write(&cert_file, zone_cert.pem()).unwrap();
+    std::fs::write(&key_file, zone_key.serialize_pem()).unwrap();
+
+    let mut world = World::new();
+    let mut zone = QuinnetServer::from_world(&mut world);
+    let mut gateway = QuinnetClient::from_world(&mut world);
+    let mut anonymous = QuinnetClient::from_world(&mut world);
+    zone.start_endpoint(
+        ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port)
+            .with_client_authentication(ClientAuthentication::new(vec![authority.clone()])),
+        CertificateRetrievalMode::LoadFromFile {
+            cert_file: cert_file.to_string_lossy().into_owned(),
+            key_file: key_file.to_string_lossy().into_owned(),
+        },
+        ChannelsConfiguration::default(),
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The gateway authenticates the zone server with the private authority, and presents its own certificate
+    gateway
+        .open_connection(
+            default_client_configuration_builder(port)
+                .with_client_certificate(ClientCertificate::new(
+                    vec![gateway_cert.der().clone()],
+                    PrivatePkcs8KeyDer::from(gateway_key.serialize_der()).into(),
+                ))
+                .build()
+                .unwrap(),
+            CertificateVerificationMode::SignedBy(vec![authority.clone()]),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut gateway_id = None;
+    let mut gateway_connected = false;
+    while gateway_id.is_none() || !gateway_connected {
+        sleep(Duration::from_millis(5));
+        for event in zone.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                gateway_id = Some(event.id);
+            }
+        }
+        gateway_connected |= gateway
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let peer_certificates = zone
+        .endpoint()
+        .get_connection(gateway_id.unwrap())
+        .unwrap()
+        .peer_certificates()
+        .expect("The gateway should have presented its certificate");
+    assert_eq!(peer_certificates, vec![gateway_cert.der().clone()]);
+
+    // Channels work as between a client and a server
+    gateway
+        .connection_mut()
+        .send_payload(Bytes::from_static(b"hello zone"))
+        .unwrap();
+    let mut received = None;
+    while received.is_none() {
+        sleep(Duration::from_millis(5));
+        zone.pump();
+        gateway.pump();
+        received = zone
+            .endpoint_mut()
+            .try_receive_payload_from(gateway_id.unwrap());
+    }
+    assert_eq!(received.unwrap().1, Bytes::from_static(b"hello zone"));
+
+    // A client without certificate is refused
+    anonymous
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SignedBy(vec![authority]),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut refused = false;
+    while !refused {
+        sleep(Duration::from_millis(5));
+        for event in zone.pump() {
+            assert!(
+                !matches!(event, QuinnetServerEvent::Connection(_)),
+                "The anonymous client should not be accepted"
+            );
+        }
+        refused = anonymous.pump().iter().any(|event| {
+            matches!(
+                event,
+                QuinnetClientEvent::ConnectionFailed(_) | QuinnetClientEvent::ConnectionLost(_)
+            )
+        });
+    }
+}
+
+#[test]
+fn server_name_override() {
+    let port = 6049; // TODO Use port 0 and retrieve the port used by the server.
+    let server_name = "game.example.com";
+
+    let (authority, mut signed) = private_authority(&[server_name]);
+    let (cert, key) = signed.pop().unwrap();
+    let dir = std::env::temp_dir().join(format!("quinnet_sni_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cert_file = dir.join("server.pem");
+    let key_file = dir.join("server.key");
+    std::fs::write(&cert_file, cert.pem()).unwrap();
+    std::fs::write(&key_file, key.serialize_pem()).unwrap();
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::LoadFromFile {
+                cert_file: cert_file.to_string_lossy().into_owned(),
+                key_file: key_file.to_string_lossy().into_owned(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Connecting by IP address, the certificate is verified against the DNS name it carries
+    let named = client
+        .open_connection(
+            default_client_configuration_builder(port)
+                .with_server_name(server_name)
+                .build()
+                .unwrap(),
+            CertificateVerificationMode::SignedBy(vec![authority.clone()]),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let unnamed = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SignedBy(vec![authority]),
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut named_connected = false;
+    let mut unnamed_failed = false;
+    while !named_connected || !unnamed_failed {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        for event in client.pump() {
+            match event {
+                QuinnetClientEvent::Connection(event) => {
+                    assert_eq!(event.id, named);
+                    named_connected = true;
+                }
+                QuinnetClientEvent::ConnectionFailed(event) => {
+                    assert_eq!(event.id, unnamed);
+                    unnamed_failed = true;
+                }
+                _ => {}
+            }
+        }
+    }
+    assert_eq!(
+        client
+            .get_connection_by_id(named)
+            .unwrap()
+            .endpoint_configuration()
+            .unwrap()
+            .server_name(),
+        server_name
+    );
+}
+
+#[test]
+fn gateway_client_forwarding() {
+    let gateway_port = 6037; // TODO Use port 0 and retrieve the port used by the server.
+    let internal_port = 6038;
+
+    let mut world = World::new();
+    let mut gateway = QuinnetServer::from_world(&mut world);
+    let mut internal = QuinnetServer::from_world(&mut world);
+    let mut player = QuinnetClient::from_world(&mut world);
+    let mut gateway_links = QuinnetClient::from_world(&mut world);
+
+    for (server, port) in [(&mut gateway, gateway_port), (&mut internal, internal_port)] {
+        server
+            .start_endpoint(
+                ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+                CertificateRetrievalMode::GenerateSelfSigned {
+                    server_hostname: SERVER_IP.to_string(),
+                },
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+    }
+    let key = ForwardingKey::new(b"a secret shared by the gateway and its servers");
+    internal
+        .endpoint_mut()
+        .set_forwarding_key(Some(key.clone()));
+
+    player
+        .open_connection(
+            default_client_configuration(gateway_port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut player_id = None;
+    while player_id.is_none() {
+        sleep(Duration::from_millis(5));
+        player.pump();
+        for event in gateway.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                player_id = Some(event.id);
+            }
+        }
+    }
+    let player_id = player_id.unwrap();
+    let player_addr = gateway
+        .endpoint()
+        .get_connection(player_id)
+        .unwrap()
+        .remote_address()
+        .unwrap();
+
+    // The gateway forwards the player to the internal server, then a forger tries without the key
+    let forwarded =
+        ForwardedClient::new(player_addr).with_identity(player_id.to_le_bytes().to_vec());
+    let forwarded_link = gateway_links
+        .open_connection(
+            default_client_configuration_builder(internal_port)
+                .with_forwarded_client(key, forwarded.clone())
+                .build()
+                .unwrap(),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    gateway_links
+        .open_connection(
+            default_client_configuration_builder(internal_port)
+                .with_forwarded_client(ForwardingKey::new(b"a guessed secret"), forwarded.clone())
+                .build()
+                .unwrap(),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut forwarded_event = None;
+    let mut rejected_event = None;
+    while forwarded_event.is_none() || rejected_event.is_none() {
+        sleep(Duration::from_millis(5));
+        gateway_links.pump();
+        for event in internal.pump() {
+            match event {
+                QuinnetServerEvent::ClientForwarded(event) => forwarded_event = Some(event),
+                QuinnetServerEvent::ClientForwardingRejected(event) => rejected_event = Some(event),
+                _ => {}
+            }
+        }
+    }
+    let forwarded_event = forwarded_event.unwrap();
+    assert_eq!(forwarded_event.client.client_addr(), player_addr);
+    assert_eq!(forwarded_event.client.identity(), player_id.to_le_bytes());
+    let rejected_event = rejected_event.unwrap();
+    assert_eq!(rejected_event.err, ForwardingError::InvalidSignature);
+
+    let forwarded_connection = internal
+        .endpoint()
+        .get_connection(forwarded_event.id)
+        .unwrap();
+    assert_eq!(forwarded_connection.client_address(), Some(player_addr));
+    assert_eq!(
+        forwarded_connection.forwarded_client(),
+        Some(&forwarded_event.client)
+    );
+    let rejected_connection = internal
+        .endpoint()
+        .get_connection(rejected_event.id)
+        .unwrap();
+    assert!(rejected_connection.forwarded_client().is_none());
+    assert_eq!(
+        rejected_connection.client_address(),
+        rejected_connection.remote_address()
+    );
+
+    // The link to the internal server carries the traffic of the player
+    gateway_links
+        .get_connection_mut_by_id(forwarded_link)
+        .unwrap()
+        .send_payload(Bytes::from_static(b"relayed"))
+        .unwrap();
+    let mut received = None;
+    while received.is_none() {
+        sleep(Duration::from_millis(5));
+        gateway_links.pump();
+        internal.pump();
+        received = internal
+            .endpoint_mut()
+            .try_receive_payload_from(forwarded_event.id);
+    }
+    assert_eq!(received.unwrap().1, Bytes::from_static(b"relayed"));
+}
+
+#[test]
+fn client_conditions() {
+    let port = 6042; // TODO Use port 0 and retrieve the port used by the server.
+    let latency = Duration::from_millis(300);
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    // Channel 0 is reliable, channel 1 unreliable
+    let channels =
+        ChannelsConfiguration::from_types(vec![ChannelKind::default(), ChannelKind::Unreliable])
+            .unwrap();
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels,
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+    let connection = server.endpoint_mut().get_connection_mut(client_id).unwrap();
+    connection.set_conditions(Some(ClientConditions::new(latency)));
+    assert_eq!(connection.conditions().unwrap().latency(), latency);
+
+    // Outbound, held by the server
+    let message = SharedMessage::TestMessage("to the client".to_string());
+    let sent_at = Instant::now();
+    server
+        .endpoint_mut()
+        .send_message_on(client_id, 0, message.clone())
+        .unwrap();
+    let received = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some((_, received)) = client
+            .connection_mut()
+            .receive_message::<SharedMessage>()
+            .unwrap()
+        {
+            break received;
+        }
+    };
+    assert_eq!(received, message);
+    assert!(sent_at.elapsed() >= latency);
+
+    // Inbound, held by the server
+    let message = SharedMessage::TestMessage("to the server".to_string());
+    let sent_at = Instant::now();
+    client
+        .connection_mut()
+        .send_message_on(0, message.clone())
+        .unwrap();
+    let received = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        if let Some((_, received)) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            break received;
+        }
+    };
+    assert_eq!(received, message);
+    assert!(sent_at.elapsed() >= latency);
+
+    // Losses only on the unreliable channels
+    server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .set_conditions(Some(ClientConditions::new(Duration::ZERO).with_loss(1.)));
+    server
+        .endpoint_mut()
+        .send_message_on(client_id, 1, SharedMessage::TestMessage("lost".to_string()))
+        .unwrap();
+    server
+        .endpoint_mut()
+        .send_message_on(client_id, 0, message.clone())
+        .unwrap();
+    let received = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(received) = client
+            .connection_mut()
+            .receive_message::<SharedMessage>()
+            .unwrap()
+        {
+            break received;
+        }
+    };
+    assert_eq!(received, (0, message.clone()));
+
+    // Restoring the real link
+    let connection = server.endpoint_mut().get_connection_mut(client_id).unwrap();
+    connection.set_conditions(None);
+    assert!(connection.conditions().is_none());
+    client
+        .connection_mut()
+        .send_message_on(1, message.clone())
+        .unwrap();
+    let received = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(received) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            break received;
+        }
+    };
+    assert_eq!(received, (1, message));
+}
+
+#[test]
+fn client_configuration_builder() {
+    // Defaults from the server
+    let config = ClientEndpointConfiguration::builder()
+        .with_server_ip(Ipv6Addr::LOCALHOST, 6000)
+        .build()
+        .unwrap();
+    assert_eq!(config.server_name(), "::1");
+    assert_eq!(
+        config.local_bind_addr(),
+        SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0)
+    );
+
+    // A host is resolved to an address reachable from the local bind address, and names the server
+    let config = ClientEndpointConfiguration::builder()
+        .with_server_host("localhost", 6000)
+        .with_local_bind_ip(Ipv4Addr::UNSPECIFIED, 0)
+        .build()
+        .unwrap();
+    assert_eq!(
+        config.server_addr(),
+        SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 6000)
+    );
+    assert_eq!(config.server_name(), "localhost");
+    let config = ClientEndpointConfiguration::builder()
+        .with_server_ip(Ipv4Addr::LOCALHOST, 6000)
+        .with_server_name("shard-1.example.com")
+        .build()
+        .unwrap();
+    assert_eq!(config.server_name(), "shard-1.example.com");
+
+    // Invalid combinations
+    let server =
+        || ClientEndpointConfiguration::builder().with_server_ip(Ipv6Addr::LOCALHOST, 6000);
+    assert!(matches!(
+        ClientEndpointConfiguration::builder().build(),
+        Err(ClientConfigurationError::MissingServerAddress)
+    ));
+    assert!(matches!(
+        server().with_server_name("not a name").build(),
+        Err(ClientConfigurationError::InvalidServerName(_))
+    ));
+    assert!(matches!(
+        server()
+            .with_local_bind_ip(Ipv4Addr::UNSPECIFIED, 0)
+            .build(),
+        Err(ClientConfigurationError::AddressFamilyMismatch { .. })
+    ));
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _guard = runtime.enter();
+    let endpoint = quinn::Endpoint::client(SocketAddr::new(LOCAL_BIND_IP.into(), 0)).unwrap();
+    assert!(matches!(
+        server()
+            .with_endpoint(endpoint)
+            .with_local_bind_ip(LOCAL_BIND_IP, 0)
+            .build(),
+        Err(ClientConfigurationError::LocalBindWithEndpoint)
+    ));
+    assert!(matches!(
+        server().with_alpn_protocols(vec![Vec::new()]).build(),
+        Err(ClientConfigurationError::InvalidAlpnProtocol(0))
+    ));
+    assert!(matches!(
+        server()
+            .with_idle_timeout(Duration::from_secs(5))
+            .with_keep_alive_interval(Duration::from_secs(5))
+            .build(),
+        Err(ClientConfigurationError::KeepAliveNotBelowIdleTimeout { .. })
+    ));
+    assert!(matches!(
+        server().with_datagram_receive_buffer_size(0).build(),
+        Err(ClientConfigurationError::ZeroDatagramBuffer)
+    ));
+}
+
+#[test]
+fn receive_timestamps() {
+    let port = 6048; // TODO Use port 0 and retrieve the port used by the server.
+    let latency = Duration::from_millis(50);
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+
+    // The arrival of the message, not the time it is read
+    let message = SharedMessage::TestMessage("fire".to_string());
+    let sent_at = Instant::now();
+    client
+        .connection_mut()
+        .send_message_on(0, message.clone())
+        .unwrap();
+    sleep(2 * latency);
+    let (channel_id, received, timestamp) = loop {
+        server.pump();
+        if let Some(received) = server
+            .endpoint_mut()
+            .receive_timestamped_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            break received;
+        }
+        sleep(Duration::from_millis(5));
+    };
+    assert_eq!((channel_id, received), (0, message.clone()));
+    assert!(timestamp.received_at() >= sent_at);
+    assert!(timestamp.since_received() >= latency);
+    assert!(timestamp.estimated_sent_at() <= timestamp.received_at());
+
+    // The added latency of the conditions counts in the estimate
+    server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .set_conditions(Some(ClientConditions::new(latency)));
+    let sent_at = Instant::now();
+    client
+        .connection_mut()
+        .send_message_on(0, message.clone())
+        .unwrap();
+    let timestamp = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        if let Some((_, timestamp)) = server
+            .endpoint_mut()
+            .receive_all_timestamped_from_on(client_id, 0)
+            .unwrap()
+            .next()
+        {
+            break timestamp;
+        }
+    };
+    assert!(timestamp.received_at() >= sent_at + latency);
+    assert!(timestamp.one_way_latency() >= latency);
+    assert!(timestamp.estimated_sent_at() <= timestamp.received_at() - latency);
+}
+
+#[test]
+fn connection_race() {
+    let port = 6051; // TODO Use port 0 and retrieve the port used by the server.
+
+    // Bound but never answering, its handshake can't finish
+    let silent_port = 6052;
+    let _silent = UdpSocket::bind((LOCAL_BIND_IP, silent_port)).unwrap();
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let id = client
+        .open_connection_race(
+            vec![
+                default_client_configuration(silent_port),
+                default_client_configuration(port),
+            ],
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut race = None;
+    let mut connected = false;
+    while !connected {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        for event in client.pump() {
+            match event {
+                QuinnetClientEvent::ConnectionRace(event) => race = Some(event),
+                QuinnetClientEvent::Connection(event) => {
+                    assert!(race.is_some(), "The race is reported before the connection");
+                    assert_eq!(event.id, id);
+                    connected = true;
+                }
+                QuinnetClientEvent::ConnectionFailed(event) => {
+                    panic!("Race failed: {}", event.err)
+                }
+                _ => {}
+            }
+        }
+    }
+    let race = race.unwrap();
+    assert_eq!(race.id, id);
+    assert_eq!(race.attempts.len(), 2);
+    assert_eq!(race.attempts[0].server_addr.port(), silent_port);
+    assert!(matches!(race.attempts[0].outcome, RaceOutcome::Aborted));
+    assert_eq!(race.attempts[1].server_addr.port(), port);
+    assert!(race.attempts[1].outcome.is_won());
+    // The winner is kept to reconnect
+    assert_eq!(
+        client
+            .get_connection_by_id(id)
+            .unwrap()
+            .endpoint_configuration()
+            .unwrap()
+            .server_addr()
+            .port(),
+        port
+    );
+
+    // Nothing to race
+    let id = client
+        .open_connection_race(
+            vec![],
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut race = None;
+    let err = loop {
+        sleep(Duration::from_millis(5));
+        let mut failed = None;
+        for event in client.pump() {
+            match event {
+                QuinnetClientEvent::ConnectionRace(event) => race = Some(event),
+                QuinnetClientEvent::ConnectionFailed(event) if event.id == id => {
+                    failed = Some(event.err)
+                }
+                _ => {}
+            }
+        }
+        if let Some(err) = failed {
+            break err;
+        }
+    };
+    assert!(matches!(err, QuinnetConnectionError::NoRaceWinner));
+    assert!(race.unwrap().attempts.is_empty());
+}
+
+#[test]
+fn close_timeline() {
+    let port = 6056; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let closed_by_client = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let kicked = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let kicked_client_id = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        let connected = |id| client.get_connection_by_id(id).unwrap().client_id();
+        if let (Some(_), Some(client_id)) = (connected(closed_by_client), connected(kicked)) {
+            break client_id;
+        }
+    };
+
+    // The stages of a connection closed by the client are raised once it is removed
+    for i in 0..10 {
+        client
+            .get_connection_mut_by_id(closed_by_client)
+            .unwrap()
+            .send_message(SharedMessage::TestMessage(format!("drained {}", i)))
+            .unwrap();
+    }
+    client.close_connection(closed_by_client).unwrap();
+    server
+        .endpoint_mut()
+        .disconnect_client(kicked_client_id)
+        .unwrap();
+    let (mut client_stages, mut server_stages) = (Vec::new(), Vec::new());
+    let start = Instant::now();
+    while client_stages.len() < 3 || server_stages.len() < 3 {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        for event in client.pump() {
+            if let QuinnetClientEvent::ConnectionCloseStage(event) = event {
+                if event.id == closed_by_client {
+                    client_stages.push((event.stage, event.elapsed));
+                }
+            }
+        }
+        for event in server.pump() {
+            if let QuinnetServerEvent::ClientCloseStage(event) = event {
+                if event.id == kicked_client_id {
+                    server_stages.push((event.stage, event.elapsed));
+                }
+            }
+        }
+    }
+
+    for (stages, code) in [
+        (&client_stages, CloseCode::Closed),
+        (&server_stages, CloseCode::Kicked),
+    ] {
+        assert!(matches!(stages[0].0, CloseStage::DrainStarted { .. }));
+        assert_eq!(
+            stages[1].0,
+            CloseStage::BuffersFlushed {
+                remainders: vec![(0, 0)]
+            }
+        );
+        assert_eq!(stages[2].0, CloseStage::Closed { code });
+        assert!(stages.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    }
+}
+
+#[test]
+fn reliable_channel_resume() {
+    let port = 6057; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    // The server stops reading the streams carrying frames over 1 KiB
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port)
+                .with_hardening(HardeningConfiguration::strict().with_max_frame_len(1_024)),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client.connection().client_id() {
+            break client_id;
+        }
+    };
+
+    client
+        .connection_mut()
+        .send_payload(Bytes::from(vec![0; 4_096]))
+        .unwrap();
+    loop {
+        sleep(Duration::from_millis(5));
+        client.pump();
+        if server
+            .pump()
+            .into_iter()
+            .any(|event| matches!(event, QuinnetServerEvent::ProtocolViolation(_)))
+        {
+            break;
+        }
+    }
+    // Let the stop of the stream reach the client
+    sleep(Duration::from_millis(100));
+
+    // The channel resumes on a new stream instead of losing the connection
+    let message = SharedMessage::TestMessage("resumed".to_string());
+    client
+        .connection_mut()
+        .send_message(message.clone())
+        .unwrap();
+    let (mut resumed, mut received) = (None, None);
+    let start = Instant::now();
+    while resumed.is_none() || received.is_none() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        for event in client.pump() {
+            match event {
+                QuinnetClientEvent::ChannelResumed(event) => resumed = Some(event.channel_id),
+                QuinnetClientEvent::ConnectionLost(_) => panic!("Connection lost"),
+                _ => {}
+            }
+        }
+        if let Some((_, message)) = server
+            .endpoint_mut()
+            .receive_message_from::<SharedMessage>(client_id)
+            .unwrap()
+        {
+            received = Some(message);
+        }
+    }
+    assert_eq!(resumed, Some(0));
+    assert_eq!(received, Some(message));
+    assert_eq!(client.connection().state(), ConnectionState::Connected);
+}
+
+#[test]
+fn channel_error_isolation() {
+    let port = 6058; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::from_types(vec![ChannelKind::OrderedReliable {
+                max_frame_size: 64,
+            }])
+            .unwrap(),
+        )
+        .unwrap();
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client.connection().client_id() {
+            break client_id;
+        }
+    };
+
+    // The oversized payload is dropped, the channel keeps sending
+    client
+        .connection_mut()
+        .send_payload(Bytes::from(vec![0; 1_024]))
+        .unwrap();
+    client
+        .connection_mut()
+        .send_payload(Bytes::from_static(b"small"))
+        .unwrap();
+    let (mut error, mut received) = (None, None);
+    let start = Instant::now();
+    while error.is_none() || received.is_none() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        for event in client.pump() {
+            match event {
+                QuinnetClientEvent::ChannelError(event) => error = Some(event),
+                QuinnetClientEvent::ConnectionLost(_) => panic!("Connection lost"),
+                _ => {}
+            }
+        }
+        if let Some((_, payload)) = server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap()
+        {
+            received = Some(payload);
+        }
+    }
+    let error = error.unwrap();
+    assert_eq!(error.channel_id, 0);
+    assert!(matches!(
+        error.error,
+        ChannelError::FrameTooLarge { max_len: 64, .. }
+    ));
+    assert_eq!(received, Some(Bytes::from_static(b"small")));
+    assert_eq!(client.connection().state(), ConnectionState::Connected);
+}
+
+#[test]
+fn drain_received_in_arrival_order() {
+    let port = 6059; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let connections = [(); 2].map(|_| {
+        client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap()
+    });
+    let client_ids = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        let client_ids = connections.map(|id| client.get_connection_by_id(id).unwrap().client_id());
+        if let [Some(first), Some(second)] = client_ids {
+            break [first, second];
+        }
+    };
+
+    // Interleaved between the clients
+    let sent = [(0, "first"), (1, "second"), (0, "third"), (1, "fourth")];
+    for (connection, payload) in sent {
+        client
+            .get_connection_mut_by_id(connections[connection])
+            .unwrap()
+            .send_payload(Bytes::from_static(payload.as_bytes()))
+            .unwrap();
+        sleep(Duration::from_millis(50));
+    }
+    let mut received = Vec::new();
+    let start = Instant::now();
+    while received.len() < sent.len() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        received.extend(server.endpoint_mut().drain_received());
+    }
+    assert_eq!(
+        received,
+        sent.map(|(connection, payload)| (
+            client_ids[connection],
+            0,
+            Bytes::from_static(payload.as_bytes())
+        ))
+    );
+}
+
+#[test]
+fn generational_client_ids() {
+    let port = 6060; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    server
+        .endpoint_mut()
+        .set_client_id_policy(ClientIdPolicy::Generational);
+
+    let mut client_ids = Vec::new();
+    for _ in 0..2 {
+        let connection_id = client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+        let start = Instant::now();
+        let client_id = loop {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            sleep(Duration::from_millis(5));
+            server.pump();
+            client.pump();
+            if let Some(client_id) = client
+                .get_connection_by_id(connection_id)
+                .unwrap()
+                .client_id()
+            {
+                break client_id;
+            }
+        };
+        server.endpoint_mut().disconnect_client(client_id).unwrap();
+        client_ids.push(client_id);
+    }
+
+    // The index of the disconnected client is reused, with a new generation
+    assert_eq!(client_id_index(client_ids[0]), 1);
+    assert_eq!(client_id_index(client_ids[1]), 1);
+    assert_eq!(client_id_generation(client_ids[0]), 0);
+    assert_eq!(client_id_generation(client_ids[1]), 1);
+}
+
+#[test]
+fn client_data_dropped_on_disconnect() {
+    let port = 6061; // TODO Use port 0 and retrieve the port used by the server.
+
+    #[derive(Debug, PartialEq)]
+    struct Throttle(u32);
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let connection_id = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let start = Instant::now();
+    let client_id = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client
+            .get_connection_by_id(connection_id)
+            .unwrap()
+            .client_id()
+        {
+            break client_id;
+        }
+    };
+
+    let endpoint = server.endpoint_mut();
+    assert!(endpoint
+        .set_client_data(client_id, Throttle(1))
+        .unwrap()
+        .is_none());
+    assert!(endpoint
+        .set_client_data(client_id, "user".to_string())
+        .unwrap()
+        .is_none());
+    endpoint
+        .get_client_data_mut::<Throttle>(client_id)
+        .unwrap()
+        .0 += 1;
+    assert_eq!(
+        endpoint.set_client_data(client_id, Throttle(5)).unwrap(),
+        Some(Throttle(2))
+    );
+    assert_eq!(
+        endpoint
+            .get_client_data::<String>(client_id)
+            .map(String::as_str),
+        Some("user")
+    );
+    assert_eq!(
+        endpoint.remove_client_data::<String>(client_id),
+        Some("user".to_string())
+    );
+    assert!(endpoint.get_client_data::<String>(client_id).is_none());
+
+    endpoint.disconnect_client(client_id).unwrap();
+    assert!(endpoint.get_client_data::<Throttle>(client_id).is_none());
+    assert!(matches!(
+        endpoint.set_client_data(client_id, Throttle(0)),
+        Err(ServerClientDataError::UnknownClient(id)) if id == client_id
+    ));
+}
+
+#[test]
+fn disconnect_hook_completion() {
+    let port = 6062; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let (finalized_send, mut finalized_recv) = mpsc::unbounded_channel();
+    server
+        .endpoint_mut()
+        .set_disconnect_hook(move |client_id, reason| {
+            let finalized_send = finalized_send.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let _ = finalized_send.send((client_id, reason));
+            }
+        });
+    let connection_id = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let start = Instant::now();
+    let client_id = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client
+            .get_connection_by_id(connection_id)
+            .unwrap()
+            .client_id()
+        {
+            break client_id;
+        }
+    };
+
+    server.endpoint_mut().disconnect_client(client_id).unwrap();
+    let mut connection_lost = false;
+    let start = Instant::now();
+    let completed = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        let completed = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::ConnectionLost(event) => {
+                assert_eq!(event.id, client_id);
+                connection_lost = true;
+                None
+            }
+            QuinnetServerEvent::DisconnectHookCompleted(event) => Some(event),
+            _ => None,
+        });
+        if let Some(completed) = completed {
+            break completed;
+        }
+    };
+
+    // The hook ran to completion before its event, raised after the connection lost event
+    assert!(connection_lost);
+    assert_eq!(completed.id, client_id);
+    assert_eq!(completed.reason, DisconnectReason::DisconnectedByServer);
+    assert_eq!(
+        finalized_recv.try_recv().unwrap(),
+        (client_id, DisconnectReason::DisconnectedByServer)
+    );
+}
+
+#[test]
+fn routed_channel_payloads() {
+    let port = 6063; // TODO Use port 0 and retrieve the port used by the server.
+
+    let channels = || {
+        ChannelsConfiguration::from_types(vec![ChannelKind::default(), ChannelKind::default()])
+            .unwrap()
+    };
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels(),
+        )
+        .unwrap();
+    let route = server.endpoint_mut().route_channels([1]);
+    let simulation = thread::spawn(move || route.recv().unwrap());
+    let connection_id = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels(),
+        )
+        .unwrap();
+    let start = Instant::now();
+    let client_id = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client
+            .get_connection_by_id(connection_id)
+            .unwrap()
+            .client_id()
+        {
+            break client_id;
+        }
+    };
+
+    let connection = client.get_connection_mut_by_id(connection_id).unwrap();
+    connection
+        .send_payload_on(0, Bytes::from_static(b"main"))
+        .unwrap();
+    connection
+        .send_payload_on(1, Bytes::from_static(b"simulation"))
+        .unwrap();
+    let start = Instant::now();
+    while !simulation.is_finished() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+    }
+
+    // The routed payload never reaches the endpoint, the other channels are not affected
+    let routed = simulation.join().unwrap();
+    assert_eq!(routed.client_id, client_id);
+    assert_eq!(routed.channel_id, 1);
+    assert_eq!(routed.payload, Bytes::from_static(b"simulation"));
+    let received = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        if let Some(received) = server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap()
+        {
+            break received;
+        }
+        sleep(Duration::from_millis(5));
+        server.pump();
+    };
+    assert_eq!(received, (0, Bytes::from_static(b"main")));
+    assert_eq!(
+        server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn instance_payloads() {
+    let port = 6088; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let route = server.endpoint_mut().open_instance("match");
+    let connection_ids = [0, 1].map(|_| {
+        client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap()
+    });
+    let start = Instant::now();
+    let client_ids = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        let client_ids = connection_ids.map(|connection_id| {
+            client
+                .get_connection_by_id(connection_id)
+                .unwrap()
+                .client_id()
+        });
+        if let [Some(player), Some(lobby)] = client_ids {
+            break [player, lobby];
+        }
+    };
+    let [player, lobby] = client_ids;
+
+    assert!(matches!(
+        server.endpoint_mut().assign_instance(player, "other"),
+        Err(ServerInstanceError::UnknownInstance(_))
+    ));
+    assert_eq!(
+        server
+            .endpoint_mut()
+            .assign_instance(player, "match")
+            .unwrap(),
+        None
+    );
+    assert_eq!(server.endpoint().client_instance(player), Some("match"));
+    assert_eq!(server.endpoint().instance_clients("match"), vec![player]);
+
+    for (connection_id, payload) in connection_ids.iter().zip([b"player", b"lobby!"]) {
+        client
+            .get_connection_mut_by_id(*connection_id)
+            .unwrap()
+            .send_payload_on(0, Bytes::from_static(payload))
+            .unwrap();
+    }
+    let start = Instant::now();
+    let routed = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        if let Some(routed) = route.try_recv() {
+            break routed;
+        }
+    };
+
+    // The payloads of the clients of the instance only reach its route
+    assert_eq!(routed.client_id, player);
+    assert_eq!(routed.payload, Bytes::from_static(b"player"));
+    let received = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        if let Some(received) = server.endpoint_mut().receive_payload_from(lobby).unwrap() {
+            break received;
+        }
+        sleep(Duration::from_millis(5));
+        server.pump();
+    };
+    assert_eq!(received, (0, Bytes::from_static(b"lobby!")));
+    assert_eq!(
+        server.endpoint_mut().receive_payload_from(player).unwrap(),
+        None
+    );
+
+    // Dropping the route closes the instance
+    drop(route);
+    client
+        .get_connection_mut_by_id(connection_ids[0])
+        .unwrap()
+        .send_payload_on(0, Bytes::from_static(b"back"))
+        .unwrap();
+    let received = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        if let Some(received) = server.endpoint_mut().receive_payload_from(player).unwrap() {
+            break received;
+        }
+    };
+    assert_eq!(received, (0, Bytes::from_static(b"back")));
+    assert_eq!(server.endpoint().client_instance(player), None);
+    assert!(server.endpoint().instances().is_empty());
+}
+
+#[test]
+fn protocol_hash_mismatch() {
+    let port = 6065; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    let server_channels = ChannelsConfiguration::default().with_message_types(["SharedMessage"]);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            server_channels.clone(),
+        )
+        .unwrap();
+    server.endpoint_mut().set_protocol_check(true);
+    assert_eq!(
+        server.endpoint().protocol_hash(),
+        server_channels.protocol_hash()
+    );
+
+    let matching = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            server_channels.clone(),
+        )
+        .unwrap();
+    // Same channels, another message type: a desynced build
+    let desynced_channels = ChannelsConfiguration::default().with_message_types(["OtherMessage"]);
+    let desynced = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            desynced_channels.clone(),
+        )
+        .unwrap();
+
+    let (mut server_mismatch, mut client_mismatch) = (None, None);
+    let (mut server_lost, mut client_lost) = (None, None);
+    let start = Instant::now();
+    while server_lost.is_none() || client_lost.is_none() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            match event {
+                QuinnetServerEvent::ProtocolMismatch(event) => server_mismatch = Some(event),
+                QuinnetServerEvent::ConnectionLost(event) => server_lost = Some(event),
+                _ => (),
+            }
+        }
+        for event in client.pump() {
+            match event {
+                QuinnetClientEvent::ProtocolMismatch(event) => client_mismatch = Some(event),
+                QuinnetClientEvent::ConnectionLost(event) => client_lost = Some(event),
+                _ => (),
+            }
+        }
+    }
+
+    let server_mismatch = server_mismatch.unwrap();
+    assert_eq!(
+        server_mismatch.client_hash,
+        desynced_channels.protocol_hash()
+    );
+    assert_eq!(server_mismatch.server_hash, server_channels.protocol_hash());
+    let server_lost = server_lost.unwrap();
+    assert_eq!(server_lost.id, server_mismatch.id);
+    assert_eq!(server_lost.reason, DisconnectReason::ProtocolMismatch);
+
+    let client_mismatch = client_mismatch.unwrap();
+    assert_eq!(client_mismatch.id, desynced);
+    assert_eq!(
+        client_mismatch.local_hash,
+        desynced_channels.protocol_hash()
+    );
+    assert_eq!(client_mismatch.server_hash, server_channels.protocol_hash());
+    let client_lost = client_lost.unwrap();
+    assert_eq!(client_lost.id, desynced);
+    assert_eq!(client_lost.close_code, Some(CloseCode::ProtocolMismatch));
+
+    // The client with the same protocol stays connected
+    while client
+        .get_connection_by_id(matching)
+        .unwrap()
+        .client_id()
+        .is_none()
+    {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+    }
+    for _ in 0..10 {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+    }
+    assert_eq!(server.endpoint().clients().len(), 1);
+}
+
+#[test]
+fn adaptive_send_rate() {
+    let port = 6070; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+
+    let mut send_rate = AdaptiveSendRate::new(5., 30.).with_sample_interval(Duration::ZERO);
+    assert!(
+        send_rate.update(server.endpoint()).is_empty(),
+        "A healthy client should stay at the max rate"
+    );
+    assert_eq!(send_rate.rate(client_id), 30.);
+    assert!(send_rate.is_due(client_id));
+    send_rate.mark_sent(client_id);
+    assert!(!send_rate.is_due(client_id));
+
+    // Round-trip time above the default target
+    server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .set_conditions(Some(ClientConditions::new(Duration::from_millis(150))));
+    assert_eq!(
+        send_rate.update(server.endpoint()),
+        vec![(client_id, 22.5)],
+        "The rate of a degraded client should be decreased"
+    );
+    for _ in 0..10 {
+        send_rate.update(server.endpoint());
+    }
+    assert_eq!(send_rate.rate(client_id), 5., "The rate should be bounded");
+
+    server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .set_conditions(None);
+    assert_eq!(send_rate.update(server.endpoint()), vec![(client_id, 6.)]);
+}
+
+#[test]
+fn network_tick_broadcast() {
+    let port = 6071; // TODO Use port 0 and retrieve the port used by the server.
+
+    let channels = ChannelsConfiguration::from_configs(vec![
+        ChannelConfig::reliable_ordered().ticked(),
+        ChannelConfig::unreliable().ticked(),
+    ])
+    .unwrap();
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels,
+        )
+        .unwrap();
+    let mut client_id = None;
+    while client_id.is_none() {
+        sleep(Duration::from_millis(5));
+        client.pump();
+        client_id = server.pump().iter().find_map(|event| match event {
+            QuinnetServerEvent::Connection(event) => Some(event.id),
+            _ => None,
+        });
+    }
+    let client_id = client_id.unwrap();
+
+    server.endpoint_mut().set_network_tick(NetworkTick(1));
+    server
+        .endpoint_mut()
+        .send_payload_on(client_id, 0, "before")
+        .unwrap();
+    client
+        .connection_mut()
+        .send_payload_on(0, "from client")
+        .unwrap();
+    let start = Instant::now();
+    let mut received = None;
+    while received.is_none() {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The client should receive the payload of the server"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        received = client.connection_mut().receive_payload().unwrap();
+    }
+    assert_eq!(received, Some((0, Bytes::from("before"))));
+    assert_eq!(
+        client.connection().server_tick(),
+        Some(NetworkTick(1)),
+        "The tick should be read from the payload of the server"
+    );
+    assert_eq!(
+        server
+            .endpoint_mut()
+            .receive_payload_from(client_id)
+            .unwrap(),
+        Some((0, Bytes::from("from client"))),
+        "The payloads of the client should not carry a tick"
+    );
+
+    server.endpoint_mut().set_network_tick(NetworkTick(2));
+    for _ in 0..10 {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+    }
+    assert_eq!(
+        client.connection().server_tick(),
+        Some(NetworkTick(1)),
+        "The tick should only be sent along with the payloads of the server"
+    );
+
+    let start = Instant::now();
+    while client.connection().server_tick() != Some(NetworkTick(2)) {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The client should receive the tick of the server"
+        );
+        server
+            .endpoint_mut()
+            .send_payload_on(client_id, 1, "after")
+            .unwrap();
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+    }
+    assert_eq!(
+        client.connection_mut().receive_payload().unwrap(),
+        Some((1, Bytes::from("after")))
+    );
+    assert_eq!(NetworkTick(u32::MAX).next(), NetworkTick(0));
+    assert_eq!(NetworkTick(1).since(NetworkTick(u32::MAX)), 2);
+}
+
+#[test]
+fn lockstep_bundles() {
+    let port = 6072; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut clients = [
+        QuinnetClient::from_world(&mut world),
+        QuinnetClient::from_world(&mut world),
+    ];
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    for client in clients.iter_mut() {
+        client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+    }
+    let mut client_ids = Vec::new();
+    while client_ids.len() < clients.len() {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_ids.push(event.id);
+            }
+        }
+        for client in clients.iter_mut() {
+            client.pump();
+        }
+    }
+    client_ids.sort_unstable();
+
+    let mut lockstep_server =
+        LockstepServer::<u32>::new(0).with_timeout(Duration::from_millis(100));
+    let mut lockstep_clients = [LockstepClient::<u32>::new(0), LockstepClient::<u32>::new(0)];
+
+    // Both inputs of tick 0: released as soon as received
+    for (client, lockstep) in clients.iter_mut().zip(lockstep_clients.iter()) {
+        lockstep.send(client.connection_mut(), 0, &12).unwrap();
+    }
+    let mut released = Vec::new();
+    let start = Instant::now();
+    while released.is_empty() {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The complete tick should be released"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        lockstep_server.receive(server.endpoint_mut());
+        released = lockstep_server.release(server.endpoint_mut());
+    }
+    let complete_bundle = LockstepBundle {
+        tick: 0,
+        inputs: vec![(client_ids[0], 12), (client_ids[1], 12)],
+        missing: vec![],
+    };
+    assert_eq!(released, vec![complete_bundle.clone()]);
+    assert_eq!(lockstep_server.next_tick(), 1);
+
+    // Input of tick 1 missing for the second client: released after the timeout
+    lockstep_clients[0]
+        .send(clients[0].connection_mut(), 1, &7)
+        .unwrap();
+    let mut released = Vec::new();
+    let start = Instant::now();
+    while released.is_empty() {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The stalled tick should be released"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        lockstep_server.receive(server.endpoint_mut());
+        released = lockstep_server.release(server.endpoint_mut());
+    }
+    assert_eq!(released.len(), 1);
+    let stalled_bundle = released.remove(0);
+    assert!(stalled_bundle.stalled());
+    assert_eq!(stalled_bundle.tick, 1);
+    assert_eq!(stalled_bundle.inputs.len(), 1);
+    assert_eq!(stalled_bundle.inputs[0].1, 7);
+    assert_eq!(stalled_bundle.missing.len(), 1);
+    assert_ne!(stalled_bundle.inputs[0].0, stalled_bundle.missing[0]);
+
+    // Late input of a released tick: dropped
+    lockstep_clients[1]
+        .send(clients[1].connection_mut(), 1, &8)
+        .unwrap();
+
+    let start = Instant::now();
+    for (client, lockstep) in clients.iter_mut().zip(lockstep_clients.iter_mut()) {
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            assert!(
+                start.elapsed() < Duration::from_secs(2),
+                "The clients should receive the bundles"
+            );
+            sleep(Duration::from_millis(5));
+            server.pump();
+            client.pump();
+            received.extend(lockstep.receive(client.connection_mut()).unwrap());
+        }
+        assert_eq!(
+            received,
+            vec![complete_bundle.clone(), stalled_bundle.clone()]
+        );
+        assert_eq!(lockstep.last_tick(), Some(1));
+    }
+    lockstep_server.receive(server.endpoint_mut());
+    assert!(lockstep_server.release(server.endpoint_mut()).is_empty());
+    assert_eq!(lockstep_server.next_tick(), 2);
+}
+
+#[test]
+fn spectator_stream() {
+    let port = 6073; // TODO Use port 0 and retrieve the port used by the server.
+    let (snapshot_channel, live_channel) = (0, 1);
+    let channels = || {
+        ChannelsConfiguration::from_types(vec![ChannelKind::default(), ChannelKind::default()])
+            .unwrap()
+    };
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels(),
+        )
+        .unwrap();
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client.connection().client_id() {
+            break client_id;
+        }
+    };
+
+    let mut stream = SpectatorStream::new(snapshot_channel);
+    let mut spectator = SpectatorClient::new(snapshot_channel);
+    assert!(matches!(
+        stream.send_snapshot_message(server.endpoint_mut(), client_id, "state"),
+        Err(ServerSpectatorError::UnknownSpectator(_))
+    ));
+
+    stream.add_spectator(client_id);
+    stream
+        .broadcast_message_on(server.endpoint_mut(), live_channel, 1u32)
+        .unwrap();
+    for part in ["state 1", "state 2"] {
+        stream
+            .send_snapshot_message(server.endpoint_mut(), client_id, part)
+            .unwrap();
+    }
+    stream
+        .end_snapshot(server.endpoint_mut(), client_id)
+        .unwrap();
+    assert_eq!(stream.state(client_id), Some(SpectatorState::Synchronizing));
+    assert!(matches!(
+        stream.send_snapshot_message(server.endpoint_mut(), client_id, "state 3"),
+        Err(ServerSpectatorError::SnapshotEnded(_))
+    ));
+    stream
+        .broadcast_message_on(server.endpoint_mut(), live_channel, 2u32)
+        .unwrap();
+    assert_eq!(
+        stream.pending_len(client_id),
+        2,
+        "The live updates should be held until the snapshot is acknowledged"
+    );
+
+    let start = Instant::now();
+    let parts = loop {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The spectator should receive its snapshot"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        assert_eq!(
+            client
+                .connection_mut()
+                .receive_all_on(live_channel)
+                .unwrap()
+                .count(),
+            0,
+            "No live update should be received before the snapshot"
+        );
+        if let Some(parts) = spectator.receive(client.connection_mut()).unwrap() {
+            break parts;
+        }
+    };
+    let parts: Vec<String> = parts
+        .iter()
+        .map(|part| bincode::deserialize(part).unwrap())
+        .collect();
+    assert_eq!(parts, vec!["state 1", "state 2"]);
+    assert!(spectator.is_synchronized());
+
+    let start = Instant::now();
+    while stream.state(client_id) != Some(SpectatorState::Live) {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The server should receive the acknowledgement of the snapshot"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        stream.update(server.endpoint_mut());
+    }
+    assert_eq!(stream.pending_len(client_id), 0);
+    stream
+        .broadcast_message_on(server.endpoint_mut(), live_channel, 3u32)
+        .unwrap();
+
+    let mut updates = Vec::new();
+    let start = Instant::now();
+    while updates.len() < 3 {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The spectator should receive the live updates"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        updates.extend(
+            client
+                .connection_mut()
+                .receive_all_on(live_channel)
+                .unwrap()
+                .map(|payload| bincode::deserialize::<u32>(&payload).unwrap()),
+        );
+    }
+    assert_eq!(updates, vec![1, 2, 3]);
+}
+
+#[test]
+fn chunked_baseline() {
+    let port = 6074; // TODO Use port 0 and retrieve the port used by the server.
+    let (baseline_channel, live_channel) = (0, 1);
+    let channels = || {
+        ChannelsConfiguration::from_types(vec![ChannelKind::default(), ChannelKind::default()])
+            .unwrap()
+    };
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels(),
+        )
+        .unwrap();
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        if let Some(client_id) = client.connection().client_id() {
+            break client_id;
+        }
+    };
+
+    let baseline: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+    let mut sync = BaselineSync::new(baseline_channel)
+        .with_chunk_size(10_000)
+        .with_chunks_per_update(3);
+    let mut receiver = BaselineReceiver::new(baseline_channel);
+    sync.start(client_id, baseline.clone());
+    sync.send_message_on(server.endpoint_mut(), client_id, live_channel, 1u32)
+        .unwrap();
+
+    let progress = sync.send_chunks(server.endpoint_mut());
+    assert_eq!(progress.len(), 1);
+    assert_eq!(
+        progress[0].sent, 30_000,
+        "The chunks should be bounded per update"
+    );
+    assert_eq!(progress[0].total, 100_000);
+    sync.broadcast_message_on(server.endpoint_mut(), live_channel, 2u32)
+        .unwrap();
+    while sync.state(client_id) == Some(BaselineState::Sending) {
+        sync.send_chunks(server.endpoint_mut());
+    }
+    assert_eq!(sync.progress(client_id), Some((100_000, 100_000)));
+    assert_eq!(sync.pending_len(client_id), 2);
+
+    let start = Instant::now();
+    let received = loop {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The client should receive its baseline"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        assert_eq!(
+            client
+                .connection_mut()
+                .receive_all_on(live_channel)
+                .unwrap()
+                .count(),
+            0,
+            "No live update should be received before the baseline"
+        );
+        if let Some(received) = receiver.receive(client.connection_mut()).unwrap() {
+            break received;
+        }
+    };
+    assert_eq!(received, baseline);
+    assert_eq!(receiver.progress(), None);
+
+    let start = Instant::now();
+    while sync.state(client_id).is_some() {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The server should receive the ready answer"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        sync.receive_ready(server.endpoint_mut());
+    }
+    sync.send_message_on(server.endpoint_mut(), client_id, live_channel, 3u32)
+        .unwrap();
+
+    let mut updates = Vec::new();
+    let start = Instant::now();
+    while updates.len() < 3 {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The client should receive the live updates"
+        );
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        updates.extend(
+            client
+                .connection_mut()
+                .receive_all_on(live_channel)
+                .unwrap()
+                .map(|payload| bincode::deserialize::<u32>(&payload).unwrap()),
+        );
+    }
+    assert_eq!(updates, vec![1, 2, 3]);
+}
+
+#[test]
+fn chat_relay() {
+    let port = 6075; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut clients = [
+        QuinnetClient::from_world(&mut world),
+        QuinnetClient::from_world(&mut world),
+    ];
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    for client in clients.iter_mut() {
+        client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+    }
+    let client_ids = loop {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        for client in clients.iter_mut() {
+            client.pump();
+        }
+        if let [Some(first), Some(second)] = clients
+            .each_ref()
+            .map(|client| client.connection().client_id())
+        {
+            break [first, second];
+        }
+    };
+
+    let mut chat_server = ChatServer::new(0)
+        .with_rate_limit(3, Duration::from_secs(60))
+        .with_filter(|_, _, text| match text.contains("forbidden") {
+            true => None,
+            false => Some(text.replace("darn", "****")),
+        });
+    let mut chats = [ChatClient::new(0), ChatClient::new(0)];
+    let mut events: [Vec<ChatEvent>; 2] = Default::default();
+    let mut relayed = Vec::new();
+    let mut rejected = Vec::new();
+    let mut exchange = |server: &mut QuinnetServer,
+                        clients: &mut [QuinnetClient; 2],
+                        chats: &mut [ChatClient; 2],
+                        chat_server: &mut ChatServer,
+                        expected_events: [usize; 2]| {
+        let start = Instant::now();
+        while events
+            .iter()
+            .zip(expected_events)
+            .any(|(events, expected)| events.len() < expected)
+        {
+            assert!(
+                start.elapsed() < Duration::from_secs(2),
+                "The chat events should be received"
+            );
+            sleep(Duration::from_millis(5));
+            server.pump();
+            let (messages, rejections) = chat_server.receive(server.endpoint_mut());
+            relayed.extend(messages);
+            rejected.extend(rejections);
+            for ((client, chat), events) in clients
+                .iter_mut()
+                .zip(chats.iter_mut())
+                .zip(events.iter_mut())
+            {
+                client.pump();
+                events.extend(chat.receive(client.connection_mut()).unwrap());
+            }
+        }
+        std::mem::take(&mut events)
+    };
+
+    chats[0].join(clients[0].connection_mut(), "lobby").unwrap();
+    let received = exchange(
+        &mut server,
+        &mut clients,
+        &mut chats,
+        &mut chat_server,
+        [1, 0],
+    );
+    assert_eq!(received[0], vec![ChatEvent::Joined("lobby".to_string())]);
+    assert!(chats[0].joined().contains("lobby"));
+    assert_eq!(
+        chat_server.members("lobby"),
+        Some(&[client_ids[0]].into_iter().collect())
+    );
+
+    // Filtered message, then a message of a client outside of the chat channel
+    chats[0]
+        .send(clients[0].connection_mut(), "lobby", "darn it")
+        .unwrap();
+    chats[1]
+        .send(clients[1].connection_mut(), "lobby", "hello")
+        .unwrap();
+    let received = exchange(
+        &mut server,
+        &mut clients,
+        &mut chats,
+        &mut chat_server,
+        [1, 1],
+    );
+    assert_eq!(
+        received[0],
+        vec![ChatEvent::Message(ChatMessage {
+            from: Some(client_ids[0]),
+            target: ChatTarget::Channel("lobby".to_string()),
+            text: "**** it".to_string(),
+        })]
+    );
+    assert_eq!(
+        received[1],
+        vec![ChatEvent::Rejected(ChatRejection::NotMember)]
+    );
+
+    // Whisper, received by both the recipient and the sender
+    chats[0]
+        .whisper(clients[0].connection_mut(), client_ids[1], "psst")
+        .unwrap();
+    let received = exchange(
+        &mut server,
+        &mut clients,
+        &mut chats,
+        &mut chat_server,
+        [1, 1],
+    );
+    let whisper = ChatEvent::Message(ChatMessage {
+        from: Some(client_ids[0]),
+        target: ChatTarget::Whisper(client_ids[1]),
+        text: "psst".to_string(),
+    });
+    assert_eq!(received, [vec![whisper.clone()], vec![whisper]]);
+
+    // Refused by the filter, then over the rate limit
+    chats[0]
+        .send(clients[0].connection_mut(), "lobby", "forbidden")
+        .unwrap();
+    chats[0]
+        .send(clients[0].connection_mut(), "lobby", "one more")
+        .unwrap();
+    let received = exchange(
+        &mut server,
+        &mut clients,
+        &mut chats,
+        &mut chat_server,
+        [2, 0],
+    );
+    assert_eq!(
+        received[0],
+        vec![
+            ChatEvent::Rejected(ChatRejection::Filtered),
+            ChatEvent::Rejected(ChatRejection::RateLimited)
+        ]
+    );
+    assert_eq!(relayed.len(), 2);
+    assert_eq!(
+        rejected,
+        vec![
+            ChatRejectedEvent {
+                client_id: client_ids[1],
+                reason: ChatRejection::NotMember
+            },
+            ChatRejectedEvent {
+                client_id: client_ids[0],
+                reason: ChatRejection::Filtered
+            },
+            ChatRejectedEvent {
+                client_id: client_ids[0],
+                reason: ChatRejection::RateLimited
+            },
+        ]
+    );
+}
+
+#[test]
+fn master_server_listing() {
+    let port = 6076; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut master = QuinnetServer::from_world(&mut world);
+    let mut game_server = QuinnetClient::from_world(&mut world);
+    let mut player = QuinnetClient::from_world(&mut world);
+    master
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let connection_ids = [&mut game_server, &mut player].map(|client| {
+        client
+            .open_connection(
+                default_client_configuration(port),
+                CertificateVerificationMode::SkipVerification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap()
+    });
+    let browser_connection = connection_ids[1];
+    let mut master_server = MasterServer::new(0);
+    let mut info = ServerInfo::new("Arena", 7000);
+    info.max_players = 8;
+    let mut registration = MasterRegistration::new(connection_ids[0], 0, info.clone())
+        .with_heartbeat_interval(Duration::from_millis(20));
+    let mut browser = ServerBrowser::new(browser_connection, 0);
+
+    let mut registered = Vec::new();
+    let start = Instant::now();
+    while registered.is_empty() {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The game server should register"
+        );
+        sleep(Duration::from_millis(5));
+        master.pump();
+        game_server.pump();
+        player.pump();
+        registration.update(&mut game_server);
+        registered = master_server.update(master.endpoint_mut()).0;
+    }
+    let listing = registered.remove(0).listing;
+    assert_eq!(listing.info, info);
+    assert_eq!(listing.addr.port(), 7000);
+    assert!(listing.addr.ip().is_loopback());
+
+    // Heartbeat with updated info
+    registration.info_mut().players = 3;
+    info.players = 3;
+    let start = Instant::now();
+    while master_server.listings()[0].info.players != 3 {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The heartbeats should update the listing"
+        );
+        sleep(Duration::from_millis(5));
+        master.pump();
+        game_server.pump();
+        registration.update(&mut game_server);
+        assert!(master_server.update(master.endpoint_mut()).0.is_empty());
+    }
+
+    let start = Instant::now();
+    while player
+        .get_connection_by_id(browser_connection)
+        .unwrap()
+        .state()
+        != ConnectionState::Connected
+    {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        master.pump();
+        player.pump();
+    }
+    browser.refresh(&mut player).unwrap();
+    let listings = loop {
+        assert!(
+            start.elapsed() < Duration::from_secs(4),
+            "The browser should receive the list"
+        );
+        sleep(Duration::from_millis(5));
+        master.pump();
+        player.pump();
+        master_server.update(master.endpoint_mut());
+        if let Some(listings) = browser.receive(&mut player).unwrap() {
+            break listings;
+        }
+    };
+    assert_eq!(listings.len(), 1);
+    assert_eq!(listings[0].info, info);
+    assert_eq!(browser.listings(), listings.as_slice());
+
+    registration.unregister(&mut game_server).unwrap();
+    let start = Instant::now();
+    loop {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "The game server should unregister"
+        );
+        sleep(Duration::from_millis(5));
+        master.pump();
+        game_server.pump();
+        let (_, unregistered) = master_server.update(master.endpoint_mut());
+        if let Some(event) = unregistered.first() {
+            assert_eq!(event.addr, listing.addr);
+            break;
+        }
+    }
+    assert!(master_server.listings().is_empty());
+}
+
+#[test]
+fn invite_code_connection() {
+    let port = 6077; // TODO Use port 0 and retrieve the port used by the server.
+
+    let key = InviteKey::new([9; 32]);
+    let fingerprint = CertificateFingerprint::new([42; 32]);
+    let invite = InviteCode::new("play.example.com", 7000, fingerprint.clone())
+        .unwrap()
+        .with_token(b"join-me".to_vec())
+        .unwrap();
+    let code = invite.encode(&key);
+    assert_eq!(InviteCode::decode(&code, &key).unwrap(), invite);
+    assert_eq!(invite.token(), Some(&b"join-me"[..]));
+    assert!(
+        !String::from_utf8_lossy(&base64::decode_config(&code, base64::URL_SAFE_NO_PAD).unwrap())
+            .contains("play.example.com"),
+        "The content of the code should be encrypted"
+    );
+    assert_ne!(
+        invite.encode(&key),
+        code,
+        "Each encoding should draw its own nonce"
+    );
+    let ipv6_invite = InviteCode::new("::1", 7000, fingerprint.clone()).unwrap();
+    assert_eq!(
+        InviteCode::decode(&ipv6_invite.encode(&key), &key).unwrap(),
+        ipv6_invite
+    );
+    assert_eq!(
+        InviteCode::decode(&code, &InviteKey::new([8; 32])),
+        Err(InviteCodeError::Undecryptable)
+    );
+    let mut typo = code.into_bytes();
+    typo[20] = if typo[20] == b'A' { b'B' } else { b'A' };
+    assert_eq!(
+        InviteCode::decode(std::str::from_utf8(&typo).unwrap(), &key),
+        Err(InviteCodeError::Undecryptable)
+    );
+    assert!(matches!(
+        InviteCode::new("not a host", 7000, fingerprint.clone()),
+        Err(InviteCodeError::InvalidHost(_))
+    ));
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    let mut impostor_client = QuinnetClient::from_world(&mut world);
+    let server_cert = server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let invite = InviteCode::new(SERVER_IP.to_string(), port, server_cert.fingerprint).unwrap();
+    client
+        .open_connection_from_invite(&invite.encode(&key), &key, ChannelsConfiguration::default())
+        .unwrap();
+    let impostor_invite = InviteCode::new(SERVER_IP.to_string(), port, fingerprint).unwrap();
+    impostor_client
+        .open_connection_from_invite(
+            &impostor_invite.encode(&key),
+            &key,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let start = Instant::now();
+    while client.connection().state() != ConnectionState::Connected
+        || impostor_client.connection().state() != ConnectionState::Disconnected
+    {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+        impostor_client.pump();
+    }
+    assert_eq!(server.endpoint().clients().len(), 1);
+}
+
+#[test]
+fn custom_tls_configs() {
+    let port = 6078; // TODO Use port 0 and retrieve the port used by the server.
+
+    let certified_key = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let cert = CertificateDer::from(certified_key.cert);
+    let key = PrivatePkcs8KeyDer::from(certified_key.key_pair.serialize_der());
+    let provider = Arc::new(rustls::crypto::CryptoProvider {
+        cipher_suites: vec![rustls::crypto::ring::cipher_suite::TLS13_AES_128_GCM_SHA256],
+        ..rustls::crypto::ring::default_provider()
+    });
+    let server_crypto = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.clone()], key.into())
+        .unwrap();
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert).unwrap();
+    let client_crypto = rustls::ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port)
+                .with_tls_config(Arc::new(server_crypto)),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            ClientEndpointConfiguration::builder()
+                .with_server_ip(SERVER_IP, port)
+                .with_server_name("localhost")
+                .with_tls_config(Arc::new(client_crypto))
+                .build()
+                .unwrap(),
+            // Ignored, the custom config verifies the server with its roots
+            CertificateVerificationMode::SignedByCertificateAuthority,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let start = Instant::now();
+    while client.connection().state() != ConnectionState::Connected {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+    }
+    assert_eq!(server.endpoint().clients().len(), 1);
+}
+
+#[test]
+fn tls_key_log() {
+    let port = 6079; // TODO Use port 0 and retrieve the port used by the server.
+
+    let key_log_file =
+        std::env::temp_dir().join(format!("quinnet_keylog_{}.txt", std::process::id()));
+    std::env::set_var("SSLKEYLOGFILE", &key_log_file);
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port).with_key_log(true),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            ClientEndpointConfiguration::builder()
+                .with_server_ip(SERVER_IP, port)
+                .with_local_bind_ip(LOCAL_BIND_IP, 0)
+                .with_key_log(true)
+                .build()
+                .unwrap(),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let start = Instant::now();
+    while client.connection().state() != ConnectionState::Connected {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        server.pump();
+        client.pump();
+    }
+    std::env::remove_var("SSLKEYLOGFILE");
+
+    // Both the client and the server log the secrets of the handshake
+    let key_log = std::fs::read_to_string(&key_log_file).unwrap();
+    assert!(
+        key_log
+            .lines()
+            .filter(|line| line.starts_with("CLIENT_HANDSHAKE_TRAFFIC_SECRET"))
+            .count()
+            >= 2
+    );
+    assert!(key_log.contains("SERVER_TRAFFIC_SECRET_0"));
+    std::fs::remove_file(&key_log_file).unwrap();
+}
+
+#[test]
+fn congestion_monitor() {
+    let mut path = PathStats::default();
+    path.current_mtu = 1200;
+    path.cwnd = 12_000;
+    let mut monitor = CongestionMonitor::default();
+    assert!(monitor.in_slow_start());
+    assert!(monitor.update(path).is_empty());
+
+    // The first loss ends the slow start
+    path.congestion_events = 1;
+    path.lost_packets = 2;
+    path.lost_bytes = 2400;
+    path.cwnd = 8400;
+    assert_eq!(
+        monitor.update(path),
+        vec![
+            CongestionEventKind::SlowStartExited,
+            CongestionEventKind::LossEpisode {
+                lost_packets: 2,
+                lost_bytes: 2400
+            }
+        ]
+    );
+    assert!(!monitor.in_slow_start());
+    assert!(monitor.update(path).is_empty());
+
+    // Congestion events between two updates are reported once
+    path.congestion_events = 3;
+    path.lost_packets = 5;
+    path.lost_bytes = 6000;
+    path.cwnd = 6000;
+    assert_eq!(
+        monitor.update(path),
+        vec![CongestionEventKind::LossEpisode {
+            lost_packets: 3,
+            lost_bytes: 3600
+        }]
+    );
+
+    // A persistent congestion collapses the window, back to slow start
+    path.congestion_events = 4;
+    path.lost_packets = 15;
+    path.lost_bytes = 18000;
+    path.cwnd = 2400;
+    assert_eq!(
+        monitor.update(path),
+        vec![
+            CongestionEventKind::LossEpisode {
+                lost_packets: 10,
+                lost_bytes: 12000
+            },
+            CongestionEventKind::SlowStartEntered
+        ]
+    );
+    assert!(monitor.in_slow_start());
+
+    // The window grows back without a congestion event, until the next loss
+    path.cwnd = 20_000;
+    assert!(monitor.update(path).is_empty());
+    path.congestion_events = 5;
+    path.cwnd = 14_000;
+    assert_eq!(
+        monitor.update(path)[0],
+        CongestionEventKind::SlowStartExited
+    );
+}
+
+#[test]
+fn max_datagram_size_changes() {
+    let port = 6083; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    // The initial size is reported once connected, then each change of the path MTU discovery
+    let mut client_changes = Vec::new();
+    let mut server_changes = Vec::new();
+    let start = Instant::now();
+    loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        server_changes.extend(server.pump().into_iter().filter_map(|event| match event {
+            QuinnetServerEvent::MaxDatagramSizeChanged(event) => Some(event),
+            _ => None,
+        }));
+        client_changes.extend(client.pump().into_iter().filter_map(|event| match event {
+            QuinnetClientEvent::MaxDatagramSizeChanged(event) => Some(event),
+            _ => None,
+        }));
+        let (Some(client_change), Some(server_change)) =
+            (client_changes.last(), server_changes.last())
+        else {
+            continue;
+        };
+        let server_size = server
+            .endpoint()
+            .get_connection(server_change.id)
+            .unwrap()
+            .max_datagram_size();
+        if client_change.max_datagram_size == client.connection().max_datagram_size()
+            && server_change.max_datagram_size == server_size
+        {
+            break;
+        }
+    }
+    let client_sizes: Vec<_> = client_changes
+        .iter()
+        .map(|change| (change.previous, change.max_datagram_size))
+        .collect();
+    let server_sizes: Vec<_> = server_changes
+        .iter()
+        .map(|change| (change.previous, change.max_datagram_size))
+        .collect();
+    for sizes in [client_sizes, server_sizes] {
+        assert_eq!(sizes[0].0, None);
+        assert!(sizes[0].1.is_some());
+        assert!(sizes
+            .iter()
+            .zip(sizes.iter().skip(1))
+            .all(|(older, newer)| newer.0 == older.1));
+    }
+
+    // The channel id is the only header of the payloads of a plain unreliable channel, the headers of the options are deducted
+    let connection = client.connection_mut();
+    let plain = connection
+        .open_channel(ChannelConfig::unreliable())
+        .unwrap();
+    let stamped = connection
+        .open_channel(
+            ChannelConfig::unreliable()
+                .acknowledged()
+                .replay_protected(),
+        )
+        .unwrap();
+    assert_eq!(
+        connection.max_unreliable_payload_size(plain),
+        connection.max_datagram_size().map(|size| size - 1)
+    );
+    assert_eq!(
+        connection.max_unreliable_payload_size(stamped),
+        connection
+            .max_datagram_size()
+            .map(|size| size - 1 - ACK_HEADER_LEN - REPLAY_HEADER_LEN)
+    );
+    // Not an unreliable channel
+    assert_eq!(connection.max_unreliable_payload_size(0), None);
+}
+
+#[test]
+fn handshake_stats() {
+    let port = 6091; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port).with_address_validation(),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    let mut connect = |server: &mut QuinnetServer, verification| {
+        let connection_id = client
+            .open_connection(
+                default_client_configuration(port),
+                verification,
+                ChannelsConfiguration::default(),
+            )
+            .unwrap();
+        let start = Instant::now();
+        loop {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            sleep(Duration::from_millis(5));
+            server.pump();
+            client.pump();
+            match client.get_connection_by_id(connection_id).unwrap().state() {
+                ConnectionState::Connecting => (),
+                state => break state,
+            }
+        }
+    };
+
+    // The address of the client is validated before its handshake
+    assert_eq!(
+        connect(&mut server, CertificateVerificationMode::SkipVerification),
+        ConnectionState::Connected
+    );
+    let stats = server.endpoint().endpoint_stats();
+    assert_eq!(stats.retries_sent(), 1);
+    assert_eq!(stats.pending_handshakes(), 0);
+    assert_eq!(stats.failed_handshakes(), 0);
+    assert_eq!(stats.refused_connections_count(), 0);
+
+    // The self-signed certificate of the server is rejected by the client
+    assert_eq!(
+        connect(
+            &mut server,
+            CertificateVerificationMode::SignedByCertificateAuthority
+        ),
+        ConnectionState::Disconnected
+    );
+    let start = Instant::now();
+    while server.endpoint().endpoint_stats().failed_handshakes() == 0 {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+    }
+    let stats = server.endpoint().endpoint_stats();
+    assert_eq!(stats.retries_sent(), 2);
+    assert_eq!(stats.pending_handshakes(), 0);
+    assert_eq!(stats.failed_handshakes(), 1);
+
+    server.endpoint_mut().set_accepting(false);
+    assert_eq!(
+        connect(&mut server, CertificateVerificationMode::SkipVerification),
+        ConnectionState::Disconnected
+    );
+    let stats = server.endpoint().endpoint_stats();
+    assert_eq!(stats.refused_connections(RefusalReason::NotAccepting), 1);
+    assert_eq!(stats.refused_connections(RefusalReason::NoService), 0);
+    assert_eq!(stats.refused_connections(RefusalReason::NoClientId), 0);
+    assert_eq!(stats.refused_connections_count(), 1);
+    assert_eq!(stats.connect_count(), 1);
+}
+
+#[test]
+fn connection_attempt_events() {
+    let port = 6092; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+
+    // The attempt is raised before the connection
+    let connection_id = client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut events = Vec::new();
+    let start = Instant::now();
+    while !events
+        .iter()
+        .any(|event| matches!(event, QuinnetServerEvent::Connection(_)))
+        || client.get_connection_by_id(connection_id).unwrap().state() != ConnectionState::Connected
+    {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        events.extend(server.pump());
+        client.pump();
+    }
+    let client_port = client
+        .get_connection_by_id(connection_id)
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let attempt = events.iter().position(|event| {
+        matches!(
+            event,
+            QuinnetServerEvent::ConnectionAttempt(ConnectionAttemptEvent { remote_addr })
+                if remote_addr.port() == client_port
+        )
+    });
+    let connection = events
+        .iter()
+        .position(|event| matches!(event, QuinnetServerEvent::Connection(_)));
+    assert!(attempt.unwrap() < connection.unwrap());
+
+    // The self-signed certificate of the server is rejected by the client
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SignedByCertificateAuthority,
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let mut events = Vec::new();
+    let start = Instant::now();
+    let failed = loop {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(5));
+        client.pump();
+        events.extend(server.pump());
+        if let Some(QuinnetServerEvent::HandshakeFailed(event)) = events.last() {
+            break event.clone();
+        }
+    };
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, QuinnetServerEvent::ConnectionAttempt(_))));
+    // Alert 48, unknown CA
+    assert_eq!(failed.tls_alert(), Some(48));
+}
@@ -0,0 +1,47 @@
+use bevy::prelude::App;
+
+use bevy_quinnet::{
+    client::QuinnetClient,
+    debug_hud::{client_report, server_report},
+    server::QuinnetServer,
+};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn debug_hud_reports() {
+    let port = 6040;
+    let mut server_app: App = start_simple_server_app(port);
+    let mut client_app: App = start_simple_client_app(port);
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let client_channel = get_default_client_channel(&client_app);
+    let server_channel = get_default_server_channel(&server_app);
+
+    let report = client_report(client_app.world().resource::<QuinnetClient>());
+    assert!(report.starts_with("Client\n"));
+    assert!(report.contains("rtt"), "Should report the transport stats");
+    assert!(report.contains(&format!("channel {}: 0 pending", client_channel)));
+
+    let report = server_report(server_app.world().resource::<QuinnetServer>());
+    assert!(report.contains("1 clients"));
+    assert!(report.contains(&format!("client {}", client_id)));
+    assert!(report.contains(&format!("channel {}: 0 pending", server_channel)));
+
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .stop_endpoint()
+        .unwrap();
+    let report = server_report(server_app.world().resource::<QuinnetServer>());
+    assert_eq!(report, "Server not listening\n");
+}
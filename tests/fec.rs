@@ -0,0 +1,197 @@
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::{FromWorld, World};
+
+use bevy_quinnet::{
+    server::{
+        certificate::CertificateRetrievalMode, QuinnetServer, QuinnetServerEvent,
+        ServerEndpointConfiguration,
+    },
+    shared::{
+        channels::{ChannelConfig, ChannelsConfiguration, FEC_FLUSH_DELAY, FEC_HEADER_LEN},
+        transport::{
+            memory::{MemoryConnection, MEMORY_MAX_DATAGRAM_SIZE},
+            TransportConnection,
+        },
+    },
+};
+use bytes::Bytes;
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+//                                                       //
+//                          Test                         //
+//                                                       //
+///////////////////////////////////////////////////////////
+
+fn fec_header(group: u64, index: u8, count: u8, len: u16) -> Vec<u8> {
+    let mut datagram = vec![0];
+    datagram.extend_from_slice(&group.to_be_bytes()[3..]);
+    datagram.push(index);
+    datagram.push(count);
+    datagram.extend_from_slice(&len.to_be_bytes());
+    datagram
+}
+
+fn fec_datagram(group: u64, index: u8, payload: &[u8]) -> Bytes {
+    let mut datagram = fec_header(group, index, 0, payload.len() as u16);
+    datagram.extend_from_slice(payload);
+    Bytes::from(datagram)
+}
+
+/// XOR of the payloads and of their lengths
+fn parity_datagram(group: u64, payloads: &[&[u8]]) -> Bytes {
+    let mut parity = Vec::new();
+    let mut len = 0;
+    for payload in payloads {
+        if parity.len() < payload.len() {
+            parity.resize(payload.len(), 0);
+        }
+        for (parity, byte) in parity.iter_mut().zip(payload.iter()) {
+            *parity ^= byte;
+        }
+        len ^= payload.len() as u16;
+    }
+    let mut datagram = fec_header(group, u8::MAX, payloads.len() as u8, len);
+    datagram.extend_from_slice(&parity);
+    Bytes::from(datagram)
+}
+
+#[test]
+fn fec_channels() {
+    let port = 6046; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            ChannelsConfiguration::from_configs(vec![ChannelConfig::unreliable().fec(2)]).unwrap(),
+        )
+        .unwrap();
+
+    // A raw peer, to see and drop each datagram
+    let (client_end, server_end) = MemoryConnection::pair();
+    server.endpoint().add_transport_connection(server_end);
+    let client_id = loop {
+        sleep(Duration::from_millis(5));
+        if let Some(client_id) = server.pump().into_iter().find_map(|event| match event {
+            QuinnetServerEvent::Connection(event) => Some(event.id),
+            _ => None,
+        }) {
+            break client_id;
+        }
+    };
+    let read_datagram = || futures::executor::block_on(client_end.read_datagram()).unwrap();
+
+    // A parity datagram after each group
+    for payload in [&b"jump"[..], b"crouch", b"fire"] {
+        server
+            .endpoint_mut()
+            .send_payload(client_id, Bytes::copy_from_slice(payload))
+            .unwrap();
+    }
+    assert_eq!(read_datagram(), fec_datagram(0, 0, b"jump"));
+    assert_eq!(read_datagram(), fec_datagram(0, 1, b"crouch"));
+    assert_eq!(read_datagram(), parity_datagram(0, &[b"jump", b"crouch"]));
+    assert_eq!(read_datagram(), fec_datagram(1, 0, b"fire"));
+    // The partial group gets its parity once the channel is idle
+    let start = Instant::now();
+    assert_eq!(read_datagram(), parity_datagram(1, &[b"fire"]));
+    assert!(start.elapsed() >= FEC_FLUSH_DELAY / 2);
+
+    // The parity of the largest payloads fits in a datagram
+    let max_payload_size = server
+        .endpoint()
+        .get_connection(client_id)
+        .unwrap()
+        .max_unreliable_payload_size(0)
+        .unwrap();
+    assert_eq!(
+        max_payload_size,
+        MEMORY_MAX_DATAGRAM_SIZE - 1 - FEC_HEADER_LEN
+    );
+    let largest = vec![7; max_payload_size];
+    let shorter = vec![9; max_payload_size - 3];
+    for payload in [&largest, &shorter] {
+        server
+            .endpoint_mut()
+            .send_payload(client_id, Bytes::copy_from_slice(payload))
+            .unwrap();
+    }
+    assert_eq!(read_datagram(), fec_datagram(2, 0, &largest));
+    assert_eq!(read_datagram(), fec_datagram(2, 1, &shorter));
+    let parity = read_datagram();
+    assert_eq!(parity.len(), MEMORY_MAX_DATAGRAM_SIZE);
+    assert_eq!(parity, parity_datagram(2, &[&largest, &shorter]));
+
+    // A payload which could not be sent is left out of its group
+    for payload in [
+        vec![1; max_payload_size + 1],
+        b"reload".to_vec(),
+        b"aim".to_vec(),
+    ] {
+        server
+            .endpoint_mut()
+            .send_payload(client_id, Bytes::from(payload))
+            .unwrap();
+    }
+    assert_eq!(read_datagram(), fec_datagram(3, 0, b"reload"));
+    assert_eq!(read_datagram(), fec_datagram(3, 1, b"aim"));
+    assert_eq!(read_datagram(), parity_datagram(3, &[b"reload", b"aim"]));
+
+    // A single loss per group is recovered
+    let mut wait_received = |count: usize| {
+        let mut received = Vec::new();
+        while received.len() < count {
+            sleep(Duration::from_millis(5));
+            server.pump();
+            while let Some((_, payload)) = server
+                .endpoint_mut()
+                .receive_payload_from(client_id)
+                .unwrap()
+            {
+                received.push(payload);
+            }
+        }
+        received
+    };
+    client_end
+        .send_datagram(fec_datagram(0, 1, b"crouch"))
+        .unwrap();
+    client_end
+        .send_datagram(parity_datagram(0, &[b"jump", b"crouch"]))
+        .unwrap();
+    assert_eq!(
+        wait_received(2),
+        vec![Bytes::from_static(b"crouch"), Bytes::from_static(b"jump")]
+    );
+
+    // The original of a recovered payload is not delivered again, two losses are not recovered
+    client_end
+        .send_datagram(fec_datagram(0, 0, b"jump"))
+        .unwrap();
+    client_end
+        .send_datagram(parity_datagram(1, &[b"fire", b"reload"]))
+        .unwrap();
+    client_end
+        .send_datagram(fec_datagram(2, 0, b"aim"))
+        .unwrap();
+    assert_eq!(wait_received(1), vec![Bytes::from_static(b"aim")]);
+
+    // The single payload of a partial group is recovered from its parity alone
+    client_end
+        .send_datagram(parity_datagram(3, &[b"last"]))
+        .unwrap();
+    assert_eq!(wait_received(1), vec![Bytes::from_static(b"last")]);
+}
@@ -0,0 +1,138 @@
+use std::{thread::sleep, time::Duration};
+
+use bevy::prelude::{FromWorld, World};
+
+use bevy_quinnet::{
+    client::{
+        certificate::CertificateVerificationMode, input::InputStream, QuinnetClient,
+        QuinnetClientEvent,
+    },
+    server::{
+        certificate::CertificateRetrievalMode, conditions::ClientConditions, input::InputReceiver,
+        QuinnetServer, QuinnetServerEvent, ServerEndpointConfiguration,
+    },
+    shared::{
+        channels::{ChannelConfig, ChannelsConfiguration},
+        input::InputTick,
+        ClientId,
+    },
+};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn input_streams() {
+    let port = 6047; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    let channels =
+        ChannelsConfiguration::from_configs(vec![ChannelConfig::unreliable().compressed()])
+            .unwrap();
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels,
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+
+    let mut stream = InputStream::<u8>::new(0).with_history(3);
+    let mut receiver = InputReceiver::<u8>::new(0);
+
+    // Each tick is delivered once, although carried by several messages
+    for tick in 1..=3 {
+        stream
+            .send(client.connection_mut(), tick, tick as u8 * 10)
+            .unwrap();
+    }
+    assert_eq!(
+        receive_inputs(&mut server, &mut receiver, client_id, 3),
+        vec![(1, 10), (2, 20), (3, 30)]
+    );
+
+    // A lost message is covered by the next one
+    server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .set_conditions(Some(ClientConditions::new(Duration::ZERO).with_loss(1.)));
+    stream.send(client.connection_mut(), 4, 40).unwrap();
+    for _ in 0..20 {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        assert!(receiver.receive(server.endpoint_mut()).is_empty());
+    }
+    server
+        .endpoint_mut()
+        .get_connection_mut(client_id)
+        .unwrap()
+        .set_conditions(None);
+    stream.send(client.connection_mut(), 5, 50).unwrap();
+    assert_eq!(
+        receive_inputs(&mut server, &mut receiver, client_id, 5),
+        vec![(4, 40), (5, 50)]
+    );
+
+    // A skipped tick restarts the history
+    stream.send(client.connection_mut(), 9, 90).unwrap();
+    assert_eq!(
+        receive_inputs(&mut server, &mut receiver, client_id, 9),
+        vec![(9, 90)]
+    );
+}
+
+fn receive_inputs(
+    server: &mut QuinnetServer,
+    receiver: &mut InputReceiver<u8>,
+    client_id: ClientId,
+    until_tick: InputTick,
+) -> Vec<(InputTick, u8)> {
+    let mut received = Vec::new();
+    while receiver.last_tick(client_id) != Some(until_tick) {
+        sleep(Duration::from_millis(5));
+        server.pump();
+        received.extend(
+            receiver
+                .receive(server.endpoint_mut())
+                .into_iter()
+                .map(|event| (event.tick, event.input)),
+        );
+    }
+    received
+}
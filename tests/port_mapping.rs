@@ -0,0 +1,111 @@
+#![cfg(feature = "port-mapping")]
+
+use std::{
+    net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::App};
+use bevy_quinnet::{
+    server::{
+        certificate::CertificateRetrievalMode, port_mapping::PortMappingConfiguration,
+        QuinnetServer, QuinnetServerPlugin, ServerEndpointConfiguration,
+    },
+    shared::channels::ChannelsConfiguration,
+};
+
+const FAKE_EXTERNAL_IP: Ipv4Addr = Ipv4Addr::new(203, 0, 113, 7);
+const FAKE_EXTERNAL_PORT: u16 = 40000;
+
+/// Answers NAT-PMP requests on localhost, and forwards the received mapping requests as (internal port, lifetime)
+fn spawn_fake_gateway() -> mpsc::Receiver<(u16, u32)> {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 5351)).unwrap();
+    let (requests_send, requests_recv) = mpsc::channel();
+    thread::spawn(move || loop {
+        let mut request = [0; 12];
+        let Ok((_, from)) = socket.recv_from(&mut request) else {
+            break;
+        };
+        let mut response = vec![0, 128 + request[1], 0, 0, 0, 0, 0, 1];
+        match request[1] {
+            0 => response.extend_from_slice(&FAKE_EXTERNAL_IP.octets()),
+            _ => {
+                let internal_port = u16::from_be_bytes([request[4], request[5]]);
+                let lifetime =
+                    u32::from_be_bytes([request[8], request[9], request[10], request[11]]);
+                response.extend_from_slice(&request[4..6]);
+                response.extend_from_slice(&FAKE_EXTERNAL_PORT.to_be_bytes());
+                response.extend_from_slice(&lifetime.to_be_bytes());
+                if requests_send.send((internal_port, lifetime)).is_err() {
+                    break;
+                }
+            }
+        }
+        socket.send_to(&response, from).unwrap();
+    });
+    requests_recv
+}
+
+#[test]
+fn port_mapping_lifecycle() {
+    let gateway_requests = spawn_fake_gateway();
+
+    let mut server_app = App::new();
+    server_app.add_plugins((
+        ScheduleRunnerPlugin::default(),
+        QuinnetServerPlugin::default(),
+    ));
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(Ipv4Addr::UNSPECIFIED, 0).with_port_mapping(
+                PortMappingConfiguration::default().with_gateway(Ipv4Addr::LOCALHOST),
+            ),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: Ipv4Addr::LOCALHOST.to_string(),
+            },
+            ChannelsConfiguration::default(),
+        )
+        .unwrap();
+    let local_port = server_app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .local_addr()
+        .port();
+
+    let mapping = loop {
+        server_app.update();
+        if let Some(mapping) = server_app
+            .world()
+            .resource::<QuinnetServer>()
+            .endpoint()
+            .port_mapping()
+        {
+            break mapping;
+        }
+    };
+    assert_eq!(mapping.internal_port, local_port);
+    assert_eq!(
+        mapping.external_addr,
+        SocketAddrV4::new(FAKE_EXTERNAL_IP, FAKE_EXTERNAL_PORT)
+    );
+    assert_eq!(
+        gateway_requests.recv_timeout(Duration::from_secs(1)),
+        Ok((local_port, 7200))
+    );
+
+    // Stopping the endpoint removes the mapping
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .stop_endpoint()
+        .unwrap();
+    assert_eq!(
+        gateway_requests.recv_timeout(Duration::from_secs(5)),
+        Ok((local_port, 0))
+    );
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::{FromWorld, World};
+use bevy_quinnet::{
+    client::{certificate::CertificateVerificationMode, QuinnetClient, QuinnetClientEvent},
+    server::{
+        certificate::CertificateRetrievalMode, QuinnetServer, QuinnetServerEvent,
+        ServerEndpointConfiguration,
+    },
+    shared::channels::{ChannelKind, ChannelsConfiguration},
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+//                                                       //
+//                          Test                         //
+//                                                       //
+///////////////////////////////////////////////////////////
+
+/// Names of the spans created, with their numeric fields
+type RecordedSpans = Arc<Mutex<Vec<(&'static str, BTreeMap<&'static str, u64>)>>>;
+
+struct SpanRecorder {
+    spans: RecordedSpans,
+    next_id: AtomicU64,
+}
+
+struct U64Fields(BTreeMap<&'static str, u64>);
+
+impl Visit for U64Fields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name(), value);
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+impl Subscriber for SpanRecorder {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span()
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = U64Fields(BTreeMap::new());
+        span.record(&mut fields);
+        self.spans
+            .lock()
+            .unwrap()
+            .push((span.metadata().name(), fields.0));
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn profiling_spans() {
+    let port = 6084; // TODO Use port 0 and retrieve the port used by the server.
+
+    let spans = RecordedSpans::default();
+    tracing::subscriber::set_global_default(SpanRecorder {
+        spans: spans.clone(),
+        next_id: AtomicU64::new(1),
+    })
+    .unwrap();
+
+    let mut channels = ChannelsConfiguration::default();
+    let unreliable = channels.add(ChannelKind::Unreliable).unwrap();
+    let mut world = World::new();
+    let mut server = QuinnetServer::from_world(&mut world);
+    let mut client = QuinnetClient::from_world(&mut world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, port),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: SERVER_IP.to_string(),
+            },
+            channels.clone(),
+        )
+        .unwrap();
+    client
+        .open_connection(
+            default_client_configuration(port),
+            CertificateVerificationMode::SkipVerification,
+            channels,
+        )
+        .unwrap();
+    let mut client_id = None;
+    let mut client_connected = false;
+    while client_id.is_none() || !client_connected {
+        sleep(Duration::from_millis(5));
+        for event in server.pump() {
+            if let QuinnetServerEvent::Connection(event) = event {
+                client_id = Some(event.id);
+            }
+        }
+        client_connected |= client
+            .pump()
+            .iter()
+            .any(|event| matches!(event, QuinnetClientEvent::Connection(_)));
+    }
+    let client_id = client_id.unwrap();
+
+    let message = SharedMessage::TestMessage("profiled".to_string());
+    server
+        .endpoint_mut()
+        .send_message(client_id, message.clone())
+        .unwrap();
+    server
+        .endpoint_mut()
+        .send_message_on(client_id, unreliable, message.clone())
+        .unwrap();
+    let start = Instant::now();
+    let mut received = 0;
+    while received < 2 {
+        assert!(start.elapsed() < Duration::from_secs(2));
+        sleep(Duration::from_millis(5));
+        client.pump();
+        while client
+            .connection_mut()
+            .receive_message::<SharedMessage>()
+            .unwrap()
+            .is_some()
+        {
+            received += 1;
+        }
+    }
+
+    // Each step of the send of a message is labeled with its connection and channel
+    let spans = spans.lock().unwrap();
+    let has_span = |name: &str, field: &str, value: u64| {
+        spans
+            .iter()
+            .any(|(span, fields)| *span == name && fields.get(field) == Some(&value))
+    };
+    assert!(has_span("quinnet_serialize", "client_id", client_id));
+    assert!(has_span(
+        "quinnet_serialize",
+        "channel_id",
+        unreliable as u64
+    ));
+    assert!(has_span("quinnet_connection", "connection_id", client_id));
+    assert!(has_span(
+        "quinnet_send_channel",
+        "channel_id",
+        unreliable as u64
+    ));
+    for step in ["quinnet_encode", "quinnet_frame", "quinnet_write"] {
+        assert!(spans.iter().any(|(span, _)| *span == step), "{}", step);
+    }
+}
@@ -0,0 +1,104 @@
+use bevy::{app::ScheduleRunnerPlugin, prelude::App};
+use bevy_quinnet::{
+    client::{QuinnetClient, QuinnetClientPlugin},
+    server::QuinnetServer,
+    shared::{channels::ChannelsConfiguration, transport::memory::MemoryConnection},
+};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn custom_streams_on_quic_connections() {
+    let port = 6032; // TODO Use port 0 and retrieve the port used by the server.
+
+    let mut server_app = start_simple_server_app(port);
+    let mut client_app = start_simple_client_app(port);
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let client_connection = client_app
+        .world()
+        .resource::<QuinnetClient>()
+        .connection()
+        .quic_connection()
+        .expect("The client should be connected over QUIC");
+    let server_connection = server_app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .get_quic_connection(client_id)
+        .expect("The client should be connected over QUIC");
+    // A custom request/response sub-protocol on a bidirectional stream
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let response = runtime.block_on(async move {
+        let server = tokio::spawn(async move {
+            let (mut send, mut recv) = server_connection.accept_bi().await.unwrap();
+            let request = recv.read_to_end(64).await.unwrap();
+            send.write_all(&[&request[..], b" pong"].concat())
+                .await
+                .unwrap();
+            send.finish().unwrap();
+            // Keep the stream alive until the peer read it
+            let _ = send.stopped().await;
+        });
+        let (mut send, mut recv) = client_connection.open_bi().await.unwrap();
+        send.write_all(b"ping").await.unwrap();
+        send.finish().unwrap();
+        let response = recv.read_to_end(64).await.unwrap();
+        server.await.unwrap();
+        response
+    });
+    assert_eq!(response, b"ping pong");
+
+    // The Quinnet channels are still usable
+    let mut msg_counter = 0;
+    let client_channel = get_default_client_channel(&client_app);
+    send_and_test_client_message(
+        client_id,
+        client_channel,
+        &mut client_app,
+        &mut server_app,
+        &mut msg_counter,
+    );
+
+    // No QUIC connection for custom transports
+    let mut memory_client_app = App::new();
+    memory_client_app.add_plugins((
+        ScheduleRunnerPlugin::default(),
+        QuinnetClientPlugin::default(),
+    ));
+    let (client_end, server_end) = MemoryConnection::pair();
+    server_app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .add_transport_connection(server_end);
+    memory_client_app
+        .world_mut()
+        .resource_mut::<QuinnetClient>()
+        .open_transport_connection(client_end, ChannelsConfiguration::default())
+        .unwrap();
+    let memory_client_id = wait_for_client_connected(&mut memory_client_app, &mut server_app);
+    assert!(memory_client_app
+        .world()
+        .resource::<QuinnetClient>()
+        .connection()
+        .quic_connection()
+        .is_none());
+    assert!(server_app
+        .world()
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .get_connection(memory_client_id)
+        .unwrap()
+        .quic_connection()
+        .is_none());
+}
@@ -0,0 +1,389 @@
+use std::{thread::sleep, time::Duration};
+
+use bevy::prelude::{Component, Event, EventReader, ResMut, Resource, Update, World};
+use bevy_quinnet::{
+    client::QuinnetClient,
+    replication::{
+        upload::{
+            ClientAuthority, ClientOwner, ComponentUpload, UploadRejectedEvent, UploadRejection,
+        },
+        FromClient, NetworkVisibility, PriorityAccumulators, Replicated, ReplicationAppExt,
+        ReplicationDirection, ServerEntity, ServerEntityMap,
+    },
+    server::QuinnetServer,
+    shared::{channels::ChannelKind, ClientId},
+};
+use serde::{Deserialize, Serialize};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+#[derive(Event, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Ping(u32);
+
+#[derive(Resource, Debug, Default)]
+struct ReceivedPings {
+    pings: Vec<u32>,
+    from_clients: Vec<(ClientId, u32)>,
+}
+
+fn collect_pings(mut received: ResMut<ReceivedPings>, mut pings: EventReader<Ping>) {
+    received.pings.extend(pings.read().map(|ping| ping.0));
+}
+
+fn collect_client_pings(
+    mut received: ResMut<ReceivedPings>,
+    mut pings: EventReader<FromClient<Ping>>,
+) {
+    received.from_clients.extend(
+        pings
+            .read()
+            .map(|from_client| (from_client.client_id, from_client.event.0)),
+    );
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn replicated_events() {
+    let port = 6066; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app = start_simple_server_app(port);
+    server_app
+        .replicate_event::<Ping>(ReplicationDirection::Bidirectional, 0)
+        .init_resource::<ReceivedPings>()
+        .add_systems(Update, (collect_pings, collect_client_pings));
+    let mut client_app = start_simple_client_app(port);
+    client_app
+        .replicate_event::<Ping>(ReplicationDirection::Bidirectional, 0)
+        .init_resource::<ReceivedPings>()
+        .add_systems(Update, collect_pings);
+
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    client_app.world_mut().send_event(Ping(1));
+    loop {
+        client_app.update();
+        server_app.update();
+        if !server_app
+            .world()
+            .resource::<ReceivedPings>()
+            .from_clients
+            .is_empty()
+        {
+            break;
+        }
+        sleep(Duration::from_millis(1));
+    }
+
+    server_app.world_mut().send_event(Ping(2));
+    loop {
+        server_app.update();
+        client_app.update();
+        if client_app.world().resource::<ReceivedPings>().pings.len() >= 2 {
+            break;
+        }
+        sleep(Duration::from_millis(1));
+    }
+
+    // Give the peers a chance to echo the received events back
+    for _ in 0..10 {
+        server_app.update();
+        client_app.update();
+        sleep(Duration::from_millis(10));
+    }
+
+    let server_received = server_app.world().resource::<ReceivedPings>();
+    assert_eq!(
+        server_received.from_clients,
+        vec![(client_id, 1)],
+        "The server should have received the event of the client once"
+    );
+    assert_eq!(
+        server_received.pings,
+        vec![1, 2],
+        "The server should have re-emitted the event of the client, then sent its own"
+    );
+    assert_eq!(
+        client_app.world().resource::<ReceivedPings>().pings,
+        vec![1, 2],
+        "The client should have received the event of the server, without an echo of its own"
+    );
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn visibility_filtered_broadcast() {
+    let port = 6067; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app = start_simple_server_app(port);
+    let mut client_app_1 = start_simple_client_app(port);
+    let client_id_1 = wait_for_client_connected(&mut client_app_1, &mut server_app);
+    let mut client_app_2 = start_simple_client_app(port);
+    let client_id_2 = wait_for_client_connected(&mut client_app_2, &mut server_app);
+
+    let mut visibility = NetworkVisibility::only([client_id_1]);
+    assert!(visibility.is_visible_to(client_id_1));
+    assert!(!visibility.is_visible_to(client_id_2));
+
+    let hidden_msg = SharedMessage::TestMessage("hidden".to_string());
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .broadcast_visible_message_on(&visibility, 0, hidden_msg.clone())
+        .unwrap();
+    assert_eq!(
+        wait_for_server_message(&mut client_app_1),
+        (0, hidden_msg),
+        "The client seeing the entity should receive the message"
+    );
+
+    visibility.show(client_id_2);
+    visibility.hide(client_id_1);
+    let shown_msg = SharedMessage::TestMessage("shown".to_string());
+    server_app
+        .world_mut()
+        .resource_mut::<QuinnetServer>()
+        .endpoint_mut()
+        .broadcast_visible_message_on(&visibility, 0, shown_msg.clone())
+        .unwrap();
+    assert_eq!(
+        wait_for_server_message(&mut client_app_2),
+        (0, shown_msg),
+        "The first message should not have been sent to the client not seeing the entity"
+    );
+
+    sleep(Duration::from_millis(100));
+    client_app_1.update();
+    assert_eq!(
+        client_app_1
+            .world_mut()
+            .resource_mut::<QuinnetClient>()
+            .connection_mut()
+            .receive_message::<SharedMessage>()
+            .unwrap(),
+        None,
+        "The client no longer seeing the entity should not receive the message"
+    );
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn replicated_entities() {
+    let port = 6068; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app = start_simple_server_app(port);
+    server_app.replicate_entities(0);
+    let mut client_app = start_simple_client_app(port);
+    client_app.replicate_entities(0);
+
+    // Spawned before the connection of the client
+    let early_entity = server_app.world_mut().spawn(Replicated).id();
+    server_app.update();
+    wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let entity = server_app.world_mut().spawn(Replicated).id();
+    server_app
+        .world_mut()
+        .spawn((Replicated, NetworkVisibility::hidden()));
+    loop {
+        server_app.update();
+        client_app.update();
+        if client_app.world().resource::<ServerEntityMap>().len() >= 2 {
+            break;
+        }
+        sleep(Duration::from_millis(1));
+    }
+    let client_entity = {
+        let entity_map = client_app.world().resource::<ServerEntityMap>();
+        let client_entity = entity_map
+            .client_entity(entity)
+            .expect("The entity should have been spawned on the client");
+        assert!(
+            entity_map.client_entity(early_entity).is_some(),
+            "The entity spawned before the connection should have been spawned on the client"
+        );
+        assert_eq!(entity_map.server_entity(client_entity), Some(entity));
+        client_entity
+    };
+    assert_eq!(
+        client_app.world().get::<ServerEntity>(client_entity),
+        Some(&ServerEntity(entity))
+    );
+
+    server_app.world_mut().despawn(entity);
+    loop {
+        server_app.update();
+        client_app.update();
+        if client_app.world().resource::<ServerEntityMap>().len() < 2 {
+            break;
+        }
+        sleep(Duration::from_millis(1));
+    }
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<ServerEntityMap>()
+            .client_entity(entity),
+        None
+    );
+    assert!(
+        client_app.world().get_entity(client_entity).is_err(),
+        "The client entity should have been despawned"
+    );
+    assert_eq!(
+        client_app.world().resource::<ServerEntityMap>().len(),
+        1,
+        "The hidden entity should not have been spawned on the client"
+    );
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Position(f32);
+
+#[derive(Resource, Debug, Default)]
+struct Rejections(Vec<UploadRejectedEvent>);
+
+fn collect_rejections(
+    mut rejections: ResMut<Rejections>,
+    mut events: EventReader<UploadRejectedEvent>,
+) {
+    rejections.0.extend(events.read().cloned());
+}
+
+#[test]
+fn uploaded_components() {
+    let port = 6069; // TODO Use port 0 and retrieve the port used by the server.
+    let mut server_app = start_simple_server_app(port);
+    let mut client_app = start_simple_client_app(port);
+    let server_channel = open_server_channel(ChannelKind::default(), &mut server_app);
+    let client_channel = open_client_channel(ChannelKind::default(), &mut client_app);
+    server_app
+        .replicate_entities(0)
+        .upload_component(
+            ComponentUpload::<Position>::new(server_channel)
+                .with_validator(|_, _, _, position| position.0 >= 0.),
+        )
+        .init_resource::<Rejections>()
+        .add_systems(Update, collect_rejections);
+    client_app.replicate_entities(0).upload_component(
+        ComponentUpload::<Position>::new(client_channel).with_interval(Duration::from_millis(10)),
+    );
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+
+    let server_entity = server_app
+        .world_mut()
+        .spawn((Replicated, ClientOwner(client_id), Position(0.)))
+        .id();
+    let client_entity = loop {
+        server_app.update();
+        client_app.update();
+        if let Some(client_entity) = client_app
+            .world()
+            .resource::<ServerEntityMap>()
+            .client_entity(server_entity)
+        {
+            break client_entity;
+        }
+        sleep(Duration::from_millis(1));
+    };
+
+    client_app
+        .world_mut()
+        .entity_mut(client_entity)
+        .insert((ClientAuthority, Position(1.)));
+    loop {
+        client_app.update();
+        server_app.update();
+        if server_app.world().get::<Position>(server_entity) == Some(&Position(1.)) {
+            break;
+        }
+        sleep(Duration::from_millis(1));
+    }
+
+    client_app
+        .world_mut()
+        .entity_mut(client_entity)
+        .insert(Position(-1.));
+    loop {
+        client_app.update();
+        server_app.update();
+        if !server_app.world().resource::<Rejections>().0.is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(1));
+    }
+    let rejection = &server_app.world().resource::<Rejections>().0[0];
+    assert_eq!(rejection.client_id, client_id);
+    assert_eq!(rejection.entity, Some(server_entity));
+    assert_eq!(rejection.reason, UploadRejection::Invalid);
+    assert_eq!(
+        server_app.world().get::<Position>(server_entity),
+        Some(&Position(1.)),
+        "The rejected component should not have been applied"
+    );
+}
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn priority_accumulation() {
+    let mut world = World::new();
+    let low = world.spawn_empty().id();
+    let high = world.spawn_empty().id();
+    let client_id = 1;
+
+    let mut accumulators = PriorityAccumulators::default();
+    let tick = |accumulators: &mut PriorityAccumulators| {
+        accumulators.accumulate(client_id, low, 1.);
+        accumulators.accumulate(client_id, high, 1.5);
+    };
+
+    tick(&mut accumulators);
+    assert_eq!(accumulators.select(client_id, 1), vec![high]);
+    tick(&mut accumulators);
+    assert_eq!(
+        accumulators.select(client_id, 1),
+        vec![low],
+        "The starved entity should be selected"
+    );
+    assert_eq!(accumulators.priority(client_id, low), 0.);
+    assert_eq!(accumulators.priority(client_id, high), 1.5);
+    tick(&mut accumulators);
+    assert_eq!(accumulators.select(client_id, 1), vec![high]);
+
+    tick(&mut accumulators);
+    assert_eq!(
+        accumulators.select_within(client_id, 10, |entity| if entity == high { 20 } else { 5 }),
+        vec![low],
+        "The entity exceeding the budget should be skipped"
+    );
+    assert_eq!(accumulators.priority(client_id, high), 1.5);
+
+    accumulators.remove_entity(high);
+    assert_eq!(accumulators.select(client_id, 2), Vec::new());
+}
@@ -0,0 +1,300 @@
+use bevy::prelude::{FromWorld, World};
+use bevy_quinnet::{
+    client::{
+        certificate::{
+            CertVerificationInfo, CertVerificationStatus, CertVerifierAction, ServerName,
+        },
+        connection::ConnectionState,
+        QuinnetClient, QuinnetClientEvent, QuinnetConnectionError,
+    },
+    server::{
+        certificate::CertificateRetrievalMode, DisconnectReason, QuinnetServer, QuinnetServerEvent,
+        ServerEndpointConfiguration,
+    },
+    shared::{
+        certificate::CertificateFingerprint,
+        channels::{ChannelConfig, ChannelId, ChannelsConfiguration},
+        close::CloseCode,
+        hardening::ProtocolViolation,
+    },
+    testing::{relay, LinkConditions, ScriptedClient, ScriptedServer},
+};
+use bytes::Bytes;
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+fn cert_info() -> CertVerificationInfo {
+    CertVerificationInfo {
+        server_name: ServerName::try_from("localhost").unwrap(),
+        port: 6000,
+        fingerprint: CertificateFingerprint::new([1; 32]),
+        known_fingerprint: None,
+    }
+}
+
+fn start_server(world: &mut World, channels_config: ChannelsConfiguration) -> QuinnetServer {
+    let mut server = QuinnetServer::from_world(world);
+    server
+        .start_endpoint(
+            ServerEndpointConfiguration::from_ip(LOCAL_BIND_IP, 0),
+            CertificateRetrievalMode::GenerateSelfSigned {
+                server_hostname: "localhost".to_string(),
+            },
+            channels_config,
+        )
+        .unwrap();
+    server.pump();
+    server
+}
+
+#[test]
+fn scripted_client_connection_lifecycle() {
+    let mut world = World::new();
+    let mut client = QuinnetClient::from_world(&mut world);
+    let (connection_id, mut server) = client
+        .open_scripted_connection(ChannelsConfiguration::default())
+        .unwrap();
+
+    assert!(client.pump().is_empty());
+    assert_eq!(client.connection().state(), ConnectionState::Connecting);
+
+    // Connection
+    server.connect(Some(42)).unwrap();
+    server.send_payload(0, "welcome").unwrap();
+    assert!(matches!(
+        client.pump()[..],
+        [QuinnetClientEvent::Connection(event)] if event.id == connection_id && event.client_id == Some(42)
+    ));
+    let connection = client.connection_mut();
+    assert_eq!(connection.state(), ConnectionState::Connected);
+    assert_eq!(
+        connection.receive_payload().unwrap(),
+        Some((0, Bytes::from("welcome")))
+    );
+    connection.send_payload("first").unwrap();
+    connection.send_payload("second").unwrap();
+    assert_eq!(
+        server.sent_payloads(),
+        vec![(0, Bytes::from("first")), (0, Bytes::from("second"))]
+    );
+    assert!(server.sent_payloads().is_empty());
+
+    // Loss and reconnection
+    server.lose(Some(CloseCode::Kicked)).unwrap();
+    assert!(matches!(
+        client.pump()[..],
+        [QuinnetClientEvent::ConnectionLost(event)] if event.close_code == Some(CloseCode::Kicked)
+    ));
+    assert_eq!(client.connection().state(), ConnectionState::Disconnected);
+    client.connection_mut().reconnect().unwrap();
+    assert_eq!(client.connection().state(), ConnectionState::Connecting);
+
+    server
+        .fail(QuinnetConnectionError::ClientIdNotReceived)
+        .unwrap();
+    assert!(matches!(
+        client.pump()[..],
+        [QuinnetClientEvent::ConnectionFailed(_)]
+    ));
+    client.connection_mut().reconnect().unwrap();
+    server.connect(Some(43)).unwrap();
+    assert!(matches!(
+        client.pump()[..],
+        [QuinnetClientEvent::Connection(event)] if event.client_id == Some(43)
+    ));
+    client.connection_mut().send_payload("again").unwrap();
+    assert_eq!(server.sent_payloads(), vec![(0, Bytes::from("again"))]);
+
+    // Disconnection by the app
+    assert_eq!(server.close_code(), None);
+    client.connection_mut().disconnect().unwrap();
+    assert_eq!(server.close_code(), Some(CloseCode::Closed));
+}
+
+#[test]
+fn scripted_certificate_interactions() {
+    let mut world = World::new();
+    let mut client = QuinnetClient::from_world(&mut world);
+    let (_, mut server) = client
+        .open_scripted_connection(ChannelsConfiguration::default())
+        .unwrap();
+
+    let mut interaction = server
+        .request_cert_interaction(CertVerificationStatus::UnknownCertificate, cert_info())
+        .unwrap();
+    server.update_cert_trust(cert_info()).unwrap();
+    let events = client.pump();
+    assert_eq!(events.len(), 2);
+    let QuinnetClientEvent::CertInteraction(event) = &events[0] else {
+        panic!("Expected a certificate interaction");
+    };
+    assert_eq!(event.status, CertVerificationStatus::UnknownCertificate);
+    assert_eq!(event.info, cert_info());
+    assert!(matches!(
+        &events[1],
+        QuinnetClientEvent::CertTrustUpdate(event) if event.cert_info == cert_info()
+    ));
+
+    assert_eq!(interaction.action(), None);
+    event
+        .apply_cert_verifier_action(CertVerifierAction::TrustAndStore)
+        .unwrap();
+    assert_eq!(
+        interaction.action(),
+        Some(CertVerifierAction::TrustAndStore)
+    );
+
+    server
+        .abort_on_cert(CertVerificationStatus::UntrustedCertificate, cert_info())
+        .unwrap();
+    assert!(matches!(
+        client.pump()[..],
+        [QuinnetClientEvent::CertConnectionAbort(ref event)] if event.status == CertVerificationStatus::UntrustedCertificate
+    ));
+}
+
+#[test]
+fn scripted_endpoint_clients() {
+    let mut world = World::new();
+    let mut server = start_server(&mut world, ChannelsConfiguration::default());
+
+    let mut client: ScriptedClient = server.endpoint().add_scripted_client();
+    assert_eq!(client.client_id(), None);
+    assert!(client.lose(DisconnectReason::TimedOut).is_err());
+    let events = server.pump();
+    assert!(matches!(events[..], [QuinnetServerEvent::Connection(_)]));
+    let client_id = client.client_id().unwrap();
+    assert_eq!(server.endpoint().clients(), vec![client_id]);
+
+    // Messages
+    client.send_payload(0, "ping").unwrap();
+    assert!(server.pump().is_empty());
+    let endpoint = server.endpoint_mut();
+    assert_eq!(
+        endpoint.receive_payload_from(client_id).unwrap(),
+        Some((0, Bytes::from("ping")))
+    );
+    endpoint.send_payload(client_id, "pong").unwrap();
+    assert_eq!(client.sent_payloads(), vec![(0, Bytes::from("pong"))]);
+
+    // Protocol violations
+    client.violate(ProtocolViolation::EmptyFrame).unwrap();
+    assert!(matches!(
+        &server.pump()[..],
+        [QuinnetServerEvent::ProtocolViolation(event)] if event.id == client_id && event.violation == ProtocolViolation::EmptyFrame
+    ));
+
+    // Loss
+    client.lose(DisconnectReason::TimedOut).unwrap();
+    assert!(matches!(
+        &server.pump()[..],
+        [QuinnetServerEvent::ConnectionLost(event)] if event.id == client_id && event.reason == DisconnectReason::TimedOut
+    ));
+    assert!(server.endpoint().clients().is_empty());
+
+    // Disconnection by the app
+    let mut kicked = server.endpoint().add_scripted_client();
+    server.pump();
+    let kicked_id = kicked.client_id().unwrap();
+    assert_ne!(kicked_id, client_id);
+    server.endpoint_mut().disconnect_client(kicked_id).unwrap();
+    assert_eq!(kicked.close_code(), Some(CloseCode::Kicked));
+    assert!(matches!(
+        &server.pump()[..],
+        [QuinnetServerEvent::ConnectionLost(event)] if event.id == kicked_id && event.reason == DisconnectReason::DisconnectedByServer
+    ));
+
+    // Refused while not accepting
+    server.endpoint_mut().set_accepting(false);
+    let mut refused = server.endpoint().add_scripted_client();
+    assert!(server.pump().is_empty());
+    assert_eq!(refused.client_id(), None);
+    assert_eq!(refused.close_code(), Some(CloseCode::NotAccepting));
+}
+
+fn relayed_payloads(seed: u64) -> Vec<(ChannelId, Bytes)> {
+    let channels_config = ChannelsConfiguration::from_configs(vec![
+        ChannelConfig::reliable_ordered(),
+        ChannelConfig::reliable_unordered(),
+        ChannelConfig::unreliable(),
+    ])
+    .unwrap();
+    let mut world = World::new();
+    let mut server = start_server(&mut world, channels_config.clone());
+    let mut client = QuinnetClient::from_world(&mut world);
+
+    let mut client_script = server.endpoint().add_scripted_client();
+    server.pump();
+    let client_id = client_script.client_id().unwrap();
+    let (_, mut server_script): (_, ScriptedServer) =
+        client.open_scripted_connection(channels_config).unwrap();
+    server_script.connect(Some(client_id)).unwrap();
+    client.pump();
+
+    let mut conditions = LinkConditions::new(seed).with_loss(0.5).with_reordering();
+    for i in 0..30u8 {
+        client
+            .connection_mut()
+            .send_payload_on(i % 3, vec![i])
+            .unwrap();
+    }
+    relay(&mut server_script, &mut client_script, &mut conditions).unwrap();
+    server.pump();
+
+    let endpoint = server.endpoint_mut();
+    let mut received = Vec::new();
+    while let Some(payload) = endpoint.receive_payload_from(client_id).unwrap() {
+        received.push(payload);
+    }
+
+    // Back to the client
+    for (channel_id, payload) in &received {
+        endpoint
+            .send_payload_on(client_id, *channel_id, payload.clone())
+            .unwrap();
+    }
+    relay(
+        &mut server_script,
+        &mut client_script,
+        &mut LinkConditions::new(0),
+    )
+    .unwrap();
+    client.pump();
+    let connection = client.connection_mut();
+    let mut echoed = Vec::new();
+    while let Some(payload) = connection.receive_payload().unwrap() {
+        echoed.push(payload);
+    }
+    // Relayed by channel id, without reordering
+    let mut expected = received.clone();
+    expected.sort_by_key(|(channel_id, _)| *channel_id);
+    assert_eq!(echoed, expected);
+    received
+}
+
+#[test]
+fn relay_with_seeded_link_conditions() {
+    let received = relayed_payloads(7);
+    assert_eq!(relayed_payloads(7), received);
+    assert_ne!(relayed_payloads(8), received);
+
+    // Reliable payloads are all delivered, in order on the ordered channel
+    let on_channel = |channel_id: ChannelId| {
+        received
+            .iter()
+            .filter(|(id, _)| *id == channel_id)
+            .map(|(_, payload)| payload[0])
+            .collect::<Vec<u8>>()
+    };
+    assert_eq!(on_channel(0), (0..30).step_by(3).collect::<Vec<u8>>());
+    let mut unordered = on_channel(1);
+    assert_ne!(unordered, (1..30).step_by(3).collect::<Vec<u8>>());
+    unordered.sort();
+    assert_eq!(unordered, (1..30).step_by(3).collect::<Vec<u8>>());
+    let unreliable = on_channel(2);
+    assert!(unreliable.len() < 10);
+    assert!(unreliable.iter().all(|i| i % 3 == 2));
+}
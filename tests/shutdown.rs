@@ -0,0 +1,68 @@
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use bevy::app::AppExit;
+use bevy_quinnet::{
+    client::{connection::ConnectionState, QuinnetClient},
+    server::QuinnetServer,
+};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn app_exit_closes_connections() {
+    let port = 6090; // TODO Use port 0 and retrieve the port used by the server.
+
+    // The server is notified right away when the client app exits
+    let (mut server_app, mut client_app, client_id) = start_connected_apps(port);
+    client_app.world_mut().send_event(AppExit::Success);
+    client_app.update();
+    assert_eq!(
+        client_app
+            .world()
+            .resource::<QuinnetClient>()
+            .connections()
+            .count(),
+        0
+    );
+    let start = Instant::now();
+    assert_eq!(
+        wait_for_all_clients_disconnected(&mut server_app),
+        client_id
+    );
+    assert!(start.elapsed() < Duration::from_secs(1));
+    drop(client_app);
+
+    // The clients are notified right away when the server app exits
+    let mut client_app = start_simple_client_app(port);
+    wait_for_client_connected(&mut client_app, &mut server_app);
+    server_app.world_mut().send_event(AppExit::Success);
+    server_app.update();
+    assert!(!server_app
+        .world()
+        .resource::<QuinnetServer>()
+        .is_listening());
+    let start = Instant::now();
+    while client_app
+        .world()
+        .resource::<QuinnetClient>()
+        .connection()
+        .state()
+        != ConnectionState::Disconnected
+    {
+        assert!(start.elapsed() < Duration::from_secs(1));
+        sleep(Duration::from_millis(5));
+        client_app.update();
+    }
+}
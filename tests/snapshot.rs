@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use bevy_quinnet::client::snapshot::SnapshotBuffer;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn snapshot_interpolation() {
+    let mut buffer = SnapshotBuffer::<f32>::new(Duration::from_millis(125))
+        .with_capacity(3)
+        .with_interpolation_delay(1.);
+    assert_eq!(buffer.sample(0.), None);
+
+    // Out of order
+    assert!(buffer.insert(12, 20.));
+    assert!(buffer.insert(10, 0.));
+    assert!(!buffer.insert(10, 5.), "Duplicated ticks should be dropped");
+    assert_eq!(buffer.latest_tick(), Some(12));
+
+    assert_eq!(
+        buffer.sample(9.),
+        Some(0.),
+        "Should hold the oldest snapshot"
+    );
+    assert_eq!(buffer.sample(11.), Some(10.));
+    assert_eq!(buffer.sample(11.5), Some(15.));
+    assert_eq!(buffer.sample(13.), Some(20.), "Should not extrapolate");
+
+    // 1 tick behind the server, at its tick 13
+    assert_eq!(buffer.render_tick(Duration::from_millis(1625)), 12.);
+    assert_eq!(buffer.sample_at(Duration::from_millis(1500)), Some(10.));
+
+    assert!(buffer.insert(14, 40.));
+    assert_eq!(buffer.len(), 3);
+    assert!(
+        !buffer.insert(9, 0.),
+        "A full buffer should drop the snapshots older than its snapshots"
+    );
+    assert!(buffer.insert(13, 30.));
+    assert_eq!(
+        buffer.sample(10.),
+        Some(20.),
+        "Tick 10 should have been evicted"
+    );
+    assert_eq!(buffer.sample(13.5), Some(35.));
+}
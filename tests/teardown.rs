@@ -0,0 +1,78 @@
+use std::{
+    panic::PanicHookInfo,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::sleep,
+    time::Duration,
+};
+
+use bevy_quinnet::{client::QuinnetClient, server::QuinnetServer, shared::AsyncRuntime};
+
+// https://github.com/rust-lang/rust/issues/46379
+pub use utils::*;
+
+mod utils;
+
+type PanicHook = dyn Fn(&PanicHookInfo) + Send + Sync;
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn teardown_with_live_connections() {
+    let port = 6089; // TODO Use port 0 and retrieve the port used by the server.
+
+    // Panics of the async tasks don't fail the test by themselves, count them all. The hook is global to the process, hence this test alone in its binary.
+    let panics = Arc::new(AtomicUsize::new(0));
+    let counter = panics.clone();
+    let previous_hook: Arc<PanicHook> = Arc::from(std::panic::take_hook());
+    let hook = previous_hook.clone();
+    std::panic::set_hook(Box::new(move |info| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        hook(info);
+    }));
+
+    // Apps dropped as is
+    let (server_app, client_app, _) = start_connected_apps(port);
+    drop(server_app);
+    drop(client_app);
+    sleep(Duration::from_millis(100));
+
+    // Runtimes dropped before the client & server
+    let (mut server_app, mut client_app, _) = start_connected_apps(port);
+    drop(server_app.world_mut().remove_resource::<AsyncRuntime>());
+    drop(client_app.world_mut().remove_resource::<AsyncRuntime>());
+    server_app.update();
+    client_app.update();
+    drop(server_app);
+    drop(client_app);
+    sleep(Duration::from_millis(100));
+
+    // Client & server dropped before their runtimes, while their tasks still run
+    let (mut server_app, mut client_app, _) = start_connected_apps(port);
+    drop(server_app.world_mut().remove_resource::<QuinnetServer>());
+    sleep(Duration::from_millis(50));
+    drop(client_app.world_mut().remove_resource::<QuinnetClient>());
+    sleep(Duration::from_millis(100));
+    drop(server_app);
+    drop(client_app);
+
+    // Apps dropped from an async context
+    let (server_app, client_app, _) = start_connected_apps(port);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async move {
+        drop(server_app);
+        drop(client_app);
+    });
+    sleep(Duration::from_millis(100));
+
+    std::panic::set_hook(Box::new(move |info| previous_hook(info)));
+    assert_eq!(panics.load(Ordering::SeqCst), 0);
+}
@@ -12,7 +12,7 @@ use bevy_quinnet::{
             CertVerificationInfo, CertVerificationStatus, CertVerifierAction,
             CertificateVerificationMode,
         },
-        connection::ClientEndpointConfiguration,
+        connection::{ClientEndpointConfiguration, ClientEndpointConfigurationBuilder},
         QuinnetClient, QuinnetClientPlugin,
     },
     server::{
@@ -20,7 +20,7 @@ use bevy_quinnet::{
         ServerEndpointConfiguration,
     },
     shared::{
-        channels::{ChannelId, ChannelKind, ChannelsConfiguration},
+        channels::{ChannelConfig, ChannelId, ChannelsConfiguration},
         ClientId,
     },
 };
@@ -87,8 +87,14 @@ pub fn build_server_app() -> App {
     server_app
 }
 
+pub fn default_client_configuration_builder(port: u16) -> ClientEndpointConfigurationBuilder {
+    ClientEndpointConfiguration::builder()
+        .with_server_ip(SERVER_IP, port)
+        .with_local_bind_ip(LOCAL_BIND_IP, 0)
+}
+
 pub fn default_client_configuration(port: u16) -> ClientEndpointConfiguration {
-    ClientEndpointConfiguration::from_ips(SERVER_IP, port, LOCAL_BIND_IP, 0)
+    default_client_configuration_builder(port).build().unwrap()
 }
 
 pub fn start_simple_connection(mut client: ResMut<QuinnetClient>, port: Res<Port>) {
@@ -222,6 +228,28 @@ pub fn wait_for_all_clients_disconnected(server_app: &mut App) -> ClientId {
         .expect("A client should have connected")
 }
 
+/// Connects a client app to a server app, with messages in flight on both sides
+pub fn start_connected_apps(port: u16) -> (App, App, ClientId) {
+    let mut server_app = start_simple_server_app(port);
+    let mut client_app = start_simple_client_app(port);
+    let client_id = wait_for_client_connected(&mut client_app, &mut server_app);
+    for _ in 0..20 {
+        client_app
+            .world_mut()
+            .resource_mut::<QuinnetClient>()
+            .connection_mut()
+            .send_message(SharedMessage::TestMessage("up".to_string()))
+            .unwrap();
+        server_app
+            .world_mut()
+            .resource_mut::<QuinnetServer>()
+            .endpoint_mut()
+            .send_message(client_id, SharedMessage::TestMessage("down".to_string()))
+            .unwrap();
+    }
+    (server_app, client_app, client_id)
+}
+
 pub fn get_default_client_channel(app: &App) -> ChannelId {
     let client = app.world().resource::<QuinnetClient>();
     client
@@ -254,19 +282,19 @@ pub fn close_server_channel(channel_id: ChannelId, app: &mut App) {
         .expect("Failed to close channel")
 }
 
-pub fn open_client_channel(channel_type: ChannelKind, app: &mut App) -> ChannelId {
+pub fn open_client_channel<T: Into<ChannelConfig>>(channel_config: T, app: &mut App) -> ChannelId {
     let mut client = app.world_mut().resource_mut::<QuinnetClient>();
     client
         .connection_mut()
-        .open_channel(channel_type)
+        .open_channel(channel_config)
         .expect("Failed to open channel")
 }
 
-pub fn open_server_channel(channel_type: ChannelKind, app: &mut App) -> ChannelId {
+pub fn open_server_channel<T: Into<ChannelConfig>>(channel_config: T, app: &mut App) -> ChannelId {
     let mut server = app.world_mut().resource_mut::<QuinnetServer>();
     server
         .endpoint_mut()
-        .open_channel(channel_type)
+        .open_channel(channel_config)
         .expect("Failed to open channel")
 }
 
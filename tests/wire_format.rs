@@ -0,0 +1,57 @@
+use bevy_quinnet::shared::wire::{
+    decode_client_id, decode_datagram, decode_reliable_frame, encode_datagram,
+    encode_reliable_frame, format_wire_test_vectors, wire_test_vectors,
+};
+
+const PUBLISHED_VECTORS_PATH: &str = "docs/wire_format_vectors.tsv";
+
+///////////////////////////////////////////////////////////
+///                                                     ///
+///                        Test                         ///
+///                                                     ///
+///////////////////////////////////////////////////////////
+
+#[test]
+fn wire_format_conformance() {
+    let vectors = wire_test_vectors();
+    let formatted = format_wire_test_vectors(&vectors);
+    // Regenerates the published vectors after a deliberate change of the wire format, along with a bump of WIRE_FORMAT_VERSION
+    if std::env::var_os("QUINNET_BLESS_WIRE_VECTORS").is_some() {
+        std::fs::write(PUBLISHED_VECTORS_PATH, &formatted).unwrap();
+    }
+    assert_eq!(
+        formatted,
+        std::fs::read_to_string(PUBLISHED_VECTORS_PATH).unwrap(),
+        "the wire format changed, see docs/WireFormat.md"
+    );
+
+    for vector in vectors {
+        if vector.name.starts_with("reliable_frame") {
+            let (channel_id, payload, rest) = decode_reliable_frame(&vector.encoded).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(encode_reliable_frame(channel_id, payload), vector.encoded);
+        } else if vector.name.starts_with("datagram") {
+            let (channel_id, payload) = decode_datagram(&vector.encoded).unwrap();
+            assert_eq!(encode_datagram(channel_id, payload), vector.encoded);
+        } else if vector.name == "client_id" {
+            assert_eq!(decode_client_id(&vector.encoded), Some(7));
+        }
+    }
+
+    // Incomplete frames
+    let frame = encode_reliable_frame(0, b"hello");
+    assert_eq!(decode_reliable_frame(&frame[..frame.len() - 1]), None);
+    assert_eq!(decode_reliable_frame(&[0, 0, 0, 0]), None);
+    assert_eq!(decode_datagram(&[3]), None);
+    assert_eq!(decode_client_id(&[0, 0, 0, 8, 0]), None);
+
+    // Frames read back to back from a stream
+    let mut stream = frame.to_vec();
+    stream.extend_from_slice(&encode_reliable_frame(1, b"world"));
+    let (_, first, rest) = decode_reliable_frame(&stream).unwrap();
+    assert_eq!(first, b"hello");
+    assert_eq!(
+        decode_reliable_frame(rest),
+        Some((1, &b"world"[..], &[][..]))
+    );
+}